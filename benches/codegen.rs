@@ -0,0 +1,166 @@
+//! Benchmarks for the back half of the compiler on large synthetic programs:
+//! instruction selection from TACKY, and formatting the assembly as text, plus
+//! the whole pipeline on many small functions, and the symbol table every
+//! stage names things in.
+//! `cargo bench --bench codegen` runs them; criterion compares each run with
+//! the one before.
+use std::io;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use crumb::{
+    emit_asm_to, gen_asm, gen_tacky, lex, parse, AsmFormatter, CodegenOptions, Context,
+    ProgramTacky, Symbol,
+};
+
+const OPS: [&str; 8] = ["+", "-", "*", "/", "%", "&", "|", "^"];
+
+/// a fully parenthesized expression tree `depth` levels deep, its operators and
+/// nonzero constants picked in turn, so every run compiles the same program.
+fn expression(depth: u32, next: &mut usize) -> String {
+    *next += 1;
+    if depth == 0 {
+        return (*next % 9 + 1).to_string();
+    }
+    let op = OPS[*next % OPS.len()];
+    let l = expression(depth - 1, next);
+    let r = expression(depth - 1, next);
+    match *next % 5 {
+        0 => format!("-({} {} {})", l, op, r),
+        _ => format!("({} {} {})", l, op, r),
+    }
+}
+
+/// `functions` functions, each returning a tree of 2^depth constants, which
+/// lowers to about 2^depth TACKY instructions apiece.
+fn source(functions: usize, depth: u32) -> String {
+    let mut next = 0;
+    (0..functions)
+        .map(|i| {
+            format!(
+                "int f{}(void) {{ return {}; }}\n",
+                i,
+                expression(depth, &mut next)
+            )
+        })
+        .collect()
+}
+
+fn tacky(source: &str) -> ProgramTacky {
+    gen_tacky(parse(lex(source).unwrap()).unwrap())
+}
+
+fn instructions(prog: &ProgramTacky) -> u64 {
+    prog.functions().map(|f| f.instructions.len() as u64).sum()
+}
+
+fn codegen(c: &mut Criterion) {
+    let source = source(8, 12);
+    let mut group = c.benchmark_group("codegen");
+    group.throughput(Throughput::Elements(instructions(&tacky(&source))));
+    for (name, options) in [
+        ("tacky_to_asm", CodegenOptions::default()),
+        (
+            "tacky_to_asm_with_comments",
+            CodegenOptions {
+                asm_comments: true,
+                ..Default::default()
+            },
+        ),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || tacky(&source),
+                |prog| gen_asm(prog, options).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    let asm = gen_asm(tacky(&source), CodegenOptions::default()).unwrap();
+    group.bench_function("format_asm", |b| {
+        b.iter(|| AsmFormatter::default().program(&asm))
+    });
+    group.bench_function("stream_asm", |b| {
+        b.iter(|| emit_asm_to(&asm, &mut io::sink(), AsmFormatter::default()).unwrap())
+    });
+    group.finish();
+}
+
+/// many small functions, translated on 1 to 8 threads. Without the `parallel`
+/// feature every case runs on one thread.
+fn jobs(c: &mut Criterion) {
+    let source = source(256, 7);
+    let mut group = c.benchmark_group("jobs");
+    group.throughput(Throughput::Elements(instructions(&tacky(&source))));
+    for jobs in [1, 2, 4, 8] {
+        let options = CodegenOptions {
+            jobs,
+            ..Default::default()
+        };
+        group.bench_function(jobs.to_string(), |b| {
+            b.iter_batched(
+                || tacky(&source),
+                |prog| gen_asm(prog, options).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// thousands of one-line functions through the whole pipeline, where the cost
+/// is in handling each function's name rather than its body.
+fn functions(c: &mut Criterion) {
+    let source = source(20_000, 0);
+    let mut group = c.benchmark_group("functions");
+    group.throughput(Throughput::Elements(20_000));
+    group.bench_function("source_to_asm", |b| {
+        b.iter(|| {
+            let asm = gen_asm(tacky(&source), CodegenOptions::default()).unwrap();
+            emit_asm_to(&asm, &mut io::sink(), AsmFormatter::default()).unwrap()
+        })
+    });
+    group.finish();
+}
+
+/// interning names the table hasn't seen, as resolution and TACKY generation
+/// do for each variable and temporary, names it has, as the lexer does for
+/// each identifier, and looking names up to print them.
+fn symbols(c: &mut Criterion) {
+    const NAMES: u32 = 10_000;
+    let mut group = c.benchmark_group("symbols");
+    group.throughput(Throughput::Elements(NAMES as u64));
+    // each batch interns into an empty context, as a compilation starts with
+    group.bench_function("intern_new", |b| {
+        b.iter_batched(
+            || {
+                let names: Vec<String> = (0..NAMES).map(|no| format!("new.{}", no)).collect();
+                (Context::new(), names)
+            },
+            |(context, names)| {
+                let entered = context.enter();
+                let symbols: Vec<Symbol> = names.iter().map(|name| Symbol::intern(name)).collect();
+                drop(entered);
+                (context, symbols)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    let names: Vec<String> = (0..NAMES).map(|no| format!("old.{}", no)).collect();
+    let symbols: Vec<Symbol> = names.iter().map(|name| Symbol::intern(name)).collect();
+    group.bench_function("intern_existing", |b| {
+        b.iter(|| {
+            names
+                .iter()
+                .map(|name| Symbol::intern(name))
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("as_str", |b| {
+        b.iter(|| symbols.iter().map(|s| s.as_str().len()).sum::<usize>())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, codegen, jobs, functions, symbols);
+criterion_main!(benches);
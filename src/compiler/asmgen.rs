@@ -1,431 +1,5613 @@
-use std::{collections::HashMap, fmt::Display, fs};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+};
+use thiserror::Error;
 
 use super::{
-    parser::{BinaryOp, UnaryOp},
+    emit::AsmFormatter,
+    parser::{BinaryOp, CType, Const, UnaryOp},
+    symbol::Symbol,
     tacky::*,
+    target::{RelocationModel, Target},
+    validate::{validate_asm, AsmValidationError},
 };
 
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CodegenError {
+    NoSingleInstruction { op: BinaryOp },
+    Invalid { e: AsmValidationError },
+    FramePointerRequired { target: Target },
+}
+
+impl Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoSingleInstruction { op } => write!(
+                f,
+                "(!) Error generating assembly: no single instruction computes {}",
+                op
+            ),
+            Self::Invalid { e } => write!(f, "{}", e),
+            Self::FramePointerRequired { target } => write!(
+                f,
+                "(!) Error generating assembly: unwind information for {} needs the frame pointer, which can't be omitted",
+                target.name()
+            ),
+        }
+    }
+}
+
 /// x86-64 program
-/// ### Grammar as of v0.1.0
+/// ### Grammar as of v0.1.18
 /// ```text
-/// program = Program(function_definition)
+/// program = Program(top_level*)
+/// top_level = Function(function_definition)
+///           | StaticVariable(identifier name, bool global, static_init init)
+///           | StaticConstant(identifier name, static_init init)
+///           | File(int no, identifier name)
 /// ```
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProgramAsm {
-    pub function: Box<FunDefAsm>,
+    pub items: Vec<TopLevelAsm>,
+}
+
+/// x86-64 top-level item
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TopLevelAsm {
+    Function {
+        fundef: FunDefAsm,
+    },
+    StaticVariable {
+        name: Symbol,
+        global: bool,
+        init: StaticInit,
+    },
+    /// a read-only value local to the file: a `double` constant, which no
+    /// instruction can take as an immediate, or a string literal's bytes
+    StaticConstant {
+        name: Symbol,
+        init: StaticInit,
+    },
+    /// a source file `Loc` instructions name by its number
+    File {
+        no: u32,
+        name: Symbol,
+    },
+}
+
+impl ProgramAsm {
+    /// the function definitions, in the order they appear in the source.
+    pub fn functions(&self) -> impl Iterator<Item = &FunDefAsm> {
+        self.items.iter().filter_map(|item| match item {
+            TopLevelAsm::Function { fundef } => Some(fundef),
+            TopLevelAsm::StaticVariable { .. }
+            | TopLevelAsm::StaticConstant { .. }
+            | TopLevelAsm::File { .. } => None,
+        })
+    }
 }
 
 impl Display for ProgramAsm {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}\n\t.section .note.GNU-stack,\"\",@progbits\n",
-            *self.function
-        )
+        AsmFormatter::default().write_program(f, self)
     }
 }
 
 /// x86-64 function definition
-/// ### Grammar as of v0.1.0
+/// ### Grammar as of v0.1.11
 /// ```text
-/// function_definition = Function(identifier, instruction* body)
+/// function_definition = Function(identifier, bool global, instruction* body)
 /// ```
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunDefAsm {
-    pub identifier: String,
+    pub identifier: Symbol,
+    pub global: bool,
     pub instructions: Vec<InstructionAsm>,
 }
 
 impl Display for FunDefAsm {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "\t.globl {}\n{}:\n\tpushq %rbp\n\tmovq %rsp, %rbp{}", // including prologue
-            self.identifier,
-            self.identifier,
-            {
-                let mut format_instrs = String::from("");
-                self.instructions
-                    .clone()
-                    .into_iter()
-                    .for_each(|i| format_instrs.push_str(&format!("\n\t{}", i)));
-                format_instrs
-            }
-        )
+        AsmFormatter::default().write_fundef(f, self)
     }
 }
 
 /// x86-64 instruction
-/// ### Grammar as of v0.1.2
+/// ### Grammar as of v0.1.16
 /// ```text
-/// instruction = Mov(operand src, operand dst)
-///             | Unary(unary_operator, operand)
-///             | Binary(binary_operator, operand, operand)
-///             | Idiv(operand)
-///             | Cdq
+/// instruction = Mov(assembly_type, operand src, operand dst)
+///             | MovQ(reg src, reg dst)
+///             | Cvttsd2si(assembly_type, operand src, operand dst)
+///             | Cvtsi2sd(assembly_type, operand src, operand dst)
+///             | Unary(assembly_type, unary_operator, operand)
+///             | Binary(assembly_type, binary_operator, operand, operand)
+///             | Idiv(assembly_type, operand)
+///             | Div(assembly_type, operand)
+///             | Cdq(assembly_type)
 ///             | AllocateStack(int)
+///             | DeallocateStack(int)
+///             | Push(reg)
+///             | Pop(reg)
+///             | Cmp(assembly_type, operand, operand)
+///             | Jmp(identifier)
+///             | JmpCC(cond_code, identifier)
+///             | SetCC(cond_code, operand)
+///             | Movzx(assembly_type src_type, assembly_type dst_type, operand src, operand dst)
+///             | Movsx(assembly_type src_type, assembly_type dst_type, operand src, operand dst)
+///             | Cmov(assembly_type, cond_code, operand src, operand dst)
+///             | Lea(operand src, operand dst)
+///             | Label(identifier)
+///             | Call(identifier)
+///             | TailCall(identifier)
+///             | Comment(string)
+///             | Cfi(cfi_directive)
+///             | Loc(int file, int line, int col)
 ///             | Ret
 /// ```
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InstructionAsm {
     Mov {
+        ty: AsmType,
         src: OperandAsm,
         dst: OperandAsm,
     },
     Ret,
     Unary {
-        unop: UnaryOp,
+        ty: AsmType,
+        unop: AsmUnaryOp,
         operand: OperandAsm,
     },
     AllocStack {
         off: i32,
     },
+    DeallocStack {
+        off: i32,
+    },
     Binary {
-        binop: BinaryOp,
+        ty: AsmType,
+        binop: AsmBinaryOp,
         src: OperandAsm,
         dst: OperandAsm,
     },
     Idiv {
+        ty: AsmType,
+        operand: OperandAsm,
+    },
+    /// divides %edx:%eax, or %rdx:%rax, by `operand` as unsigned numbers
+    Div {
+        ty: AsmType,
+        operand: OperandAsm,
+    },
+    Cdq {
+        ty: AsmType,
+    },
+    /// converts the `double` `src` to the integer `dst` of size `ty`, truncating
+    Cvttsd2si {
+        ty: AsmType,
+        src: OperandAsm,
+        dst: OperandAsm,
+    },
+    /// converts the integer `src` of size `ty` to the `double` `dst`
+    Cvtsi2sd {
+        ty: AsmType,
+        src: OperandAsm,
+        dst: OperandAsm,
+    },
+    Push {
+        r: Register,
+    },
+    Pop {
+        r: Register,
+    },
+    MovQ {
+        src: Register,
+        dst: Register,
+    },
+    Cmp {
+        ty: AsmType,
+        src: OperandAsm,
+        dst: OperandAsm,
+    },
+    Jmp {
+        target: String,
+    },
+    JmpCC {
+        cc: CondCode,
+        target: String,
+    },
+    /// writes 1 to the byte `operand` if the flags satisfy `cc`, and 0 otherwise
+    SetCC {
+        cc: CondCode,
         operand: OperandAsm,
     },
-    Cdq,
+    /// zero-extends `src`, of size `src_ty`, into the wider `dst`, of size `dst_ty`
+    Movzx {
+        src_ty: AsmType,
+        dst_ty: AsmType,
+        src: OperandAsm,
+        dst: OperandAsm,
+    },
+    /// sign-extends `src`, of size `src_ty`, into the wider `dst`, of size `dst_ty`
+    Movsx {
+        src_ty: AsmType,
+        dst_ty: AsmType,
+        src: OperandAsm,
+        dst: OperandAsm,
+    },
+    /// copies `src` into `dst` if the flags satisfy `cc`, and leaves `dst` alone otherwise
+    Cmov {
+        ty: AsmType,
+        cc: CondCode,
+        src: OperandAsm,
+        dst: OperandAsm,
+    },
+    /// writes the address of the memory operand `src` to `dst`
+    Lea {
+        src: OperandAsm,
+        dst: OperandAsm,
+    },
+    Label {
+        name: String,
+    },
+    /// calls the function `name`, which may be defined in another file
+    Call {
+        name: Symbol,
+    },
+    /// jumps to the function `name`, which returns to this function's caller
+    TailCall {
+        name: Symbol,
+    },
+    Comment {
+        text: String,
+    },
+    Cfi {
+        directive: CfiDirective,
+    },
+    /// the source line and column the instructions after it were written at
+    Loc {
+        file: u32,
+        line: usize,
+        col: usize,
+    },
+}
+
+impl InstructionAsm {
+    /// the operands of the instruction, other than the registers some name outright.
+    pub fn operands(&self) -> Vec<&OperandAsm> {
+        match self {
+            Self::Mov { src, dst, .. }
+            | Self::Binary { src, dst, .. }
+            | Self::Cmp { src, dst, .. }
+            | Self::Cvttsd2si { src, dst, .. }
+            | Self::Cvtsi2sd { src, dst, .. }
+            | Self::Movzx { src, dst, .. }
+            | Self::Movsx { src, dst, .. }
+            | Self::Cmov { src, dst, .. }
+            | Self::Lea { src, dst } => vec![src, dst],
+            Self::Unary { operand, .. }
+            | Self::Idiv { operand, .. }
+            | Self::Div { operand, .. }
+            | Self::SetCC { operand, .. } => {
+                vec![operand]
+            }
+            _ => vec![],
+        }
+    }
 }
 
 impl Display for InstructionAsm {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        AsmFormatter::default().write_instruction(f, self)
+    }
+}
+
+/// Call frame information for unwinders and debuggers, which the assembler
+/// turns into `.eh_frame` entries. The canonical frame address (CFA) is the
+/// value %rsp had before the call, and each directive records how to find it,
+/// or a saved register, from the instruction it follows onward.
+/// - `StartProc`, `EndProc`: bracket the function's frame description
+/// - `DefCfaOffset`: the CFA is now `off` bytes above the register it is measured from
+/// - `DefCfaRegister`: the CFA is now measured from `r`
+/// - `DefCfa`: the CFA is now `off` bytes above `r`
+/// - `Offset`: `r` was saved `off` bytes from the CFA
+/// - `RememberState`, `RestoreState`: save the rules before an epilogue, and bring
+///   them back for the code after its `ret`
+///
+/// Windows describes only the prologue instead, with `.seh_*` directives from
+/// which the assembler builds the `.pdata` and `.xdata` entries; the unwinder
+/// undoes its steps in reverse, and recognizes epilogues by their instructions.
+/// - `PushReg`: `r` was pushed
+/// - `StackAlloc`: %rsp moved down by `size` bytes
+/// - `SetFrame`: `r` now holds %rsp plus `off`, and stands in for it from here on
+/// - `EndPrologue`: ends the prologue
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CfiDirective {
+    StartProc,
+    EndProc,
+    DefCfaOffset { off: i32 },
+    DefCfaRegister { r: Register },
+    DefCfa { r: Register, off: i32 },
+    Offset { r: Register, off: i32 },
+    RememberState,
+    RestoreState,
+    PushReg { r: Register },
+    StackAlloc { size: i32 },
+    SetFrame { r: Register, off: i32 },
+    EndPrologue,
+}
+
+/// x86-64 operand sizes, which pick the instruction suffix and register names.
+/// - `Byte`: 1 byte, `b` suffix
+/// - `Word`: 2 bytes, `w` suffix
+/// - `Longword`: 4 bytes, `l` suffix
+/// - `Quadword`: 8 bytes, `q` suffix
+/// - `Double`: 8 bytes in an XMM register, whose SSE instructions are spelled in full
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AsmType {
+    Byte,
+    Word,
+    #[default]
+    Longword,
+    Quadword,
+    Double,
+}
+
+impl From<CType> for AsmType {
+    fn from(ty: CType) -> Self {
+        match ty {
+            CType::Bool | CType::Char | CType::SChar | CType::UChar => Self::Byte,
+            CType::Short | CType::UShort => Self::Word,
+            CType::Int | CType::UInt => Self::Longword,
+            // an array is only ever handled as the address it decays to, and
+            // a structure is copied a piece at a time
+            CType::Long
+            | CType::ULong
+            | CType::Pointer { .. }
+            | CType::Array { .. }
+            | CType::Struct { .. } => Self::Quadword,
+            CType::Double => Self::Double,
+        }
+    }
+}
+
+impl AsmType {
+    /// size in bytes.
+    pub fn size(&self) -> i32 {
         match self {
-            Self::Mov { src, dst } => write!(f, "movl {}, {}", src, dst),
-            Self::Ret => write!(f, "movq %rbp, %rsp\n\tpopq %rbp\n\tret"), // including epilogue
-            Self::Unary { unop, operand } => match unop {
-                UnaryOp::Negate => write!(f, "negl {}", operand),
-                UnaryOp::BitwiseComplement => write!(f, "notl {}", operand),
-            },
-            Self::AllocStack { off } => write!(f, "subq ${}, %rsp", -1 * off),
-            Self::Cdq => write!(f, "cdq"),
-            Self::Binary { binop, src, dst } => match binop {
-                BinaryOp::Add => write!(f, "addl {}, {}", src, dst),
-                BinaryOp::Subtract => write!(f, "subl {}, {}", src, dst),
-                BinaryOp::Multiply => write!(f, "imull {}, {}", src, dst),
-                BinaryOp::BitwiseAnd => write!(f, "andl {}, {}", src, dst),
-                BinaryOp::BitwiseOr => write!(f, "orl {}, {}", src, dst),
-                BinaryOp::BitwiseXor => write!(f, "xorl {}, {}", src, dst),
-                _ => panic!(
-                    "unsupported BinaryOp variant stored in InstructionAsm::Binary {:?}",
-                    self
-                ),
-            },
-            Self::Idiv { operand } => write!(f, "idivl {}", operand),
+            Self::Byte => 1,
+            Self::Word => 2,
+            Self::Longword => 4,
+            Self::Quadword | Self::Double => 8,
+        }
+    }
+
+    /// AT&T mnemonic suffix.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Self::Byte => "b",
+            Self::Word => "w",
+            Self::Longword => "l",
+            Self::Quadword => "q",
+            Self::Double => "",
+        }
+    }
+}
+
+/// x86-64 unary operations.
+/// ### Grammar as of v0.1.3
+/// ```text
+/// unary_operator = Neg | Not
+/// ```
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AsmUnaryOp {
+    Neg,
+    Not,
+}
+
+impl From<&UnaryOp> for AsmUnaryOp {
+    fn from(op: &UnaryOp) -> Self {
+        match op {
+            UnaryOp::Negate => Self::Neg,
+            UnaryOp::BitwiseComplement => Self::Not,
+        }
+    }
+}
+
+impl Display for AsmUnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Neg => write!(f, "neg"),
+            Self::Not => write!(f, "not"),
+        }
+    }
+}
+
+/// x86-64 two-operand operations, each a single instruction.
+/// The shifts take their count as the source operand, an immediate or `%cl`.
+/// - `Shl`: shift left
+/// - `Sar`: arithmetic (sign-filling) shift right
+/// - `Shr`: logical (zero-filling) shift right
+/// - `DivDouble`: floating-point division, which has no integer counterpart
+///
+/// On `double` operands `Add`, `Sub`, `Imul` and `Xor` are their SSE versions.
+/// ### Grammar as of v0.1.14
+/// ```text
+/// binary_operator = Add | Sub | Imul | DivDouble | And | Or | Xor | Shl | Sar | Shr
+/// ```
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AsmBinaryOp {
+    Add,
+    Sub,
+    Imul,
+    DivDouble,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Sar,
+    Shr,
+}
+
+impl TryFrom<&BinaryOp> for AsmBinaryOp {
+    type Error = CodegenError;
+
+    /// the instruction computing a C operator, if a single one does; `>>` is
+    /// `sar` or `shr` depending on whether its operand is signed.
+    fn try_from(op: &BinaryOp) -> Result<Self, Self::Error> {
+        match op {
+            BinaryOp::Add => Ok(Self::Add),
+            BinaryOp::Subtract => Ok(Self::Sub),
+            BinaryOp::Multiply => Ok(Self::Imul),
+            BinaryOp::BitwiseAnd => Ok(Self::And),
+            BinaryOp::BitwiseOr => Ok(Self::Or),
+            BinaryOp::BitwiseXor => Ok(Self::Xor),
+            BinaryOp::LeftShift => Ok(Self::Shl),
+            BinaryOp::Divide
+            | BinaryOp::Remainder
+            | BinaryOp::RightShift
+            | BinaryOp::LessThan
+            | BinaryOp::GreaterThan
+            | BinaryOp::LessOrEqual
+            | BinaryOp::GreaterOrEqual
+            | BinaryOp::Equal
+            | BinaryOp::NotEqual
+            | BinaryOp::And
+            | BinaryOp::Or => Err(CodegenError::NoSingleInstruction { op: op.clone() }),
+        }
+    }
+}
+
+impl Display for AsmBinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add => write!(f, "add"),
+            Self::Sub => write!(f, "sub"),
+            Self::Imul => write!(f, "imul"),
+            Self::DivDouble => write!(f, "div"),
+            Self::And => write!(f, "and"),
+            Self::Or => write!(f, "or"),
+            Self::Xor => write!(f, "xor"),
+            Self::Shl => write!(f, "shl"),
+            Self::Sar => write!(f, "sar"),
+            Self::Shr => write!(f, "shr"),
+        }
+    }
+}
+
+/// x86-64 condition codes, as used by conditional jumps, `setcc` and `cmov`.
+/// The orderings are read after `cmp src, dst` as `dst` against `src`: greater
+/// and less for signed numbers, above and below for unsigned ones. Parity is
+/// set after `comisd` when the comparison was unordered, with a NaN.
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CondCode {
+    E,
+    NE,
+    G,
+    GE,
+    L,
+    LE,
+    A,
+    AE,
+    B,
+    BE,
+    P,
+    NP,
+}
+
+impl CondCode {
+    /// the condition a comparison operator tests on operands of a signed or
+    /// unsigned type, if it is one.
+    fn of(op: &BinaryOp, signed: bool) -> Option<Self> {
+        match (op, signed) {
+            (BinaryOp::Equal, _) => Some(Self::E),
+            (BinaryOp::NotEqual, _) => Some(Self::NE),
+            (BinaryOp::GreaterThan, true) => Some(Self::G),
+            (BinaryOp::GreaterOrEqual, true) => Some(Self::GE),
+            (BinaryOp::LessThan, true) => Some(Self::L),
+            (BinaryOp::LessOrEqual, true) => Some(Self::LE),
+            (BinaryOp::GreaterThan, false) => Some(Self::A),
+            (BinaryOp::GreaterOrEqual, false) => Some(Self::AE),
+            (BinaryOp::LessThan, false) => Some(Self::B),
+            (BinaryOp::LessOrEqual, false) => Some(Self::BE),
+            _ => None,
+        }
+    }
+}
+
+impl Display for CondCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::E => write!(f, "e"),
+            Self::NE => write!(f, "ne"),
+            Self::G => write!(f, "g"),
+            Self::GE => write!(f, "ge"),
+            Self::L => write!(f, "l"),
+            Self::LE => write!(f, "le"),
+            Self::A => write!(f, "a"),
+            Self::AE => write!(f, "ae"),
+            Self::B => write!(f, "b"),
+            Self::BE => write!(f, "be"),
+            Self::P => write!(f, "p"),
+            Self::NP => write!(f, "np"),
         }
     }
 }
 
 /// x86-64 operand
-/// ### Grammar as of v0.1.1
+/// ### Grammar as of v0.1.17
 /// ```text
-/// operand = Imm(int) | Reg(reg) | Pseudo(identifier) | Stack(int)
+/// operand = Imm(int) | Reg(reg) | Pseudo(identifier) | Stack(int) | Memory(reg, int)
+///         | Indexed(reg base, reg index, int scale) | Data(identifier) | Got(identifier)
 /// ```
+/// `Stack` is relative to the frame pointer; `Memory` names its base register,
+/// which is how a value is read or written through a pointer. `Indexed` is
+/// `base` plus `index` times a `scale` of 1, 2, 4 or 8, an element of an array.
+/// `Data` is a variable with static storage, addressed relative to %rip.
+/// `Got` is the entry of the global offset table holding a variable's address,
+/// also addressed relative to %rip.
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OperandAsm {
-    Imm { int: i32 },
-    Reg { r: Register },
-    Pseudo { id: u16 },
-    Stack { off: i32 },
+    Imm {
+        int: i64,
+    },
+    Reg {
+        r: Register,
+    },
+    Pseudo {
+        name: Symbol,
+    },
+    Stack {
+        off: i32,
+    },
+    Memory {
+        base: Register,
+        off: i32,
+    },
+    Indexed {
+        base: Register,
+        index: Register,
+        scale: i32,
+    },
+    Data {
+        name: Symbol,
+    },
+    Got {
+        name: Symbol,
+    },
+}
+
+impl OperandAsm {
+    /// whether the operand is in memory; an instruction takes at most one that is.
+    pub fn is_memory(&self) -> bool {
+        matches!(
+            self,
+            Self::Stack { .. }
+                | Self::Memory { .. }
+                | Self::Indexed { .. }
+                | Self::Data { .. }
+                | Self::Got { .. }
+        )
+    }
 }
 
 impl Display for OperandAsm {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Imm { int } => write!(f, "${}", int),
-            Self::Reg { r } => write!(f, "{}", r),
-            Self::Stack { off } => write!(f, "{}(%rbp)", off),
-            Self::Pseudo { id } => panic!("display format called on a pseudo operand id: {}", id),
-        }
+        AsmFormatter::default().write_operand(f, self, AsmType::Longword)
     }
 }
 
 /// x86-64 registers
-/// ### Used registers as of v0.1.2
+/// ### Used registers as of v0.1.14
 /// - AX
 /// - R10
 /// - DX
 /// - R11
+/// - DI, SI, CX, R8, R9 (arguments, with DX)
+/// - BX, R12-R15 (callee-saved; BX holds the address of a variable reached through the GOT)
+/// - BP, SP (frame management only)
+/// - XMM0-XMM7 (`double` arguments and return values, with XMM4 and XMM5 as scratch)
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Register {
     AX,
     R10,
     DX,
     R11,
+    DI,
+    SI,
+    CX,
+    R8,
+    R9,
+    BX,
+    R12,
+    R13,
+    R14,
+    R15,
+    BP,
+    SP,
+    XMM0,
+    XMM1,
+    XMM2,
+    XMM3,
+    XMM4,
+    XMM5,
+    XMM6,
+    XMM7,
 }
 
-impl Display for Register {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Register {
+    /// number of the register in DWARF unwind information.
+    pub fn dwarf_no(&self) -> u8 {
         match self {
-            Self::AX => write!(f, "%eax"),
-            Self::R10 => write!(f, "%r10d"),
-            Self::DX => write!(f, "%edx"),
-            Self::R11 => write!(f, "%r11d"),
+            Self::AX => 0,
+            Self::DX => 1,
+            Self::CX => 2,
+            Self::BX => 3,
+            Self::SI => 4,
+            Self::DI => 5,
+            Self::BP => 6,
+            Self::SP => 7,
+            Self::R8 => 8,
+            Self::R9 => 9,
+            Self::R10 => 10,
+            Self::R11 => 11,
+            Self::R12 => 12,
+            Self::R13 => 13,
+            Self::R14 => 14,
+            Self::R15 => 15,
+            Self::XMM0 => 17,
+            Self::XMM1 => 18,
+            Self::XMM2 => 19,
+            Self::XMM3 => 20,
+            Self::XMM4 => 21,
+            Self::XMM5 => 22,
+            Self::XMM6 => 23,
+            Self::XMM7 => 24,
+        }
+    }
+
+    /// whether the register is one of the SSE registers holding `double`s.
+    pub fn is_xmm(&self) -> bool {
+        matches!(
+            self,
+            Self::XMM0
+                | Self::XMM1
+                | Self::XMM2
+                | Self::XMM3
+                | Self::XMM4
+                | Self::XMM5
+                | Self::XMM6
+                | Self::XMM7
+        )
+    }
+
+    /// name of the register when it holds a value of the given size, without the `%` sigil.
+    /// An XMM register has the one name whatever it holds, and a general-purpose
+    /// register holding a `double` goes by its 64-bit name.
+    pub fn name(&self, ty: AsmType) -> &'static str {
+        match (self, ty) {
+            (Self::XMM0, _) => "xmm0",
+            (Self::XMM1, _) => "xmm1",
+            (Self::XMM2, _) => "xmm2",
+            (Self::XMM3, _) => "xmm3",
+            (Self::XMM4, _) => "xmm4",
+            (Self::XMM5, _) => "xmm5",
+            (Self::XMM6, _) => "xmm6",
+            (Self::XMM7, _) => "xmm7",
+            (Self::AX, AsmType::Byte) => "al",
+            (Self::AX, AsmType::Word) => "ax",
+            (Self::AX, AsmType::Longword) => "eax",
+            (Self::AX, AsmType::Quadword | AsmType::Double) => "rax",
+            (Self::R10, AsmType::Byte) => "r10b",
+            (Self::R10, AsmType::Word) => "r10w",
+            (Self::R10, AsmType::Longword) => "r10d",
+            (Self::R10, AsmType::Quadword | AsmType::Double) => "r10",
+            (Self::DX, AsmType::Byte) => "dl",
+            (Self::DX, AsmType::Word) => "dx",
+            (Self::DX, AsmType::Longword) => "edx",
+            (Self::DX, AsmType::Quadword | AsmType::Double) => "rdx",
+            (Self::R11, AsmType::Byte) => "r11b",
+            (Self::R11, AsmType::Word) => "r11w",
+            (Self::R11, AsmType::Longword) => "r11d",
+            (Self::R11, AsmType::Quadword | AsmType::Double) => "r11",
+            (Self::DI, AsmType::Byte) => "dil",
+            (Self::DI, AsmType::Word) => "di",
+            (Self::DI, AsmType::Longword) => "edi",
+            (Self::DI, AsmType::Quadword | AsmType::Double) => "rdi",
+            (Self::SI, AsmType::Byte) => "sil",
+            (Self::SI, AsmType::Word) => "si",
+            (Self::SI, AsmType::Longword) => "esi",
+            (Self::SI, AsmType::Quadword | AsmType::Double) => "rsi",
+            (Self::CX, AsmType::Byte) => "cl",
+            (Self::CX, AsmType::Word) => "cx",
+            (Self::CX, AsmType::Longword) => "ecx",
+            (Self::CX, AsmType::Quadword | AsmType::Double) => "rcx",
+            (Self::R8, AsmType::Byte) => "r8b",
+            (Self::R8, AsmType::Word) => "r8w",
+            (Self::R8, AsmType::Longword) => "r8d",
+            (Self::R8, AsmType::Quadword | AsmType::Double) => "r8",
+            (Self::R9, AsmType::Byte) => "r9b",
+            (Self::R9, AsmType::Word) => "r9w",
+            (Self::R9, AsmType::Longword) => "r9d",
+            (Self::R9, AsmType::Quadword | AsmType::Double) => "r9",
+            (Self::BX, AsmType::Byte) => "bl",
+            (Self::BX, AsmType::Word) => "bx",
+            (Self::BX, AsmType::Longword) => "ebx",
+            (Self::BX, AsmType::Quadword | AsmType::Double) => "rbx",
+            (Self::R12, AsmType::Byte) => "r12b",
+            (Self::R12, AsmType::Word) => "r12w",
+            (Self::R12, AsmType::Longword) => "r12d",
+            (Self::R12, AsmType::Quadword | AsmType::Double) => "r12",
+            (Self::R13, AsmType::Byte) => "r13b",
+            (Self::R13, AsmType::Word) => "r13w",
+            (Self::R13, AsmType::Longword) => "r13d",
+            (Self::R13, AsmType::Quadword | AsmType::Double) => "r13",
+            (Self::R14, AsmType::Byte) => "r14b",
+            (Self::R14, AsmType::Word) => "r14w",
+            (Self::R14, AsmType::Longword) => "r14d",
+            (Self::R14, AsmType::Quadword | AsmType::Double) => "r14",
+            (Self::R15, AsmType::Byte) => "r15b",
+            (Self::R15, AsmType::Word) => "r15w",
+            (Self::R15, AsmType::Longword) => "r15d",
+            (Self::R15, AsmType::Quadword | AsmType::Double) => "r15",
+            (Self::BP, AsmType::Byte) => "bpl",
+            (Self::BP, AsmType::Word) => "bp",
+            (Self::BP, AsmType::Longword) => "ebp",
+            (Self::BP, AsmType::Quadword | AsmType::Double) => "rbp",
+            (Self::SP, AsmType::Byte) => "spl",
+            (Self::SP, AsmType::Word) => "sp",
+            (Self::SP, AsmType::Longword) => "esp",
+            (Self::SP, AsmType::Quadword | AsmType::Double) => "rsp",
         }
     }
 }
 
-/// Converts ASM AST to syntax and writes to output file
-pub fn emit_asm(asmprog: ProgramAsm, output_file: String) -> std::io::Result<()> {
-    fs::write(output_file, format!("{}", asmprog))
+/// Choices affecting the generated instructions.
+/// - target: platform whose calling convention to follow
+/// - omit_frame_pointer: address the stack frame from %rsp and leave %rbp alone
+/// - asm_comments: precede the instructions for each TACKY instruction with a comment
+/// - red_zone: keep a small leaf function's frame below %rsp instead of allocating it
+/// - cmov: choose between two values already computed with a conditional move
+///   rather than a branch
+/// - jobs: threads translating functions at once, with the `parallel` feature;
+///   0 and 1 both translate them one at a time
+/// - relocation: kind of module the code is linked into, which decides whether
+///   variables visible outside the file are reached through the GOT
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct CodegenOptions {
+    pub target: Target,
+    pub omit_frame_pointer: bool,
+    pub asm_comments: bool,
+    pub red_zone: bool,
+    pub cmov: bool,
+    pub jobs: usize,
+    pub relocation: RelocationModel,
+}
+
+/// Translates each function to assembly. Functions share no state but the
+/// names of the variables with static storage, so with the `parallel` feature
+/// and `options.jobs` above 1 they are translated on that many threads; the
+/// output keeps the functions in program order, and an error is the first in
+/// program order, whatever the scheduling. The variables defined in this file
+/// follow the functions; those defined in another need nothing more, and the
+/// string constants follow them. Last come the `double` constants the
+/// functions read, each value once, in order of their bits.
+/// For a shared library, the variables visible outside the file are reached
+/// through the GOT.
+pub fn gen_asm(
+    tacky_prog: ProgramTacky,
+    options: CodegenOptions,
+) -> Result<ProgramAsm, CodegenError> {
+    if options.omit_frame_pointer && !options.target.has_cfi() {
+        return Err(CodegenError::FramePointerRequired {
+            target: options.target,
+        });
+    }
+    let statics = tacky_prog.statics();
+    let got: HashSet<Symbol> = match options.target.uses_got(options.relocation) {
+        true => tacky_prog
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                TopLevelTacky::StaticVariable {
+                    name, global: true, ..
+                } => Some(*name),
+                _ => None,
+            })
+            .collect(),
+        false => HashSet::new(),
+    };
+    let mut fundefs = Vec::new();
+    let mut variables = Vec::new();
+    let mut doubles = BTreeSet::new();
+    let mut files = Vec::new();
+    for item in tacky_prog.items.into_iter() {
+        match item {
+            TopLevelTacky::Function { fundef } => {
+                for instr in fundef.instructions.iter() {
+                    for src in instr.srcs() {
+                        if let ValTacky::Const {
+                            c: c @ Const::Double { .. },
+                        } = src
+                        {
+                            doubles.insert(c.value());
+                        }
+                    }
+                }
+                fundefs.push(fundef)
+            }
+            TopLevelTacky::StaticVariable {
+                name,
+                global,
+                init: Some(init),
+            } => variables.push(TopLevelAsm::StaticVariable { name, global, init }),
+            TopLevelTacky::StaticVariable { init: None, .. } => {}
+            TopLevelTacky::StaticConstant { name, init } => {
+                variables.push(TopLevelAsm::StaticConstant { name, init })
+            }
+            TopLevelTacky::File { no, name } => files.push(TopLevelAsm::File { no, name }),
+        }
+    }
+    let mut items = files;
+    items.extend(
+        translate_fundefs(fundefs, &statics, &got, options)?
+            .into_iter()
+            .map(|fundef| TopLevelAsm::Function { fundef }),
+    );
+    items.extend(variables);
+    items.extend(doubles.into_iter().map(|bits| {
+        let init = Const::Double {
+            double: f64::from_bits(bits as u64),
+        };
+        TopLevelAsm::StaticConstant {
+            name: double_constant(init),
+            init: StaticInit::Scalar { c: init },
+        }
+    }));
+    Ok(ProgramAsm { items })
 }
 
-pub fn gen_asm(tacky_prog: ProgramTacky) -> ProgramAsm {
-    ProgramAsm {
-        function: Box::new(translate_fundef(*tacky_prog.function)),
+#[cfg(feature = "parallel")]
+fn translate_fundefs(
+    fundefs: Vec<FunDefTacky>,
+    statics: &HashSet<Symbol>,
+    got: &HashSet<Symbol>,
+    options: CodegenOptions,
+) -> Result<Vec<FunDefAsm>, CodegenError> {
+    use super::context::Context;
+    use rayon::prelude::*;
+
+    // the workers intern into the compilation's context
+    let context = Context::current();
+    let pool = match options.jobs {
+        0 | 1 => None,
+        jobs => rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .ok(),
+    };
+    match pool {
+        // collected in order before the errors are looked at
+        Some(pool) => pool
+            .install(|| {
+                fundefs
+                    .into_par_iter()
+                    .map(|fundef| {
+                        let _entered = context.enter();
+                        translate_fundef(fundef, statics, got, options)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .into_iter()
+            .collect(),
+        // also when no threads could be started, which changes nothing but the speed
+        None => fundefs
+            .into_iter()
+            .map(|fundef| translate_fundef(fundef, statics, got, options))
+            .collect(),
     }
 }
 
-fn translate_fundef(tacky_fundef: FunDefTacky) -> FunDefAsm {
-    let pseudo_instrs = translate_with_pseudo(tacky_fundef.instructions);
-    let mut tmp_resolver = TmpVarResolver::new();
+#[cfg(not(feature = "parallel"))]
+fn translate_fundefs(
+    fundefs: Vec<FunDefTacky>,
+    statics: &HashSet<Symbol>,
+    got: &HashSet<Symbol>,
+    options: CodegenOptions,
+) -> Result<Vec<FunDefAsm>, CodegenError> {
+    fundefs
+        .into_iter()
+        .map(|fundef| translate_fundef(fundef, statics, got, options))
+        .collect()
+}
+
+/// a variable with static storage lives in its own place in memory, rather
+/// than in a stack slot; those in `got` are found through the GOT.
+fn translate_fundef(
+    mut tacky_fundef: FunDefTacky,
+    statics: &HashSet<Symbol>,
+    got: &HashSet<Symbol>,
+    options: CodegenOptions,
+) -> Result<FunDefAsm, CodegenError> {
+    // the line the function starts on covers its prologue, as gcc's does
+    let entry = match tacky_fundef.instructions.first() {
+        Some(InstructionTacky::Loc { file, line, col }) => {
+            let loc = InstructionAsm::Loc {
+                file: *file,
+                line: *line,
+                col: *col,
+            };
+            tacky_fundef.instructions.remove(0);
+            Some(loc)
+        }
+        _ => None,
+    };
+    let aggregates = aggregates(&tacky_fundef.instructions, statics);
+    let params_on_stack = stack_args(&tacky_fundef.params, options.target).len();
+    let mut pseudo_instrs = copy_params(&tacky_fundef.params, options.target);
+    pseudo_instrs.append(&mut if options.asm_comments {
+        translate_with_comments(tacky_fundef.instructions, options.target, params_on_stack)?
+    } else {
+        translate_with_pseudo(tacky_fundef.instructions, options.target, params_on_stack)?
+    });
+    let mut pseudo_instrs = if options.cmov {
+        select_conditional_moves(pseudo_instrs)
+    } else {
+        pseudo_instrs
+    };
+    for instr in pseudo_instrs.iter_mut() {
+        for operand in operands_mut(instr) {
+            if let OperandAsm::Pseudo { name } = *operand {
+                if statics.contains(&name) {
+                    *operand = OperandAsm::Data { name };
+                }
+            }
+        }
+    }
+    let mut tmp_resolver = TmpVarResolver::new(&pseudo_instrs, &aggregates);
     let resolved_instrs = pseudo_instrs
         .into_iter()
         .map(|i| tmp_resolver.resolve_temps(i))
         .collect();
-    let fixed_instrs = fix_up_instrs(resolved_instrs, tmp_resolver.get_min_used());
-    FunDefAsm {
+    let fixed_instrs = through_got(fix_up_instrs(resolved_instrs), got);
+    let mut instructions =
+        add_prologue_epilogue(fixed_instrs, tmp_resolver.get_min_used(), options);
+    // just inside `.cfi_startproc`, which opens every function
+    instructions.splice(1..1, entry);
+    let fundef = FunDefAsm {
         identifier: tacky_fundef.identifier,
-        instructions: fixed_instrs,
+        global: tacky_fundef.global,
+        instructions,
+    };
+    match validate_asm(&fundef) {
+        Err(e) => Err(CodegenError::Invalid { e }),
+        Ok(()) => Ok(fundef),
     }
 }
 
-/// fixes up instructions so that non-pseudo operands are correct for different instructions.
-/// Assumes that pseudo-operands have already been resolved.
-fn fix_up_instrs(resolved_instrs: Vec<InstructionAsm>, min_used: i32) -> Vec<InstructionAsm> {
-    let mut res = Vec::with_capacity(resolved_instrs.len() + 1);
-    if min_used != 0 {
-        res.push(InstructionAsm::AllocStack { off: min_used });
-    }
-
-    for instr in resolved_instrs.into_iter() {
-        match instr {
-            InstructionAsm::Mov { src, dst } => {
-                if matches!(src, OperandAsm::Stack { off: _ })
-                    && matches!(dst, OperandAsm::Stack { off: _ })
-                {
-                    res.append(&mut vec![
-                        InstructionAsm::Mov {
-                            src,
-                            dst: OperandAsm::Reg { r: Register::R10 },
-                        },
-                        InstructionAsm::Mov {
-                            src: OperandAsm::Reg { r: Register::R10 },
-                            dst,
-                        },
-                    ])
-                } else {
-                    res.push(instr)
+/// the variables of `instrs` too big for a register, the arrays and
+/// structures, with their types, other than those with static storage.
+fn aggregates(instrs: &[InstructionTacky], statics: &HashSet<Symbol>) -> HashMap<Symbol, CType> {
+    let mut res = HashMap::new();
+    for instr in instrs.iter() {
+        let mut vals = instr.srcs();
+        vals.extend(instr.dst());
+        if let InstructionTacky::GetAddress { src, .. } = instr {
+            vals.push(src);
+        }
+        for val in vals {
+            if let ValTacky::TmpVar { name, ty } = val {
+                if (ty.is_array() || ty.is_struct()) && !statics.contains(name) {
+                    res.insert(*name, *ty);
                 }
             }
-            InstructionAsm::Binary {
-                binop: _,
-                src: _,
-                dst: _,
-            } => resolve_binary(instr, &mut res),
-            InstructionAsm::Idiv { operand } => match operand {
-                OperandAsm::Imm { int } => res.append(&mut vec![
-                    InstructionAsm::Mov {
-                        src: OperandAsm::Imm { int },
-                        dst: OperandAsm::Reg { r: Register::R10 },
-                    },
-                    InstructionAsm::Idiv {
-                        operand: OperandAsm::Reg { r: Register::R10 },
-                    },
-                ]),
-                _ => res.push(instr),
-            },
-            _ => res.push(instr),
         }
     }
-
     res
 }
 
-fn resolve_binary(instr: InstructionAsm, instrs: &mut Vec<InstructionAsm>) {
-    if let InstructionAsm::Binary { binop, src, dst } = &instr {
-        match binop {
-            BinaryOp::Multiply => instrs.append(&mut vec![
-                InstructionAsm::Mov {
-                    src: *dst,
-                    dst: OperandAsm::Reg { r: Register::R11 },
-                },
-                InstructionAsm::Binary {
-                    binop: binop.clone(),
-                    src: *src,
-                    dst: OperandAsm::Reg { r: Register::R11 },
-                },
-                InstructionAsm::Mov {
-                    src: OperandAsm::Reg { r: Register::R11 },
-                    dst: *dst,
-                },
-            ]),
-            _ => {
-                if matches!(src, OperandAsm::Stack { off: _ })
-                    && matches!(dst, OperandAsm::Stack { off: _ })
-                {
-                    instrs.append(&mut vec![
-                        InstructionAsm::Mov {
-                            src: *src,
-                            dst: OperandAsm::Reg { r: Register::R10 },
-                        },
-                        InstructionAsm::Binary {
-                            binop: binop.clone(),
-                            src: OperandAsm::Reg { r: Register::R10 },
-                            dst: *dst,
-                        },
-                    ])
-                } else {
-                    instrs.push(instr)
+/// copies each parameter from where the caller passed it into its pseudo, so
+/// the body can treat it like any other variable: the first few from registers,
+/// and the rest from above the return address, past the shadow space.
+fn copy_params(params: &[ValTacky], target: Target) -> Vec<InstructionAsm> {
+    let doubles: Vec<bool> = params.iter().map(|p| p.ty() == CType::Double).collect();
+    let mut on_stack = 0;
+    params
+        .iter()
+        .zip(target.arg_registers(&doubles))
+        .map(|(param, r)| {
+            let src = match r {
+                Some(r) => OperandAsm::Reg { r },
+                None => {
+                    on_stack += 1;
+                    OperandAsm::Stack {
+                        off: 16 + target.shadow_space() + 8 * (on_stack - 1),
+                    }
                 }
+            };
+            InstructionAsm::Mov {
+                ty: param.ty().into(),
+                src,
+                dst: translate_valtacky(param.clone()),
             }
-        }
-    }
+        })
+        .collect()
 }
 
-/// resolves temporary, or pseudo operands, to use an actual operand.
-struct TmpVarResolver {
-    min: i32,
+/// wraps a function body in its prologue and epilogue.
+/// The prologue sets up the frame pointer, allocates the stack frame, and saves
+/// any callee-saved registers the body touches; every `Ret`, and every tail call,
+/// is preceded by the matching restores and frame teardown. Which registers need
+/// saving is up to the target's calling convention.
+/// Without a frame pointer, the frame is only allocated and freed, and the body's
+/// %rbp-relative slots are rewritten relative to %rsp as it stands after the pushes.
+/// A frame that fits in the red zone is not allocated at all.
+/// On targets that take them, CFI directives follow each instruction that moves
+/// the CFA or saves a register, and an epilogue with code after its `ret`
+/// remembers the body's rules and restores them after it. Windows lays out the
+/// frame its own way, to suit its unwinder, and always keeps the frame pointer.
+fn add_prologue_epilogue(
+    body: Vec<InstructionAsm>,
     min_used: i32,
-    id_to_off: HashMap<u16, i32>,
+    options: CodegenOptions,
+) -> Vec<InstructionAsm> {
+    let saved: Vec<Register> = options
+        .target
+        .callee_saved()
+        .iter()
+        .copied()
+        .filter(|r| body.iter().any(|i| instr_uses_reg(i, r)))
+        .collect();
+    let in_red_zone = options.red_zone && fits_red_zone(&body, min_used, &saved, options.target);
+
+    if !options.target.has_cfi() {
+        add_windows_prologue_epilogue(body, min_used, saved)
+    } else if options.omit_frame_pointer {
+        add_frameless_prologue_epilogue(body, min_used, saved, in_red_zone)
+    } else {
+        add_frame_pointer_prologue_epilogue(body, min_used, saved, in_red_zone)
+    }
 }
 
-impl TmpVarResolver {
-    fn new() -> Self {
-        TmpVarResolver {
-            min: -4,
-            min_used: 0,
-            id_to_off: HashMap::new(),
-        }
+/// whether `instr` leaves the function, and so needs the epilogue before it.
+fn leaves(instr: &InstructionAsm) -> bool {
+    matches!(instr, InstructionAsm::Ret | InstructionAsm::TailCall { .. })
+}
+
+fn cfi(directive: CfiDirective) -> InstructionAsm {
+    InstructionAsm::Cfi { directive }
+}
+
+/// the CFA stays 16 bytes above %rbp from the `mov` on, so only the pushes
+/// around it need describing.
+fn add_frame_pointer_prologue_epilogue(
+    body: Vec<InstructionAsm>,
+    min_used: i32,
+    saved: Vec<Register>,
+    in_red_zone: bool,
+) -> Vec<InstructionAsm> {
+    let frame = if in_red_zone {
+        0
+    } else {
+        round_frame_size(-min_used, 8 * saved.len() as i32)
+    };
+
+    let mut res = Vec::with_capacity(body.len() + 3 * saved.len() + 10);
+    res.push(cfi(CfiDirective::StartProc));
+    res.push(InstructionAsm::Push { r: Register::BP });
+    res.push(cfi(CfiDirective::DefCfaOffset { off: 16 }));
+    res.push(cfi(CfiDirective::Offset {
+        r: Register::BP,
+        off: -16,
+    }));
+    res.push(InstructionAsm::MovQ {
+        src: Register::SP,
+        dst: Register::BP,
+    });
+    res.push(cfi(CfiDirective::DefCfaRegister { r: Register::BP }));
+    res.append(&mut allocate_frame(frame));
+    for (i, r) in saved.iter().enumerate() {
+        res.push(InstructionAsm::Push { r: *r });
+        res.push(cfi(CfiDirective::Offset {
+            r: *r,
+            off: -16 - frame - 8 * (i as i32 + 1),
+        }));
     }
 
-    fn get_min_used(&mut self) -> i32 {
-        self.min_used
+    let last_ret = body.iter().rposition(leaves);
+    for (i, instr) in body.into_iter().enumerate() {
+        let returns = leaves(&instr);
+        let code_follows = returns && Some(i) != last_ret;
+        if returns {
+            if code_follows {
+                res.push(cfi(CfiDirective::RememberState));
+            }
+            res.extend(saved.iter().rev().map(|r| InstructionAsm::Pop { r: *r }));
+            res.push(InstructionAsm::MovQ {
+                src: Register::BP,
+                dst: Register::SP,
+            });
+            res.push(InstructionAsm::Pop { r: Register::BP });
+            res.push(cfi(CfiDirective::DefCfa {
+                r: Register::SP,
+                off: 8,
+            }));
+        }
+        res.push(instr);
+        if code_follows {
+            res.push(cfi(CfiDirective::RestoreState));
+        }
     }
+    res.push(cfi(CfiDirective::EndProc));
 
-    fn resolve_temps(&mut self, instr: InstructionAsm) -> InstructionAsm {
-        match instr {
-            InstructionAsm::Mov { src, dst } => InstructionAsm::Mov {
-                src: self.temp_to_stack(src),
-                dst: self.temp_to_stack(dst),
-            },
-            InstructionAsm::Unary { unop, operand } => InstructionAsm::Unary {
-                unop,
-                operand: self.temp_to_stack(operand),
-            },
-            InstructionAsm::Binary { binop, src, dst } => InstructionAsm::Binary {
-                binop,
-                src: self.temp_to_stack(src),
-                dst: self.temp_to_stack(dst),
-            },
-            InstructionAsm::Idiv { operand } => InstructionAsm::Idiv {
-                operand: self.temp_to_stack(operand),
+    res
+}
+
+/// the CFA is measured from %rsp throughout, so every instruction that moves
+/// %rsp is followed by its new distance. That includes the body's own pushes
+/// and allocations around calls, which the slots' offsets follow too.
+fn add_frameless_prologue_epilogue(
+    body: Vec<InstructionAsm>,
+    min_used: i32,
+    saved: Vec<Register>,
+    in_red_zone: bool,
+) -> Vec<InstructionAsm> {
+    // only the return address sits above the frame now, so it counts as pushed
+    let pushed = 8 * saved.len() as i32;
+    let frame = if in_red_zone {
+        0
+    } else {
+        round_frame_size(-min_used, pushed + 8)
+    };
+
+    let mut res = Vec::with_capacity(body.len() + 4 * saved.len() + 8);
+    res.push(cfi(CfiDirective::StartProc));
+    // the return address
+    let mut cfa = 8;
+    for instr in allocate_frame(frame).into_iter() {
+        let moves_sp = matches!(instr, InstructionAsm::AllocStack { .. });
+        if let InstructionAsm::AllocStack { off } = instr {
+            cfa -= off;
+        }
+        res.push(instr);
+        if moves_sp {
+            res.push(cfi(CfiDirective::DefCfaOffset { off: cfa }));
+        }
+    }
+    for r in saved.iter() {
+        res.push(InstructionAsm::Push { r: *r });
+        cfa += 8;
+        res.push(cfi(CfiDirective::DefCfaOffset { off: cfa }));
+        res.push(cfi(CfiDirective::Offset { r: *r, off: -cfa }));
+    }
+
+    let last_ret = body.iter().rposition(leaves);
+    // bytes the body has moved %rsp down by since the prologue
+    let mut moved = 0;
+    for (i, mut instr) in body.into_iter().enumerate() {
+        let returns = leaves(&instr);
+        let code_follows = returns && Some(i) != last_ret;
+        if returns {
+            if code_follows {
+                res.push(cfi(CfiDirective::RememberState));
+            }
+            let mut cfa = cfa;
+            for r in saved.iter().rev() {
+                res.push(InstructionAsm::Pop { r: *r });
+                cfa -= 8;
+                res.push(cfi(CfiDirective::DefCfaOffset { off: cfa }));
+            }
+            if frame != 0 {
+                res.push(InstructionAsm::DeallocStack { off: -frame });
+                res.push(cfi(CfiDirective::DefCfaOffset { off: 8 }));
+            }
+        }
+        for operand in operands_mut(&mut instr) {
+            // slots sit just below the return address, which is `frame + pushed`
+            // above %rsp, and stack arguments just above it, where they would be
+            // 8 bytes further from a saved %rbp
+            if let OperandAsm::Stack { off } = *operand {
+                *operand = OperandAsm::Memory {
+                    base: Register::SP,
+                    off: frame + pushed + moved + if off > 0 { off - 8 } else { off },
+                };
+            }
+        }
+        let moves_by = match instr {
+            InstructionAsm::Push { .. } => 8,
+            InstructionAsm::Pop { .. } => -8,
+            InstructionAsm::AllocStack { off } => -off,
+            InstructionAsm::DeallocStack { off } => off,
+            _ => 0,
+        };
+        res.push(instr);
+        if moves_by != 0 {
+            moved += moves_by;
+            res.push(cfi(CfiDirective::DefCfaOffset { off: cfa + moved }));
+        }
+        if code_follows {
+            res.push(cfi(CfiDirective::RestoreState));
+        }
+    }
+    res.push(cfi(CfiDirective::EndProc));
+
+    res
+}
+
+/// the unwind codes Windows keeps can't follow %rsp through the body's pushes,
+/// nor a register pushed after the frame pointer is set, so %rbp and the
+/// callee-saved registers are pushed first, the frame allocated below them,
+/// and %rbp pointed at its bottom, where it stays. Each epilogue frees the
+/// frame with a `lea` from %rbp and pops the registers back, the only shape
+/// the unwinder recognizes. The body's slots are rewritten relative to %rbp.
+fn add_windows_prologue_epilogue(
+    body: Vec<InstructionAsm>,
+    min_used: i32,
+    saved: Vec<Register>,
+) -> Vec<InstructionAsm> {
+    let pushed = 8 * saved.len() as i32;
+    let frame = round_frame_size(-min_used, pushed);
+
+    let mut res = Vec::with_capacity(body.len() + 3 * saved.len() + 12);
+    res.push(cfi(CfiDirective::StartProc));
+    for r in std::iter::once(Register::BP).chain(saved.iter().copied()) {
+        res.push(InstructionAsm::Push { r });
+        res.push(cfi(CfiDirective::PushReg { r }));
+    }
+    for instr in allocate_frame(frame).into_iter() {
+        let allocates = match instr {
+            InstructionAsm::AllocStack { off } => Some(-off),
+            _ => None,
+        };
+        res.push(instr);
+        if let Some(size) = allocates {
+            res.push(cfi(CfiDirective::StackAlloc { size }));
+        }
+    }
+    res.push(InstructionAsm::MovQ {
+        src: Register::SP,
+        dst: Register::BP,
+    });
+    res.push(cfi(CfiDirective::SetFrame {
+        r: Register::BP,
+        off: 0,
+    }));
+    res.push(cfi(CfiDirective::EndPrologue));
+
+    for mut instr in body.into_iter() {
+        if leaves(&instr) {
+            res.push(InstructionAsm::Lea {
+                src: OperandAsm::Memory {
+                    base: Register::BP,
+                    off: frame,
+                },
+                dst: OperandAsm::Reg { r: Register::SP },
+            });
+            res.extend(saved.iter().rev().map(|r| InstructionAsm::Pop { r: *r }));
+            res.push(InstructionAsm::Pop { r: Register::BP });
+        }
+        for operand in operands_mut(&mut instr) {
+            // slots and stack arguments are measured from where %rbp was
+            // saved, `frame + pushed` bytes above where it now points
+            if let OperandAsm::Stack { off } = *operand {
+                *operand = OperandAsm::Memory {
+                    base: Register::BP,
+                    off: off + frame + pushed,
+                };
+            }
+        }
+        res.push(instr);
+    }
+    res.push(cfi(CfiDirective::EndProc));
+
+    res
+}
+
+/// Bytes below %rsp the System V ABI promises signal and interrupt handlers leave alone.
+const RED_ZONE_SIZE: i32 = 128;
+
+/// whether a function's slots can live below %rsp without moving it.
+/// That needs a target ABI with a red zone, a frame that fits in it, and nothing
+/// that pushes onto the stack after the prologue: no calls, and no callee-saved
+/// registers to save.
+fn fits_red_zone(
+    body: &[InstructionAsm],
+    min_used: i32,
+    saved: &[Register],
+    target: Target,
+) -> bool {
+    target.has_red_zone() && -min_used <= RED_ZONE_SIZE && saved.is_empty() && is_leaf(body)
+}
+
+/// whether a function body makes no calls.
+/// Every instruction is listed, so any other that leaves the function has to be
+/// classified here when it is added.
+fn is_leaf(body: &[InstructionAsm]) -> bool {
+    body.iter().all(|instr| match instr {
+        InstructionAsm::Call { .. } => false,
+        // the callee's frame replaces this one's
+        InstructionAsm::Mov { .. }
+        | InstructionAsm::Ret
+        | InstructionAsm::TailCall { .. }
+        | InstructionAsm::Unary { .. }
+        | InstructionAsm::AllocStack { .. }
+        | InstructionAsm::DeallocStack { .. }
+        | InstructionAsm::Binary { .. }
+        | InstructionAsm::Idiv { .. }
+        | InstructionAsm::Div { .. }
+        | InstructionAsm::Cdq { .. }
+        | InstructionAsm::Cvttsd2si { .. }
+        | InstructionAsm::Cvtsi2sd { .. }
+        | InstructionAsm::Push { .. }
+        | InstructionAsm::Pop { .. }
+        | InstructionAsm::MovQ { .. }
+        | InstructionAsm::Cmp { .. }
+        | InstructionAsm::Jmp { .. }
+        | InstructionAsm::JmpCC { .. }
+        | InstructionAsm::SetCC { .. }
+        | InstructionAsm::Movzx { .. }
+        | InstructionAsm::Movsx { .. }
+        | InstructionAsm::Cmov { .. }
+        | InstructionAsm::Lea { .. }
+        | InstructionAsm::Label { .. }
+        | InstructionAsm::Comment { .. }
+        | InstructionAsm::Cfi { .. }
+        | InstructionAsm::Loc { .. } => true,
+    })
+}
+
+/// Largest stack adjustment made without touching the new stack, one page.
+/// A bigger jump could skip straight over the guard page below the stack.
+const STACK_PROBE_INTERVAL: i32 = 4096;
+
+/// moves %rsp down by `frame` bytes. Frames larger than `STACK_PROBE_INTERVAL` are
+/// allocated a page at a time, writing to each new page (`orl $0, (%rsp)`) before
+/// moving past it.
+fn allocate_frame(frame: i32) -> Vec<InstructionAsm> {
+    let mut res = Vec::new();
+    let mut remaining = frame;
+    while remaining > STACK_PROBE_INTERVAL {
+        res.push(InstructionAsm::AllocStack {
+            off: -STACK_PROBE_INTERVAL,
+        });
+        res.push(InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Or,
+            src: OperandAsm::Imm { int: 0 },
+            dst: OperandAsm::Memory {
+                base: Register::SP,
+                off: 0,
             },
-            _ => instr,
+        });
+        remaining -= STACK_PROBE_INTERVAL;
+    }
+    if remaining != 0 {
+        res.push(InstructionAsm::AllocStack { off: -remaining });
+    }
+    res
+}
+
+/// every operand an instruction reads or writes.
+fn operands_mut(instr: &mut InstructionAsm) -> Vec<&mut OperandAsm> {
+    match instr {
+        InstructionAsm::Mov { src, dst, .. }
+        | InstructionAsm::Binary { src, dst, .. }
+        | InstructionAsm::Cmp { src, dst, .. }
+        | InstructionAsm::Cvttsd2si { src, dst, .. }
+        | InstructionAsm::Cvtsi2sd { src, dst, .. }
+        | InstructionAsm::Movzx { src, dst, .. }
+        | InstructionAsm::Movsx { src, dst, .. }
+        | InstructionAsm::Cmov { src, dst, .. }
+        | InstructionAsm::Lea { src, dst } => vec![src, dst],
+        InstructionAsm::Unary { operand, .. }
+        | InstructionAsm::Idiv { operand, .. }
+        | InstructionAsm::Div { operand, .. }
+        | InstructionAsm::SetCC { operand, .. } => vec![operand],
+        _ => vec![],
+    }
+}
+
+/// reaches each variable in `got` through its GOT entry: %rbx is loaded with
+/// the variable's address just before the instruction using it, which then
+/// goes through %rbx instead. Nothing else uses %rbx, so it is free at any
+/// instruction, and the prologue saves it like any callee-saved register.
+/// Taking such a variable's address is loading its entry.
+fn through_got(instrs: Vec<InstructionAsm>, got: &HashSet<Symbol>) -> Vec<InstructionAsm> {
+    if got.is_empty() {
+        return instrs;
+    }
+    let mut res = Vec::with_capacity(instrs.len());
+    for mut instr in instrs.into_iter() {
+        if let InstructionAsm::Lea {
+            src: OperandAsm::Data { name },
+            dst,
+        } = instr
+        {
+            if got.contains(&name) {
+                res.push(InstructionAsm::Mov {
+                    ty: AsmType::Quadword,
+                    src: OperandAsm::Got { name },
+                    dst,
+                });
+                continue;
+            }
+        }
+        for operand in operands_mut(&mut instr) {
+            if let OperandAsm::Data { name } = *operand {
+                if got.contains(&name) {
+                    res.push(InstructionAsm::Mov {
+                        ty: AsmType::Quadword,
+                        src: OperandAsm::Got { name },
+                        dst: OperandAsm::Reg { r: Register::BX },
+                    });
+                    *operand = OperandAsm::Memory {
+                        base: Register::BX,
+                        off: 0,
+                    };
+                }
+            }
         }
+        res.push(instr);
     }
+    res
+}
 
-    fn temp_to_stack(&mut self, operand: OperandAsm) -> OperandAsm {
-        match operand {
-            OperandAsm::Pseudo { id } => match self.id_to_off.get(&id) {
-                Some(off) => OperandAsm::Stack { off: *off },
-                None => {
-                    self.min_used = self.min;
-                    self.min -= 4;
-                    self.id_to_off.insert(id, self.min_used);
-                    OperandAsm::Stack { off: self.min_used }
+/// rounds the bytes used by locals up so that %rsp is 16-byte aligned once the frame
+/// is allocated and `pushed` bytes of callee-saved registers are pushed after it.
+/// The return address and the saved %rbp already add up to 16, so only the frame and the
+/// later pushes count. A function that uses no stack and saves nothing keeps a frame of 0
+/// rather than allocating 16 bytes it never touches.
+fn round_frame_size(used: i32, pushed: i32) -> i32 {
+    let total = used + pushed;
+    let aligned = (total + 15) / 16 * 16;
+    aligned - pushed
+}
+
+fn instr_uses_reg(instr: &InstructionAsm, r: &Register) -> bool {
+    let is_reg = |o: &OperandAsm| *o == OperandAsm::Reg { r: *r };
+    match instr {
+        InstructionAsm::Mov { src, dst, .. }
+        | InstructionAsm::Binary { src, dst, .. }
+        | InstructionAsm::Cmp { src, dst, .. }
+        | InstructionAsm::Cvttsd2si { src, dst, .. }
+        | InstructionAsm::Cvtsi2sd { src, dst, .. }
+        | InstructionAsm::Movzx { src, dst, .. }
+        | InstructionAsm::Movsx { src, dst, .. }
+        | InstructionAsm::Cmov { src, dst, .. }
+        | InstructionAsm::Lea { src, dst } => is_reg(src) || is_reg(dst),
+        InstructionAsm::Unary { operand, .. }
+        | InstructionAsm::Idiv { operand, .. }
+        | InstructionAsm::Div { operand, .. }
+        | InstructionAsm::SetCC { operand, .. } => is_reg(operand),
+        InstructionAsm::Push { r: used }
+        | InstructionAsm::Pop { r: used }
+        | InstructionAsm::MovQ { src: used, .. } => used == r,
+        _ => false,
+    }
+}
+
+/// fixes up instructions so that non-pseudo operands are correct for different instructions.
+/// Assumes that pseudo-operands have already been resolved.
+fn fix_up_instrs(resolved_instrs: Vec<InstructionAsm>) -> Vec<InstructionAsm> {
+    let mut res = Vec::with_capacity(resolved_instrs.len());
+    let mut immediates_fixed = Vec::with_capacity(2);
+
+    for instr in resolved_instrs.into_iter() {
+        fix_up_immediates(instr, &mut immediates_fixed);
+        for instr in immediates_fixed.drain(..) {
+            match instr {
+                // movsd between two places in memory goes through an XMM register
+                InstructionAsm::Mov {
+                    ty: AsmType::Double,
+                    src,
+                    dst,
+                } if src.is_memory() && dst.is_memory() => res.extend([
+                    InstructionAsm::Mov {
+                        ty: AsmType::Double,
+                        src,
+                        dst: OperandAsm::Reg { r: Register::XMM4 },
+                    },
+                    InstructionAsm::Mov {
+                        ty: AsmType::Double,
+                        src: OperandAsm::Reg { r: Register::XMM4 },
+                        dst,
+                    },
+                ]),
+                InstructionAsm::Mov { ty, src, dst } => {
+                    if src.is_memory() && dst.is_memory() {
+                        res.extend([
+                            InstructionAsm::Mov {
+                                ty,
+                                src,
+                                dst: OperandAsm::Reg { r: Register::R10 },
+                            },
+                            InstructionAsm::Mov {
+                                ty,
+                                src: OperandAsm::Reg { r: Register::R10 },
+                                dst,
+                            },
+                        ])
+                    } else {
+                        res.push(instr)
+                    }
+                }
+                InstructionAsm::Binary { .. } => resolve_binary(instr, &mut res),
+                InstructionAsm::Idiv {
+                    ty,
+                    operand: OperandAsm::Imm { int },
+                } => res.extend([
+                    InstructionAsm::Mov {
+                        ty,
+                        src: OperandAsm::Imm { int },
+                        dst: OperandAsm::Reg { r: Register::R10 },
+                    },
+                    InstructionAsm::Idiv {
+                        ty,
+                        operand: OperandAsm::Reg { r: Register::R10 },
+                    },
+                ]),
+                InstructionAsm::Div {
+                    ty,
+                    operand: OperandAsm::Imm { int },
+                } => res.extend([
+                    InstructionAsm::Mov {
+                        ty,
+                        src: OperandAsm::Imm { int },
+                        dst: OperandAsm::Reg { r: Register::R10 },
+                    },
+                    InstructionAsm::Div {
+                        ty,
+                        operand: OperandAsm::Reg { r: Register::R10 },
+                    },
+                ]),
+                // comisd compares against an XMM register
+                InstructionAsm::Cmp {
+                    ty: AsmType::Double,
+                    src,
+                    dst,
+                } if !is_xmm(&dst) => res.extend([
+                    InstructionAsm::Mov {
+                        ty: AsmType::Double,
+                        src: dst,
+                        dst: OperandAsm::Reg { r: Register::XMM5 },
+                    },
+                    InstructionAsm::Cmp {
+                        ty: AsmType::Double,
+                        src,
+                        dst: OperandAsm::Reg { r: Register::XMM5 },
+                    },
+                ]),
+                InstructionAsm::Cmp { ty, src, dst } => {
+                    if matches!(dst, OperandAsm::Imm { int: _ }) {
+                        res.extend([
+                            InstructionAsm::Mov {
+                                ty,
+                                src: dst,
+                                dst: OperandAsm::Reg { r: Register::R11 },
+                            },
+                            InstructionAsm::Cmp {
+                                ty,
+                                src,
+                                dst: OperandAsm::Reg { r: Register::R11 },
+                            },
+                        ])
+                    } else if src.is_memory() && dst.is_memory() {
+                        res.extend([
+                            InstructionAsm::Mov {
+                                ty,
+                                src,
+                                dst: OperandAsm::Reg { r: Register::R10 },
+                            },
+                            InstructionAsm::Cmp {
+                                ty,
+                                src: OperandAsm::Reg { r: Register::R10 },
+                                dst,
+                            },
+                        ])
+                    } else {
+                        res.push(instr)
+                    }
+                }
+                InstructionAsm::Movzx { .. } | InstructionAsm::Movsx { .. } => {
+                    resolve_extension(instr, &mut res)
+                }
+                InstructionAsm::Cmov { ty, cc, src, dst } => {
+                    resolve_cmov(ty, cc, src, dst, &mut res)
+                }
+                // lea can only write to a register
+                InstructionAsm::Lea { src, dst } if dst.is_memory() => res.extend([
+                    InstructionAsm::Lea {
+                        src,
+                        dst: OperandAsm::Reg { r: Register::R11 },
+                    },
+                    InstructionAsm::Mov {
+                        ty: AsmType::Quadword,
+                        src: OperandAsm::Reg { r: Register::R11 },
+                        dst,
+                    },
+                ]),
+                // cvttsd2si can only write to a general-purpose register
+                InstructionAsm::Cvttsd2si { ty, src, dst } if dst.is_memory() => res.extend([
+                    InstructionAsm::Cvttsd2si {
+                        ty,
+                        src,
+                        dst: OperandAsm::Reg { r: Register::R11 },
+                    },
+                    InstructionAsm::Mov {
+                        ty,
+                        src: OperandAsm::Reg { r: Register::R11 },
+                        dst,
+                    },
+                ]),
+                // cvtsi2sd can't take an immediate, and can only write to an XMM register
+                InstructionAsm::Cvtsi2sd { ty, src, dst } => {
+                    let src = match src {
+                        OperandAsm::Imm { .. } => {
+                            res.push(InstructionAsm::Mov {
+                                ty,
+                                src,
+                                dst: OperandAsm::Reg { r: Register::R10 },
+                            });
+                            OperandAsm::Reg { r: Register::R10 }
+                        }
+                        _ => src,
+                    };
+                    if is_xmm(&dst) {
+                        res.push(InstructionAsm::Cvtsi2sd { ty, src, dst })
+                    } else {
+                        res.extend([
+                            InstructionAsm::Cvtsi2sd {
+                                ty,
+                                src,
+                                dst: OperandAsm::Reg { r: Register::XMM5 },
+                            },
+                            InstructionAsm::Mov {
+                                ty: AsmType::Double,
+                                src: OperandAsm::Reg { r: Register::XMM5 },
+                                dst,
+                            },
+                        ])
+                    }
                 }
+                _ => res.push(instr),
+            }
+        }
+    }
+
+    res
+}
+
+/// keeps immediates within what each instruction can encode, pushing the
+/// instruction, or the instructions replacing it, onto `res`.
+/// Only `movq` into a register takes a full 64-bit immediate (as `movabsq`); any other
+/// quadword instruction with an immediate outside the i32 range loads it into R10
+/// first, as does the fix-up of a quadword `idiv`, `div`, `cmov` or `cvtsi2sd`, which
+/// take no immediate at all. Longword instructions only see the low 32 bits of an immediate, as the
+/// assembler would, and byte and word ones the low 8 or 16.
+fn fix_up_immediates(instr: InstructionAsm, res: &mut Vec<InstructionAsm>) {
+    let is_wide =
+        |o: &OperandAsm| matches!(o, OperandAsm::Imm { int } if i32::try_from(*int).is_err());
+    let r10 = OperandAsm::Reg { r: Register::R10 };
+    match instr {
+        InstructionAsm::Mov {
+            ty: AsmType::Quadword,
+            dst: OperandAsm::Reg { .. },
+            ..
+        } => res.push(instr),
+        InstructionAsm::Mov {
+            ty: AsmType::Quadword,
+            src,
+            dst,
+        } if is_wide(&src) => res.extend([
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src,
+                dst: r10,
             },
-            _ => operand,
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: r10,
+                dst,
+            },
+        ]),
+        InstructionAsm::Binary {
+            ty: AsmType::Quadword,
+            binop,
+            src,
+            dst,
+        } if is_wide(&src) => res.extend([
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src,
+                dst: r10,
+            },
+            InstructionAsm::Binary {
+                ty: AsmType::Quadword,
+                binop,
+                src: r10,
+                dst,
+            },
+        ]),
+        InstructionAsm::Cmp {
+            ty: AsmType::Quadword,
+            src,
+            dst,
+        } if is_wide(&src) => res.extend([
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src,
+                dst: r10,
+            },
+            InstructionAsm::Cmp {
+                ty: AsmType::Quadword,
+                src: r10,
+                dst,
+            },
+        ]),
+        mut instr => {
+            if !matches!(
+                instr,
+                InstructionAsm::Mov {
+                    ty: AsmType::Quadword,
+                    ..
+                } | InstructionAsm::Binary {
+                    ty: AsmType::Quadword,
+                    ..
+                } | InstructionAsm::Cmp {
+                    ty: AsmType::Quadword,
+                    ..
+                } | InstructionAsm::Idiv {
+                    ty: AsmType::Quadword,
+                    ..
+                } | InstructionAsm::Div {
+                    ty: AsmType::Quadword,
+                    ..
+                } | InstructionAsm::Cmov {
+                    ty: AsmType::Quadword,
+                    ..
+                } | InstructionAsm::Cvtsi2sd {
+                    ty: AsmType::Quadword,
+                    ..
+                }
+            ) {
+                let ty = match instr {
+                    InstructionAsm::Mov { ty, .. }
+                    | InstructionAsm::Cmp { ty, .. }
+                    | InstructionAsm::Movzx { src_ty: ty, .. }
+                    | InstructionAsm::Movsx { src_ty: ty, .. } => ty,
+                    _ => AsmType::Longword,
+                };
+                for operand in operands_mut(&mut instr) {
+                    if let OperandAsm::Imm { int } = operand {
+                        *int = match ty {
+                            AsmType::Byte => i64::from(*int as i8),
+                            AsmType::Word => i64::from(*int as i16),
+                            _ => i64::from(*int as i32),
+                        };
+                    }
+                }
+            }
+            res.push(instr)
         }
     }
 }
 
-fn translate_with_pseudo(tacky_instrs: Vec<InstructionTacky>) -> Vec<InstructionAsm> {
-    let mut res = Vec::with_capacity(tacky_instrs.len() * 2);
+/// whether the operand is one of the XMM registers.
+fn is_xmm(operand: &OperandAsm) -> bool {
+    matches!(operand, OperandAsm::Reg { r } if r.is_xmm())
+}
+
+/// movzx and movsx can't take an immediate, and can only write to a register,
+/// so an immediate goes into R10 first, and a result headed for memory by way
+/// of R11.
+fn resolve_extension(mut instr: InstructionAsm, instrs: &mut Vec<InstructionAsm>) {
+    let (InstructionAsm::Movzx {
+        src_ty,
+        dst_ty,
+        src,
+        dst,
+    }
+    | InstructionAsm::Movsx {
+        src_ty,
+        dst_ty,
+        src,
+        dst,
+    }) = &mut instr
+    else {
+        return instrs.push(instr);
+    };
+    let (r10, r11) = (
+        OperandAsm::Reg { r: Register::R10 },
+        OperandAsm::Reg { r: Register::R11 },
+    );
+    let dst_ty = *dst_ty;
+    if let OperandAsm::Imm { .. } = src {
+        instrs.push(InstructionAsm::Mov {
+            ty: *src_ty,
+            src: *src,
+            dst: r10,
+        });
+        *src = r10;
+    }
+    let stored = dst.is_memory().then(|| std::mem::replace(dst, r11));
+    instrs.push(instr);
+    if let Some(dst) = stored {
+        instrs.push(InstructionAsm::Mov {
+            ty: dst_ty,
+            src: r11,
+            dst,
+        });
+    }
+}
 
-    for tacky_instr in tacky_instrs.into_iter() {
-        match tacky_instr {
-            InstructionTacky::Ret { v } => res.append(&mut vec![
+fn resolve_binary(instr: InstructionAsm, instrs: &mut Vec<InstructionAsm>) {
+    if let InstructionAsm::Binary {
+        ty,
+        binop,
+        src,
+        dst,
+    } = &instr
+    {
+        match binop {
+            // SSE arithmetic can only write to an XMM register
+            _ if *ty == AsmType::Double && !is_xmm(dst) => instrs.extend([
                 InstructionAsm::Mov {
-                    src: translate_valtacky(v),
-                    dst: OperandAsm::Reg { r: Register::AX },
+                    ty: *ty,
+                    src: *dst,
+                    dst: OperandAsm::Reg { r: Register::XMM5 },
+                },
+                InstructionAsm::Binary {
+                    ty: *ty,
+                    binop: *binop,
+                    src: *src,
+                    dst: OperandAsm::Reg { r: Register::XMM5 },
+                },
+                InstructionAsm::Mov {
+                    ty: *ty,
+                    src: OperandAsm::Reg { r: Register::XMM5 },
+                    dst: *dst,
                 },
-                InstructionAsm::Ret,
             ]),
-            InstructionTacky::Unary { op, src, dst } => {
-                let src = translate_valtacky(src);
-                let dst = translate_valtacky(dst);
-                res.append(&mut vec![
+            // a shift counts by an immediate or by %cl
+            AsmBinaryOp::Shl | AsmBinaryOp::Sar | AsmBinaryOp::Shr
+                if !matches!(
+                    src,
+                    OperandAsm::Imm { .. } | OperandAsm::Reg { r: Register::CX }
+                ) =>
+            {
+                instrs.extend([
                     InstructionAsm::Mov {
-                        src,
-                        dst: dst.clone(),
+                        ty: *ty,
+                        src: *src,
+                        dst: OperandAsm::Reg { r: Register::CX },
                     },
-                    InstructionAsm::Unary {
-                        unop: op,
-                        operand: dst,
+                    InstructionAsm::Binary {
+                        ty: *ty,
+                        binop: *binop,
+                        src: OperandAsm::Reg { r: Register::CX },
+                        dst: *dst,
                     },
                 ])
             }
-            InstructionTacky::Binary {
-                op,
-                src1,
-                src2,
-                dst,
-            } => {
-                let src1 = translate_valtacky(src1);
-                let src2 = translate_valtacky(src2);
-                let dst = translate_valtacky(dst);
-                match op {
-                    BinaryOp::Divide => res.append(&mut vec![
-                        InstructionAsm::Mov {
-                            src: src1,
-                            dst: OperandAsm::Reg { r: Register::AX },
-                        },
-                        InstructionAsm::Cdq,
-                        InstructionAsm::Idiv { operand: src2 },
-                        InstructionAsm::Mov {
-                            src: OperandAsm::Reg { r: Register::AX },
-                            dst,
-                        },
-                    ]),
-                    BinaryOp::Remainder => res.append(&mut vec![
-                        InstructionAsm::Mov {
-                            src: src1,
-                            dst: OperandAsm::Reg { r: Register::AX },
-                        },
-                        InstructionAsm::Cdq,
-                        InstructionAsm::Idiv { operand: src2 },
-                        InstructionAsm::Mov {
-                            src: OperandAsm::Reg { r: Register::DX },
-                            dst,
-                        },
-                    ]),
-                    _ => res.append(&mut vec![
+            // imul can only write to a register
+            AsmBinaryOp::Imul if !matches!(dst, OperandAsm::Reg { .. }) => instrs.extend([
+                InstructionAsm::Mov {
+                    ty: *ty,
+                    src: *dst,
+                    dst: OperandAsm::Reg { r: Register::R11 },
+                },
+                InstructionAsm::Binary {
+                    ty: *ty,
+                    binop: *binop,
+                    src: *src,
+                    dst: OperandAsm::Reg { r: Register::R11 },
+                },
+                InstructionAsm::Mov {
+                    ty: *ty,
+                    src: OperandAsm::Reg { r: Register::R11 },
+                    dst: *dst,
+                },
+            ]),
+            _ => {
+                if src.is_memory() && dst.is_memory() {
+                    instrs.extend([
                         InstructionAsm::Mov {
-                            src: src1,
-                            dst: dst.clone(),
+                            ty: *ty,
+                            src: *src,
+                            dst: OperandAsm::Reg { r: Register::R10 },
                         },
                         InstructionAsm::Binary {
-                            binop: op,
-                            src: src2,
-                            dst,
+                            ty: *ty,
+                            binop: *binop,
+                            src: OperandAsm::Reg { r: Register::R10 },
+                            dst: *dst,
                         },
-                    ]),
+                    ])
+                } else {
+                    instrs.push(instr)
                 }
             }
         }
     }
+}
 
-    res
+/// cmov can't take an immediate, and can only write to a register. A destination
+/// in memory is loaded first, so that it keeps its value if the move doesn't happen;
+/// none of the moves around it touch the flags.
+fn resolve_cmov(
+    ty: AsmType,
+    cc: CondCode,
+    src: OperandAsm,
+    dst: OperandAsm,
+    instrs: &mut Vec<InstructionAsm>,
+) {
+    let src = match src {
+        OperandAsm::Imm { .. } => {
+            instrs.push(InstructionAsm::Mov {
+                ty,
+                src,
+                dst: OperandAsm::Reg { r: Register::R10 },
+            });
+            OperandAsm::Reg { r: Register::R10 }
+        }
+        _ => src,
+    };
+    if matches!(dst, OperandAsm::Reg { .. }) {
+        instrs.push(InstructionAsm::Cmov { ty, cc, src, dst });
+        return;
+    }
+    instrs.extend([
+        InstructionAsm::Mov {
+            ty,
+            src: dst,
+            dst: OperandAsm::Reg { r: Register::R11 },
+        },
+        InstructionAsm::Cmov {
+            ty,
+            cc,
+            src,
+            dst: OperandAsm::Reg { r: Register::R11 },
+        },
+        InstructionAsm::Mov {
+            ty,
+            src: OperandAsm::Reg { r: Register::R11 },
+            dst,
+        },
+    ]);
 }
 
-fn translate_valtacky(tval: ValTacky) -> OperandAsm {
-    match tval {
-        ValTacky::Const { int } => OperandAsm::Imm { int },
-        ValTacky::TmpVar { no } => OperandAsm::Pseudo { id: no },
+/// resolves temporary, or pseudo operands, to use an actual operand.
+/// Pseudos whose live ranges never overlap share a stack slot, so the frame only
+/// needs as many slots as there are values live at the same time. Slots are sized
+/// and aligned to the widest type each pseudo is used at, and only shared between
+/// pseudos of the same size. An array gets a slot of its own, sized and aligned
+/// for its type.
+struct TmpVarResolver {
+    min_used: i32,
+    name_to_off: HashMap<Symbol, i32>,
+}
+
+impl TmpVarResolver {
+    /// assigns slots by a linear scan over the live ranges, in order of where they start,
+    /// handing out the slot closest to the frame pointer among those free.
+    fn new(instrs: &[InstructionAsm], aggregates: &HashMap<Symbol, CType>) -> Self {
+        let mut ranges: Vec<(Symbol, (usize, usize))> = live_ranges(instrs).into_iter().collect();
+        ranges.sort_by_key(|(id, (start, _))| (*start, *id));
+        let sizes = pseudo_sizes(instrs);
+
+        let mut min_used = 0;
+        let mut name_to_off = HashMap::new();
+        let mut active: Vec<(usize, i32, i32)> = Vec::new();
+        let mut free: BTreeSet<(i32, i32)> = BTreeSet::new();
+
+        for (id, (start, end)) in ranges.into_iter() {
+            active.retain(|(active_end, size, off)| {
+                let expired = *active_end < start;
+                if expired {
+                    free.insert((*size, *off));
+                }
+                !expired
+            });
+            let size = sizes[&id];
+            if let Some(ty) = aggregates.get(&id) {
+                let size = size.max(ty.size() as i32);
+                let alignment = ty.variable_alignment() as i32;
+                min_used = (min_used - size).div_euclid(alignment) * alignment;
+                name_to_off.insert(id, min_used);
+                continue;
+            }
+            let off = match free.range((size, i32::MIN)..=(size, i32::MAX)).next_back() {
+                Some(&(_, off)) => {
+                    free.remove(&(size, off));
+                    off
+                }
+                None => {
+                    min_used = (min_used - size).div_euclid(size) * size;
+                    min_used
+                }
+            };
+            active.push((end, size, off));
+            name_to_off.insert(id, off);
+        }
+
+        TmpVarResolver {
+            min_used,
+            name_to_off,
+        }
+    }
+
+    fn get_min_used(&mut self) -> i32 {
+        self.min_used
     }
+
+    fn resolve_temps(&mut self, instr: InstructionAsm) -> InstructionAsm {
+        match instr {
+            InstructionAsm::Mov { ty, src, dst } => InstructionAsm::Mov {
+                ty,
+                src: self.temp_to_stack(src),
+                dst: self.temp_to_stack(dst),
+            },
+            InstructionAsm::Unary { ty, unop, operand } => InstructionAsm::Unary {
+                ty,
+                unop,
+                operand: self.temp_to_stack(operand),
+            },
+            InstructionAsm::Binary {
+                ty,
+                binop,
+                src,
+                dst,
+            } => InstructionAsm::Binary {
+                ty,
+                binop,
+                src: self.temp_to_stack(src),
+                dst: self.temp_to_stack(dst),
+            },
+            InstructionAsm::Idiv { ty, operand } => InstructionAsm::Idiv {
+                ty,
+                operand: self.temp_to_stack(operand),
+            },
+            InstructionAsm::Div { ty, operand } => InstructionAsm::Div {
+                ty,
+                operand: self.temp_to_stack(operand),
+            },
+            InstructionAsm::Cmp { ty, src, dst } => InstructionAsm::Cmp {
+                ty,
+                src: self.temp_to_stack(src),
+                dst: self.temp_to_stack(dst),
+            },
+            InstructionAsm::Cvttsd2si { ty, src, dst } => InstructionAsm::Cvttsd2si {
+                ty,
+                src: self.temp_to_stack(src),
+                dst: self.temp_to_stack(dst),
+            },
+            InstructionAsm::Cvtsi2sd { ty, src, dst } => InstructionAsm::Cvtsi2sd {
+                ty,
+                src: self.temp_to_stack(src),
+                dst: self.temp_to_stack(dst),
+            },
+            InstructionAsm::SetCC { cc, operand } => InstructionAsm::SetCC {
+                cc,
+                operand: self.temp_to_stack(operand),
+            },
+            InstructionAsm::Movzx {
+                src_ty,
+                dst_ty,
+                src,
+                dst,
+            } => InstructionAsm::Movzx {
+                src_ty,
+                dst_ty,
+                src: self.temp_to_stack(src),
+                dst: self.temp_to_stack(dst),
+            },
+            InstructionAsm::Movsx {
+                src_ty,
+                dst_ty,
+                src,
+                dst,
+            } => InstructionAsm::Movsx {
+                src_ty,
+                dst_ty,
+                src: self.temp_to_stack(src),
+                dst: self.temp_to_stack(dst),
+            },
+            InstructionAsm::Cmov { ty, cc, src, dst } => InstructionAsm::Cmov {
+                ty,
+                cc,
+                src: self.temp_to_stack(src),
+                dst: self.temp_to_stack(dst),
+            },
+            InstructionAsm::Lea { src, dst } => InstructionAsm::Lea {
+                src: self.temp_to_stack(src),
+                dst: self.temp_to_stack(dst),
+            },
+            _ => instr,
+        }
+    }
+
+    fn temp_to_stack(&mut self, operand: OperandAsm) -> OperandAsm {
+        match operand {
+            OperandAsm::Pseudo { name } => OperandAsm::Stack {
+                off: self.name_to_off[&name],
+            },
+            _ => operand,
+        }
+    }
+}
+
+/// bytes each pseudo needs, the widest size any instruction uses it at.
+fn pseudo_sizes(instrs: &[InstructionAsm]) -> HashMap<Symbol, i32> {
+    let mut sizes = HashMap::new();
+    for instr in instrs.iter() {
+        let operands: Vec<(AsmType, &OperandAsm)> = match instr {
+            InstructionAsm::Mov { ty, src, dst }
+            | InstructionAsm::Binary { ty, src, dst, .. }
+            | InstructionAsm::Cmp { ty, src, dst }
+            | InstructionAsm::Cmov { ty, src, dst, .. } => vec![(*ty, src), (*ty, dst)],
+            InstructionAsm::Unary { ty, operand, .. }
+            | InstructionAsm::Idiv { ty, operand }
+            | InstructionAsm::Div { ty, operand } => {
+                vec![(*ty, operand)]
+            }
+            InstructionAsm::SetCC { operand, .. } => vec![(AsmType::Byte, operand)],
+            InstructionAsm::Movzx {
+                src_ty,
+                dst_ty,
+                src,
+                dst,
+            }
+            | InstructionAsm::Movsx {
+                src_ty,
+                dst_ty,
+                src,
+                dst,
+            } => vec![(*src_ty, src), (*dst_ty, dst)],
+            InstructionAsm::Cvttsd2si { ty, src, dst } => {
+                vec![(AsmType::Double, src), (*ty, dst)]
+            }
+            InstructionAsm::Cvtsi2sd { ty, src, dst } => {
+                vec![(*ty, src), (AsmType::Double, dst)]
+            }
+            // the pointer doesn't say what it points to, so a variable whose
+            // address is taken gets room for the widest type
+            InstructionAsm::Lea { src, dst } => {
+                vec![(AsmType::Quadword, src), (AsmType::Quadword, dst)]
+            }
+            _ => continue,
+        };
+        for (ty, operand) in operands.into_iter() {
+            if let OperandAsm::Pseudo { name } = operand {
+                let size = sizes.entry(*name).or_insert(0);
+                *size = ty.size().max(*size);
+            }
+        }
+    }
+    sizes
+}
+
+/// pseudos read and written by an instruction, in that order.
+fn pseudo_uses_defs(instr: &InstructionAsm) -> (Vec<Symbol>, Vec<Symbol>) {
+    let ids = |operands: &[&OperandAsm]| -> Vec<Symbol> {
+        operands
+            .iter()
+            .filter_map(|o| match o {
+                OperandAsm::Pseudo { name } => Some(*name),
+                _ => None,
+            })
+            .collect()
+    };
+    match instr {
+        InstructionAsm::Mov { src, dst, .. } => (ids(&[src]), ids(&[dst])),
+        InstructionAsm::Unary { operand, .. } => (ids(&[operand]), ids(&[operand])),
+        InstructionAsm::Binary { src, dst, .. } => (ids(&[src, dst]), ids(&[dst])),
+        InstructionAsm::Idiv { operand, .. } | InstructionAsm::Div { operand, .. } => {
+            (ids(&[operand]), vec![])
+        }
+        InstructionAsm::Cmp { src, dst, .. } => (ids(&[src, dst]), vec![]),
+        InstructionAsm::SetCC { operand, .. } => (vec![], ids(&[operand])),
+        InstructionAsm::Movzx { src, dst, .. }
+        | InstructionAsm::Movsx { src, dst, .. }
+        | InstructionAsm::Cvttsd2si { src, dst, .. }
+        | InstructionAsm::Cvtsi2sd { src, dst, .. } => (ids(&[src]), ids(&[dst])),
+        // the old value stays when the move doesn't happen
+        InstructionAsm::Cmov { src, dst, .. } => (ids(&[src, dst]), ids(&[dst])),
+        // taking an address reads nothing; `live_ranges` keeps the variable anyway
+        InstructionAsm::Lea { dst, .. } => (vec![], ids(&[dst])),
+        _ => (vec![], vec![]),
+    }
+}
+
+/// computes, for every pseudo, the span of program points it occupies.
+/// Point `2i` is just before instruction `i` and `2i + 1` just after it; a pseudo
+/// occupies every point where it is live, plus the point after each write to it
+/// (even a dead write needs somewhere to go). Liveness is solved backwards over
+/// the instruction-level control flow until it stops changing, so values live
+/// around loops keep their slot for the whole loop. A pseudo whose address is
+/// taken can be read or written through a pointer anywhere, so it occupies
+/// every point.
+fn live_ranges(instrs: &[InstructionAsm]) -> HashMap<Symbol, (usize, usize)> {
+    let labels: HashMap<&String, usize> = instrs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, instr)| match instr {
+            InstructionAsm::Label { name } => Some((name, i)),
+            _ => None,
+        })
+        .collect();
+    let succs: Vec<Vec<usize>> = instrs
+        .iter()
+        .enumerate()
+        .map(|(i, instr)| match instr {
+            InstructionAsm::Ret | InstructionAsm::TailCall { .. } => vec![],
+            InstructionAsm::Jmp { target } => vec![labels[target]],
+            InstructionAsm::JmpCC { target, .. } => vec![labels[target], i + 1],
+            _ => vec![i + 1],
+        })
+        .map(|s| s.into_iter().filter(|i| *i < instrs.len()).collect())
+        .collect();
+    // pseudos numbered in order of first appearance, so live sets can be small
+    // sorted vectors of indices rather than hash sets of names
+    let mut names: Vec<Symbol> = Vec::new();
+    let mut index: HashMap<Symbol, usize> = HashMap::new();
+    let mut number = |names_used: Vec<Symbol>| -> Vec<usize> {
+        let mut ids: Vec<usize> = names_used
+            .into_iter()
+            .map(|name| {
+                *index.entry(name).or_insert_with(|| {
+                    names.push(name);
+                    names.len() - 1
+                })
+            })
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    };
+    let uses_defs: Vec<(Vec<usize>, Vec<usize>)> = instrs
+        .iter()
+        .map(|instr| {
+            let (uses, defs) = pseudo_uses_defs(instr);
+            (number(uses), number(defs))
+        })
+        .collect();
+    let addressed = number(
+        instrs
+            .iter()
+            .filter_map(|instr| match instr {
+                InstructionAsm::Lea {
+                    src: OperandAsm::Pseudo { name },
+                    ..
+                } => Some(*name),
+                _ => None,
+            })
+            .collect(),
+    );
+    // with no edge back to an earlier instruction, one backward pass is enough
+    let has_back_edge = succs
+        .iter()
+        .enumerate()
+        .any(|(i, s)| s.iter().any(|succ| *succ <= i));
+
+    let mut live_in: Vec<Vec<usize>> = vec![Vec::new(); instrs.len()];
+    let mut live_out: Vec<Vec<usize>> = vec![Vec::new(); instrs.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (0..instrs.len()).rev() {
+            let mut out: Vec<usize> = succs[i]
+                .iter()
+                .flat_map(|s| live_in[*s].iter().copied())
+                .collect();
+            if succs[i].len() > 1 {
+                out.sort_unstable();
+                out.dedup();
+            }
+            let (uses, defs) = &uses_defs[i];
+            let mut inn: Vec<usize> = out
+                .iter()
+                .filter(|id| defs.binary_search(id).is_err())
+                .chain(uses.iter())
+                .copied()
+                .collect();
+            inn.sort_unstable();
+            inn.dedup();
+            if inn != live_in[i] {
+                live_in[i] = inn;
+                changed = has_back_edge;
+            }
+            live_out[i] = out;
+        }
+    }
+
+    let mut ranges: Vec<Option<(usize, usize)>> = vec![None; names.len()];
+    let mut occupy = |id: usize, point: usize| {
+        let range = ranges[id].get_or_insert((point, point));
+        range.0 = range.0.min(point);
+        range.1 = range.1.max(point);
+    };
+    for i in 0..instrs.len() {
+        live_in[i].iter().for_each(|id| occupy(*id, 2 * i));
+        live_out[i].iter().for_each(|id| occupy(*id, 2 * i + 1));
+        uses_defs[i].1.iter().for_each(|id| occupy(*id, 2 * i + 1));
+    }
+    for id in addressed.into_iter() {
+        occupy(id, 0);
+        occupy(id, 2 * instrs.len() - 1);
+    }
+    names
+        .into_iter()
+        .zip(ranges)
+        .filter_map(|(name, range)| Some((name, range?)))
+        .collect()
+}
+
+/// translates each TACKY instruction behind a comment showing it.
+/// Comments pass through the later stages untouched, so they stay in front of the
+/// instructions generated for them even as those get expanded or rewritten.
+fn translate_with_comments(
+    tacky_instrs: Vec<InstructionTacky>,
+    target: Target,
+    params_on_stack: usize,
+) -> Result<Vec<InstructionAsm>, CodegenError> {
+    let mut res = Vec::with_capacity(tacky_instrs.len() * 3);
+    for i in tacky_instrs.into_iter() {
+        res.push(InstructionAsm::Comment {
+            text: i.to_string(),
+        });
+        translate_instr(i, target, params_on_stack, &mut res)?;
+    }
+    Ok(res)
+}
+
+fn translate_with_pseudo(
+    tacky_instrs: Vec<InstructionTacky>,
+    target: Target,
+    params_on_stack: usize,
+) -> Result<Vec<InstructionAsm>, CodegenError> {
+    let mut res = Vec::with_capacity(tacky_instrs.len() * 2);
+    for i in tacky_instrs.into_iter() {
+        translate_instr(i, target, params_on_stack, &mut res)?;
+    }
+    Ok(res)
+}
+
+/// replaces each branch choosing which of two values ends up in the same place
+/// with a conditional move. That is the shape a conditional expression whose arms
+/// are already computed lowers to:
+/// ```text
+///     cmpl $0, cond           cmpl $0, cond
+///     jcc else                movl a, dst
+///     movl a, dst        =>   cmovccl b, dst
+///     jmp end               end:
+///   else:
+///     movl b, dst
+///   end:
+/// ```
+/// Both moves are side-effect free, so doing the first one unconditionally changes
+/// nothing but `dst`, which the second one then overwrites if the branch would have
+/// been taken. The `else` label must be reached from this jump alone, and `b` can't
+/// be `dst`, which the first move has already changed. There is a `cmov` for longwords
+/// and quadwords only. `end` stays for any other jumps to it, and comments and
+/// line markers in between are kept, ahead of the new instructions.
+fn select_conditional_moves(instrs: Vec<InstructionAsm>) -> Vec<InstructionAsm> {
+    let mut jumps_to: HashMap<String, usize> = HashMap::new();
+    for instr in instrs.iter() {
+        if let InstructionAsm::Jmp { target } | InstructionAsm::JmpCC { target, .. } = instr {
+            *jumps_to.entry(target.clone()).or_insert(0) += 1;
+        }
+    }
+
+    let is_comment = |i: &InstructionAsm| {
+        matches!(
+            i,
+            InstructionAsm::Comment { .. } | InstructionAsm::Loc { .. }
+        )
+    };
+    let mut res = Vec::with_capacity(instrs.len());
+    let mut i = 0;
+    while i < instrs.len() {
+        let window: Vec<usize> = (i..instrs.len())
+            .filter(|j| !is_comment(&instrs[*j]))
+            .take(7)
+            .collect();
+        let shape: Vec<&InstructionAsm> = window.iter().map(|j| &instrs[*j]).collect();
+        if let Some(moves) = conditional_move(&shape, &jumps_to) {
+            let end = window[6] + 1;
+            res.extend(instrs[i..end].iter().filter(|i| is_comment(i)).cloned());
+            res.extend(moves);
+            i = end;
+        } else {
+            res.push(instrs[i].clone());
+            i += 1;
+        }
+    }
+    res
+}
+
+/// the conditional move for seven instructions making up a branch and join, if
+/// they do.
+fn conditional_move(
+    shape: &[&InstructionAsm],
+    jumps_to: &HashMap<String, usize>,
+) -> Option<[InstructionAsm; 4]> {
+    let [cmp, jcc, first, jmp, else_label, second, end_label] = shape else {
+        return None;
+    };
+    let InstructionAsm::Cmp {
+        ty: cond_ty,
+        src: OperandAsm::Imm { int: 0 },
+        dst: cond,
+    } = cmp
+    else {
+        return None;
+    };
+    let InstructionAsm::JmpCC {
+        cc,
+        target: else_target,
+    } = jcc
+    else {
+        return None;
+    };
+    let InstructionAsm::Mov { ty, src: a, dst } = first else {
+        return None;
+    };
+    let InstructionAsm::Jmp { target: end_target } = jmp else {
+        return None;
+    };
+    let InstructionAsm::Label { name: else_name } = else_label else {
+        return None;
+    };
+    let InstructionAsm::Mov {
+        ty: b_ty,
+        src: b,
+        dst: b_dst,
+    } = second
+    else {
+        return None;
+    };
+    let InstructionAsm::Label { name: end_name } = end_label else {
+        return None;
+    };
+    if else_target != else_name
+        || end_target != end_name
+        || dst != b_dst
+        || ty != b_ty
+        || !matches!(ty, AsmType::Longword | AsmType::Quadword)
+        || b == dst
+        || jumps_to.get(else_target) != Some(&1)
+    {
+        return None;
+    }
+    Some([
+        InstructionAsm::Cmp {
+            ty: *cond_ty,
+            src: OperandAsm::Imm { int: 0 },
+            dst: *cond,
+        },
+        InstructionAsm::Mov {
+            ty: *ty,
+            src: *a,
+            dst: *dst,
+        },
+        InstructionAsm::Cmov {
+            ty: *ty,
+            cc: *cc,
+            src: *b,
+            dst: *dst,
+        },
+        InstructionAsm::Label {
+            name: end_name.clone(),
+        },
+    ])
+}
+
+/// selects the instructions for one TACKY instruction, pushing them onto `res`.
+/// Calls follow `target`'s calling convention; `params_on_stack` is how many of
+/// the function's own parameters its caller passed on the stack.
+fn translate_instr(
+    tacky_instr: InstructionTacky,
+    target: Target,
+    params_on_stack: usize,
+    res: &mut Vec<InstructionAsm>,
+) -> Result<(), CodegenError> {
+    match tacky_instr {
+        InstructionTacky::FunCall { name, args, dst } => {
+            translate_call(name, args, dst, target, res)
+        }
+        InstructionTacky::TailCall { name, args } => {
+            translate_tail_call(name, args, params_on_stack, target, res)
+        }
+        InstructionTacky::Ret { v } => res.extend([
+            InstructionAsm::Mov {
+                ty: v.ty().into(),
+                src: translate_valtacky(v.clone()),
+                dst: OperandAsm::Reg {
+                    r: return_register(v.ty()),
+                },
+            },
+            InstructionAsm::Ret,
+        ]),
+        InstructionTacky::Copy { src, dst }
+            if (src.ty().is_struct() || src.ty().is_array()) && src.ty() == dst.ty() =>
+        {
+            let size = src.ty().size();
+            res.extend([
+                InstructionAsm::Lea {
+                    src: translate_valtacky(src),
+                    dst: OperandAsm::Reg { r: Register::AX },
+                },
+                InstructionAsm::Lea {
+                    src: translate_valtacky(dst),
+                    dst: OperandAsm::Reg { r: Register::DX },
+                },
+            ]);
+            copy_struct(size, res);
+        }
+        InstructionTacky::Copy { src, dst } => res.push(InstructionAsm::Mov {
+            ty: src.ty().into(),
+            src: translate_valtacky(src),
+            dst: translate_valtacky(dst),
+        }),
+        InstructionTacky::SignExtend { src, dst } => res.push(InstructionAsm::Movsx {
+            src_ty: src.ty().into(),
+            dst_ty: dst.ty().into(),
+            src: translate_valtacky(src),
+            dst: translate_valtacky(dst),
+        }),
+        // the low bytes of a value are the narrower one it truncates to
+        InstructionTacky::Truncate { src, dst } => res.push(InstructionAsm::Mov {
+            ty: dst.ty().into(),
+            src: translate_valtacky(src),
+            dst: translate_valtacky(dst),
+        }),
+        // a longword move clears the upper half of its register, so a round trip
+        // through %eax zero-extends a longword
+        InstructionTacky::ZeroExtend { src, dst } if src.ty().size() == 4 => res.extend([
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: translate_valtacky(src),
+                dst: OperandAsm::Reg { r: Register::AX },
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: OperandAsm::Reg { r: Register::AX },
+                dst: translate_valtacky(dst),
+            },
+        ]),
+        InstructionTacky::ZeroExtend { src, dst } => res.push(InstructionAsm::Movzx {
+            src_ty: src.ty().into(),
+            dst_ty: dst.ty().into(),
+            src: translate_valtacky(src),
+            dst: translate_valtacky(dst),
+        }),
+        InstructionTacky::DoubleToInt { src, dst } => res.push(InstructionAsm::Cvttsd2si {
+            ty: dst.ty().into(),
+            src: translate_valtacky(src),
+            dst: translate_valtacky(dst),
+        }),
+        InstructionTacky::IntToDouble { src, dst } => res.push(InstructionAsm::Cvtsi2sd {
+            ty: src.ty().into(),
+            src: translate_valtacky(src),
+            dst: translate_valtacky(dst),
+        }),
+        InstructionTacky::Jump { target } => res.push(InstructionAsm::Jmp { target }),
+        // a NaN compares equal to zero as well as unordered, and only the parity
+        // flag tells the two apart
+        InstructionTacky::JumpIfZero { condition, target } if condition.ty() == CType::Double => {
+            let ax = OperandAsm::Reg { r: Register::AX };
+            compare_to_zero(condition, res);
+            set_cc(CondCode::E, AsmType::Double, ax, res);
+            res.extend([
+                InstructionAsm::Cmp {
+                    ty: AsmType::Byte,
+                    src: OperandAsm::Imm { int: 0 },
+                    dst: ax,
+                },
+                InstructionAsm::JmpCC {
+                    cc: CondCode::NE,
+                    target,
+                },
+            ])
+        }
+        InstructionTacky::JumpIfNotZero { condition, target }
+            if condition.ty() == CType::Double =>
+        {
+            compare_to_zero(condition, res);
+            res.extend([
+                InstructionAsm::JmpCC {
+                    cc: CondCode::NE,
+                    target: target.clone(),
+                },
+                InstructionAsm::JmpCC {
+                    cc: CondCode::P,
+                    target,
+                },
+            ])
+        }
+        InstructionTacky::JumpIfZero { condition, target } => {
+            compare_to_zero(condition, res);
+            res.push(InstructionAsm::JmpCC {
+                cc: CondCode::E,
+                target,
+            })
+        }
+        InstructionTacky::JumpIfNotZero { condition, target } => {
+            compare_to_zero(condition, res);
+            res.push(InstructionAsm::JmpCC {
+                cc: CondCode::NE,
+                target,
+            })
+        }
+        InstructionTacky::Label { name } => res.push(InstructionAsm::Label { name }),
+        InstructionTacky::Loc { file, line, col } => {
+            res.push(InstructionAsm::Loc { file, line, col })
+        }
+        InstructionTacky::GetAddress { src, dst } => res.push(InstructionAsm::Lea {
+            src: translate_valtacky(src),
+            dst: translate_valtacky(dst),
+        }),
+        InstructionTacky::AddPtr {
+            ptr,
+            index,
+            scale,
+            dst,
+        } => translate_add_ptr(ptr, index, scale, dst, res),
+        InstructionTacky::Load { src_ptr, dst } if dst.ty().is_struct() => {
+            let size = dst.ty().size();
+            res.extend([
+                InstructionAsm::Mov {
+                    ty: AsmType::Quadword,
+                    src: translate_valtacky(src_ptr),
+                    dst: OperandAsm::Reg { r: Register::AX },
+                },
+                InstructionAsm::Lea {
+                    src: translate_valtacky(dst),
+                    dst: OperandAsm::Reg { r: Register::DX },
+                },
+            ]);
+            copy_struct(size, res);
+        }
+        InstructionTacky::Store { src, dst_ptr } if src.ty().is_struct() => {
+            let size = src.ty().size();
+            res.extend([
+                InstructionAsm::Lea {
+                    src: translate_valtacky(src),
+                    dst: OperandAsm::Reg { r: Register::AX },
+                },
+                InstructionAsm::Mov {
+                    ty: AsmType::Quadword,
+                    src: translate_valtacky(dst_ptr),
+                    dst: OperandAsm::Reg { r: Register::DX },
+                },
+            ]);
+            copy_struct(size, res);
+        }
+        // the pointer goes through %rax, which no value is kept in between instructions
+        InstructionTacky::Load { src_ptr, dst } => res.extend([
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: translate_valtacky(src_ptr),
+                dst: OperandAsm::Reg { r: Register::AX },
+            },
+            InstructionAsm::Mov {
+                ty: dst.ty().into(),
+                src: OperandAsm::Memory {
+                    base: Register::AX,
+                    off: 0,
+                },
+                dst: translate_valtacky(dst),
+            },
+        ]),
+        InstructionTacky::Store { src, dst_ptr } => res.extend([
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: translate_valtacky(dst_ptr),
+                dst: OperandAsm::Reg { r: Register::AX },
+            },
+            InstructionAsm::Mov {
+                ty: src.ty().into(),
+                src: translate_valtacky(src),
+                dst: OperandAsm::Memory {
+                    base: Register::AX,
+                    off: 0,
+                },
+            },
+        ]),
+        InstructionTacky::Unary { op, src, dst } => {
+            let ty = src.ty().into();
+            let src = translate_valtacky(src);
+            let dst = translate_valtacky(dst);
+            // negating a `double` flips its sign bit
+            if ty == AsmType::Double {
+                res.extend([
+                    InstructionAsm::Mov { ty, src, dst },
+                    InstructionAsm::Binary {
+                        ty: AsmType::Quadword,
+                        binop: AsmBinaryOp::Xor,
+                        src: OperandAsm::Imm { int: i64::MIN },
+                        dst,
+                    },
+                ]);
+                return Ok(());
+            }
+            res.extend([
+                InstructionAsm::Mov { ty, src, dst },
+                InstructionAsm::Unary {
+                    ty,
+                    unop: AsmUnaryOp::from(&op),
+                    operand: dst,
+                },
+            ])
+        }
+        InstructionTacky::Binary {
+            op,
+            src1,
+            src2,
+            dst,
+        } => {
+            // the operands' type; a comparison's result is an int whatever they are,
+            // but for the `_Bool` a conversion to one sets directly.
+            // comisd sets the flags the way an unsigned comparison does
+            let signed = src1.ty().is_signed();
+            let ty = src1.ty().into();
+            let to_bool = dst.ty() == CType::Bool;
+            let src1 = translate_valtacky(src1);
+            let src2 = translate_valtacky(src2);
+            let dst = translate_valtacky(dst);
+            if let Some(reduced) = reduce_strength(&op, ty, signed, src1, src2, dst) {
+                res.extend(reduced);
+                return Ok(());
+            }
+            if let Some(cc) = CondCode::of(&op, signed) {
+                // an unordered comparison sets CF as well as ZF and PF, so `<` and
+                // `<=` on doubles are `>` and `>=` the other way round, which a NaN
+                // leaves false
+                let (cc, src1, src2) = match cc {
+                    CondCode::B if ty == AsmType::Double => (CondCode::A, src2, src1),
+                    CondCode::BE if ty == AsmType::Double => (CondCode::AE, src2, src1),
+                    _ => (cc, src1, src2),
+                };
+                res.extend([InstructionAsm::Cmp {
+                    ty,
+                    src: src2,
+                    dst: src1,
+                }]);
+                if to_bool {
+                    set_cc(cc, ty, dst, res);
+                    return Ok(());
+                }
+                set_cc(cc, ty, OperandAsm::Reg { r: Register::AX }, res);
+                res.extend([InstructionAsm::Movzx {
+                    src_ty: AsmType::Byte,
+                    dst_ty: AsmType::Longword,
+                    src: OperandAsm::Reg { r: Register::AX },
+                    dst,
+                }]);
+                return Ok(());
+            }
+            match op {
+                BinaryOp::Divide if ty == AsmType::Double => res.extend([
+                    InstructionAsm::Mov { ty, src: src1, dst },
+                    InstructionAsm::Binary {
+                        ty,
+                        binop: AsmBinaryOp::DivDouble,
+                        src: src2,
+                        dst,
+                    },
+                ]),
+                BinaryOp::Divide | BinaryOp::Remainder => {
+                    // the quotient is left in %eax and the remainder in %edx
+                    let result = if op == BinaryOp::Divide {
+                        Register::AX
+                    } else {
+                        Register::DX
+                    };
+                    res.push(InstructionAsm::Mov {
+                        ty,
+                        src: src1,
+                        dst: OperandAsm::Reg { r: Register::AX },
+                    });
+                    if signed {
+                        res.extend([
+                            InstructionAsm::Cdq { ty },
+                            InstructionAsm::Idiv { ty, operand: src2 },
+                        ]);
+                    } else {
+                        res.extend([
+                            InstructionAsm::Mov {
+                                ty,
+                                src: OperandAsm::Imm { int: 0 },
+                                dst: OperandAsm::Reg { r: Register::DX },
+                            },
+                            InstructionAsm::Div { ty, operand: src2 },
+                        ]);
+                    }
+                    res.push(InstructionAsm::Mov {
+                        ty,
+                        src: OperandAsm::Reg { r: result },
+                        dst,
+                    });
+                }
+                BinaryOp::LeftShift | BinaryOp::RightShift => {
+                    // a constant count is masked as the instruction masks one
+                    // in a register, so that it fits in its byte
+                    let binop = match (&op, signed) {
+                        (BinaryOp::RightShift, true) => AsmBinaryOp::Sar,
+                        (BinaryOp::RightShift, false) => AsmBinaryOp::Shr,
+                        _ => AsmBinaryOp::try_from(&op)?,
+                    };
+                    let src = match src2 {
+                        OperandAsm::Imm { int } => OperandAsm::Imm {
+                            int: int & i64::from(8 * ty.size() - 1),
+                        },
+                        _ => src2,
+                    };
+                    res.extend([
+                        InstructionAsm::Mov { ty, src: src1, dst },
+                        InstructionAsm::Binary {
+                            ty,
+                            binop,
+                            src,
+                            dst,
+                        },
+                    ])
+                }
+                _ => res.extend([
+                    InstructionAsm::Mov { ty, src: src1, dst },
+                    InstructionAsm::Binary {
+                        ty,
+                        binop: AsmBinaryOp::try_from(&op)?,
+                        src: src2,
+                        dst,
+                    },
+                ]),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// moves `ptr` along `index` elements of `scale` bytes into `dst` with a `lea`
+/// from %rax: by a displacement if the index is constant, or with the index in
+/// %rdx, scaled by the `lea` itself if it can be, and multiplied first if not.
+fn translate_add_ptr(
+    ptr: ValTacky,
+    index: ValTacky,
+    scale: usize,
+    dst: ValTacky,
+    res: &mut Vec<InstructionAsm>,
+) {
+    let (ax, dx) = (Register::AX, Register::DX);
+    res.push(InstructionAsm::Mov {
+        ty: AsmType::Quadword,
+        src: translate_valtacky(ptr),
+        dst: OperandAsm::Reg { r: ax },
+    });
+    let off = match &index {
+        ValTacky::Const { c } => i64::try_from(scale)
+            .ok()
+            .and_then(|scale| c.value().checked_mul(scale))
+            .and_then(|off| i32::try_from(off).ok()),
+        ValTacky::TmpVar { .. } => None,
+    };
+    let src = match off {
+        Some(off) => OperandAsm::Memory { base: ax, off },
+        None => {
+            res.push(InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: translate_valtacky(index),
+                dst: OperandAsm::Reg { r: dx },
+            });
+            match scale {
+                1 | 2 | 4 | 8 => OperandAsm::Indexed {
+                    base: ax,
+                    index: dx,
+                    scale: scale as i32,
+                },
+                _ => {
+                    res.push(InstructionAsm::Binary {
+                        ty: AsmType::Quadword,
+                        binop: AsmBinaryOp::Imul,
+                        src: OperandAsm::Imm { int: scale as i64 },
+                        dst: OperandAsm::Reg { r: dx },
+                    });
+                    OperandAsm::Indexed {
+                        base: ax,
+                        index: dx,
+                        scale: 1,
+                    }
+                }
+            }
+        }
+    };
+    res.push(InstructionAsm::Lea {
+        src,
+        dst: translate_valtacky(dst),
+    });
+}
+
+/// copies the `size` bytes of a structure from where %rax points to where
+/// %rdx points, eight at a time, then what is left in the widest moves that
+/// fit. Both pointers are taken before any byte is moved, so nothing else has
+/// to hold a value in between.
+fn copy_struct(size: usize, res: &mut Vec<InstructionAsm>) {
+    let mut off = 0;
+    for ty in [
+        AsmType::Quadword,
+        AsmType::Longword,
+        AsmType::Word,
+        AsmType::Byte,
+    ] {
+        while size as i32 - off >= ty.size() {
+            res.push(InstructionAsm::Mov {
+                ty,
+                src: OperandAsm::Memory {
+                    base: Register::AX,
+                    off,
+                },
+                dst: OperandAsm::Memory {
+                    base: Register::DX,
+                    off,
+                },
+            });
+            off += ty.size();
+        }
+    }
+}
+
+/// sets the flags by comparing `condition` against zero. A `double` is compared
+/// against %xmm0, cleared by xoring it with itself, as no SSE instruction takes
+/// an immediate.
+fn compare_to_zero(condition: ValTacky, res: &mut Vec<InstructionAsm>) {
+    if condition.ty() == CType::Double {
+        let xmm0 = OperandAsm::Reg { r: Register::XMM0 };
+        res.extend([
+            InstructionAsm::Binary {
+                ty: AsmType::Double,
+                binop: AsmBinaryOp::Xor,
+                src: xmm0,
+                dst: xmm0,
+            },
+            InstructionAsm::Cmp {
+                ty: AsmType::Double,
+                src: translate_valtacky(condition),
+                dst: xmm0,
+            },
+        ]);
+    } else {
+        res.push(InstructionAsm::Cmp {
+            ty: condition.ty().into(),
+            src: OperandAsm::Imm { int: 0 },
+            dst: translate_valtacky(condition),
+        });
+    }
+}
+
+/// sets the byte `dst` to whether `cc` holds after a comparison of `ty`. After
+/// `comisd`, `e` also needs PF clear and `ne` takes PF set, since a NaN is unequal
+/// to everything; the two flags are combined in %eax and %edx.
+fn set_cc(cc: CondCode, ty: AsmType, dst: OperandAsm, res: &mut Vec<InstructionAsm>) {
+    let (parity, binop) = match cc {
+        CondCode::E if ty == AsmType::Double => (CondCode::NP, AsmBinaryOp::And),
+        CondCode::NE if ty == AsmType::Double => (CondCode::P, AsmBinaryOp::Or),
+        _ => {
+            res.push(InstructionAsm::SetCC { cc, operand: dst });
+            return;
+        }
+    };
+    let ax = OperandAsm::Reg { r: Register::AX };
+    let dx = OperandAsm::Reg { r: Register::DX };
+    res.extend([
+        InstructionAsm::SetCC { cc, operand: ax },
+        InstructionAsm::SetCC {
+            cc: parity,
+            operand: dx,
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop,
+            src: dx,
+            dst: ax,
+        },
+    ]);
+    if dst != ax {
+        res.push(InstructionAsm::Mov {
+            ty: AsmType::Byte,
+            src: ax,
+            dst,
+        });
+    }
+}
+
+/// the register a value of type `ty` is returned in.
+fn return_register(ty: CType) -> Register {
+    match ty {
+        CType::Double => Register::XMM0,
+        _ => Register::AX,
+    }
+}
+
+/// passes `args` in `target`'s argument registers and then on the stack, last
+/// first, calls `name` and copies its result from %rax, or %xmm0 for a `double`,
+/// into `dst`, each at the size of its own type.
+/// %rsp is 16-byte aligned after the prologue, so a padding slot keeps it that
+/// way at the call when an odd number of arguments is pushed. Every value lives
+/// in a stack slot between instructions, so no caller-saved register holds
+/// anything the call could clobber, and none need saving around it.
+fn translate_call(
+    name: Symbol,
+    args: Vec<ValTacky>,
+    dst: ValTacky,
+    target: Target,
+    res: &mut Vec<InstructionAsm>,
+) {
+    let pushed = pass_args(&args, target, res);
+    res.push(InstructionAsm::Call { name });
+    if pushed != 0 {
+        res.push(InstructionAsm::DeallocStack { off: -pushed });
+    }
+    res.push(InstructionAsm::Mov {
+        ty: dst.ty().into(),
+        src: OperandAsm::Reg {
+            r: return_register(dst.ty()),
+        },
+        dst: translate_valtacky(dst),
+    });
+}
+
+/// calls `name` and returns what it returns, by jumping to it once this
+/// function's frame is gone. Its stack arguments are written over this
+/// function's own, where its caller passed them, so the callee finds them just
+/// above the return address it will use. A callee taking more stack arguments
+/// than that space holds is called as usual instead, and its result, still in
+/// %rax or %xmm0, returned as it is.
+fn translate_tail_call(
+    name: Symbol,
+    args: Vec<ValTacky>,
+    params_on_stack: usize,
+    target: Target,
+    res: &mut Vec<InstructionAsm>,
+) {
+    let on_stack = stack_args(&args, target);
+    if on_stack.len() > params_on_stack {
+        let pushed = pass_args(&args, target, res);
+        res.push(InstructionAsm::Call { name });
+        if pushed != 0 {
+            res.push(InstructionAsm::DeallocStack { off: -pushed });
+        }
+        res.push(InstructionAsm::Ret);
+        return;
+    }
+    for (i, arg) in on_stack.iter().enumerate() {
+        res.extend([
+            load_arg(arg, Register::AX),
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: OperandAsm::Reg { r: Register::AX },
+                dst: OperandAsm::Stack {
+                    off: 16 + target.shadow_space() + 8 * i as i32,
+                },
+            },
+        ]);
+    }
+    let doubles: Vec<bool> = args.iter().map(|a| a.ty() == CType::Double).collect();
+    for (arg, r) in args.iter().zip(target.arg_registers(&doubles)) {
+        if let Some(r) = r {
+            res.push(load_arg(arg, r));
+        }
+    }
+    res.push(InstructionAsm::TailCall { name });
+}
+
+/// the values of `args` that `target` passes on the stack rather than in
+/// registers, first to last.
+fn stack_args(args: &[ValTacky], target: Target) -> Vec<&ValTacky> {
+    let doubles: Vec<bool> = args.iter().map(|a| a.ty() == CType::Double).collect();
+    args.iter()
+        .zip(target.arg_registers(&doubles))
+        .filter_map(|(arg, r)| r.is_none().then_some(arg))
+        .collect()
+}
+
+/// sets up `args` for a call, as `translate_call` describes, returning how
+/// many bytes it moved %rsp down by.
+fn pass_args(args: &[ValTacky], target: Target, res: &mut Vec<InstructionAsm>) -> i32 {
+    let stack_args = stack_args(args, target);
+    let padding = if stack_args.len() % 2 == 1 { 8 } else { 0 };
+    if padding != 0 {
+        res.push(InstructionAsm::AllocStack { off: -padding });
+    }
+    // pushes move all 8 bytes, so the argument goes through a register
+    for arg in stack_args.iter().rev() {
+        res.extend([
+            load_arg(arg, Register::AX),
+            InstructionAsm::Push { r: Register::AX },
+        ]);
+    }
+    let doubles: Vec<bool> = args.iter().map(|a| a.ty() == CType::Double).collect();
+    for (arg, r) in args.iter().zip(target.arg_registers(&doubles)) {
+        if let Some(r) = r {
+            res.push(load_arg(arg, r));
+        }
+    }
+    if target.shadow_space() != 0 {
+        res.push(InstructionAsm::AllocStack {
+            off: -target.shadow_space(),
+        });
+    }
+    padding + 8 * stack_args.len() as i32 + target.shadow_space()
+}
+
+/// the instruction loading the argument `arg` into `r`, a `double`'s bits as
+/// they are if `r` is a general-purpose register. One of a type narrower than
+/// `int` is extended to 32 bits, which the ABI doesn't ask for but clang's
+/// code counts on.
+fn load_arg(arg: &ValTacky, r: Register) -> InstructionAsm {
+    let (src, dst) = (translate_valtacky(arg.clone()), OperandAsm::Reg { r });
+    match arg.ty() {
+        ty if ty.size() < 4 && ty.is_signed() => InstructionAsm::Movsx {
+            src_ty: ty.into(),
+            dst_ty: AsmType::Longword,
+            src,
+            dst,
+        },
+        ty if ty.size() < 4 => InstructionAsm::Movzx {
+            src_ty: ty.into(),
+            dst_ty: AsmType::Longword,
+            src,
+            dst,
+        },
+        CType::Double if !r.is_xmm() => InstructionAsm::Mov {
+            ty: AsmType::Quadword,
+            src,
+            dst,
+        },
+        ty => InstructionAsm::Mov {
+            ty: ty.into(),
+            src,
+            dst,
+        },
+    }
+}
+
+/// Replaces multiplication, division, and remainder by a positive power of two
+/// with shifts. Signed division must round toward zero, so negative dividends are
+/// biased by `2^k - 1` before shifting: the bias is built from the sign bit by an
+/// arithmetic shift by one less than the width of `ty` followed by a logical shift
+/// by the width less `k`. Unsigned division needs no bias: it is a logical shift,
+/// and the remainder a mask.
+/// Returns `None` when the operation has no cheaper form.
+fn reduce_strength(
+    op: &BinaryOp,
+    ty: AsmType,
+    signed: bool,
+    src1: OperandAsm,
+    src2: OperandAsm,
+    dst: OperandAsm,
+) -> Option<Vec<InstructionAsm>> {
+    let ax = OperandAsm::Reg { r: Register::AX };
+    let dx = OperandAsm::Reg { r: Register::DX };
+    let bits = i64::from(8 * ty.size());
+    let shift = |binop, k: i64, dst| InstructionAsm::Binary {
+        ty,
+        binop,
+        src: OperandAsm::Imm { int: k },
+        dst,
+    };
+    // leaves `2^k - 1` in %rdx if %rax is negative, and 0 otherwise
+    let bias = |k: i64| {
+        [
+            InstructionAsm::Mov {
+                ty,
+                src: ax,
+                dst: dx,
+            },
+            shift(AsmBinaryOp::Sar, bits - 1, dx),
+            shift(AsmBinaryOp::Shr, bits - k, dx),
+        ]
+    };
+
+    match op {
+        BinaryOp::Multiply => {
+            let (k, other) = match (power_of_two(src1), power_of_two(src2)) {
+                (_, Some(k)) => (k, src1),
+                (Some(k), None) => (k, src2),
+                (None, None) => return None,
+            };
+            let mut res = vec![InstructionAsm::Mov {
+                ty,
+                src: other,
+                dst,
+            }];
+            if k != 0 {
+                res.push(shift(AsmBinaryOp::Shl, k, dst));
+            }
+            Some(res)
+        }
+        BinaryOp::Divide => {
+            let k = power_of_two(src2)?;
+            if k == 0 {
+                return Some(vec![InstructionAsm::Mov { ty, src: src1, dst }]);
+            }
+            if !signed {
+                return Some(vec![
+                    InstructionAsm::Mov { ty, src: src1, dst },
+                    shift(AsmBinaryOp::Shr, k, dst),
+                ]);
+            }
+            let mut res = vec![InstructionAsm::Mov {
+                ty,
+                src: src1,
+                dst: ax,
+            }];
+            res.extend(bias(k));
+            res.extend([
+                InstructionAsm::Binary {
+                    ty,
+                    binop: AsmBinaryOp::Add,
+                    src: dx,
+                    dst: ax,
+                },
+                shift(AsmBinaryOp::Sar, k, ax),
+                InstructionAsm::Mov { ty, src: ax, dst },
+            ]);
+            Some(res)
+        }
+        BinaryOp::Remainder => {
+            let k = power_of_two(src2)?;
+            if k == 0 {
+                return Some(vec![InstructionAsm::Mov {
+                    ty,
+                    src: OperandAsm::Imm { int: 0 },
+                    dst,
+                }]);
+            }
+            if !signed {
+                return Some(vec![
+                    InstructionAsm::Mov { ty, src: src1, dst },
+                    InstructionAsm::Binary {
+                        ty,
+                        binop: AsmBinaryOp::And,
+                        src: OperandAsm::Imm { int: (1 << k) - 1 },
+                        dst,
+                    },
+                ]);
+            }
+            // x % 2^k == ((x + bias) & (2^k - 1)) - bias
+            let mut res = vec![InstructionAsm::Mov {
+                ty,
+                src: src1,
+                dst: ax,
+            }];
+            res.extend(bias(k));
+            res.extend([
+                InstructionAsm::Binary {
+                    ty,
+                    binop: AsmBinaryOp::Add,
+                    src: dx,
+                    dst: ax,
+                },
+                InstructionAsm::Binary {
+                    ty,
+                    binop: AsmBinaryOp::And,
+                    src: OperandAsm::Imm { int: (1 << k) - 1 },
+                    dst: ax,
+                },
+                InstructionAsm::Binary {
+                    ty,
+                    binop: AsmBinaryOp::Sub,
+                    src: dx,
+                    dst: ax,
+                },
+                InstructionAsm::Mov { ty, src: ax, dst },
+            ]);
+            Some(res)
+        }
+        _ => None,
+    }
+}
+
+/// `k` if the operand is the constant `2^k`.
+fn power_of_two(operand: OperandAsm) -> Option<i64> {
+    match operand {
+        OperandAsm::Imm { int } if int > 0 && (int & (int - 1)) == 0 => {
+            Some(i64::from(int.trailing_zeros()))
+        }
+        _ => None,
+    }
+}
+
+/// no instruction takes a `double` as an immediate, so a `double` constant is
+/// read from the read-only data its name in `double_constant` refers to.
+fn translate_valtacky(tval: ValTacky) -> OperandAsm {
+    match tval {
+        ValTacky::Const {
+            c: c @ Const::Double { .. },
+        } => OperandAsm::Data {
+            name: double_constant(c),
+        },
+        ValTacky::Const { c } => OperandAsm::Imm { int: c.value() },
+        ValTacky::TmpVar { name, .. } => OperandAsm::Pseudo { name },
+    }
+}
+
+/// the name of the read-only constant holding a `double`, after its bits, so
+/// the same value is only stored once. Its leading `.` keeps it apart from any
+/// C identifier, and marks it local to the file.
+fn double_constant(c: Const) -> Symbol {
+    Symbol::intern(&format!(".double.{:x}", c.value()))
+}
+
+/// the pseudo for the generated temporary `tmp.<no>`.
+#[cfg(test)]
+fn pseudo(no: u32) -> OperandAsm {
+    OperandAsm::Pseudo {
+        name: Symbol::numbered("tmp", no),
+    }
+}
+
+/// the instructions without their CFI directives, for tests about the code itself.
+#[cfg(test)]
+fn without_cfi(instrs: Vec<InstructionAsm>) -> Vec<InstructionAsm> {
+    instrs
+        .into_iter()
+        .filter(|i| !matches!(i, InstructionAsm::Cfi { .. }))
+        .collect()
+}
+
+#[test]
+fn prologue_epilogue_without_callee_saved() {
+    let body = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Imm { int: 2 },
+            dst: OperandAsm::Stack { off: -4 },
+        },
+        InstructionAsm::Ret,
+    ];
+    assert_eq!(
+        add_prologue_epilogue(body, -4, CodegenOptions::default()),
+        vec![
+            cfi(CfiDirective::StartProc),
+            InstructionAsm::Push { r: Register::BP },
+            cfi(CfiDirective::DefCfaOffset { off: 16 }),
+            cfi(CfiDirective::Offset {
+                r: Register::BP,
+                off: -16
+            }),
+            InstructionAsm::MovQ {
+                src: Register::SP,
+                dst: Register::BP
+            },
+            cfi(CfiDirective::DefCfaRegister { r: Register::BP }),
+            InstructionAsm::AllocStack { off: -16 },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 2 },
+                dst: OperandAsm::Stack { off: -4 },
+            },
+            InstructionAsm::MovQ {
+                src: Register::BP,
+                dst: Register::SP
+            },
+            InstructionAsm::Pop { r: Register::BP },
+            cfi(CfiDirective::DefCfa {
+                r: Register::SP,
+                off: 8
+            }),
+            InstructionAsm::Ret,
+            cfi(CfiDirective::EndProc),
+        ]
+    );
+}
+
+/// a body touching %ebx and %r12d saves both after the frame allocation,
+/// restores them in reverse before returning, and pads the 4-byte frame
+/// so that the two 8-byte pushes leave %rsp 16-byte aligned.
+#[test]
+fn prologue_epilogue_saves_callee_saved() {
+    let body = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: -4 },
+            dst: OperandAsm::Reg { r: Register::BX },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Reg { r: Register::BX },
+            dst: OperandAsm::Reg { r: Register::R12 },
+        },
+        InstructionAsm::Ret,
+    ];
+    let res = without_cfi(add_prologue_epilogue(body, -4, CodegenOptions::default()));
+    assert_eq!(
+        res[..5],
+        [
+            InstructionAsm::Push { r: Register::BP },
+            InstructionAsm::MovQ {
+                src: Register::SP,
+                dst: Register::BP
+            },
+            InstructionAsm::AllocStack { off: -16 },
+            InstructionAsm::Push { r: Register::BX },
+            InstructionAsm::Push { r: Register::R12 },
+        ]
+    );
+    assert_eq!(
+        res[res.len() - 5..],
+        [
+            InstructionAsm::Pop { r: Register::R12 },
+            InstructionAsm::Pop { r: Register::BX },
+            InstructionAsm::MovQ {
+                src: Register::BP,
+                dst: Register::SP
+            },
+            InstructionAsm::Pop { r: Register::BP },
+            InstructionAsm::Ret,
+        ]
+    );
+}
+
+/// a conditional jump on a constant compares through a scratch register,
+/// since `cmpl` cannot take an immediate as its second operand.
+#[test]
+fn jump_if_zero_on_constant() {
+    let instrs = translate_with_pseudo(
+        vec![
+            InstructionTacky::JumpIfZero {
+                condition: ValTacky::int(3),
+                target: String::from("skip"),
+            },
+            InstructionTacky::Label {
+                name: String::from("skip"),
+            },
+        ],
+        Target::Linux,
+        0,
+    )
+    .unwrap();
+    assert_eq!(
+        fix_up_instrs(instrs),
+        vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 3 },
+                dst: OperandAsm::Reg { r: Register::R11 },
+            },
+            InstructionAsm::Cmp {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 0 },
+                dst: OperandAsm::Reg { r: Register::R11 },
+            },
+            InstructionAsm::JmpCC {
+                cc: CondCode::E,
+                target: String::from("skip"),
+            },
+            InstructionAsm::Label {
+                name: String::from("skip"),
+            },
+        ]
+    );
+}
+
+/// `imull` writes to a register directly, and only goes through R11 when its
+/// destination is in memory.
+#[test]
+fn fix_up_multiply_operands() {
+    let imm = OperandAsm::Imm { int: 3 };
+    let reg = OperandAsm::Reg { r: Register::AX };
+    let stack = OperandAsm::Stack { off: -4 };
+    let other = OperandAsm::Stack { off: -8 };
+    let r11 = OperandAsm::Reg { r: Register::R11 };
+    let mul = |src, dst| InstructionAsm::Binary {
+        ty: AsmType::Longword,
+        binop: AsmBinaryOp::Imul,
+        src,
+        dst,
+    };
+    let mov = |src, dst| InstructionAsm::Mov {
+        ty: AsmType::Longword,
+        src,
+        dst,
+    };
+    assert_eq!(fix_up_instrs(vec![mul(imm, reg)]), vec![mul(imm, reg)]);
+    assert_eq!(fix_up_instrs(vec![mul(stack, reg)]), vec![mul(stack, reg)]);
+    assert_eq!(
+        fix_up_instrs(vec![mul(other, stack)]),
+        vec![mov(stack, r11), mul(other, r11), mov(r11, stack)]
+    );
+    assert_eq!(
+        fix_up_instrs(vec![mul(imm, stack)]),
+        vec![mov(stack, r11), mul(imm, r11), mov(r11, stack)]
+    );
+}
+
+/// a shift counts by an immediate or by %cl, so any other count is moved
+/// into %ecx first, wherever the value shifted is.
+#[test]
+fn fix_up_shift_count() {
+    let imm = OperandAsm::Imm { int: 3 };
+    let cx = OperandAsm::Reg { r: Register::CX };
+    let stack = OperandAsm::Stack { off: -4 };
+    let other = OperandAsm::Stack { off: -8 };
+    let reg = OperandAsm::Reg { r: Register::AX };
+    let shl = |src, dst| InstructionAsm::Binary {
+        ty: AsmType::Longword,
+        binop: AsmBinaryOp::Shl,
+        src,
+        dst,
+    };
+    let mov = |src, dst| InstructionAsm::Mov {
+        ty: AsmType::Longword,
+        src,
+        dst,
+    };
+    assert_eq!(fix_up_instrs(vec![shl(imm, stack)]), vec![shl(imm, stack)]);
+    assert_eq!(fix_up_instrs(vec![shl(cx, stack)]), vec![shl(cx, stack)]);
+    assert_eq!(
+        fix_up_instrs(vec![shl(other, stack)]),
+        vec![mov(other, cx), shl(cx, stack)]
+    );
+    assert_eq!(
+        fix_up_instrs(vec![shl(reg, other)]),
+        vec![mov(reg, cx), shl(cx, other)]
+    );
+}
+
+/// SSE arithmetic, `comisd` and `cvtsi2sd` write to an XMM register, and
+/// `cvttsd2si` to a general-purpose one, so memory destinations go through scratch
+/// registers, as do `movsd`s between two places in memory.
+#[test]
+fn fix_up_double_operands() {
+    let stack = OperandAsm::Stack { off: -8 };
+    let other = OperandAsm::Stack { off: -16 };
+    let xmm4 = OperandAsm::Reg { r: Register::XMM4 };
+    let xmm5 = OperandAsm::Reg { r: Register::XMM5 };
+    let r10 = OperandAsm::Reg { r: Register::R10 };
+    let r11 = OperandAsm::Reg { r: Register::R11 };
+    let movsd = |src, dst| InstructionAsm::Mov {
+        ty: AsmType::Double,
+        src,
+        dst,
+    };
+    let add = |src, dst| InstructionAsm::Binary {
+        ty: AsmType::Double,
+        binop: AsmBinaryOp::Add,
+        src,
+        dst,
+    };
+    assert_eq!(
+        fix_up_instrs(vec![movsd(other, stack)]),
+        vec![movsd(other, xmm4), movsd(xmm4, stack)]
+    );
+    assert_eq!(
+        fix_up_instrs(vec![add(other, stack)]),
+        vec![movsd(stack, xmm5), add(other, xmm5), movsd(xmm5, stack)]
+    );
+    assert_eq!(
+        fix_up_instrs(vec![add(other, xmm4)]),
+        vec![add(other, xmm4)]
+    );
+    assert_eq!(
+        fix_up_instrs(vec![InstructionAsm::Cmp {
+            ty: AsmType::Double,
+            src: other,
+            dst: stack,
+        }]),
+        vec![
+            movsd(stack, xmm5),
+            InstructionAsm::Cmp {
+                ty: AsmType::Double,
+                src: other,
+                dst: xmm5,
+            },
+        ]
+    );
+    assert_eq!(
+        fix_up_instrs(vec![InstructionAsm::Cvttsd2si {
+            ty: AsmType::Longword,
+            src: other,
+            dst: stack,
+        }]),
+        vec![
+            InstructionAsm::Cvttsd2si {
+                ty: AsmType::Longword,
+                src: other,
+                dst: r11,
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: r11,
+                dst: stack,
+            },
+        ]
+    );
+    assert_eq!(
+        fix_up_instrs(vec![InstructionAsm::Cvtsi2sd {
+            ty: AsmType::Quadword,
+            src: OperandAsm::Imm { int: 1 << 40 },
+            dst: stack,
+        }]),
+        vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: OperandAsm::Imm { int: 1 << 40 },
+                dst: r10,
+            },
+            InstructionAsm::Cvtsi2sd {
+                ty: AsmType::Quadword,
+                src: r10,
+                dst: xmm5,
+            },
+            movsd(xmm5, stack),
+        ]
+    );
+}
+
+/// `movsx` and `movzx` take their source from a register or memory and write a
+/// register, so an immediate goes through R10 and a memory destination through
+/// R11; a byte or word immediate keeps only as many bits as it has.
+#[test]
+fn fix_up_extensions() {
+    let stack = OperandAsm::Stack { off: -8 };
+    let r10 = OperandAsm::Reg { r: Register::R10 };
+    let r11 = OperandAsm::Reg { r: Register::R11 };
+    assert_eq!(
+        fix_up_instrs(vec![InstructionAsm::Movsx {
+            src_ty: AsmType::Byte,
+            dst_ty: AsmType::Quadword,
+            src: OperandAsm::Imm { int: 200 },
+            dst: stack,
+        }]),
+        vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Byte,
+                src: OperandAsm::Imm { int: -56 },
+                dst: r10,
+            },
+            InstructionAsm::Movsx {
+                src_ty: AsmType::Byte,
+                dst_ty: AsmType::Quadword,
+                src: r10,
+                dst: r11,
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: r11,
+                dst: stack,
+            },
+        ]
+    );
+    let movzwl = InstructionAsm::Movzx {
+        src_ty: AsmType::Word,
+        dst_ty: AsmType::Longword,
+        src: stack,
+        dst: r10,
+    };
+    assert_eq!(fix_up_instrs(vec![movzwl.clone()]), vec![movzwl]);
+    assert_eq!(
+        fix_up_instrs(vec![InstructionAsm::Mov {
+            ty: AsmType::Word,
+            src: OperandAsm::Imm { int: 70000 },
+            dst: stack,
+        }]),
+        vec![InstructionAsm::Mov {
+            ty: AsmType::Word,
+            src: OperandAsm::Imm { int: 4464 },
+            dst: stack,
+        }]
+    );
+}
+
+/// frames up to a page take one `subq`; bigger ones touch every page they allocate.
+#[test]
+fn probe_frames_larger_than_a_page() {
+    assert_eq!(allocate_frame(0), vec![]);
+    for frame in [4080, 4095] {
+        assert_eq!(
+            allocate_frame(frame),
+            vec![InstructionAsm::AllocStack { off: -frame }]
+        );
+    }
+    assert_eq!(
+        allocate_frame(STACK_PROBE_INTERVAL),
+        vec![InstructionAsm::AllocStack {
+            off: -STACK_PROBE_INTERVAL
+        }]
+    );
+    let probe = InstructionAsm::Binary {
+        ty: AsmType::Longword,
+        binop: AsmBinaryOp::Or,
+        src: OperandAsm::Imm { int: 0 },
+        dst: OperandAsm::Memory {
+            base: Register::SP,
+            off: 0,
+        },
+    };
+    let page = InstructionAsm::AllocStack {
+        off: -STACK_PROBE_INTERVAL,
+    };
+    assert_eq!(
+        allocate_frame(STACK_PROBE_INTERVAL + 16),
+        vec![
+            page.clone(),
+            probe.clone(),
+            InstructionAsm::AllocStack { off: -16 }
+        ]
+    );
+    assert_eq!(
+        allocate_frame(2 * STACK_PROBE_INTERVAL),
+        vec![page.clone(), probe, page]
+    );
+}
+
+/// a quadword immediate outside the i32 range reaches memory and arithmetic through
+/// R10, while `-2147483648` still fits the sign-extended immediate field.
+#[test]
+fn fix_up_wide_immediates() {
+    let big = OperandAsm::Imm { int: 4294967300 };
+    let min = OperandAsm::Imm {
+        int: i64::from(i32::MIN),
+    };
+    let r10 = OperandAsm::Reg { r: Register::R10 };
+    let ax = OperandAsm::Reg { r: Register::AX };
+    let stack = OperandAsm::Stack { off: -8 };
+    let quad = |src, dst| InstructionAsm::Mov {
+        ty: AsmType::Quadword,
+        src,
+        dst,
+    };
+    let add = |src, dst| InstructionAsm::Binary {
+        ty: AsmType::Quadword,
+        binop: AsmBinaryOp::Add,
+        src,
+        dst,
+    };
+    assert_eq!(fix_up_instrs(vec![quad(big, ax)]), vec![quad(big, ax)]);
+    assert_eq!(
+        fix_up_instrs(vec![quad(big, stack)]),
+        vec![quad(big, r10), quad(r10, stack)]
+    );
+    assert_eq!(
+        fix_up_instrs(vec![add(big, stack)]),
+        vec![quad(big, r10), add(r10, stack)]
+    );
+    assert_eq!(fix_up_instrs(vec![add(min, stack)]), vec![add(min, stack)]);
+    assert_eq!(
+        fix_up_instrs(vec![quad(min, stack)]),
+        vec![quad(min, stack)]
+    );
+}
+
+/// `x * 8` becomes a left shift by 3, whichever side the constant is on.
+#[test]
+fn multiply_by_power_of_two_shifts() {
+    let x = pseudo(0);
+    let dst = pseudo(1);
+    let expected = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: x,
+            dst,
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Shl,
+            src: OperandAsm::Imm { int: 3 },
+            dst,
+        },
+    ];
+    assert_eq!(
+        reduce_strength(
+            &BinaryOp::Multiply,
+            AsmType::Longword,
+            true,
+            x,
+            OperandAsm::Imm { int: 8 },
+            dst
+        ),
+        Some(expected.clone())
+    );
+    assert_eq!(
+        reduce_strength(
+            &BinaryOp::Multiply,
+            AsmType::Longword,
+            true,
+            OperandAsm::Imm { int: 8 },
+            x,
+            dst
+        ),
+        Some(expected)
+    );
+}
+
+#[test]
+fn divide_by_power_of_two_biases_negatives() {
+    let x = pseudo(0);
+    let dst = pseudo(1);
+    let ax = OperandAsm::Reg { r: Register::AX };
+    let dx = OperandAsm::Reg { r: Register::DX };
+    assert_eq!(
+        reduce_strength(
+            &BinaryOp::Divide,
+            AsmType::Longword,
+            true,
+            x,
+            OperandAsm::Imm { int: 4 },
+            dst
+        ),
+        Some(vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: x,
+                dst: ax
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: ax,
+                dst: dx
+            },
+            InstructionAsm::Binary {
+                ty: AsmType::Longword,
+                binop: AsmBinaryOp::Sar,
+                src: OperandAsm::Imm { int: 31 },
+                dst: dx,
+            },
+            InstructionAsm::Binary {
+                ty: AsmType::Longword,
+                binop: AsmBinaryOp::Shr,
+                src: OperandAsm::Imm { int: 30 },
+                dst: dx,
+            },
+            InstructionAsm::Binary {
+                ty: AsmType::Longword,
+                binop: AsmBinaryOp::Add,
+                src: dx,
+                dst: ax,
+            },
+            InstructionAsm::Binary {
+                ty: AsmType::Longword,
+                binop: AsmBinaryOp::Sar,
+                src: OperandAsm::Imm { int: 2 },
+                dst: ax,
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: ax,
+                dst
+            },
+        ])
+    );
+}
+
+#[test]
+fn remainder_by_power_of_two_masks() {
+    let x = pseudo(0);
+    let dst = pseudo(1);
+    let reduced = reduce_strength(
+        &BinaryOp::Remainder,
+        AsmType::Longword,
+        true,
+        x,
+        OperandAsm::Imm { int: 16 },
+        dst,
+    )
+    .unwrap();
+    assert!(reduced.contains(&InstructionAsm::Binary {
+        ty: AsmType::Longword,
+        binop: AsmBinaryOp::And,
+        src: OperandAsm::Imm { int: 15 },
+        dst: OperandAsm::Reg { r: Register::AX },
+    }));
+    assert!(!reduced
+        .iter()
+        .any(|i| matches!(i, InstructionAsm::Idiv { .. })));
+}
+
+/// non-powers of two, negative divisors, and variable divisors keep the general path.
+#[test]
+fn no_strength_reduction_for_other_constants() {
+    let x = pseudo(0);
+    let dst = pseudo(1);
+    for c in [0, 3, 6, -4, i64::from(i32::MIN)] {
+        let c = OperandAsm::Imm { int: c };
+        assert_eq!(
+            reduce_strength(&BinaryOp::Multiply, AsmType::Longword, true, x, c, dst),
+            None
+        );
+        assert_eq!(
+            reduce_strength(&BinaryOp::Divide, AsmType::Longword, true, x, c, dst),
+            None
+        );
+        assert_eq!(
+            reduce_strength(&BinaryOp::Remainder, AsmType::Longword, true, x, c, dst),
+            None
+        );
+    }
+    assert_eq!(
+        reduce_strength(
+            &BinaryOp::Divide,
+            AsmType::Longword,
+            true,
+            OperandAsm::Imm { int: 8 },
+            x,
+            dst
+        ),
+        None
+    );
+    assert_eq!(
+        reduce_strength(
+            &BinaryOp::Add,
+            AsmType::Longword,
+            true,
+            x,
+            OperandAsm::Imm { int: 8 },
+            dst
+        ),
+        None
+    );
+}
+
+/// division and remainder need the `cdq`/`idiv` sequence, so they have no
+/// `AsmBinaryOp` of their own.
+#[test]
+fn division_has_no_single_instruction() {
+    assert_eq!(
+        AsmBinaryOp::try_from(&BinaryOp::Multiply),
+        Ok(AsmBinaryOp::Imul)
+    );
+    assert_eq!(
+        AsmBinaryOp::try_from(&BinaryOp::Divide),
+        Err(CodegenError::NoSingleInstruction {
+            op: BinaryOp::Divide
+        })
+    );
+    assert_eq!(
+        AsmBinaryOp::try_from(&BinaryOp::Remainder),
+        Err(CodegenError::NoSingleInstruction {
+            op: BinaryOp::Remainder
+        })
+    );
+}
+
+/// a comparison sets %al from the flags and zero-extends it into the result,
+/// going through R11 since `movzbl` can only write to a register.
+#[test]
+fn compare_through_byte_register() {
+    let instrs = translate_with_pseudo(
+        vec![InstructionTacky::Binary {
+            op: BinaryOp::LessOrEqual,
+            src1: ValTacky::int(1),
+            src2: ValTacky::tmp(0),
+            dst: ValTacky::tmp(1),
+        }],
+        Target::Linux,
+        0,
+    )
+    .unwrap();
+    let mut resolver = TmpVarResolver::new(&instrs, &HashMap::new());
+    let slot = |no| OperandAsm::Stack {
+        off: resolver.name_to_off[&Symbol::numbered("tmp", no)],
+    };
+    let (src2, dst) = (slot(0), slot(1));
+    let resolved = instrs
+        .into_iter()
+        .map(|i| resolver.resolve_temps(i))
+        .collect();
+    let r11 = OperandAsm::Reg { r: Register::R11 };
+    let ax = OperandAsm::Reg { r: Register::AX };
+    assert_eq!(
+        fix_up_instrs(resolved),
+        vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 1 },
+                dst: r11,
+            },
+            InstructionAsm::Cmp {
+                ty: AsmType::Longword,
+                src: src2,
+                dst: r11,
+            },
+            InstructionAsm::SetCC {
+                cc: CondCode::LE,
+                operand: ax,
+            },
+            InstructionAsm::Movzx {
+                src_ty: AsmType::Byte,
+                dst_ty: AsmType::Longword,
+                src: ax,
+                dst: r11,
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: r11,
+                dst,
+            },
+        ]
+    );
+}
+
+/// unsigned operands divide with `div` after clearing %edx rather than
+/// sign-extending into it, and compare with the above/below conditions.
+#[test]
+fn unsigned_division_and_comparison() {
+    let unsigned = |no| ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", no),
+        ty: CType::UInt,
+    };
+    let instrs = translate_with_pseudo(
+        vec![
+            InstructionTacky::Binary {
+                op: BinaryOp::Remainder,
+                src1: unsigned(0),
+                src2: unsigned(1),
+                dst: unsigned(2),
+            },
+            InstructionTacky::Binary {
+                op: BinaryOp::GreaterThan,
+                src1: unsigned(0),
+                src2: unsigned(1),
+                dst: ValTacky::tmp(3),
+            },
+        ],
+        Target::Linux,
+        0,
+    )
+    .unwrap();
+    let ax = OperandAsm::Reg { r: Register::AX };
+    assert_eq!(
+        instrs,
+        vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: pseudo(0),
+                dst: ax,
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 0 },
+                dst: OperandAsm::Reg { r: Register::DX },
+            },
+            InstructionAsm::Div {
+                ty: AsmType::Longword,
+                operand: pseudo(1),
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Reg { r: Register::DX },
+                dst: pseudo(2),
+            },
+            InstructionAsm::Cmp {
+                ty: AsmType::Longword,
+                src: pseudo(1),
+                dst: pseudo(0),
+            },
+            InstructionAsm::SetCC {
+                cc: CondCode::A,
+                operand: ax,
+            },
+            InstructionAsm::Movzx {
+                src_ty: AsmType::Byte,
+                dst_ty: AsmType::Longword,
+                src: ax,
+                dst: pseudo(3),
+            },
+        ]
+    );
+}
+
+/// `>>` is arithmetic on a signed value and logical on an unsigned one, and a
+/// constant count is masked to the bits the instruction would use.
+#[test]
+fn right_shift_by_signedness() {
+    let unsigned = |no| ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", no),
+        ty: CType::UInt,
+    };
+    let instrs = translate_with_pseudo(
+        vec![
+            InstructionTacky::Binary {
+                op: BinaryOp::RightShift,
+                src1: ValTacky::tmp(0),
+                src2: ValTacky::tmp(1),
+                dst: ValTacky::tmp(2),
+            },
+            InstructionTacky::Binary {
+                op: BinaryOp::RightShift,
+                src1: unsigned(3),
+                src2: ValTacky::Const {
+                    c: Const::UInt { uint: 33 },
+                },
+                dst: unsigned(4),
+            },
+        ],
+        Target::Linux,
+        0,
+    )
+    .unwrap();
+    let shift = |binop, src, dst| InstructionAsm::Binary {
+        ty: AsmType::Longword,
+        binop,
+        src,
+        dst,
+    };
+    let mov = |src, dst| InstructionAsm::Mov {
+        ty: AsmType::Longword,
+        src,
+        dst,
+    };
+    assert_eq!(
+        instrs,
+        vec![
+            mov(pseudo(0), pseudo(2)),
+            shift(AsmBinaryOp::Sar, pseudo(1), pseudo(2)),
+            mov(pseudo(3), pseudo(4)),
+            shift(AsmBinaryOp::Shr, OperandAsm::Imm { int: 1 }, pseudo(4)),
+        ]
+    );
+}
+
+/// unsigned division by a power of two is a logical shift, and the remainder a
+/// mask, with no bias for negative dividends.
+#[test]
+fn unsigned_power_of_two_needs_no_bias() {
+    let x = pseudo(0);
+    let dst = pseudo(1);
+    let eight = OperandAsm::Imm { int: 8 };
+    assert_eq!(
+        reduce_strength(&BinaryOp::Divide, AsmType::Quadword, false, x, eight, dst),
+        Some(vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: x,
+                dst,
+            },
+            InstructionAsm::Binary {
+                ty: AsmType::Quadword,
+                binop: AsmBinaryOp::Shr,
+                src: OperandAsm::Imm { int: 3 },
+                dst,
+            },
+        ])
+    );
+    assert_eq!(
+        reduce_strength(
+            &BinaryOp::Remainder,
+            AsmType::Quadword,
+            false,
+            x,
+            eight,
+            dst
+        ),
+        Some(vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: x,
+                dst,
+            },
+            InstructionAsm::Binary {
+                ty: AsmType::Quadword,
+                binop: AsmBinaryOp::And,
+                src: OperandAsm::Imm { int: 7 },
+                dst,
+            },
+        ])
+    );
+}
+
+/// a longword move clears the upper half of %rax, so zero extension needs no
+/// instruction of its own.
+#[test]
+fn zero_extend_through_longword_move() {
+    let wide = ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", 1),
+        ty: CType::ULong,
+    };
+    let instrs = translate_with_pseudo(
+        vec![InstructionTacky::ZeroExtend {
+            src: ValTacky::tmp(0),
+            dst: wide,
+        }],
+        Target::Linux,
+        0,
+    )
+    .unwrap();
+    let ax = OperandAsm::Reg { r: Register::AX };
+    assert_eq!(
+        instrs,
+        vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: pseudo(0),
+                dst: ax,
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: ax,
+                dst: pseudo(1),
+            },
+        ]
+    );
+}
+
+/// a branch between two ready values becomes a conditional move, keeping the
+/// comments in front and the end label for anything else jumping there.
+#[test]
+fn select_conditional_move() {
+    let branch = vec![
+        InstructionTacky::JumpIfZero {
+            condition: ValTacky::tmp(0),
+            target: String::from("else"),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::int(3),
+            dst: ValTacky::tmp(1),
+        },
+        InstructionTacky::Jump {
+            target: String::from("end"),
+        },
+        InstructionTacky::Label {
+            name: String::from("else"),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::tmp(2),
+            dst: ValTacky::tmp(1),
+        },
+        InstructionTacky::Label {
+            name: String::from("end"),
+        },
+    ];
+    let selected = vec![
+        InstructionAsm::Cmp {
+            ty: AsmType::Longword,
+            src: OperandAsm::Imm { int: 0 },
+            dst: pseudo(0),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Imm { int: 3 },
+            dst: pseudo(1),
+        },
+        InstructionAsm::Cmov {
+            ty: AsmType::Longword,
+            cc: CondCode::E,
+            src: pseudo(2),
+            dst: pseudo(1),
+        },
+        InstructionAsm::Label {
+            name: String::from("end"),
+        },
+    ];
+    assert_eq!(
+        select_conditional_moves(translate_with_pseudo(branch.clone(), Target::Linux, 0).unwrap()),
+        selected
+    );
+
+    let commented =
+        select_conditional_moves(translate_with_comments(branch, Target::Linux, 0).unwrap());
+    assert_eq!(commented.len(), 6 + selected.len());
+    assert!(commented[..6]
+        .iter()
+        .all(|i| matches!(i, InstructionAsm::Comment { .. })));
+    assert_eq!(commented[6..], selected);
+}
+
+/// a join reached from elsewhere, as `&&` makes, or whose second value is the
+/// first one's destination, stays a branch.
+#[test]
+fn keep_branch_unless_conditional_move_is_equivalent() {
+    let branch = |first_jump: bool, b: ValTacky| {
+        let mut instrs = vec![];
+        if first_jump {
+            instrs.push(InstructionTacky::JumpIfZero {
+                condition: ValTacky::tmp(3),
+                target: String::from("else"),
+            });
+        }
+        instrs.extend([
+            InstructionTacky::JumpIfZero {
+                condition: ValTacky::tmp(0),
+                target: String::from("else"),
+            },
+            InstructionTacky::Copy {
+                src: ValTacky::int(1),
+                dst: ValTacky::tmp(1),
+            },
+            InstructionTacky::Jump {
+                target: String::from("end"),
+            },
+            InstructionTacky::Label {
+                name: String::from("else"),
+            },
+            InstructionTacky::Copy {
+                src: b,
+                dst: ValTacky::tmp(1),
+            },
+            InstructionTacky::Label {
+                name: String::from("end"),
+            },
+        ]);
+        translate_with_pseudo(instrs, Target::Linux, 0).unwrap()
+    };
+    for instrs in [
+        branch(true, ValTacky::int(0)),
+        branch(false, ValTacky::tmp(1)),
+    ] {
+        assert_eq!(select_conditional_moves(instrs.clone()), instrs);
+    }
+}
+
+/// `cmov` takes no immediate and only writes to a register, so a constant goes
+/// through R10 and a destination on the stack through R11, loaded first in case
+/// the move doesn't happen.
+#[test]
+fn cmov_through_registers() {
+    let r10 = OperandAsm::Reg { r: Register::R10 };
+    let r11 = OperandAsm::Reg { r: Register::R11 };
+    let dst = OperandAsm::Stack { off: -4 };
+    assert_eq!(
+        fix_up_instrs(vec![InstructionAsm::Cmov {
+            ty: AsmType::Longword,
+            cc: CondCode::NE,
+            src: OperandAsm::Imm { int: 7 },
+            dst,
+        }]),
+        vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 7 },
+                dst: r10,
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: dst,
+                dst: r11,
+            },
+            InstructionAsm::Cmov {
+                ty: AsmType::Longword,
+                cc: CondCode::NE,
+                src: r10,
+                dst: r11,
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: r11,
+                dst,
+            },
+        ]
+    );
+}
+
+/// fifty chained temporaries only ever have one or two live at once,
+/// so the frame must stay small instead of growing by 4 bytes per temporary.
+#[test]
+fn reuse_slots_for_sequential_temporaries() {
+    let mut instrs = vec![InstructionTacky::Unary {
+        op: UnaryOp::Negate,
+        src: ValTacky::int(2),
+        dst: ValTacky::tmp(0),
+    }];
+    for no in 1..50 {
+        instrs.push(InstructionTacky::Binary {
+            op: BinaryOp::Add,
+            src1: ValTacky::tmp(no - 1),
+            src2: ValTacky::int(1),
+            dst: ValTacky::tmp(no),
+        });
+    }
+    instrs.push(InstructionTacky::Ret {
+        v: ValTacky::tmp(49),
+    });
+
+    let fundef = translate_fundef(
+        FunDefTacky {
+            identifier: Symbol::intern("main"),
+            global: true,
+            params: vec![],
+            instructions: instrs,
+        },
+        &HashSet::new(),
+        &HashSet::new(),
+        CodegenOptions::default(),
+    )
+    .unwrap();
+    let frame = fundef
+        .instructions
+        .iter()
+        .find_map(|i| match i {
+            InstructionAsm::AllocStack { off } => Some(-off),
+            _ => None,
+        })
+        .unwrap();
+    assert!(frame <= 16, "frame of {} bytes", frame);
+}
+
+/// a variable whose address is taken may be read through the pointer after
+/// its last direct use, so no other temporary shares its slot.
+#[test]
+fn keep_address_taken_variable_apart() {
+    let ptr = ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", 1),
+        ty: CType::Int.pointer_to(),
+    };
+    let instrs = translate_with_pseudo(
+        vec![
+            InstructionTacky::Copy {
+                src: ValTacky::int(1),
+                dst: ValTacky::tmp(0),
+            },
+            InstructionTacky::GetAddress {
+                src: ValTacky::tmp(0),
+                dst: ptr.clone(),
+            },
+            InstructionTacky::Copy {
+                src: ValTacky::int(2),
+                dst: ValTacky::tmp(2),
+            },
+            InstructionTacky::Store {
+                src: ValTacky::tmp(2),
+                dst_ptr: ptr.clone(),
+            },
+            InstructionTacky::Load {
+                src_ptr: ptr,
+                dst: ValTacky::tmp(3),
+            },
+            InstructionTacky::Ret {
+                v: ValTacky::tmp(3),
+            },
+        ],
+        Target::Linux,
+        0,
+    )
+    .unwrap();
+    let mut resolver = TmpVarResolver::new(&instrs, &HashMap::new());
+    let slot = |resolver: &mut TmpVarResolver, no| resolver.temp_to_stack(pseudo(no));
+    let addressed = slot(&mut resolver, 0);
+    assert_eq!(addressed, OperandAsm::Stack { off: -8 });
+    for no in 1..4 {
+        assert_ne!(slot(&mut resolver, no), addressed, "tmp.{}", no);
+    }
+    assert_eq!(
+        fix_up_instrs(vec![InstructionAsm::Lea {
+            src: addressed,
+            dst: OperandAsm::Stack { off: -16 },
+        }]),
+        vec![
+            InstructionAsm::Lea {
+                src: addressed,
+                dst: OperandAsm::Reg { r: Register::R11 },
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: OperandAsm::Reg { r: Register::R11 },
+                dst: OperandAsm::Stack { off: -16 },
+            },
+        ]
+    );
+}
+
+/// both operands of a binary operation are live at once and need separate slots.
+#[test]
+fn keep_overlapping_temporaries_apart() {
+    let instrs = translate_with_pseudo(
+        vec![
+            InstructionTacky::Unary {
+                op: UnaryOp::Negate,
+                src: ValTacky::int(1),
+                dst: ValTacky::tmp(0),
+            },
+            InstructionTacky::Unary {
+                op: UnaryOp::Negate,
+                src: ValTacky::int(2),
+                dst: ValTacky::tmp(1),
+            },
+            InstructionTacky::Binary {
+                op: BinaryOp::Add,
+                src1: ValTacky::tmp(0),
+                src2: ValTacky::tmp(1),
+                dst: ValTacky::tmp(2),
+            },
+            InstructionTacky::Ret {
+                v: ValTacky::tmp(2),
+            },
+        ],
+        Target::Linux,
+        0,
+    )
+    .unwrap();
+    let resolver = TmpVarResolver::new(&instrs, &HashMap::new());
+    assert_ne!(
+        resolver.name_to_off[&Symbol::numbered("tmp", 0)],
+        resolver.name_to_off[&Symbol::numbered("tmp", 1)]
+    );
+    assert_ne!(
+        resolver.name_to_off[&Symbol::numbered("tmp", 1)],
+        resolver.name_to_off[&Symbol::numbered("tmp", 2)]
+    );
+    assert_eq!(
+        resolver.name_to_off[&Symbol::numbered("tmp", 0)],
+        resolver.name_to_off[&Symbol::numbered("tmp", 2)]
+    );
+}
+
+/// a quadword after a longword skips ahead to an 8-byte aligned slot, and a longword
+/// freed early isn't handed to a later quadword that wouldn't fit in it.
+#[test]
+fn size_and_align_slots_by_type() {
+    let instrs = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Imm { int: 1 },
+            dst: pseudo(0),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Quadword,
+            src: OperandAsm::Imm { int: 2 },
+            dst: pseudo(1),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: pseudo(0),
+            dst: OperandAsm::Reg { r: Register::AX },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Quadword,
+            src: OperandAsm::Imm { int: 3 },
+            dst: pseudo(2),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Quadword,
+            binop: AsmBinaryOp::Add,
+            src: pseudo(1),
+            dst: pseudo(2),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Quadword,
+            src: pseudo(2),
+            dst: OperandAsm::Reg { r: Register::AX },
+        },
+        InstructionAsm::Ret,
+    ];
+    let resolver = TmpVarResolver::new(&instrs, &HashMap::new());
+    assert_eq!(resolver.name_to_off[&Symbol::numbered("tmp", 0)], -4);
+    assert_eq!(resolver.name_to_off[&Symbol::numbered("tmp", 1)], -16);
+    assert_eq!(resolver.name_to_off[&Symbol::numbered("tmp", 2)], -24);
+    assert_eq!(resolver.min_used, -24);
+}
+
+/// an array gets a slot as big and as aligned as it is, which no other
+/// temporary shares, and an element's address is a `lea` off the pointer: by a
+/// displacement for a constant index, or through %rdx, multiplied first when
+/// the element size isn't a scale the `lea` has.
+#[test]
+fn lay_out_and_index_arrays() {
+    let array = ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", 0),
+        ty: CType::Int.array_of(3),
+    };
+    let ptr = |no| ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", no),
+        ty: CType::Int.pointer_to(),
+    };
+    let index = ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", 2),
+        ty: CType::Long,
+    };
+    let instrs = translate_with_pseudo(
+        vec![
+            InstructionTacky::GetAddress {
+                src: array.clone(),
+                dst: ptr(1),
+            },
+            InstructionTacky::AddPtr {
+                ptr: ptr(1),
+                index: ValTacky::Const {
+                    c: Const::Long { long: 2 },
+                },
+                scale: 4,
+                dst: ptr(3),
+            },
+            InstructionTacky::AddPtr {
+                ptr: ptr(3),
+                index: index.clone(),
+                scale: 12,
+                dst: ptr(3),
+            },
+            InstructionTacky::Store {
+                src: ValTacky::int(7),
+                dst_ptr: ptr(3),
+            },
+            InstructionTacky::Ret { v: index },
+        ],
+        Target::Linux,
+        0,
+    )
+    .unwrap();
+    let (ax, dx) = (Register::AX, Register::DX);
+    assert_eq!(
+        instrs[1..9],
+        [
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: pseudo(1),
+                dst: OperandAsm::Reg { r: ax },
+            },
+            InstructionAsm::Lea {
+                src: OperandAsm::Memory { base: ax, off: 8 },
+                dst: pseudo(3),
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: pseudo(3),
+                dst: OperandAsm::Reg { r: ax },
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: pseudo(2),
+                dst: OperandAsm::Reg { r: dx },
+            },
+            InstructionAsm::Binary {
+                ty: AsmType::Quadword,
+                binop: AsmBinaryOp::Imul,
+                src: OperandAsm::Imm { int: 12 },
+                dst: OperandAsm::Reg { r: dx },
+            },
+            InstructionAsm::Lea {
+                src: OperandAsm::Indexed {
+                    base: ax,
+                    index: dx,
+                    scale: 1,
+                },
+                dst: pseudo(3),
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: pseudo(3),
+                dst: OperandAsm::Reg { r: ax },
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 7 },
+                dst: OperandAsm::Memory { base: ax, off: 0 },
+            },
+        ]
+    );
+    let aggregates = HashMap::from([(Symbol::numbered("tmp", 0), CType::Int.array_of(3))]);
+    let resolver = TmpVarResolver::new(&instrs, &aggregates);
+    assert_eq!(resolver.name_to_off[&Symbol::numbered("tmp", 0)], -12);
+    for no in 1..4 {
+        assert!(
+            resolver.name_to_off[&Symbol::numbered("tmp", no)] <= -20,
+            "tmp.{}",
+            no
+        );
+    }
+}
+
+/// a value read on the next trip around a loop must keep its slot through the whole loop.
+#[test]
+fn keep_loop_carried_temporary_live() {
+    let instrs = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Imm { int: 0 },
+            dst: pseudo(0),
+        },
+        InstructionAsm::Label {
+            name: String::from("loop"),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Imm { int: 1 },
+            dst: pseudo(1),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Add,
+            src: pseudo(1),
+            dst: pseudo(0),
+        },
+        InstructionAsm::Cmp {
+            ty: AsmType::Longword,
+            src: OperandAsm::Imm { int: 0 },
+            dst: pseudo(1),
+        },
+        InstructionAsm::JmpCC {
+            cc: CondCode::NE,
+            target: String::from("loop"),
+        },
+        InstructionAsm::Ret,
+    ];
+    let resolver = TmpVarResolver::new(&instrs, &HashMap::new());
+    assert_ne!(
+        resolver.name_to_off[&Symbol::numbered("tmp", 0)],
+        resolver.name_to_off[&Symbol::numbered("tmp", 1)]
+    );
+}
+
+#[test]
+fn round_frame_size_to_sixteen() {
+    assert_eq!(round_frame_size(0, 0), 0);
+    assert_eq!(round_frame_size(4, 0), 16);
+    assert_eq!(round_frame_size(16, 0), 16);
+    assert_eq!(round_frame_size(20, 0), 32);
+}
+
+/// callee-saved pushes land below the frame, so they count towards the alignment.
+#[test]
+fn round_frame_size_with_pushed_registers() {
+    assert_eq!(round_frame_size(0, 8), 8);
+    assert_eq!(round_frame_size(4, 8), 8);
+    assert_eq!(round_frame_size(12, 8), 24);
+    assert_eq!(round_frame_size(4, 16), 16);
+}
+
+/// without a frame pointer nothing touches %rbp, and the slots move to %rsp-relative
+/// addresses just below the return address.
+#[test]
+fn omit_frame_pointer_addresses_from_rsp() {
+    let body = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Imm { int: 2 },
+            dst: OperandAsm::Stack { off: -4 },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: -4 },
+            dst: OperandAsm::Reg { r: Register::AX },
+        },
+        InstructionAsm::Ret,
+    ];
+    let options = CodegenOptions {
+        omit_frame_pointer: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        without_cfi(add_prologue_epilogue(body, -4, options)),
+        vec![
+            InstructionAsm::AllocStack { off: -8 },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 2 },
+                dst: OperandAsm::Memory {
+                    base: Register::SP,
+                    off: 4
+                },
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Memory {
+                    base: Register::SP,
+                    off: 4
+                },
+                dst: OperandAsm::Reg { r: Register::AX },
+            },
+            InstructionAsm::DeallocStack { off: -8 },
+            InstructionAsm::Ret,
+        ]
+    );
+}
+
+/// callee-saved pushes sit between the frame and %rsp, so slot addresses account for
+/// them, and with the CFA measured from %rsp every move of %rsp is described.
+#[test]
+fn omit_frame_pointer_with_callee_saved() {
+    let body = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: -4 },
+            dst: OperandAsm::Reg { r: Register::BX },
+        },
+        InstructionAsm::Ret,
+    ];
+    let options = CodegenOptions {
+        omit_frame_pointer: true,
+        ..Default::default()
+    };
+    let res = add_prologue_epilogue(body, -4, options);
+    assert_eq!(
+        res,
+        vec![
+            cfi(CfiDirective::StartProc),
+            InstructionAsm::AllocStack { off: -16 },
+            cfi(CfiDirective::DefCfaOffset { off: 24 }),
+            InstructionAsm::Push { r: Register::BX },
+            cfi(CfiDirective::DefCfaOffset { off: 32 }),
+            cfi(CfiDirective::Offset {
+                r: Register::BX,
+                off: -32
+            }),
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Memory {
+                    base: Register::SP,
+                    off: 20
+                },
+                dst: OperandAsm::Reg { r: Register::BX },
+            },
+            InstructionAsm::Pop { r: Register::BX },
+            cfi(CfiDirective::DefCfaOffset { off: 24 }),
+            InstructionAsm::DeallocStack { off: -16 },
+            cfi(CfiDirective::DefCfaOffset { off: 8 }),
+            InstructionAsm::Ret,
+            cfi(CfiDirective::EndProc),
+        ]
+    );
+}
+
+/// a leaf frame of up to 128 bytes stays below %rsp, with or without a frame pointer.
+#[test]
+fn red_zone_skips_allocation() {
+    let body = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Imm { int: 3 },
+            dst: OperandAsm::Stack { off: -128 },
+        },
+        InstructionAsm::Ret,
+    ];
+    let options = CodegenOptions {
+        red_zone: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        without_cfi(add_prologue_epilogue(body.clone(), -128, options)),
+        vec![
+            InstructionAsm::Push { r: Register::BP },
+            InstructionAsm::MovQ {
+                src: Register::SP,
+                dst: Register::BP,
+            },
+            body[0].clone(),
+            InstructionAsm::MovQ {
+                src: Register::BP,
+                dst: Register::SP,
+            },
+            InstructionAsm::Pop { r: Register::BP },
+            InstructionAsm::Ret,
+        ]
+    );
+    let frameless = CodegenOptions {
+        omit_frame_pointer: true,
+        ..options
+    };
+    assert_eq!(
+        without_cfi(add_prologue_epilogue(body, -128, frameless)),
+        vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 3 },
+                dst: OperandAsm::Memory {
+                    base: Register::SP,
+                    off: -128
+                },
+            },
+            InstructionAsm::Ret,
+        ]
+    );
+}
+
+/// saved registers are found below the frame, 16 bytes under the CFA for the
+/// return address and %rbp.
+#[test]
+fn cfi_records_callee_saved_below_frame() {
+    let body = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: -4 },
+            dst: OperandAsm::Reg { r: Register::BX },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Reg { r: Register::BX },
+            dst: OperandAsm::Reg { r: Register::R12 },
+        },
+        InstructionAsm::Ret,
+    ];
+    let res = add_prologue_epilogue(body, -4, CodegenOptions::default());
+    let offsets: Vec<&InstructionAsm> = res
+        .iter()
+        .filter(|i| {
+            matches!(
+                i,
+                InstructionAsm::Cfi {
+                    directive: CfiDirective::Offset { .. }
+                }
+            )
+        })
+        .collect();
+    assert_eq!(
+        offsets,
+        [
+            &cfi(CfiDirective::Offset {
+                r: Register::BP,
+                off: -16
+            }),
+            &cfi(CfiDirective::Offset {
+                r: Register::BX,
+                off: -40
+            }),
+            &cfi(CfiDirective::Offset {
+                r: Register::R12,
+                off: -48
+            }),
+        ]
+    );
+}
+
+/// code after an early `ret` gets the body's rules back, not the epilogue's.
+#[test]
+fn cfi_restores_state_after_early_return() {
+    let label = InstructionAsm::Label {
+        name: String::from("later"),
+    };
+    let body = vec![InstructionAsm::Ret, label.clone(), InstructionAsm::Ret];
+    for omit_frame_pointer in [false, true] {
+        let options = CodegenOptions {
+            omit_frame_pointer,
+            ..Default::default()
+        };
+        let res = add_prologue_epilogue(body.clone(), -4, options);
+        let first_ret = res.iter().position(|i| *i == InstructionAsm::Ret).unwrap();
+        let remember = res
+            .iter()
+            .position(|i| *i == cfi(CfiDirective::RememberState))
+            .unwrap();
+        assert!(remember < first_ret);
+        assert_eq!(
+            res[first_ret + 1..first_ret + 3],
+            [cfi(CfiDirective::RestoreState), label.clone()]
+        );
+        assert_eq!(
+            res.iter()
+                .filter(|i| **i == cfi(CfiDirective::RestoreState))
+                .count(),
+            1
+        );
+        assert_eq!(res.last(), Some(&cfi(CfiDirective::EndProc)));
+    }
+}
+
+/// without a frame pointer, each page of a large frame moves the CFA as it is allocated.
+#[test]
+fn cfi_follows_probed_frame() {
+    let options = CodegenOptions {
+        omit_frame_pointer: true,
+        ..Default::default()
+    };
+    let res = add_prologue_epilogue(
+        vec![InstructionAsm::Ret],
+        -(STACK_PROBE_INTERVAL + 8),
+        options,
+    );
+    let offsets: Vec<i32> = res
+        .iter()
+        .filter_map(|i| match i {
+            InstructionAsm::Cfi {
+                directive: CfiDirective::DefCfaOffset { off },
+            } => Some(*off),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        offsets,
+        [8 + STACK_PROBE_INTERVAL, 8 + STACK_PROBE_INTERVAL + 8, 8]
+    );
+}
+
+/// Windows pushes the callee-saved registers before allocating the frame, and
+/// addresses the frame from its bottom, so that the `.seh_*` directives can
+/// describe the prologue.
+#[test]
+fn windows_prologue_for_seh() {
+    let body = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: -4 },
+            dst: OperandAsm::Reg { r: Register::BX },
+        },
+        InstructionAsm::Ret,
+    ];
+    let options = CodegenOptions {
+        target: Target::Windows,
+        ..Default::default()
+    };
+    let res = add_prologue_epilogue(body, -4, options);
+    assert_eq!(
+        res,
+        vec![
+            cfi(CfiDirective::StartProc),
+            InstructionAsm::Push { r: Register::BP },
+            cfi(CfiDirective::PushReg { r: Register::BP }),
+            InstructionAsm::Push { r: Register::BX },
+            cfi(CfiDirective::PushReg { r: Register::BX }),
+            InstructionAsm::AllocStack { off: -8 },
+            cfi(CfiDirective::StackAlloc { size: 8 }),
+            InstructionAsm::MovQ {
+                src: Register::SP,
+                dst: Register::BP,
+            },
+            cfi(CfiDirective::SetFrame {
+                r: Register::BP,
+                off: 0
+            }),
+            cfi(CfiDirective::EndPrologue),
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Memory {
+                    base: Register::BP,
+                    off: 12
+                },
+                dst: OperandAsm::Reg { r: Register::BX },
+            },
+            InstructionAsm::Lea {
+                src: OperandAsm::Memory {
+                    base: Register::BP,
+                    off: 8
+                },
+                dst: OperandAsm::Reg { r: Register::SP },
+            },
+            InstructionAsm::Pop { r: Register::BX },
+            InstructionAsm::Pop { r: Register::BP },
+            InstructionAsm::Ret,
+            cfi(CfiDirective::EndProc),
+        ]
+    );
+}
+
+/// the unwind codes can't describe a frame without %rbp.
+#[test]
+fn windows_keeps_frame_pointer() {
+    let options = CodegenOptions {
+        target: Target::Windows,
+        omit_frame_pointer: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        gen_asm(ProgramTacky { items: vec![] }, options),
+        Err(CodegenError::FramePointerRequired {
+            target: Target::Windows
+        })
+    );
+}
+
+/// bigger frames, callee-saved pushes, and ABIs without a red zone all allocate as usual.
+#[test]
+fn red_zone_falls_back_to_allocation() {
+    let allocates = |body: Vec<InstructionAsm>, min_used, target| {
+        let options = CodegenOptions {
+            target,
+            red_zone: true,
+            ..Default::default()
+        };
+        add_prologue_epilogue(body, min_used, options)
+            .iter()
+            .any(|i| matches!(i, InstructionAsm::AllocStack { .. }))
+    };
+    let store = |off| {
+        vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 3 },
+                dst: OperandAsm::Stack { off },
+            },
+            InstructionAsm::Ret,
+        ]
+    };
+    assert!(!allocates(store(-128), -128, Target::Linux));
+    assert!(allocates(store(-132), -132, Target::Linux));
+    assert!(allocates(store(-4), -4, Target::Windows));
+    let saves_bx = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: -4 },
+            dst: OperandAsm::Reg { r: Register::BX },
+        },
+        InstructionAsm::Ret,
+    ];
+    assert!(allocates(saves_bx, -4, Target::Linux));
+}
+
+/// the first six arguments go in registers and the rest are pushed last first,
+/// through %rax, with a padding slot when an odd number of them would leave
+/// %rsp misaligned at the call.
+#[test]
+fn call_passes_arguments_in_registers_then_on_stack() {
+    let call = |count: i32| {
+        let mut res = vec![];
+        translate_call(
+            Symbol::intern("f"),
+            (1..=count).map(ValTacky::int).collect(),
+            ValTacky::tmp(0),
+            Target::Linux,
+            &mut res,
+        );
+        res
+    };
+    let arg = |int, r| InstructionAsm::Mov {
+        ty: AsmType::Longword,
+        src: OperandAsm::Imm { int },
+        dst: OperandAsm::Reg { r },
+    };
+    let result = InstructionAsm::Mov {
+        ty: AsmType::Longword,
+        src: OperandAsm::Reg { r: Register::AX },
+        dst: pseudo(0),
+    };
+    let registers = [
+        arg(1, Register::DI),
+        arg(2, Register::SI),
+        arg(3, Register::DX),
+        arg(4, Register::CX),
+        arg(5, Register::R8),
+        arg(6, Register::R9),
+    ];
+    assert_eq!(
+        call(2),
+        [
+            registers[0].clone(),
+            registers[1].clone(),
+            InstructionAsm::Call {
+                name: Symbol::intern("f")
+            },
+            result.clone(),
+        ]
+    );
+    let mut expected = vec![
+        InstructionAsm::AllocStack { off: -8 },
+        arg(7, Register::AX),
+        InstructionAsm::Push { r: Register::AX },
+    ];
+    expected.extend(registers.iter().cloned());
+    expected.extend([
+        InstructionAsm::Call {
+            name: Symbol::intern("f"),
+        },
+        InstructionAsm::DeallocStack { off: -16 },
+        result.clone(),
+    ]);
+    assert_eq!(call(7), expected);
+    let pushed: Vec<i64> = call(8)
+        .windows(2)
+        .filter_map(|pair| match pair {
+            [InstructionAsm::Mov {
+                src: OperandAsm::Imm { int },
+                ..
+            }, InstructionAsm::Push { .. }] => Some(*int),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(pushed, [8, 7]);
+    assert!(!call(8)
+        .iter()
+        .any(|i| matches!(i, InstructionAsm::AllocStack { .. })));
+}
+
+/// `double` arguments take the XMM registers, counted apart from the integer
+/// ones, and a `double` result comes back in %xmm0. A `double` constant is read
+/// from its read-only copy.
+#[test]
+fn call_passes_doubles_in_xmm_registers() {
+    let half = Const::Double { double: 0.5 };
+    let x = ValTacky::TmpVar {
+        name: Symbol::intern("x"),
+        ty: CType::Double,
+    };
+    let mut res = vec![];
+    translate_call(
+        Symbol::intern("f"),
+        vec![ValTacky::Const { c: half }, ValTacky::int(1), x],
+        ValTacky::TmpVar {
+            name: Symbol::intern("y"),
+            ty: CType::Double,
+        },
+        Target::Linux,
+        &mut res,
+    );
+    assert_eq!(
+        res,
+        [
+            InstructionAsm::Mov {
+                ty: AsmType::Double,
+                src: OperandAsm::Data {
+                    name: Symbol::intern(".double.3fe0000000000000")
+                },
+                dst: OperandAsm::Reg { r: Register::XMM0 },
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 1 },
+                dst: OperandAsm::Reg { r: Register::DI },
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Double,
+                src: OperandAsm::Pseudo {
+                    name: Symbol::intern("x")
+                },
+                dst: OperandAsm::Reg { r: Register::XMM1 },
+            },
+            InstructionAsm::Call {
+                name: Symbol::intern("f")
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Double,
+                src: OperandAsm::Reg { r: Register::XMM0 },
+                dst: OperandAsm::Pseudo {
+                    name: Symbol::intern("y")
+                },
+            },
+        ]
+    );
+}
+
+/// an argument narrower than an `int` is extended to one as it is passed, as it
+/// is signed or not, and a narrow result is read from the low bytes of %rax.
+#[test]
+fn call_extends_narrow_arguments() {
+    let var = |name, ty| ValTacky::TmpVar {
+        name: Symbol::intern(name),
+        ty,
+    };
+    let pseudo = |name| OperandAsm::Pseudo {
+        name: Symbol::intern(name),
+    };
+    let mut res = vec![];
+    translate_call(
+        Symbol::intern("f"),
+        vec![var("c", CType::Char), var("s", CType::UShort)],
+        var("r", CType::UChar),
+        Target::Linux,
+        &mut res,
+    );
+    assert_eq!(
+        res,
+        [
+            InstructionAsm::Movsx {
+                src_ty: AsmType::Byte,
+                dst_ty: AsmType::Longword,
+                src: pseudo("c"),
+                dst: OperandAsm::Reg { r: Register::DI },
+            },
+            InstructionAsm::Movzx {
+                src_ty: AsmType::Word,
+                dst_ty: AsmType::Longword,
+                src: pseudo("s"),
+                dst: OperandAsm::Reg { r: Register::SI },
+            },
+            InstructionAsm::Call {
+                name: Symbol::intern("f")
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Byte,
+                src: OperandAsm::Reg { r: Register::AX },
+                dst: pseudo("r"),
+            },
+        ]
+    );
+}
+
+/// Windows passes four arguments in registers and leaves 32 bytes of shadow
+/// space below the stack arguments, which the callee finds past it.
+#[test]
+fn windows_calls_leave_shadow_space() {
+    let mut res = vec![];
+    translate_call(
+        Symbol::intern("f"),
+        (1..=5).map(ValTacky::int).collect(),
+        ValTacky::tmp(0),
+        Target::Windows,
+        &mut res,
+    );
+    let allocs: Vec<&InstructionAsm> = res
+        .iter()
+        .filter(|i| {
+            matches!(
+                i,
+                InstructionAsm::AllocStack { .. } | InstructionAsm::DeallocStack { .. }
+            )
+        })
+        .collect();
+    assert_eq!(
+        allocs,
+        [
+            &InstructionAsm::AllocStack { off: -8 },
+            &InstructionAsm::AllocStack { off: -32 },
+            &InstructionAsm::DeallocStack { off: -48 },
+        ]
+    );
+    assert_eq!(
+        copy_params(
+            &[1, 2, 3, 4, 5].map(|no| ValTacky::TmpVar {
+                name: Symbol::numbered("a", no),
+                ty: CType::Int,
+            }),
+            Target::Windows
+        )[4],
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: 48 },
+            dst: OperandAsm::Pseudo {
+                name: Symbol::numbered("a", 5)
+            },
+        }
+    );
+}
+
+/// a tail call writes its stack arguments over the caller's own and jumps,
+/// unless it has more of them than the caller was passed, when it is an
+/// ordinary call followed by a return.
+#[test]
+fn tail_call_reuses_stack_arguments() {
+    let tail_call = |count: i32, params_on_stack| {
+        let mut res = vec![];
+        translate_tail_call(
+            Symbol::intern("f"),
+            (1..=count).map(ValTacky::int).collect(),
+            params_on_stack,
+            Target::Linux,
+            &mut res,
+        );
+        res
+    };
+    let jump = InstructionAsm::TailCall {
+        name: Symbol::intern("f"),
+    };
+    let res = tail_call(7, 1);
+    assert_eq!(
+        res[..2],
+        [
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 7 },
+                dst: OperandAsm::Reg { r: Register::AX },
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: OperandAsm::Reg { r: Register::AX },
+                dst: OperandAsm::Stack { off: 16 },
+            },
+        ]
+    );
+    assert_eq!(res.len(), 9);
+    assert_eq!(res.last(), Some(&jump));
+    let res = tail_call(7, 0);
+    assert!(!res.contains(&jump));
+    assert_eq!(
+        res[res.len() - 3..],
+        [
+            InstructionAsm::Call {
+                name: Symbol::intern("f")
+            },
+            InstructionAsm::DeallocStack { off: -16 },
+            InstructionAsm::Ret,
+        ]
+    );
+}
+
+/// the frame is torn down before a tail call, as before a `ret`.
+#[test]
+fn epilogue_precedes_tail_call() {
+    let jump = InstructionAsm::TailCall {
+        name: Symbol::intern("f"),
+    };
+    for omit_frame_pointer in [false, true] {
+        let options = CodegenOptions {
+            omit_frame_pointer,
+            ..Default::default()
+        };
+        let res = add_prologue_epilogue(vec![jump.clone()], -16, options);
+        let at = res.iter().position(|i| *i == jump).unwrap();
+        assert!(res[..at].iter().any(|i| if omit_frame_pointer {
+            matches!(i, InstructionAsm::DeallocStack { .. })
+        } else {
+            *i == InstructionAsm::Pop { r: Register::BP }
+        }));
+        assert_eq!(res[at + 1..], [cfi(CfiDirective::EndProc)]);
+    }
+}
+
+/// without a frame pointer, a stack parameter is found above the return address,
+/// and further up while the body has arguments of its own pushed for a call.
+#[test]
+fn omit_frame_pointer_follows_pushes() {
+    let body = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: 16 },
+            dst: OperandAsm::Reg { r: Register::AX },
+        },
+        InstructionAsm::Push { r: Register::AX },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: 16 },
+            dst: OperandAsm::Reg { r: Register::AX },
+        },
+        InstructionAsm::Call {
+            name: Symbol::intern("f"),
+        },
+        InstructionAsm::DeallocStack { off: -8 },
+        InstructionAsm::Ret,
+    ];
+    let options = CodegenOptions {
+        omit_frame_pointer: true,
+        ..Default::default()
+    };
+    let offsets: Vec<i32> = add_prologue_epilogue(body, 0, options)
+        .into_iter()
+        .filter_map(|i| match i {
+            InstructionAsm::Mov {
+                src: OperandAsm::Memory { off, .. },
+                ..
+            } => Some(off),
+            InstructionAsm::Cfi {
+                directive: CfiDirective::DefCfaOffset { off },
+            } => Some(-off),
+            _ => None,
+        })
+        .collect();
+    // the frame is padded to 8 bytes, so %rsp is aligned at the call
+    assert_eq!(offsets, [-16, 16, -24, 24, -16, -8]);
+}
+
+/// a structure is copied in the widest moves that fit what is left of it.
+#[test]
+fn copy_struct_in_widest_pieces() {
+    let mut res = vec![];
+    copy_struct(15, &mut res);
+    let pieces: Vec<_> = res
+        .iter()
+        .map(|instr| match instr {
+            InstructionAsm::Mov {
+                ty,
+                src:
+                    OperandAsm::Memory {
+                        base: Register::AX,
+                        off,
+                    },
+                dst:
+                    OperandAsm::Memory {
+                        base: Register::DX,
+                        off: dst_off,
+                    },
+            } if off == dst_off => (*ty, *off),
+            _ => panic!("unexpected {instr:?}"),
+        })
+        .collect();
+    assert_eq!(
+        pieces,
+        [
+            (AsmType::Quadword, 0),
+            (AsmType::Longword, 8),
+            (AsmType::Word, 12),
+            (AsmType::Byte, 14),
+        ]
+    );
+}
+
+/// a comparison is an `int` made from the flags, but one that converts to a
+/// `_Bool` sets the byte it goes in straight from them.
+#[test]
+fn setcc_into_bool() {
+    let bool_var = ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", 1),
+        ty: CType::Bool,
+    };
+    let instrs = translate_with_pseudo(
+        vec![InstructionTacky::Binary {
+            op: BinaryOp::NotEqual,
+            src1: ValTacky::tmp(0),
+            src2: ValTacky::Const {
+                c: Const::Int { int: 0 },
+            },
+            dst: bool_var,
+        }],
+        Target::Linux,
+        0,
+    )
+    .unwrap();
+    assert_eq!(
+        instrs,
+        vec![
+            InstructionAsm::Cmp {
+                ty: AsmType::Longword,
+                src: OperandAsm::Imm { int: 0 },
+                dst: pseudo(0),
+            },
+            InstructionAsm::SetCC {
+                cc: CondCode::NE,
+                operand: pseudo(1),
+            },
+        ]
+    );
+}
+
+/// a variable in the GOT is read through %rbx, loaded with its entry, and its
+/// address is the entry itself; other variables are addressed directly.
+#[test]
+fn variables_reached_through_got() {
+    let shared = Symbol::intern("shared");
+    let hidden = OperandAsm::Data {
+        name: Symbol::intern("hidden"),
+    };
+    let eax = OperandAsm::Reg { r: Register::AX };
+    let got = HashSet::from([shared]);
+    assert_eq!(
+        through_got(
+            vec![
+                InstructionAsm::Mov {
+                    ty: AsmType::Longword,
+                    src: OperandAsm::Data { name: shared },
+                    dst: eax,
+                },
+                InstructionAsm::Lea {
+                    src: OperandAsm::Data { name: shared },
+                    dst: eax,
+                },
+                InstructionAsm::Mov {
+                    ty: AsmType::Longword,
+                    src: hidden,
+                    dst: eax,
+                },
+            ],
+            &got
+        ),
+        vec![
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: OperandAsm::Got { name: shared },
+                dst: OperandAsm::Reg { r: Register::BX },
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: OperandAsm::Memory {
+                    base: Register::BX,
+                    off: 0,
+                },
+                dst: eax,
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: OperandAsm::Got { name: shared },
+                dst: eax,
+            },
+            InstructionAsm::Mov {
+                ty: AsmType::Longword,
+                src: hidden,
+                dst: eax,
+            },
+        ]
+    );
 }
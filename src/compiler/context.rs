@@ -0,0 +1,115 @@
+//! What a compilation keeps for as long as it runs: the names and types it
+//! interns, the layouts of the structures it defines, and the count its
+//! structure tags are numbered from. Every stage reaches the context current
+//! on its thread rather than having one handed to it, since a symbol prints
+//! itself, and a type sizes itself, wherever an IR is displayed, sorted or
+//! laid out. The driver makes a fresh context for each file it compiles,
+//! which frees everything kept for it once the file is done; a program
+//! embedding the compiler can do the same, and otherwise each thread has one
+//! of its own for as long as it runs.
+//!
+//! Symbols and types belong to the context they were interned in, and mean
+//! nothing in another: whatever a compilation returns is only printed,
+//! compared or sorted while its context is current.
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    marker::PhantomData,
+    rc::Rc,
+    sync::{atomic::AtomicU32, Arc, RwLock},
+};
+
+use super::{
+    parser::{StructLayout, TypeTable},
+    symbol::{Symbol, SymbolTable},
+};
+
+/// The tables a compilation interns into. Threads working on the same
+/// compilation share one, so each table is behind a lock.
+#[derive(Default)]
+pub struct Context {
+    pub(crate) symbols: RwLock<SymbolTable>,
+    pub(crate) types: RwLock<TypeTable>,
+    pub(crate) structs: RwLock<HashMap<Symbol, Arc<StructLayout>>>,
+    pub(crate) tag_no: AtomicU32,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Arc<Context>> = RefCell::new(Arc::default());
+}
+
+/// Keeps a context current on the thread that entered it, until dropped,
+/// when the one current before comes back.
+pub struct Entered {
+    previous: Option<Arc<Context>>,
+    // restoring the previous context on another thread would be wrong
+    _thread: PhantomData<Rc<()>>,
+}
+
+impl Context {
+    /// an empty context, to be entered for a compilation.
+    pub fn new() -> Arc<Self> {
+        Arc::default()
+    }
+
+    /// the context current on this thread.
+    pub fn current() -> Arc<Self> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// makes this the context current on this thread, until the returned
+    /// guard is dropped.
+    pub fn enter(self: &Arc<Self>) -> Entered {
+        let previous = CURRENT.with(|current| current.replace(self.clone()));
+        Entered {
+            previous: Some(previous),
+            _thread: PhantomData,
+        }
+    }
+
+    /// runs `f` with the context current on this thread.
+    pub(crate) fn with<R>(f: impl FnOnce(&Context) -> R) -> R {
+        CURRENT.with(|current| f(&current.borrow()))
+    }
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+}
+
+/// a context starts out empty, whatever was interned in the one before it,
+/// and is freed once nothing holds it.
+#[test]
+fn contexts_are_separate() {
+    let first = Context::new();
+    let freed = Arc::downgrade(&first);
+    {
+        let _entered = first.enter();
+        Symbol::intern("context_test.a");
+        assert_eq!(first.symbols.read().unwrap().len(), 1);
+    }
+    drop(first);
+    assert!(freed.upgrade().is_none());
+    let second = Context::new();
+    let _entered = second.enter();
+    assert_eq!(second.symbols.read().unwrap().len(), 0);
+    Symbol::intern("context_test.b");
+    assert_eq!(second.symbols.read().unwrap().len(), 1);
+}
+
+/// leaving a context brings back the one entered before it.
+#[test]
+fn entering_nests() {
+    let outer = Context::new();
+    let inner = Context::new();
+    let _outer = outer.enter();
+    {
+        let _inner = inner.enter();
+        assert!(Arc::ptr_eq(&Context::current(), &inner));
+    }
+    assert!(Arc::ptr_eq(&Context::current(), &outer));
+}
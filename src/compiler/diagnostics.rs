@@ -0,0 +1,557 @@
+//! Rendering of errors against the source text they were found in.
+//! The text is the preprocessor's output, whose `# <line> "<file>"` linemarkers
+//! say which file and line each stretch of it came from, so locations are
+//! reported against the file the user wrote. The quoted line is the
+//! preprocessed one, which is what the compiler actually read.
+//! Tools can ask for the same diagnostics as JSON instead, in the shape of
+//! `JsonDiagnostic`.
+use std::ffi::OsStr;
+
+use clap::ValueEnum;
+
+use super::lexer::Span;
+
+/// How serious a diagnostic is, which picks its label and color.
+/// - `Error`: red, and compilation fails
+/// - `Warning`: yellow
+/// - `Note`: blue, for the places a diagnostic points to besides its own
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Note => "note",
+        }
+    }
+
+    /// the ANSI escape starting the bold color of the label and carets.
+    fn color(&self) -> &'static str {
+        match self {
+            Self::Error => "\x1b[1;31m",
+            Self::Warning => "\x1b[1;33m",
+            Self::Note => "\x1b[1;34m",
+        }
+    }
+}
+
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// When diagnostics are colored.
+/// - `Auto`: when standard error is a terminal and `NO_COLOR` isn't set
+/// - `Always`: even through pipes and with `NO_COLOR` set
+/// - `Never`: plain text
+#[derive(PartialEq, Debug, Clone, Copy, Default, ValueEnum)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// whether to color, given the `NO_COLOR` variable and whether stderr is a terminal.
+    /// As <https://no-color.org> asks, a `NO_COLOR` that is set but empty doesn't count.
+    pub fn enabled(&self, no_color: Option<&OsStr>, is_terminal: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => is_terminal && no_color.is_none_or(OsStr::is_empty),
+        }
+    }
+}
+
+/// How diagnostics are printed.
+/// - `Human`: the source snippets `Diagnostic::render` draws
+/// - `Json`: one `JsonDiagnostic` object per line, which needs the `serde` feature
+#[derive(PartialEq, Debug, Clone, Copy, Default, ValueEnum)]
+pub enum DiagnosticsFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+/// A message, the span it is about, and any notes pointing at other spans
+/// with messages of their own, such as the opening delimiter an error failed to close.
+/// `code` names what went wrong, and stays the same when the message's wording changes.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub notes: Vec<(String, Span)>,
+}
+
+impl Diagnostic {
+    /// an error with no notes.
+    pub fn error(code: &'static str, message: String, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message,
+            span,
+            notes: vec![],
+        }
+    }
+
+    /// renders the diagnostic, and its notes, as a header, a `file:line:col` location,
+    /// the source line, and carets under the span, finding lines through `lines`.
+    /// With `color`, labels, messages and carets carry ANSI escapes.
+    pub fn render(&self, lines: &LineIndex, color: bool) -> String {
+        let mut res = snippet(self.severity, &self.message, self.span, lines, color);
+        for (message, span) in self.notes.iter() {
+            res.push_str(&snippet(Severity::Note, message, *span, lines, color));
+        }
+        res
+    }
+
+    /// the diagnostic as `--diagnostics-format=json` prints it, its spans
+    /// located through `lines`.
+    pub fn to_json(&self, lines: &LineIndex) -> JsonDiagnostic {
+        let (file, line, column) = json_location(lines, self.span);
+        JsonDiagnostic {
+            severity: self.severity,
+            code: String::from(self.code),
+            message: self.message.clone(),
+            file: Some(file),
+            line: Some(line),
+            column: Some(column),
+            notes: self
+                .notes
+                .iter()
+                .map(|(message, span)| {
+                    let (file, line, column) = json_location(lines, *span);
+                    JsonNote {
+                        message: message.clone(),
+                        file,
+                        line,
+                        column,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One diagnostic as `--diagnostics-format=json` prints it, serialized as it
+/// stands, one object to a line. `line` and `column` count as in the human
+/// format, in the file the user wrote.
+/// Errors that point nowhere in the source, such as a failed assembler, have
+/// no `line` or `column`, and no `file` if no one file is to blame.
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JsonDiagnostic {
+    pub severity: Severity,
+    pub code: String,
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub notes: Vec<JsonNote>,
+}
+
+/// A note on a `JsonDiagnostic`, located like the diagnostic itself.
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JsonNote {
+    pub message: String,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl JsonDiagnostic {
+    /// a diagnostic with no place in the source, its message without the `(!)`
+    /// marker the human format gives errors.
+    pub fn unlocated(severity: Severity, code: &str, message: &str, file: Option<&str>) -> Self {
+        JsonDiagnostic {
+            severity,
+            code: String::from(code),
+            message: String::from(message.trim_start_matches("(!) ").trim_end()),
+            file: file.map(String::from),
+            line: None,
+            column: None,
+            notes: vec![],
+        }
+    }
+}
+
+fn json_location(lines: &LineIndex, span: Span) -> (String, usize, usize) {
+    let loc = lines.locate(span.start);
+    (String::from(loc.file), loc.line, loc.col)
+}
+
+/// where a byte offset falls, as the user would count it.
+/// Columns count characters, not bytes, from 1.
+pub struct Location<'a> {
+    pub file: &'a str,
+    pub line: usize,
+    pub col: usize,
+    text: &'a str,
+    /// byte offset of `text` in the source
+    start: usize,
+}
+
+/// A source line other than a linemarker, and the file and line number it counts as.
+struct Line<'a> {
+    text: &'a str,
+    start: usize,
+    file: usize,
+    number: usize,
+}
+
+/// Where each line of a source starts, built in one pass over it so that
+/// offsets are only turned into lines and columns when a diagnostic needs them.
+/// The text is the preprocessor's output, so linemarkers name the file and
+/// line of what follows them; `file` names the source until one does.
+pub struct LineIndex<'a> {
+    files: Vec<&'a str>,
+    lines: Vec<Line<'a>>,
+    /// the line number and byte offset just past the last line
+    end: (usize, usize),
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str, file: &'a str) -> Self {
+        let mut files = vec![file];
+        let mut lines = vec![];
+        let mut number = 1;
+        let mut start = 0;
+        for raw in source.split_inclusive('\n') {
+            let text = raw.strip_suffix('\n').unwrap_or(raw);
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            if let Some((marked_line, marked_file)) = linemarker(text) {
+                number = marked_line;
+                files.push(marked_file);
+            } else {
+                lines.push(Line {
+                    text,
+                    start,
+                    file: files.len() - 1,
+                    number,
+                });
+                number += 1;
+            }
+            start += raw.len();
+        }
+        LineIndex {
+            files,
+            lines,
+            end: (number, start),
+        }
+    }
+
+    /// finds the line holding `offset`. An offset at the very end of a line, as for
+    /// errors at the end of input, belongs to that line.
+    pub fn locate(&self, offset: usize) -> Location<'a> {
+        let i = self
+            .lines
+            .partition_point(|l| l.start + l.text.len() < offset);
+        match self.lines.get(i).or(self.lines.last()) {
+            Some(line) => Location {
+                file: self.files[line.file],
+                line: line.number,
+                col: line.text
+                    [..offset.clamp(line.start, line.start + line.text.len()) - line.start]
+                    .chars()
+                    .count()
+                    + 1,
+                text: line.text,
+                start: line.start,
+            },
+            None => Location {
+                file: self.files[self.files.len() - 1],
+                line: self.end.0,
+                col: 1,
+                text: "",
+                start: self.end.1,
+            },
+        }
+    }
+}
+
+fn snippet(
+    severity: Severity,
+    message: &str,
+    span: Span,
+    lines: &LineIndex,
+    color: bool,
+) -> String {
+    let (color, bold, reset) = match color {
+        true => (severity.color(), BOLD, RESET),
+        false => ("", "", ""),
+    };
+    let loc = lines.locate(span.start);
+    let line_no = loc.line.to_string();
+    let pad = " ".repeat(line_no.len());
+    let before = &loc.text[..span.start - loc.start];
+    // keep tabs so the carets line up however wide the terminal draws them
+    let indent: String = before
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = loc.text[span.start - loc.start..]
+        .char_indices()
+        .take_while(|(i, _)| span.start + i < span.end)
+        .count()
+        .max(1);
+    format!(
+        "{}{}:{} {}{}{}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{}{}{}\n",
+        color,
+        severity.label(),
+        reset,
+        bold,
+        message,
+        reset,
+        pad,
+        loc.file,
+        loc.line,
+        loc.col,
+        pad,
+        line_no,
+        loc.text,
+        pad,
+        indent,
+        color,
+        "^".repeat(width),
+        reset
+    )
+}
+
+/// the line number and file a `# <line> "<file>" <flags>` linemarker sets.
+fn linemarker(text: &str) -> Option<(usize, &str)> {
+    let rest = text.strip_prefix('#')?.trim_start();
+    let (number, rest) = rest.split_once(' ')?;
+    let name = rest.strip_prefix('"')?;
+    let (name, _) = name.split_once('"')?;
+    Some((number.parse().ok()?, name))
+}
+
+#[test]
+fn render_error_on_line() {
+    let source = "int main(void) {\n\treturn 2 @ 3;\n}\n";
+    let diagnostic = Diagnostic::error(
+        "unrecognized-token",
+        String::from("unrecognized character '@'"),
+        Span { start: 27, end: 28 },
+    );
+    assert_eq!(
+        diagnostic.render(&LineIndex::new(source, "main.c"), false),
+        "error: unrecognized character '@'
+ --> main.c:2:11
+  |
+2 | \treturn 2 @ 3;
+  | \t         ^
+"
+    );
+}
+
+/// linemarkers rename the file and renumber the lines after them.
+#[test]
+fn render_after_linemarkers() {
+    let source = "# 0 \"<built-in>\"\n# 15 \"q.c\"\nint main(void) {\n  return 2\n}\n";
+    let diagnostic = Diagnostic::error(
+        "missing-semicolon",
+        String::from("expected ';' after expression"),
+        Span { start: 55, end: 55 },
+    );
+    assert_eq!(
+        diagnostic.render(&LineIndex::new(source, "<stdin>"), false),
+        "error: expected ';' after expression
+  --> q.c:16:11
+   |
+16 |   return 2
+   |           ^
+"
+    );
+}
+
+/// a span at the very end of the input sits just past the last character.
+#[test]
+fn render_at_end_of_input() {
+    let source = "int main(void) { return 2;";
+    let diagnostic = Diagnostic::error(
+        "unexpected-end",
+        String::from("expected '}' at end of input"),
+        Span { start: 26, end: 26 },
+    );
+    assert_eq!(
+        diagnostic.render(&LineIndex::new(source, "eof.c"), false),
+        "error: expected '}' at end of input
+ --> eof.c:1:27
+  |
+1 | int main(void) { return 2;
+  |                           ^
+"
+    );
+}
+
+/// colored output wraps each label, message and caret run in escapes, and
+/// notes get their own color.
+#[test]
+fn render_colored() {
+    let source = "int f(void) { return 2; }\nint f(void) { return 3; }\n";
+    let diagnostic = Diagnostic {
+        severity: Severity::Warning,
+        code: "redefinition",
+        message: String::from("redefinition of 'f'"),
+        span: Span { start: 30, end: 31 },
+        notes: vec![(
+            String::from("previous definition was here"),
+            Span { start: 4, end: 5 },
+        )],
+    };
+    assert_eq!(
+        diagnostic.render(&LineIndex::new(source, "f.c"), true),
+        "\x1b[1;33mwarning:\x1b[0m \x1b[1mredefinition of 'f'\x1b[0m
+ --> f.c:2:5
+  |
+2 | int f(void) { return 3; }
+  |     \x1b[1;33m^\x1b[0m
+\x1b[1;34mnote:\x1b[0m \x1b[1mprevious definition was here\x1b[0m
+ --> f.c:1:5
+  |
+1 | int f(void) { return 2; }
+  |     \x1b[1;34m^\x1b[0m
+"
+    );
+    let plain = diagnostic.render(&LineIndex::new(source, "f.c"), false);
+    assert!(plain.starts_with("warning: redefinition of 'f'\n"));
+    assert!(plain.contains("\nnote: previous definition was here\n"));
+    assert!(!plain.contains('\x1b'));
+}
+
+/// the JSON form carries the code, and locates the diagnostic and its notes
+/// as the human form does.
+#[test]
+fn to_json_locates_notes() {
+    let source = "# 1 \"u.c\"\nint main(void) {\n  return (2 + 3;\n}\n";
+    let diagnostic = Diagnostic {
+        notes: vec![(
+            String::from("to match this '('"),
+            Span { start: 36, end: 37 },
+        )],
+        ..Diagnostic::error(
+            "unexpected-token",
+            String::from("expected ')' before ';'"),
+            Span { start: 42, end: 43 },
+        )
+    };
+    assert_eq!(
+        diagnostic.to_json(&LineIndex::new(source, "<stdin>")),
+        JsonDiagnostic {
+            severity: Severity::Error,
+            code: String::from("unexpected-token"),
+            message: String::from("expected ')' before ';'"),
+            file: Some(String::from("u.c")),
+            line: Some(2),
+            column: Some(16),
+            notes: vec![JsonNote {
+                message: String::from("to match this '('"),
+                file: String::from("u.c"),
+                line: 2,
+                column: 10,
+            }],
+        }
+    );
+    let unlocated = JsonDiagnostic::unlocated(Severity::Error, "tool", "(!) ld failed\n", None);
+    assert_eq!(unlocated.message, "ld failed");
+    assert_eq!((unlocated.file, unlocated.line), (None, None));
+}
+
+#[cfg(test)]
+fn line_col(lines: &LineIndex, offset: usize) -> (usize, usize) {
+    let loc = lines.locate(offset);
+    (loc.line, loc.col)
+}
+
+/// offsets at the start, middle and end of lines, including one just past the
+/// last character of the input.
+#[test]
+fn line_index_positions() {
+    let lines = LineIndex::new("int main(void) {\n  return 2;\n}", "main.c");
+    assert_eq!(line_col(&lines, 0), (1, 1));
+    assert_eq!(line_col(&lines, 4), (1, 5));
+    assert_eq!(line_col(&lines, 16), (1, 17));
+    assert_eq!(line_col(&lines, 17), (2, 1));
+    assert_eq!(line_col(&lines, 26), (2, 10));
+    assert_eq!(line_col(&lines, 29), (3, 1));
+    assert_eq!(line_col(&lines, 30), (3, 2));
+}
+
+/// columns count characters, so they hold after multi-byte UTF-8, as in a
+/// string literal, and the carets under such characters are one per character.
+#[test]
+fn line_index_after_multibyte() {
+    let source = "int main(void) {\n  \"h\u{e9}llo w\u{f6}rld\"; return 2\n}";
+    let lines = LineIndex::new(source, "main.c");
+    assert_eq!(line_col(&lines, 19), (2, 3));
+    assert_eq!(line_col(&lines, 21), (2, 5));
+    assert_eq!(line_col(&lines, 23), (2, 6));
+    assert_eq!(line_col(&lines, 36), (2, 18));
+    assert_eq!(line_col(&lines, 43), (2, 25));
+    assert_eq!(line_col(&lines, 45), (3, 1));
+    let diagnostic = Diagnostic::error(
+        "unused-string",
+        String::from("unused string"),
+        Span { start: 19, end: 34 },
+    );
+    assert_eq!(
+        diagnostic.render(&lines, false),
+        "error: unused string
+ --> main.c:2:3
+  |
+2 |   \"h\u{e9}llo w\u{f6}rld\"; return 2
+  |   ^^^^^^^^^^^^^
+"
+    );
+}
+
+/// `\r\n` ends a line like `\n`, and the `\r` isn't quoted.
+#[test]
+fn line_index_crlf() {
+    let source = "int main(void) {\r\n  return 2 @ 3;\r\n}\r\n";
+    let lines = LineIndex::new(source, "dos.c");
+    assert_eq!(line_col(&lines, 18), (2, 1));
+    assert_eq!(line_col(&lines, 29), (2, 12));
+    assert_eq!(line_col(&lines, 35), (3, 1));
+    assert_eq!(
+        Diagnostic::error(
+            "unrecognized-token",
+            String::from("unrecognized character '@'"),
+            Span { start: 29, end: 30 }
+        )
+        .render(&lines, false),
+        "error: unrecognized character '@'
+ --> dos.c:2:12
+  |
+2 |   return 2 @ 3;
+  |            ^
+"
+    );
+}
+
+#[test]
+fn color_choice() {
+    let set = Some(OsStr::new("1"));
+    let empty = Some(OsStr::new(""));
+    assert!(ColorChoice::Auto.enabled(None, true));
+    assert!(ColorChoice::Auto.enabled(empty, true));
+    assert!(!ColorChoice::Auto.enabled(set, true));
+    assert!(!ColorChoice::Auto.enabled(None, false));
+    assert!(ColorChoice::Always.enabled(set, false));
+    assert!(!ColorChoice::Never.enabled(None, true));
+}
@@ -0,0 +1,1713 @@
+//! Emission for the x86-64 assembly AST.
+//! The same `ProgramAsm` can be printed in AT&T syntax, which GNU as expects by
+//! default, or in Intel syntax behind an `.intel_syntax noprefix` directive,
+//! for any `Target`. It can also be written straight to an ELF object file.
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+};
+
+use clap::ValueEnum;
+use object::{
+    write::{Object, Relocation, Symbol, SymbolSection},
+    Architecture, BinaryFormat, Endianness, RelocationFlags, SectionFlags, SectionKind,
+    SymbolFlags, SymbolKind, SymbolScope,
+};
+
+use super::{
+    asmgen::{
+        AsmBinaryOp, AsmType, CfiDirective, FunDefAsm, InstructionAsm, OperandAsm, ProgramAsm,
+        Register, TopLevelAsm,
+    },
+    encode::{encode_cfi, encode_function, SymbolRef, CIE, ENDBR64},
+    lexer::escape,
+    parser::Const,
+    tacky::StaticInit,
+    target::{RelocationModel, Target},
+    CompileError,
+};
+
+/// Assembly dialect of the emitted text.
+/// - `Att`: `movl $2, -4(%rbp)`, source before destination
+/// - `Intel`: `mov DWORD PTR [rbp-4], 2`, destination before source
+#[derive(PartialEq, Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Syntax {
+    #[default]
+    Att,
+    Intel,
+}
+
+/// Kind of file the compiler writes.
+/// - `Asm`: assembly text, assembled by the system toolchain
+/// - `Obj`: a relocatable object file, encoded by the compiler itself
+#[derive(PartialEq, Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Emit {
+    #[default]
+    Asm,
+    Obj,
+}
+
+/// Prints assembly ASTs in a chosen syntax, for a chosen target.
+/// With `function_sections`, each function goes in its own `.text.<name>` section
+/// so the linker can drop the unused ones; only ELF targets support this, and the
+/// others keep sharing `.text`.
+/// With `cf_protection`, each function starts with an `endbr64` landing pad and
+/// ELF output carries a `.note.gnu.property` advertising IBT and shadow stack support.
+/// With a `Static` relocation model, calls name their functions directly
+/// rather than going through the PLT.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct AsmFormatter {
+    pub syntax: Syntax,
+    pub target: Target,
+    pub function_sections: bool,
+    pub cf_protection: bool,
+    pub relocation: RelocationModel,
+}
+
+impl AsmFormatter {
+    pub fn new(syntax: Syntax, target: Target) -> Self {
+        AsmFormatter {
+            syntax,
+            target,
+            function_sections: false,
+            cf_protection: false,
+            relocation: RelocationModel::default(),
+        }
+    }
+
+    pub fn program(&self, prog: &ProgramAsm) -> String {
+        to_string(|res| self.write_program(res, prog))
+    }
+
+    pub fn fundef(&self, fundef: &FunDefAsm) -> String {
+        to_string(|res| self.write_fundef(res, fundef))
+    }
+
+    pub fn instruction(&self, instr: &InstructionAsm) -> String {
+        to_string(|res| self.write_instruction(res, instr))
+    }
+
+    /// formats an operand of the given size.
+    pub fn operand(&self, operand: &OperandAsm, ty: AsmType) -> String {
+        to_string(|res| self.write_operand(res, operand, ty))
+    }
+
+    /// writes the whole program a function at a time, without building it in memory.
+    /// The source files `.loc` directives name are numbered first. The variables with static storage follow the functions, and the read-only
+    /// constants come last.
+    pub fn write_program(&self, w: &mut impl fmt::Write, prog: &ProgramAsm) -> fmt::Result {
+        if self.syntax == Syntax::Intel {
+            w.write_str("\t.intel_syntax noprefix\n")?;
+        }
+        for item in prog.items.iter() {
+            if let TopLevelAsm::File { no, name } = item {
+                writeln!(w, "\t.file {} \"{}\"", no, escape(name.as_str().as_bytes()))?;
+            }
+        }
+        let separate = self.function_sections && self.target.has_elf_symbol_info();
+        if !separate {
+            w.write_str("\t.text\n")?;
+        }
+        for fundef in prog.functions() {
+            if separate {
+                writeln!(
+                    w,
+                    "\t.section .text.{},\"ax\",@progbits",
+                    self.target.symbol(&fundef.identifier.as_str())
+                )?;
+            }
+            self.write_fundef(w, fundef)?;
+            w.write_char('\n')?;
+        }
+        for item in prog.items.iter() {
+            if let TopLevelAsm::StaticVariable { name, global, init } = item {
+                self.write_static_variable(w, &name.as_str(), *global, init)?;
+            }
+        }
+        for item in prog.items.iter() {
+            if let TopLevelAsm::StaticConstant { name, init } = item {
+                self.write_static_constant(w, &name.as_str(), init)?;
+            }
+        }
+        if self.cf_protection && self.target.has_gnu_stack_note() {
+            w.write_str(GNU_PROPERTY_NOTE)?;
+        }
+        if self.target.has_gnu_stack_note() {
+            w.write_str("\t.section .note.GNU-stack,\"\",@progbits\n")?;
+        }
+        Ok(())
+    }
+
+    pub fn write_fundef(&self, w: &mut impl fmt::Write, fundef: &FunDefAsm) -> fmt::Result {
+        let name = self.target.symbol(&fundef.identifier.as_str());
+        if self.target.has_coff_defs() {
+            // external (2) or static (3) storage class, function type (32)
+            let class = if fundef.global { 2 } else { 3 };
+            write!(
+                w,
+                "\t.def {};\n\t.scl {};\n\t.type 32;\n\t.endef\n",
+                name, class
+            )?;
+        }
+        if fundef.global {
+            writeln!(w, "\t.globl {}", name)?;
+        }
+        if self.target.has_elf_symbol_info() {
+            writeln!(w, "\t.type {}, @function", name)?;
+        }
+        write!(w, "{}:", name)?;
+        // the frame description covers the landing pad too, as gcc's does
+        let body = match fundef.instructions.split_first() {
+            Some((
+                start @ InstructionAsm::Cfi {
+                    directive: CfiDirective::StartProc,
+                },
+                rest,
+            )) => {
+                w.write_str("\n\t")?;
+                if self.target.has_cfi() {
+                    self.write_instruction(w, start)?;
+                } else {
+                    write!(w, ".seh_proc {}", name)?;
+                }
+                rest
+            }
+            _ => &fundef.instructions[..],
+        };
+        if self.cf_protection {
+            w.write_str("\n\tendbr64")?;
+        }
+        for instr in body.iter() {
+            match instr {
+                InstructionAsm::Label { name: _ } => w.write_char('\n')?,
+                _ => w.write_str("\n\t")?,
+            }
+            self.write_instruction(w, instr)?;
+        }
+        if self.target.has_elf_symbol_info() {
+            write!(w, "\n\t.size {}, .-{}", name, name)?;
+        }
+        Ok(())
+    }
+
+    /// a variable the size of its type, and aligned for it, in `.data` if it
+    /// starts out nonzero and in `.bss` if not.
+    pub fn write_static_variable(
+        &self,
+        w: &mut impl fmt::Write,
+        name: &str,
+        global: bool,
+        init: &StaticInit,
+    ) -> fmt::Result {
+        let name = self.target.symbol(name);
+        let (size, alignment) = (init.ty().size(), init.ty().variable_alignment());
+        let zero = match init {
+            StaticInit::Scalar { c } => c.value() == 0,
+            StaticInit::Zero { .. } => true,
+            StaticInit::String { .. } | StaticInit::Address { .. } => false,
+        };
+        if global {
+            writeln!(w, "\t.globl {}", name)?;
+        }
+        match zero {
+            true => w.write_str("\t.bss\n")?,
+            false => w.write_str("\t.data\n")?,
+        }
+        writeln!(w, "\t.balign {}", alignment)?;
+        if self.target.has_elf_symbol_info() {
+            writeln!(w, "\t.type {}, @object\n\t.size {}, {}", name, name, size)?;
+        }
+        writeln!(w, "{}:", name)?;
+        match init {
+            StaticInit::Scalar { c } if !zero => match *c {
+                Const::Bool { bool } => writeln!(w, "\t.byte {}", u8::from(bool)),
+                Const::Char { char } => writeln!(w, "\t.byte {}", char),
+                Const::SChar { schar } => writeln!(w, "\t.byte {}", schar),
+                Const::UChar { uchar } => writeln!(w, "\t.byte {}", uchar),
+                Const::Short { short } => writeln!(w, "\t.short {}", short),
+                Const::UShort { ushort } => writeln!(w, "\t.short {}", ushort),
+                Const::Int { int } => writeln!(w, "\t.long {}", int),
+                Const::Long { long } => writeln!(w, "\t.quad {}", long),
+                Const::UInt { uint } => writeln!(w, "\t.long {}", uint),
+                Const::ULong { ulong } => writeln!(w, "\t.quad {}", ulong),
+                Const::Double { double } => writeln!(w, "\t.quad {}", double.to_bits()),
+            },
+            StaticInit::Scalar { .. } | StaticInit::Zero { .. } => writeln!(w, "\t.zero {}", size),
+            StaticInit::String {
+                val,
+                null_terminated,
+            } => self.write_string(w, val, *null_terminated),
+            StaticInit::Address { name, .. } => {
+                writeln!(w, "\t.quad {}", self.target.symbol(&name.as_str()))
+            }
+        }
+    }
+
+    /// a constant in the read-only section, known only to this file: an
+    /// 8-byte value aligned for it, or a string's bytes, which need none.
+    pub fn write_static_constant(
+        &self,
+        w: &mut impl fmt::Write,
+        name: &str,
+        init: &StaticInit,
+    ) -> fmt::Result {
+        match init {
+            StaticInit::String {
+                val,
+                null_terminated,
+            } => {
+                writeln!(w, "\t{}", self.target.rodata_section())?;
+                writeln!(w, "{}:", self.target.symbol(name))?;
+                self.write_string(w, val, *null_terminated)
+            }
+            StaticInit::Scalar { c } => {
+                writeln!(w, "\t{}\n\t.balign 8", self.target.rodata_section())?;
+                writeln!(w, "{}:", self.target.symbol(name))?;
+                writeln!(w, "\t.quad {}", c.value() as u64)
+            }
+            StaticInit::Zero { ty } => {
+                writeln!(w, "\t{}\n\t.balign 8", self.target.rodata_section())?;
+                writeln!(w, "{}:", self.target.symbol(name))?;
+                writeln!(w, "\t.zero {}", ty.size())
+            }
+            StaticInit::Address { name: target, .. } => {
+                writeln!(w, "\t{}\n\t.balign 8", self.target.rodata_section())?;
+                writeln!(w, "{}:", self.target.symbol(name))?;
+                writeln!(w, "\t.quad {}", self.target.symbol(&target.as_str()))
+            }
+        }
+    }
+
+    /// the bytes `val` quoted, `.asciz` adding a null after them.
+    fn write_string(
+        &self,
+        w: &mut impl fmt::Write,
+        val: &[u8],
+        null_terminated: bool,
+    ) -> fmt::Result {
+        match null_terminated {
+            true => writeln!(w, "\t.asciz \"{}\"", escape(val)),
+            false => writeln!(w, "\t.ascii \"{}\"", escape(val)),
+        }
+    }
+
+    pub fn write_instruction(
+        &self,
+        w: &mut impl fmt::Write,
+        instr: &InstructionAsm,
+    ) -> fmt::Result {
+        match instr {
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                src: src @ OperandAsm::Imm { int },
+                dst,
+            } if i32::try_from(*int).is_err() => self.two(w, "movabs", AsmType::Quadword, src, dst),
+            InstructionAsm::Mov {
+                ty: AsmType::Double,
+                src,
+                dst,
+            } => self.two(w, "movsd", AsmType::Double, src, dst),
+            InstructionAsm::Mov { ty, src, dst } => self.two(w, "mov", *ty, src, dst),
+            InstructionAsm::Ret => w.write_str("ret"),
+            InstructionAsm::Unary { ty, unop, operand } => {
+                self.one(w, &unop.to_string(), *ty, operand)
+            }
+            InstructionAsm::AllocStack { off } => match self.syntax {
+                Syntax::Att => write!(w, "subq ${}, %rsp", -off),
+                Syntax::Intel => write!(w, "sub rsp, {}", -off),
+            },
+            InstructionAsm::DeallocStack { off } => match self.syntax {
+                Syntax::Att => write!(w, "addq ${}, %rsp", -off),
+                Syntax::Intel => write!(w, "add rsp, {}", -off),
+            },
+            InstructionAsm::Cdq { ty } => match ty {
+                AsmType::Quadword => w.write_str("cqo"),
+                _ => w.write_str("cdq"),
+            },
+            InstructionAsm::Binary {
+                ty: AsmType::Double,
+                binop,
+                src,
+                dst,
+            } => {
+                let mnemonic = match binop {
+                    AsmBinaryOp::Xor => String::from("xorpd"),
+                    AsmBinaryOp::Imul => String::from("mulsd"),
+                    _ => format!("{}sd", binop),
+                };
+                self.two(w, &mnemonic, AsmType::Double, src, dst)
+            }
+            InstructionAsm::Binary {
+                ty,
+                binop: binop @ (AsmBinaryOp::Shl | AsmBinaryOp::Sar | AsmBinaryOp::Shr),
+                src: src @ OperandAsm::Reg { .. },
+                dst,
+            } => self.shift_by_register(w, &binop.to_string(), *ty, src, dst),
+            InstructionAsm::Binary {
+                ty,
+                binop,
+                src,
+                dst,
+            } => self.two(w, &binop.to_string(), *ty, src, dst),
+            InstructionAsm::Idiv { ty, operand } => self.one(w, "idiv", *ty, operand),
+            InstructionAsm::Div { ty, operand } => self.one(w, "div", *ty, operand),
+            InstructionAsm::Push { r } => self.one_reg(w, "push", *r),
+            InstructionAsm::Pop { r } => self.one_reg(w, "pop", *r),
+            InstructionAsm::MovQ { src, dst } => {
+                let (first, second) = match self.syntax {
+                    Syntax::Att => (src, dst),
+                    Syntax::Intel => (dst, src),
+                };
+                w.write_str(match self.syntax {
+                    Syntax::Att => "movq ",
+                    Syntax::Intel => "mov ",
+                })?;
+                self.register(w, *first, AsmType::Quadword)?;
+                w.write_str(", ")?;
+                self.register(w, *second, AsmType::Quadword)
+            }
+            InstructionAsm::Cmp {
+                ty: AsmType::Double,
+                src,
+                dst,
+            } => self.two(w, "comisd", AsmType::Double, src, dst),
+            InstructionAsm::Cmp { ty, src, dst } => self.two(w, "cmp", *ty, src, dst),
+            InstructionAsm::Cvttsd2si { ty, src, dst } => {
+                self.convert(w, "cvttsd2si", (AsmType::Double, src), (*ty, dst), *ty)
+            }
+            InstructionAsm::Cvtsi2sd { ty, src, dst } => {
+                self.convert(w, "cvtsi2sd", (*ty, src), (AsmType::Double, dst), *ty)
+            }
+            InstructionAsm::Jmp { target } => write!(w, "jmp {}", self.target.local_label(target)),
+            InstructionAsm::Call { name } => write!(w, "call {}", self.call_symbol(&name.as_str())),
+            InstructionAsm::TailCall { name } => {
+                write!(w, "jmp {}", self.call_symbol(&name.as_str()))
+            }
+            InstructionAsm::JmpCC { cc, target } => {
+                write!(w, "j{} {}", cc, self.target.local_label(target))
+            }
+            InstructionAsm::SetCC { cc, operand } => {
+                write!(w, "set{} ", cc)?;
+                self.write_operand(w, operand, AsmType::Byte)
+            }
+            InstructionAsm::Movzx {
+                src_ty,
+                dst_ty,
+                src,
+                dst,
+            } => self.extend(w, "movz", (*src_ty, src), (*dst_ty, dst)),
+            InstructionAsm::Movsx {
+                src_ty,
+                dst_ty,
+                src,
+                dst,
+            } => self.extend(w, "movs", (*src_ty, src), (*dst_ty, dst)),
+            InstructionAsm::Cmov { ty, cc, src, dst } => {
+                self.two(w, &format!("cmov{}", cc), *ty, src, dst)
+            }
+            InstructionAsm::Lea { src, dst } => self.two(w, "lea", AsmType::Quadword, src, dst),
+            InstructionAsm::Label { name } => write!(w, "{}:", self.target.local_label(name)),
+            InstructionAsm::Comment { text } => write!(w, "# {}", text),
+            InstructionAsm::Cfi { directive } => self.write_cfi(w, directive),
+            InstructionAsm::Loc { file, line, col } => {
+                write!(w, ".loc {} {} {}", file, line, col)
+            }
+        }
+    }
+
+    /// a CFI directive, naming registers by their DWARF numbers as gcc does,
+    /// which reads the same in either syntax. The `.seh_*` directives name them
+    /// as instructions do. `write_fundef` starts a Windows function itself,
+    /// since `.seh_proc` takes its name.
+    fn write_cfi(&self, w: &mut impl fmt::Write, directive: &CfiDirective) -> fmt::Result {
+        match directive {
+            CfiDirective::StartProc => w.write_str(".cfi_startproc"),
+            CfiDirective::EndProc if !self.target.has_cfi() => w.write_str(".seh_endproc"),
+            CfiDirective::EndProc => w.write_str(".cfi_endproc"),
+            CfiDirective::DefCfaOffset { off } => write!(w, ".cfi_def_cfa_offset {}", off),
+            CfiDirective::DefCfaRegister { r } => {
+                write!(w, ".cfi_def_cfa_register {}", r.dwarf_no())
+            }
+            CfiDirective::DefCfa { r, off } => write!(w, ".cfi_def_cfa {}, {}", r.dwarf_no(), off),
+            CfiDirective::Offset { r, off } => write!(w, ".cfi_offset {}, {}", r.dwarf_no(), off),
+            CfiDirective::RememberState => w.write_str(".cfi_remember_state"),
+            CfiDirective::RestoreState => w.write_str(".cfi_restore_state"),
+            CfiDirective::PushReg { r } => {
+                w.write_str(".seh_pushreg ")?;
+                self.register(w, *r, AsmType::Quadword)
+            }
+            CfiDirective::StackAlloc { size } => write!(w, ".seh_stackalloc {}", size),
+            CfiDirective::SetFrame { r, off } => {
+                w.write_str(".seh_setframe ")?;
+                self.register(w, *r, AsmType::Quadword)?;
+                write!(w, ", {}", off)
+            }
+            CfiDirective::EndPrologue => w.write_str(".seh_endprologue"),
+        }
+    }
+
+    pub fn write_operand(
+        &self,
+        w: &mut impl fmt::Write,
+        operand: &OperandAsm,
+        ty: AsmType,
+    ) -> fmt::Result {
+        match (self.syntax, operand) {
+            // only reachable when printing code before pseudo replacement
+            (_, OperandAsm::Pseudo { name }) => write!(w, "{}", name),
+            (_, OperandAsm::Reg { r }) => self.register(w, *r, ty),
+            (Syntax::Att, OperandAsm::Imm { int }) => write!(w, "${}", int),
+            (Syntax::Att, OperandAsm::Stack { off }) => write!(w, "{}(%rbp)", off),
+            (Syntax::Att, OperandAsm::Memory { base, off }) => {
+                write!(w, "{}(", off)?;
+                self.register(w, *base, AsmType::Quadword)?;
+                w.write_char(')')
+            }
+            (Syntax::Att, OperandAsm::Indexed { base, index, scale }) => {
+                w.write_char('(')?;
+                self.register(w, *base, AsmType::Quadword)?;
+                w.write_char(',')?;
+                self.register(w, *index, AsmType::Quadword)?;
+                write!(w, ",{})", scale)
+            }
+            (Syntax::Intel, OperandAsm::Imm { int }) => write!(w, "{}", int),
+            (Syntax::Intel, OperandAsm::Indexed { base, index, scale }) => {
+                write!(w, "{} PTR [", intel_ptr(ty))?;
+                self.register(w, *base, AsmType::Quadword)?;
+                w.write_char('+')?;
+                self.register(w, *index, AsmType::Quadword)?;
+                write!(w, "*{}]", scale)
+            }
+            (Syntax::Intel, OperandAsm::Stack { off }) => {
+                self.intel_memory(w, Register::BP, *off, ty)
+            }
+            (Syntax::Intel, OperandAsm::Memory { base, off }) => {
+                self.intel_memory(w, *base, *off, ty)
+            }
+            (Syntax::Att, OperandAsm::Data { name }) => {
+                write!(w, "{}(%rip)", self.target.symbol(&name.as_str()))
+            }
+            (Syntax::Intel, OperandAsm::Data { name }) => write!(
+                w,
+                "{} PTR [rip+{}]",
+                intel_ptr(ty),
+                self.target.symbol(&name.as_str())
+            ),
+            (Syntax::Att, OperandAsm::Got { name }) => {
+                write!(w, "{}@GOTPCREL(%rip)", self.target.symbol(&name.as_str()))
+            }
+            (Syntax::Intel, OperandAsm::Got { name }) => write!(
+                w,
+                "QWORD PTR [rip+{}@GOTPCREL]",
+                self.target.symbol(&name.as_str())
+            ),
+        }
+    }
+
+    /// how a call names the function `name`: through the PLT, as the target
+    /// spells it, unless the output is linked into a static executable.
+    fn call_symbol(&self, name: &str) -> String {
+        match self.relocation {
+            RelocationModel::Static => self.target.symbol(name),
+            RelocationModel::Pie | RelocationModel::Pic => self.target.call_symbol(name),
+        }
+    }
+
+    fn intel_memory(
+        &self,
+        w: &mut impl fmt::Write,
+        base: Register,
+        off: i32,
+        ty: AsmType,
+    ) -> fmt::Result {
+        write!(w, "{} PTR [", intel_ptr(ty))?;
+        self.register(w, base, AsmType::Quadword)?;
+        write!(w, "{}{}]", if off < 0 { "-" } else { "+" }, off.abs())
+    }
+
+    /// an instruction with a single operand; AT&T appends the size suffix.
+    fn one(
+        &self,
+        w: &mut impl fmt::Write,
+        mnemonic: &str,
+        ty: AsmType,
+        operand: &OperandAsm,
+    ) -> fmt::Result {
+        match self.syntax {
+            Syntax::Att => write!(w, "{}{} ", mnemonic, ty.suffix())?,
+            Syntax::Intel => write!(w, "{} ", mnemonic)?,
+        }
+        self.write_operand(w, operand, ty)
+    }
+
+    /// a conversion between a `double` and an integer, each operand at its own
+    /// size; AT&T appends the integer's size suffix.
+    fn convert(
+        &self,
+        w: &mut impl fmt::Write,
+        mnemonic: &str,
+        src: (AsmType, &OperandAsm),
+        dst: (AsmType, &OperandAsm),
+        int_ty: AsmType,
+    ) -> fmt::Result {
+        let (first, second) = match self.syntax {
+            Syntax::Att => {
+                write!(w, "{}{} ", mnemonic, int_ty.suffix())?;
+                (src, dst)
+            }
+            Syntax::Intel => {
+                write!(w, "{} ", mnemonic)?;
+                (dst, src)
+            }
+        };
+        self.write_operand(w, first.1, first.0)?;
+        w.write_str(", ")?;
+        self.write_operand(w, second.1, second.0)
+    }
+
+    /// a sign or zero extension, `movs` or `movz`, each operand at its own size.
+    /// AT&T appends both sizes' suffixes, and Intel spells a longword's sign
+    /// extension `movsxd`.
+    fn extend(
+        &self,
+        w: &mut impl fmt::Write,
+        mnemonic: &str,
+        src: (AsmType, &OperandAsm),
+        dst: (AsmType, &OperandAsm),
+    ) -> fmt::Result {
+        let (first, second) = match self.syntax {
+            Syntax::Att => {
+                write!(w, "{}{}{} ", mnemonic, src.0.suffix(), dst.0.suffix())?;
+                (src, dst)
+            }
+            Syntax::Intel => {
+                match src.0 {
+                    AsmType::Longword => write!(w, "{}xd ", mnemonic)?,
+                    _ => write!(w, "{}x ", mnemonic)?,
+                }
+                (dst, src)
+            }
+        };
+        self.write_operand(w, first.1, first.0)?;
+        w.write_str(", ")?;
+        self.write_operand(w, second.1, second.0)
+    }
+
+    /// a push or pop, which always moves the full 64-bit register.
+    fn one_reg(&self, w: &mut impl fmt::Write, mnemonic: &str, r: Register) -> fmt::Result {
+        match self.syntax {
+            Syntax::Att => write!(w, "{}q ", mnemonic)?,
+            Syntax::Intel => write!(w, "{} ", mnemonic)?,
+        }
+        self.register(w, r, AsmType::Quadword)
+    }
+
+    /// an instruction with a source and destination; Intel puts the destination first.
+    fn two(
+        &self,
+        w: &mut impl fmt::Write,
+        mnemonic: &str,
+        ty: AsmType,
+        src: &OperandAsm,
+        dst: &OperandAsm,
+    ) -> fmt::Result {
+        let (first, second) = match self.syntax {
+            Syntax::Att => {
+                write!(w, "{}{} ", mnemonic, ty.suffix())?;
+                (src, dst)
+            }
+            Syntax::Intel => {
+                write!(w, "{} ", mnemonic)?;
+                (dst, src)
+            }
+        };
+        self.write_operand(w, first, ty)?;
+        w.write_str(", ")?;
+        self.write_operand(w, second, ty)
+    }
+
+    /// a shift by the count in a register, which is `%cl` whatever the size
+    /// of what is shifted, as only its low byte is read.
+    fn shift_by_register(
+        &self,
+        w: &mut impl fmt::Write,
+        mnemonic: &str,
+        ty: AsmType,
+        src: &OperandAsm,
+        dst: &OperandAsm,
+    ) -> fmt::Result {
+        let (first, second) = match self.syntax {
+            Syntax::Att => {
+                write!(w, "{}{} ", mnemonic, ty.suffix())?;
+                ((AsmType::Byte, src), (ty, dst))
+            }
+            Syntax::Intel => {
+                write!(w, "{} ", mnemonic)?;
+                ((ty, dst), (AsmType::Byte, src))
+            }
+        };
+        self.write_operand(w, first.1, first.0)?;
+        w.write_str(", ")?;
+        self.write_operand(w, second.1, second.0)
+    }
+
+    fn register(&self, w: &mut impl fmt::Write, r: Register, ty: AsmType) -> fmt::Result {
+        if self.syntax == Syntax::Att {
+            w.write_char('%')?;
+        }
+        w.write_str(r.name(ty))
+    }
+}
+
+/// runs one of the formatter's writers into a fresh string.
+fn to_string(write: impl FnOnce(&mut String) -> fmt::Result) -> String {
+    let mut res = String::new();
+    write(&mut res).expect("writing to a String can't fail");
+    res
+}
+
+/// the size Intel syntax gives a memory operand.
+fn intel_ptr(ty: AsmType) -> &'static str {
+    match ty {
+        AsmType::Byte => "BYTE",
+        AsmType::Word => "WORD",
+        AsmType::Longword => "DWORD",
+        AsmType::Quadword | AsmType::Double => "QWORD",
+    }
+}
+
+/// `NT_GNU_PROPERTY_TYPE_0` note whose x86 feature property sets IBT (bit 0) and
+/// SHSTK (bit 1), laid out the way gcc -fcf-protection writes it.
+const GNU_PROPERTY_NOTE: &str = "\t.section .note.gnu.property,\"a\"
+\t.align 8
+\t.long 4
+\t.long 16
+\t.long 5
+\t.string \"GNU\"
+\t.long 0xc0000002
+\t.long 4
+\t.long 3
+\t.align 8
+";
+
+/// Converts ASM AST to syntax and writes to output file
+pub fn emit_asm(
+    asmprog: ProgramAsm,
+    output_file: String,
+    formatter: AsmFormatter,
+) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(output_file)?);
+    emit_asm_to(&asmprog, &mut file, formatter)?;
+    file.flush()
+}
+
+/// Writes the ASM AST as text to `w` as it is formatted, so the whole listing
+/// never sits in memory. Wrap unbuffered writers in a `BufWriter`.
+pub fn emit_asm_to<W: Write>(
+    asmprog: &ProgramAsm,
+    w: &mut W,
+    formatter: AsmFormatter,
+) -> io::Result<()> {
+    let mut adapter = IoWriter {
+        inner: w,
+        result: Ok(()),
+    };
+    match formatter.write_program(&mut adapter, asmprog) {
+        Ok(()) => Ok(()),
+        // a formatting error only ever comes from a failed write, kept in the adapter
+        Err(fmt::Error) => adapter
+            .result
+            .and(Err(io::Error::other("(!) formatting assembly failed"))),
+    }
+}
+
+/// lets the formatter's `fmt::Write` methods write to an `io::Write`, keeping
+/// the io error that `fmt::Error` has no room for.
+struct IoWriter<'a, W: Write> {
+    inner: &'a mut W,
+    result: io::Result<()>,
+}
+
+impl<W: Write> fmt::Write for IoWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.result = Err(e);
+            fmt::Error
+        })
+    }
+}
+
+/// the bytes `init` starts out as, little-endian, or `None` if they are all
+/// zero. An address is left zero, for a relocation to fill in.
+fn static_bytes(init: &StaticInit) -> Option<Vec<u8>> {
+    match init {
+        StaticInit::Scalar { c } if c.value() != 0 => {
+            Some(c.value().to_le_bytes()[..init.ty().size()].to_vec())
+        }
+        StaticInit::Scalar { .. } | StaticInit::Zero { .. } => None,
+        StaticInit::String {
+            val,
+            null_terminated,
+        } => Some(
+            val.iter()
+                .copied()
+                .chain(null_terminated.then_some(0))
+                .collect(),
+        ),
+        StaticInit::Address { .. } => Some(vec![0; 8]),
+    }
+}
+
+/// Encodes the ASM AST and writes it to a relocatable object file.
+/// Only ELF objects, for the Linux target, are supported. Every call goes
+/// through the PLT, and a callee the program doesn't define is left undefined
+/// for the linker. A variable holding the address of a constant gets it from
+/// an absolute relocation. The compiler's own constants get no symbol, as
+/// with the assembler's local labels; they are reached from their section's
+/// symbol instead. Each function's CFI directives become its entry in
+/// `.eh_frame`, which points at the function with a %pc-relative relocation.
+pub fn emit_object(
+    asmprog: ProgramAsm,
+    output_file: String,
+    target: Target,
+    function_sections: bool,
+    cf_protection: bool,
+) -> Result<(), CompileError> {
+    if target != Target::Linux {
+        return Err(CompileError::FileIo {
+            e: io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "(!) object files can only be emitted for linux, not {}",
+                    target.name()
+                ),
+            ),
+        });
+    }
+
+    let mut obj = Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+    let shared_text = obj.section_id(object::write::StandardSection::Text);
+    let scope = |global| match global {
+        true => SymbolScope::Dynamic,
+        false => SymbolScope::Compilation,
+    };
+    // each symbol's definition, and how far past it the name points
+    let mut symbols = HashMap::new();
+    let mut refs = Vec::new();
+    let mut addresses = Vec::new();
+    let mut eh_frame = CIE.to_vec();
+    // where each function's address goes in `.eh_frame`
+    let mut frame_starts = Vec::new();
+    // and the section symbol and offset it is given by, which unlike the
+    // function's own symbol a shared library can't have another module define
+    let mut functions = Vec::new();
+    for item in asmprog.items.iter() {
+        let TopLevelAsm::StaticVariable { name, global, init } = item else {
+            continue;
+        };
+        let (size, alignment) = (
+            init.ty().size() as u64,
+            init.ty().variable_alignment() as u64,
+        );
+        let (section, value) = match static_bytes(init) {
+            None => {
+                let bss = obj.section_id(object::write::StandardSection::UninitializedData);
+                (bss, obj.append_section_bss(bss, size, alignment))
+            }
+            Some(bytes) => {
+                let data = obj.section_id(object::write::StandardSection::Data);
+                (data, obj.append_section_data(data, &bytes, alignment))
+            }
+        };
+        if let StaticInit::Address { name: target, .. } = init {
+            addresses.push((section, value, *target));
+        }
+        let id = obj.add_symbol(Symbol {
+            name: name.as_str().as_bytes().to_vec(),
+            value,
+            size,
+            kind: SymbolKind::Data,
+            scope: scope(*global),
+            weak: false,
+            section: SymbolSection::Section(section),
+            flags: SymbolFlags::None,
+        });
+        symbols.insert(*name, (id, 0));
+    }
+    for item in asmprog.items.iter() {
+        let TopLevelAsm::StaticConstant { name, init } = item else {
+            continue;
+        };
+        let rodata = obj.section_id(object::write::StandardSection::ReadOnlyData);
+        let size = init.ty().size() as u64;
+        let bytes = static_bytes(init).unwrap_or_else(|| vec![0; size as usize]);
+        let alignment = match init {
+            StaticInit::String { .. } => 1,
+            _ => 8,
+        };
+        let value = obj.append_section_data(rodata, &bytes, alignment);
+        if let StaticInit::Address { name: target, .. } = init {
+            addresses.push((rodata, value, *target));
+        }
+        symbols.insert(*name, (obj.section_symbol(rodata), value as i64));
+    }
+    for (section, offset, target) in addresses {
+        let (symbol, past) = symbols[&target];
+        obj.add_relocation(
+            section,
+            Relocation {
+                offset,
+                symbol,
+                addend: past,
+                flags: RelocationFlags::Elf {
+                    r_type: object::elf::R_X86_64_64,
+                },
+            },
+        )
+        .map_err(|e| CompileError::FileIo {
+            e: io::Error::other(e.to_string()),
+        })?;
+    }
+    for fundef in asmprog.functions() {
+        let text = if function_sections {
+            let name = format!(".text.{}", fundef.identifier).into_bytes();
+            obj.add_section(vec![], name, SectionKind::Text)
+        } else {
+            shared_text
+        };
+        let mut code = Vec::new();
+        if cf_protection {
+            code.extend(ENDBR64);
+        }
+        let body =
+            encode_function(&fundef.instructions).map_err(|e| CompileError::Internal { e })?;
+        let start = code.len();
+        code.extend(body.code);
+        let size = code.len() as u64;
+        let offset = obj.append_section_data(text, &code, 16);
+        refs.extend(
+            body.refs
+                .into_iter()
+                .map(|r| (text, offset + start as u64, r)),
+        );
+        // the frame description covers the landing pad too, as gcc's does
+        let cfi: Vec<_> = body
+            .cfi
+            .into_iter()
+            .map(|(at, d)| (start + at, d))
+            .collect();
+        let instructions = encode_cfi(&cfi);
+        // the CIE pointer, the function's address and size, no augmentation
+        // data, and the instructions, padded to 8 bytes with DW_CFA_nop
+        let length = (4 + 4 + 4 + 1 + instructions.len()).next_multiple_of(8) - 4;
+        let fde = eh_frame.len();
+        eh_frame.extend((length as u32).to_le_bytes());
+        eh_frame.extend((fde as u32 + 4).to_le_bytes());
+        frame_starts.push(eh_frame.len() as u64);
+        eh_frame.extend([0; 4]);
+        eh_frame.extend((size as u32).to_le_bytes());
+        eh_frame.push(0);
+        eh_frame.extend(instructions);
+        eh_frame.resize(fde + 4 + length, 0);
+        let id = obj.add_symbol(Symbol {
+            name: fundef.identifier.as_str().as_bytes().to_vec(),
+            value: offset,
+            size,
+            kind: SymbolKind::Text,
+            scope: scope(fundef.global),
+            weak: false,
+            section: SymbolSection::Section(text),
+            flags: SymbolFlags::None,
+        });
+        symbols.insert(fundef.identifier, (id, 0));
+        functions.push((obj.section_symbol(text), offset as i64));
+    }
+    let eh_frame_section = obj.add_section(
+        vec![],
+        b".eh_frame".to_vec(),
+        SectionKind::Elf(object::elf::SHT_X86_64_UNWIND),
+    );
+    obj.section_mut(eh_frame_section).flags = SectionFlags::Elf {
+        sh_flags: object::elf::SHF_ALLOC.into(),
+    };
+    obj.append_section_data(eh_frame_section, &eh_frame, 8);
+    for (offset, (symbol, addend)) in frame_starts.into_iter().zip(functions) {
+        obj.add_relocation(
+            eh_frame_section,
+            Relocation {
+                offset,
+                symbol,
+                addend,
+                flags: RelocationFlags::Elf {
+                    r_type: object::elf::R_X86_64_PC32,
+                },
+            },
+        )
+        .map_err(|e| CompileError::FileIo {
+            e: io::Error::other(e.to_string()),
+        })?;
+    }
+    for (section, start, symbol_ref) in refs {
+        // the displacement is taken from the end of the instruction, which for
+        // a call is the end of its 4-byte field
+        let (at, name, kind, addend, r_type) = match symbol_ref {
+            SymbolRef::Call { at, name } => {
+                (at, name, SymbolKind::Text, -4, object::elf::R_X86_64_PLT32)
+            }
+            SymbolRef::Data { at, name, trailing } => (
+                at,
+                name,
+                SymbolKind::Data,
+                -4 - trailing as i64,
+                object::elf::R_X86_64_PC32,
+            ),
+            SymbolRef::Got { at, name, trailing } => (
+                at,
+                name,
+                SymbolKind::Data,
+                -4 - trailing as i64,
+                object::elf::R_X86_64_GOTPCREL,
+            ),
+        };
+        let (symbol, past) = *symbols.entry(name).or_insert_with(|| {
+            let id = obj.add_symbol(Symbol {
+                name: name.as_str().as_bytes().to_vec(),
+                value: 0,
+                size: 0,
+                kind,
+                scope: SymbolScope::Dynamic,
+                weak: false,
+                section: SymbolSection::Undefined,
+                flags: SymbolFlags::None,
+            });
+            (id, 0)
+        });
+        obj.add_relocation(
+            section,
+            Relocation {
+                offset: start + at as u64,
+                symbol,
+                addend: addend + past,
+                flags: RelocationFlags::Elf { r_type },
+            },
+        )
+        .map_err(|e| CompileError::FileIo {
+            e: io::Error::other(e.to_string()),
+        })?;
+    }
+    if cf_protection {
+        obj.add_elf_gnu_property_u32(
+            object::elf::GNU_PROPERTY_X86_FEATURE_1_AND,
+            object::elf::GNU_PROPERTY_X86_FEATURE_1_IBT
+                | object::elf::GNU_PROPERTY_X86_FEATURE_1_SHSTK,
+        );
+    }
+    // same non-executable stack marker the assembler output carries
+    obj.add_section(
+        vec![],
+        b".note.GNU-stack".to_vec(),
+        SectionKind::Elf(object::elf::SHT_PROGBITS),
+    );
+
+    let bytes = obj.write().map_err(|e| CompileError::FileIo {
+        e: io::Error::other(e.to_string()),
+    })?;
+    fs::write(output_file, bytes).map_err(|e| CompileError::FileIo { e })
+}
+
+#[cfg(test)]
+use super::{
+    asmgen::{AsmUnaryOp, CondCode},
+    parser::CType,
+    symbol,
+};
+
+/// a function touching every instruction and operand variant, in forms the
+/// assembler accepts, and the variable it stores to.
+#[cfg(test)]
+fn every_variant() -> ProgramAsm {
+    ProgramAsm {
+        items: vec![
+            TopLevelAsm::Function {
+                fundef: FunDefAsm {
+                    identifier: symbol::Symbol::intern("main"),
+                    global: true,
+                    instructions: vec![
+                        InstructionAsm::Push { r: Register::BP },
+                        InstructionAsm::MovQ {
+                            src: Register::SP,
+                            dst: Register::BP,
+                        },
+                        InstructionAsm::AllocStack { off: -16 },
+                        InstructionAsm::Push { r: Register::BX },
+                        InstructionAsm::Mov {
+                            ty: AsmType::Longword,
+                            src: OperandAsm::Imm { int: 7 },
+                            dst: OperandAsm::Stack { off: -4 },
+                        },
+                        InstructionAsm::Unary {
+                            ty: AsmType::Longword,
+                            unop: AsmUnaryOp::Neg,
+                            operand: OperandAsm::Stack { off: -4 },
+                        },
+                        InstructionAsm::Unary {
+                            ty: AsmType::Longword,
+                            unop: AsmUnaryOp::Not,
+                            operand: OperandAsm::Reg { r: Register::R10 },
+                        },
+                        InstructionAsm::Binary {
+                            ty: AsmType::Longword,
+                            binop: AsmBinaryOp::Add,
+                            src: OperandAsm::Imm { int: 1 },
+                            dst: OperandAsm::Stack { off: -4 },
+                        },
+                        InstructionAsm::Binary {
+                            ty: AsmType::Longword,
+                            binop: AsmBinaryOp::Imul,
+                            src: OperandAsm::Stack { off: -4 },
+                            dst: OperandAsm::Reg { r: Register::R11 },
+                        },
+                        InstructionAsm::Binary {
+                            ty: AsmType::Longword,
+                            binop: AsmBinaryOp::Sar,
+                            src: OperandAsm::Imm { int: 2 },
+                            dst: OperandAsm::Reg { r: Register::AX },
+                        },
+                        InstructionAsm::Binary {
+                            ty: AsmType::Quadword,
+                            binop: AsmBinaryOp::Shl,
+                            src: OperandAsm::Reg { r: Register::CX },
+                            dst: OperandAsm::Stack { off: -16 },
+                        },
+                        InstructionAsm::Cdq {
+                            ty: AsmType::Longword,
+                        },
+                        InstructionAsm::Idiv {
+                            ty: AsmType::Longword,
+                            operand: OperandAsm::Stack { off: -8 },
+                        },
+                        InstructionAsm::Mov {
+                            ty: AsmType::Longword,
+                            src: OperandAsm::Memory {
+                                base: Register::SP,
+                                off: 12,
+                            },
+                            dst: OperandAsm::Reg { r: Register::R10 },
+                        },
+                        InstructionAsm::Mov {
+                            ty: AsmType::Longword,
+                            src: OperandAsm::Imm { int: 5 },
+                            dst: OperandAsm::Data {
+                                name: symbol::Symbol::intern("counter"),
+                            },
+                        },
+                        InstructionAsm::Mov {
+                            ty: AsmType::Double,
+                            src: OperandAsm::Data {
+                                name: symbol::Symbol::intern("half"),
+                            },
+                            dst: OperandAsm::Reg { r: Register::XMM1 },
+                        },
+                        InstructionAsm::Binary {
+                            ty: AsmType::Double,
+                            binop: AsmBinaryOp::DivDouble,
+                            src: OperandAsm::Stack { off: -16 },
+                            dst: OperandAsm::Reg { r: Register::XMM1 },
+                        },
+                        InstructionAsm::Cmp {
+                            ty: AsmType::Double,
+                            src: OperandAsm::Reg { r: Register::XMM0 },
+                            dst: OperandAsm::Reg { r: Register::XMM1 },
+                        },
+                        InstructionAsm::Cvttsd2si {
+                            ty: AsmType::Quadword,
+                            src: OperandAsm::Reg { r: Register::XMM1 },
+                            dst: OperandAsm::Reg { r: Register::AX },
+                        },
+                        InstructionAsm::Cvtsi2sd {
+                            ty: AsmType::Longword,
+                            src: OperandAsm::Stack { off: -4 },
+                            dst: OperandAsm::Reg { r: Register::XMM0 },
+                        },
+                        InstructionAsm::Cmp {
+                            ty: AsmType::Longword,
+                            src: OperandAsm::Imm { int: 0 },
+                            dst: OperandAsm::Reg { r: Register::DX },
+                        },
+                        InstructionAsm::SetCC {
+                            cc: CondCode::LE,
+                            operand: OperandAsm::Reg { r: Register::AX },
+                        },
+                        InstructionAsm::Movzx {
+                            src_ty: AsmType::Byte,
+                            dst_ty: AsmType::Longword,
+                            src: OperandAsm::Reg { r: Register::AX },
+                            dst: OperandAsm::Reg { r: Register::R11 },
+                        },
+                        InstructionAsm::Movsx {
+                            src_ty: AsmType::Word,
+                            dst_ty: AsmType::Quadword,
+                            src: OperandAsm::Stack { off: -2 },
+                            dst: OperandAsm::Reg { r: Register::R10 },
+                        },
+                        InstructionAsm::Mov {
+                            ty: AsmType::Byte,
+                            src: OperandAsm::Reg { r: Register::R10 },
+                            dst: OperandAsm::Stack { off: -1 },
+                        },
+                        InstructionAsm::Cmov {
+                            ty: AsmType::Longword,
+                            cc: CondCode::GE,
+                            src: OperandAsm::Stack { off: -8 },
+                            dst: OperandAsm::Reg { r: Register::R11 },
+                        },
+                        InstructionAsm::Lea {
+                            src: OperandAsm::Indexed {
+                                base: Register::AX,
+                                index: Register::DX,
+                                scale: 4,
+                            },
+                            dst: OperandAsm::Reg { r: Register::AX },
+                        },
+                        InstructionAsm::JmpCC {
+                            cc: CondCode::E,
+                            target: String::from("end"),
+                        },
+                        InstructionAsm::Jmp {
+                            target: String::from("end"),
+                        },
+                        InstructionAsm::Label {
+                            name: String::from("end"),
+                        },
+                        InstructionAsm::Mov {
+                            ty: AsmType::Longword,
+                            src: OperandAsm::Imm { int: 65 },
+                            dst: OperandAsm::Reg { r: Register::DI },
+                        },
+                        InstructionAsm::Call {
+                            name: symbol::Symbol::intern("putchar"),
+                        },
+                        InstructionAsm::Pop { r: Register::BX },
+                        InstructionAsm::DeallocStack { off: -16 },
+                        InstructionAsm::AllocStack { off: -16 },
+                        InstructionAsm::MovQ {
+                            src: Register::BP,
+                            dst: Register::SP,
+                        },
+                        InstructionAsm::Pop { r: Register::BP },
+                        InstructionAsm::Ret,
+                    ],
+                },
+            },
+            TopLevelAsm::StaticVariable {
+                name: symbol::Symbol::intern("counter"),
+                global: false,
+                init: StaticInit::Scalar {
+                    c: Const::Int { int: 0 },
+                },
+            },
+            TopLevelAsm::StaticVariable {
+                name: symbol::Symbol::intern("letter"),
+                global: true,
+                init: StaticInit::Scalar {
+                    c: Const::Char { char: 65 },
+                },
+            },
+            TopLevelAsm::StaticVariable {
+                name: symbol::Symbol::intern("table"),
+                global: false,
+                init: StaticInit::Zero {
+                    ty: CType::Long.array_of(3),
+                },
+            },
+            TopLevelAsm::StaticConstant {
+                name: symbol::Symbol::intern("greeting"),
+                init: StaticInit::String {
+                    val: b"hi\n".to_vec(),
+                    null_terminated: true,
+                },
+            },
+            TopLevelAsm::StaticConstant {
+                name: symbol::Symbol::intern("half"),
+                init: StaticInit::Scalar {
+                    c: Const::Double { double: 0.5 },
+                },
+            },
+        ],
+    }
+}
+
+#[test]
+fn emit_every_variant_att() {
+    assert_eq!(
+        AsmFormatter::new(Syntax::Att, Target::Linux).program(&every_variant()),
+        "\t.text
+\t.globl main
+\t.type main, @function
+main:
+\tpushq %rbp
+\tmovq %rsp, %rbp
+\tsubq $16, %rsp
+\tpushq %rbx
+\tmovl $7, -4(%rbp)
+\tnegl -4(%rbp)
+\tnotl %r10d
+\taddl $1, -4(%rbp)
+\timull -4(%rbp), %r11d
+\tsarl $2, %eax
+\tshlq %cl, -16(%rbp)
+\tcdq
+\tidivl -8(%rbp)
+\tmovl 12(%rsp), %r10d
+\tmovl $5, counter(%rip)
+\tmovsd half(%rip), %xmm1
+\tdivsd -16(%rbp), %xmm1
+\tcomisd %xmm0, %xmm1
+\tcvttsd2siq %xmm1, %rax
+\tcvtsi2sdl -4(%rbp), %xmm0
+\tcmpl $0, %edx
+\tsetle %al
+\tmovzbl %al, %r11d
+\tmovswq -2(%rbp), %r10
+\tmovb %r10b, -1(%rbp)
+\tcmovgel -8(%rbp), %r11d
+\tleaq (%rax,%rdx,4), %rax
+\tje .Lend
+\tjmp .Lend
+.Lend:
+\tmovl $65, %edi
+\tcall putchar@PLT
+\tpopq %rbx
+\taddq $16, %rsp
+\tsubq $16, %rsp
+\tmovq %rbp, %rsp
+\tpopq %rbp
+\tret
+\t.size main, .-main
+\t.bss
+\t.balign 4
+\t.type counter, @object
+\t.size counter, 4
+counter:
+\t.zero 4
+\t.globl letter
+\t.data
+\t.balign 1
+\t.type letter, @object
+\t.size letter, 1
+letter:
+\t.byte 65
+\t.bss
+\t.balign 16
+\t.type table, @object
+\t.size table, 24
+table:
+\t.zero 24
+\t.section .rodata
+greeting:
+\t.asciz \"hi\\012\"
+\t.section .rodata
+\t.balign 8
+half:
+\t.quad 4602678819172646912
+\t.section .note.GNU-stack,\"\",@progbits
+"
+    );
+}
+
+#[test]
+fn emit_every_variant_intel() {
+    assert_eq!(
+        AsmFormatter::new(Syntax::Intel, Target::Linux).program(&every_variant()),
+        "\t.intel_syntax noprefix
+\t.text
+\t.globl main
+\t.type main, @function
+main:
+\tpush rbp
+\tmov rbp, rsp
+\tsub rsp, 16
+\tpush rbx
+\tmov DWORD PTR [rbp-4], 7
+\tneg DWORD PTR [rbp-4]
+\tnot r10d
+\tadd DWORD PTR [rbp-4], 1
+\timul r11d, DWORD PTR [rbp-4]
+\tsar eax, 2
+\tshl QWORD PTR [rbp-16], cl
+\tcdq
+\tidiv DWORD PTR [rbp-8]
+\tmov r10d, DWORD PTR [rsp+12]
+\tmov DWORD PTR [rip+counter], 5
+\tmovsd xmm1, QWORD PTR [rip+half]
+\tdivsd xmm1, QWORD PTR [rbp-16]
+\tcomisd xmm1, xmm0
+\tcvttsd2si rax, xmm1
+\tcvtsi2sd xmm0, DWORD PTR [rbp-4]
+\tcmp edx, 0
+\tsetle al
+\tmovzx r11d, al
+\tmovsx r10, WORD PTR [rbp-2]
+\tmov BYTE PTR [rbp-1], r10b
+\tcmovge r11d, DWORD PTR [rbp-8]
+\tlea rax, QWORD PTR [rax+rdx*4]
+\tje .Lend
+\tjmp .Lend
+.Lend:
+\tmov edi, 65
+\tcall putchar@PLT
+\tpop rbx
+\tadd rsp, 16
+\tsub rsp, 16
+\tmov rsp, rbp
+\tpop rbp
+\tret
+\t.size main, .-main
+\t.bss
+\t.balign 4
+\t.type counter, @object
+\t.size counter, 4
+counter:
+\t.zero 4
+\t.globl letter
+\t.data
+\t.balign 1
+\t.type letter, @object
+\t.size letter, 1
+letter:
+\t.byte 65
+\t.bss
+\t.balign 16
+\t.type table, @object
+\t.size table, 24
+table:
+\t.zero 24
+\t.section .rodata
+greeting:
+\t.asciz \"hi\\012\"
+\t.section .rodata
+\t.balign 8
+half:
+\t.quad 4602678819172646912
+\t.section .note.GNU-stack,\"\",@progbits
+"
+    );
+}
+
+/// both syntaxes must describe the same machine code.
+#[test]
+fn att_and_intel_assemble_to_same_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let text_bytes = |syntax: Syntax| {
+        let name = format!("{:?}", syntax);
+        let source = dir.path().join(format!("{}.s", name));
+        let object = dir.path().join(format!("{}.o", name));
+        let raw = dir.path().join(format!("{}.bin", name));
+        emit_asm(
+            every_variant(),
+            source.to_str().unwrap().to_string(),
+            AsmFormatter::new(syntax, Target::Linux),
+        )
+        .unwrap();
+        let assembled = std::process::Command::new("as")
+            .arg(&source)
+            .arg("-o")
+            .arg(&object)
+            .status()
+            .unwrap();
+        assert!(assembled.success());
+        let copied = std::process::Command::new("objcopy")
+            .args(["-O", "binary", "-j", ".text"])
+            .arg(&object)
+            .arg(&raw)
+            .status()
+            .unwrap();
+        assert!(copied.success());
+        fs::read(raw).unwrap()
+    };
+    let att = text_bytes(Syntax::Att);
+    assert!(!att.is_empty());
+    assert_eq!(att, text_bytes(Syntax::Intel));
+}
+
+/// Mach-O wants underscored symbols, `L` local labels, and no GNU-stack note.
+#[test]
+fn emit_for_macos() {
+    let prog = ProgramAsm {
+        items: vec![TopLevelAsm::Function {
+            fundef: FunDefAsm {
+                identifier: symbol::Symbol::intern("main"),
+                global: true,
+                instructions: vec![
+                    InstructionAsm::Jmp {
+                        target: String::from("end"),
+                    },
+                    InstructionAsm::Label {
+                        name: String::from("end"),
+                    },
+                    InstructionAsm::Mov {
+                        ty: AsmType::Longword,
+                        src: OperandAsm::Imm { int: 3 },
+                        dst: OperandAsm::Reg { r: Register::AX },
+                    },
+                    InstructionAsm::Ret,
+                ],
+            },
+        }],
+    };
+    assert_eq!(
+        AsmFormatter::new(Syntax::Att, Target::MacOs).program(&prog),
+        "\t.text
+\t.globl _main
+_main:
+\tjmp Lend
+Lend:
+\tmovl $3, %eax
+\tret
+"
+    );
+}
+
+/// the output of both syntaxes must be accepted by an assembler for each platform.
+/// Skipped when `llvm-mc` isn't installed.
+#[test]
+fn other_targets_assemble() {
+    if std::process::Command::new("llvm-mc")
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let targets = [
+        (Target::MacOs, "-triple=x86_64-apple-macos"),
+        (Target::Windows, "-triple=x86_64-pc-windows-msvc"),
+    ];
+    for (target, triple) in targets {
+        for syntax in [Syntax::Att, Syntax::Intel] {
+            let source = dir.path().join("other.s");
+            let object = dir.path().join("other.o");
+            emit_asm(
+                every_variant(),
+                source.to_str().unwrap().to_string(),
+                AsmFormatter::new(syntax, target),
+            )
+            .unwrap();
+            let assembled = std::process::Command::new("llvm-mc")
+                .args([triple, "-filetype=obj", "-o"])
+                .arg(&object)
+                .arg(&source)
+                .status()
+                .unwrap();
+            assert!(
+                assembled.success(),
+                "{:?} {:?} failed to assemble",
+                target,
+                syntax
+            );
+        }
+    }
+}
+
+/// COFF gets a `.def` block for each function and no GNU-stack note.
+#[test]
+fn emit_for_windows() {
+    let prog = ProgramAsm {
+        items: vec![TopLevelAsm::Function {
+            fundef: FunDefAsm {
+                identifier: symbol::Symbol::intern("main"),
+                global: true,
+                instructions: vec![
+                    InstructionAsm::Mov {
+                        ty: AsmType::Longword,
+                        src: OperandAsm::Imm { int: 3 },
+                        dst: OperandAsm::Reg { r: Register::AX },
+                    },
+                    InstructionAsm::Ret,
+                ],
+            },
+        }],
+    };
+    assert_eq!(
+        AsmFormatter::new(Syntax::Att, Target::Windows).program(&prog),
+        "\t.text
+\t.def main;
+\t.scl 2;
+\t.type 32;
+\t.endef
+\t.globl main
+main:
+\tmovl $3, %eax
+\tret
+"
+    );
+}
+
+/// the ELF symbol directives bracket the function, inside the text section.
+#[test]
+fn emit_elf_directives_in_order() {
+    let prog = ProgramAsm {
+        items: vec![TopLevelAsm::Function {
+            fundef: FunDefAsm {
+                identifier: symbol::Symbol::intern("main"),
+                global: true,
+                instructions: vec![InstructionAsm::Ret],
+            },
+        }],
+    };
+    let text = AsmFormatter::default().program(&prog);
+    let lines: Vec<&str> = text.lines().map(|l| l.trim()).collect();
+    assert_eq!(
+        lines,
+        vec![
+            ".text",
+            ".globl main",
+            ".type main, @function",
+            "main:",
+            "ret",
+            ".size main, .-main",
+            ".section .note.GNU-stack,\"\",@progbits",
+        ]
+    );
+}
+
+/// every register prints under its name for each operand size, in both syntaxes.
+#[test]
+fn format_every_register_at_every_size() {
+    let names = [
+        (Register::AX, ["al", "eax", "rax"]),
+        (Register::R10, ["r10b", "r10d", "r10"]),
+        (Register::DX, ["dl", "edx", "rdx"]),
+        (Register::R11, ["r11b", "r11d", "r11"]),
+        (Register::DI, ["dil", "edi", "rdi"]),
+        (Register::SI, ["sil", "esi", "rsi"]),
+        (Register::CX, ["cl", "ecx", "rcx"]),
+        (Register::R8, ["r8b", "r8d", "r8"]),
+        (Register::R9, ["r9b", "r9d", "r9"]),
+        (Register::BX, ["bl", "ebx", "rbx"]),
+        (Register::R12, ["r12b", "r12d", "r12"]),
+        (Register::R13, ["r13b", "r13d", "r13"]),
+        (Register::R14, ["r14b", "r14d", "r14"]),
+        (Register::R15, ["r15b", "r15d", "r15"]),
+        (Register::BP, ["bpl", "ebp", "rbp"]),
+        (Register::SP, ["spl", "esp", "rsp"]),
+    ];
+    let att = AsmFormatter::new(Syntax::Att, Target::Linux);
+    let intel = AsmFormatter::new(Syntax::Intel, Target::Linux);
+    for (r, sized) in names {
+        let types = [AsmType::Byte, AsmType::Longword, AsmType::Quadword];
+        for (ty, name) in types.into_iter().zip(sized) {
+            let operand = OperandAsm::Reg { r };
+            assert_eq!(att.operand(&operand, ty), format!("%{}", name));
+            assert_eq!(intel.operand(&operand, ty), name);
+        }
+    }
+    let xmm = OperandAsm::Reg { r: Register::XMM7 };
+    assert_eq!(att.operand(&xmm, AsmType::Double), "%xmm7");
+    assert_eq!(intel.operand(&xmm, AsmType::Longword), "xmm7");
+}
+
+/// the operand size picks the AT&T suffix, the Intel pointer size, and `cdq` or `cqo`.
+#[test]
+fn format_instructions_by_size() {
+    let att = AsmFormatter::new(Syntax::Att, Target::Linux);
+    let intel = AsmFormatter::new(Syntax::Intel, Target::Linux);
+    let mov = |ty| InstructionAsm::Mov {
+        ty,
+        src: OperandAsm::Imm { int: 1 },
+        dst: OperandAsm::Stack { off: -8 },
+    };
+    let neg = |ty| InstructionAsm::Unary {
+        ty,
+        unop: AsmUnaryOp::Neg,
+        operand: OperandAsm::Reg { r: Register::R10 },
+    };
+    let cases = [
+        (
+            mov(AsmType::Byte),
+            "movb $1, -8(%rbp)",
+            "mov BYTE PTR [rbp-8], 1",
+        ),
+        (
+            mov(AsmType::Longword),
+            "movl $1, -8(%rbp)",
+            "mov DWORD PTR [rbp-8], 1",
+        ),
+        (
+            mov(AsmType::Quadword),
+            "movq $1, -8(%rbp)",
+            "mov QWORD PTR [rbp-8], 1",
+        ),
+        (neg(AsmType::Byte), "negb %r10b", "neg r10b"),
+        (neg(AsmType::Quadword), "negq %r10", "neg r10"),
+        (
+            InstructionAsm::Cdq {
+                ty: AsmType::Longword,
+            },
+            "cdq",
+            "cdq",
+        ),
+        (
+            InstructionAsm::Cdq {
+                ty: AsmType::Quadword,
+            },
+            "cqo",
+            "cqo",
+        ),
+    ];
+    for (instr, att_text, intel_text) in cases {
+        assert_eq!(att.instruction(&instr), att_text);
+        assert_eq!(intel.instruction(&instr), intel_text);
+    }
+}
+
+/// streaming the program to a writer, to a file, and through `Display` all
+/// produce the same bytes as `program`, for every syntax, target and section layout.
+#[test]
+fn streamed_output_matches_formatted() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.s");
+    let prog = every_variant();
+    for syntax in [Syntax::Att, Syntax::Intel] {
+        for target in [Target::Linux, Target::MacOs, Target::Windows] {
+            for (function_sections, cf_protection) in [(false, false), (true, true)] {
+                let formatter = AsmFormatter {
+                    function_sections,
+                    cf_protection,
+                    ..AsmFormatter::new(syntax, target)
+                };
+                let expected = formatter.program(&prog);
+                let mut streamed = Vec::new();
+                emit_asm_to(&prog, &mut streamed, formatter).unwrap();
+                assert_eq!(String::from_utf8(streamed).unwrap(), expected);
+                emit_asm(
+                    every_variant(),
+                    path.to_str().unwrap().to_string(),
+                    formatter,
+                )
+                .unwrap();
+                assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+            }
+        }
+    }
+    assert_eq!(prog.to_string(), AsmFormatter::default().program(&prog));
+}
+
+/// a failed write comes back as the writer's own error.
+#[test]
+fn streamed_output_reports_write_errors() {
+    struct Full;
+    impl Write for Full {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    let e = emit_asm_to(&every_variant(), &mut Full, AsmFormatter::default()).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::StorageFull);
+}
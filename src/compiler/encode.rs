@@ -0,0 +1,1724 @@
+//! Machine code encoding for the x86-64 assembly AST.
+//! Covers exactly the instructions and operand forms the code generator produces,
+//! picking the same encodings GNU as does where there is a choice, except that
+//! jumps always use a 32-bit displacement. Of the byte- and word-sized operations
+//! only moves, comparisons, `setcc` and extensions have an encoding, which is
+//! all the code generator makes of them; a word operation takes an operand-size
+//! prefix, as does the odd SSE instruction. The SSE instructions for `double`s
+//! take a mandatory prefix ahead of any REX prefix. Calls and %rip-relative operands
+//! leave their displacement zeroed for the linker, and report where it is.
+//! CFI directives become the call frame instructions of the function's entry
+//! in `.eh_frame`, as the assembler would make them.
+use std::{collections::HashMap, fmt::Display};
+use thiserror::Error;
+
+use super::{
+    asmgen::{
+        AsmBinaryOp, AsmType, AsmUnaryOp, CfiDirective, CondCode, InstructionAsm, OperandAsm,
+        Register,
+    },
+    symbol::Symbol,
+};
+
+/// An instruction the code generator should never have produced.
+/// Always a compiler bug rather than a problem with the program being compiled.
+#[derive(Error, Debug)]
+pub enum EncodeError {
+    Unencodable { instr: InstructionAsm },
+    Unaddressable { operand: OperandAsm },
+    UndefinedLabel { target: String },
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(!) Internal compiler error: ")?;
+        match self {
+            Self::Unencodable { instr } => write!(f, "no encoding for instruction {:?}", instr),
+            Self::Unaddressable { operand } => {
+                write!(
+                    f,
+                    "operand {:?} cannot be addressed by a ModRM byte",
+                    operand
+                )
+            }
+            Self::UndefinedLabel { target } => write!(f, "jump to undefined label {}", target),
+        }
+    }
+}
+
+/// `endbr64`, the landing pad indirect branches must hit under CET.
+pub const ENDBR64: [u8; 4] = [0xF3, 0x0F, 0x1E, 0xFA];
+
+/// Where a 32-bit displacement to a symbol sits in a function's code, and the
+/// symbol it must reach: a function a call jumps to, or a variable, or its GOT
+/// entry, an operand addresses relative to %rip. %rip is then the end of the
+/// instruction, which may go on for `trailing` bytes past the field, with an
+/// immediate.
+#[derive(PartialEq, Debug)]
+pub enum SymbolRef {
+    Call {
+        at: usize,
+        name: Symbol,
+    },
+    Data {
+        at: usize,
+        name: Symbol,
+        trailing: usize,
+    },
+    Got {
+        at: usize,
+        name: Symbol,
+        trailing: usize,
+    },
+}
+
+/// A function body's machine code, its references to symbols, and each CFI
+/// directive with the offset of the code it follows.
+#[derive(PartialEq, Debug)]
+pub struct EncodedFunction {
+    pub code: Vec<u8>,
+    pub refs: Vec<SymbolRef>,
+    pub cfi: Vec<(usize, CfiDirective)>,
+}
+
+/// Encodes a function body, resolving jumps to its local labels.
+pub fn encode_instructions(instrs: &[InstructionAsm]) -> Result<Vec<u8>, EncodeError> {
+    encode_function(instrs).map(|f| f.code)
+}
+
+/// Encodes a function body like [`encode_instructions`], also returning its
+/// references to symbols, for the linker to resolve.
+pub fn encode_with_symbols(
+    instrs: &[InstructionAsm],
+) -> Result<(Vec<u8>, Vec<SymbolRef>), EncodeError> {
+    encode_function(instrs).map(|f| (f.code, f.refs))
+}
+
+/// Encodes a function body like [`encode_with_symbols`], also returning where
+/// its CFI directives fall.
+pub fn encode_function(instrs: &[InstructionAsm]) -> Result<EncodedFunction, EncodeError> {
+    let mut code = Vec::new();
+    let mut refs = Vec::new();
+    let mut cfi = Vec::new();
+    let mut labels: HashMap<&String, usize> = HashMap::new();
+    // offset of each rel32 field and the label it must reach
+    let mut fixups: Vec<(usize, &String)> = Vec::new();
+
+    for instr in instrs.iter() {
+        match instr {
+            InstructionAsm::Label { name } => {
+                labels.insert(name, code.len());
+            }
+            InstructionAsm::Cfi { directive } => cfi.push((code.len(), *directive)),
+            // object files carry no line information yet
+            InstructionAsm::Comment { text: _ } | InstructionAsm::Loc { .. } => {}
+            InstructionAsm::Jmp { target } => {
+                code.push(0xE9);
+                fixups.push((code.len(), target));
+                code.extend([0; 4]);
+            }
+            InstructionAsm::JmpCC { cc, target } => {
+                code.extend([0x0F, 0x80 | cc_no(*cc)]);
+                fixups.push((code.len(), target));
+                code.extend([0; 4]);
+            }
+            InstructionAsm::Call { name } | InstructionAsm::TailCall { name } => {
+                code.push(if matches!(instr, InstructionAsm::Call { .. }) {
+                    0xE8
+                } else {
+                    0xE9
+                });
+                refs.push(SymbolRef::Call {
+                    at: code.len(),
+                    name: *name,
+                });
+                code.extend([0; 4]);
+            }
+            _ => {
+                let bytes = encode_instruction(instr)?;
+                let data = instr.operands().into_iter().find_map(|o| match o {
+                    OperandAsm::Data { name } => Some((*name, false)),
+                    OperandAsm::Got { name } => Some((*name, true)),
+                    _ => None,
+                });
+                if let Some((name, got)) = data {
+                    let disp = rip_disp_at(&bytes);
+                    let (at, trailing) = (code.len() + disp, bytes.len() - disp - 4);
+                    refs.push(match got {
+                        false => SymbolRef::Data { at, name, trailing },
+                        true => SymbolRef::Got { at, name, trailing },
+                    });
+                }
+                code.extend(bytes);
+            }
+        }
+    }
+
+    for (at, target) in fixups.into_iter() {
+        let dest = *labels
+            .get(target)
+            .ok_or_else(|| EncodeError::UndefinedLabel {
+                target: target.clone(),
+            })?;
+        let rel = dest as i32 - (at as i32 + 4);
+        code[at..at + 4].copy_from_slice(&rel.to_le_bytes());
+    }
+
+    Ok(EncodedFunction { code, refs, cfi })
+}
+
+/// The common information entry that each function's entry in `.eh_frame`
+/// refers to, the same one GNU as writes: code offsets count in bytes, saved
+/// registers sit in 8-byte steps from the CFA, the return address is column 16,
+/// and a function's start is given as a 4-byte offset from where it is stored.
+/// On entry the CFA is 8 bytes above %rsp, and the return address just below it.
+pub const CIE: [u8; 24] = [
+    0x14, 0, 0, 0, // length
+    0, 0, 0, 0, // CIE id
+    1, // version
+    b'z', b'R', 0,    // augmentation
+    1,    // code alignment
+    0x78, // data alignment, -8
+    16,   // return address column
+    1,    // augmentation data length
+    0x1B, // %pc-relative, 4-byte function addresses
+    0x0C, 7, 8, // DW_CFA_def_cfa %rsp, 8
+    0x90, 1, // DW_CFA_offset %rip, -8
+    0, 0, // DW_CFA_nop padding
+];
+
+/// The call frame instructions for a function's CFI directives, each at the
+/// offset from the function's start that it follows. The frame description
+/// spans the whole function, so `StartProc` and `EndProc` add nothing, and
+/// neither do the directives only Windows takes.
+pub fn encode_cfi(cfi: &[(usize, CfiDirective)]) -> Vec<u8> {
+    let mut res = Vec::new();
+    let mut loc = 0;
+    for (at, directive) in cfi.iter() {
+        if matches!(
+            directive,
+            CfiDirective::StartProc
+                | CfiDirective::EndProc
+                | CfiDirective::PushReg { .. }
+                | CfiDirective::StackAlloc { .. }
+                | CfiDirective::SetFrame { .. }
+                | CfiDirective::EndPrologue
+        ) {
+            continue;
+        }
+        // DW_CFA_advance_loc, then its 1-, 2- and 4-byte forms
+        match (at - loc) as u32 {
+            0 => {}
+            delta @ 1..0x40 => res.push(0x40 | delta as u8),
+            delta @ 0x40..0x100 => res.extend([0x02, delta as u8]),
+            delta @ 0x100..0x10000 => {
+                res.push(0x03);
+                res.extend((delta as u16).to_le_bytes());
+            }
+            delta => {
+                res.push(0x04);
+                res.extend(delta.to_le_bytes());
+            }
+        }
+        loc = *at;
+        match *directive {
+            CfiDirective::StartProc
+            | CfiDirective::EndProc
+            | CfiDirective::PushReg { .. }
+            | CfiDirective::StackAlloc { .. }
+            | CfiDirective::SetFrame { .. }
+            | CfiDirective::EndPrologue => {}
+            CfiDirective::DefCfaOffset { off } => {
+                res.push(0x0E);
+                uleb128(&mut res, off as u64);
+            }
+            CfiDirective::DefCfaRegister { r } => res.extend([0x0D, r.dwarf_no()]),
+            CfiDirective::DefCfa { r, off } => {
+                res.extend([0x0C, r.dwarf_no()]);
+                uleb128(&mut res, off as u64);
+            }
+            // DW_CFA_offset, counting in the CIE's 8-byte steps below the CFA
+            CfiDirective::Offset { r, off } => {
+                res.push(0x80 | r.dwarf_no());
+                uleb128(&mut res, (-off / 8) as u64);
+            }
+            CfiDirective::RememberState => res.push(0x0A),
+            CfiDirective::RestoreState => res.push(0x0B),
+        }
+    }
+    res
+}
+
+/// `value` as an unsigned LEB128 number: seven bits to a byte, low bits first,
+/// with the top bit set on every byte but the last.
+fn uleb128(res: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            res.push(byte);
+            return;
+        }
+        res.push(byte | 0x80);
+    }
+}
+
+/// where the displacement of a %rip-relative operand sits in the instruction's
+/// encoding: after any mandatory prefix, any REX prefix, an opcode of one byte,
+/// or two starting with 0x0F, and the ModRM byte.
+fn rip_disp_at(code: &[u8]) -> usize {
+    let prefix = usize::from(matches!(code[0], 0x66 | 0xF2 | 0xF3));
+    let rex = prefix + usize::from((0x40..0x50).contains(&code[prefix]));
+    let opcode = if code[rex] == 0x0F { 2 } else { 1 };
+    rex + opcode + 1
+}
+
+/// Encodes a single instruction that doesn't refer to a label.
+fn encode_instruction(instr: &InstructionAsm) -> Result<Vec<u8>, EncodeError> {
+    match instr {
+        InstructionAsm::Unary {
+            ty: AsmType::Byte | AsmType::Word,
+            ..
+        }
+        | InstructionAsm::Binary {
+            ty: AsmType::Byte | AsmType::Word,
+            ..
+        }
+        | InstructionAsm::Idiv {
+            ty: AsmType::Byte | AsmType::Word,
+            ..
+        }
+        | InstructionAsm::Div {
+            ty: AsmType::Byte | AsmType::Word,
+            ..
+        }
+        | InstructionAsm::Cdq {
+            ty: AsmType::Byte | AsmType::Word,
+            ..
+        }
+        | InstructionAsm::Cmov {
+            ty: AsmType::Byte | AsmType::Word,
+            ..
+        } => Err(unencodable(instr)),
+        InstructionAsm::Mov {
+            ty: AsmType::Byte,
+            src,
+            dst,
+        } => match (src, dst) {
+            (OperandAsm::Imm { int }, OperandAsm::Reg { r }) => {
+                let mut res = rex(false, 0, reg_no(*r));
+                if res.is_empty() && (4..8).contains(&reg_no(*r)) {
+                    res.push(0x40);
+                }
+                res.extend([0xB0 + (reg_no(*r) & 7), *int as u8]);
+                Ok(res)
+            }
+            (OperandAsm::Imm { int }, _) => {
+                let mut res = byte_modrm(&[0xC6], 0, dst, false, false)?;
+                res.push(*int as u8);
+                Ok(res)
+            }
+            (OperandAsm::Reg { r }, _) => byte_modrm(&[0x88], reg_no(*r), dst, false, true),
+            (_, OperandAsm::Reg { r }) => byte_modrm(&[0x8A], reg_no(*r), src, false, true),
+            _ => Err(unencodable(instr)),
+        },
+        InstructionAsm::Cmp {
+            ty: AsmType::Byte,
+            src,
+            dst,
+        } => match (src, dst) {
+            // the accumulator has a shorter form taking the immediate
+            (OperandAsm::Imm { int }, OperandAsm::Reg { r: Register::AX }) => {
+                Ok(vec![0x3C, *int as u8])
+            }
+            (OperandAsm::Imm { int }, _) => {
+                let mut res = byte_modrm(&[0x80], 7, dst, false, false)?;
+                res.push(*int as u8);
+                Ok(res)
+            }
+            (OperandAsm::Reg { r }, _) => byte_modrm(&[0x38], reg_no(*r), dst, false, true),
+            (_, OperandAsm::Reg { r }) => byte_modrm(&[0x3A], reg_no(*r), src, false, true),
+            _ => Err(unencodable(instr)),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Word,
+            src,
+            dst,
+        } => word(match (src, dst) {
+            (OperandAsm::Imm { int }, OperandAsm::Reg { r }) => {
+                let mut res = rex(false, 0, reg_no(*r));
+                res.push(0xB8 + (reg_no(*r) & 7));
+                res.extend((*int as i16).to_le_bytes());
+                Ok(res)
+            }
+            (OperandAsm::Imm { int }, _) => {
+                let mut res = modrm(&[0xC7], 0, dst, false)?;
+                res.extend((*int as i16).to_le_bytes());
+                Ok(res)
+            }
+            (OperandAsm::Reg { r }, _) => modrm(&[0x89], reg_no(*r), dst, false),
+            (_, OperandAsm::Reg { r }) => modrm(&[0x8B], reg_no(*r), src, false),
+            _ => Err(unencodable(instr)),
+        }),
+        InstructionAsm::Cmp {
+            ty: AsmType::Word,
+            src,
+            dst,
+        } => word(match (src, dst) {
+            (OperandAsm::Imm { int }, OperandAsm::Reg { r: Register::AX })
+                if i8::try_from(*int).is_err() =>
+            {
+                let mut res = vec![0x3D];
+                res.extend((*int as i16).to_le_bytes());
+                Ok(res)
+            }
+            (OperandAsm::Imm { int }, _) => match i8::try_from(*int) {
+                Ok(byte) => {
+                    let mut res = modrm(&[0x83], 7, dst, false)?;
+                    res.push(byte as u8);
+                    Ok(res)
+                }
+                Err(_) => {
+                    let mut res = modrm(&[0x81], 7, dst, false)?;
+                    res.extend((*int as i16).to_le_bytes());
+                    Ok(res)
+                }
+            },
+            (OperandAsm::Reg { r }, _) => modrm(&[0x39], reg_no(*r), dst, false),
+            (_, OperandAsm::Reg { r }) => modrm(&[0x3B], reg_no(*r), src, false),
+            _ => Err(unencodable(instr)),
+        }),
+        InstructionAsm::Mov {
+            ty: AsmType::Double,
+            src,
+            dst,
+        } => match (src, dst) {
+            (_, OperandAsm::Reg { r }) if r.is_xmm() => sse(0xF2, 0x10, reg_no(*r), src, false),
+            (OperandAsm::Reg { r }, _) if r.is_xmm() => sse(0xF2, 0x11, reg_no(*r), dst, false),
+            _ => Err(unencodable(instr)),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Double,
+            binop,
+            src,
+            dst: OperandAsm::Reg { r },
+        } if r.is_xmm() => {
+            let (prefix, opcode) = match binop {
+                AsmBinaryOp::Add => (0xF2, 0x58),
+                AsmBinaryOp::Imul => (0xF2, 0x59),
+                AsmBinaryOp::Sub => (0xF2, 0x5C),
+                AsmBinaryOp::DivDouble => (0xF2, 0x5E),
+                AsmBinaryOp::Xor => (0x66, 0x57),
+                _ => return Err(unencodable(instr)),
+            };
+            sse(prefix, opcode, reg_no(*r), src, false)
+        }
+        InstructionAsm::Cmp {
+            ty: AsmType::Double,
+            src,
+            dst: OperandAsm::Reg { r },
+        } if r.is_xmm() => sse(0x66, 0x2F, reg_no(*r), src, false),
+        InstructionAsm::Binary {
+            ty: AsmType::Double,
+            ..
+        }
+        | InstructionAsm::Cmp {
+            ty: AsmType::Double,
+            ..
+        } => Err(unencodable(instr)),
+        InstructionAsm::Cvttsd2si {
+            ty,
+            src,
+            dst: OperandAsm::Reg { r },
+        } if !r.is_xmm() => sse(0xF2, 0x2C, reg_no(*r), src, is_wide(ty)),
+        InstructionAsm::Cvtsi2sd {
+            ty,
+            src,
+            dst: OperandAsm::Reg { r },
+        } if r.is_xmm() => sse(0xF2, 0x2A, reg_no(*r), src, is_wide(ty)),
+        InstructionAsm::Cvttsd2si { .. } | InstructionAsm::Cvtsi2sd { .. } => {
+            Err(unencodable(instr))
+        }
+        InstructionAsm::Mov { ty, src, dst } => match (src, dst) {
+            (OperandAsm::Imm { int }, OperandAsm::Reg { r }) if *ty == AsmType::Longword => {
+                let mut res = rex(false, 0, reg_no(*r));
+                res.push(0xB8 + (reg_no(*r) & 7));
+                res.extend(imm32(instr, *int)?);
+                Ok(res)
+            }
+            // only an immediate that doesn't fit the sign-extended 32-bit form below
+            // gets the full 64 bits of `movabsq`
+            (OperandAsm::Imm { int }, OperandAsm::Reg { r }) if i32::try_from(*int).is_err() => {
+                let mut res = rex(true, 0, reg_no(*r));
+                res.push(0xB8 + (reg_no(*r) & 7));
+                res.extend(int.to_le_bytes());
+                Ok(res)
+            }
+            (OperandAsm::Imm { int }, _) => {
+                let mut res = modrm(&[0xC7], 0, dst, is_wide(ty))?;
+                res.extend(imm32(instr, *int)?);
+                Ok(res)
+            }
+            (OperandAsm::Reg { r }, _) => modrm(&[0x89], reg_no(*r), dst, is_wide(ty)),
+            (_, OperandAsm::Reg { r }) => modrm(&[0x8B], reg_no(*r), src, is_wide(ty)),
+            _ => Err(unencodable(instr)),
+        },
+        InstructionAsm::Ret => Ok(vec![0xC3]),
+        InstructionAsm::Unary { ty, unop, operand } => {
+            let ext = match unop {
+                AsmUnaryOp::Not => 2,
+                AsmUnaryOp::Neg => 3,
+            };
+            modrm(&[0xF7], ext, operand, is_wide(ty))
+        }
+        InstructionAsm::AllocStack { off } => {
+            let rsp = OperandAsm::Reg { r: Register::SP };
+            with_imm(instr, &[0x81], &[0x83], 5, &rsp, i64::from(-off), true)
+        }
+        InstructionAsm::DeallocStack { off } => {
+            let rsp = OperandAsm::Reg { r: Register::SP };
+            with_imm(instr, &[0x81], &[0x83], 0, &rsp, i64::from(-off), true)
+        }
+        InstructionAsm::Cdq { ty } => {
+            let mut res = rex(is_wide(ty), 0, 0);
+            res.push(0x99);
+            Ok(res)
+        }
+        InstructionAsm::Binary {
+            ty,
+            binop,
+            src,
+            dst,
+        } => match binop {
+            // only `double`s divide with a single instruction
+            AsmBinaryOp::DivDouble => Err(unencodable(instr)),
+            AsmBinaryOp::Imul => match (src, dst) {
+                (OperandAsm::Imm { int }, OperandAsm::Reg { r }) => {
+                    with_imm(instr, &[0x69], &[0x6B], reg_no(*r), dst, *int, is_wide(ty))
+                }
+                (_, OperandAsm::Reg { r }) => modrm(&[0x0F, 0xAF], reg_no(*r), src, is_wide(ty)),
+                _ => Err(unencodable(instr)),
+            },
+            AsmBinaryOp::Shl | AsmBinaryOp::Shr | AsmBinaryOp::Sar => {
+                let ext = match binop {
+                    AsmBinaryOp::Shl => 4,
+                    AsmBinaryOp::Shr => 5,
+                    _ => 7,
+                };
+                match src {
+                    // shifting by one has its own opcode without an immediate
+                    OperandAsm::Imm { int: 1 } => modrm(&[0xD1], ext, dst, is_wide(ty)),
+                    OperandAsm::Imm { int } => {
+                        let mut res = modrm(&[0xC1], ext, dst, is_wide(ty))?;
+                        res.push(*int as u8);
+                        Ok(res)
+                    }
+                    OperandAsm::Reg { r: Register::CX } => modrm(&[0xD3], ext, dst, is_wide(ty)),
+                    _ => Err(unencodable(instr)),
+                }
+            }
+            AsmBinaryOp::Add
+            | AsmBinaryOp::Sub
+            | AsmBinaryOp::And
+            | AsmBinaryOp::Or
+            | AsmBinaryOp::Xor => {
+                // (opcode extension for an immediate, r/m <- reg opcode, reg <- r/m opcode)
+                let (ext, to_rm, to_reg) = match binop {
+                    AsmBinaryOp::Add => (0, 0x01, 0x03),
+                    AsmBinaryOp::Or => (1, 0x09, 0x0B),
+                    AsmBinaryOp::And => (4, 0x21, 0x23),
+                    AsmBinaryOp::Sub => (5, 0x29, 0x2B),
+                    _ => (6, 0x31, 0x33),
+                };
+                arith(instr, ext, to_rm, to_reg, src, dst)
+            }
+        },
+        InstructionAsm::Idiv { ty, operand } => modrm(&[0xF7], 7, operand, is_wide(ty)),
+        InstructionAsm::Div { ty, operand } => modrm(&[0xF7], 6, operand, is_wide(ty)),
+        InstructionAsm::Push { r } => {
+            let mut res = rex(false, 0, reg_no(*r));
+            res.push(0x50 + (reg_no(*r) & 7));
+            Ok(res)
+        }
+        InstructionAsm::Pop { r } => {
+            let mut res = rex(false, 0, reg_no(*r));
+            res.push(0x58 + (reg_no(*r) & 7));
+            Ok(res)
+        }
+        InstructionAsm::MovQ { src, dst } => {
+            modrm(&[0x89], reg_no(*src), &OperandAsm::Reg { r: *dst }, true)
+        }
+        InstructionAsm::Cmp { src, dst, .. } => arith(instr, 7, 0x39, 0x3B, src, dst),
+        InstructionAsm::SetCC { cc, operand } => {
+            byte_modrm(&[0x0F, 0x90 | cc_no(*cc)], 0, operand, false, false)
+        }
+        InstructionAsm::Movzx {
+            src_ty,
+            dst_ty,
+            src,
+            dst: OperandAsm::Reg { r },
+        } => {
+            let encoding = match src_ty {
+                AsmType::Byte => byte_modrm(&[0x0F, 0xB6], reg_no(*r), src, is_wide(dst_ty), false),
+                AsmType::Word => modrm(&[0x0F, 0xB7], reg_no(*r), src, is_wide(dst_ty)),
+                _ => Err(unencodable(instr)),
+            };
+            match dst_ty {
+                AsmType::Word => word(encoding),
+                _ => encoding,
+            }
+        }
+        InstructionAsm::Movzx { .. } => Err(unencodable(instr)),
+        InstructionAsm::Movsx {
+            src_ty,
+            dst_ty,
+            src,
+            dst: OperandAsm::Reg { r },
+        } => {
+            let encoding = match src_ty {
+                AsmType::Byte => byte_modrm(&[0x0F, 0xBE], reg_no(*r), src, is_wide(dst_ty), false),
+                AsmType::Word => modrm(&[0x0F, 0xBF], reg_no(*r), src, is_wide(dst_ty)),
+                AsmType::Longword => modrm(&[0x63], reg_no(*r), src, true),
+                _ => Err(unencodable(instr)),
+            };
+            match dst_ty {
+                AsmType::Word => word(encoding),
+                _ => encoding,
+            }
+        }
+        InstructionAsm::Movsx { .. } => Err(unencodable(instr)),
+        InstructionAsm::Cmov {
+            ty,
+            cc,
+            src,
+            dst: OperandAsm::Reg { r },
+        } => modrm(&[0x0F, 0x40 | cc_no(*cc)], reg_no(*r), src, is_wide(ty)),
+        InstructionAsm::Cmov { .. } => Err(unencodable(instr)),
+        InstructionAsm::Lea {
+            src,
+            dst: OperandAsm::Reg { r },
+        } if src.is_memory() => modrm(&[0x8D], reg_no(*r), src, true),
+        InstructionAsm::Lea { .. } => Err(unencodable(instr)),
+        // labels, jumps, calls, comments and directives are encoded with their function
+        InstructionAsm::Jmp { .. }
+        | InstructionAsm::JmpCC { .. }
+        | InstructionAsm::Label { .. }
+        | InstructionAsm::Call { .. }
+        | InstructionAsm::TailCall { .. }
+        | InstructionAsm::Comment { .. }
+        | InstructionAsm::Cfi { .. }
+        | InstructionAsm::Loc { .. } => Err(unencodable(instr)),
+    }
+}
+
+/// encodes one of the classic two-operand ALU instructions, given its opcode
+/// extension for the immediate forms and its two register forms.
+fn arith(
+    instr: &InstructionAsm,
+    ext: u8,
+    to_rm: u8,
+    to_reg: u8,
+    src: &OperandAsm,
+    dst: &OperandAsm,
+) -> Result<Vec<u8>, EncodeError> {
+    let wide = match instr {
+        InstructionAsm::Binary { ty, .. } | InstructionAsm::Cmp { ty, .. } => is_wide(ty),
+        _ => false,
+    };
+    match (src, dst) {
+        (OperandAsm::Imm { int }, OperandAsm::Reg { r: Register::AX })
+            if i8::try_from(*int).is_err() =>
+        {
+            // the accumulator has a shorter form taking a full immediate
+            let mut res = rex(wide, 0, 0);
+            res.push((ext << 3) | 0x05);
+            res.extend(imm32(instr, *int)?);
+            Ok(res)
+        }
+        (OperandAsm::Imm { int }, _) => with_imm(instr, &[0x81], &[0x83], ext, dst, *int, wide),
+        (OperandAsm::Reg { r }, _) => modrm(&[to_rm], reg_no(*r), dst, wide),
+        (_, OperandAsm::Reg { r }) => modrm(&[to_reg], reg_no(*r), src, wide),
+        _ => Err(unencodable(instr)),
+    }
+}
+
+/// encodes an instruction with an immediate, using the sign-extended 8-bit form when it fits.
+fn with_imm(
+    instr: &InstructionAsm,
+    long: &[u8],
+    short: &[u8],
+    reg: u8,
+    rm: &OperandAsm,
+    int: i64,
+    wide: bool,
+) -> Result<Vec<u8>, EncodeError> {
+    match i8::try_from(int) {
+        Ok(byte) => {
+            let mut res = modrm(short, reg, rm, wide)?;
+            res.push(byte as u8);
+            Ok(res)
+        }
+        Err(_) => {
+            let mut res = modrm(long, reg, rm, wide)?;
+            res.extend(imm32(instr, int)?);
+            Ok(res)
+        }
+    }
+}
+
+/// the bytes of a 32-bit immediate field, which quadword instructions sign-extend.
+fn imm32(instr: &InstructionAsm, int: i64) -> Result<[u8; 4], EncodeError> {
+    i32::try_from(int)
+        .map(i32::to_le_bytes)
+        .map_err(|_| unencodable(instr))
+}
+
+/// encodes an opcode followed by a ModRM byte addressing `rm`, with `reg` either a
+/// register number or an opcode extension.
+fn modrm(opcode: &[u8], reg: u8, rm: &OperandAsm, wide: bool) -> Result<Vec<u8>, EncodeError> {
+    let (base, off) = match rm {
+        OperandAsm::Reg { r } => {
+            let mut res = rex(wide, reg, reg_no(*r));
+            res.extend(opcode);
+            res.push(0b11000000 | ((reg & 7) << 3) | (reg_no(*r) & 7));
+            return Ok(res);
+        }
+        OperandAsm::Stack { off } => (Register::BP, *off),
+        OperandAsm::Memory { base, off } => (*base, *off),
+        OperandAsm::Indexed { base, index, scale } => {
+            return indexed(opcode, reg, rm, (*base, *index, *scale), wide)
+        }
+        // mode 00 with %rbp's number means a disp32 from %rip
+        OperandAsm::Data { .. } | OperandAsm::Got { .. } => {
+            let mut res = rex(wide, reg, 0);
+            res.extend(opcode);
+            res.push(((reg & 7) << 3) | 0b101);
+            res.extend([0; 4]);
+            return Ok(res);
+        }
+        _ => return Err(EncodeError::Unaddressable { operand: *rm }),
+    };
+    let base_no = reg_no(base);
+    // %rbp and %r13 as a base always carry a displacement
+    let (mode, disp) = match i8::try_from(off) {
+        Ok(0) if base_no & 7 != 5 => (0b00, vec![]),
+        Ok(byte) => (0b01, vec![byte as u8]),
+        Err(_) => (0b10, off.to_le_bytes().to_vec()),
+    };
+    let mut res = rex(wide, reg, base_no);
+    res.extend(opcode);
+    res.push((mode << 6) | ((reg & 7) << 3) | (base_no & 7));
+    // %rsp and %r12 as a base need a SIB byte with no index
+    if base_no & 7 == 4 {
+        res.push(0x24);
+    }
+    res.extend(disp);
+    Ok(res)
+}
+
+/// encodes an opcode followed by a ModRM byte and a SIB byte addressing `base`
+/// plus `index` times `scale`, whose high register bits go in REX.X and REX.B.
+/// %rsp can't be an index, its number meaning none.
+fn indexed(
+    opcode: &[u8],
+    reg: u8,
+    rm: &OperandAsm,
+    (base, index, scale): (Register, Register, i32),
+    wide: bool,
+) -> Result<Vec<u8>, EncodeError> {
+    let scale_bits = match scale {
+        1 => 0,
+        2 => 1,
+        4 => 2,
+        8 => 3,
+        _ => return Err(EncodeError::Unaddressable { operand: *rm }),
+    };
+    let (base_no, index_no) = (reg_no(base), reg_no(index));
+    if index_no == 4 {
+        return Err(EncodeError::Unaddressable { operand: *rm });
+    }
+    let bits = (u8::from(wide) << 3) | ((reg >> 3) << 2) | ((index_no >> 3) << 1) | (base_no >> 3);
+    let mut res = match bits {
+        0 => vec![],
+        _ => vec![0x40 | bits],
+    };
+    res.extend(opcode);
+    // %rbp and %r13 as a base always carry a displacement, here a zero byte
+    let mode = match base_no & 7 {
+        5 => 0b01,
+        _ => 0b00,
+    };
+    res.push((mode << 6) | ((reg & 7) << 3) | 0b100);
+    res.push((scale_bits << 6) | ((index_no & 7) << 3) | (base_no & 7));
+    if mode == 0b01 {
+        res.push(0);
+    }
+    Ok(res)
+}
+
+/// encodes an SSE instruction: its mandatory prefix, then the two-byte opcode
+/// `0F opcode` with a ModRM byte, which the REX prefix goes in front of.
+fn sse(
+    prefix: u8,
+    opcode: u8,
+    reg: u8,
+    rm: &OperandAsm,
+    wide: bool,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut res = vec![prefix];
+    res.extend(modrm(&[0x0F, opcode], reg, rm, wide)?);
+    Ok(res)
+}
+
+/// encodes an opcode and ModRM byte whose r/m operand is a byte, as is the
+/// register `reg` if `byte_reg`. Without a REX prefix, register numbers 4 to 7
+/// name %ah to %bh rather than %spl to %dil.
+fn byte_modrm(
+    opcode: &[u8],
+    reg: u8,
+    rm: &OperandAsm,
+    wide: bool,
+    byte_reg: bool,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut res = modrm(opcode, reg, rm, wide)?;
+    let high = matches!(rm, OperandAsm::Reg { r } if (4..8).contains(&reg_no(*r)))
+        || (byte_reg && (4..8).contains(&reg));
+    if high && res[0] == opcode[0] {
+        res.insert(0, 0x40);
+    }
+    Ok(res)
+}
+
+/// puts the operand-size prefix in front of an encoding, making its operation
+/// a word one.
+fn word(encoding: Result<Vec<u8>, EncodeError>) -> Result<Vec<u8>, EncodeError> {
+    let mut res = encoding?;
+    res.insert(0, 0x66);
+    Ok(res)
+}
+
+/// the REX prefix needed for a 64-bit operation or registers r8 and up, if any.
+fn rex(wide: bool, reg: u8, rm: u8) -> Vec<u8> {
+    let bits = (u8::from(wide) << 3) | ((reg >> 3) << 2) | (rm >> 3);
+    if bits == 0 {
+        vec![]
+    } else {
+        vec![0x40 | bits]
+    }
+}
+
+/// whether the operation needs REX.W for 64-bit operands.
+fn is_wide(ty: &AsmType) -> bool {
+    *ty == AsmType::Quadword
+}
+
+/// the condition's number, which `jcc` and `setcc` add to their base opcodes.
+fn cc_no(cc: CondCode) -> u8 {
+    match cc {
+        CondCode::B => 0x2,
+        CondCode::AE => 0x3,
+        CondCode::E => 0x4,
+        CondCode::NE => 0x5,
+        CondCode::L => 0xC,
+        CondCode::GE => 0xD,
+        CondCode::BE => 0x6,
+        CondCode::A => 0x7,
+        CondCode::LE => 0xE,
+        CondCode::G => 0xF,
+        CondCode::P => 0xA,
+        CondCode::NP => 0xB,
+    }
+}
+
+fn reg_no(r: Register) -> u8 {
+    match r {
+        Register::AX => 0,
+        Register::CX => 1,
+        Register::DX => 2,
+        Register::BX => 3,
+        Register::SP => 4,
+        Register::BP => 5,
+        Register::SI => 6,
+        Register::DI => 7,
+        Register::R8 => 8,
+        Register::R9 => 9,
+        Register::R10 => 10,
+        Register::R11 => 11,
+        Register::R12 => 12,
+        Register::R13 => 13,
+        Register::R14 => 14,
+        Register::R15 => 15,
+        Register::XMM0 => 0,
+        Register::XMM1 => 1,
+        Register::XMM2 => 2,
+        Register::XMM3 => 3,
+        Register::XMM4 => 4,
+        Register::XMM5 => 5,
+        Register::XMM6 => 6,
+        Register::XMM7 => 7,
+    }
+}
+
+fn unencodable(instr: &InstructionAsm) -> EncodeError {
+    EncodeError::Unencodable {
+        instr: instr.clone(),
+    }
+}
+
+#[test]
+fn encode_prologue() {
+    let code = encode_instructions(&[
+        InstructionAsm::Push { r: Register::BP },
+        InstructionAsm::MovQ {
+            src: Register::SP,
+            dst: Register::BP,
+        },
+        InstructionAsm::AllocStack { off: -16 },
+        InstructionAsm::Push { r: Register::R12 },
+    ])
+    .unwrap();
+    assert_eq!(
+        code,
+        vec![0x55, 0x48, 0x89, 0xE5, 0x48, 0x83, 0xEC, 0x10, 0x41, 0x54]
+    );
+}
+
+/// `lea` takes any memory operand, and a pointer in a register is a base
+/// like any other.
+#[test]
+fn encode_addresses() {
+    let rax = OperandAsm::Reg { r: Register::AX };
+    let at_rax = OperandAsm::Memory {
+        base: Register::AX,
+        off: 0,
+    };
+    let code = encode_instructions(&[
+        InstructionAsm::Lea {
+            src: OperandAsm::Stack { off: -8 },
+            dst: rax,
+        },
+        InstructionAsm::Lea {
+            src: OperandAsm::Data {
+                name: Symbol::intern("x"),
+            },
+            dst: OperandAsm::Reg { r: Register::R11 },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: at_rax,
+            dst: OperandAsm::Reg { r: Register::R10 },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Quadword,
+            src: OperandAsm::Reg { r: Register::R10 },
+            dst: at_rax,
+        },
+    ])
+    .unwrap();
+    assert_eq!(
+        code,
+        vec![
+            0x48, 0x8D, 0x45, 0xF8, 0x4C, 0x8D, 0x1D, 0, 0, 0, 0, 0x44, 0x8B, 0x10, 0x4C, 0x89,
+            0x10
+        ]
+    );
+    assert!(encode_instructions(&[InstructionAsm::Lea { src: rax, dst: rax }]).is_err());
+}
+
+/// an indexed operand takes a SIB byte, with a zero displacement for a base
+/// the plain form can't name, and %rsp can't be an index.
+#[test]
+fn encode_indexed_addresses() {
+    let indexed = |base, index, scale| OperandAsm::Indexed { base, index, scale };
+    let code = encode_instructions(&[
+        InstructionAsm::Lea {
+            src: indexed(Register::AX, Register::DX, 4),
+            dst: OperandAsm::Reg { r: Register::AX },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Reg { r: Register::R10 },
+            dst: indexed(Register::BP, Register::R11, 8),
+        },
+    ])
+    .unwrap();
+    assert_eq!(
+        code,
+        vec![0x48, 0x8D, 0x04, 0x90, 0x46, 0x89, 0x54, 0xDD, 0x00]
+    );
+    assert!(encode_instructions(&[InstructionAsm::Lea {
+        src: indexed(Register::AX, Register::SP, 1),
+        dst: OperandAsm::Reg { r: Register::AX },
+    }])
+    .is_err());
+}
+
+/// jumps reach their labels both forwards and backwards.
+#[test]
+fn encode_jumps() {
+    let code = encode_instructions(&[
+        InstructionAsm::Label {
+            name: String::from("top"),
+        },
+        InstructionAsm::JmpCC {
+            cc: CondCode::NE,
+            target: String::from("end"),
+        },
+        InstructionAsm::Jmp {
+            target: String::from("top"),
+        },
+        InstructionAsm::Label {
+            name: String::from("end"),
+        },
+        InstructionAsm::Ret,
+    ])
+    .unwrap();
+    assert_eq!(
+        code,
+        vec![0x0F, 0x85, 0x05, 0, 0, 0, 0xE9, 0xF5, 0xFF, 0xFF, 0xFF, 0xC3]
+    );
+}
+
+/// a call's displacement is left for the linker, and reported with its callee.
+#[test]
+fn encode_calls() {
+    let (code, calls) = encode_with_symbols(&[
+        InstructionAsm::Push { r: Register::AX },
+        InstructionAsm::Call {
+            name: Symbol::intern("f"),
+        },
+        InstructionAsm::Ret,
+    ])
+    .unwrap();
+    assert_eq!(code, vec![0x50, 0xE8, 0, 0, 0, 0, 0xC3]);
+    assert_eq!(
+        calls,
+        vec![SymbolRef::Call {
+            at: 2,
+            name: Symbol::intern("f")
+        }]
+    );
+    // a tail call is a jump to the callee, relocated the same way
+    let (code, calls) = encode_with_symbols(&[InstructionAsm::TailCall {
+        name: Symbol::intern("f"),
+    }])
+    .unwrap();
+    assert_eq!(code, vec![0xE9, 0, 0, 0, 0]);
+    assert_eq!(
+        calls,
+        vec![SymbolRef::Call {
+            at: 1,
+            name: Symbol::intern("f")
+        }]
+    );
+}
+
+/// so is a %rip-relative operand's, with the bytes of any immediate after it.
+#[test]
+fn encode_data_references() {
+    let x = OperandAsm::Data {
+        name: Symbol::intern("x"),
+    };
+    let (code, refs) = encode_with_symbols(&[
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: x,
+            dst: OperandAsm::Reg { r: Register::R10 },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Imm { int: 3 },
+            dst: x,
+        },
+        InstructionAsm::SetCC {
+            cc: CondCode::E,
+            operand: x,
+        },
+        InstructionAsm::Cvtsi2sd {
+            ty: AsmType::Quadword,
+            src: x,
+            dst: OperandAsm::Reg { r: Register::XMM1 },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Byte,
+            src: OperandAsm::Imm { int: 3 },
+            dst: x,
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Word,
+            src: OperandAsm::Imm { int: 3 },
+            dst: x,
+        },
+    ])
+    .unwrap();
+    assert_eq!(
+        code,
+        vec![
+            0x44, 0x8B, 0x15, 0, 0, 0, 0, // movl x(%rip), %r10d
+            0xC7, 0x05, 0, 0, 0, 0, 3, 0, 0, 0, // movl $3, x(%rip)
+            0x0F, 0x94, 0x05, 0, 0, 0, 0, // sete x(%rip)
+            0xF2, 0x48, 0x0F, 0x2A, 0x0D, 0, 0, 0, 0, // cvtsi2sdq x(%rip), %xmm1
+            0xC6, 0x05, 0, 0, 0, 0, 3, // movb $3, x(%rip)
+            0x66, 0xC7, 0x05, 0, 0, 0, 0, 3, 0, // movw $3, x(%rip)
+        ]
+    );
+    let name = Symbol::intern("x");
+    assert_eq!(
+        refs,
+        vec![
+            SymbolRef::Data {
+                at: 3,
+                name,
+                trailing: 0
+            },
+            SymbolRef::Data {
+                at: 9,
+                name,
+                trailing: 4
+            },
+            SymbolRef::Data {
+                at: 20,
+                name,
+                trailing: 0
+            },
+            SymbolRef::Data {
+                at: 29,
+                name,
+                trailing: 0
+            },
+            SymbolRef::Data {
+                at: 35,
+                name,
+                trailing: 1
+            },
+            SymbolRef::Data {
+                at: 43,
+                name,
+                trailing: 2
+            },
+        ]
+    );
+}
+
+/// every operand form the code generator produces encodes to the same bytes GNU as picks.
+#[test]
+fn encoding_matches_gnu_as() {
+    use super::emit::AsmFormatter;
+
+    let stack = |off| OperandAsm::Stack { off };
+    let reg = |r| OperandAsm::Reg { r };
+    let imm = |int| OperandAsm::Imm { int };
+    let mut instrs = vec![
+        InstructionAsm::Push { r: Register::BP },
+        InstructionAsm::MovQ {
+            src: Register::SP,
+            dst: Register::BP,
+        },
+        InstructionAsm::AllocStack { off: -16 },
+        InstructionAsm::AllocStack { off: -400 },
+        InstructionAsm::DeallocStack { off: -400 },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: reg(Register::DI),
+            dst: stack(-4),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: reg(Register::R9),
+            dst: stack(-8),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: stack(16),
+            dst: reg(Register::R10),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: imm(5),
+            dst: reg(Register::R8),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: reg(Register::SI),
+            dst: reg(Register::CX),
+        },
+        InstructionAsm::Push { r: Register::AX },
+        InstructionAsm::SetCC {
+            cc: CondCode::E,
+            operand: reg(Register::SI),
+        },
+        InstructionAsm::SetCC {
+            cc: CondCode::E,
+            operand: reg(Register::DI),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: imm(9),
+            dst: OperandAsm::Memory {
+                base: Register::SP,
+                off: 0,
+            },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Memory {
+                base: Register::SP,
+                off: 300,
+            },
+            dst: reg(Register::R12),
+        },
+        InstructionAsm::Unary {
+            ty: AsmType::Longword,
+            unop: AsmUnaryOp::Neg,
+            operand: OperandAsm::Memory {
+                base: Register::SP,
+                off: 4,
+            },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: imm(7),
+            dst: stack(-4),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: imm(-3),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: reg(Register::R10),
+            dst: stack(-200),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: stack(-8),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: reg(Register::BX),
+            dst: reg(Register::R15),
+        },
+        InstructionAsm::Unary {
+            ty: AsmType::Longword,
+            unop: AsmUnaryOp::Neg,
+            operand: stack(-4),
+        },
+        InstructionAsm::Unary {
+            ty: AsmType::Longword,
+            unop: AsmUnaryOp::Not,
+            operand: reg(Register::R13),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Imul,
+            src: stack(-4),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Imul,
+            src: imm(3),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Imul,
+            src: imm(3000),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Shl,
+            src: imm(3),
+            dst: stack(-4),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Shr,
+            src: imm(31),
+            dst: reg(Register::R10),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Sar,
+            src: imm(1),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Cdq {
+            ty: AsmType::Longword,
+        },
+        InstructionAsm::Idiv {
+            ty: AsmType::Longword,
+            operand: stack(-8),
+        },
+        InstructionAsm::Idiv {
+            ty: AsmType::Longword,
+            operand: reg(Register::R10),
+        },
+        InstructionAsm::Div {
+            ty: AsmType::Longword,
+            operand: stack(-8),
+        },
+        InstructionAsm::Div {
+            ty: AsmType::Longword,
+            operand: reg(Register::R10),
+        },
+        InstructionAsm::Cmp {
+            ty: AsmType::Longword,
+            src: imm(0),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Cmp {
+            ty: AsmType::Longword,
+            src: imm(1000),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Cmp {
+            ty: AsmType::Longword,
+            src: reg(Register::R10),
+            dst: stack(-4),
+        },
+        InstructionAsm::Cmp {
+            ty: AsmType::Longword,
+            src: stack(-4),
+            dst: reg(Register::DX),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Quadword,
+            src: imm(-3),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Quadword,
+            src: stack(-16),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Quadword,
+            src: imm(4294967300),
+            dst: reg(Register::R10),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Quadword,
+            src: imm(i64::from(i32::MIN)),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Quadword,
+            binop: AsmBinaryOp::Add,
+            src: imm(i64::from(i32::MIN)),
+            dst: stack(-8),
+        },
+        InstructionAsm::Unary {
+            ty: AsmType::Quadword,
+            unop: AsmUnaryOp::Not,
+            operand: reg(Register::BX),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Quadword,
+            binop: AsmBinaryOp::Imul,
+            src: imm(3000),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Quadword,
+            binop: AsmBinaryOp::Sar,
+            src: imm(63),
+            dst: stack(-8),
+        },
+        InstructionAsm::Cdq {
+            ty: AsmType::Quadword,
+        },
+        InstructionAsm::Idiv {
+            ty: AsmType::Quadword,
+            operand: reg(Register::R10),
+        },
+        InstructionAsm::Div {
+            ty: AsmType::Quadword,
+            operand: reg(Register::R10),
+        },
+        InstructionAsm::Cmp {
+            ty: AsmType::Quadword,
+            src: imm(1000),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::SetCC {
+            cc: CondCode::L,
+            operand: reg(Register::AX),
+        },
+        InstructionAsm::SetCC {
+            cc: CondCode::GE,
+            operand: reg(Register::R11),
+        },
+        InstructionAsm::SetCC {
+            cc: CondCode::NE,
+            operand: reg(Register::BP),
+        },
+        InstructionAsm::SetCC {
+            cc: CondCode::G,
+            operand: stack(-1),
+        },
+        InstructionAsm::SetCC {
+            cc: CondCode::B,
+            operand: reg(Register::AX),
+        },
+        InstructionAsm::SetCC {
+            cc: CondCode::AE,
+            operand: reg(Register::R11),
+        },
+        InstructionAsm::SetCC {
+            cc: CondCode::A,
+            operand: stack(-1),
+        },
+        InstructionAsm::Movzx {
+            src_ty: AsmType::Byte,
+            dst_ty: AsmType::Longword,
+            src: reg(Register::AX),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Movzx {
+            src_ty: AsmType::Byte,
+            dst_ty: AsmType::Longword,
+            src: reg(Register::SP),
+            dst: reg(Register::DX),
+        },
+        InstructionAsm::Movzx {
+            src_ty: AsmType::Byte,
+            dst_ty: AsmType::Longword,
+            src: stack(-1),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Movzx {
+            src_ty: AsmType::Byte,
+            dst_ty: AsmType::Quadword,
+            src: stack(-1),
+            dst: reg(Register::R10),
+        },
+        InstructionAsm::Movzx {
+            src_ty: AsmType::Word,
+            dst_ty: AsmType::Longword,
+            src: stack(-2),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Movzx {
+            src_ty: AsmType::Word,
+            dst_ty: AsmType::Quadword,
+            src: reg(Register::SI),
+            dst: reg(Register::DI),
+        },
+        InstructionAsm::Movsx {
+            src_ty: AsmType::Longword,
+            dst_ty: AsmType::Quadword,
+            src: reg(Register::R10),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Movsx {
+            src_ty: AsmType::Longword,
+            dst_ty: AsmType::Quadword,
+            src: stack(-4),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Movsx {
+            src_ty: AsmType::Byte,
+            dst_ty: AsmType::Longword,
+            src: reg(Register::DI),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Movsx {
+            src_ty: AsmType::Byte,
+            dst_ty: AsmType::Quadword,
+            src: stack(-1),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Movsx {
+            src_ty: AsmType::Word,
+            dst_ty: AsmType::Longword,
+            src: stack(-2),
+            dst: reg(Register::DX),
+        },
+        InstructionAsm::Movsx {
+            src_ty: AsmType::Word,
+            dst_ty: AsmType::Quadword,
+            src: reg(Register::R10),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Movsx {
+            src_ty: AsmType::Byte,
+            dst_ty: AsmType::Word,
+            src: stack(-1),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Movzx {
+            src_ty: AsmType::Byte,
+            dst_ty: AsmType::Word,
+            src: reg(Register::SI),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Cmov {
+            ty: AsmType::Longword,
+            cc: CondCode::E,
+            src: reg(Register::R10),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Cmov {
+            ty: AsmType::Longword,
+            cc: CondCode::LE,
+            src: stack(-4),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Cmov {
+            ty: AsmType::Quadword,
+            cc: CondCode::BE,
+            src: stack(-8),
+            dst: reg(Register::R10),
+        },
+        InstructionAsm::Cmov {
+            ty: AsmType::Quadword,
+            cc: CondCode::NE,
+            src: reg(Register::SP),
+            dst: reg(Register::R12),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Double,
+            src: stack(-8),
+            dst: reg(Register::XMM0),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Double,
+            src: reg(Register::XMM5),
+            dst: stack(-16),
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Double,
+            src: reg(Register::XMM4),
+            dst: OperandAsm::Memory {
+                base: Register::SP,
+                off: 8,
+            },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Double,
+            src: reg(Register::XMM7),
+            dst: reg(Register::XMM1),
+        },
+        InstructionAsm::Cmp {
+            ty: AsmType::Double,
+            src: stack(-8),
+            dst: reg(Register::XMM5),
+        },
+        InstructionAsm::Cvttsd2si {
+            ty: AsmType::Longword,
+            src: stack(-8),
+            dst: reg(Register::R11),
+        },
+        InstructionAsm::Cvttsd2si {
+            ty: AsmType::Quadword,
+            src: reg(Register::XMM3),
+            dst: reg(Register::AX),
+        },
+        InstructionAsm::Cvtsi2sd {
+            ty: AsmType::Longword,
+            src: stack(-4),
+            dst: reg(Register::XMM5),
+        },
+        InstructionAsm::Cvtsi2sd {
+            ty: AsmType::Quadword,
+            src: reg(Register::R10),
+            dst: reg(Register::XMM2),
+        },
+        InstructionAsm::Pop { r: Register::R14 },
+        InstructionAsm::Ret,
+    ];
+    // the byte forms, with registers that need a REX prefix to be named at all
+    for (src, dst) in [
+        (imm(-56), reg(Register::SI)),
+        (imm(7), reg(Register::R10)),
+        (imm(-1), stack(-1)),
+        (reg(Register::SI), stack(-1)),
+        (stack(-1), reg(Register::DI)),
+        (reg(Register::AX), reg(Register::BP)),
+    ] {
+        instrs.push(InstructionAsm::Mov {
+            ty: AsmType::Byte,
+            src,
+            dst,
+        });
+    }
+    for (src, dst) in [
+        (imm(0), reg(Register::AX)),
+        (imm(0), stack(-1)),
+        (imm(5), reg(Register::DI)),
+        (reg(Register::R10), stack(-1)),
+        (stack(-1), reg(Register::SI)),
+    ] {
+        instrs.push(InstructionAsm::Cmp {
+            ty: AsmType::Byte,
+            src,
+            dst,
+        });
+    }
+    for (src, dst) in [
+        (imm(1000), reg(Register::AX)),
+        (imm(-3), reg(Register::R11)),
+        (imm(1000), stack(-2)),
+        (reg(Register::SI), stack(-2)),
+        (stack(-2), reg(Register::R10)),
+    ] {
+        instrs.push(InstructionAsm::Mov {
+            ty: AsmType::Word,
+            src,
+            dst,
+        });
+    }
+    for (src, dst) in [
+        (imm(0), stack(-2)),
+        (imm(1000), reg(Register::AX)),
+        (imm(1000), stack(-2)),
+        (imm(5), reg(Register::R10)),
+        (reg(Register::DX), stack(-2)),
+        (stack(-2), reg(Register::SI)),
+    ] {
+        instrs.push(InstructionAsm::Cmp {
+            ty: AsmType::Word,
+            src,
+            dst,
+        });
+    }
+    for binop in [
+        AsmBinaryOp::Add,
+        AsmBinaryOp::Sub,
+        AsmBinaryOp::Imul,
+        AsmBinaryOp::DivDouble,
+        AsmBinaryOp::Xor,
+    ] {
+        for src in [stack(-8), reg(Register::XMM6)] {
+            instrs.push(InstructionAsm::Binary {
+                ty: AsmType::Double,
+                binop,
+                src,
+                dst: reg(Register::XMM5),
+            });
+        }
+    }
+    let binops = [
+        AsmBinaryOp::Add,
+        AsmBinaryOp::Sub,
+        AsmBinaryOp::And,
+        AsmBinaryOp::Or,
+        AsmBinaryOp::Xor,
+    ];
+    for (binop, ty) in binops
+        .into_iter()
+        .flat_map(|b| [(b, AsmType::Longword), (b, AsmType::Quadword)])
+    {
+        for (src, dst) in [
+            (imm(5), stack(-4)),
+            (imm(5000), stack(-4)),
+            (imm(5000), reg(Register::AX)),
+            (imm(5000), reg(Register::R12)),
+            (reg(Register::R10), stack(-4)),
+            (stack(-4), reg(Register::R10)),
+        ] {
+            instrs.push(InstructionAsm::Binary {
+                ty,
+                binop,
+                src,
+                dst,
+            });
+        }
+    }
+    for binop in [AsmBinaryOp::Shl, AsmBinaryOp::Sar, AsmBinaryOp::Shr] {
+        for (ty, dst) in [
+            (AsmType::Longword, stack(-4)),
+            (AsmType::Quadword, reg(Register::AX)),
+            (AsmType::Quadword, reg(Register::R12)),
+        ] {
+            instrs.push(InstructionAsm::Binary {
+                ty,
+                binop,
+                src: reg(Register::CX),
+                dst,
+            });
+        }
+    }
+
+    let formatter = AsmFormatter::default();
+    let text: String = instrs
+        .iter()
+        .map(|i| format!("\t{}\n", formatter.instruction(i)))
+        .collect();
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("gnu.s");
+    let object = dir.path().join("gnu.o");
+    let raw = dir.path().join("gnu.bin");
+    std::fs::write(&source, text).unwrap();
+    assert!(std::process::Command::new("as")
+        .arg(&source)
+        .arg("-o")
+        .arg(&object)
+        .status()
+        .unwrap()
+        .success());
+    assert!(std::process::Command::new("objcopy")
+        .args(["-O", "binary", "-j", ".text"])
+        .arg(&object)
+        .arg(&raw)
+        .status()
+        .unwrap()
+        .success());
+
+    assert_eq!(
+        encode_instructions(&instrs).unwrap(),
+        std::fs::read(raw).unwrap()
+    );
+}
+
+/// each directive follows an advance to its offset, in the shortest form that
+/// reaches it, and saved registers count down from the CFA in 8-byte steps.
+#[test]
+fn encode_call_frame_instructions() {
+    let cfi = [
+        (0, CfiDirective::StartProc),
+        (1, CfiDirective::DefCfaOffset { off: 16 }),
+        (
+            1,
+            CfiDirective::Offset {
+                r: Register::BP,
+                off: -16,
+            },
+        ),
+        (4, CfiDirective::DefCfaRegister { r: Register::BP }),
+        (0x50, CfiDirective::RememberState),
+        (
+            0x300,
+            CfiDirective::DefCfa {
+                r: Register::SP,
+                off: 200,
+            },
+        ),
+        (0x10300, CfiDirective::RestoreState),
+        (0x10300, CfiDirective::EndProc),
+    ];
+    assert_eq!(
+        encode_cfi(&cfi),
+        vec![
+            0x41, 0x0E, 16, 0x86, 2, 0x43, 0x0D, 6, 0x02, 0x4C, 0x0A, 0x03, 0xB0, 0x02, 0x0C, 7,
+            0xC8, 0x01, 0x04, 0, 0, 1, 0, 0x0B,
+        ]
+    );
+}
+
+/// a jump nothing defines is reported rather than encoded.
+#[test]
+fn undefined_label_is_an_error() {
+    let err = encode_instructions(&[InstructionAsm::Jmp {
+        target: String::from("nowhere"),
+    }])
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "(!) Internal compiler error: jump to undefined label nowhere"
+    );
+}
@@ -0,0 +1,1089 @@
+//! An interpreter for TACKY, for differential testing of the backend.
+//! It runs `main` directly on the TACKY program, keeping temporaries in a map,
+//! and gives the value `main` returns without going through assembly, gcc or
+//! the machine. A call runs the function it names with a map of its own, its
+//! parameters set to the arguments; only functions defined in the program can
+//! be called, and calls nest at most `MAX_CALL_DEPTH` deep. A tail call's
+//! callee takes the place of the function making it, so those don't nest.
+//! Arithmetic matches x86-64 at each value's width: addition, subtraction,
+//! multiplication and negation wrap, and division truncates toward zero with
+//! the remainder taking the dividend's sign. Sign extension and truncation
+//! convert between widths, and `double` arithmetic is IEEE 754's, as constant
+//! folding does it. Where `idiv` would trap, the interpreter returns an error
+//! instead. Variables with static storage are kept apart from the frames, for
+//! every call to share; one defined outside the program can't be read or
+//! written. Taking a variable's address records it as a place, and the pointer
+//! is the place's number and a byte offset into it, so loads and stores reach
+//! the variable itself, in whichever frame it lives, and an array's elements
+//! are told apart by their offsets; one through a null pointer, into a call
+//! that has returned, or outside the variable, is an error. A string literal's
+//! constant is a static array like any other, its bytes already stored. A
+//! structure's members are kept the way an array's elements are, so copying one
+//! copies each byte's cell, and a static structure's that were never stored to
+//! copy as zero.
+use std::{collections::HashMap, fmt::Display};
+use thiserror::Error;
+
+use super::{
+    optimize::const_fold::{fold_binary, fold_unary},
+    parser::{BinaryOp, CType, Const},
+    symbol::Symbol,
+    tacky::{FunDefTacky, InstructionTacky, ProgramTacky, StaticInit, TopLevelTacky, ValTacky},
+};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum InterpretError {
+    DivideByZero {
+        function: String,
+        index: usize,
+    },
+    DivideOverflow {
+        function: String,
+        index: usize,
+    },
+    NoMain,
+    UndefinedTmp {
+        function: String,
+        name: String,
+    },
+    ExternalVariable {
+        function: String,
+        name: String,
+    },
+    UndefinedLabel {
+        function: String,
+        name: String,
+    },
+    NoReturn {
+        function: String,
+    },
+    UndefinedFunction {
+        function: String,
+        name: String,
+    },
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        got: usize,
+    },
+    CallTooDeep {
+        function: String,
+    },
+    BadPointer {
+        function: String,
+        index: usize,
+    },
+}
+
+impl Display for InterpretError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DivideByZero { function, index } => write!(
+                f,
+                "(!) Division by zero in {} at instruction {}",
+                function, index
+            ),
+            Self::DivideOverflow { function, index } => write!(
+                f,
+                "(!) Division of the least int by -1 overflows in {} at instruction {}",
+                function, index
+            ),
+            Self::NoMain => write!(f, "(!) No main function to run"),
+            Self::UndefinedTmp { function, name } => {
+                write!(f, "(!) {} read before it is written in {}", name, function)
+            }
+            Self::ExternalVariable { function, name } => write!(
+                f,
+                "(!) {} is used in {}, but the program doesn't define it",
+                name, function
+            ),
+            Self::UndefinedLabel { function, name } => {
+                write!(f, "(!) Jump to undefined label {} in {}", name, function)
+            }
+            Self::NoReturn { function } => {
+                write!(f, "(!) {} ran off its end without returning", function)
+            }
+            Self::UndefinedFunction { function, name } => write!(
+                f,
+                "(!) Call to {} in {}, which the program doesn't define",
+                name, function
+            ),
+            Self::ArgumentCount {
+                name,
+                expected,
+                got,
+            } => write!(
+                f,
+                "(!) {} takes {} arguments but was passed {}",
+                name, expected, got
+            ),
+            Self::CallTooDeep { function } => write!(
+                f,
+                "(!) Calls nested more than {} deep in {}",
+                MAX_CALL_DEPTH, function
+            ),
+            Self::BadPointer { function, index } => write!(
+                f,
+                "(!) Pointer to no live variable dereferenced in {} at instruction {}",
+                function, index
+            ),
+        }
+    }
+}
+
+/// How deeply calls may nest before the program is taken to recurse without end.
+pub const MAX_CALL_DEPTH: usize = 100_000;
+
+/// runs the program's `main`, returning the value it returns.
+/// Calls are kept on a stack of frames of its own rather than the interpreter's,
+/// so a program that recurses deeply can't overflow it.
+pub fn interpret(prog: &ProgramTacky) -> Result<i32, InterpretError> {
+    let functions: HashMap<Symbol, &FunDefTacky> =
+        prog.functions().map(|f| (f.identifier, f)).collect();
+    let main = match functions.get(&Symbol::intern("main")) {
+        Some(main) => Frame::new(main, &[], 0),
+        None => return Err(InterpretError::NoMain),
+    };
+    let mut places = Places::default();
+    let mut statics: Statics = prog
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            // an array's elements are kept with the places, so its value only
+            // marks it defined
+            TopLevelTacky::StaticVariable { name, init, .. } => {
+                Some((*name, init.as_ref().map(|init| places.define(*name, init))))
+            }
+            TopLevelTacky::StaticConstant { name, init } => {
+                Some((*name, Some(places.define(*name, init))))
+            }
+            TopLevelTacky::Function { .. } | TopLevelTacky::File { .. } => None,
+        })
+        .collect();
+
+    let mut frames = vec![main];
+    let mut calls = 1;
+    loop {
+        let frame = frames.last_mut().expect("a function is always running");
+        let fundef = frame.fundef;
+        let function = fundef.identifier.as_str();
+        let Some(instr) = fundef.instructions.get(frame.pc) else {
+            return Err(InterpretError::NoReturn {
+                function: function.to_string(),
+            });
+        };
+        let mut next = frame.pc + 1;
+        let written = match instr {
+            InstructionTacky::Ret { v } => {
+                let res = frame.read(v, &statics)?;
+                frames.pop();
+                let Some(caller) = frames.last_mut() else {
+                    return Ok(res.value() as i32);
+                };
+                // the caller is still at its call, waiting for the result
+                if let InstructionTacky::FunCall { dst, .. } =
+                    &caller.fundef.instructions[caller.pc]
+                {
+                    caller.write(dst, res, &mut statics)?;
+                }
+                caller.pc += 1;
+                continue;
+            }
+            InstructionTacky::Unary { op, src, dst } => {
+                Some((dst, fold_unary(op, frame.read(src, &statics)?)))
+            }
+            InstructionTacky::Copy { src, dst }
+                if (src.ty().is_struct() || src.ty().is_array()) && src.ty() == dst.ty() =>
+            {
+                let src = places.address(frame.place(src, &statics), src.ty());
+                let dst_ptr = places.address(frame.place(dst, &statics), dst.ty());
+                places.copy(src, dst_ptr, dst.ty(), &statics, &function, frame.pc)?;
+                None
+            }
+            // a copy may also be between a signed and an unsigned type of one size
+            InstructionTacky::SignExtend { src, dst }
+            | InstructionTacky::Truncate { src, dst }
+            | InstructionTacky::ZeroExtend { src, dst }
+            | InstructionTacky::DoubleToInt { src, dst }
+            | InstructionTacky::IntToDouble { src, dst }
+            | InstructionTacky::Copy { src, dst } => {
+                Some((dst, frame.read(src, &statics)?.convert(dst.ty())))
+            }
+            // the difference of two pointers is a `long`
+            InstructionTacky::Binary {
+                op,
+                src1,
+                src2,
+                dst,
+            } => Some((
+                dst,
+                binary(
+                    op,
+                    frame.read(src1, &statics)?,
+                    frame.read(src2, &statics)?,
+                    &function,
+                    frame.pc,
+                )?
+                .convert(dst.ty()),
+            )),
+            InstructionTacky::AddPtr {
+                ptr,
+                index,
+                scale,
+                dst,
+            } => {
+                let ptr = frame.read(ptr, &statics)?.value();
+                let offset = frame
+                    .read(index, &statics)?
+                    .value()
+                    .wrapping_mul(*scale as i64);
+                let ulong = ptr.wrapping_add(offset) as u64;
+                Some((dst, Const::ULong { ulong }))
+            }
+            InstructionTacky::Jump { target } => {
+                next = frame.jump(target)?;
+                None
+            }
+            InstructionTacky::JumpIfZero { condition, target } => {
+                if frame.read(condition, &statics)?.is_zero() {
+                    next = frame.jump(target)?;
+                }
+                None
+            }
+            InstructionTacky::JumpIfNotZero { condition, target } => {
+                if !frame.read(condition, &statics)?.is_zero() {
+                    next = frame.jump(target)?;
+                }
+                None
+            }
+            InstructionTacky::Label { .. } | InstructionTacky::Loc { .. } => None,
+            InstructionTacky::FunCall { name, args, .. }
+            | InstructionTacky::TailCall { name, args } => {
+                let callee = match functions.get(name) {
+                    Some(callee) => callee,
+                    None => {
+                        return Err(InterpretError::UndefinedFunction {
+                            function: function.to_string(),
+                            name: name.to_string(),
+                        })
+                    }
+                };
+                if callee.params.len() != args.len() {
+                    return Err(InterpretError::ArgumentCount {
+                        name: name.to_string(),
+                        expected: callee.params.len(),
+                        got: args.len(),
+                    });
+                }
+                let args = args
+                    .iter()
+                    .map(|arg| frame.read(arg, &statics))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if frames.len() > MAX_CALL_DEPTH {
+                    return Err(InterpretError::CallTooDeep {
+                        function: function.to_string(),
+                    });
+                }
+                // the callee returns to this function's caller, in its place
+                if let InstructionTacky::TailCall { .. } = instr {
+                    frames.pop();
+                }
+                frames.push(Frame::new(callee, &args, calls));
+                calls += 1;
+                continue;
+            }
+            InstructionTacky::GetAddress { src, dst } => {
+                Some((dst, places.address(frame.place(src, &statics), src.ty())))
+            }
+            InstructionTacky::Load { src_ptr, dst } if dst.ty().is_struct() => {
+                let src = frame.read(src_ptr, &statics)?;
+                let dst_ptr = places.address(frame.place(dst, &statics), dst.ty());
+                places.copy(src, dst_ptr, dst.ty(), &statics, &function, frame.pc)?;
+                None
+            }
+            InstructionTacky::Store { src, dst_ptr } if src.ty().is_struct() => {
+                let dst = frame.read(dst_ptr, &statics)?;
+                let src_ptr = places.address(frame.place(src, &statics), src.ty());
+                places.copy(src_ptr, dst, src.ty(), &statics, &function, frame.pc)?;
+                None
+            }
+            // the other frames are reached through `frames`, so this one is
+            // looked up again afterwards
+            InstructionTacky::Load { src_ptr, dst } => {
+                let index = frame.pc;
+                let ptr = frame.read(src_ptr, &statics)?;
+                let res = places.load(ptr, dst.ty(), &frames, &statics, &function, index)?;
+                let frame = frames.last_mut().expect("a function is always running");
+                frame.write(dst, res.convert(dst.ty()), &mut statics)?;
+                frame.pc = next;
+                continue;
+            }
+            InstructionTacky::Store { src, dst_ptr } => {
+                let index = frame.pc;
+                let res = frame.read(src, &statics)?;
+                let ptr = frame.read(dst_ptr, &statics)?;
+                places.store(ptr, res, &mut frames, &mut statics, &function, index)?;
+                frames.last_mut().expect("a function is always running").pc = next;
+                continue;
+            }
+        };
+        if let Some((dst, res)) = written {
+            frame.write(dst, res, &mut statics)?;
+        }
+        frame.pc = next;
+    }
+}
+
+/// The variables with static storage, and their values; `None` for those
+/// defined outside the program.
+type Statics = HashMap<Symbol, Option<Const>>;
+
+/// a variable whose address has been taken: one with static storage, or one
+/// in the frame of the call numbered `frame`.
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+enum Place {
+    Static { name: Symbol },
+    Local { frame: usize, name: Symbol },
+}
+
+/// The places addresses have been taken of, with their types, and the values
+/// of the elements of the arrays among them, by place number and byte offset.
+/// A pointer is its place's number plus one in the upper 32 bits, which leaves
+/// 0 for the null pointer, and the offset in the lower 32.
+#[derive(Default)]
+struct Places {
+    places: Vec<(Place, CType)>,
+    numbers: HashMap<Place, usize>,
+    cells: HashMap<(usize, u32), Const>,
+}
+
+impl Places {
+    /// the pointer to `place`, of type `ty`, the same each time its address
+    /// is taken.
+    fn address(&mut self, place: Place, ty: CType) -> Const {
+        let places = &mut self.places;
+        let no = *self.numbers.entry(place).or_insert_with(|| {
+            places.push((place, ty));
+            places.len() - 1
+        });
+        Const::ULong {
+            ulong: (no as u64 + 1) << 32,
+        }
+    }
+
+    /// the value of the static variable `name` initialized to `init`. A
+    /// string's bytes go in the cells of its place, like any array's elements,
+    /// and an address is the pointer to the constant it names.
+    fn define(&mut self, name: Symbol, init: &StaticInit) -> Const {
+        match init {
+            StaticInit::Scalar { c } => *c,
+            StaticInit::Zero { .. } => Const::ULong { ulong: 0 },
+            StaticInit::String { val, .. } => {
+                let ptr = self.address(Place::Static { name }, init.ty());
+                let no = (ptr.value() as u64 >> 32) as usize - 1;
+                for (offset, byte) in val.iter().enumerate() {
+                    let char = *byte as i8;
+                    self.cells.insert((no, offset as u32), Const::Char { char });
+                }
+                Const::ULong { ulong: 0 }
+            }
+            StaticInit::Address { name, ty } => self.address(Place::Static { name: *name }, *ty),
+        }
+    }
+
+    /// the place a `ty` `ptr` points to is in, and for an array or structure
+    /// the cell holding it. A null or made-up pointer points to no place, and
+    /// one to a scalar must point to the start of it, one into an array or
+    /// structure within it.
+    fn locate(
+        &self,
+        ptr: Const,
+        ty: CType,
+        function: &str,
+        index: usize,
+    ) -> Result<(Place, Option<(usize, u32)>), InterpretError> {
+        let bits = ptr.value() as u64;
+        let offset = bits as u32;
+        let no = (bits >> 32).checked_sub(1).map(|no| no as usize);
+        match no.and_then(|no| Some((no, self.places.get(no)?))) {
+            Some((no, (place, aggregate)))
+                if is_aggregate(aggregate) && offset as usize + ty.size() <= aggregate.size() =>
+            {
+                Ok((*place, Some((no, offset))))
+            }
+            Some((_, (place, scalar))) if !is_aggregate(scalar) && offset == 0 => {
+                Ok((*place, None))
+            }
+            _ => Err(InterpretError::BadPointer {
+                function: function.to_string(),
+                index,
+            }),
+        }
+    }
+
+    /// an element of a static array that was never stored to is zero, and
+    /// one of a local array has to be stored to before it is read.
+    fn load(
+        &self,
+        ptr: Const,
+        ty: CType,
+        frames: &[Frame],
+        statics: &Statics,
+        function: &str,
+        index: usize,
+    ) -> Result<Const, InterpretError> {
+        let (place, cell) = self.locate(ptr, ty, function, index)?;
+        let (id, name) = match place {
+            Place::Static { name } => {
+                let defined = statics[&name].ok_or_else(|| InterpretError::ExternalVariable {
+                    function: function.to_string(),
+                    name: name.to_string(),
+                })?;
+                return Ok(match cell {
+                    Some(cell) => self
+                        .cells
+                        .get(&cell)
+                        .copied()
+                        .unwrap_or(Const::Int { int: 0 }.convert(ty)),
+                    None => defined,
+                });
+            }
+            Place::Local { frame, name } => (frame, name),
+        };
+        let Some(frame) = frames.iter().find(|frame| frame.id == id) else {
+            return Err(InterpretError::BadPointer {
+                function: function.to_string(),
+                index,
+            });
+        };
+        let value = match cell {
+            Some(cell) => self.cells.get(&cell),
+            None => frame.tmps.get(&name),
+        };
+        value.copied().ok_or(InterpretError::UndefinedTmp {
+            function: frame.fundef.identifier.to_string(),
+            name: name.to_string(),
+        })
+    }
+
+    /// copies the structure of type `ty` that `src` points to where `dst`
+    /// points, a cell at a time. Both have to be in an array or structure, in a
+    /// variable the program defines or a call still running.
+    fn copy(
+        &mut self,
+        src: Const,
+        dst: Const,
+        ty: CType,
+        statics: &Statics,
+        function: &str,
+        index: usize,
+    ) -> Result<(), InterpretError> {
+        let bad_pointer = || InterpretError::BadPointer {
+            function: function.to_string(),
+            index,
+        };
+        let (src_place, src_cell) = self.locate(src, ty, function, index)?;
+        let (dst_place, dst_cell) = self.locate(dst, ty, function, index)?;
+        let (Some((src_no, src_off)), Some((dst_no, dst_off))) = (src_cell, dst_cell) else {
+            return Err(bad_pointer());
+        };
+        for place in [src_place, dst_place] {
+            if let Place::Static { name } = place {
+                if statics[&name].is_none() {
+                    return Err(InterpretError::ExternalVariable {
+                        function: function.to_string(),
+                        name: name.to_string(),
+                    });
+                }
+            }
+        }
+        let zero = matches!(src_place, Place::Static { .. });
+        for byte in 0..ty.size() as u32 {
+            let dst = (dst_no, dst_off + byte);
+            match self.cells.get(&(src_no, src_off + byte)).copied() {
+                Some(c) => self.cells.insert(dst, c),
+                None if zero => self.cells.insert(dst, Const::Char { char: 0 }),
+                None => self.cells.remove(&dst),
+            };
+        }
+        Ok(())
+    }
+
+    fn store(
+        &mut self,
+        ptr: Const,
+        res: Const,
+        frames: &mut [Frame],
+        statics: &mut Statics,
+        function: &str,
+        index: usize,
+    ) -> Result<(), InterpretError> {
+        let (place, cell) = self.locate(ptr, res.ty(), function, index)?;
+        match place {
+            Place::Static { name } => match (statics.get_mut(&name), cell) {
+                (Some(Some(_)), Some(cell)) => {
+                    self.cells.insert(cell, res);
+                }
+                (Some(Some(value)), None) => *value = res,
+                _ => {
+                    return Err(InterpretError::ExternalVariable {
+                        function: function.to_string(),
+                        name: name.to_string(),
+                    })
+                }
+            },
+            Place::Local { frame: id, name } => {
+                let Some(frame) = frames.iter_mut().find(|frame| frame.id == id) else {
+                    return Err(InterpretError::BadPointer {
+                        function: function.to_string(),
+                        index,
+                    });
+                };
+                match cell {
+                    Some(cell) => self.cells.insert(cell, res),
+                    None => frame.tmps.insert(name, res),
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+/// a call in progress: the function, where it has got to, and its temporaries,
+/// the parameters among them. `id` numbers the calls in the order they are made.
+struct Frame<'a> {
+    fundef: &'a FunDefTacky,
+    labels: HashMap<&'a str, usize>,
+    tmps: HashMap<Symbol, Const>,
+    pc: usize,
+    id: usize,
+}
+
+impl<'a> Frame<'a> {
+    /// a call to `fundef` about to start, its parameters set to `args`.
+    fn new(fundef: &'a FunDefTacky, args: &[Const], id: usize) -> Self {
+        let labels = fundef
+            .instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, instr)| match instr {
+                InstructionTacky::Label { name } => Some((name.as_str(), index)),
+                _ => None,
+            })
+            .collect();
+        let tmps = fundef
+            .params
+            .iter()
+            .filter_map(|param| match param {
+                ValTacky::TmpVar { name, .. } => Some(*name),
+                ValTacky::Const { .. } => None,
+            })
+            .zip(args.iter().copied())
+            .collect();
+        Frame {
+            fundef,
+            labels,
+            tmps,
+            pc: 0,
+            id,
+        }
+    }
+
+    fn read(&self, val: &ValTacky, statics: &Statics) -> Result<Const, InterpretError> {
+        match val {
+            ValTacky::Const { c } => Ok(*c),
+            ValTacky::TmpVar { name, .. } if statics.contains_key(name) => {
+                statics[name].ok_or_else(|| self.external(*name))
+            }
+            ValTacky::TmpVar { name, .. } => {
+                self.tmps
+                    .get(name)
+                    .copied()
+                    .ok_or(InterpretError::UndefinedTmp {
+                        function: self.fundef.identifier.to_string(),
+                        name: name.to_string(),
+                    })
+            }
+        }
+    }
+
+    fn write(
+        &mut self,
+        dst: &ValTacky,
+        res: Const,
+        statics: &mut Statics,
+    ) -> Result<(), InterpretError> {
+        if let ValTacky::TmpVar { name, .. } = dst {
+            match statics.get_mut(name) {
+                Some(Some(value)) => *value = res,
+                Some(None) => return Err(self.external(*name)),
+                None => {
+                    self.tmps.insert(*name, res);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// the place of the variable `val`, in this call unless it has static storage.
+    fn place(&self, val: &ValTacky, statics: &Statics) -> Place {
+        let ValTacky::TmpVar { name, .. } = val else {
+            unreachable!("only a variable has an address")
+        };
+        match statics.contains_key(name) {
+            true => Place::Static { name: *name },
+            false => Place::Local {
+                frame: self.id,
+                name: *name,
+            },
+        }
+    }
+
+    fn external(&self, name: Symbol) -> InterpretError {
+        InterpretError::ExternalVariable {
+            function: self.fundef.identifier.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    fn jump(&self, target: &str) -> Result<usize, InterpretError> {
+        match self.labels.get(target) {
+            Some(index) => Ok(*index),
+            None => Err(InterpretError::UndefinedLabel {
+                function: self.fundef.identifier.to_string(),
+                name: target.to_string(),
+            }),
+        }
+    }
+}
+
+/// whether a variable of type `ty` is kept in cells, one for each byte a
+/// value is stored at, rather than as a single value.
+fn is_aggregate(ty: &CType) -> bool {
+    ty.is_array() || ty.is_struct()
+}
+
+/// works the result out exactly, then keeps as many bits as the operands'
+/// type has; comparisons follow that type's ordering, and give an `int`.
+/// `double`s and shifts are folded, as neither ever traps.
+fn binary(
+    op: &BinaryOp,
+    l: Const,
+    r: Const,
+    function: &str,
+    index: usize,
+) -> Result<Const, InterpretError> {
+    let ty = l.ty();
+    if ty == CType::Double {
+        return Ok(fold_binary(op, l, r).expect("only integers take the bitwise operators and '%'"));
+    }
+    if matches!(op, BinaryOp::LeftShift | BinaryOp::RightShift) {
+        return Ok(fold_binary(op, l, r).expect("an integer can always be shifted"));
+    }
+    let (l, r) = (l.wide(), r.wide());
+    if matches!(op, BinaryOp::Divide | BinaryOp::Remainder) {
+        if r == 0 {
+            return Err(InterpretError::DivideByZero {
+                function: function.to_string(),
+                index,
+            });
+        }
+        // idiv traps on the quotient not fitting, for the remainder too
+        if Const::wrap(l / r, ty).wide() != l / r {
+            return Err(InterpretError::DivideOverflow {
+                function: function.to_string(),
+                index,
+            });
+        }
+    }
+    let res = match op {
+        BinaryOp::Add => l + r,
+        BinaryOp::Subtract => l - r,
+        BinaryOp::Multiply => l.wrapping_mul(r),
+        BinaryOp::Divide => l / r,
+        BinaryOp::Remainder => l % r,
+        BinaryOp::BitwiseAnd => l & r,
+        BinaryOp::BitwiseOr => l | r,
+        BinaryOp::BitwiseXor => l ^ r,
+        BinaryOp::LeftShift | BinaryOp::RightShift => unreachable!("shifts are folded above"),
+        BinaryOp::LessThan => return Ok(truth(l < r)),
+        BinaryOp::GreaterThan => return Ok(truth(l > r)),
+        BinaryOp::LessOrEqual => return Ok(truth(l <= r)),
+        BinaryOp::GreaterOrEqual => return Ok(truth(l >= r)),
+        BinaryOp::Equal => return Ok(truth(l == r)),
+        BinaryOp::NotEqual => return Ok(truth(l != r)),
+        BinaryOp::And => return Ok(truth(l != 0 && r != 0)),
+        BinaryOp::Or => return Ok(truth(l != 0 || r != 0)),
+    };
+    Ok(Const::wrap(res, ty))
+}
+
+fn truth(holds: bool) -> Const {
+    Const::Int { int: holds as i32 }
+}
+
+#[cfg(test)]
+use super::{lexer::tokenize, parser::parse, tacky::gen_tacky};
+
+#[cfg(test)]
+fn interpret_source(source: &str) -> Result<i32, InterpretError> {
+    interpret(&gen_tacky(
+        parse(tokenize(String::from(source)).unwrap()).unwrap(),
+    ))
+}
+
+/// quotients truncate toward zero and remainders take the dividend's sign.
+#[test]
+fn division_matches_idiv() {
+    for (source, expected) in [
+        ("int main(void) { return 7 / 2; }", 3),
+        ("int main(void) { return -7 / 2; }", -3),
+        ("int main(void) { return 7 / -2; }", -3),
+        ("int main(void) { return -7 % 2; }", -1),
+        ("int main(void) { return 7 % -2; }", 1),
+    ] {
+        assert_eq!(interpret_source(source), Ok(expected), "{}", source);
+    }
+}
+
+#[test]
+fn arithmetic_wraps() {
+    assert_eq!(
+        interpret_source("int main(void) { return 2147483647 + 1; }"),
+        Ok(i32::MIN)
+    );
+    assert_eq!(
+        interpret_source("int main(void) { return -(-2147483647 - 1); }"),
+        Ok(i32::MIN)
+    );
+    assert_eq!(
+        interpret_source("int main(void) { return 65536 * 65536 + ~5; }"),
+        Ok(-6)
+    );
+}
+
+/// where `idiv` traps, the interpreter reports which instruction did it.
+#[test]
+fn trapping_division_is_an_error() {
+    assert_eq!(
+        interpret_source("int main(void) { return 1 + 2 / (3 - 3); }"),
+        Err(InterpretError::DivideByZero {
+            function: String::from("main"),
+            index: 1,
+        })
+    );
+    assert!(matches!(
+        interpret_source("int main(void) { return (-2147483647 - 1) % -1; }"),
+        Err(InterpretError::DivideOverflow { .. })
+    ));
+}
+
+#[test]
+fn runs_main_and_follows_jumps() {
+    assert_eq!(
+        interpret_source("int f(void) { return 1; } int main(void) { return 2; }"),
+        Ok(2)
+    );
+    assert_eq!(
+        interpret_source("int f(void) { return 1; }"),
+        Err(InterpretError::NoMain)
+    );
+    let tmp = ValTacky::tmp;
+    let label = |name: &str| String::from(name);
+    let main = FunDefTacky {
+        identifier: Symbol::intern("main"),
+        global: true,
+        params: vec![],
+        instructions: vec![
+            InstructionTacky::Copy {
+                src: ValTacky::int(0),
+                dst: tmp(0),
+            },
+            InstructionTacky::JumpIfZero {
+                condition: tmp(0),
+                target: label("zero"),
+            },
+            InstructionTacky::Ret { v: tmp(0) },
+            InstructionTacky::Label {
+                name: label("zero"),
+            },
+            InstructionTacky::JumpIfNotZero {
+                condition: ValTacky::int(3),
+                target: label("end"),
+            },
+            InstructionTacky::Ret { v: tmp(1) },
+            InstructionTacky::Label { name: label("end") },
+            InstructionTacky::Ret {
+                v: ValTacky::int(9),
+            },
+        ],
+    };
+    let prog = |fundef| ProgramTacky {
+        items: vec![super::tacky::TopLevelTacky::Function { fundef }],
+    };
+    assert_eq!(interpret(&prog(main)), Ok(9));
+    let undefined = FunDefTacky {
+        identifier: Symbol::intern("main"),
+        global: true,
+        params: vec![],
+        instructions: vec![InstructionTacky::Jump {
+            target: label("nowhere"),
+        }],
+    };
+    assert!(matches!(
+        interpret(&prog(undefined)),
+        Err(InterpretError::UndefinedLabel { .. })
+    ));
+}
+
+/// each call gets temporaries of its own, so recursion works.
+#[test]
+fn calls_run_with_their_own_variables() {
+    assert_eq!(
+        interpret_source(
+            "int fib(int n) { if (n < 2) return n; int a = fib(n - 1); return a + fib(n - 2); } \
+             int main(void) { return fib(10); }"
+        ),
+        Ok(55)
+    );
+    assert_eq!(
+        interpret_source("int main(void) { return putchar(65); }"),
+        Err(InterpretError::UndefinedFunction {
+            function: String::from("main"),
+            name: String::from("putchar"),
+        })
+    );
+    assert_eq!(
+        interpret_source("int f(int a) { return a; } int main(void) { return f(1, 2); }"),
+        Err(InterpretError::ArgumentCount {
+            name: String::from("f"),
+            expected: 1,
+            got: 2,
+        })
+    );
+    assert!(matches!(
+        interpret_source("int f(int a) { return f(a); } int main(void) { return f(1); }"),
+        Err(InterpretError::CallTooDeep { .. })
+    ));
+}
+
+/// a `long` keeps all 64 bits of its arithmetic, and converting it to an `int`
+/// keeps the low 32.
+#[test]
+fn long_arithmetic_and_conversions() {
+    assert_eq!(
+        interpret_source(
+            "long big(long a) { return a * 4L; } \
+             int main(void) { long x = big(1073741824L); return (x / 1024L) == 4194304L; }"
+        ),
+        Ok(1)
+    );
+    let main = FunDefTacky {
+        identifier: Symbol::intern("main"),
+        global: true,
+        params: vec![],
+        instructions: vec![
+            InstructionTacky::Truncate {
+                src: ValTacky::Const {
+                    c: Const::Long {
+                        long: (1 << 32) + 7,
+                    },
+                },
+                dst: ValTacky::tmp(0),
+            },
+            InstructionTacky::Ret {
+                v: ValTacky::tmp(0),
+            },
+        ],
+    };
+    let prog = ProgramTacky {
+        items: vec![TopLevelTacky::Function { fundef: main }],
+    };
+    assert_eq!(interpret(&prog), Ok(7));
+}
+
+/// every call shares the variables with static storage, which start out with
+/// their initializers; one the program only declares can't be used.
+#[test]
+fn statics_outlive_calls() {
+    assert_eq!(
+        interpret_source(
+            "int total = 5; int add(int n) { static int calls; calls = calls + 1; \
+             total = total + n; return calls; } \
+             int main(void) { add(3); add(4); return add(0) * 100 + total; }"
+        ),
+        Ok(312)
+    );
+    assert_eq!(
+        interpret_source("extern int elsewhere; int main(void) { return elsewhere; }"),
+        Err(InterpretError::ExternalVariable {
+            function: String::from("main"),
+            name: String::from("elsewhere"),
+        })
+    );
+}
+
+/// stores through a pointer change the variable itself, and a pointer into a
+/// call that has returned, or a null one, can't be followed.
+#[test]
+fn pointers_reach_live_variables_only() {
+    let ptr = ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", 1),
+        ty: CType::Int.pointer_to(),
+    };
+    let run = |functions: Vec<(&str, Vec<InstructionTacky>)>| {
+        interpret(&ProgramTacky {
+            items: functions
+                .into_iter()
+                .map(|(name, instructions)| TopLevelTacky::Function {
+                    fundef: FunDefTacky {
+                        identifier: Symbol::intern(name),
+                        global: true,
+                        params: vec![],
+                        instructions,
+                    },
+                })
+                .collect(),
+        })
+    };
+    let take_address = vec![
+        InstructionTacky::Copy {
+            src: ValTacky::int(1),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::GetAddress {
+            src: ValTacky::tmp(0),
+            dst: ptr.clone(),
+        },
+    ];
+
+    let mut main = take_address.clone();
+    main.extend([
+        InstructionTacky::Store {
+            src: ValTacky::int(9),
+            dst_ptr: ptr.clone(),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(0),
+        },
+    ]);
+    assert_eq!(run(vec![("main", main)]), Ok(9));
+
+    let mut escape = take_address;
+    escape.push(InstructionTacky::Ret { v: ptr.clone() });
+    let main = vec![
+        InstructionTacky::FunCall {
+            name: Symbol::intern("escape"),
+            args: vec![],
+            dst: ptr.clone(),
+        },
+        InstructionTacky::Load {
+            src_ptr: ptr,
+            dst: ValTacky::tmp(2),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(2),
+        },
+    ];
+    assert_eq!(
+        run(vec![("escape", escape), ("main", main)]),
+        Err(InterpretError::BadPointer {
+            function: String::from("main"),
+            index: 1,
+        })
+    );
+
+    let main = vec![InstructionTacky::Load {
+        src_ptr: ValTacky::Const {
+            c: Const::ULong { ulong: 0 },
+        },
+        dst: ValTacky::tmp(0),
+    }];
+    assert!(matches!(
+        run(vec![("main", main)]),
+        Err(InterpretError::BadPointer { index: 0, .. })
+    ));
+}
+
+/// each element of an array is a value of its own, which has to be stored
+/// before it is read, and a pointer past the end reaches none of them.
+#[test]
+fn arrays_hold_elements_in_bounds() {
+    let array = ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", 0),
+        ty: CType::Int.array_of(2),
+    };
+    let ptr = |no| ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", no),
+        ty: CType::Int.pointer_to(),
+    };
+    let run = |element: i64, read: i64| {
+        let index = |long| ValTacky::Const {
+            c: Const::Long { long },
+        };
+        interpret(&ProgramTacky {
+            items: vec![TopLevelTacky::Function {
+                fundef: FunDefTacky {
+                    identifier: Symbol::intern("main"),
+                    global: true,
+                    params: vec![],
+                    instructions: vec![
+                        InstructionTacky::GetAddress {
+                            src: array.clone(),
+                            dst: ptr(1),
+                        },
+                        InstructionTacky::AddPtr {
+                            ptr: ptr(1),
+                            index: index(element),
+                            scale: 4,
+                            dst: ptr(2),
+                        },
+                        InstructionTacky::Store {
+                            src: ValTacky::int(5),
+                            dst_ptr: ptr(2),
+                        },
+                        InstructionTacky::AddPtr {
+                            ptr: ptr(1),
+                            index: index(read),
+                            scale: 4,
+                            dst: ptr(3),
+                        },
+                        InstructionTacky::Load {
+                            src_ptr: ptr(3),
+                            dst: ValTacky::tmp(4),
+                        },
+                        InstructionTacky::Ret {
+                            v: ValTacky::tmp(4),
+                        },
+                    ],
+                },
+            }],
+        })
+    };
+    assert_eq!(run(1, 1), Ok(5));
+    assert!(matches!(
+        run(0, 1),
+        Err(InterpretError::UndefinedTmp { .. })
+    ));
+    assert_eq!(
+        run(2, 0),
+        Err(InterpretError::BadPointer {
+            function: String::from("main"),
+            index: 2,
+        })
+    );
+}
+
+/// a string literal's bytes are there to be read, its null included, and a
+/// pointer past the null reaches nothing.
+#[test]
+fn strings_hold_their_bytes() {
+    assert_eq!(
+        interpret_source("int main(void) { char *s = \"a\\tb\"; return s[1] + s[2] + s[3]; }"),
+        Ok(107)
+    );
+    assert!(matches!(
+        interpret_source("int main(void) { return \"ab\"[3]; }"),
+        Err(InterpretError::BadPointer { .. })
+    ));
+}
@@ -1,58 +1,196 @@
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::{fmt::Display, str::FromStr};
+use std::{
+    fmt::Display,
+    io::{self, BufRead, BufReader, Read},
+    str::FromStr,
+};
 use thiserror::Error;
 
+use super::symbol::Symbol;
+
 lazy_static! {
     static ref idre: Regex =    // identifiers
         Regex::new(r"^[a-zA-Z_]\w*\b").expect("failure creating identifier regex");
-    static ref constre: Regex = Regex::new(r"^[0-9]+\b").expect("failure creating const regex");    // constants
+    static ref constre: Regex = Regex::new(r"^([0-9]+(?:[lL][uU]?|[uU][lL]?)?)(?:[^\w.]|$)").expect("failure creating const regex");    // constants
+    static ref doublere: Regex =    // floating-point constants, tried before integer ones
+        Regex::new(r"^((?:[0-9]*\.[0-9]+|[0-9]+\.?)[eE][+-]?[0-9]+|[0-9]*\.[0-9]+|[0-9]+\.)(?:[^\w.]|$)").expect("failure creating double regex");
     static ref single_char_re: Regex =    // single char tokens
-        Regex::new(r"^(\(|\)|\{|\}|;|\-|~|\+|\*|\/|%|&|\||\^)").expect("failure creating single_charre regex");
-    static ref double_char_re: Regex = Regex::new(r"^(?:\-|\+|>|<){2}").expect("failure creating double_charre regex");
-    // ^ double char tokens; may have some weirdness with multiple matches?
+        Regex::new(r"^(\(|\)|\{|\}|\[|\]|;|\-|~|\+|\*|\/|%|&|\||\^|<|>|\?|:|=|,|\.)").expect("failure creating single_charre regex");
+    static ref double_char_re: Regex =    // double and triple char tokens, tried before single char ones
+        Regex::new(r"^(?:<<=|>>=|->|--|\+\+|<<|>>|<=|>=|==|!=|&&|\|\||\+=|-=|\*=|/=|%=|&=|\|=|\^=)").expect("failure creating double_charre regex");
 }
 
 #[derive(Clone, Error, Debug)]
 pub enum LexError {
-    Unrecognized { strang: String },
+    Unrecognized { strang: String, span: Span },
+    ConstantTooLarge { strang: String, span: Span },
+    Unterminated { quote: char, span: Span },
+    BadEscape { strang: String, span: Span },
+    EscapeOutOfRange { strang: String, span: Span },
+    BadCharConstant { strang: String, span: Span },
+    UnterminatedComment { span: Span },
 }
 
 impl Display for LexError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Unrecognized { strang } => write!(
-                f,
-                "(!) Lexer error: Unrecognized syntax on string: {}",
-                strang
-            ),
+            Self::Unrecognized { strang, span: _ } if strang.chars().count() > 1 => {
+                write!(f, "unrecognized token '{}'", strang)
+            }
+            Self::Unrecognized { strang, span: _ } => {
+                write!(f, "unrecognized character '{}'", strang)
+            }
+            Self::ConstantTooLarge { strang, span: _ } => {
+                write!(f, "integer constant '{}' is too large for its type", strang)
+            }
+            Self::Unterminated { quote, span: _ } => {
+                write!(f, "missing terminating {} character", quote)
+            }
+            Self::BadEscape { strang, span: _ } => {
+                write!(f, "invalid escape sequence '{}'", strang)
+            }
+            Self::EscapeOutOfRange { strang, span: _ } => {
+                write!(f, "escape sequence '{}' is out of range for a byte", strang)
+            }
+            Self::BadCharConstant { strang, span: _ } if strang == "''" => {
+                write!(f, "empty character constant")
+            }
+            Self::BadCharConstant { strang, span: _ } => {
+                write!(
+                    f,
+                    "character constant {} holds more than one character",
+                    strang
+                )
+            }
+            Self::UnterminatedComment { span: _ } => write!(f, "unterminated comment"),
+        }
+    }
+}
+
+impl LexError {
+    /// where in the source the error was found.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Unrecognized { span, .. }
+            | Self::ConstantTooLarge { span, .. }
+            | Self::Unterminated { span, .. }
+            | Self::BadEscape { span, .. }
+            | Self::EscapeOutOfRange { span, .. }
+            | Self::BadCharConstant { span, .. }
+            | Self::UnterminatedComment { span } => *span,
+        }
+    }
+
+    /// the stable name of the error, for tools reading diagnostics.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Unrecognized { .. } => "unrecognized-token",
+            Self::ConstantTooLarge { .. } => "constant-too-large",
+            Self::Unterminated { .. } => "unterminated-literal",
+            Self::BadEscape { .. } => "bad-escape",
+            Self::EscapeOutOfRange { .. } => "escape-out-of-range",
+            Self::BadCharConstant { .. } => "bad-char-constant",
+            Self::UnterminatedComment { .. } => "unterminated-comment",
+        }
+    }
+}
+
+/// Byte range of the preprocessed source a token was read from.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// the span from the start of this one to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
         }
     }
 }
 
+/// A token and where it came from.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Type representing individual tokens.
 /// Tree structure should not be here.
 pub enum Token {
-    Identifier { val: String }, // [a-zA-Z_]\w*\b
-    Constant { val: i32 },      // [0-9]+\b
-    TyKeyword { ty: Type },     // whatever keyword followed by \b
-    RetKeyword,                 // return\b
-    OpenParens,                 // \(
-    CloseParens,                // \)
-    OpenBrace,                  // {
-    CloseBrace,                 // }
-    Semicolon,                  // ;
-    Minus,                      // -
-    MinusMinus,                 // --
-    Tilde,                      // ~
-    Plus,                       // +
-    Asterisk,                   // *
-    FSlash,                     // /
-    Percent,                    // %
-    Ampersand,                  // &
-    Pipe,                       // |
-    Caret,                      // ^
+    Identifier { val: Symbol },        // [a-zA-Z_]\w*\b
+    Constant { val: i64 },             // [0-9]+\b
+    LongConstant { val: i64 },         // [0-9]+[lL]\b
+    UnsignedConstant { val: u64 },     // [0-9]+[uU]\b
+    UnsignedLongConstant { val: u64 }, // [0-9]+([lL][uU]|[uU][lL])\b
+    DoubleConstant { val: f64 },       // [0-9]*\.[0-9]+, [0-9]+\. or either with an exponent
+    StringLiteral { val: Vec<u8> },    // "([^"\\\n]|\\.)*", escapes replaced by what they stand for
+    TyKeyword { ty: Type },            // whatever keyword followed by \b
+    RetKeyword,                        // return\b
+    IfKeyword,                         // if\b
+    ElseKeyword,                       // else\b
+    ForKeyword,                        // for\b
+    BreakKeyword,                      // break\b
+    ContinueKeyword,                   // continue\b
+    SwitchKeyword,                     // switch\b
+    CaseKeyword,                       // case\b
+    DefaultKeyword,                    // default\b
+    StaticKeyword,                     // static\b
+    ExternKeyword,                     // extern\b
+    StructKeyword,                     // struct\b
+    OpenParens,                        // \(
+    CloseParens,                       // \)
+    OpenBrace,                         // {
+    CloseBrace,                        // }
+    OpenBracket,                       // [
+    CloseBracket,                      // ]
+    Semicolon,                         // ;
+    Minus,                             // -
+    MinusMinus,                        // --
+    Tilde,                             // ~
+    Plus,                              // +
+    PlusPlus,                          // ++
+    Asterisk,                          // *
+    FSlash,                            // /
+    Percent,                           // %
+    Ampersand,                         // &
+    Pipe,                              // |
+    Caret,                             // ^
+    Less,                              // <
+    Greater,                           // >
+    LessEqual,                         // <=
+    GreaterEqual,                      // >=
+    LessLess,                          // <<
+    GreaterGreater,                    // >>
+    EqualEqual,                        // ==
+    BangEqual,                         // !=
+    AmpersandAmpersand,                // &&
+    PipePipe,                          // ||
+    Question,                          // ?
+    Colon,                             // :
+    Equal,                             // =
+    PlusEqual,                         // +=
+    MinusEqual,                        // -=
+    AsteriskEqual,                     // *=
+    FSlashEqual,                       // /=
+    PercentEqual,                      // %=
+    AmpersandEqual,                    // &=
+    PipeEqual,                         // |=
+    CaretEqual,                        // ^=
+    LessLessEqual,                     // <<=
+    GreaterGreaterEqual,               // >>=
+    Comma,                             // ,
+    Dot,                               // .
+    Arrow,                             // ->
 }
 
 impl Display for Token {
@@ -60,23 +198,144 @@ impl Display for Token {
         match self {
             Self::Identifier { val } => write!(f, "Identifier string (val = {})", val),
             Self::Constant { val } => write!(f, "Constant token (val = {})", val),
+            Self::LongConstant { val } => write!(f, "Long constant token (val = {})", val),
+            Self::UnsignedConstant { val } => write!(f, "Unsigned constant token (val = {})", val),
+            Self::UnsignedLongConstant { val } => {
+                write!(f, "Unsigned long constant token (val = {})", val)
+            }
+            Self::DoubleConstant { val } => write!(f, "Double constant token (val = {:?})", val),
+            Self::StringLiteral { val } => {
+                write!(f, "String literal token (val = \"{}\")", escape(val))
+            }
             Self::TyKeyword { ty } => write!(f, "Type keyword (ty = {})", ty),
             Self::RetKeyword => write!(f, "Return keyword"),
+            Self::IfKeyword => write!(f, "If keyword"),
+            Self::ElseKeyword => write!(f, "Else keyword"),
+            Self::ForKeyword => write!(f, "For keyword"),
+            Self::BreakKeyword => write!(f, "Break keyword"),
+            Self::ContinueKeyword => write!(f, "Continue keyword"),
+            Self::SwitchKeyword => write!(f, "Switch keyword"),
+            Self::CaseKeyword => write!(f, "Case keyword"),
+            Self::DefaultKeyword => write!(f, "Default keyword"),
+            Self::StaticKeyword => write!(f, "Static keyword"),
+            Self::ExternKeyword => write!(f, "Extern keyword"),
+            Self::StructKeyword => write!(f, "Struct keyword"),
             Self::OpenParens => write!(f, "( symbol"),
             Self::CloseParens => write!(f, ") symbol"),
             Self::OpenBrace => write!(f, "{{ symbol"),
             Self::CloseBrace => write!(f, "}} symbol"),
+            Self::OpenBracket => write!(f, "[ symbol"),
+            Self::CloseBracket => write!(f, "] symbol"),
             Self::Semicolon => write!(f, "; symbol"),
             Self::Minus => write!(f, "- symbol"),
             Self::MinusMinus => write!(f, "-- symbol"),
             Self::Tilde => write!(f, "~ symbol"),
             Self::Plus => write!(f, "+ symbol"),
+            Self::PlusPlus => write!(f, "++ symbol"),
             Self::Asterisk => write!(f, "* symbol"),
             Self::FSlash => write!(f, "/ symbol"),
             Self::Percent => write!(f, "% symbol"),
             Token::Ampersand => write!(f, "& symbol"),
             Token::Pipe => write!(f, "| symbol"),
             Token::Caret => write!(f, "^ symbol"),
+            Token::Less => write!(f, "< symbol"),
+            Token::Greater => write!(f, "> symbol"),
+            Token::LessEqual => write!(f, "<= symbol"),
+            Token::GreaterEqual => write!(f, ">= symbol"),
+            Token::LessLess => write!(f, "<< symbol"),
+            Token::GreaterGreater => write!(f, ">> symbol"),
+            Token::EqualEqual => write!(f, "== symbol"),
+            Token::BangEqual => write!(f, "!= symbol"),
+            Token::AmpersandAmpersand => write!(f, "&& symbol"),
+            Token::PipePipe => write!(f, "|| symbol"),
+            Token::Question => write!(f, "? symbol"),
+            Token::Colon => write!(f, ": symbol"),
+            Token::Equal => write!(f, "= symbol"),
+            Token::PlusEqual => write!(f, "+= symbol"),
+            Token::MinusEqual => write!(f, "-= symbol"),
+            Token::AsteriskEqual => write!(f, "*= symbol"),
+            Token::FSlashEqual => write!(f, "/= symbol"),
+            Token::PercentEqual => write!(f, "%= symbol"),
+            Token::AmpersandEqual => write!(f, "&= symbol"),
+            Token::PipeEqual => write!(f, "|= symbol"),
+            Token::CaretEqual => write!(f, "^= symbol"),
+            Token::LessLessEqual => write!(f, "<<= symbol"),
+            Token::GreaterGreaterEqual => write!(f, ">>= symbol"),
+            Token::Comma => write!(f, ", symbol"),
+            Token::Dot => write!(f, ". symbol"),
+            Token::Arrow => write!(f, "-> symbol"),
+        }
+    }
+}
+
+impl Token {
+    /// the token as written in C source, as quoted in diagnostics.
+    pub fn spelling(&self) -> String {
+        match self {
+            Self::Identifier { val } => val.to_string(),
+            Self::Constant { val } => val.to_string(),
+            Self::LongConstant { val } => format!("{}L", val),
+            Self::UnsignedConstant { val } => format!("{}U", val),
+            Self::UnsignedLongConstant { val } => format!("{}UL", val),
+            Self::DoubleConstant { val } => format!("{:?}", val),
+            Self::StringLiteral { val } => format!("\"{}\"", escape(val)),
+            Self::TyKeyword { ty } => ty.to_string(),
+            Self::RetKeyword => String::from("return"),
+            Self::IfKeyword => String::from("if"),
+            Self::ElseKeyword => String::from("else"),
+            Self::ForKeyword => String::from("for"),
+            Self::BreakKeyword => String::from("break"),
+            Self::ContinueKeyword => String::from("continue"),
+            Self::SwitchKeyword => String::from("switch"),
+            Self::CaseKeyword => String::from("case"),
+            Self::DefaultKeyword => String::from("default"),
+            Self::StaticKeyword => String::from("static"),
+            Self::ExternKeyword => String::from("extern"),
+            Self::StructKeyword => String::from("struct"),
+            Self::OpenParens => String::from("("),
+            Self::CloseParens => String::from(")"),
+            Self::OpenBrace => String::from("{"),
+            Self::CloseBrace => String::from("}"),
+            Self::OpenBracket => String::from("["),
+            Self::CloseBracket => String::from("]"),
+            Self::Semicolon => String::from(";"),
+            Self::Minus => String::from("-"),
+            Self::MinusMinus => String::from("--"),
+            Self::Tilde => String::from("~"),
+            Self::Plus => String::from("+"),
+            Self::PlusPlus => String::from("++"),
+            Self::Asterisk => String::from("*"),
+            Self::FSlash => String::from("/"),
+            Self::Percent => String::from("%"),
+            Self::Ampersand => String::from("&"),
+            Self::Pipe => String::from("|"),
+            Self::Caret => String::from("^"),
+            Self::Less => String::from("<"),
+            Self::Greater => String::from(">"),
+            Self::LessEqual => String::from("<="),
+            Self::GreaterEqual => String::from(">="),
+            Self::LessLess => String::from("<<"),
+            Self::GreaterGreater => String::from(">>"),
+            Self::EqualEqual => String::from("=="),
+            Self::BangEqual => String::from("!="),
+            Self::AmpersandAmpersand => String::from("&&"),
+            Self::PipePipe => String::from("||"),
+            Self::Question => String::from("?"),
+            Self::Colon => String::from(":"),
+            Self::Equal => String::from("="),
+            Self::PlusEqual => String::from("+="),
+            Self::MinusEqual => String::from("-="),
+            Self::AsteriskEqual => String::from("*="),
+            Self::FSlashEqual => String::from("/="),
+            Self::PercentEqual => String::from("%="),
+            Self::AmpersandEqual => String::from("&="),
+            Self::PipeEqual => String::from("|="),
+            Self::CaretEqual => String::from("^="),
+            Self::LessLessEqual => String::from("<<="),
+            Self::GreaterGreaterEqual => String::from(">>="),
+            Self::Comma => String::from(","),
+            Self::Dot => String::from("."),
+            Self::Arrow => String::from("->"),
         }
     }
 }
@@ -90,29 +349,67 @@ impl FromStr for Token {
             r")" => Ok(Self::CloseParens),
             r"{" => Ok(Self::OpenBrace),
             r"}" => Ok(Self::CloseBrace),
+            r"[" => Ok(Self::OpenBracket),
+            r"]" => Ok(Self::CloseBracket),
             r";" => Ok(Self::Semicolon),
             r"-" => Ok(Self::Minus),
             r"--" => Ok(Self::MinusMinus),
             r"~" => Ok(Self::Tilde),
             r"+" => Ok(Self::Plus),
+            r"++" => Ok(Self::PlusPlus),
             r"*" => Ok(Self::Asterisk),
             r"/" => Ok(Self::FSlash),
             r"%" => Ok(Self::Percent),
             r"&" => Ok(Self::Ampersand),
             r"|" => Ok(Self::Pipe),
             r"^" => Ok(Self::Caret),
+            r"<" => Ok(Self::Less),
+            r">" => Ok(Self::Greater),
+            r"<=" => Ok(Self::LessEqual),
+            r">=" => Ok(Self::GreaterEqual),
+            r"<<" => Ok(Self::LessLess),
+            r">>" => Ok(Self::GreaterGreater),
+            r"==" => Ok(Self::EqualEqual),
+            r"!=" => Ok(Self::BangEqual),
+            r"&&" => Ok(Self::AmpersandAmpersand),
+            r"||" => Ok(Self::PipePipe),
+            r"?" => Ok(Self::Question),
+            r":" => Ok(Self::Colon),
+            r"=" => Ok(Self::Equal),
+            r"+=" => Ok(Self::PlusEqual),
+            r"-=" => Ok(Self::MinusEqual),
+            r"*=" => Ok(Self::AsteriskEqual),
+            r"/=" => Ok(Self::FSlashEqual),
+            r"%=" => Ok(Self::PercentEqual),
+            r"&=" => Ok(Self::AmpersandEqual),
+            r"|=" => Ok(Self::PipeEqual),
+            r"^=" => Ok(Self::CaretEqual),
+            r"<<=" => Ok(Self::LessLessEqual),
+            r">>=" => Ok(Self::GreaterGreaterEqual),
+            r"," => Ok(Self::Comma),
+            r"." => Ok(Self::Dot),
+            r"->" => Ok(Self::Arrow),
             _ => Err(LexError::Unrecognized {
                 strang: s.to_string(),
+                span: Span::default(),
             }),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Type for types supported by the compiler.
 /// Only useful in tokenizing.
 pub enum Type {
     Int,
+    Long,
+    Short,
+    Char,
+    Signed,
+    Unsigned,
+    Double,
+    Bool,
     Void,
 }
 
@@ -120,50 +417,413 @@ impl Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Type::Int => write!(f, "int"),
+            Type::Long => write!(f, "long"),
+            Type::Short => write!(f, "short"),
+            Type::Char => write!(f, "char"),
+            Type::Signed => write!(f, "signed"),
+            Type::Unsigned => write!(f, "unsigned"),
+            Type::Double => write!(f, "double"),
+            Type::Bool => write!(f, "_Bool"),
             Type::Void => write!(f, "void"),
         }
     }
 }
 
-/// Tokenize function, literally translating a source file
-/// into a stream of tokens.
-pub fn tokenize(source: String) -> Result<Vec<Token>, LexError> {
-    let mut strang = source.as_str().trim();
-    let mut tokens = Vec::new();
+/// Lex function, literally translating a source file
+/// into a stream of tokens, each with its span in the source.
+/// Collects a Lexer, stopping at the first error; the compiler pulls tokens
+/// as it parses instead.
+pub fn lex(source: &str) -> Result<Vec<SpannedToken>, LexError> {
+    Lexer::new(source.as_bytes()).collect()
+}
+
+#[cfg(test)]
+pub fn tokenize(source: String) -> Result<Vec<SpannedToken>, LexError> {
+    lex(&source)
+}
+
+/// Tokens read lazily from the source, one per call to `next`. The source is
+/// read a logical line at a time, as tokens are asked for, so only the line
+/// being lexed is held, and memory grows with the longest line rather than
+/// with the file.
+/// Linemarkers left by the preprocessor are skipped like whitespace, and `\r`
+/// is whitespace too, so `\r\n` line endings lex like `\n`. So are `//` and
+/// `/* */` comments, which the preprocessor would have taken out, so source
+/// that hasn't been through one lexes the same.
+/// After an error the lexer is done, and yields nothing more. Failing to read
+/// the source, or source that isn't UTF-8, ends the tokens as the end of the
+/// source would; `read_error` tells the two apart.
+pub struct Lexer<R> {
+    source: BufReader<R>,
+    line: Line,
+    pos: usize,
+    /// bytes of the source read so far
+    read: usize,
+    /// the physical line being read, kept to reuse its buffer
+    bytes: Vec<u8>,
+    done: bool,
+    read_error: Option<io::Error>,
+}
 
-    while !(strang.is_empty()) {
-        strang = strang.trim_start();
-        tokens.push(if let Some(mat) = idre.find(strang) {
-            strang = strang.trim_start_matches(mat.as_str());
-            check_for_keywords(mat.as_str())
-        } else if let Some(mat) = constre.find(strang) {
-            strang = strang.strip_prefix(mat.as_str()).unwrap();
-            Token::Constant {
-                val: mat.as_str().parse().unwrap(),
+impl<R: Read> Lexer<R> {
+    pub fn new(source: R) -> Self {
+        Lexer {
+            source: BufReader::new(source),
+            line: Line {
+                text: String::new(),
+                start: 0,
+                splices: vec![],
+            },
+            pos: 0,
+            read: 0,
+            bytes: vec![],
+            done: false,
+            read_error: None,
+        }
+    }
+
+    /// the error reading the source that ended the tokens early, if one did.
+    pub fn read_error(&mut self) -> Option<io::Error> {
+        self.read_error.take()
+    }
+
+    /// reads the next logical line into `line`, returning whether there was
+    /// one. An error reading it is kept for `read_error`, and ends the source.
+    fn next_line(&mut self) -> bool {
+        self.line.text.clear();
+        self.line.splices.clear();
+        self.line.start = self.read;
+        self.pos = 0;
+        loop {
+            self.bytes.clear();
+            match self.source.read_until(b'\n', &mut self.bytes) {
+                Ok(0) => return !self.line.text.is_empty(),
+                Ok(read) => self.read += read,
+                Err(e) => {
+                    self.read_error = Some(e);
+                    return false;
+                }
             }
-        } else if let Some(mat) = double_char_re.find(strang) {
-            strang = strang.strip_prefix(mat.as_str()).unwrap();
-            Token::from(mat.as_str().parse().unwrap())
-        } else if let Some(mat) = single_char_re.find(strang) {
-            strang = strang.strip_prefix(mat.as_str()).unwrap();
-            Token::from(mat.as_str().parse().unwrap())
+            let Ok(physical) = std::str::from_utf8(&self.bytes) else {
+                self.read_error = Some(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                ));
+                return false;
+            };
+            let Some(spliced) = physical
+                .strip_suffix("\\\n")
+                .or_else(|| physical.strip_suffix("\\\r\n"))
+            else {
+                self.line.text.push_str(physical);
+                return true;
+            };
+            self.line.text.push_str(spliced);
+            let deleted = self.line.splices.last().map_or(0, |(_, deleted)| *deleted);
+            self.line.splices.push((
+                self.line.text.len(),
+                deleted + physical.len() - spliced.len(),
+            ));
+        }
+    }
+
+    /// skips whitespace, comments and any `# <line> "<file>"` linemarker lines,
+    /// reading lines as it goes, and returns whether a token follows. A `/*`
+    /// comment that is never closed is an error.
+    fn skip_blanks(&mut self) -> Result<bool, LexError> {
+        loop {
+            let rest = &self.line.text[self.pos..];
+            let at_line_start = self.pos == 0;
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.is_empty() {
+                if !self.next_line() {
+                    return Ok(false);
+                }
+            } else if at_line_start && trimmed.starts_with('#') || trimmed.starts_with("//") {
+                self.pos = self.line.text.len();
+            } else if trimmed.starts_with("/*") {
+                let open = self.line.physical(self.pos, self.pos + 2);
+                self.pos += 2;
+                loop {
+                    if let Some(i) = self.line.text[self.pos..].find("*/") {
+                        self.pos += i + 2;
+                        break;
+                    }
+                    if !self.next_line() {
+                        return match self.read_error {
+                            Some(_) => Ok(false),
+                            None => Err(LexError::UnterminatedComment { span: open }),
+                        };
+                    }
+                }
+            } else {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn lex_token(&mut self) -> Result<SpannedToken, LexError> {
+        let start = self.pos;
+        let rest = &self.line.text[start..];
+        let span_of = |len: usize| self.line.physical(start, start + len);
+        let (token, len) = if rest.starts_with(['\'', '"']) {
+            let (val, len) = lex_quoted(rest, |from, to| {
+                self.line.physical(start + from, start + to)
+            })?;
+            // a character constant is an `int`, of the value a `char` of its
+            // byte has
+            let token = match (rest.starts_with('"'), val.as_slice()) {
+                (true, _) => Token::StringLiteral { val },
+                (false, [byte]) => Token::Constant {
+                    val: *byte as i8 as i64,
+                },
+                (false, _) => {
+                    return Err(LexError::BadCharConstant {
+                        strang: rest[..len].to_string(),
+                        span: span_of(len),
+                    })
+                }
+            };
+            (token, len)
+        } else if let Some(mat) = idre.find(rest) {
+            (check_for_keywords(mat.as_str()), mat.len())
+        } else if let Some(caps) = doublere.captures(rest) {
+            // the character after the constant is checked but not taken in;
+            // every constant this matches parses, rounding to the nearest double
+            let mat = caps.get(1).unwrap();
+            let val = mat
+                .as_str()
+                .parse()
+                .expect("a matched double constant parses");
+            (Token::DoubleConstant { val }, mat.len())
+        } else if let Some(caps) = constre.captures(rest) {
+            // like a double's, the character after the constant is checked but
+            // not taken in; the parser gives one too large for `int` the wider type
+            let mat = caps.get(1).unwrap();
+            let digits = mat.as_str().trim_end_matches(['l', 'L', 'u', 'U']);
+            let suffix = mat.as_str()[digits.len()..].to_ascii_lowercase();
+            let too_large = |_| LexError::ConstantTooLarge {
+                strang: mat.as_str().to_string(),
+                span: span_of(mat.len()),
+            };
+            let token = match suffix.as_str() {
+                "" => Token::Constant {
+                    val: digits.parse().map_err(too_large)?,
+                },
+                "l" => Token::LongConstant {
+                    val: digits.parse().map_err(too_large)?,
+                },
+                "u" => Token::UnsignedConstant {
+                    val: digits.parse().map_err(too_large)?,
+                },
+                _ => Token::UnsignedLongConstant {
+                    val: digits.parse().map_err(too_large)?,
+                },
+            };
+            (token, mat.len())
+        } else if let Some(mat) = double_char_re
+            .find(rest)
+            .or_else(|| single_char_re.find(rest))
+        {
+            let token = mat.as_str().parse().map_err(|_| LexError::Unrecognized {
+                strang: mat.as_str().to_string(),
+                span: span_of(mat.len()),
+            })?;
+            (token, mat.len())
         } else {
+            let bad = rest.chars().next().unwrap();
             return Err(LexError::Unrecognized {
-                strang: strang.to_string(),
+                strang: bad.to_string(),
+                span: span_of(bad.len_utf8()),
             });
-        });
+        };
+        let span = span_of(len);
+        self.pos += len;
+        Ok(SpannedToken { token, span })
     }
+}
 
-    Ok(tokens)
+impl<R: Read> Iterator for Lexer<R> {
+    type Item = Result<SpannedToken, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let token = match self.skip_blanks() {
+            Ok(true) => self.lex_token(),
+            Ok(false) => {
+                self.done = true;
+                return None;
+            }
+            Err(e) => Err(e),
+        };
+        self.done = token.is_err();
+        Some(token)
+    }
+}
+
+/// Reads the character constant or string literal `rest` starts with, up to
+/// the closing quote matching the opening one, giving the bytes it stands for
+/// and its length. It has to end on the line it starts on. `span_at` turns an
+/// offset range into `rest` into a span of the source.
+fn lex_quoted(
+    rest: &str,
+    span_at: impl Fn(usize, usize) -> Span,
+) -> Result<(Vec<u8>, usize), LexError> {
+    let text = rest.as_bytes();
+    let quote = text[0];
+    let mut val = vec![];
+    let mut i = 1;
+    loop {
+        match text.get(i) {
+            None | Some(b'\n') => {
+                return Err(LexError::Unterminated {
+                    quote: quote as char,
+                    span: span_at(0, i),
+                })
+            }
+            Some(&c) if c == quote => return Ok((val, i + 1)),
+            // a backslash ending the source leaves the literal unterminated
+            Some(b'\\') if i + 1 == text.len() => {
+                return Err(LexError::Unterminated {
+                    quote: quote as char,
+                    span: span_at(0, i + 1),
+                })
+            }
+            Some(b'\\') => {
+                let (byte, len) = unescape(&rest[i + 1..]).map_err(|(len, out_of_range)| {
+                    let (strang, span) = (rest[i..=i + len].to_string(), span_at(i, i + 1 + len));
+                    match out_of_range {
+                        true => LexError::EscapeOutOfRange { strang, span },
+                        false => LexError::BadEscape { strang, span },
+                    }
+                })?;
+                val.push(byte);
+                i += 1 + len;
+            }
+            // a character outside ASCII is kept as its UTF-8 bytes
+            Some(&c) => {
+                val.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// the byte the escape sequence after a backslash at the start of `rest`
+/// stands for, and its length: one of the simple escapes like `\n`, up to
+/// three octal digits, or `\x` and any number of hex digits, whose value has
+/// to fit in a byte. An error gives the length of the bad sequence, and
+/// whether it is only bad for standing for more than a byte.
+fn unescape(rest: &str) -> Result<(u8, usize), (usize, bool)> {
+    let simple = match rest.chars().next() {
+        Some('\'') => b'\'',
+        Some('"') => b'"',
+        Some('?') => b'?',
+        Some('\\') => b'\\',
+        Some('a') => 0x07,
+        Some('b') => 0x08,
+        Some('f') => 0x0c,
+        Some('n') => b'\n',
+        Some('r') => b'\r',
+        Some('t') => b'\t',
+        Some('v') => 0x0b,
+        Some('0'..='7') => {
+            let len = rest
+                .bytes()
+                .take(3)
+                .take_while(|b| (b'0'..=b'7').contains(b))
+                .count();
+            return u8::from_str_radix(&rest[..len], 8)
+                .map(|byte| (byte, len))
+                .map_err(|_| (len, true));
+        }
+        Some('x') => {
+            let len = 1 + rest[1..].bytes().take_while(u8::is_ascii_hexdigit).count();
+            return u8::from_str_radix(&rest[1..len], 16)
+                .map(|byte| (byte, len))
+                .map_err(|_| (len, len > 1));
+        }
+        Some(c) => return Err((c.len_utf8(), false)),
+        None => unreachable!("a backslash ending the source is checked for"),
+    };
+    Ok((simple, 1))
+}
+
+/// `val` as it could be written between the quotes of a string literal, in C
+/// or in assembly: printable ASCII as itself, but for a quote or a backslash,
+/// and any other byte as a three-digit octal escape, which can't run into a
+/// digit after it.
+pub fn escape(val: &[u8]) -> String {
+    let mut res = String::with_capacity(val.len());
+    for byte in val.iter() {
+        match byte {
+            b'"' => res.push_str("\\\""),
+            b'\\' => res.push_str("\\\\"),
+            b' '..=b'~' => res.push(*byte as char),
+            _ => res.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    res
+}
+
+/// A logical line of the source: a physical line, and those after it while
+/// each ends in a backslash-newline, which is deleted, splicing the lines around
+/// it into one, as translation phase 2 does. Spans are still reported against
+/// the physical source, so diagnostics point at the lines as written.
+struct Line {
+    text: String,
+    /// offset in the source of the line's first byte
+    start: usize,
+    /// for each splice, its offset in `text` and the bytes deleted up to and including it
+    splices: Vec<(usize, usize)>,
+}
+
+impl Line {
+    /// the physical span of `start..end` in the spliced text. A token starting just
+    /// after a splice starts on the next physical line, and one ending just before
+    /// it ends on the line with the backslash.
+    fn physical(&self, start: usize, end: usize) -> Span {
+        let deleted_before = |offset: usize, inclusive: bool| {
+            let n = self
+                .splices
+                .partition_point(|(at, _)| *at < offset || inclusive && *at == offset);
+            n.checked_sub(1).map_or(0, |i| self.splices[i].1)
+        };
+        Span {
+            start: self.start + start + deleted_before(start, true),
+            end: self.start + end + deleted_before(end, false),
+        }
+    }
 }
 
 fn check_for_keywords(strang: &str) -> Token {
     match strang {
         "int" => Token::TyKeyword { ty: Type::Int },
+        "long" => Token::TyKeyword { ty: Type::Long },
+        "short" => Token::TyKeyword { ty: Type::Short },
+        "char" => Token::TyKeyword { ty: Type::Char },
+        "signed" => Token::TyKeyword { ty: Type::Signed },
+        "unsigned" => Token::TyKeyword { ty: Type::Unsigned },
+        "double" => Token::TyKeyword { ty: Type::Double },
+        "_Bool" => Token::TyKeyword { ty: Type::Bool },
         "void" => Token::TyKeyword { ty: Type::Void },
         "return" => Token::RetKeyword,
+        "if" => Token::IfKeyword,
+        "else" => Token::ElseKeyword,
+        "for" => Token::ForKeyword,
+        "break" => Token::BreakKeyword,
+        "continue" => Token::ContinueKeyword,
+        "switch" => Token::SwitchKeyword,
+        "case" => Token::CaseKeyword,
+        "default" => Token::DefaultKeyword,
+        "static" => Token::StaticKeyword,
+        "extern" => Token::ExternKeyword,
+        "struct" => Token::StructKeyword,
         _ => Token::Identifier {
-            val: String::from(strang),
+            val: Symbol::intern(strang),
         },
     }
 }
@@ -171,11 +831,15 @@ fn check_for_keywords(strang: &str) -> Token {
 #[test]
 fn test_lex_nested_cmp() {
     let source = String::from("int main(void) { return ~(~(~2)); }");
-    let tokens = tokenize(source).unwrap();
+    let tokens: Vec<Token> = tokenize(source)
+        .unwrap()
+        .into_iter()
+        .map(|t| t.token)
+        .collect();
     let expected = vec![
         Token::TyKeyword { ty: Type::Int },
         Token::Identifier {
-            val: String::from("main"),
+            val: Symbol::intern("main"),
         },
         Token::OpenParens,
         Token::TyKeyword { ty: Type::Void },
@@ -199,7 +863,11 @@ fn test_lex_nested_cmp() {
 #[test]
 fn test_parenthesis() {
     let source = String::from("(())");
-    let tokens = tokenize(source).unwrap();
+    let tokens: Vec<Token> = tokenize(source)
+        .unwrap()
+        .into_iter()
+        .map(|t| t.token)
+        .collect();
     let expected = vec![
         Token::OpenParens,
         Token::OpenParens,
@@ -213,24 +881,509 @@ fn test_parenthesis() {
 /// BE SURE TO CHANGE THIS TEST WITH MORE OPERATORS
 #[test]
 fn test_lex_operators() {
-    let source = String::from(r"( ) { } ; - -- ~ + * / % & | ^");
-    let tokens = tokenize(source).unwrap();
+    let source = String::from(
+        r"( ) { } [ ] ; - -- ~ + ++ * / % & | ^ < > <= >= == != && || ? : = += -= *= /= %= &= |= ^= << >> <<= >>= , . ->",
+    );
+    let tokens: Vec<Token> = tokenize(source)
+        .unwrap()
+        .into_iter()
+        .map(|t| t.token)
+        .collect();
     let expected = vec![
         Token::OpenParens,
         Token::CloseParens,
         Token::OpenBrace,
         Token::CloseBrace,
+        Token::OpenBracket,
+        Token::CloseBracket,
         Token::Semicolon,
         Token::Minus,
         Token::MinusMinus,
         Token::Tilde,
         Token::Plus,
+        Token::PlusPlus,
         Token::Asterisk,
         Token::FSlash,
         Token::Percent,
         Token::Ampersand,
         Token::Pipe,
         Token::Caret,
+        Token::Less,
+        Token::Greater,
+        Token::LessEqual,
+        Token::GreaterEqual,
+        Token::EqualEqual,
+        Token::BangEqual,
+        Token::AmpersandAmpersand,
+        Token::PipePipe,
+        Token::Question,
+        Token::Colon,
+        Token::Equal,
+        Token::PlusEqual,
+        Token::MinusEqual,
+        Token::AsteriskEqual,
+        Token::FSlashEqual,
+        Token::PercentEqual,
+        Token::AmpersandEqual,
+        Token::PipeEqual,
+        Token::CaretEqual,
+        Token::LessLess,
+        Token::GreaterGreater,
+        Token::LessLessEqual,
+        Token::GreaterGreaterEqual,
+        Token::Comma,
+        Token::Dot,
+        Token::Arrow,
     ];
     assert_eq!(tokens, expected);
 }
+
+/// a dot between names is member access, and one before digits starts a
+/// `double`; `->` is one token, not a minus and a greater.
+#[test]
+fn test_lex_member_access() {
+    let tokens: Vec<Token> = tokenize(String::from("a.b->c-.5"))
+        .unwrap()
+        .into_iter()
+        .map(|t| t.token)
+        .collect();
+    let expected = vec![
+        Token::Identifier {
+            val: Symbol::intern("a"),
+        },
+        Token::Dot,
+        Token::Identifier {
+            val: Symbol::intern("b"),
+        },
+        Token::Arrow,
+        Token::Identifier {
+            val: Symbol::intern("c"),
+        },
+        Token::Minus,
+        Token::DoubleConstant { val: 0.5 },
+    ];
+    assert_eq!(tokens, expected);
+}
+
+/// operators written together split into the longest tokens, so a comparison
+/// with a negated operand needs no space, and `a+++b` is `a++ + b`.
+#[test]
+fn test_lex_adjacent_operators() {
+    let tokens: Vec<Token> = tokenize(String::from("1<-2>=~3"))
+        .unwrap()
+        .into_iter()
+        .map(|t| t.token)
+        .collect();
+    let expected = vec![
+        Token::Constant { val: 1 },
+        Token::Less,
+        Token::Minus,
+        Token::Constant { val: 2 },
+        Token::GreaterEqual,
+        Token::Tilde,
+        Token::Constant { val: 3 },
+    ];
+    assert_eq!(tokens, expected);
+    let tokens: Vec<Token> = tokenize(String::from("a+++b"))
+        .unwrap()
+        .into_iter()
+        .map(|t| t.token)
+        .collect();
+    let (a, b) = (Symbol::intern("a"), Symbol::intern("b"));
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Identifier { val: a },
+            Token::PlusPlus,
+            Token::Plus,
+            Token::Identifier { val: b },
+        ]
+    );
+}
+
+/// spans are byte ranges of the source, and linemarkers don't produce tokens.
+#[test]
+fn test_spans_skip_linemarkers() {
+    let source = String::from("# 1 \"a.c\"\nint  main\n# 7 \"a.c\"\n  (--");
+    let tokens = tokenize(source.clone()).unwrap();
+    let spans: Vec<&str> = tokens
+        .iter()
+        .map(|t| &source[t.span.start..t.span.end])
+        .collect();
+    assert_eq!(spans, vec!["int", "main", "(", "--"]);
+    assert_eq!(tokens[1].span, Span { start: 15, end: 19 });
+}
+
+/// comments lex as whitespace: a line comment runs to the end of its line,
+/// and a block comment to the first `*/`, across lines; neither is looked for
+/// inside a string literal, and a `/` on its own is still division.
+#[test]
+fn test_spans_skip_comments() {
+    let source = String::from("int/* a */main // (\n/*\n * x */ (\"//\"/2/*/ */ /=/**/1);");
+    let tokens = tokenize(source.clone()).unwrap();
+    let spans: Vec<&str> = tokens
+        .iter()
+        .map(|t| &source[t.span.start..t.span.end])
+        .collect();
+    assert_eq!(
+        spans,
+        vec!["int", "main", "(", "\"//\"", "/", "2", "/=", "1", ")", ";"]
+    );
+    assert_eq!(tokens[1].span, Span { start: 10, end: 14 });
+}
+
+/// a block comment that is never closed is reported where it opens.
+#[test]
+fn test_unterminated_comment() {
+    let source = String::from("int main(void) {\n    /* return 0; */ return 1; /* }\n");
+    match tokenize(source) {
+        Err(e) => {
+            assert_eq!(e.span(), Span { start: 47, end: 49 });
+            assert_eq!(e.to_string(), "unterminated comment");
+            assert_eq!(e.code(), "unterminated-comment");
+        }
+        Ok(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn test_unrecognized_character() {
+    let source = String::from("int main(void) { return 2 @ 3; }");
+    match tokenize(source) {
+        Err(e) => {
+            assert_eq!(e.span(), Span { start: 26, end: 27 });
+            assert_eq!(e.to_string(), "unrecognized character '@'");
+        }
+        Ok(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn test_constant_too_large() {
+    let source = String::from("int main(void) { return 9223372036854775808l; }");
+    match tokenize(source) {
+        Err(e) => {
+            assert_eq!(e.span(), Span { start: 24, end: 44 });
+            assert_eq!(
+                e.to_string(),
+                "integer constant '9223372036854775808l' is too large for its type"
+            );
+        }
+        Ok(_) => unreachable!(),
+    }
+}
+
+/// an `l` or `L` suffix makes a long constant, and takes in no more than one letter.
+#[test]
+fn test_long_constants() {
+    let tokens: Vec<Token> = tokenize(String::from("long 2147483648 7l 9L"))
+        .unwrap()
+        .into_iter()
+        .map(|t| t.token)
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::TyKeyword { ty: Type::Long },
+            Token::Constant { val: 2147483648 },
+            Token::LongConstant { val: 7 },
+            Token::LongConstant { val: 9 },
+        ]
+    );
+    assert!(tokenize(String::from("1ll")).is_err());
+}
+
+/// a `u` or `U` suffix makes an unsigned constant, which takes in the whole
+/// 64-bit range, and goes with an `l` or `L` either side of it.
+#[test]
+fn test_unsigned_constants() {
+    let tokens: Vec<Token> = tokenize(String::from(
+        "unsigned signed 4294967295u 18446744073709551615UL 3lu 4Ul",
+    ))
+    .unwrap()
+    .into_iter()
+    .map(|t| t.token)
+    .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::TyKeyword { ty: Type::Unsigned },
+            Token::TyKeyword { ty: Type::Signed },
+            Token::UnsignedConstant { val: 4294967295 },
+            Token::UnsignedLongConstant {
+                val: 18446744073709551615
+            },
+            Token::UnsignedLongConstant { val: 3 },
+            Token::UnsignedLongConstant { val: 4 },
+        ]
+    );
+    assert!(tokenize(String::from("18446744073709551616u")).is_err());
+    assert!(tokenize(String::from("1uu")).is_err());
+    assert!(tokenize(String::from("1lul")).is_err());
+}
+
+/// a constant with a decimal point or an exponent is a double, which may leave
+/// out the digits on either side of the point but not both, and must not run
+/// into a letter or another point.
+#[test]
+fn test_double_constants() {
+    let tokens: Vec<Token> = tokenize(String::from("double 1.5 .25 3. 1e3 2.5E-1 1e+2;"))
+        .unwrap()
+        .into_iter()
+        .map(|t| t.token)
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::TyKeyword { ty: Type::Double },
+            Token::DoubleConstant { val: 1.5 },
+            Token::DoubleConstant { val: 0.25 },
+            Token::DoubleConstant { val: 3.0 },
+            Token::DoubleConstant { val: 1000.0 },
+            Token::DoubleConstant { val: 0.25 },
+            Token::DoubleConstant { val: 100.0 },
+            Token::Semicolon,
+        ]
+    );
+    assert!(tokenize(String::from("1.5x")).is_err());
+    assert!(tokenize(String::from("1.5.")).is_err());
+    assert!(tokenize(String::from("1e")).is_err());
+    assert!(tokenize(String::from("1.5.2")).is_err());
+}
+
+/// the longest operator is taken, so `<<=` is one token rather than `<<` and
+/// `=` or `<` and `<=`, and `<<<` a shift and a less.
+#[test]
+fn test_lex_shifts() {
+    let tokens: Vec<Token> = tokenize(String::from("a<<=b>>c<<<d>>=e"))
+        .unwrap()
+        .into_iter()
+        .map(|t| t.token)
+        .collect();
+    let name = |s| Token::Identifier {
+        val: Symbol::intern(s),
+    };
+    assert_eq!(
+        tokens,
+        vec![
+            name("a"),
+            Token::LessLessEqual,
+            name("b"),
+            Token::GreaterGreater,
+            name("c"),
+            Token::LessLess,
+            Token::Less,
+            name("d"),
+            Token::GreaterGreaterEqual,
+            name("e"),
+        ]
+    );
+}
+
+/// keywords are whole words only, so identifiers may start with one.
+#[test]
+fn test_statement_keywords() {
+    let tokens: Vec<Token> = tokenize(String::from(
+        "if else for break continue switch case default static extern struct _Bool iffy elsewhere format _Boolean",
+    ))
+    .unwrap()
+    .into_iter()
+    .map(|t| t.token)
+    .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::IfKeyword,
+            Token::ElseKeyword,
+            Token::ForKeyword,
+            Token::BreakKeyword,
+            Token::ContinueKeyword,
+            Token::SwitchKeyword,
+            Token::CaseKeyword,
+            Token::DefaultKeyword,
+            Token::StaticKeyword,
+            Token::ExternKeyword,
+            Token::StructKeyword,
+            Token::TyKeyword { ty: Type::Bool },
+            Token::Identifier {
+                val: Symbol::intern("iffy")
+            },
+            Token::Identifier {
+                val: Symbol::intern("elsewhere")
+            },
+            Token::Identifier {
+                val: Symbol::intern("format")
+            },
+            Token::Identifier {
+                val: Symbol::intern("_Boolean")
+            },
+        ]
+    );
+}
+
+/// tokens come one at a time, the error only once lexing reaches it, and then nothing.
+#[test]
+fn test_lexer_is_lazy() {
+    let mut lexer = Lexer::new("return 1 @ 2;".as_bytes());
+    assert_eq!(lexer.next().unwrap().unwrap().token, Token::RetKeyword);
+    assert_eq!(
+        lexer.next().unwrap().unwrap(),
+        SpannedToken {
+            token: Token::Constant { val: 1 },
+            span: Span { start: 7, end: 8 },
+        }
+    );
+    assert_eq!(
+        lexer.next().unwrap().unwrap_err().span(),
+        Span { start: 9, end: 10 }
+    );
+    assert!(lexer.next().is_none());
+}
+
+/// the source is read a line at a time as tokens are asked for, so the first
+/// tokens of a long source need little of it read.
+#[test]
+fn test_lexer_reads_as_it_goes() {
+    let source = "int x = 1 + 2;\n".repeat(100_000);
+    let mut unread = source.as_bytes();
+    let mut lexer = Lexer::new(&mut unread);
+    assert!(lexer.by_ref().take(5000).all(|t| t.is_ok()));
+    drop(lexer);
+    assert!(source.len() - unread.len() < 32 * 1024);
+}
+
+/// source that isn't UTF-8 ends the tokens where it goes bad, as an error
+/// reading it rather than one lexing it.
+#[test]
+fn test_read_error_ends_tokens() {
+    let mut lexer = Lexer::new(&b"int main\n(\xff)"[..]);
+    assert_eq!(lexer.by_ref().filter_map(Result::ok).count(), 2);
+    assert_eq!(
+        lexer.read_error().map(|e| e.kind()),
+        Some(io::ErrorKind::InvalidData)
+    );
+}
+
+#[cfg(test)]
+fn lex_spans(source: &str) -> Vec<(Token, Span)> {
+    tokenize(String::from(source))
+        .unwrap()
+        .into_iter()
+        .map(|t| (t.token, t.span))
+        .collect()
+}
+
+/// `\r\n` line endings lex as `\n` ones do.
+#[test]
+fn test_crlf_line_endings() {
+    let crlf = lex_spans("int main(void) {\r\n  return 2;\r\n}\r\n");
+    let tokens: Vec<Token> = crlf.iter().map(|(t, _)| t.clone()).collect();
+    let lf: Vec<Token> = lex_spans("int main(void) {\n  return 2;\n}\n")
+        .into_iter()
+        .map(|(t, _)| t)
+        .collect();
+    assert_eq!(tokens, lf);
+    assert_eq!(crlf[6], (Token::RetKeyword, Span { start: 20, end: 26 }));
+}
+
+/// a backslash-newline splices lines even inside a token, which then spans both
+/// physical lines, and tokens after it keep their physical offsets.
+#[test]
+fn test_line_splices() {
+    for (source, start, end) in [
+        ("int main(void) {\n  ret\\\nurn 2;\n}\n", 19, 27),
+        ("int main(void) {\r\n  ret\\\r\nurn 2;\r\n}\r\n", 20, 29),
+    ] {
+        let tokens = lex_spans(source);
+        assert_eq!(tokens[6], (Token::RetKeyword, Span { start, end }));
+        assert_eq!(
+            tokens[7],
+            (
+                Token::Constant { val: 2 },
+                Span {
+                    start: end + 1,
+                    end: end + 2
+                }
+            )
+        );
+    }
+    assert_eq!(
+        lex_spans("1\\\n+\\\n\\\n2"),
+        vec![
+            (Token::Constant { val: 1 }, Span { start: 0, end: 1 }),
+            (Token::Plus, Span { start: 3, end: 4 }),
+            (Token::Constant { val: 2 }, Span { start: 8, end: 9 }),
+        ]
+    );
+    // a backslash not before a newline is left for the lexer to reject
+    assert!(tokenize(String::from("return \\ 2;")).is_err());
+}
+
+/// escapes in string literals and character constants stand for the byte they
+/// name, an octal one taking up to three digits and a hex one all that follow.
+#[test]
+fn test_string_literals() {
+    assert_eq!(
+        lex_spans(r#""a\n\"\\" '\'' '\0' "\1012\x41z" '\377'"#),
+        vec![
+            (
+                Token::StringLiteral {
+                    val: b"a\n\"\\".to_vec()
+                },
+                Span { start: 0, end: 9 }
+            ),
+            (Token::Constant { val: 39 }, Span { start: 10, end: 14 }),
+            (Token::Constant { val: 0 }, Span { start: 15, end: 19 }),
+            (
+                Token::StringLiteral {
+                    val: b"A2Az".to_vec()
+                },
+                Span { start: 20, end: 32 }
+            ),
+            (Token::Constant { val: -1 }, Span { start: 33, end: 39 }),
+        ]
+    );
+    assert_eq!(escape(b"a\"\\\n\xff"), r#"a\"\\\012\377"#);
+}
+
+/// a literal can't run past the end of its line, and an escape must name a
+/// byte the way C spells one; one that names a number too large for a byte
+/// says so.
+#[test]
+fn test_bad_literals() {
+    for (source, message, span) in [
+        ("\"abc\nx\"", "missing terminating \" character", (0, 4)),
+        ("'a", "missing terminating ' character", (0, 2)),
+        ("\"\\q\"", "invalid escape sequence '\\q'", (1, 3)),
+        (
+            "'\\400'",
+            "escape sequence '\\400' is out of range for a byte",
+            (1, 5),
+        ),
+        (
+            "\"a\\x100\"",
+            "escape sequence '\\x100' is out of range for a byte",
+            (2, 7),
+        ),
+        ("\"\\x\"", "invalid escape sequence '\\x'", (1, 3)),
+        ("\"\\xg\"", "invalid escape sequence '\\x'", (1, 3)),
+        ("''", "empty character constant", (0, 2)),
+        (
+            "'ab'",
+            "character constant 'ab' holds more than one character",
+            (0, 4),
+        ),
+    ] {
+        let e = tokenize(String::from(source)).unwrap_err();
+        assert_eq!(e.to_string(), message, "{}", source);
+        assert_eq!(
+            e.span(),
+            Span {
+                start: span.0,
+                end: span.1
+            },
+            "{}",
+            source
+        );
+    }
+}
@@ -1,83 +1,653 @@
-use std::{fmt::Display, fs};
+use std::{fmt::Display, io::Read};
 use thiserror::Error;
 
+pub mod diagnostics;
+use diagnostics::{Diagnostic, LineIndex, Severity};
+
 pub mod lexer;
-use lexer::tokenize;
+use lexer::Lexer;
 
 pub mod parser;
 use parser::parse;
 
+pub mod pretty;
+
+pub mod unparse;
+
+pub mod semantic;
+
+pub mod symbol;
+
+pub mod context;
+
 pub mod tacky;
 
+pub mod interpret;
+
+pub mod optimize;
+use clap::ValueEnum;
+use optimize::{Pass, Pipeline};
+
 pub mod asmgen;
-use asmgen::{emit_asm, gen_asm};
+use asmgen::{gen_asm, CodegenOptions};
+
+pub mod encode;
+
+pub mod emit;
+use emit::{emit_asm, emit_object, AsmFormatter, Emit, Syntax};
 
-use crate::Args;
+pub mod target;
+use target::{RelocationModel, Target};
+
+pub mod validate;
+
+pub mod timings;
+use timings::Timings;
+
+pub mod warnings;
+use warnings::Warnings;
 
 #[derive(Error, Debug)]
 pub enum CompileError {
-    Lex { e: lexer::LexError },
-    Parse { e: parser::ParseError },
-    FileIo { e: std::io::Error },
+    Lex {
+        e: lexer::LexError,
+    },
+    Parse {
+        errors: Vec<parser::ParseError>,
+    },
+    Semantic {
+        errors: Vec<semantic::SemanticError>,
+    },
+    Codegen {
+        e: asmgen::CodegenError,
+    },
+    Internal {
+        e: encode::EncodeError,
+    },
+    WarningsAsErrors {
+        count: usize,
+    },
+    FileIo {
+        e: std::io::Error,
+    },
 }
 
 impl Display for CompileError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Lex { e } => write!(f, "{}", e),
-            Self::Parse { e } => write!(f, "{}", e),
+            Self::Lex { e } => write!(f, "(!) Lexer error: {}", e),
+            Self::Parse { errors } => match errors.len() {
+                1 => write!(f, "(!) Parse error: {}", errors[0]),
+                n => write!(f, "(!) {} parse errors, the first: {}", n, errors[0]),
+            },
+            Self::Semantic { errors } => match errors.len() {
+                1 => write!(f, "(!) Semantic error: {}", errors[0]),
+                n => write!(f, "(!) {} semantic errors, the first: {}", n, errors[0]),
+            },
+            Self::Codegen { e } => write!(f, "{}", e),
+            Self::Internal { e } => write!(f, "{}", e),
+            Self::WarningsAsErrors { count: 1 } => write!(f, "(!) 1 warning treated as an error"),
+            Self::WarningsAsErrors { count } => {
+                write!(f, "(!) {} warnings treated as errors", count)
+            }
             Self::FileIo { e } => write!(f, "{}", e),
         }
     }
 }
 
+impl CompileError {
+    /// the stable name of the error, for tools reading diagnostics; for a
+    /// failed parse or semantic analysis, that of the first error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Lex { e } => e.code(),
+            Self::Parse { errors } => errors.first().map_or("parse", |e| e.code()),
+            Self::Semantic { errors } => errors.first().map_or("semantic", |e| e.code()),
+            Self::Codegen { .. } => "codegen",
+            Self::Internal { .. } => "internal",
+            Self::WarningsAsErrors { .. } => "warnings-as-errors",
+            Self::FileIo { .. } => "io",
+        }
+    }
+
+    /// the error as diagnostics pointing into the source, one per error found,
+    /// or none if it has no place there.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            Self::Lex { e } => vec![Diagnostic::error(e.code(), e.to_string(), e.span())],
+            Self::Parse { errors } => errors
+                .iter()
+                .map(|e| Diagnostic {
+                    notes: e.note().into_iter().collect(),
+                    ..Diagnostic::error(e.code(), e.to_string(), e.span())
+                })
+                .collect(),
+            Self::Semantic { errors } => errors
+                .iter()
+                .map(|e| Diagnostic {
+                    notes: e.note().into_iter().collect(),
+                    ..Diagnostic::error(e.code(), e.to_string(), e.span())
+                })
+                .collect(),
+            Self::Codegen { .. }
+            | Self::Internal { .. }
+            | Self::WarningsAsErrors { .. }
+            | Self::FileIo { .. } => vec![],
+        }
+    }
+}
+
+/// How `--emit-ast` and `--emit-tacky` print the IR.
+/// - `Text`: the indented AST tree, or the TACKY listing
+/// - `Json`: the IR's serde serialization, which needs the `serde` feature
+#[derive(PartialEq, Debug, Clone, Copy, ValueEnum)]
+pub enum IrFormat {
+    Text,
+    Json,
+}
+
+/// Everything that decides how a program compiles: where to stop, which
+/// passes run, and the shape of the assembly. The driver's flags of the same
+/// names fill it in; `CompileOptions::default()` is what no flags give.
+#[derive(clap::Args, Debug, Clone, PartialEq)]
+pub struct CompileOptions {
+    #[clap(
+        long,
+        action,
+        help = "Directs compiler to run to lexer, but stop before parsing"
+    )]
+    pub lex: bool,
+    #[clap(
+        long,
+        short,
+        action,
+        help = "Directs compiler to run lexer and parser, but stop before assembly generation"
+    )]
+    pub parse: bool,
+    #[clap(
+        long,
+        action,
+        help = "Directs compiler to lex, parse and analyze the program, reporting every warning and error, and stop before TACKY generation without writing anything"
+    )]
+    pub check: bool,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        help = "Directs compiler to print the parsed AST as an indented tree, or as json, and stop before assembly generation"
+    )]
+    pub emit_ast: Option<IrFormat>,
+    #[clap(
+        long,
+        action,
+        help = "Directs compiler to print the parsed AST back out as fully parenthesized C, and stop before assembly generation"
+    )]
+    pub emit_c: bool,
+    #[clap(
+        long,
+        action,
+        help = "Directs compiler to perform lexing, parsing, and assembly generation, but stop before code emission"
+    )]
+    pub codegen: bool,
+    #[clap(
+        long,
+        short,
+        action,
+        help = "Directs compiler to perform lexing, parsing, and tacky, but stop before code emission"
+    )]
+    pub tacky: bool,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        help = "Directs compiler to print the TACKY program, after any optimization, as text or as json, and stop before assembly generation"
+    )]
+    pub emit_tacky: Option<IrFormat>,
+    #[clap(
+        short = 'O',
+        long,
+        value_name = "LEVEL",
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u8).range(0..=2),
+        help = "Optimization level, 1 for a bare -O: 1 runs the TACKY passes and keeps small leaf frames in the red zone, 2 adds tail calls and the peephole pass"
+    )]
+    pub optimize: u8,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "PASS",
+        help = "Runs a pass whatever the optimization level"
+    )]
+    pub enable_pass: Vec<Pass>,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "PASS",
+        help = "Skips a pass whatever the optimization level"
+    )]
+    pub disable_pass: Vec<Pass>,
+    #[clap(
+        long,
+        action,
+        help = "Prints the program to stderr after every optimization pass that runs, numbered in order"
+    )]
+    pub dump_after_all: bool,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "PASS",
+        help = "Prints the program to stderr after each run of the pass"
+    )]
+    pub dump_after: Vec<Pass>,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = Syntax::Att,
+        help = "Assembly syntax to emit"
+    )]
+    pub syntax: Syntax,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = Target::host(),
+        help = "Platform to emit assembly for"
+    )]
+    pub target: Target,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = Emit::Asm,
+        help = "Whether to hand assembly to the system assembler or write an object file directly"
+    )]
+    pub emit: Emit,
+    #[clap(
+        long,
+        action,
+        help = "Directs compiler to address the stack frame from %rsp and leave %rbp untouched, except for Windows, whose unwinder needs %rbp; also spelled -fomit-frame-pointer"
+    )]
+    pub omit_frame_pointer: bool,
+    #[clap(
+        long,
+        action,
+        help = "Directs compiler to comment each group of assembly with the TACKY instruction it came from"
+    )]
+    pub asm_comments: bool,
+    #[clap(
+        long,
+        action,
+        help = "Directs compiler to place each function in its own section, so the linker can remove unused ones; also spelled -ffunction-sections"
+    )]
+    pub function_sections: bool,
+    #[clap(
+        long,
+        action,
+        help = "Directs compiler to start each function with endbr64 and mark the output as CET compatible; also spelled -fcf-protection"
+    )]
+    pub cf_protection: bool,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "MODEL",
+        default_value_t = RelocationModel::Pie,
+        help = "Kind of module the output is linked into: a static executable, a position-independent executable, or a shared library, whose variables visible outside the file are reached through the GOT; also spelled -fno-pic, -fpie and -fpic"
+    )]
+    pub relocation_model: RelocationModel,
+    #[clap(
+        short = 'g',
+        action,
+        help = "Directs compiler to mark each statement's assembly with its source line, for debuggers; needs --emit asm"
+    )]
+    pub debug_info: bool,
+    #[clap(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Number of threads translating functions to assembly, when built with the parallel feature"
+    )]
+    pub jobs: u16,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions {
+            lex: false,
+            parse: false,
+            check: false,
+            emit_ast: None,
+            emit_c: false,
+            codegen: false,
+            tacky: false,
+            emit_tacky: None,
+            optimize: 0,
+            enable_pass: vec![],
+            disable_pass: vec![],
+            dump_after_all: false,
+            dump_after: vec![],
+            syntax: Syntax::Att,
+            target: Target::host(),
+            emit: Emit::Asm,
+            omit_frame_pointer: false,
+            asm_comments: false,
+            function_sections: false,
+            cf_protection: false,
+            relocation_model: RelocationModel::Pie,
+            debug_info: false,
+            jobs: 1,
+        }
+    }
+}
+
+/// compiles C source, already preprocessed, to assembly text, in memory.
+/// Comments may be left in; the lexer skips them.
+/// The flags that stop at an earlier stage, `emit`, and the dump flags are
+/// ignored; warnings are not checked for.
+///
+/// ```
+/// use crumb::{compile_to_assembly, CompileOptions};
+///
+/// let asm = compile_to_assembly(
+///     "int main(void) { return 2; }",
+///     &CompileOptions::default(),
+/// )
+/// .unwrap();
+/// assert!(asm.contains("main:"));
+/// assert!(asm.contains("movl $2, %eax"));
+/// ```
+pub fn compile_to_assembly(source: &str, options: &CompileOptions) -> Result<String, CompileError> {
+    let mut timings = Timings::default();
+    let c_ast = run_semantic(run_parser(source.as_bytes())?, &mut Warnings::default())?;
+    let mut pipeline = Pipeline::new(
+        options.optimize,
+        &options.enable_pass,
+        &options.disable_pass,
+    );
+    let lines = options
+        .debug_info
+        .then(|| LineIndex::new(source, "<stdin>"));
+    let tacky = run_tacky(c_ast, lines.as_ref(), &mut pipeline, &mut timings);
+    let asm_ast = run_codegen(tacky, options, &mut pipeline, &mut timings)?;
+    Ok(formatter(options).program(&asm_ast))
+}
+
 /// Compiling. IAFM.
+/// Runs each stage in turn, stopping early if `options` asks for it.
+/// Returns the file written, or `None` when a stage flag stopped compilation
+/// before anything was written.
 /// ### Parameters
-/// - input_file: path to file to compile
-/// - l: bool, stop after lexing
-/// - p: bool, stop after parsing
-/// - c: bool, stop after assembly code generation
-pub fn compile(input_file: String, args: Args) -> Result<String, CompileError> {
-    let source = match fs::read_to_string(format!("{}.i", input_file)) {
-        Ok(s) => s,
-        Err(e) => return Err(CompileError::FileIo { e }),
-    };
+/// - source: preprocessed C source
+/// - output_file: path to write the assembly or object file to
+/// - options: CompileOptions, where to stop and how to compile; its fields:
+///   - lex: bool, stop after lexing
+///   - parse: bool, stop after parsing
+///   - emit_ast: `Option<IrFormat>`, print the AST indented or as JSON and stop after parsing
+///   - emit_c: bool, print the AST back out as C and stop after parsing
+///   - tacky: bool, stop after TACKY generation
+///   - emit_tacky: `Option<IrFormat>`, print the TACKY program as text or JSON and stop after TACKY generation
+///   - codegen: bool, stop after assembly code generation
+///   - optimize: u8, optimization level choosing the passes, whether small leaf frames stay in the red zone,
+///     and whether a choice between two computed values is a conditional move
+///   - enable_pass: `Vec<Pass>`, passes to run whatever the level
+///   - disable_pass: `Vec<Pass>`, passes to skip whatever the level
+///   - dump_after_all: bool, print the program to stderr after every pass that runs
+///   - dump_after: `Vec<Pass>`, print the program to stderr after these passes
+///   - syntax: Syntax, dialect of the emitted assembly
+///   - target: Target, platform the emitted assembly is for
+///   - omit_frame_pointer: bool, address the stack from %rsp without saving %rbp
+///   - asm_comments: bool, annotate the assembly with the TACKY it came from
+///   - emit: Emit, whether to write assembly text or an object file
+///   - function_sections: bool, put each function in its own section
+///   - cf_protection: bool, mark functions as indirect branch targets for CET
+///   - relocation_model: RelocationModel, whether the output is for a static executable, a PIE or a shared library
+///   - debug_info: bool, give the assembly `.file` and `.loc` directives for each statement
+///   - jobs: u16, threads translating functions to assembly, with the parallel feature
+/// - timings: Timings, where the time each phase takes is added
+/// - warnings: Warnings, which warnings to give, and where to add those found
+pub fn compile(
+    source: impl Read,
+    output_file: String,
+    options: &CompileOptions,
+    timings: &mut Timings,
+    warnings: &mut Warnings,
+) -> Result<Option<String>, CompileError> {
+    if options.lex {
+        let mut lexer = Lexer::new(source);
+        for token in lexer.by_ref() {
+            let token = token.map_err(|e| CompileError::Lex { e })?;
+            println!("TOKEN!!! {}", token.token);
+        }
+        return match lexer.read_error() {
+            Some(e) => Err(CompileError::FileIo { e }),
+            None => Ok(None),
+        };
+    }
 
-    let tokens = match tokenize(source) {
-        Err(e) => return Err(CompileError::Lex { e }),
-        Ok(ts) => {
-            if args.lex {
-                ts.into_iter().for_each(|t| println!("TOKEN!!! {}", t));
-                return Ok(String::from("magic words"));
-            } else {
-                ts
-            }
+    // the line table for -g is built from the whole source, so only then is it
+    // kept; otherwise it is read as it is parsed
+    let (text, c_ast) = match options.debug_info {
+        true => {
+            let text = read_source(source, timings)?;
+            let c_ast = timings.time("parse", || run_parser(text.as_bytes()))?;
+            (Some(text), c_ast)
         }
+        false => (None, timings.time("parse", || run_parser(source))?),
     };
+    if options.parse {
+        println!("VALID AST RETURNED: {}", c_ast);
+        return Ok(None);
+    }
+    if let Some(format) = options.emit_ast {
+        match format {
+            IrFormat::Text => print!("{}", pretty::program(&c_ast)),
+            IrFormat::Json => print!("{}", to_json(&c_ast)?),
+        }
+        return Ok(None);
+    }
+    if options.emit_c {
+        print!("{}", unparse::program(&c_ast));
+        return Ok(None);
+    }
 
-    let c_ast = match parse(tokens) {
-        Err(e) => return Err(CompileError::Parse { e }),
-        Ok(ast) => {
-            if args.parse {
-                println!("VALID AST RETURNED: {}", ast);
-                return Ok(String::from("magic words"));
-            } else {
-                ast
-            }
+    let c_ast = timings.time("semantic", || run_semantic(c_ast, warnings))?;
+    if options.check {
+        return Ok(None);
+    }
+
+    let mut pipeline = pipeline(options);
+    // the linemarkers name the source files, so the name given here never shows
+    let lines = text.as_deref().map(|text| LineIndex::new(text, "<stdin>"));
+    let tacky = run_tacky(c_ast, lines.as_ref(), &mut pipeline, timings);
+    pipeline
+        .take_dumps()
+        .iter()
+        .for_each(|dump| eprint!("{}", dump));
+    if options.tacky {
+        return Ok(None);
+    }
+    if let Some(format) = options.emit_tacky {
+        match format {
+            IrFormat::Text => print!("{}", tacky),
+            IrFormat::Json => print!("{}", to_json(&tacky)?),
         }
-    };
-    let tacky = tacky::TackyEmitter::gen_tacky(c_ast);
-    if args.tacky {
-        return Ok(String::from("magic words"));
+        return Ok(None);
     }
-    let asm_ast = gen_asm(tacky);
-    if args.codegen {
-        println!("GENERATED ASSEMBLY: {}", asm_ast);
-        return Ok(String::from("magic words"));
+
+    let asm_ast = run_codegen(tacky, options, &mut pipeline, timings)?;
+    pipeline
+        .take_dumps()
+        .iter()
+        .for_each(|dump| eprint!("{}", dump));
+    if options.codegen {
+        println!(
+            "GENERATED ASSEMBLY: {}",
+            formatter(options).program(&asm_ast)
+        );
+        return Ok(None);
     }
-    if let Err(e) = emit_asm(asm_ast, format!("{}.s", input_file)) {
+
+    timings
+        .time("emit", || run_emission(asm_ast, output_file, options))
+        .map(Some)
+}
+
+/// reads preprocessed source to the end, for what needs it whole.
+pub fn read_source(mut source: impl Read, timings: &mut Timings) -> Result<String, CompileError> {
+    let mut text = String::new();
+    match timings.time("read", || source.read_to_string(&mut text)) {
+        Ok(_) => Ok(text),
+        Err(e) => Err(CompileError::FileIo { e }),
+    }
+}
+
+/// builds the C AST, reading and lexing the source as the parser asks for
+/// tokens. The lexer stops at its first error, which outranks any the parser
+/// found in the input cut short, as does failing to read the source.
+pub fn run_parser(source: impl Read) -> Result<parser::ProgramC, CompileError> {
+    let mut lex_error = None;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer
+        .by_ref()
+        .map_while(|t| t.map_err(|e| lex_error = Some(e)).ok());
+    let parsed = parse(tokens);
+    if let Some(e) = lexer.read_error() {
         return Err(CompileError::FileIo { e });
     }
+    if let Some(e) = lex_error {
+        return Err(CompileError::Lex { e });
+    }
+    parsed.map_err(|errors| CompileError::Parse { errors })
+}
+
+/// runs the semantic passes over the parsed program, renaming each variable to
+/// be unique and labeling loops, and reports every declaration or use that
+/// doesn't make sense. The warnings found go to `warnings`, errors or not;
+/// those `-Werror` made errors fail a program that has no others.
+pub fn run_semantic(
+    c_ast: parser::ProgramC,
+    warnings: &mut Warnings,
+) -> Result<parser::ProgramC, CompileError> {
+    let mut found = vec![];
+    let analyzed = semantic::analyze_with_warnings(c_ast, &warnings.config, &mut found);
+    let count = found
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    warnings.found.extend(found);
+    let c_ast = analyzed.map_err(|errors| CompileError::Semantic { errors })?;
+    match count {
+        0 => Ok(c_ast),
+        count => Err(CompileError::WarningsAsErrors { count }),
+    }
+}
+
+/// lowers the C AST to TACKY and runs the pipeline's TACKY passes. Given the
+/// source's `lines`, each statement is marked with the line it came from.
+pub fn run_tacky(
+    c_ast: parser::ProgramC,
+    lines: Option<&LineIndex>,
+    pipeline: &mut Pipeline,
+    timings: &mut Timings,
+) -> tacky::ProgramTacky {
+    let tacky = timings.time("tacky", || match lines {
+        Some(lines) => tacky::TackyEmitter::gen_tacky_with_lines(c_ast, lines),
+        None => tacky::TackyEmitter::gen_tacky(c_ast),
+    });
+    pipeline.optimize(tacky, timings)
+}
+
+/// selects instructions for the TACKY program and runs the pipeline's assembly passes.
+pub fn run_codegen(
+    tacky: tacky::ProgramTacky,
+    options: &CompileOptions,
+    pipeline: &mut Pipeline,
+    timings: &mut Timings,
+) -> Result<asmgen::ProgramAsm, CompileError> {
+    let codegen = CodegenOptions {
+        target: options.target,
+        omit_frame_pointer: options.omit_frame_pointer,
+        asm_comments: options.asm_comments,
+        red_zone: options.optimize >= 1,
+        cmov: options.optimize >= 1,
+        jobs: options.jobs.into(),
+        relocation: options.relocation_model,
+    };
+    let asm_ast = match timings.time("asmgen", || gen_asm(tacky, codegen)) {
+        Err(e) => return Err(CompileError::Codegen { e }),
+        Ok(asm) => asm,
+    };
+    Ok(pipeline.optimize_asm(asm_ast, timings))
+}
+
+/// writes the assembly program to `output_file`, as text or as an object file
+/// with `--emit obj`, and returns the path written.
+pub fn run_emission(
+    asm_ast: asmgen::ProgramAsm,
+    output_file: String,
+    options: &CompileOptions,
+) -> Result<String, CompileError> {
+    match options.emit {
+        Emit::Asm => emit_asm(asm_ast, output_file.clone(), formatter(options))
+            .map_err(|e| CompileError::FileIo { e })?,
+        Emit::Obj if options.debug_info => return Err(CompileError::FileIo {
+            e: std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "(!) line information (-g) can only be emitted as assembly, not with --emit obj",
+            ),
+        }),
+        Emit::Obj => emit_object(
+            asm_ast,
+            output_file.clone(),
+            options.target,
+            options.function_sections,
+            options.cf_protection,
+        )?,
+    }
+
+    Ok(output_file)
+}
+
+fn pipeline(options: &CompileOptions) -> Pipeline {
+    let pipeline = Pipeline::new(
+        options.optimize,
+        &options.enable_pass,
+        &options.disable_pass,
+    );
+    if options.dump_after_all {
+        pipeline.dump_after(Pass::value_variants())
+    } else {
+        pipeline.dump_after(&options.dump_after)
+    }
+}
+
+fn formatter(options: &CompileOptions) -> AsmFormatter {
+    AsmFormatter {
+        function_sections: options.function_sections,
+        cf_protection: options.cf_protection,
+        relocation: options.relocation_model,
+        ..AsmFormatter::new(options.syntax, options.target)
+    }
+}
+
+/// the IR as pretty-printed JSON, ending in a newline.
+#[cfg(feature = "serde")]
+fn to_json(ir: &impl serde::Serialize) -> Result<String, CompileError> {
+    match serde_json::to_string_pretty(ir) {
+        Ok(json) => Ok(json + "\n"),
+        Err(e) => Err(CompileError::FileIo { e: e.into() }),
+    }
+}
 
-    Ok(format!("{}.s", input_file))
+#[cfg(not(feature = "serde"))]
+fn to_json<T>(_ir: &T) -> Result<String, CompileError> {
+    Err(CompileError::FileIo {
+        e: std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "(!) json output needs crumb built with the serde feature",
+        ),
+    })
 }
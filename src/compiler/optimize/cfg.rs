@@ -0,0 +1,579 @@
+//! Control-flow graph over a function's TACKY instructions.
+//! Instructions are split into basic blocks at labels and after jumps and returns,
+//! with predecessor and successor edges between them. The dataflow passes run over
+//! this graph, and it also hosts the branch simplification and unreachable-code
+//! elimination passes.
+use std::collections::{HashMap, HashSet};
+
+use crate::compiler::tacky::{InstructionTacky, ValTacky};
+
+/// A node in the graph: the function's entry and exit are virtual nodes
+/// surrounding the basic blocks, which are numbered in program order.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum NodeId {
+    Entry,
+    Block { no: usize },
+    Exit,
+}
+
+/// A maximal run of instructions with a single entry point and a single exit point.
+#[derive(PartialEq, Debug, Clone)]
+pub struct BasicBlock {
+    pub instructions: Vec<InstructionTacky>,
+    pub preds: Vec<NodeId>,
+    pub succs: Vec<NodeId>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Cfg {
+    /// successors of the virtual entry node
+    pub entry_succs: Vec<NodeId>,
+    blocks: Vec<BasicBlock>,
+}
+
+impl Cfg {
+    /// Partitions instructions into basic blocks and connects them.
+    pub fn from_instructions(instrs: Vec<InstructionTacky>) -> Self {
+        let mut blocks = Vec::new();
+        let mut current = Vec::new();
+
+        for instr in instrs.into_iter() {
+            match instr {
+                InstructionTacky::Label { name: _ } => {
+                    if !current.is_empty() {
+                        blocks.push(std::mem::take(&mut current));
+                    }
+                    current.push(instr);
+                }
+                InstructionTacky::Jump { target: _ }
+                | InstructionTacky::JumpIfZero { .. }
+                | InstructionTacky::JumpIfNotZero { .. }
+                | InstructionTacky::Ret { v: _ }
+                | InstructionTacky::TailCall { .. } => {
+                    current.push(instr);
+                    blocks.push(std::mem::take(&mut current));
+                }
+                _ => current.push(instr),
+            }
+        }
+        if !current.is_empty() {
+            blocks.push(current);
+        }
+
+        let mut cfg = Cfg {
+            entry_succs: Vec::new(),
+            blocks: blocks
+                .into_iter()
+                .map(|instructions| BasicBlock {
+                    instructions,
+                    preds: Vec::new(),
+                    succs: Vec::new(),
+                })
+                .collect(),
+        };
+        cfg.connect();
+        cfg
+    }
+
+    /// Flattens the graph back into a list of instructions, in block order.
+    #[allow(clippy::wrong_self_convention)] // consumes the graph to avoid cloning every block
+    pub fn to_instructions(self) -> Vec<InstructionTacky> {
+        self.blocks
+            .into_iter()
+            .flat_map(|b| b.instructions.into_iter())
+            .collect()
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &BasicBlock> {
+        self.blocks.iter()
+    }
+
+    pub fn block(&self, no: usize) -> &BasicBlock {
+        &self.blocks[no]
+    }
+
+    pub fn block_mut(&mut self, no: usize) -> &mut BasicBlock {
+        &mut self.blocks[no]
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Removes blocks that cannot be reached from the entry, jumps to the block
+    /// that follows anyway, and labels nothing jumps to.
+    pub fn eliminate_unreachable_code(self) -> Self {
+        self.remove_unreachable_blocks()
+            .remove_redundant_jumps()
+            .remove_useless_labels()
+    }
+
+    /// Turns conditional jumps on constants into unconditional jumps or nothing, and
+    /// retargets jumps whose destination block does nothing but jump again.
+    /// Blocks left without predecessors are for `eliminate_unreachable_code` to remove.
+    pub fn simplify_branches(mut self) -> Self {
+        for block in self.blocks.iter_mut() {
+            let folded = match block.instructions.last() {
+                Some(InstructionTacky::JumpIfZero {
+                    condition: ValTacky::Const { c },
+                    target,
+                }) => Some((c.is_zero(), target.clone())),
+                Some(InstructionTacky::JumpIfNotZero {
+                    condition: ValTacky::Const { c },
+                    target,
+                }) => Some((!c.is_zero(), target.clone())),
+                _ => None,
+            };
+            if let Some((taken, target)) = folded {
+                block.instructions.pop();
+                if taken {
+                    block.instructions.push(InstructionTacky::Jump { target });
+                }
+            }
+        }
+
+        let forwards: HashMap<String, String> = self
+            .blocks
+            .iter()
+            .filter_map(|b| {
+                // a line marker does nothing, so it doesn't stop a block being a bare jump
+                let code: Vec<&InstructionTacky> = b
+                    .instructions
+                    .iter()
+                    .filter(|i| !matches!(i, InstructionTacky::Loc { .. }))
+                    .collect();
+                match code.as_slice() {
+                    [InstructionTacky::Label { name }, InstructionTacky::Jump { target }] => {
+                        Some((name.clone(), target.clone()))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        let thread = |target: &mut String| {
+            let mut seen = HashSet::new();
+            while let Some(next) = forwards.get(target.as_str()) {
+                // a cycle of bare jumps is an infinite loop; leave it be
+                if !seen.insert(target.clone()) {
+                    break;
+                }
+                *target = next.clone();
+            }
+        };
+        for block in self.blocks.iter_mut() {
+            if let Some(
+                InstructionTacky::Jump { target }
+                | InstructionTacky::JumpIfZero { target, .. }
+                | InstructionTacky::JumpIfNotZero { target, .. },
+            ) = block.instructions.last_mut()
+            {
+                thread(target);
+            }
+        }
+
+        Cfg::from_instructions(self.to_instructions())
+    }
+
+    fn remove_unreachable_blocks(self) -> Self {
+        let mut reached = HashSet::new();
+        let mut stack = self.entry_succs.clone();
+        while let Some(node) = stack.pop() {
+            if let NodeId::Block { no } = node {
+                if reached.insert(no) {
+                    stack.extend(self.blocks[no].succs.iter().copied());
+                }
+            }
+        }
+
+        // a block is only ever entered by falling through if its predecessor can fall
+        // through, which would make it reachable, so dropping blocks keeps semantics
+        Cfg::from_instructions(
+            self.blocks
+                .into_iter()
+                .enumerate()
+                .filter(|(no, _)| reached.contains(no))
+                .flat_map(|(_, b)| b.instructions.into_iter())
+                .collect(),
+        )
+    }
+
+    fn remove_redundant_jumps(mut self) -> Self {
+        let count = self.blocks.len();
+        for (no, block) in self.blocks.iter_mut().enumerate() {
+            if no + 1 == count {
+                break;
+            }
+            let is_jump = matches!(
+                block.instructions.last(),
+                Some(
+                    InstructionTacky::Jump { target: _ }
+                        | InstructionTacky::JumpIfZero { .. }
+                        | InstructionTacky::JumpIfNotZero { .. }
+                )
+            );
+            if is_jump && block.succs == [NodeId::Block { no: no + 1 }] {
+                block.instructions.pop();
+            }
+        }
+        Cfg::from_instructions(self.to_instructions())
+    }
+
+    fn remove_useless_labels(self) -> Self {
+        let targets: HashSet<String> = self
+            .blocks
+            .iter()
+            .filter_map(|b| match b.instructions.last() {
+                Some(
+                    InstructionTacky::Jump { target }
+                    | InstructionTacky::JumpIfZero { target, .. }
+                    | InstructionTacky::JumpIfNotZero { target, .. },
+                ) => Some(target.clone()),
+                _ => None,
+            })
+            .collect();
+
+        Cfg::from_instructions(
+            self.to_instructions()
+                .into_iter()
+                .filter(|i| match i {
+                    InstructionTacky::Label { name } => targets.contains(name),
+                    _ => true,
+                })
+                .collect(),
+        )
+    }
+
+    /// Computes the edges between blocks from their final instructions.
+    fn connect(&mut self) {
+        let count = self.blocks.len();
+        self.entry_succs = vec![if count == 0 {
+            NodeId::Exit
+        } else {
+            NodeId::Block { no: 0 }
+        }];
+
+        for no in 0..count {
+            let next = if no + 1 < count {
+                NodeId::Block { no: no + 1 }
+            } else {
+                NodeId::Exit
+            };
+            let succs = match self.blocks[no].instructions.last() {
+                Some(InstructionTacky::Ret { v: _ } | InstructionTacky::TailCall { .. }) => {
+                    vec![NodeId::Exit]
+                }
+                Some(InstructionTacky::Jump { target }) => vec![self.label_node(target)],
+                Some(
+                    InstructionTacky::JumpIfZero { target, .. }
+                    | InstructionTacky::JumpIfNotZero { target, .. },
+                ) => {
+                    let taken = self.label_node(target);
+                    if taken == next {
+                        vec![next]
+                    } else {
+                        vec![taken, next]
+                    }
+                }
+                _ => vec![next],
+            };
+            self.blocks[no].succs = succs;
+        }
+
+        for block in self.blocks.iter_mut() {
+            block.preds.clear();
+        }
+        for succ in self.entry_succs.clone() {
+            if let NodeId::Block { no } = succ {
+                self.blocks[no].preds.push(NodeId::Entry);
+            }
+        }
+        for no in 0..count {
+            for succ in self.blocks[no].succs.clone() {
+                if let NodeId::Block { no: s } = succ {
+                    self.blocks[s].preds.push(NodeId::Block { no });
+                }
+            }
+        }
+    }
+
+    fn label_node(&self, target: &String) -> NodeId {
+        let no = self
+            .blocks
+            .iter()
+            .position(|b| {
+                matches!(b.instructions.first(), Some(InstructionTacky::Label { name }) if name == target)
+            })
+            .unwrap_or_else(|| panic!("jump to undefined label {}", target));
+        NodeId::Block { no }
+    }
+}
+
+#[cfg(test)]
+fn label(name: &str) -> InstructionTacky {
+    InstructionTacky::Label {
+        name: String::from(name),
+    }
+}
+
+/// ### TACKY (input):
+/// ```text
+/// JumpIfZero(Var("tmp.0"), "else")
+/// Return(Constant(1))
+/// Label("else")
+/// Return(Constant(2))
+/// ```
+#[test]
+fn build_blocks_and_edges() {
+    let instrs = vec![
+        InstructionTacky::JumpIfZero {
+            condition: ValTacky::tmp(0),
+            target: String::from("else"),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::int(1),
+        },
+        label("else"),
+        InstructionTacky::Ret {
+            v: ValTacky::int(2),
+        },
+    ];
+    let cfg = Cfg::from_instructions(instrs.clone());
+
+    assert_eq!(cfg.len(), 3);
+    assert_eq!(cfg.entry_succs, vec![NodeId::Block { no: 0 }]);
+    assert_eq!(
+        cfg.block(0).succs,
+        vec![NodeId::Block { no: 2 }, NodeId::Block { no: 1 }]
+    );
+    assert_eq!(cfg.block(1).succs, vec![NodeId::Exit]);
+    assert_eq!(cfg.block(2).preds, vec![NodeId::Block { no: 0 }]);
+    assert_eq!(cfg.block(2).succs, vec![NodeId::Exit]);
+    assert_eq!(cfg.to_instructions(), instrs);
+}
+
+#[test]
+fn empty_function_connects_entry_to_exit() {
+    let cfg = Cfg::from_instructions(vec![]);
+    assert_eq!(cfg.len(), 0);
+    assert_eq!(cfg.entry_succs, vec![NodeId::Exit]);
+}
+
+/// everything after the first return is unreachable, including a labelled
+/// block that is only jumped to from other unreachable code.
+#[test]
+fn remove_code_after_return() {
+    let instrs = vec![
+        InstructionTacky::Ret {
+            v: ValTacky::int(1),
+        },
+        InstructionTacky::Unary {
+            op: crate::compiler::parser::UnaryOp::Negate,
+            src: ValTacky::int(1),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Jump {
+            target: String::from("end"),
+        },
+        label("end"),
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(0),
+        },
+    ];
+    assert_eq!(
+        Cfg::from_instructions(instrs)
+            .eliminate_unreachable_code()
+            .to_instructions(),
+        vec![InstructionTacky::Ret {
+            v: ValTacky::int(1),
+        }]
+    );
+}
+
+/// a jump to the very next block goes away, and so does the label it used.
+#[test]
+fn remove_jump_to_next_block() {
+    let instrs = vec![
+        InstructionTacky::Copy {
+            src: ValTacky::int(1),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::JumpIfNotZero {
+            condition: ValTacky::tmp(0),
+            target: String::from("next"),
+        },
+        label("next"),
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(0),
+        },
+    ];
+    let cfg = Cfg::from_instructions(instrs).eliminate_unreachable_code();
+    assert_eq!(cfg.len(), 1);
+    assert_eq!(
+        cfg.to_instructions(),
+        vec![
+            InstructionTacky::Copy {
+                src: ValTacky::int(1),
+                dst: ValTacky::tmp(0),
+            },
+            InstructionTacky::Ret {
+                v: ValTacky::tmp(0),
+            },
+        ]
+    );
+}
+
+/// both arms of a branch stay reachable and keep their labels.
+#[test]
+fn keep_reachable_branches() {
+    let instrs = vec![
+        InstructionTacky::JumpIfZero {
+            condition: ValTacky::tmp(0),
+            target: String::from("else"),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::int(1),
+        },
+        label("else"),
+        InstructionTacky::Ret {
+            v: ValTacky::int(2),
+        },
+    ];
+    assert_eq!(
+        Cfg::from_instructions(instrs.clone())
+            .eliminate_unreachable_code()
+            .to_instructions(),
+        instrs
+    );
+}
+
+/// ### TACKY (input):
+/// ```text
+/// JumpIfZero(Constant(0), "else")
+/// Return(Constant(1))
+/// Label("else")
+/// Return(Constant(2))
+/// ```
+/// the branch is always taken, so only the `else` arm survives.
+#[test]
+fn fold_jump_on_zero_constant() {
+    let instrs = vec![
+        InstructionTacky::JumpIfZero {
+            condition: ValTacky::int(0),
+            target: String::from("else"),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::int(1),
+        },
+        label("else"),
+        InstructionTacky::Ret {
+            v: ValTacky::int(2),
+        },
+    ];
+    assert_eq!(
+        Cfg::from_instructions(instrs)
+            .simplify_branches()
+            .eliminate_unreachable_code()
+            .to_instructions(),
+        vec![InstructionTacky::Ret {
+            v: ValTacky::int(2),
+        }]
+    );
+}
+
+/// a conditional jump that is never taken disappears, and its target with it
+/// once nothing else jumps there.
+#[test]
+fn fold_jump_never_taken() {
+    let instrs = vec![
+        InstructionTacky::JumpIfNotZero {
+            condition: ValTacky::int(0),
+            target: String::from("skip"),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::int(1),
+        },
+        label("skip"),
+        InstructionTacky::Ret {
+            v: ValTacky::int(2),
+        },
+    ];
+    assert_eq!(
+        Cfg::from_instructions(instrs)
+            .simplify_branches()
+            .eliminate_unreachable_code()
+            .to_instructions(),
+        vec![InstructionTacky::Ret {
+            v: ValTacky::int(1),
+        }]
+    );
+}
+
+/// a jump to a block that only jumps on goes straight to the final target.
+#[test]
+fn thread_jump_chains() {
+    let instrs = vec![
+        InstructionTacky::JumpIfZero {
+            condition: ValTacky::tmp(0),
+            target: String::from("a"),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::int(1),
+        },
+        label("a"),
+        InstructionTacky::Jump {
+            target: String::from("b"),
+        },
+        label("b"),
+        InstructionTacky::Jump {
+            target: String::from("c"),
+        },
+        label("c"),
+        InstructionTacky::Ret {
+            v: ValTacky::int(2),
+        },
+    ];
+    let cfg = Cfg::from_instructions(instrs).simplify_branches();
+    assert_eq!(
+        cfg.block(0).instructions,
+        vec![InstructionTacky::JumpIfZero {
+            condition: ValTacky::tmp(0),
+            target: String::from("c"),
+        }]
+    );
+    assert_eq!(
+        cfg.eliminate_unreachable_code().to_instructions(),
+        vec![
+            InstructionTacky::JumpIfZero {
+                condition: ValTacky::tmp(0),
+                target: String::from("c"),
+            },
+            InstructionTacky::Ret {
+                v: ValTacky::int(1),
+            },
+            label("c"),
+            InstructionTacky::Ret {
+                v: ValTacky::int(2),
+            },
+        ]
+    );
+}
+
+/// threading stops at a cycle of bare jumps instead of looping forever.
+#[test]
+fn thread_jump_cycle_terminates() {
+    let instrs = vec![
+        label("a"),
+        InstructionTacky::Jump {
+            target: String::from("b"),
+        },
+        label("b"),
+        InstructionTacky::Jump {
+            target: String::from("a"),
+        },
+    ];
+    assert_eq!(Cfg::from_instructions(instrs).simplify_branches().len(), 2);
+}
@@ -0,0 +1,411 @@
+//! Constant folding.
+//! Evaluates `Unary`, `Binary` and conversion instructions whose operands are
+//! all constants at compile time, replacing them with a `Copy` of the result.
+//! Arithmetic wraps exactly like the 32- or 64-bit instructions it replaces,
+//! signed or unsigned, and a `double`'s is IEEE 754's, rounding to nearest.
+use crate::compiler::{
+    parser::{BinaryOp, CType, Const, UnaryOp},
+    tacky::{InstructionTacky, ValTacky},
+};
+
+pub fn fold_constants(instrs: Vec<InstructionTacky>) -> Vec<InstructionTacky> {
+    instrs.into_iter().map(fold_instruction).collect()
+}
+
+fn fold_instruction(instr: InstructionTacky) -> InstructionTacky {
+    let folded = match &instr {
+        InstructionTacky::Unary {
+            op,
+            src: ValTacky::Const { c },
+            dst,
+        } => Some((fold_unary(op, *c), dst)),
+        InstructionTacky::Binary {
+            op,
+            src1: ValTacky::Const { c: a },
+            src2: ValTacky::Const { c: b },
+            dst,
+            // a comparison gives an `int`, unless it is a conversion to `_Bool`
+        } => fold_binary(op, *a, *b).map(|c| (c.convert(dst.ty()), dst)),
+        InstructionTacky::SignExtend {
+            src: ValTacky::Const { c },
+            dst,
+        }
+        | InstructionTacky::Truncate {
+            src: ValTacky::Const { c },
+            dst,
+        }
+        | InstructionTacky::ZeroExtend {
+            src: ValTacky::Const { c },
+            dst,
+        }
+        | InstructionTacky::DoubleToInt {
+            src: ValTacky::Const { c },
+            dst,
+        }
+        | InstructionTacky::IntToDouble {
+            src: ValTacky::Const { c },
+            dst,
+        } => Some((c.convert(dst.ty()), dst)),
+        // between a signed and an unsigned type, which keeps the bits
+        InstructionTacky::Copy {
+            src: ValTacky::Const { c },
+            dst,
+        } if c.ty() != dst.ty() => Some((c.convert(dst.ty()), dst)),
+        _ => None,
+    };
+
+    match folded {
+        Some((c, dst)) => InstructionTacky::Copy {
+            src: ValTacky::Const { c },
+            dst: dst.clone(),
+        },
+        None => instr,
+    }
+}
+
+pub fn fold_unary(op: &UnaryOp, a: Const) -> Const {
+    if let Const::Double { double } = a {
+        return match op {
+            UnaryOp::Negate => Const::Double { double: -double },
+            UnaryOp::BitwiseComplement => unreachable!("'~' on a double doesn't type check"),
+        };
+    }
+    let value = match op {
+        UnaryOp::Negate => -a.wide(),
+        UnaryOp::BitwiseComplement => !a.wide(),
+    };
+    Const::wrap(value, a.ty())
+}
+
+/// Works out the result on the operands' values converted to their common
+/// type, exactly, then keeps as many bits as that type has, which is what
+/// wrapping in it would leave. Comparisons follow the type's own ordering.
+/// Returns `None` for operations that trap at runtime (division by zero, and
+/// a quotient that doesn't fit, the most negative value divided by -1, both
+/// raise #DE from `idiv`), so the trap is preserved. `double`s are worked out
+/// by `fold_double`, and shifts by `fold_shift`.
+pub fn fold_binary(op: &BinaryOp, a: Const, b: Const) -> Option<Const> {
+    if matches!(op, BinaryOp::LeftShift | BinaryOp::RightShift) {
+        return fold_shift(op, a, b);
+    }
+    let ty = a.ty().common(b.ty());
+    if let (Const::Double { double: x }, Const::Double { double: y }) =
+        (a.convert(ty), b.convert(ty))
+    {
+        return fold_double(op, x, y);
+    }
+    let (x, y) = (a.convert(ty).wide(), b.convert(ty).wide());
+    let wrapped = |value: i128| Const::wrap(value, ty);
+    let truth = |holds: bool| Const::Int { int: holds as i32 };
+    match op {
+        BinaryOp::Divide | BinaryOp::Remainder if y == 0 || wrapped(x / y).wide() != x / y => None,
+        BinaryOp::Add => Some(wrapped(x + y)),
+        BinaryOp::Subtract => Some(wrapped(x - y)),
+        BinaryOp::Multiply => Some(wrapped(x.wrapping_mul(y))),
+        BinaryOp::Divide => Some(wrapped(x / y)),
+        BinaryOp::Remainder => Some(wrapped(x % y)),
+        BinaryOp::BitwiseAnd => Some(wrapped(x & y)),
+        BinaryOp::BitwiseOr => Some(wrapped(x | y)),
+        BinaryOp::BitwiseXor => Some(wrapped(x ^ y)),
+        BinaryOp::LeftShift | BinaryOp::RightShift => unreachable!("shifts are folded apart"),
+        BinaryOp::LessThan => Some(truth(x < y)),
+        BinaryOp::GreaterThan => Some(truth(x > y)),
+        BinaryOp::LessOrEqual => Some(truth(x <= y)),
+        BinaryOp::GreaterOrEqual => Some(truth(x >= y)),
+        BinaryOp::Equal => Some(truth(x == y)),
+        BinaryOp::NotEqual => Some(truth(x != y)),
+        BinaryOp::And => Some(truth(x != 0 && y != 0)),
+        BinaryOp::Or => Some(truth(x != 0 || y != 0)),
+    }
+}
+
+/// `double` arithmetic, which never traps: dividing by zero gives an infinity
+/// or a NaN. Returns `None` for the operators that only take integers.
+fn fold_double(op: &BinaryOp, x: f64, y: f64) -> Option<Const> {
+    let double = |double: f64| Const::Double { double };
+    let truth = |holds: bool| Const::Int { int: holds as i32 };
+    match op {
+        BinaryOp::Add => Some(double(x + y)),
+        BinaryOp::Subtract => Some(double(x - y)),
+        BinaryOp::Multiply => Some(double(x * y)),
+        BinaryOp::Divide => Some(double(x / y)),
+        BinaryOp::Remainder
+        | BinaryOp::BitwiseAnd
+        | BinaryOp::BitwiseOr
+        | BinaryOp::BitwiseXor
+        | BinaryOp::LeftShift
+        | BinaryOp::RightShift => None,
+        BinaryOp::LessThan => Some(truth(x < y)),
+        BinaryOp::GreaterThan => Some(truth(x > y)),
+        BinaryOp::LessOrEqual => Some(truth(x <= y)),
+        BinaryOp::GreaterOrEqual => Some(truth(x >= y)),
+        BinaryOp::Equal => Some(truth(x == y)),
+        BinaryOp::NotEqual => Some(truth(x != y)),
+        BinaryOp::And => Some(truth(x != 0.0 && y != 0.0)),
+        BinaryOp::Or => Some(truth(x != 0.0 || y != 0.0)),
+    }
+}
+
+/// `a << b` or `a >> b`, in the type of `a` once promoted rather than a common
+/// type. Only as many low bits of the count are used as `shl`, `sar` and `shr`
+/// use, five or six, so a count too large for the type folds to what the
+/// instruction would give; `>>` fills with the sign bit only if `a` is signed.
+/// Returns `None` for a `double` operand, which can't be shifted.
+fn fold_shift(op: &BinaryOp, a: Const, b: Const) -> Option<Const> {
+    let ty = a.ty().promoted();
+    if ty == CType::Double || b.ty() == CType::Double {
+        return None;
+    }
+    let x = a.convert(ty).wide();
+    let count = (b.wide() & (8 * ty.size() as i128 - 1)) as u32;
+    let value = match op {
+        BinaryOp::LeftShift => x.wrapping_shl(count),
+        _ => x >> count,
+    };
+    Some(Const::wrap(value, ty))
+}
+
+#[test]
+fn fold_nested_arithmetic() {
+    // (3 + 4) * 2 - 6 / 3
+    let instrs = vec![
+        InstructionTacky::Binary {
+            op: BinaryOp::Add,
+            src1: ValTacky::int(3),
+            src2: ValTacky::int(4),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Binary {
+            op: BinaryOp::Divide,
+            src1: ValTacky::int(6),
+            src2: ValTacky::int(3),
+            dst: ValTacky::tmp(1),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(1),
+        },
+    ];
+    assert_eq!(
+        fold_constants(instrs),
+        vec![
+            InstructionTacky::Copy {
+                src: ValTacky::int(7),
+                dst: ValTacky::tmp(0),
+            },
+            InstructionTacky::Copy {
+                src: ValTacky::int(2),
+                dst: ValTacky::tmp(1),
+            },
+            InstructionTacky::Ret {
+                v: ValTacky::tmp(1),
+            },
+        ]
+    );
+}
+
+#[cfg(test)]
+fn int(int: i32) -> Const {
+    Const::Int { int }
+}
+
+#[cfg(test)]
+fn long(long: i64) -> Const {
+    Const::Long { long }
+}
+
+#[cfg(test)]
+fn uint(uint: u32) -> Const {
+    Const::UInt { uint }
+}
+
+#[cfg(test)]
+fn ulong(ulong: u64) -> Const {
+    Const::ULong { ulong }
+}
+
+#[test]
+fn fold_wraps_on_overflow() {
+    let add = |a, b| fold_binary(&BinaryOp::Add, a, b);
+    assert_eq!(add(int(i32::MAX), int(1)), Some(int(i32::MIN)));
+    assert_eq!(add(long(i32::MAX as i64), long(1)), Some(long(1 << 31)));
+    assert_eq!(add(long(i64::MAX), long(1)), Some(long(i64::MIN)));
+    let product = fold_binary(&BinaryOp::Multiply, int(65536), int(65536));
+    assert_eq!(product, Some(int(0)));
+    assert_eq!(fold_unary(&UnaryOp::Negate, int(i32::MIN)), int(i32::MIN));
+    assert_eq!(
+        fold_unary(&UnaryOp::Negate, long(i32::MIN as i64)),
+        long(1 << 31)
+    );
+}
+
+/// unsigned arithmetic wraps modulo 2^32 or 2^64, and mixing an `int` with an
+/// `unsigned int` converts it to unsigned first.
+#[test]
+fn fold_unsigned_wraps() {
+    let add = |a, b| fold_binary(&BinaryOp::Add, a, b);
+    assert_eq!(add(uint(u32::MAX), uint(1)), Some(uint(0)));
+    assert_eq!(add(ulong(u64::MAX), ulong(2)), Some(ulong(1)));
+    assert_eq!(add(int(-1), uint(0)), Some(uint(u32::MAX)));
+    assert_eq!(fold_unary(&UnaryOp::Negate, uint(1)), uint(u32::MAX));
+    let product = fold_binary(&BinaryOp::Multiply, ulong(u64::MAX), ulong(u64::MAX));
+    assert_eq!(product, Some(ulong(1)));
+    let quotient = fold_binary(&BinaryOp::Divide, uint(u32::MAX), uint(2));
+    assert_eq!(quotient, Some(uint(i32::MAX as u32)));
+    // -1 converts to the largest unsigned int rather than comparing as negative
+    let less = fold_binary(&BinaryOp::LessThan, int(-1), uint(1));
+    assert_eq!(less, Some(int(0)));
+    // a long can hold every unsigned int, so the sum is a long
+    let sum = add(uint(u32::MAX), long(1));
+    assert_eq!(sum, Some(long(1 << 32)));
+}
+
+#[test]
+fn fold_truncates_division_toward_zero() {
+    assert_eq!(
+        fold_binary(&BinaryOp::Divide, int(-7), int(2)),
+        Some(int(-3))
+    );
+    assert_eq!(
+        fold_binary(&BinaryOp::Remainder, long(-7), long(2)),
+        Some(long(-1))
+    );
+}
+
+/// a shift keeps the type of its left operand, fills from the sign bit only
+/// if that is signed, and masks its count as `shl` and `sar` do.
+#[test]
+fn fold_shifts() {
+    let left = |a, b| fold_binary(&BinaryOp::LeftShift, a, b);
+    let right = |a, b| fold_binary(&BinaryOp::RightShift, a, b);
+    assert_eq!(left(int(1), long(31)), Some(int(i32::MIN)));
+    assert_eq!(left(long(1), int(40)), Some(long(1 << 40)));
+    assert_eq!(left(ulong(u64::MAX), int(63)), Some(ulong(1 << 63)));
+    assert_eq!(right(int(-17), int(2)), Some(int(-5)));
+    assert_eq!(right(uint(u32::MAX), int(28)), Some(uint(15)));
+    assert_eq!(right(long(-1), long(63)), Some(long(-1)));
+    assert_eq!(left(int(3), int(33)), Some(int(6)));
+    assert_eq!(left(long(3), int(65)), Some(long(6)));
+    assert_eq!(right(double(1.0), int(1)), None);
+}
+
+#[cfg(test)]
+fn double(double: f64) -> Const {
+    Const::Double { double }
+}
+
+/// an integer meeting a `double` converts to it, and dividing by zero doesn't
+/// trap, unlike in an integer type.
+#[test]
+fn fold_doubles() {
+    let sum = fold_binary(&BinaryOp::Add, int(1), double(0.5));
+    assert_eq!(sum, Some(double(1.5)));
+    let quotient = fold_binary(&BinaryOp::Divide, double(1.0), double(0.0));
+    assert_eq!(quotient, Some(double(f64::INFINITY)));
+    let less = fold_binary(&BinaryOp::LessThan, double(-0.5), ulong(0));
+    assert_eq!(less, Some(int(1)));
+    assert_eq!(fold_unary(&UnaryOp::Negate, double(0.0)), double(-0.0));
+    assert_ne!(double(0.0), double(-0.0));
+    assert_eq!(
+        double(-2.75).convert(crate::compiler::parser::CType::Int),
+        int(-2)
+    );
+}
+
+/// a comparison is an `int` whatever its operands' type.
+#[test]
+fn fold_long_comparison() {
+    let less = fold_binary(&BinaryOp::LessThan, long(-1), long(1 << 40));
+    assert_eq!(less, Some(int(1)));
+}
+
+/// a conversion of a constant is the constant in its new type: truncation
+/// keeps the low 32 bits, and sign extension the value.
+#[test]
+fn fold_conversions() {
+    let instrs = vec![
+        InstructionTacky::Truncate {
+            src: ValTacky::Const {
+                c: long((1 << 32) + 5),
+            },
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::SignExtend {
+            src: ValTacky::int(-3),
+            dst: ValTacky::TmpVar {
+                name: crate::compiler::symbol::Symbol::intern("x"),
+                ty: crate::compiler::parser::CType::Long,
+            },
+        },
+    ];
+    let folded: Vec<ValTacky> = fold_constants(instrs)
+        .into_iter()
+        .map(|instr| match instr {
+            InstructionTacky::Copy { src, .. } => src,
+            instr => panic!("{} was not folded", instr),
+        })
+        .collect();
+    assert_eq!(
+        folded,
+        vec![ValTacky::int(5), ValTacky::Const { c: long(-3) }]
+    );
+}
+
+/// the comparison a conversion to `_Bool` is made of folds to a `_Bool`, not
+/// the `int` other comparisons give, and a `double` is 1 if it isn't zero.
+#[test]
+fn fold_bool_conversion() {
+    let instr = InstructionTacky::Binary {
+        op: BinaryOp::NotEqual,
+        src1: ValTacky::Const { c: double(0.25) },
+        src2: ValTacky::Const { c: double(0.0) },
+        dst: ValTacky::TmpVar {
+            name: crate::compiler::symbol::Symbol::intern("b"),
+            ty: crate::compiler::parser::CType::Bool,
+        },
+    };
+    match &fold_constants(vec![instr])[..] {
+        [InstructionTacky::Copy {
+            src: ValTacky::Const { c },
+            ..
+        }] => assert_eq!(*c, Const::Bool { bool: true }),
+        instrs => panic!("{:?} was not folded", instrs),
+    }
+    assert_eq!(
+        double(0.5).convert(crate::compiler::parser::CType::Bool),
+        Const::Bool { bool: true }
+    );
+    assert_eq!(
+        long(1 << 40).convert(crate::compiler::parser::CType::Bool),
+        Const::Bool { bool: true }
+    );
+}
+
+#[test]
+fn no_fold_trapping_division() {
+    let div_zero = InstructionTacky::Binary {
+        op: BinaryOp::Divide,
+        src1: ValTacky::int(1),
+        src2: ValTacky::int(0),
+        dst: ValTacky::tmp(0),
+    };
+    assert_eq!(fold_constants(vec![div_zero.clone()]), vec![div_zero]);
+    assert_eq!(fold_binary(&BinaryOp::Remainder, int(1), int(0)), None);
+    assert_eq!(fold_binary(&BinaryOp::Divide, int(i32::MIN), int(-1)), None);
+    assert_eq!(
+        fold_binary(&BinaryOp::Divide, long(i64::MIN), long(-1)),
+        None
+    );
+    let wide = fold_binary(&BinaryOp::Divide, long(i32::MIN as i64), long(-1));
+    assert_eq!(wide, Some(long(1 << 31)));
+}
+
+/// a variable operand blocks folding.
+#[test]
+fn no_fold_non_constant() {
+    let instr = InstructionTacky::Unary {
+        op: UnaryOp::Negate,
+        src: ValTacky::tmp(0),
+        dst: ValTacky::tmp(1),
+    };
+    assert_eq!(fold_constants(vec![instr.clone()]), vec![instr]);
+}
@@ -0,0 +1,474 @@
+//! Copy propagation.
+//! Tracks which `Copy` instructions reach each point of a function and rewrites
+//! reads of a copy's destination into reads of its source, as long as neither
+//! side has been redefined in between, a call or a store through a pointer
+//! counting as redefining every aliased variable. A copy between a signed and
+//! an unsigned type reinterprets its value, so it is never propagated. Copies
+//! left without any readers are then dropped, unless they are into an aliased
+//! variable, which may yet be read through a pointer or after the function.
+use std::collections::{HashMap, HashSet};
+
+use crate::compiler::{
+    symbol::Symbol,
+    tacky::{InstructionTacky, ValTacky},
+};
+
+use super::{
+    cfg::{Cfg, NodeId},
+    is_aliased,
+};
+
+/// The copies `dst = src` known to hold at a program point. Each destination
+/// has at most one, since a copy into it kills any other; they are indexed by
+/// source too, so overwriting a variable kills the copies from it without
+/// looking at the rest.
+#[derive(Debug, Clone, Default)]
+struct ReachingCopies {
+    by_dst: HashMap<ValTacky, ValTacky>,
+    by_src: HashMap<ValTacky, HashSet<ValTacky>>,
+}
+
+impl PartialEq for ReachingCopies {
+    fn eq(&self, other: &Self) -> bool {
+        self.by_dst == other.by_dst
+    }
+}
+
+impl ReachingCopies {
+    /// the source of the copy into `dst`, if one reaches.
+    fn src_of(&self, dst: &ValTacky) -> Option<&ValTacky> {
+        self.by_dst.get(dst)
+    }
+
+    fn holds(&self, src: &ValTacky, dst: &ValTacky) -> bool {
+        self.src_of(dst) == Some(src)
+    }
+
+    fn insert(&mut self, src: ValTacky, dst: ValTacky) {
+        self.remove(&dst);
+        self.by_src
+            .entry(src.clone())
+            .or_default()
+            .insert(dst.clone());
+        self.by_dst.insert(dst, src);
+    }
+
+    /// drops the copy into `dst`.
+    fn remove(&mut self, dst: &ValTacky) {
+        if let Some(src) = self.by_dst.remove(dst) {
+            if let Some(dsts) = self.by_src.get_mut(&src) {
+                dsts.remove(dst);
+            }
+        }
+    }
+
+    /// drops every copy into or out of `v`, once it is overwritten.
+    fn kill(&mut self, v: &ValTacky) {
+        self.remove(v);
+        for dst in self.by_src.remove(v).unwrap_or_default() {
+            self.by_dst.remove(&dst);
+        }
+    }
+
+    /// drops every copy involving an aliased variable.
+    fn kill_aliased(&mut self, aliased: &HashSet<Symbol>) {
+        let dsts: Vec<ValTacky> = self
+            .by_dst
+            .iter()
+            .filter(|(dst, src)| is_aliased(dst, aliased) || is_aliased(src, aliased))
+            .map(|(dst, _)| dst.clone())
+            .collect();
+        for dst in dsts {
+            self.remove(&dst);
+        }
+    }
+
+    /// keeps only the copies `other` holds as well.
+    fn meet(&mut self, other: &Self) {
+        let dsts: Vec<ValTacky> = self
+            .by_dst
+            .iter()
+            .filter(|(dst, src)| !other.holds(src, dst))
+            .map(|(dst, _)| dst.clone())
+            .collect();
+        for dst in dsts {
+            self.remove(&dst);
+        }
+    }
+}
+
+/// Propagates copies through a function.
+/// The reaching set is the dataflow fact: a copy reaches the start of a block only if
+/// it reaches the end of every predecessor, and `transfer` carries the set through each
+/// instruction. Facts are iterated to a fixpoint over the CFG before any rewriting.
+pub fn propagate_copies(
+    instrs: Vec<InstructionTacky>,
+    aliased: &HashSet<Symbol>,
+) -> Vec<InstructionTacky> {
+    let mut cfg = Cfg::from_instructions(instrs);
+    let reaching_in = find_reaching_copies(&cfg, aliased);
+
+    for (no, mut reaching) in reaching_in.into_iter().enumerate() {
+        let block = cfg.block_mut(no);
+        let mut rewritten = Vec::with_capacity(block.instructions.len());
+
+        for mut instr in std::mem::take(&mut block.instructions).into_iter() {
+            for v in instr.srcs_mut() {
+                if let Some(src) = reaching.src_of(v) {
+                    *v = src.clone();
+                }
+            }
+
+            if let InstructionTacky::Copy { src, dst } = &instr {
+                // `dst` already holds `src`; the copy changes nothing
+                if src == dst || reaching.holds(src, dst) || reaching.holds(dst, src) {
+                    continue;
+                }
+            }
+
+            transfer(&instr, &mut reaching, aliased);
+            rewritten.push(instr);
+        }
+
+        block.instructions = rewritten;
+    }
+
+    remove_dead_copies(cfg.to_instructions(), aliased)
+}
+
+/// Iterative forward dataflow computing the copies reaching the start of each block.
+/// Every block's output starts as the set of all copies in the function (the top of
+/// the lattice), left as `None` since meeting with it changes nothing, and shrinks
+/// until nothing changes.
+fn find_reaching_copies(cfg: &Cfg, aliased: &HashSet<Symbol>) -> Vec<ReachingCopies> {
+    let mut reaching_in = vec![ReachingCopies::default(); cfg.len()];
+    let mut reaching_out: Vec<Option<ReachingCopies>> = vec![None; cfg.len()];
+    let mut worklist: Vec<usize> = (0..cfg.len()).rev().collect();
+
+    while let Some(no) = worklist.pop() {
+        let block = cfg.block(no);
+
+        let mut incoming: Option<ReachingCopies> = None;
+        for pred in block.preds.iter() {
+            let pred_out = match pred {
+                NodeId::Block { no: p } => match &reaching_out[*p] {
+                    Some(out) => out,
+                    None => continue,
+                },
+                _ => &ReachingCopies::default(),
+            };
+            match incoming.as_mut() {
+                None => incoming = Some(pred_out.clone()),
+                Some(meet) => meet.meet(pred_out),
+            }
+        }
+        let incoming = incoming.unwrap_or_default();
+
+        let mut outgoing = incoming.clone();
+        for instr in block.instructions.iter() {
+            transfer(instr, &mut outgoing, aliased);
+        }
+        reaching_in[no] = incoming;
+
+        if reaching_out[no].as_ref() != Some(&outgoing) {
+            reaching_out[no] = Some(outgoing);
+            for succ in block.succs.iter() {
+                if let NodeId::Block { no: s } = succ {
+                    if !worklist.contains(s) {
+                        worklist.push(*s);
+                    }
+                }
+            }
+        }
+    }
+
+    reaching_in
+}
+
+/// Kills every copy that involves the value an instruction overwrites,
+/// and generates a new fact if the instruction is itself a copy within one type.
+/// A call or a store through a pointer may overwrite any aliased variable too.
+fn transfer(instr: &InstructionTacky, reaching: &mut ReachingCopies, aliased: &HashSet<Symbol>) {
+    if let Some(dst) = instr.dst() {
+        reaching.kill(dst);
+    }
+    if let InstructionTacky::FunCall { .. }
+    | InstructionTacky::TailCall { .. }
+    | InstructionTacky::Store { .. } = instr
+    {
+        reaching.kill_aliased(aliased);
+    }
+    if let InstructionTacky::Copy { src, dst } = instr {
+        if src.ty() != dst.ty() {
+            return;
+        }
+        reaching.insert(src.clone(), dst.clone());
+    }
+}
+
+/// Drops copies into temporaries that nothing reads anymore.
+/// Temporaries never outlive their function, so an unread one is dead; an
+/// aliased variable may be read some other way, and is left for dead store
+/// elimination.
+fn remove_dead_copies(
+    mut instrs: Vec<InstructionTacky>,
+    aliased: &HashSet<Symbol>,
+) -> Vec<InstructionTacky> {
+    loop {
+        let before = instrs.len();
+        let read: HashSet<ValTacky> = instrs
+            .iter()
+            .flat_map(|i| i.srcs().into_iter().cloned())
+            .collect();
+        instrs.retain(|i| match i {
+            InstructionTacky::Copy {
+                src: _,
+                dst: dst @ ValTacky::TmpVar { .. },
+            } => read.contains(dst) || is_aliased(dst, aliased),
+            _ => true,
+        });
+        if instrs.len() == before {
+            return instrs;
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::compiler::parser::{BinaryOp, UnaryOp};
+
+/// ### TACKY (input):
+/// ```text
+/// Copy(Constant(3), Var("tmp.0"))
+/// Binary(Add, Var("tmp.0"), Var("tmp.0"), Var("tmp.1"))
+/// Return(Var("tmp.1"))
+/// ```
+/// ### TACKY (output):
+/// ```text
+/// Binary(Add, Constant(3), Constant(3), Var("tmp.1"))
+/// Return(Var("tmp.1"))
+/// ```
+#[test]
+fn propagate_constant_copy() {
+    let instrs = vec![
+        InstructionTacky::Copy {
+            src: ValTacky::int(3),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Binary {
+            op: BinaryOp::Add,
+            src1: ValTacky::tmp(0),
+            src2: ValTacky::tmp(0),
+            dst: ValTacky::tmp(1),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(1),
+        },
+    ];
+    assert_eq!(
+        propagate_copies(instrs, &HashSet::new()),
+        vec![
+            InstructionTacky::Binary {
+                op: BinaryOp::Add,
+                src1: ValTacky::int(3),
+                src2: ValTacky::int(3),
+                dst: ValTacky::tmp(1),
+            },
+            InstructionTacky::Ret {
+                v: ValTacky::tmp(1),
+            },
+        ]
+    );
+}
+
+/// a chain of copies collapses into returning the original value.
+#[test]
+fn propagate_copy_chain() {
+    let instrs = vec![
+        InstructionTacky::Copy {
+            src: ValTacky::int(5),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::tmp(0),
+            dst: ValTacky::tmp(1),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(1),
+        },
+    ];
+    assert_eq!(
+        propagate_copies(instrs, &HashSet::new()),
+        vec![InstructionTacky::Ret {
+            v: ValTacky::int(5),
+        }]
+    );
+}
+
+/// a copy from an `int` into an `unsigned int` changes the type its value is
+/// read as, so the comparison must keep reading the unsigned copy.
+#[test]
+fn no_propagate_across_types() {
+    let unsigned = ValTacky::TmpVar {
+        name: Symbol::numbered("tmp", 1),
+        ty: crate::compiler::parser::CType::UInt,
+    };
+    let instrs = vec![
+        InstructionTacky::Copy {
+            src: ValTacky::tmp(0),
+            dst: unsigned.clone(),
+        },
+        InstructionTacky::Binary {
+            op: BinaryOp::LessThan,
+            src1: unsigned.clone(),
+            src2: ValTacky::Const {
+                c: crate::compiler::parser::Const::UInt { uint: 1 },
+            },
+            dst: ValTacky::tmp(2),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(2),
+        },
+    ];
+    assert_eq!(propagate_copies(instrs.clone(), &HashSet::new()), instrs);
+}
+
+/// redefining a copy's source kills the copy, so the later read must stay as is.
+#[test]
+fn redefinition_kills_copy() {
+    let instrs = vec![
+        InstructionTacky::Unary {
+            op: UnaryOp::Negate,
+            src: ValTacky::int(1),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::tmp(0),
+            dst: ValTacky::tmp(1),
+        },
+        InstructionTacky::Unary {
+            op: UnaryOp::Negate,
+            src: ValTacky::int(2),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Binary {
+            op: BinaryOp::Add,
+            src1: ValTacky::tmp(0),
+            src2: ValTacky::tmp(1),
+            dst: ValTacky::tmp(2),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(2),
+        },
+    ];
+    assert_eq!(propagate_copies(instrs.clone(), &HashSet::new()), instrs);
+}
+
+/// copying a value back into the temporary it was just copied from is a no-op.
+#[test]
+fn remove_redundant_reverse_copy() {
+    let instrs = vec![
+        InstructionTacky::Unary {
+            op: UnaryOp::Negate,
+            src: ValTacky::int(1),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::tmp(0),
+            dst: ValTacky::tmp(1),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::tmp(1),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(0),
+        },
+    ];
+    assert_eq!(
+        propagate_copies(instrs, &HashSet::new()),
+        vec![
+            InstructionTacky::Unary {
+                op: UnaryOp::Negate,
+                src: ValTacky::int(1),
+                dst: ValTacky::tmp(0),
+            },
+            InstructionTacky::Ret {
+                v: ValTacky::tmp(0),
+            },
+        ]
+    );
+}
+
+#[cfg(test)]
+fn label(name: &str) -> InstructionTacky {
+    InstructionTacky::Label {
+        name: String::from(name),
+    }
+}
+
+/// a copy made on only one path into a join must not be propagated past it.
+#[test]
+fn copy_on_one_branch_does_not_reach_join() {
+    let instrs = vec![
+        InstructionTacky::Unary {
+            op: UnaryOp::Negate,
+            src: ValTacky::int(1),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::JumpIfZero {
+            condition: ValTacky::tmp(0),
+            target: String::from("join"),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::int(7),
+            dst: ValTacky::tmp(0),
+        },
+        label("join"),
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(0),
+        },
+    ];
+    assert_eq!(propagate_copies(instrs.clone(), &HashSet::new()), instrs);
+}
+
+/// a copy made before a branch holds on both paths, so it reaches the join.
+#[test]
+fn copy_before_branch_reaches_join() {
+    let instrs = vec![
+        InstructionTacky::Copy {
+            src: ValTacky::int(7),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::JumpIfZero {
+            condition: ValTacky::tmp(1),
+            target: String::from("join"),
+        },
+        InstructionTacky::Unary {
+            op: UnaryOp::Negate,
+            src: ValTacky::tmp(0),
+            dst: ValTacky::tmp(1),
+        },
+        label("join"),
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(0),
+        },
+    ];
+    assert_eq!(
+        propagate_copies(instrs, &HashSet::new()),
+        vec![
+            InstructionTacky::JumpIfZero {
+                condition: ValTacky::tmp(1),
+                target: String::from("join"),
+            },
+            InstructionTacky::Unary {
+                op: UnaryOp::Negate,
+                src: ValTacky::int(7),
+                dst: ValTacky::tmp(1),
+            },
+            label("join"),
+            InstructionTacky::Ret {
+                v: ValTacky::int(7),
+            },
+        ]
+    );
+}
@@ -0,0 +1,426 @@
+//! Local common subexpression elimination.
+//! Numbers the `Unary` and `Binary` computations within each basic block and
+//! replaces a repeated computation with a `Copy` of the temporary that already
+//! holds its result. Copy propagation and dead store elimination then clean up
+//! the copies this leaves behind.
+use std::collections::{HashMap, HashSet};
+
+use crate::compiler::{
+    parser::{BinaryOp, UnaryOp},
+    symbol::Symbol,
+    tacky::{InstructionTacky, ValTacky},
+};
+
+use super::{cfg::Cfg, is_aliased};
+
+/// A pure computation, keyed by its operator and operands.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+enum Expression {
+    Unary {
+        op: UnaryOp,
+        src: ValTacky,
+    },
+    Binary {
+        op: BinaryOp,
+        src1: ValTacky,
+        src2: ValTacky,
+    },
+}
+
+impl Expression {
+    fn from_instruction(instr: &InstructionTacky) -> Option<Self> {
+        match instr {
+            InstructionTacky::Unary { op, src, dst: _ } => Some(Expression::Unary {
+                op: op.clone(),
+                src: src.clone(),
+            }),
+            InstructionTacky::Binary {
+                op,
+                src1,
+                src2,
+                dst: _,
+            } => Some(Expression::Binary {
+                op: op.clone(),
+                src1: src1.clone(),
+                src2: src2.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// the same computation with the operands of a commutative operator swapped,
+    /// if it has them.
+    fn swapped(&self) -> Option<Self> {
+        match self {
+            Expression::Binary { op, src1, src2 } if is_commutative(op) => {
+                Some(Expression::Binary {
+                    op: op.clone(),
+                    src1: src2.clone(),
+                    src2: src1.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn reads(&self, v: &ValTacky) -> bool {
+        self.operands().contains(&v)
+    }
+
+    fn operands(&self) -> Vec<&ValTacky> {
+        match self {
+            Expression::Unary { op: _, src } => vec![src],
+            Expression::Binary { op: _, src1, src2 } => vec![src1, src2],
+        }
+    }
+}
+
+/// The expressions available at a point in a block, each with the variable
+/// holding its value. They are indexed by the variables they involve, operands
+/// and holder alike, so that overwriting one forgets just the expressions it
+/// reaches, and those involving an aliased variable are kept apart for calls and
+/// stores to forget, each in time proportional to what it forgets.
+#[derive(Default)]
+struct Available {
+    holders: HashMap<Expression, ValTacky>,
+    involving: HashMap<ValTacky, HashSet<Expression>>,
+    aliased: HashSet<Expression>,
+}
+
+impl Available {
+    /// the variable holding the value of `expr`, computed with a commutative
+    /// operator's operands in either order.
+    fn holder(&self, expr: &Expression) -> Option<&ValTacky> {
+        self.holders
+            .get(expr)
+            .or_else(|| self.holders.get(&expr.swapped()?))
+    }
+
+    fn insert(&mut self, expr: Expression, holder: ValTacky, aliased: &HashSet<Symbol>) {
+        let mut vars = expr.operands();
+        vars.push(&holder);
+        if vars.iter().any(|v| is_aliased(v, aliased)) {
+            self.aliased.insert(expr.clone());
+        }
+        for v in vars {
+            self.involving
+                .entry(v.clone())
+                .or_default()
+                .insert(expr.clone());
+        }
+        self.holders.insert(expr, holder);
+    }
+
+    fn remove(&mut self, expr: &Expression) {
+        let Some(holder) = self.holders.remove(expr) else {
+            return;
+        };
+        self.aliased.remove(expr);
+        let mut vars = expr.operands();
+        vars.push(&holder);
+        for v in vars {
+            if let Some(exprs) = self.involving.get_mut(v) {
+                exprs.remove(expr);
+            }
+        }
+    }
+
+    /// forgets the expressions that read `v` or are held in it, once it is
+    /// overwritten.
+    fn overwrite(&mut self, v: &ValTacky) {
+        for expr in self.involving.remove(v).unwrap_or_default() {
+            self.remove(&expr);
+        }
+    }
+
+    /// forgets the expressions involving an aliased variable, which a call or a
+    /// store through a pointer may overwrite.
+    fn overwrite_aliased(&mut self) {
+        for expr in std::mem::take(&mut self.aliased) {
+            self.remove(&expr);
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+fn is_commutative(op: &BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Add
+            | BinaryOp::Multiply
+            | BinaryOp::BitwiseAnd
+            | BinaryOp::BitwiseOr
+            | BinaryOp::BitwiseXor
+            | BinaryOp::Equal
+            | BinaryOp::NotEqual
+    )
+}
+
+/// Eliminates repeated computations inside each basic block of a function.
+/// An expression stays available until one of its operands or the temporary holding
+/// it is overwritten. Instructions this pass doesn't understand forget everything.
+pub fn eliminate_common_subexpressions(
+    instrs: Vec<InstructionTacky>,
+    aliased: &HashSet<Symbol>,
+) -> Vec<InstructionTacky> {
+    let mut cfg = Cfg::from_instructions(instrs);
+
+    for no in 0..cfg.len() {
+        let block = cfg.block_mut(no);
+        let mut available = Available::default();
+        let mut rewritten = Vec::with_capacity(block.instructions.len());
+
+        for instr in std::mem::take(&mut block.instructions).into_iter() {
+            let expr = Expression::from_instruction(&instr);
+            let instr = match (&expr, instr.dst()) {
+                (Some(expr), Some(dst)) => match available.holder(expr) {
+                    Some(holder) if holder == dst => continue,
+                    Some(holder) => InstructionTacky::Copy {
+                        src: holder.clone(),
+                        dst: dst.clone(),
+                    },
+                    None => instr,
+                },
+                _ => instr,
+            };
+
+            match (&instr, instr.dst()) {
+                (
+                    InstructionTacky::Copy { .. }
+                    | InstructionTacky::Unary { .. }
+                    | InstructionTacky::Binary { .. }
+                    | InstructionTacky::AddPtr { .. },
+                    Some(dst),
+                ) => available.overwrite(dst),
+                // a call can't reach the caller's temporaries, so besides its
+                // own result it only overwrites aliased variables
+                (InstructionTacky::FunCall { .. }, Some(dst)) => {
+                    available.overwrite(dst);
+                    available.overwrite_aliased();
+                }
+                (_, Some(_)) => available.clear(),
+                // and a store through a pointer only those
+                (InstructionTacky::Store { .. }, None) => available.overwrite_aliased(),
+                (_, None) => {}
+            }
+
+            if let (Some(expr), Some(dst)) = (expr, instr.dst()) {
+                // `tmp = tmp + 1` no longer computes its own operands afterwards
+                if matches!(
+                    instr,
+                    InstructionTacky::Unary { .. } | InstructionTacky::Binary { .. }
+                ) && !expr.reads(dst)
+                {
+                    available.insert(expr, dst.clone(), aliased);
+                }
+            }
+
+            rewritten.push(instr);
+        }
+
+        block.instructions = rewritten;
+    }
+
+    cfg.to_instructions()
+}
+
+#[cfg(test)]
+fn tmp(no: u32) -> ValTacky {
+    ValTacky::tmp(no)
+}
+
+/// ### TACKY (input):
+/// ```text
+/// Binary(Multiply, Var("tmp.0"), Var("tmp.1"), Var("tmp.2"))
+/// Binary(Multiply, Var("tmp.0"), Var("tmp.1"), Var("tmp.3"))
+/// Binary(Add, Var("tmp.2"), Var("tmp.3"), Var("tmp.4"))
+/// Return(Var("tmp.4"))
+/// ```
+/// ### TACKY (output):
+/// ```text
+/// Binary(Multiply, Var("tmp.0"), Var("tmp.1"), Var("tmp.2"))
+/// Copy(Var("tmp.2"), Var("tmp.3"))
+/// Binary(Add, Var("tmp.2"), Var("tmp.3"), Var("tmp.4"))
+/// Return(Var("tmp.4"))
+/// ```
+#[test]
+fn reuse_repeated_binary() {
+    let instrs = vec![
+        InstructionTacky::Binary {
+            op: BinaryOp::Multiply,
+            src1: tmp(0),
+            src2: tmp(1),
+            dst: tmp(2),
+        },
+        InstructionTacky::Binary {
+            op: BinaryOp::Multiply,
+            src1: tmp(0),
+            src2: tmp(1),
+            dst: tmp(3),
+        },
+        InstructionTacky::Binary {
+            op: BinaryOp::Add,
+            src1: tmp(2),
+            src2: tmp(3),
+            dst: tmp(4),
+        },
+        InstructionTacky::Ret { v: tmp(4) },
+    ];
+    let res = eliminate_common_subexpressions(instrs, &HashSet::new());
+    assert_eq!(
+        res[1],
+        InstructionTacky::Copy {
+            src: tmp(2),
+            dst: tmp(3),
+        }
+    );
+    assert_eq!(
+        res.iter()
+            .filter(|i| matches!(i, InstructionTacky::Binary { .. }))
+            .count(),
+        2
+    );
+}
+
+/// `b*a` is the same value as `a*b`, but `b-a` is not `a-b`.
+#[test]
+fn match_commutative_operands_only() {
+    let instrs = vec![
+        InstructionTacky::Binary {
+            op: BinaryOp::Multiply,
+            src1: tmp(0),
+            src2: tmp(1),
+            dst: tmp(2),
+        },
+        InstructionTacky::Binary {
+            op: BinaryOp::Multiply,
+            src1: tmp(1),
+            src2: tmp(0),
+            dst: tmp(3),
+        },
+        InstructionTacky::Binary {
+            op: BinaryOp::Subtract,
+            src1: tmp(0),
+            src2: tmp(1),
+            dst: tmp(4),
+        },
+        InstructionTacky::Binary {
+            op: BinaryOp::Subtract,
+            src1: tmp(1),
+            src2: tmp(0),
+            dst: tmp(5),
+        },
+    ];
+    let res = eliminate_common_subexpressions(instrs.clone(), &HashSet::new());
+    assert_eq!(
+        res[1],
+        InstructionTacky::Copy {
+            src: tmp(2),
+            dst: tmp(3),
+        }
+    );
+    assert_eq!(res[3], instrs[3]);
+}
+
+/// overwriting an operand between the two computations makes them differ.
+#[test]
+fn redefined_operand_invalidates_expression() {
+    let instrs = vec![
+        InstructionTacky::Unary {
+            op: UnaryOp::Negate,
+            src: tmp(0),
+            dst: tmp(1),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::int(4),
+            dst: tmp(0),
+        },
+        InstructionTacky::Unary {
+            op: UnaryOp::Negate,
+            src: tmp(0),
+            dst: tmp(2),
+        },
+    ];
+    assert_eq!(
+        eliminate_common_subexpressions(instrs.clone(), &HashSet::new()),
+        instrs
+    );
+}
+
+/// overwriting the temporary holding the result loses the expression too.
+#[test]
+fn redefined_holder_invalidates_expression() {
+    let instrs = vec![
+        InstructionTacky::Unary {
+            op: UnaryOp::BitwiseComplement,
+            src: tmp(0),
+            dst: tmp(1),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::int(4),
+            dst: tmp(1),
+        },
+        InstructionTacky::Unary {
+            op: UnaryOp::BitwiseComplement,
+            src: tmp(0),
+            dst: tmp(2),
+        },
+    ];
+    assert_eq!(
+        eliminate_common_subexpressions(instrs.clone(), &HashSet::new()),
+        instrs
+    );
+}
+
+/// expressions don't carry across a label, since another path may reach it.
+#[test]
+fn expressions_stay_within_block() {
+    let instrs = vec![
+        InstructionTacky::Unary {
+            op: UnaryOp::Negate,
+            src: tmp(0),
+            dst: tmp(1),
+        },
+        InstructionTacky::Label {
+            name: String::from("join"),
+        },
+        InstructionTacky::Unary {
+            op: UnaryOp::Negate,
+            src: tmp(0),
+            dst: tmp(2),
+        },
+    ];
+    assert_eq!(
+        eliminate_common_subexpressions(instrs.clone(), &HashSet::new()),
+        instrs
+    );
+}
+
+/// two calls with the same arguments may give different results, but an
+/// expression computed before a call is still there after it.
+#[test]
+fn calls_are_not_reused() {
+    let call = |dst| InstructionTacky::FunCall {
+        name: crate::compiler::symbol::Symbol::intern("f"),
+        args: vec![tmp(0)],
+        dst,
+    };
+    let negate = |dst| InstructionTacky::Unary {
+        op: UnaryOp::Negate,
+        src: tmp(0),
+        dst,
+    };
+    let instrs = vec![negate(tmp(1)), call(tmp(2)), call(tmp(3)), negate(tmp(4))];
+    let res = eliminate_common_subexpressions(instrs.clone(), &HashSet::new());
+    assert_eq!(res[..3], instrs[..3]);
+    assert_eq!(
+        res[3],
+        InstructionTacky::Copy {
+            src: tmp(1),
+            dst: tmp(4),
+        }
+    );
+}
@@ -0,0 +1,282 @@
+//! Dead store elimination.
+//! Computes backward liveness over a function and deletes `Copy`, `Unary`, and
+//! `Binary` instructions whose destination is never read afterwards.
+//! Instructions that can trap are kept even when their result is dead.
+use std::collections::HashSet;
+
+use crate::compiler::{
+    parser::BinaryOp,
+    symbol::Symbol,
+    tacky::{InstructionTacky, ValTacky},
+};
+
+use super::cfg::{Cfg, NodeId};
+
+/// Removes dead stores from a function.
+/// A variable is live at a point if some path from it reads the variable before
+/// overwriting it; the live sets hold the variables' names.
+/// Liveness flows backwards: a block's live-out set is the union of its successors'
+/// live-in sets, iterated to a fixpoint over the CFG. Only the aliased variables
+/// are live at a return, since temporaries are local to the function, and a
+/// call or a load through a pointer may read any of them too.
+pub fn eliminate_dead_stores(
+    instrs: Vec<InstructionTacky>,
+    aliased: &HashSet<Symbol>,
+) -> Vec<InstructionTacky> {
+    let mut cfg = Cfg::from_instructions(instrs);
+    let live_out = find_live_out(&cfg, aliased);
+
+    for (no, mut live) in live_out.into_iter().enumerate() {
+        let block = cfg.block_mut(no);
+        let mut kept = Vec::with_capacity(block.instructions.len());
+
+        for instr in std::mem::take(&mut block.instructions).into_iter().rev() {
+            if let Some(ValTacky::TmpVar { name, .. }) = instr.dst() {
+                if !live.contains(name) && !has_side_effects(&instr) {
+                    continue;
+                }
+            }
+            transfer(&instr, &mut live, aliased);
+            kept.push(instr);
+        }
+
+        kept.reverse();
+        block.instructions = kept;
+    }
+
+    cfg.to_instructions()
+}
+
+/// Iterative backward dataflow computing the values live at the end of each block.
+fn find_live_out(cfg: &Cfg, aliased: &HashSet<Symbol>) -> Vec<HashSet<Symbol>> {
+    let mut live_in = vec![HashSet::new(); cfg.len()];
+    let mut live_out = vec![HashSet::new(); cfg.len()];
+    let mut worklist: Vec<usize> = (0..cfg.len()).collect();
+
+    while let Some(no) = worklist.pop() {
+        let block = cfg.block(no);
+
+        let mut live: HashSet<Symbol> = HashSet::new();
+        for succ in block.succs.iter() {
+            if let NodeId::Block { no: s } = succ {
+                live.extend(live_in[*s].iter().cloned());
+            }
+        }
+        live_out[no] = live.clone();
+
+        for instr in block.instructions.iter().rev() {
+            transfer(instr, &mut live, aliased);
+        }
+
+        if live != live_in[no] {
+            live_in[no] = live;
+            for pred in block.preds.iter() {
+                if let NodeId::Block { no: p } = pred {
+                    if !worklist.contains(p) {
+                        worklist.push(*p);
+                    }
+                }
+            }
+        }
+    }
+
+    live_out
+}
+
+/// Carries the live set backwards across one instruction.
+fn transfer(instr: &InstructionTacky, live: &mut HashSet<Symbol>, aliased: &HashSet<Symbol>) {
+    if let Some(ValTacky::TmpVar { name, .. }) = instr.dst() {
+        live.remove(name);
+    }
+    live.extend(instr.srcs().into_iter().filter_map(|src| match src {
+        ValTacky::TmpVar { name, .. } => Some(*name),
+        ValTacky::Const { .. } => None,
+    }));
+    if let InstructionTacky::Ret { .. }
+    | InstructionTacky::FunCall { .. }
+    | InstructionTacky::TailCall { .. }
+    | InstructionTacky::Load { .. } = instr
+    {
+        live.extend(aliased.iter().copied());
+    }
+}
+
+/// Whether an instruction does anything beyond writing its destination.
+/// A call may do anything at all, so it stays even when its result is unread.
+/// `idiv` raises #DE on a zero divisor and on the most negative value divided
+/// by -1, so a division only goes away when its divisor rules both out. A
+/// `double` division never traps.
+fn has_side_effects(instr: &InstructionTacky) -> bool {
+    match instr {
+        InstructionTacky::Ret { v: _ }
+        | InstructionTacky::Jump { target: _ }
+        | InstructionTacky::JumpIfZero { .. }
+        | InstructionTacky::JumpIfNotZero { .. }
+        | InstructionTacky::Label { name: _ }
+        | InstructionTacky::FunCall { .. } => true,
+        InstructionTacky::Binary {
+            op: BinaryOp::Divide | BinaryOp::Remainder,
+            src2,
+            ..
+        } => {
+            src2.ty().is_integer()
+                && !matches!(src2, ValTacky::Const { c } if c.value() != 0 && c.value() != -1)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+use crate::compiler::parser::UnaryOp;
+
+/// an unread unary result and the binary feeding it both disappear.
+#[test]
+fn remove_dead_chain() {
+    let instrs = vec![
+        InstructionTacky::Binary {
+            op: BinaryOp::Add,
+            src1: ValTacky::int(1),
+            src2: ValTacky::int(2),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Unary {
+            op: UnaryOp::Negate,
+            src: ValTacky::tmp(0),
+            dst: ValTacky::tmp(1),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::int(4),
+        },
+    ];
+    assert_eq!(
+        eliminate_dead_stores(instrs, &HashSet::new()),
+        vec![InstructionTacky::Ret {
+            v: ValTacky::int(4),
+        }]
+    );
+}
+
+#[test]
+fn keep_live_stores() {
+    let instrs = vec![
+        InstructionTacky::Unary {
+            op: UnaryOp::BitwiseComplement,
+            src: ValTacky::int(1),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::tmp(0),
+            dst: ValTacky::tmp(1),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(1),
+        },
+    ];
+    assert_eq!(
+        eliminate_dead_stores(instrs.clone(), &HashSet::new()),
+        instrs
+    );
+}
+
+/// a store is dead if it is overwritten before anything reads it.
+#[test]
+fn remove_overwritten_store() {
+    let instrs = vec![
+        InstructionTacky::Copy {
+            src: ValTacky::int(1),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::int(2),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(0),
+        },
+    ];
+    assert_eq!(
+        eliminate_dead_stores(instrs.clone(), &HashSet::new()),
+        instrs[1..]
+    );
+}
+
+/// divisions that may trap must survive even when their result is unused.
+#[test]
+fn keep_trapping_division() {
+    let instrs = vec![
+        InstructionTacky::Binary {
+            op: BinaryOp::Divide,
+            src1: ValTacky::int(1),
+            src2: ValTacky::tmp(0),
+            dst: ValTacky::tmp(1),
+        },
+        InstructionTacky::Binary {
+            op: BinaryOp::Remainder,
+            src1: ValTacky::tmp(0),
+            src2: ValTacky::int(-1),
+            dst: ValTacky::tmp(2),
+        },
+        InstructionTacky::Binary {
+            op: BinaryOp::Divide,
+            src1: ValTacky::tmp(0),
+            src2: ValTacky::int(4),
+            dst: ValTacky::tmp(3),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::int(0),
+        },
+    ];
+    assert_eq!(
+        eliminate_dead_stores(instrs.clone(), &HashSet::new()),
+        vec![instrs[0].clone(), instrs[1].clone(), instrs[3].clone()]
+    );
+}
+
+/// a call stays for what else it does, though nothing reads its result.
+#[test]
+fn keep_unread_call() {
+    let instrs = vec![
+        InstructionTacky::FunCall {
+            name: crate::compiler::symbol::Symbol::intern("f"),
+            args: vec![ValTacky::int(1)],
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::int(0),
+        },
+    ];
+    assert_eq!(
+        eliminate_dead_stores(instrs.clone(), &HashSet::new()),
+        instrs
+    );
+}
+
+/// a value read again around a loop's back edge stays live through the loop.
+#[test]
+fn keep_store_live_around_loop() {
+    let instrs = vec![
+        InstructionTacky::Label {
+            name: String::from("loop"),
+        },
+        InstructionTacky::Unary {
+            op: UnaryOp::Negate,
+            src: ValTacky::tmp(0),
+            dst: ValTacky::tmp(1),
+        },
+        InstructionTacky::Copy {
+            src: ValTacky::tmp(1),
+            dst: ValTacky::tmp(0),
+        },
+        InstructionTacky::JumpIfNotZero {
+            condition: ValTacky::tmp(2),
+            target: String::from("loop"),
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::int(0),
+        },
+    ];
+    assert_eq!(
+        eliminate_dead_stores(instrs.clone(), &HashSet::new()),
+        instrs
+    );
+}
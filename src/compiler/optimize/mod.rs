@@ -0,0 +1,381 @@
+//! Optimization passes, chosen by `-O` level and `--enable-pass`/`--disable-pass`.
+//! Most passes work on TACKY; the peephole pass cleans up the final assembly.
+//! A TACKY pass sees one function at a time, and is told which variables are
+//! aliased: those with static storage, which outlive the function, and those
+//! whose address the function takes. Any call may read or write an aliased
+//! variable, and so may any load or store through a pointer.
+use std::{collections::HashSet, fmt::Display};
+
+use clap::ValueEnum;
+
+use super::{
+    asmgen::{InstructionAsm, ProgramAsm, TopLevelAsm},
+    symbol::Symbol,
+    tacky::{FunDefTacky, InstructionTacky, ProgramTacky, TopLevelTacky, ValTacky},
+    timings::Timings,
+};
+use cfg::Cfg;
+
+pub mod cfg;
+pub mod const_fold;
+pub mod copy_prop;
+pub mod cse;
+pub mod dead_store;
+pub mod peephole;
+pub mod tail_calls;
+
+/// How many rounds of the TACKY passes run at most. A few are enough for
+/// nearly every function, but some changes take a round each, like folding
+/// `c = c + 1` repeated down a block, one more constant a round, so without a
+/// cap the rounds would grow with the function.
+const MAX_ROUNDS: usize = 16;
+
+/// A named optimization pass, in the order passes run.
+/// - `-O1` runs the TACKY passes but tail calls
+/// - `-O2` adds tail calls and the assembly peephole pass
+#[derive(PartialEq, Debug, Clone, Copy, ValueEnum)]
+pub enum Pass {
+    ConstantFolding,
+    SimplifyBranches,
+    UnreachableCode,
+    Cse,
+    CopyPropagation,
+    DeadStores,
+    TailCalls,
+    Peephole,
+}
+
+impl Pass {
+    /// the lowest optimization level that runs the pass.
+    fn level(&self) -> u8 {
+        match self {
+            Self::TailCalls | Self::Peephole => 2,
+            _ => 1,
+        }
+    }
+
+    /// the pass's name, as the command line spells it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ConstantFolding => "constant-folding",
+            Self::SimplifyBranches => "simplify-branches",
+            Self::UnreachableCode => "unreachable-code",
+            Self::Cse => "cse",
+            Self::CopyPropagation => "copy-propagation",
+            Self::DeadStores => "dead-stores",
+            Self::TailCalls => "tail-calls",
+            Self::Peephole => "peephole",
+        }
+    }
+
+    fn is_tacky(&self) -> bool {
+        *self != Self::Peephole
+    }
+
+    /// runs a TACKY pass over one function's instructions. `statics` are the
+    /// variables with static storage among those `aliased`.
+    fn run_tacky(
+        &self,
+        instrs: Vec<InstructionTacky>,
+        aliased: &HashSet<Symbol>,
+        statics: &HashSet<Symbol>,
+    ) -> Vec<InstructionTacky> {
+        match self {
+            Self::ConstantFolding => const_fold::fold_constants(instrs),
+            Self::SimplifyBranches => Cfg::from_instructions(instrs)
+                .simplify_branches()
+                .to_instructions(),
+            Self::UnreachableCode => Cfg::from_instructions(instrs)
+                .eliminate_unreachable_code()
+                .to_instructions(),
+            Self::Cse => cse::eliminate_common_subexpressions(instrs, aliased),
+            Self::CopyPropagation => copy_prop::propagate_copies(instrs, aliased),
+            Self::DeadStores => dead_store::eliminate_dead_stores(instrs, aliased),
+            Self::TailCalls => tail_calls::mark_tail_calls(instrs, aliased, statics),
+            Self::Peephole => instrs,
+        }
+    }
+
+    /// runs an assembly pass over one function's instructions.
+    fn run_asm(&self, instrs: Vec<InstructionAsm>) -> Vec<InstructionAsm> {
+        match self {
+            Self::Peephole => peephole::optimize_instructions(instrs),
+            _ => instrs,
+        }
+    }
+}
+
+/// The passes to run, in order, and which of them to dump the program after.
+/// Dumps are numbered in the order passes ran, and kept until taken.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Pipeline {
+    passes: Vec<Pass>,
+    dump_after: Vec<Pass>,
+    dumps: Vec<String>,
+}
+
+impl Pipeline {
+    /// every pass `level` runs, plus those in `enable`, minus those in `disable`.
+    pub fn new(level: u8, enable: &[Pass], disable: &[Pass]) -> Self {
+        Pipeline {
+            passes: Pass::value_variants()
+                .iter()
+                .filter(|pass| pass.level() <= level || enable.contains(pass))
+                .filter(|pass| !disable.contains(pass))
+                .copied()
+                .collect(),
+            dump_after: vec![],
+            dumps: vec![],
+        }
+    }
+
+    /// dumps the program after every run of each pass in `passes`.
+    pub fn dump_after(self, passes: &[Pass]) -> Self {
+        Pipeline {
+            dump_after: passes.to_vec(),
+            ..self
+        }
+    }
+
+    /// the dumps made since the last call, each headed by its number and pass.
+    pub fn take_dumps(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.dumps)
+    }
+
+    /// Runs the TACKY passes over every function in the program, timing each.
+    /// Applies them in rounds, since each pass can expose further opportunities
+    /// for the others, each round over just the functions the one before changed,
+    /// until none does or `MAX_ROUNDS` have run.
+    pub fn optimize(&mut self, mut prog: ProgramTacky, timings: &mut Timings) -> ProgramTacky {
+        let passes: Vec<Pass> = self
+            .passes
+            .iter()
+            .filter(|p| p.is_tacky())
+            .copied()
+            .collect();
+        if passes.is_empty() {
+            return prog;
+        }
+        let statics = prog.statics();
+        let mut changing: Vec<bool> = prog.functions().map(|_| true).collect();
+        for _ in 0..MAX_ROUNDS {
+            let before: Vec<Option<Vec<InstructionTacky>>> = prog
+                .functions()
+                .zip(changing.iter())
+                .map(|(f, changing)| changing.then(|| f.instructions.clone()))
+                .collect();
+            for pass in passes.iter() {
+                for (fundef, _) in functions_mut(&mut prog)
+                    .zip(changing.iter())
+                    .filter(|(_, changing)| **changing)
+                {
+                    let instrs = std::mem::take(&mut fundef.instructions);
+                    let aliased = aliased(&instrs, &statics);
+                    fundef.instructions =
+                        timings.time(pass.name(), || pass.run_tacky(instrs, &aliased, &statics));
+                }
+                self.dump(*pass, &prog);
+            }
+            changing = prog
+                .functions()
+                .zip(before)
+                .map(|(f, before)| before.is_some_and(|before| f.instructions != before))
+                .collect();
+            if !changing.contains(&true) {
+                break;
+            }
+        }
+        prog
+    }
+
+    /// Runs the assembly passes over every function in the program, timing each.
+    pub fn optimize_asm(&mut self, mut prog: ProgramAsm, timings: &mut Timings) -> ProgramAsm {
+        let passes: Vec<Pass> = self
+            .passes
+            .iter()
+            .filter(|p| !p.is_tacky())
+            .copied()
+            .collect();
+        for pass in passes {
+            for item in prog.items.iter_mut() {
+                if let TopLevelAsm::Function { fundef } = item {
+                    let instrs = std::mem::take(&mut fundef.instructions);
+                    fundef.instructions = timings.time(pass.name(), || pass.run_asm(instrs));
+                }
+            }
+            self.dump(pass, &prog);
+        }
+        prog
+    }
+
+    fn dump(&mut self, pass: Pass, prog: &impl Display) {
+        if self.dump_after.contains(&pass) {
+            let header = format!("=== {:02} after {} ===", self.dumps.len() + 1, pass.name());
+            self.dumps.push(format!("{}\n{}", header, prog));
+        }
+    }
+}
+
+/// the function definitions in `prog`, to be rewritten in place.
+fn functions_mut(prog: &mut ProgramTacky) -> impl Iterator<Item = &mut FunDefTacky> {
+    prog.items.iter_mut().filter_map(|item| match item {
+        TopLevelTacky::Function { fundef } => Some(fundef),
+        _ => None,
+    })
+}
+
+/// the variables `instrs` can reach other than by name: those with static
+/// storage, and those whose address they take.
+fn aliased(instrs: &[InstructionTacky], statics: &HashSet<Symbol>) -> HashSet<Symbol> {
+    let mut aliased = statics.clone();
+    aliased.extend(instrs.iter().filter_map(|instr| match instr {
+        InstructionTacky::GetAddress {
+            src: ValTacky::TmpVar { name, .. },
+            ..
+        } => Some(*name),
+        _ => None,
+    }));
+    aliased
+}
+
+/// whether `val` is an aliased variable.
+fn is_aliased(val: &ValTacky, aliased: &HashSet<Symbol>) -> bool {
+    matches!(val, ValTacky::TmpVar { name, .. } if aliased.contains(name))
+}
+
+#[test]
+fn levels_add_passes() {
+    assert!(Pipeline::new(0, &[], &[]).passes.is_empty());
+    assert_eq!(
+        Pipeline::new(1, &[], &[]).passes,
+        vec![
+            Pass::ConstantFolding,
+            Pass::SimplifyBranches,
+            Pass::UnreachableCode,
+            Pass::Cse,
+            Pass::CopyPropagation,
+            Pass::DeadStores,
+        ]
+    );
+    assert_eq!(
+        Pipeline::new(2, &[], &[]).passes.last(),
+        Some(&Pass::Peephole)
+    );
+}
+
+/// overrides keep the pipeline in its usual order, and disabling wins.
+#[test]
+fn overrides_adjust_level() {
+    assert_eq!(
+        Pipeline::new(0, &[Pass::DeadStores, Pass::ConstantFolding], &[]).passes,
+        vec![Pass::ConstantFolding, Pass::DeadStores]
+    );
+    assert_eq!(
+        Pipeline::new(2, &[Pass::Cse], &[Pass::Cse, Pass::Peephole])
+            .passes
+            .len(),
+        6
+    );
+}
+
+/// the command line spelling of each pass is its name.
+#[test]
+fn pass_names_match_flags() {
+    for pass in Pass::value_variants() {
+        assert_eq!(pass.to_possible_value().unwrap().get_name(), pass.name());
+    }
+}
+
+/// only the chosen passes are dumped, numbered across rounds, up to the round
+/// that changes nothing.
+#[test]
+fn dumps_after_chosen_passes() {
+    use super::{
+        parser::BinaryOp,
+        symbol::Symbol,
+        tacky::{FunDefTacky, ValTacky},
+    };
+
+    let prog = ProgramTacky {
+        items: vec![TopLevelTacky::Function {
+            fundef: FunDefTacky {
+                identifier: Symbol::intern("main"),
+                global: true,
+                params: vec![],
+                instructions: vec![
+                    InstructionTacky::Binary {
+                        op: BinaryOp::Add,
+                        src1: ValTacky::int(3),
+                        src2: ValTacky::int(4),
+                        dst: ValTacky::tmp(0),
+                    },
+                    InstructionTacky::Ret {
+                        v: ValTacky::tmp(0),
+                    },
+                ],
+            },
+        }],
+    };
+    let mut pipeline = Pipeline::new(1, &[], &[]).dump_after(&[Pass::ConstantFolding]);
+    pipeline.optimize(prog, &mut Timings::default());
+    assert_eq!(
+        pipeline.take_dumps(),
+        vec![
+            "=== 01 after constant-folding ===\nfunction main:\n    tmp.0 = 7\n    ret tmp.0\n",
+            "=== 02 after constant-folding ===\nfunction main:\n    ret 7\n",
+        ]
+    );
+    assert!(pipeline.take_dumps().is_empty());
+}
+
+/// a chain of constants folds one link a round, and stops at the cap however
+/// long it is.
+#[test]
+fn rounds_are_capped() {
+    use super::{parser::BinaryOp, symbol::Symbol, tacky::ValTacky};
+
+    let chain = |links: u32| {
+        let mut instructions = vec![InstructionTacky::Copy {
+            src: ValTacky::int(0),
+            dst: ValTacky::tmp(0),
+        }];
+        for no in 1..=links {
+            instructions.push(InstructionTacky::Binary {
+                op: BinaryOp::Add,
+                src1: ValTacky::tmp(no - 1),
+                src2: ValTacky::int(1),
+                dst: ValTacky::tmp(no),
+            });
+        }
+        instructions.push(InstructionTacky::Ret {
+            v: ValTacky::tmp(links),
+        });
+        ProgramTacky {
+            items: vec![TopLevelTacky::Function {
+                fundef: FunDefTacky {
+                    identifier: Symbol::intern("main"),
+                    global: true,
+                    params: vec![],
+                    instructions,
+                },
+            }],
+        }
+    };
+    for links in [4, 1000] {
+        let mut pipeline = Pipeline::new(1, &[], &[]).dump_after(&[Pass::ConstantFolding]);
+        let prog = pipeline.optimize(chain(links), &mut Timings::default());
+        let rounds = pipeline.take_dumps().len();
+        match links {
+            4 => {
+                assert!(rounds < MAX_ROUNDS);
+                assert_eq!(
+                    prog.functions().next().unwrap().instructions,
+                    vec![InstructionTacky::Ret {
+                        v: ValTacky::int(4)
+                    }]
+                );
+            }
+            _ => assert_eq!(rounds, MAX_ROUNDS),
+        }
+    }
+}
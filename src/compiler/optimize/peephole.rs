@@ -0,0 +1,199 @@
+//! Peephole optimization over the final assembly instructions.
+//! A handful of local rewrite rules that clean up what instruction selection
+//! and the operand fixups leave behind, applied until none of them fire.
+use crate::compiler::asmgen::{AsmBinaryOp, InstructionAsm, OperandAsm};
+
+pub fn optimize_instructions(mut instrs: Vec<InstructionAsm>) -> Vec<InstructionAsm> {
+    loop {
+        let before = instrs.len();
+        instrs = remove_no_ops(instrs);
+        instrs = remove_round_trips(instrs);
+        if instrs.len() == before {
+            return instrs;
+        }
+    }
+}
+
+/// Drops instructions that leave their destination unchanged:
+/// `movl x, x`, `addl $0, x`, `subl $0, x`, and `imull $1, x`.
+fn remove_no_ops(instrs: Vec<InstructionAsm>) -> Vec<InstructionAsm> {
+    instrs
+        .into_iter()
+        .filter(|i| {
+            !matches!(
+                i,
+                InstructionAsm::Mov { src, dst, .. } if src == dst
+            ) && !matches!(
+                i,
+                InstructionAsm::Binary {
+                    binop: AsmBinaryOp::Add | AsmBinaryOp::Sub,
+                    src: OperandAsm::Imm { int: 0 },
+                    ..
+                } | InstructionAsm::Binary {
+                    binop: AsmBinaryOp::Imul,
+                    src: OperandAsm::Imm { int: 1 },
+                    ..
+                }
+            )
+        })
+        .collect()
+}
+
+/// Drops the second move of an adjacent `movl a, b; movl b, a` pair,
+/// since `a` still holds the value being copied back into it.
+/// Comments and line markers in between don't separate the pair.
+fn remove_round_trips(instrs: Vec<InstructionAsm>) -> Vec<InstructionAsm> {
+    let mut res: Vec<InstructionAsm> = Vec::with_capacity(instrs.len());
+    for instr in instrs.into_iter() {
+        let prev = res.iter().rev().find(|i| {
+            !matches!(
+                i,
+                InstructionAsm::Comment { text: _ } | InstructionAsm::Loc { .. }
+            )
+        });
+        if let (
+            Some(InstructionAsm::Mov {
+                ty: prev_ty,
+                src: prev_src,
+                dst: prev_dst,
+            }),
+            InstructionAsm::Mov { ty, src, dst },
+        ) = (prev, &instr)
+        {
+            if prev_ty == ty && prev_src == dst && prev_dst == src {
+                continue;
+            }
+        }
+        res.push(instr);
+    }
+    res
+}
+
+#[cfg(test)]
+use crate::compiler::asmgen::{AsmType, Register};
+
+#[test]
+fn remove_self_move() {
+    let instrs = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Reg { r: Register::AX },
+            dst: OperandAsm::Reg { r: Register::AX },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: -4 },
+            dst: OperandAsm::Stack { off: -4 },
+        },
+        InstructionAsm::Ret,
+    ];
+    assert_eq!(optimize_instructions(instrs), vec![InstructionAsm::Ret]);
+}
+
+#[test]
+fn remove_round_trip_move() {
+    let instrs = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: -4 },
+            dst: OperandAsm::Reg { r: Register::R10 },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Reg { r: Register::R10 },
+            dst: OperandAsm::Stack { off: -4 },
+        },
+        InstructionAsm::Ret,
+    ];
+    assert_eq!(
+        optimize_instructions(instrs.clone()),
+        vec![instrs[0].clone(), InstructionAsm::Ret]
+    );
+}
+
+/// only an exact reversal of the previous move is redundant.
+#[test]
+fn keep_unrelated_moves() {
+    let instrs = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: -4 },
+            dst: OperandAsm::Reg { r: Register::R10 },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Reg { r: Register::R10 },
+            dst: OperandAsm::Stack { off: -8 },
+        },
+        InstructionAsm::Ret,
+    ];
+    assert_eq!(optimize_instructions(instrs.clone()), instrs);
+}
+
+#[test]
+fn remove_add_and_sub_zero() {
+    let instrs = vec![
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Add,
+            src: OperandAsm::Imm { int: 0 },
+            dst: OperandAsm::Stack { off: -4 },
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Sub,
+            src: OperandAsm::Imm { int: 0 },
+            dst: OperandAsm::Reg { r: Register::AX },
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Add,
+            src: OperandAsm::Imm { int: 1 },
+            dst: OperandAsm::Reg { r: Register::AX },
+        },
+    ];
+    assert_eq!(optimize_instructions(instrs.clone()), instrs[2..]);
+}
+
+#[test]
+fn remove_multiply_by_one() {
+    let instrs = vec![
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Imul,
+            src: OperandAsm::Imm { int: 1 },
+            dst: OperandAsm::Reg { r: Register::R11 },
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Imul,
+            src: OperandAsm::Imm { int: 0 },
+            dst: OperandAsm::Reg { r: Register::R11 },
+        },
+    ];
+    assert_eq!(optimize_instructions(instrs.clone()), instrs[1..]);
+}
+
+/// removing one rule's match can line up another, so rules repeat to a fixpoint.
+#[test]
+fn apply_rules_to_fixpoint() {
+    let instrs = vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Reg { r: Register::AX },
+            dst: OperandAsm::Reg { r: Register::DX },
+        },
+        InstructionAsm::Binary {
+            ty: AsmType::Longword,
+            binop: AsmBinaryOp::Add,
+            src: OperandAsm::Imm { int: 0 },
+            dst: OperandAsm::Reg { r: Register::DX },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Reg { r: Register::DX },
+            dst: OperandAsm::Reg { r: Register::AX },
+        },
+    ];
+    assert_eq!(optimize_instructions(instrs.clone()), instrs[..1]);
+}
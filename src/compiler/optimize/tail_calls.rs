@@ -0,0 +1,156 @@
+//! Tail calls.
+//! A call whose result is returned at once, with nothing but line markers in
+//! between, becomes a `TailCall` in the return's place, which the assembly
+//! makes a jump to the callee once the frame is gone, so that it returns
+//! straight to the caller's caller.
+//! A function that takes the address of any of its own variables keeps its
+//! calls, since the callee could be handed a pointer into the frame it would
+//! replace; so does a call whose result is written to an aliased variable,
+//! which someone may read after the function returns.
+use std::collections::HashSet;
+
+use crate::compiler::{
+    symbol::Symbol,
+    tacky::{InstructionTacky, ValTacky},
+};
+
+use super::is_aliased;
+
+/// turns each call feeding the return right after it into a tail call.
+/// `statics` are the variables with static storage, whose addresses outlive
+/// the frame.
+pub fn mark_tail_calls(
+    instrs: Vec<InstructionTacky>,
+    aliased: &HashSet<Symbol>,
+    statics: &HashSet<Symbol>,
+) -> Vec<InstructionTacky> {
+    let frame_escapes = instrs.iter().any(|instr| {
+        matches!(
+            instr,
+            InstructionTacky::GetAddress {
+                src: ValTacky::TmpVar { name, .. },
+                ..
+            } if !statics.contains(name)
+        )
+    });
+    if frame_escapes {
+        return instrs;
+    }
+
+    let mut res: Vec<InstructionTacky> = Vec::with_capacity(instrs.len());
+    for instr in instrs.into_iter() {
+        if let InstructionTacky::Ret { v } = &instr {
+            let call = res
+                .iter()
+                .rposition(|i| !matches!(i, InstructionTacky::Loc { .. }));
+            if let Some(call) = call {
+                if let InstructionTacky::FunCall { name, args, dst } = &res[call] {
+                    if dst == v && !is_aliased(dst, aliased) {
+                        // in the return's place, so it is on the return's line
+                        let tail_call = InstructionTacky::TailCall {
+                            name: *name,
+                            args: args.clone(),
+                        };
+                        res.remove(call);
+                        res.push(tail_call);
+                        continue;
+                    }
+                }
+            }
+        }
+        res.push(instr);
+    }
+    res
+}
+
+#[cfg(test)]
+fn call(dst: ValTacky) -> InstructionTacky {
+    InstructionTacky::FunCall {
+        name: Symbol::intern("f"),
+        args: vec![ValTacky::int(1)],
+        dst,
+    }
+}
+
+/// the call is returned past a line marker, which then covers the tail call.
+#[test]
+fn returned_call_becomes_tail_call() {
+    let instrs = vec![
+        call(ValTacky::tmp(0)),
+        InstructionTacky::Loc {
+            file: 1,
+            line: 2,
+            col: 3,
+        },
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(0),
+        },
+    ];
+    assert_eq!(
+        mark_tail_calls(instrs, &HashSet::new(), &HashSet::new()),
+        vec![
+            InstructionTacky::Loc {
+                file: 1,
+                line: 2,
+                col: 3,
+            },
+            InstructionTacky::TailCall {
+                name: Symbol::intern("f"),
+                args: vec![ValTacky::int(1)],
+            },
+        ]
+    );
+}
+
+/// returning something else, or a result stored where it outlives the
+/// function, keeps the call.
+#[test]
+fn other_returns_keep_the_call() {
+    let instrs = vec![
+        call(ValTacky::tmp(0)),
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(1),
+        },
+    ];
+    assert_eq!(
+        mark_tail_calls(instrs.clone(), &HashSet::new(), &HashSet::new()),
+        instrs
+    );
+    let global = ValTacky::TmpVar {
+        name: Symbol::intern("g"),
+        ty: crate::compiler::parser::CType::Int,
+    };
+    let instrs = vec![call(global.clone()), InstructionTacky::Ret { v: global }];
+    let statics = HashSet::from([Symbol::intern("g")]);
+    assert_eq!(mark_tail_calls(instrs.clone(), &statics, &statics), instrs);
+}
+
+/// taking the address of a local keeps every call, but that of a variable
+/// with static storage doesn't.
+#[test]
+fn escaping_frame_keeps_calls() {
+    let address = |name| InstructionTacky::GetAddress {
+        src: ValTacky::TmpVar {
+            name: Symbol::intern(name),
+            ty: crate::compiler::parser::CType::Int,
+        },
+        dst: ValTacky::tmp(1),
+    };
+    let statics = HashSet::from([Symbol::intern("g")]);
+    let instrs = vec![
+        address("a.0"),
+        call(ValTacky::tmp(0)),
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(0),
+        },
+    ];
+    assert_eq!(mark_tail_calls(instrs.clone(), &statics, &statics), instrs);
+    let instrs = vec![
+        address("g"),
+        call(ValTacky::tmp(0)),
+        InstructionTacky::Ret {
+            v: ValTacky::tmp(0),
+        },
+    ];
+    assert_eq!(mark_tail_calls(instrs, &statics, &statics).len(), 2);
+}
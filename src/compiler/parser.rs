@@ -1,14 +1,56 @@
-use std::{fmt::Display, iter::Peekable};
+use std::{collections::HashMap, fmt::Display, iter::Peekable, sync::Arc};
 use thiserror::Error;
 
-use super::lexer::{Token, Type};
+use super::{
+    context::Context,
+    lexer::{escape, Span, SpannedToken, Token, Type},
+    symbol::Symbol,
+};
 
+/// Parse errors, each pointing at the source it is about.
+/// `expected` fields describe what should have come next, quoted tokens included,
+/// e.g. `';'` or `an expression`.
+/// `Unclosed` wraps the error found while looking for a closing delimiter, and
+/// remembers the opening one it should have matched.
 #[derive(Error, Debug, Clone, PartialEq)]
 pub enum ParseError {
-    FundefError { reason: String },
-    SeverredStream { location: String },
-    InvalidIdentifier { wrong_id: Token },
-    InvalidSyntax { got: Token, expected: Token },
+    FundefError {
+        reason: String,
+        span: Span,
+    },
+    SeverredStream {
+        expected: String,
+        span: Span,
+    },
+    InvalidIdentifier {
+        wrong_id: Token,
+        span: Span,
+    },
+    InvalidSyntax {
+        got: Token,
+        expected: String,
+        span: Span,
+    },
+    MissingSemicolon {
+        span: Span,
+    },
+    InvalidSpecifiers {
+        reason: String,
+        span: Span,
+    },
+    TooDeep {
+        span: Span,
+        limit: usize,
+    },
+    Unclosed {
+        error: Box<ParseError>,
+        open: Token,
+        open_span: Span,
+    },
+    InvalidArraySize {
+        reason: String,
+        span: Span,
+    },
 }
 
 type ParseResult<T> = Result<T, ParseError>;
@@ -16,690 +58,5107 @@ type ParseResult<T> = Result<T, ParseError>;
 impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::FundefError { reason } => {
-                write!(f, "(!) Error parsing function definition: {}", reason)
+            Self::FundefError { reason, span: _ } => write!(f, "{}", reason),
+            Self::SeverredStream { expected, span: _ } => {
+                write!(f, "expected {} at end of input", expected)
             }
-            Self::SeverredStream { location } => write!(
+            Self::InvalidIdentifier { wrong_id, span: _ } => write!(
                 f,
-                "(!) Error parsing interrupted stream of tokens in {}",
-                location
+                "expected a function name before '{}'",
+                wrong_id.spelling()
             ),
-            Self::InvalidIdentifier { wrong_id } => {
-                write!(f, "(!) Error parsing on invalid identifier: {}", wrong_id)
+            Self::InvalidSyntax {
+                got,
+                expected,
+                span: _,
+            } => write!(f, "expected {} before '{}'", expected, got.spelling()),
+            Self::MissingSemicolon { span: _ } => write!(f, "expected ';' after expression"),
+            Self::InvalidSpecifiers { reason, .. } => write!(f, "{}", reason),
+            Self::TooDeep { limit, .. } => {
+                write!(f, "nested more than {} levels deep", limit)
             }
-            Self::InvalidSyntax { got, expected } => write!(
-                f,
-                "(!) Error parsing, invalid syntax. Got a {} when I expected a {}.",
-                got, expected
-            ),
+            Self::Unclosed { error, .. } => write!(f, "{}", error),
+            Self::InvalidArraySize { reason, .. } => write!(f, "{}", reason),
         }
     }
 }
 
-/// Abstract C program
-/// ### Abstract grammar as of v0.1.0
-/// ```text
-/// program = Program(function_definition)
-/// ```
-/// ### Concrete grammar as of v0.1.0
-/// ```text
-/// <program> ::= <function>
-/// ```
-#[derive(PartialEq, Debug)]
-pub struct ProgramC {
-    pub function: Box<FunDefC>,
-}
-
-impl Display for ProgramC {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "ProgramC with inner FunDefC : {}", self.function)
+impl ParseError {
+    /// where in the source the error was found.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::FundefError { span, .. }
+            | Self::SeverredStream { span, .. }
+            | Self::InvalidIdentifier { span, .. }
+            | Self::InvalidSyntax { span, .. }
+            | Self::MissingSemicolon { span }
+            | Self::InvalidSpecifiers { span, .. }
+            | Self::TooDeep { span, .. }
+            | Self::InvalidArraySize { span, .. } => *span,
+            Self::Unclosed { error, .. } => error.span(),
+        }
     }
-}
 
-/// Abstract C function definition
-/// ### Abstract grammar as of v0.1.0
-/// ```text
-/// function_definition = Function(identifier name, statement)
-/// ```
-/// ### Concrete grammar as of v0.1.0
-/// ```text
-/// <function> ::= "int" <identifier> "(" "void" ")" "{" <statement> "}"
-/// ```
-#[derive(PartialEq, Debug)]
-pub struct FunDefC {
-    pub identifier: String,
-    pub statement: Box<StatementC>,
-}
+    /// the stable name of the error, for tools reading diagnostics. An unclosed
+    /// delimiter is named for the error found while looking for it.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::FundefError { .. } => "bad-function",
+            Self::SeverredStream { .. } => "unexpected-end",
+            Self::InvalidIdentifier { .. } => "expected-function-name",
+            Self::InvalidSyntax { .. } => "unexpected-token",
+            Self::MissingSemicolon { .. } => "missing-semicolon",
+            Self::InvalidSpecifiers { .. } => "bad-specifiers",
+            Self::TooDeep { .. } => "nesting-too-deep",
+            Self::Unclosed { error, .. } => error.code(),
+            Self::InvalidArraySize { .. } => "bad-array-size",
+        }
+    }
 
-impl Display for FunDefC {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "FunDefC with identifier ({}) and inner statement : {}",
-            self.identifier, self.statement
-        )
+    /// a second place worth showing alongside the error, and why.
+    pub fn note(&self) -> Option<(String, Span)> {
+        match self {
+            Self::Unclosed {
+                open, open_span, ..
+            } => Some((format!("to match this '{}'", open.spelling()), *open_span)),
+            _ => None,
+        }
     }
 }
 
-/// Abstract C statement
-/// ### Abstract grammar as of v0.1.0
-/// ```text
-/// statement = Return(exp)
-/// ```
-/// ### Concrete grammar as of v0.1.0
-/// ```text
-/// <statement> ::= "return" <exp> ";"
-/// ```
-#[derive(PartialEq, Debug)]
-pub enum StatementC {
-    Return { exp: Box<Exp> },
+/// How deeply parentheses, unary operators, `if` statements, the branches of
+/// conditionals and chained assignments may nest, well past the 63 levels C requires but shallow
+/// enough that parsing them can't overflow the stack.
+const MAX_NESTING: usize = 256;
+
+/// How tall an expression's tree may grow, counting binary operators too, so
+/// that long chains like `1 + 1 + ... + 1` can't overflow the stack of the
+/// recursive walks over the AST and TACKY generation after parsing. Their frames
+/// are large in a debug build, which overflows the main thread's 8 MiB from
+/// about 2700 levels, so this leaves room to spare.
+const MAX_HEIGHT: usize = 1024;
+
+/// Tokens left to parse, pulled from the lexer as the parser asks for them.
+/// Remembers where the last token taken ended, so errors about something missing
+/// can point just past what was there, and how deeply the factor being parsed is nested.
+/// Also collects the errors recovered from, and where parsing last picked up
+/// after one, so the errors an earlier one causes aren't reported.
+struct TokenStream<'a> {
+    tokens: Peekable<Box<dyn Iterator<Item = SpannedToken> + 'a>>,
+    prev_end: usize,
+    depth: usize,
+    errors: Vec<ParseError>,
+    resumed_at: Option<usize>,
 }
 
-impl Display for StatementC {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Return { exp } => write!(f, "return expression with inner exp : {}", exp),
+impl<'a> TokenStream<'a> {
+    fn new(tokens: impl Iterator<Item = SpannedToken> + 'a) -> Self {
+        let tokens: Box<dyn Iterator<Item = SpannedToken> + 'a> = Box::new(tokens);
+        TokenStream {
+            tokens: tokens.peekable(),
+            prev_end: 0,
+            depth: 0,
+            errors: vec![],
+            resumed_at: None,
         }
     }
-}
 
-/// Resolved C expression unifying ExpC and FactorC symbols.
-#[derive(PartialEq, Debug)]
-pub enum Exp {
-    Binary {
-        op: BinaryOp,
-        l_exp: Box<Exp>,
-        r_exp: Box<Exp>,
-    },
-    Const {
-        c: i32,
-    },
-    Unary {
-        op: UnaryOp,
-        exp: Box<Exp>,
-    },
-}
+    /// records `error`, unless no token has been parsed since the last recovery,
+    /// which makes it most likely a consequence of the error recovered from.
+    fn report(&mut self, error: ParseError) {
+        if self.resumed_at.is_none_or(|at| error.span().start > at) {
+            self.errors.push(error);
+        }
+    }
 
-impl Display for Exp {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Exp::Binary { op, l_exp, r_exp } => write!(
-                f,
-                "Binary expression with op = {}, l_exp = {}, r_exp = {}",
-                op, *l_exp, *r_exp
-            ),
-            Exp::Const { c } => write!(f, "Constant expression with c = {}", c),
-            Exp::Unary { op, exp } => {
-                write!(f, "Unary expression with op = {}, exp = {}", op, *exp)
+    /// skips tokens up to the next place parsing can pick up again: just past a `;`,
+    /// or at a `}`, a `return` or the specifiers of the next declaration.
+    fn synchronize(&mut self) {
+        while let Some(token) = self.peek() {
+            match token {
+                Token::CloseBrace | Token::RetKeyword => break,
+                token if is_specifier(token) => break,
+                Token::Semicolon => {
+                    self.next();
+                    break;
+                }
+                _ => {
+                    self.next();
+                }
             }
         }
+        self.resumed_at = Some(self.tokens.peek().map_or(self.prev_end, |t| t.span.start));
     }
-}
 
-impl Exp {
-    fn from_expc(expc: ExpC) -> Self {
-        match expc {
-            ExpC::Factor { fac } => Self::from_factc(*fac),
-            ExpC::Binary { op, l_exp, r_exp } => Self::Binary {
-                op,
-                l_exp: Box::new(Self::from_expc(*l_exp)),
-                r_exp: Box::new(Self::from_expc(*r_exp)),
-            },
+    /// skips the rest of a block whose `{` has been taken, up to and including
+    /// its matching `}`, for a block nested too deeply to parse.
+    fn skip_block(&mut self) {
+        let mut open = 1usize;
+        while let Some(token) = self.next() {
+            match token.token {
+                Token::OpenBrace => open += 1,
+                Token::CloseBrace if open == 1 => break,
+                Token::CloseBrace => open -= 1,
+                _ => {}
+            }
         }
     }
-    fn from_factc(factc: FactorC) -> Self {
-        match factc {
-            FactorC::Const { c } => Self::Const { c },
-            FactorC::Unary { op, fac } => Self::Unary {
-                op,
-                exp: Box::new(Self::from_factc(*fac)),
-            },
-            FactorC::Exp { exp } => Self::from_expc(*exp),
+
+    /// skips tokens up to the specifiers of the next top-level declaration.
+    fn synchronize_to_fundef(&mut self) {
+        while self.next_if(|t| !is_specifier(t)).is_some() {}
+        self.resumed_at = Some(self.tokens.peek().map_or(self.prev_end, |t| t.span.start));
+    }
+
+    fn peek(&mut self) -> Option<&Token> {
+        self.tokens.peek().map(|t| &t.token)
+    }
+
+    fn next(&mut self) -> Option<SpannedToken> {
+        let next = self.tokens.next();
+        if let Some(t) = &next {
+            self.prev_end = t.span.end;
+        }
+        next
+    }
+
+    fn next_if(&mut self, pred: impl FnOnce(&Token) -> bool) -> Option<SpannedToken> {
+        match self.peek() {
+            Some(t) if pred(t) => self.next(),
+            _ => None,
+        }
+    }
+
+    /// an empty span just past the last token taken.
+    fn after_prev(&self) -> Span {
+        Span {
+            start: self.prev_end,
+            end: self.prev_end,
         }
     }
 }
 
-/// Abstract C expression
-/// ### Abstract grammar as of v0.1.2
+/// Abstract C program
+/// ### Abstract grammar as of v0.1.19
 /// ```text
-/// exp = Factor(factor) | Binary(binary_operator, l_exp, r_exp)
+/// program = Program(top_level*)
+/// top_level = Function(function_definition) | Declaration(function_declaration)
+///           | Variable(declaration) | Struct(struct_declaration)
 /// ```
-/// ### Concrete grammar as of v0.1.2
+/// ### Concrete grammar as of v0.1.19
 /// ```text
-/// <exp> ::= <factor> | <exp> <binop> <exp>
+/// <program> ::= { <function> | <function-declaration> | <declaration>
+///               | <struct-declaration> }
 /// ```
 #[derive(PartialEq, Debug)]
-enum ExpC {
-    Factor {
-        fac: Box<FactorC>,
-    },
-    Binary {
-        op: BinaryOp,
-        l_exp: Box<ExpC>,
-        r_exp: Box<ExpC>,
-    },
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgramC {
+    pub items: Vec<TopLevelC>,
 }
 
-impl Display for ExpC {
+impl ProgramC {
+    /// the function definitions, in the order they appear in the source.
+    pub fn functions(&self) -> impl Iterator<Item = &FunDefC> {
+        self.items.iter().filter_map(|item| match item {
+            TopLevelC::Function { fundef } => Some(fundef),
+            TopLevelC::Declaration { .. }
+            | TopLevelC::Variable { .. }
+            | TopLevelC::Struct { .. } => None,
+        })
+    }
+}
+
+impl Display for ProgramC {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Factor { fac } => write!(f, "Factor expression with inner fac = {}", *fac),
-            Self::Binary { op, l_exp, r_exp } => write!(
-                f,
-                "Binary expression with binop = {}, l_exp = {}, r_exp = {}",
-                op, *l_exp, *r_exp
-            ),
+        write!(f, "ProgramC with inner items :")?;
+        for item in self.items.iter() {
+            write!(f, " {}", item)?;
         }
+        Ok(())
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub enum BinaryOp {
-    Add,
-    Subtract,
-    Multiply,
-    Divide,
-    Remainder,
-    BitwiseAnd,
-    BitwiseOr,
-    BitwiseXor,
+/// Abstract C top-level item
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TopLevelC {
+    Function { fundef: FunDefC },
+    Declaration { decl: FunDeclC },
+    Variable { decl: Declaration },
+    Struct { decl: StructDeclaration },
 }
 
-impl Display for BinaryOp {
+impl Display for TopLevelC {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Add => write!(f, "Add"),
-            Self::Subtract => write!(f, "Subtract"),
-            Self::Multiply => write!(f, "Multiply"),
-            Self::Divide => write!(f, "Divide"),
-            Self::Remainder => write!(f, "Remainder"),
-            Self::BitwiseAnd => write!(f, "Bitwise 'and'"),
-            Self::BitwiseOr => write!(f, "Bitwise 'or'"),
-            Self::BitwiseXor => write!(f, "Bitwise 'xor'"),
+            Self::Function { fundef } => write!(f, "{}", fundef),
+            Self::Declaration { decl } => write!(f, "{}", decl),
+            Self::Variable { decl } => write!(f, "{}", decl),
+            Self::Struct { decl } => write!(f, "{}", decl),
         }
     }
 }
 
-impl BinaryOp {
-    fn from(token: Token) -> ParseResult<Self> {
-        match token {
-            Token::Plus => Ok(Self::Add),
-            Token::Minus => Ok(Self::Subtract),
-            Token::Asterisk => Ok(Self::Multiply),
-            Token::FSlash => Ok(Self::Divide),
-            Token::Percent => Ok(Self::Remainder),
-            Token::Ampersand => Ok(Self::BitwiseAnd),
-            Token::Pipe => Ok(Self::BitwiseOr),
-            Token::Caret => Ok(Self::BitwiseXor),
-            _ => Err(ParseError::InvalidSyntax {
-                got: token,
-                expected: Token::Plus,
-            }),
+impl TopLevelC {
+    /// the name of the function or variable the item defines or declares, or
+    /// the tag of the structure.
+    pub fn identifier(&self) -> Symbol {
+        match self {
+            Self::Function { fundef } => fundef.identifier,
+            Self::Declaration { decl } => decl.identifier,
+            Self::Variable { decl } => decl.name,
+            Self::Struct { decl } => decl.tag,
         }
     }
-    fn token_prec(token: &Token) -> u8 {
-        match token {
-            Token::Pipe => 0,
-            Token::Caret => 1,
-            Token::Ampersand => 2,
-            Token::Plus | Token::Minus => 4,
-            Token::Asterisk | Token::FSlash | Token::Percent => 5,
-            _ => 255,
+
+    /// the parameters of a function; a variable has none.
+    pub fn params(&self) -> &[Param] {
+        match self {
+            Self::Function { fundef } => &fundef.params,
+            Self::Declaration { decl } => &decl.params,
+            Self::Variable { .. } | Self::Struct { .. } => &[],
         }
     }
-}
 
-/// Factor. Same ADT type as an expression, but allows for mutual recursion and precedence climbing.
-/// ### Formal Grammar as of v0.1.2
-/// ```text
-/// <factor> ::= <int> | <unop> <factor> | "(" <exp> ")"
-/// ```
-#[derive(PartialEq, Debug)]
-enum FactorC {
-    Const { c: i32 },
-    Unary { op: UnaryOp, fac: Box<FactorC> },
-    Exp { exp: Box<ExpC> },
-}
+    pub fn storage(&self) -> Option<StorageClass> {
+        match self {
+            Self::Function { fundef } => fundef.storage,
+            Self::Declaration { decl } => decl.storage,
+            Self::Variable { decl } => decl.storage,
+            Self::Struct { .. } => None,
+        }
+    }
 
-impl Display for FactorC {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    pub fn span(&self) -> Span {
         match self {
-            Self::Const { c } => write!(f, "constant factor with c = {}", c),
-            Self::Unary { op, fac } => write!(f, "unary factor with unop = {}, fac = {}", op, *fac),
-            Self::Exp { exp } => write!(f, "expression factor with exp = {}", *exp),
+            Self::Function { fundef } => fundef.span,
+            Self::Declaration { decl } => decl.span,
+            Self::Variable { decl } => decl.span,
+            Self::Struct { decl } => decl.span,
         }
     }
 }
 
-/// Abstract C unary operation.
-/// - `~`: bitwise complement
-/// - `-`: integer negation
-#[derive(PartialEq, Debug, Clone)]
-pub enum UnaryOp {
-    Negate,
-    BitwiseComplement,
+/// A storage-class specifier. At file scope it gives a name its linkage: `static`
+/// keeps it to this file, and `extern` takes whatever linkage an earlier
+/// declaration gave it. In a block, `static` gives a variable static storage
+/// duration, and `extern` refers to one declared outside the function.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StorageClass {
+    Static,
+    Extern,
 }
 
-impl Display for UnaryOp {
+impl Display for StorageClass {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Negate => write!(f, "UnaryOp::Negate"),
-            Self::BitwiseComplement => write!(f, "UnaryOp::BitwiseComplement"),
+            Self::Static => write!(f, "static"),
+            Self::Extern => write!(f, "extern"),
         }
     }
 }
 
-/// Big scary parse function.
-/// As of v0.1.0, a thin wrapper over parse_fundef.
-pub fn parse(tokens: Vec<Token>) -> ParseResult<ProgramC> {
-    Ok(ProgramC {
-        function: Box::new(parse_fundef(&mut tokens.into_iter())?),
-    })
+/// A C object type.
+/// - `Bool`: 8-bit unsigned integer that only ever holds 0 or 1
+/// - `Char`: 8-bit integer, signed as on x86-64 but a type of its own
+/// - `SChar`: 8-bit signed integer
+/// - `UChar`: 8-bit unsigned integer
+/// - `Short`: 16-bit signed integer
+/// - `UShort`: 16-bit unsigned integer
+/// - `Int`: 32-bit signed integer
+/// - `Long`: 64-bit signed integer
+/// - `UInt`: 32-bit unsigned integer
+/// - `ULong`: 64-bit unsigned integer
+/// - `Double`: IEEE 754 double-precision floating point
+/// - `Pointer`: 64-bit address of an object of the `referenced` type
+/// - `Array`: `size` objects of the `element` type, one after another
+/// - `Struct`: the structure type `tag` names, its members one after another
+///
+/// The type a pointer refers to, and an array's element type, are interned
+/// in the current context, as `CType::pointer_to` and `CType::array_of` make
+/// them, so types stay as cheap to copy and compare as they were without
+/// pointers. An array's size
+/// is 32 bits for the same reason; no array may span more than `i32::MAX`
+/// bytes anyway. A structure type is named by its tag alone, which semantic
+/// analysis makes unique to each structure the program declares; its layout
+/// is looked up by the tag, once type checking has defined it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CType {
+    Bool,
+    Char,
+    SChar,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    Long,
+    UInt,
+    ULong,
+    Double,
+    Pointer {
+        #[cfg_attr(feature = "serde", serde(with = "interned"))]
+        referenced: InternedType,
+    },
+    Array {
+        #[cfg_attr(feature = "serde", serde(with = "interned"))]
+        element: InternedType,
+        size: u32,
+    },
+    Struct {
+        tag: Symbol,
+    },
 }
 
-/// Expects a function definition.
-/// If this isn't found, returns an error.
-fn parse_fundef(tokens: &mut impl Iterator<Item = Token>) -> ParseResult<FunDefC> {
-    if expect_token(tokens, String::from("parse_fundef (1)"))?
-        != (Token::TyKeyword { ty: Type::Int })
-    {
-        return Err(ParseError::FundefError {
-            reason: String::from(
-                "expected a function definition but first token was not a valid return type",
-            ),
-        });
-    }
-
-    let id_attempt = expect_token(tokens, String::from("parse_fundef (2)"))?;
-    let id_string = if let Token::Identifier { val } = id_attempt {
-        val
-    } else {
-        return Err(ParseError::InvalidIdentifier {
-            wrong_id: id_attempt,
-        });
-    };
+/// A type interned in a context, which two types built from it the same way
+/// share. It is only looked up while its context is current.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InternedType(u32);
 
-    expect_variant(tokens, Token::OpenParens)?;
+impl InternedType {
+    pub fn get(self) -> CType {
+        Context::with(|context| {
+            context.types.read().expect("type table poisoned").types[self.0 as usize]
+        })
+    }
+}
 
-    if expect_token(tokens, String::from("parse_fundef (3)"))?
-        != (Token::TyKeyword { ty: Type::Void })
-    {
-        return Err(ParseError::FundefError {
-            reason: String::from("crumb v0.1.0 only accepts the `void` parameter"),
-        });
+impl std::fmt::Debug for InternedType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.get())
     }
+}
 
-    expect_variant(tokens, Token::CloseParens)?;
-    expect_variant(tokens, Token::OpenBrace)?;
-    let function = FunDefC {
-        identifier: id_string,
-        statement: Box::new(parse_statement(tokens)?),
-    };
-    expect_variant(tokens, Token::CloseBrace)?;
+/// The types interned in a context, each at its handle's index.
+#[derive(Default)]
+pub struct TypeTable {
+    types: Vec<CType>,
+    ids: HashMap<CType, u32>,
+}
 
-    Ok(function)
+/// Where a structure's members go: each at its offset from the start of the
+/// structure, in the order they were declared, with the size and alignment
+/// of the whole, which is padded to a multiple of its alignment so the
+/// members of an array of them line up too.
+#[derive(PartialEq, Debug)]
+pub struct StructLayout {
+    pub members: Vec<Member>,
+    pub size: usize,
+    pub alignment: usize,
 }
 
-/// Expects a statement.
-/// If this isn't found, returns an error.
-fn parse_statement(tokens: &mut impl Iterator<Item = Token>) -> ParseResult<StatementC> {
-    let tokens = &mut tokens.peekable();
-    expect_variant(tokens, Token::RetKeyword)?;
-    let expc = parse_exp(tokens, 0)?;
-    let exp = Exp::from_expc(expc);
-    let ret = Ok(StatementC::Return { exp: Box::new(exp) });
-    expect_variant(tokens, Token::Semicolon)?;
-    ret
+/// A member of a structure, `offset` bytes from its start.
+#[derive(PartialEq, Debug)]
+pub struct Member {
+    pub name: Symbol,
+    pub ty: CType,
+    pub offset: usize,
 }
 
-/// Expects an expression.
-/// If this isn't found, returns an error.
-fn parse_exp(
-    tokens: &mut Peekable<&mut impl Iterator<Item = Token>>,
-    min_prec: u8,
-) -> ParseResult<ExpC> {
-    let mut left = ExpC::Factor {
-        fac: Box::new(parse_factor(tokens)?),
-    };
+impl StructLayout {
+    /// the member called `name`, if there is one.
+    pub fn member(&self, name: Symbol) -> Option<&Member> {
+        self.members.iter().find(|member| member.name == name)
+    }
+}
 
-    while let Some(next_token) = tokens.next_if(|t| {
-        matches!(
-            t,
-            Token::Plus
-                | Token::Minus
-                | Token::Asterisk
-                | Token::FSlash
-                | Token::Percent
-                | Token::Ampersand
-                | Token::Pipe
-                | Token::Caret
-        ) && BinaryOp::token_prec(&t) >= min_prec
-    }) {
-        let prec = BinaryOp::token_prec(&next_token) + 1;
-        left = ExpC::Binary {
-            op: BinaryOp::from(next_token)?,
-            l_exp: Box::new(left),
-            r_exp: Box::new(parse_exp(tokens, prec)?),
+/// prints the type as C names it without a declared name: a pointer's `*`
+/// before where the name would go and an array's size after it, in
+/// parentheses where a pointer to an array needs them, as in `int (*)[3]`.
+/// A structure is named by the tag it was declared with, as in `struct s *`.
+impl Display for CType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (mut ty, mut declarator) = (*self, String::new());
+        loop {
+            match ty {
+                Self::Pointer { referenced } => {
+                    declarator.insert(0, '*');
+                    if referenced.get().is_array() {
+                        declarator = format!("({})", declarator);
+                    }
+                    ty = referenced.get();
+                }
+                Self::Array { element, size } => {
+                    declarator.push_str(&format!("[{}]", size));
+                    ty = element.get();
+                }
+                _ => break,
+            }
+        }
+        let name = match ty {
+            // a unique tag is the declared one with a number after it
+            Self::Struct { tag } => format!("struct {}", tag.as_str().split('.').next().unwrap()),
+            _ => ty.scalar_name().to_string(),
+        };
+        match declarator.starts_with('[') || declarator.is_empty() {
+            true => write!(f, "{}{}", name, declarator),
+            false => write!(f, "{} {}", name, declarator),
         }
     }
-
-    Ok(left)
 }
 
-fn parse_factor(tokens: &mut Peekable<&mut impl Iterator<Item = Token>>) -> ParseResult<FactorC> {
-    let got = expect_token(tokens, String::from("parse_factor"))?;
-    match got {
-        Token::Constant { val } => Ok(FactorC::Const { c: val }),
-        Token::Tilde => Ok(FactorC::Unary {
-            op: UnaryOp::BitwiseComplement,
-            fac: Box::new(parse_factor(tokens)?),
-        }),
-        Token::Minus => Ok(FactorC::Unary {
-            op: UnaryOp::Negate,
-            fac: Box::new(parse_factor(tokens)?),
-        }),
-        Token::OpenParens => {
-            let inner = parse_exp(tokens, 0)?;
-            expect_variant(tokens, Token::CloseParens)?;
-            Ok(FactorC::Exp {
-                exp: Box::new(inner),
-            })
+impl CType {
+    /// the name of a type that isn't built from another one.
+    fn scalar_name(&self) -> &'static str {
+        match self {
+            Self::Bool => "_Bool",
+            Self::Char => "char",
+            Self::SChar => "signed char",
+            Self::UChar => "unsigned char",
+            Self::Short => "short",
+            Self::UShort => "unsigned short",
+            Self::Int => "int",
+            Self::Long => "long",
+            Self::UInt => "unsigned int",
+            Self::ULong => "unsigned long",
+            Self::Double => "double",
+            Self::Pointer { .. } | Self::Array { .. } | Self::Struct { .. } => {
+                unreachable!(
+                    "pointers, arrays and structures are named after what they are built from"
+                )
+            }
+        }
+    }
+
+    /// the type of a pointer to this one.
+    pub fn pointer_to(self) -> CType {
+        Self::Pointer {
+            referenced: self.interned(),
+        }
+    }
+
+    /// the type of an array of `size` of this one.
+    pub fn array_of(self, size: u32) -> CType {
+        Self::Array {
+            element: self.interned(),
+            size,
+        }
+    }
+
+    /// this type's handle in the current context.
+    fn interned(self) -> InternedType {
+        Context::with(|context| {
+            if let Some(&id) = context
+                .types
+                .read()
+                .expect("type table poisoned")
+                .ids
+                .get(&self)
+            {
+                return InternedType(id);
+            }
+            let mut table = context.types.write().expect("type table poisoned");
+            // another thread may have interned it since
+            if let Some(&id) = table.ids.get(&self) {
+                return InternedType(id);
+            }
+            let id = u32::try_from(table.types.len()).expect("more than u32::MAX types");
+            table.types.push(self);
+            table.ids.insert(self, id);
+            InternedType(id)
+        })
+    }
+
+    /// records the layout type checking gave the structure `tag` defines, in
+    /// the current context, for every later stage to find by its tag.
+    pub fn define_struct(tag: Symbol, layout: StructLayout) {
+        Context::with(|context| {
+            context
+                .structs
+                .write()
+                .expect("structure table poisoned")
+                .insert(tag, Arc::new(layout))
+        });
+    }
+
+    /// the layout of a structure type, if it has been defined.
+    pub fn layout(&self) -> Option<Arc<StructLayout>> {
+        match self {
+            Self::Struct { tag } => Context::with(|context| {
+                context
+                    .structs
+                    .read()
+                    .expect("structure table poisoned")
+                    .get(tag)
+                    .cloned()
+            }),
+            _ => None,
+        }
+    }
+
+    /// size in bytes. A structure that hasn't been defined, which only a
+    /// program that wasn't checked can have an object of, takes none.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Bool | Self::Char | Self::SChar | Self::UChar => 1,
+            Self::Short | Self::UShort => 2,
+            Self::Int | Self::UInt => 4,
+            Self::Long | Self::ULong | Self::Double | Self::Pointer { .. } => 8,
+            Self::Array { element, size } => element.get().size() * *size as usize,
+            Self::Struct { .. } => self.layout().map_or(0, |layout| layout.size),
+        }
+    }
+
+    /// the boundary, in bytes, an object of the type is placed on: its size,
+    /// or for an array its element's. A structure is aligned like its most
+    /// aligned member. This is the alignment the type has as a member of a
+    /// structure, or an element of an array, where the layout has to match the
+    /// one other compilers give it.
+    pub fn alignment(&self) -> usize {
+        match self {
+            Self::Array { element, .. } => element.get().alignment(),
+            Self::Struct { .. } => self.layout().map_or(1, |layout| layout.alignment),
+            _ => self.size(),
+        }
+    }
+
+    /// the boundary a variable of the type is placed on: its alignment, but
+    /// 16 for an array of 16 bytes or more, as the x86-64 ABI asks of array
+    /// variables so they can be worked on with SSE loads.
+    pub fn variable_alignment(&self) -> usize {
+        match self {
+            Self::Array { .. } if self.size() >= 16 => 16,
+            _ => self.alignment(),
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            Self::Char | Self::SChar | Self::Short | Self::Int | Self::Long
+        )
+    }
+
+    pub fn is_integer(&self) -> bool {
+        !matches!(
+            self,
+            Self::Double | Self::Pointer { .. } | Self::Array { .. } | Self::Struct { .. }
+        )
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self, Self::Pointer { .. })
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, Self::Array { .. })
+    }
+
+    pub fn is_struct(&self) -> bool {
+        matches!(self, Self::Struct { .. })
+    }
+
+    /// whether the size of an object of the type is known: not for a
+    /// structure declared but not yet defined, nor an array of one.
+    pub fn is_complete(&self) -> bool {
+        match self {
+            Self::Struct { .. } => self.layout().is_some(),
+            Self::Array { element, .. } => element.get().is_complete(),
+            _ => true,
+        }
+    }
+
+    /// the type a value of this type is used as in an expression: an array
+    /// decays to a pointer to its first element, and any other type stays.
+    pub fn decayed(self) -> CType {
+        match self {
+            Self::Array { element, .. } => element.get().pointer_to(),
+            _ => self,
+        }
+    }
+
+    /// the type a value of this type is promoted to in an expression: `int`
+    /// for the integer types narrower than it, which it holds all the values
+    /// of, and the type itself for the rest.
+    pub fn promoted(self) -> CType {
+        match self.is_integer() && self.size() < 4 {
+            true => Self::Int,
+            false => self,
+        }
+    }
+
+    /// the type the operands of a binary operator of types `self` and `other`
+    /// are converted to once promoted: `double` if either is, else the wider of
+    /// the two, or the unsigned one if they are as wide. Only arithmetic types
+    /// have one; type checking finds pointers' another way.
+    pub fn common(self, other: CType) -> CType {
+        let (this, other) = (self.promoted(), other.promoted());
+        if this == other {
+            this
+        } else if this == Self::Double || other == Self::Double {
+            Self::Double
+        } else if this.size() == other.size() {
+            match this.is_signed() {
+                true => other,
+                false => this,
+            }
+        } else if this.size() > other.size() {
+            this
+        } else {
+            other
         }
-        _ => Err(ParseError::InvalidSyntax {
-            got,
-            expected: Token::Constant { val: 42 },
-        }),
     }
 }
 
-fn expect_token(tokens: &mut impl Iterator<Item = Token>, location: String) -> ParseResult<Token> {
-    match tokens.next() {
-        Some(token) => Ok(token),
-        None => Err(ParseError::SeverredStream { location }),
+/// (de)serializes the type a pointer refers to, or an array's element type,
+/// by value, interning it again on the way back in.
+#[cfg(feature = "serde")]
+mod interned {
+    use super::{CType, InternedType};
+    use serde::{Deserialize, Serialize};
+
+    pub fn serialize<S: serde::Serializer>(
+        ty: &InternedType,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        ty.get().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<InternedType, D::Error> {
+        Ok(CType::deserialize(deserializer)?.interned())
     }
 }
 
-fn expect_variant(tokens: &mut impl Iterator<Item = Token>, expected: Token) -> ParseResult<()> {
-    let token = expect_token(
-        tokens,
-        format!("expect_variant with expected token = {}", expected),
-    )?;
+/// A C constant, typed by its value and suffix: an unsuffixed one is an `int`
+/// if it fits and a `long` if not, an `l` or `L` suffix makes a `long`, and a
+/// `u` or `U` suffix makes either one unsigned. One with a decimal point or an
+/// exponent is a `double`. There are no constants of the types narrower than
+/// `int`, but converting one to such a type gives one.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Const {
+    Bool { bool: bool },
+    Char { char: i8 },
+    SChar { schar: i8 },
+    UChar { uchar: u8 },
+    Short { short: i16 },
+    UShort { ushort: u16 },
+    Int { int: i32 },
+    Long { long: i64 },
+    UInt { uint: u32 },
+    ULong { ulong: u64 },
+    Double { double: f64 },
+}
+
+/// constants are equal when they have the same type and bits, so `0.0` and
+/// `-0.0` are told apart and a NaN is equal to itself, as passes keying on
+/// constants need.
+impl PartialEq for Const {
+    fn eq(&self, other: &Self) -> bool {
+        self.ty() == other.ty() && self.value() == other.value()
+    }
+}
+
+impl Eq for Const {}
+
+impl std::hash::Hash for Const {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.ty().hash(state);
+        self.value().hash(state);
+    }
+}
+
+impl Display for Const {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bool { bool } => write!(f, "{}", u8::from(*bool)),
+            Self::Char { char } => write!(f, "{}", char),
+            Self::SChar { schar } => write!(f, "{}", schar),
+            Self::UChar { uchar } => write!(f, "{}", uchar),
+            Self::Short { short } => write!(f, "{}", short),
+            Self::UShort { ushort } => write!(f, "{}", ushort),
+            Self::Int { int } => write!(f, "{}", int),
+            Self::Long { long } => write!(f, "{}L", long),
+            Self::UInt { uint } => write!(f, "{}U", uint),
+            Self::ULong { ulong } => write!(f, "{}UL", ulong),
+            Self::Double { double } => write!(f, "{:?}", double),
+        }
+    }
+}
+
+impl Const {
+    pub fn ty(&self) -> CType {
+        match self {
+            Self::Bool { .. } => CType::Bool,
+            Self::Char { .. } => CType::Char,
+            Self::SChar { .. } => CType::SChar,
+            Self::UChar { .. } => CType::UChar,
+            Self::Short { .. } => CType::Short,
+            Self::UShort { .. } => CType::UShort,
+            Self::Int { .. } => CType::Int,
+            Self::Long { .. } => CType::Long,
+            Self::UInt { .. } => CType::UInt,
+            Self::ULong { .. } => CType::ULong,
+            Self::Double { .. } => CType::Double,
+        }
+    }
+
+    /// the bits of the value, sign- or zero-extended to 64 as its type is
+    /// signed or not. A `double`'s are its IEEE 754 encoding.
+    pub fn value(&self) -> i64 {
+        match *self {
+            Self::Double { double } => double.to_bits() as i64,
+            _ => self.wide() as i64,
+        }
+    }
+
+    /// the value itself, which any of the integer types fits in. A `double` is
+    /// truncated toward zero.
+    pub fn wide(&self) -> i128 {
+        match *self {
+            Self::Bool { bool } => bool.into(),
+            Self::Char { char } => char.into(),
+            Self::SChar { schar } => schar.into(),
+            Self::UChar { uchar } => uchar.into(),
+            Self::Short { short } => short.into(),
+            Self::UShort { ushort } => ushort.into(),
+            Self::Int { int } => int.into(),
+            Self::Long { long } => long.into(),
+            Self::UInt { uint } => uint.into(),
+            Self::ULong { ulong } => ulong.into(),
+            Self::Double { double } => double as i128,
+        }
+    }
+
+    /// whether the value is zero, which is false as a condition. Both `0.0`
+    /// and `-0.0` are.
+    pub fn is_zero(&self) -> bool {
+        match *self {
+            Self::Double { double } => double == 0.0,
+            _ => self.wide() == 0,
+        }
+    }
+
+    /// the constant of type `ty` with the low bits of `value`, which is how
+    /// arithmetic wraps and how a conversion to a type as wide or narrower
+    /// works; a wider one keeps the value. There are no pointer constants, so
+    /// a pointer's value is an `unsigned long`, as the null pointer is, and so
+    /// is an array's, which is only ever used as the address it decays to, and
+    /// a structure's, which only a program that wasn't checked has a constant of.
+    /// A `_Bool` has no bits to wrap to: it is 1 for any value but zero.
+    pub fn wrap(value: i128, ty: CType) -> Const {
+        match ty {
+            CType::Bool => Self::Bool { bool: value != 0 },
+            CType::Char => Self::Char { char: value as i8 },
+            CType::SChar => Self::SChar { schar: value as i8 },
+            CType::UChar => Self::UChar { uchar: value as u8 },
+            CType::Short => Self::Short {
+                short: value as i16,
+            },
+            CType::UShort => Self::UShort {
+                ushort: value as u16,
+            },
+            CType::Int => Self::Int { int: value as i32 },
+            CType::Long => Self::Long { long: value as i64 },
+            CType::UInt => Self::UInt { uint: value as u32 },
+            CType::ULong | CType::Pointer { .. } | CType::Array { .. } | CType::Struct { .. } => {
+                Self::ULong {
+                    ulong: value as u64,
+                }
+            }
+            CType::Double => Self::Double {
+                double: value as f64,
+            },
+        }
+    }
+
+    /// the constant converted to type `ty`: a narrower type keeps as many
+    /// low bits as it has, and a wider one sign-extends a signed value and zero-extends
+    /// an unsigned one. A `double` is truncated toward zero on the way to an
+    /// integer type, and an integer rounded to the nearest `double`. Anything
+    /// converts to a `_Bool` as 0 if it is zero and 1 otherwise, so `0.5` is 1.
+    pub fn convert(self, ty: CType) -> Const {
+        match (self, ty) {
+            (Self::Double { .. }, CType::Double) => self,
+            (_, CType::Bool) => Self::Bool {
+                bool: !self.is_zero(),
+            },
+            _ => Self::wrap(self.wide(), ty),
+        }
+    }
+
+    /// the constant written as `val`, an `int` unless it doesn't fit in one.
+    fn unsuffixed(val: i64) -> Const {
+        match i32::try_from(val) {
+            Ok(int) => Self::Int { int },
+            Err(_) => Self::Long { long: val },
+        }
+    }
+
+    /// the constant written as `val` with a `u` suffix, an `unsigned int`
+    /// unless it doesn't fit in one.
+    fn unsigned(val: u64) -> Const {
+        match u32::try_from(val) {
+            Ok(uint) => Self::UInt { uint },
+            Err(_) => Self::ULong { ulong: val },
+        }
+    }
+}
+
+/// Abstract C function definition
+/// ### Abstract grammar as of v0.1.24
+/// ```text
+/// function_definition = Function(identifier name, param* params, type ret,
+///                                block body, storage_class? storage)
+/// storage_class = Static | Extern
+/// type = Bool | Char | SChar | UChar | Short | UShort | Int | Long | UInt | ULong | Double
+///      | Pointer(type referenced) | Array(type element, int size)
+///      | Struct(identifier tag)
+/// ```
+/// ### Concrete grammar as of v0.1.24
+/// ```text
+/// <function> ::= { <specifier> }+ <declarator> "(" <param-list> ")" <block>
+/// <specifier> ::= <type-specifier> | "static" | "extern"
+/// <type-specifier> ::= "int" | "long" | "short" | "char" | "signed" | "unsigned"
+///                    | "double" | "_Bool" | "struct" <identifier>
+/// <declarator> ::= { "*" } <identifier> { "[" <const> "]" }
+/// <param-list> ::= "void"
+///                | { <type-specifier> }+ <declarator> { "," { <type-specifier> }+ <declarator> }
+/// ```
+/// The specifiers are at most one each of `int`, `long` or `short`, and
+/// `signed` or `unsigned`; or `char` with at most one of `signed` or
+/// `unsigned`; or `double`, or `struct` and a tag, alone. There is at most
+/// one storage class, and they come in any order. Each `*` before a name
+/// makes its type a pointer to the type so far, so `int **p` declares a
+/// pointer to a pointer to an `int`. Each `[n]` after it makes it an array of
+/// `n` of what the ones after it make, so `int *a[2][3]` declares an array of
+/// 2 arrays of 3 pointers to `int`. A parameter declared as an array is a
+/// pointer to its element instead.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunDefC {
+    pub identifier: Symbol,
+    pub params: Vec<Param>,
+    pub ret: CType,
+    pub body: Block,
+    pub storage: Option<StorageClass>,
+    pub span: Span,
+}
+
+impl Display for FunDefC {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "FunDefC with identifier ({}) and params (",
+            self.identifier
+        )?;
+        for (i, param) in self.params.iter().enumerate() {
+            match i {
+                0 => write!(f, "{}", param.name)?,
+                _ => write!(f, ", {}", param.name)?,
+            }
+        }
+        write!(f, ") and inner body : {}", self.body)
+    }
+}
+
+/// Abstract C function declaration, a function's name and parameters without
+/// its body; its span takes in the `;`.
+/// ### Abstract grammar as of v0.1.12
+/// ```text
+/// function_declaration = FunctionDeclaration(identifier name, param* params,
+///                                            type ret, storage_class? storage)
+/// ```
+/// ### Concrete grammar as of v0.1.17
+/// ```text
+/// <function-declaration> ::= { <specifier> }+ <declarator> "(" <param-list> ")" ";"
+/// ```
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunDeclC {
+    pub identifier: Symbol,
+    pub params: Vec<Param>,
+    pub ret: CType,
+    pub storage: Option<StorageClass>,
+    pub span: Span,
+}
+
+impl Display for FunDeclC {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "FunDeclC with identifier ({}) and {} params",
+            self.identifier,
+            self.params.len()
+        )
+    }
+}
+
+/// A function parameter; its span takes in its type.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Param {
+    pub name: Symbol,
+    pub ty: CType,
+    pub span: Span,
+}
+
+/// Abstract C block, a scope of its own; its span takes in its braces.
+/// ### Abstract grammar as of v0.1.19
+/// ```text
+/// block = Block(block_item*)
+/// block_item = S(statement) | D(declaration) | Struct(struct_declaration)
+/// ```
+/// ### Concrete grammar as of v0.1.19
+/// ```text
+/// <block> ::= "{" { <block_item> } "}"
+/// <block_item> ::= <statement> | <declaration> | <struct-declaration>
+/// ```
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Block {
+    pub items: Vec<BlockItem>,
+    pub span: Span,
+}
 
-    if token == expected {
+impl Display for Block {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "block with inner items :")?;
+        for item in self.items.iter() {
+            write!(f, " {}", item)?;
+        }
         Ok(())
-    } else {
-        Err(ParseError::InvalidSyntax {
-            got: token,
-            expected,
-        })
     }
 }
 
-#[test]
-fn test_variant_error() {
-    let mut tokens = vec![Token::OpenBrace].into_iter();
-    let res = expect_variant(&mut tokens, Token::CloseBrace);
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlockItem {
+    Statement { stmt: StatementC },
+    Declaration { decl: Declaration },
+    Struct { decl: StructDeclaration },
+}
 
-    match res {
-        Ok(_) => assert!(false),
-        Err(e) => assert_eq!(
-            e,
-            ParseError::InvalidSyntax {
-                got: Token::OpenBrace,
-                expected: Token::CloseBrace
+impl Display for BlockItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Statement { stmt } => write!(f, "{}", stmt),
+            Self::Declaration { decl } => write!(f, "{}", decl),
+            Self::Struct { decl } => write!(f, "{}", decl),
+        }
+    }
+}
+
+/// Abstract C variable declaration
+/// ### Abstract grammar as of v0.1.12
+/// ```text
+/// declaration = Declaration(identifier name, type ty, exp? init,
+///                           storage_class? storage)
+/// ```
+/// ### Concrete grammar as of v0.1.17
+/// ```text
+/// <declaration> ::= { <specifier> }+ <init-declarator> { "," <init-declarator> } ";"
+/// <init-declarator> ::= <declarator> [ "=" <exp> ]
+/// ```
+/// A declaration of several variables is parsed as one of these apiece.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Declaration {
+    pub name: Symbol,
+    pub ty: CType,
+    pub init: Option<Box<Exp>>,
+    pub storage: Option<StorageClass>,
+    pub span: Span,
+}
+
+impl Display for Declaration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.storage {
+            Some(storage) => write!(f, "{} {} declaration of {}", storage, self.ty, self.name)?,
+            None => write!(f, "{} declaration of {}", self.ty, self.name)?,
+        }
+        match &self.init {
+            Some(init) => write!(f, " with init = {}", init),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Abstract C structure declaration; its span takes in the `;`.
+/// ### Abstract grammar as of v0.1.19
+/// ```text
+/// struct_declaration = StructDeclaration(identifier tag, member_declaration* members)
+/// member_declaration = MemberDeclaration(identifier name, type ty)
+/// ```
+/// ### Concrete grammar as of v0.1.19
+/// ```text
+/// <struct-declaration> ::= "struct" <identifier> [ "{" { <member-declaration> }+ "}" ] ";"
+/// <member-declaration> ::= { <type-specifier> }+ <declarator> ";"
+/// ```
+/// One with members defines the structure type `tag` names; one without only
+/// declares it, for pointers to it before it is defined.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StructDeclaration {
+    pub tag: Symbol,
+    pub members: Vec<MemberDeclaration>,
+    pub span: Span,
+}
+
+impl Display for StructDeclaration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "struct declaration of {} with {} members",
+            self.tag,
+            self.members.len()
+        )
+    }
+}
+
+/// A member of a structure as declared; its span takes in its type and the `;`.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemberDeclaration {
+    pub name: Symbol,
+    pub ty: CType,
+    pub span: Span,
+}
+
+/// Abstract C statement
+/// ### Abstract grammar as of v0.1.8
+/// ```text
+/// statement = Return(exp) | Expression(exp)
+///           | If(exp condition, statement then, statement? else)
+///           | Compound(block)
+///           | For(for_init, exp? condition, exp? post, statement body, identifier? label)
+///           | Break(identifier? label) | Continue(identifier? label)
+///           | Switch(exp control, statement body, identifier? label, switch_case* cases)
+///           | Case(exp value, statement body, identifier? label)
+///           | Default(statement body, identifier? label)
+///           | Null
+/// ```
+/// ### Concrete grammar as of v0.1.8
+/// ```text
+/// <statement> ::= "return" <exp> ";" | <exp> ";"
+///               | "if" "(" <exp> ")" <statement> [ "else" <statement> ]
+///               | <block>
+///               | "for" "(" <for_init> [ <exp> ] ";" [ <exp> ] ")" <statement>
+///               | "break" ";" | "continue" ";"
+///               | "switch" "(" <exp> ")" <statement>
+///               | "case" <exp> ":" <statement> | "default" ":" <statement>
+///               | ";"
+/// ```
+/// An `else` belongs to the nearest `if` that doesn't have one yet.
+/// A loop's `label` is `None` until semantic analysis names it, for the
+/// statements and labels that refer to the loop; a `break` or `continue` gets
+/// the label of the innermost loop around it at the same time, or for a
+/// `break`, of a `switch` inside that loop. A `switch` is named the same way,
+/// and gathers its `cases`, each `case` and `default` in its body named in
+/// turn, where TACKY jumps to on a match.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StatementC {
+    Return {
+        exp: Box<Exp>,
+        span: Span,
+    },
+    Expression {
+        exp: Box<Exp>,
+        span: Span,
+    },
+    If {
+        condition: Box<Exp>,
+        then_stmt: Box<StatementC>,
+        else_stmt: Option<Box<StatementC>>,
+        span: Span,
+    },
+    Compound {
+        block: Block,
+    },
+    For {
+        init: ForInit,
+        condition: Option<Box<Exp>>,
+        post: Option<Box<Exp>>,
+        body: Box<StatementC>,
+        label: Option<Symbol>,
+        span: Span,
+    },
+    Break {
+        label: Option<Symbol>,
+        span: Span,
+    },
+    Continue {
+        label: Option<Symbol>,
+        span: Span,
+    },
+    Switch {
+        control: Box<Exp>,
+        body: Box<StatementC>,
+        label: Option<Symbol>,
+        cases: Vec<SwitchCase>,
+        span: Span,
+    },
+    Case {
+        value: Box<Exp>,
+        body: Box<StatementC>,
+        label: Option<Symbol>,
+        span: Span,
+    },
+    Default {
+        body: Box<StatementC>,
+        label: Option<Symbol>,
+        span: Span,
+    },
+    Null {
+        span: Span,
+    },
+}
+
+impl Display for StatementC {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Return { exp, .. } => {
+                write!(f, "return expression with inner exp : {}", exp)
             }
-        ),
+            Self::Expression { exp, .. } => {
+                write!(f, "expression statement with inner exp : {}", exp)
+            }
+            Self::If {
+                condition,
+                then_stmt,
+                else_stmt,
+                ..
+            } => {
+                write!(
+                    f,
+                    "if statement with condition = {}, then = {}",
+                    condition, then_stmt
+                )?;
+                match else_stmt {
+                    Some(else_stmt) => write!(f, ", else = {}", else_stmt),
+                    None => Ok(()),
+                }
+            }
+            Self::Compound { block } => write!(f, "compound statement with inner {}", block),
+            Self::For {
+                init,
+                condition,
+                post,
+                body,
+                ..
+            } => {
+                write!(f, "for statement with init = {}", init)?;
+                if let Some(condition) = condition {
+                    write!(f, ", condition = {}", condition)?;
+                }
+                if let Some(post) = post {
+                    write!(f, ", post = {}", post)?;
+                }
+                write!(f, ", body = {}", body)
+            }
+            Self::Break { .. } => write!(f, "break statement"),
+            Self::Continue { .. } => write!(f, "continue statement"),
+            Self::Switch { control, body, .. } => {
+                write!(
+                    f,
+                    "switch statement with control = {}, body = {}",
+                    control, body
+                )
+            }
+            Self::Case { value, body, .. } => {
+                write!(f, "case with value = {}, body = {}", value, body)
+            }
+            Self::Default { body, .. } => write!(f, "default case with body = {}", body),
+            Self::Null { .. } => write!(f, "null statement"),
+        }
+    }
+}
+
+impl StatementC {
+    /// where in the source the statement was written.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Return { span, .. }
+            | Self::Expression { span, .. }
+            | Self::If { span, .. }
+            | Self::For { span, .. }
+            | Self::Break { span, .. }
+            | Self::Continue { span, .. }
+            | Self::Switch { span, .. }
+            | Self::Case { span, .. }
+            | Self::Default { span, .. }
+            | Self::Null { span } => *span,
+            Self::Compound { block } => block.span,
+        }
     }
 }
 
+/// Abstract C `for` loop initializer, which may declare a variable scoped to
+/// the loop.
+/// ### Abstract grammar as of v0.1.6
+/// ```text
+/// for_init = InitDecl(declaration) | InitExp(exp?)
+/// ```
+/// ### Concrete grammar as of v0.1.12
+/// ```text
+/// <for_init> ::= <declaration> | [ <exp> ] ";"
+/// ```
+/// The declaration takes type specifiers only. One of several variables is
+/// parsed as a block declaring them, around the loop without it.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ForInit {
+    Declaration { decl: Declaration },
+    Expression { exp: Option<Box<Exp>> },
+}
+
+impl Display for ForInit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Declaration { decl } => write!(f, "{}", decl),
+            Self::Expression { exp: Some(exp) } => write!(f, "{}", exp),
+            Self::Expression { exp: None } => write!(f, "nothing"),
+        }
+    }
+}
+
+/// A `case` or `default` a `switch` jumps to: the value it matches, `None` for
+/// the default, and the label it is named by. Only semantic analysis makes these.
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SwitchCase {
+    pub value: Option<Const>,
+    pub label: Symbol,
+}
+
+/// Resolved C expression unifying ExpC and FactorC symbols.
+/// A parenthesized expression's span takes in its parentheses.
+/// A `Cast` converts its inner expression to its `target` type; type checking
+/// makes every implicit conversion one, with the span of what it converts.
+/// `Dereference` is the object a pointer points to, and `AddrOf` a pointer to
+/// the object an lvalue designates. `Subscript` is the object `index` elements
+/// past where `exp` points, either of which may be the pointer. `String` is a
+/// string literal: an array of its bytes and a terminating null, with adjacent
+/// literals already concatenated. `Dot` is the member `member` of the
+/// structure `exp`, and `Arrow` the one of the structure `exp` points to.
+/// `Increment` adds one to the lvalue `exp` or takes one from it, giving its
+/// new value if the operator is written before it and its old one if after.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Exp {
+    Binary {
+        op: BinaryOp,
+        l_exp: Box<Exp>,
+        r_exp: Box<Exp>,
+        span: Span,
+    },
+    Const {
+        c: Const,
+        span: Span,
+    },
+    Unary {
+        op: UnaryOp,
+        exp: Box<Exp>,
+        span: Span,
+    },
+    Conditional {
+        condition: Box<Exp>,
+        then_exp: Box<Exp>,
+        else_exp: Box<Exp>,
+        span: Span,
+    },
+    Var {
+        name: Symbol,
+        span: Span,
+    },
+    Assignment {
+        lvalue: Box<Exp>,
+        rvalue: Box<Exp>,
+        span: Span,
+    },
+    CompoundAssignment {
+        op: BinaryOp,
+        lvalue: Box<Exp>,
+        rvalue: Box<Exp>,
+        span: Span,
+    },
+    FunctionCall {
+        name: Symbol,
+        args: Vec<Exp>,
+        span: Span,
+    },
+    Cast {
+        target: CType,
+        exp: Box<Exp>,
+        span: Span,
+    },
+    Dereference {
+        exp: Box<Exp>,
+        span: Span,
+    },
+    AddrOf {
+        exp: Box<Exp>,
+        span: Span,
+    },
+    Increment {
+        op: IncrementOp,
+        exp: Box<Exp>,
+        span: Span,
+    },
+    Subscript {
+        exp: Box<Exp>,
+        index: Box<Exp>,
+        span: Span,
+    },
+    String {
+        val: Vec<u8>,
+        span: Span,
+    },
+    Dot {
+        exp: Box<Exp>,
+        member: Symbol,
+        span: Span,
+    },
+    Arrow {
+        exp: Box<Exp>,
+        member: Symbol,
+        span: Span,
+    },
+}
+
+impl Display for Exp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exp::Binary {
+                op, l_exp, r_exp, ..
+            } => write!(
+                f,
+                "Binary expression with op = {}, l_exp = {}, r_exp = {}",
+                op, *l_exp, *r_exp
+            ),
+            Exp::Const { c, .. } => write!(f, "Constant expression with c = {}", c),
+            Exp::Unary { op, exp, .. } => {
+                write!(f, "Unary expression with op = {}, exp = {}", op, *exp)
+            }
+            Exp::Conditional {
+                condition,
+                then_exp,
+                else_exp,
+                ..
+            } => write!(
+                f,
+                "Conditional expression with condition = {}, then_exp = {}, else_exp = {}",
+                *condition, *then_exp, *else_exp
+            ),
+            Exp::Var { name, .. } => write!(f, "Variable expression with name = {}", name),
+            Exp::Assignment { lvalue, rvalue, .. } => write!(
+                f,
+                "Assignment expression with lvalue = {}, rvalue = {}",
+                *lvalue, *rvalue
+            ),
+            Exp::CompoundAssignment {
+                op, lvalue, rvalue, ..
+            } => write!(
+                f,
+                "Compound assignment expression with op = {}, lvalue = {}, rvalue = {}",
+                op, *lvalue, *rvalue
+            ),
+            Exp::FunctionCall { name, args, .. } => {
+                write!(f, "Function call expression with name = {}, args = [", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    match i {
+                        0 => write!(f, "{}", arg)?,
+                        _ => write!(f, ", {}", arg)?,
+                    }
+                }
+                write!(f, "]")
+            }
+            Exp::Cast { target, exp, .. } => {
+                write!(
+                    f,
+                    "Cast expression with target = {}, exp = {}",
+                    target, *exp
+                )
+            }
+            Exp::Dereference { exp, .. } => write!(f, "Dereference expression with exp = {}", *exp),
+            Exp::AddrOf { exp, .. } => write!(f, "Address-of expression with exp = {}", *exp),
+            Exp::Increment { op, exp, .. } => {
+                write!(f, "Increment expression with op = {}, exp = {}", op, *exp)
+            }
+            Exp::Subscript { exp, index, .. } => write!(
+                f,
+                "Subscript expression with exp = {}, index = {}",
+                *exp, *index
+            ),
+            Exp::String { val, .. } => {
+                write!(f, "String expression with val = \"{}\"", escape(val))
+            }
+            Exp::Dot { exp, member, .. } => {
+                write!(f, "Dot expression with exp = {}, member = {}", *exp, member)
+            }
+            Exp::Arrow { exp, member, .. } => write!(
+                f,
+                "Arrow expression with exp = {}, member = {}",
+                *exp, member
+            ),
+        }
+    }
+}
+
+impl Exp {
+    /// where in the source the expression was written.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Binary { span, .. }
+            | Self::Const { span, .. }
+            | Self::Unary { span, .. }
+            | Self::Conditional { span, .. }
+            | Self::Var { span, .. }
+            | Self::Assignment { span, .. }
+            | Self::CompoundAssignment { span, .. }
+            | Self::FunctionCall { span, .. }
+            | Self::Cast { span, .. }
+            | Self::Dereference { span, .. }
+            | Self::AddrOf { span, .. }
+            | Self::Increment { span, .. }
+            | Self::Subscript { span, .. }
+            | Self::String { span, .. }
+            | Self::Dot { span, .. }
+            | Self::Arrow { span, .. } => *span,
+        }
+    }
+
+    /// whether the expression designates an object, which can be assigned to
+    /// and have its address taken: a variable, what a pointer points to, a
+    /// string literal, or a member of a structure that is one of those.
+    pub fn is_lvalue(&self) -> bool {
+        match self {
+            Self::Dot { exp, .. } => exp.is_lvalue(),
+            _ => matches!(
+                self,
+                Self::Var { .. }
+                    | Self::Dereference { .. }
+                    | Self::Subscript { .. }
+                    | Self::String { .. }
+                    | Self::Arrow { .. }
+            ),
+        }
+    }
+
+    fn with_span(mut self, new: Span) -> Self {
+        match &mut self {
+            Self::Binary { span, .. }
+            | Self::Const { span, .. }
+            | Self::Unary { span, .. }
+            | Self::Conditional { span, .. }
+            | Self::Var { span, .. }
+            | Self::Assignment { span, .. }
+            | Self::CompoundAssignment { span, .. }
+            | Self::FunctionCall { span, .. }
+            | Self::Cast { span, .. }
+            | Self::Dereference { span, .. }
+            | Self::AddrOf { span, .. }
+            | Self::Increment { span, .. }
+            | Self::Subscript { span, .. }
+            | Self::String { span, .. }
+            | Self::Dot { span, .. }
+            | Self::Arrow { span, .. } => *span = new,
+        }
+        self
+    }
+
+    fn from_expc(expc: ExpC) -> Self {
+        match expc {
+            ExpC::Factor { fac } => Self::from_factc(*fac),
+            ExpC::Binary {
+                op, l_exp, r_exp, ..
+            } => {
+                let l_exp = Self::from_expc(*l_exp);
+                let r_exp = Self::from_expc(*r_exp);
+                Self::Binary {
+                    op,
+                    span: l_exp.span().to(r_exp.span()),
+                    l_exp: Box::new(l_exp),
+                    r_exp: Box::new(r_exp),
+                }
+            }
+            ExpC::Conditional {
+                condition,
+                then_exp,
+                else_exp,
+                ..
+            } => {
+                let condition = Self::from_expc(*condition);
+                let else_exp = Self::from_expc(*else_exp);
+                Self::Conditional {
+                    span: condition.span().to(else_exp.span()),
+                    condition: Box::new(condition),
+                    then_exp: Box::new(Self::from_expc(*then_exp)),
+                    else_exp: Box::new(else_exp),
+                }
+            }
+            ExpC::Assignment { lvalue, rvalue, .. } => {
+                let lvalue = Self::from_expc(*lvalue);
+                let rvalue = Self::from_expc(*rvalue);
+                Self::Assignment {
+                    span: lvalue.span().to(rvalue.span()),
+                    lvalue: Box::new(lvalue),
+                    rvalue: Box::new(rvalue),
+                }
+            }
+            ExpC::CompoundAssignment {
+                op, lvalue, rvalue, ..
+            } => {
+                let lvalue = Self::from_expc(*lvalue);
+                let rvalue = Self::from_expc(*rvalue);
+                Self::CompoundAssignment {
+                    op,
+                    span: lvalue.span().to(rvalue.span()),
+                    lvalue: Box::new(lvalue),
+                    rvalue: Box::new(rvalue),
+                }
+            }
+        }
+    }
+    fn from_factc(factc: FactorC) -> Self {
+        match factc {
+            FactorC::Const { c, span } => Self::Const { c, span },
+            FactorC::Var { name, span } => Self::Var { name, span },
+            FactorC::String { val, span } => Self::String { val, span },
+            FactorC::Unary { op, fac, span } => Self::Unary {
+                op,
+                exp: Box::new(Self::from_factc(*fac)),
+                span,
+            },
+            FactorC::Dereference { fac, span } => Self::Dereference {
+                exp: Box::new(Self::from_factc(*fac)),
+                span,
+            },
+            FactorC::AddrOf { fac, span } => Self::AddrOf {
+                exp: Box::new(Self::from_factc(*fac)),
+                span,
+            },
+            FactorC::Increment { op, fac, span } => Self::Increment {
+                op,
+                exp: Box::new(Self::from_factc(*fac)),
+                span,
+            },
+            FactorC::Subscript { fac, index, span } => Self::Subscript {
+                exp: Box::new(Self::from_factc(*fac)),
+                index: Box::new(Self::from_expc(*index)),
+                span,
+            },
+            FactorC::Dot { fac, member, span } => Self::Dot {
+                exp: Box::new(Self::from_factc(*fac)),
+                member,
+                span,
+            },
+            FactorC::Arrow { fac, member, span } => Self::Arrow {
+                exp: Box::new(Self::from_factc(*fac)),
+                member,
+                span,
+            },
+            FactorC::Cast { target, fac, span } => Self::Cast {
+                target,
+                exp: Box::new(Self::from_factc(*fac)),
+                span,
+            },
+            FactorC::Exp { exp, span } => Self::from_expc(*exp).with_span(span),
+            FactorC::FunctionCall { name, args, span } => Self::FunctionCall {
+                name,
+                args: args.into_iter().map(Self::from_expc).collect(),
+                span,
+            },
+        }
+    }
+}
+
+/// Abstract C expression
+/// ### Abstract grammar as of v0.1.20
+/// ```text
+/// exp = Factor(factor) | Binary(binary_operator, l_exp, r_exp)
+///     | Conditional(exp condition, exp then, exp else)
+///     | Assignment(exp lvalue, exp rvalue)
+///     | CompoundAssignment(binary_operator, exp lvalue, exp rvalue)
+/// ```
+/// ### Concrete grammar as of v0.1.22
+/// ```text
+/// <exp> ::= <factor> | <exp> <binop> <exp> | <exp> "?" <exp> ":" <exp>
+///         | <exp> <assign-op> <exp>
+/// <assign-op> ::= "=" | "+=" | "-=" | "*=" | "/=" | "%=" | "&=" | "|=" | "^="
+///               | "<<=" | ">>="
+/// ```
+/// `?` binds looser than every binary operator, and the assignments looser
+/// still; both group to the right. Whether the left of an assignment can be
+/// assigned to is left to semantic analysis.
+#[derive(PartialEq, Debug)]
+enum ExpC {
+    Factor {
+        fac: Box<FactorC>,
+    },
+    Binary {
+        op: BinaryOp,
+        l_exp: Box<ExpC>,
+        r_exp: Box<ExpC>,
+        height: usize,
+    },
+    Conditional {
+        condition: Box<ExpC>,
+        then_exp: Box<ExpC>,
+        else_exp: Box<ExpC>,
+        height: usize,
+    },
+    Assignment {
+        lvalue: Box<ExpC>,
+        rvalue: Box<ExpC>,
+        height: usize,
+    },
+    CompoundAssignment {
+        op: BinaryOp,
+        lvalue: Box<ExpC>,
+        rvalue: Box<ExpC>,
+        height: usize,
+    },
+}
+
+impl Display for ExpC {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Factor { fac } => write!(f, "Factor expression with inner fac = {}", *fac),
+            Self::Binary {
+                op, l_exp, r_exp, ..
+            } => write!(
+                f,
+                "Binary expression with binop = {}, l_exp = {}, r_exp = {}",
+                op, *l_exp, *r_exp
+            ),
+            Self::Conditional {
+                condition,
+                then_exp,
+                else_exp,
+                ..
+            } => write!(
+                f,
+                "Conditional expression with condition = {}, then_exp = {}, else_exp = {}",
+                *condition, *then_exp, *else_exp
+            ),
+            Self::Assignment { lvalue, rvalue, .. } => write!(
+                f,
+                "Assignment expression with lvalue = {}, rvalue = {}",
+                *lvalue, *rvalue
+            ),
+            Self::CompoundAssignment {
+                op, lvalue, rvalue, ..
+            } => write!(
+                f,
+                "Compound assignment expression with op = {}, lvalue = {}, rvalue = {}",
+                op, *lvalue, *rvalue
+            ),
+        }
+    }
+}
+
+impl ExpC {
+    /// the number of nodes on the longest path down the expression's tree.
+    fn height(&self) -> usize {
+        match self {
+            Self::Factor { fac } => fac.height(),
+            Self::Binary { height, .. }
+            | Self::Conditional { height, .. }
+            | Self::Assignment { height, .. }
+            | Self::CompoundAssignment { height, .. } => *height,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    LeftShift,
+    RightShift,
+    LessThan,
+    GreaterThan,
+    LessOrEqual,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add => write!(f, "Add"),
+            Self::Subtract => write!(f, "Subtract"),
+            Self::Multiply => write!(f, "Multiply"),
+            Self::Divide => write!(f, "Divide"),
+            Self::Remainder => write!(f, "Remainder"),
+            Self::BitwiseAnd => write!(f, "Bitwise 'and'"),
+            Self::BitwiseOr => write!(f, "Bitwise 'or'"),
+            Self::BitwiseXor => write!(f, "Bitwise 'xor'"),
+            Self::LeftShift => write!(f, "Left shift"),
+            Self::RightShift => write!(f, "Right shift"),
+            Self::LessThan => write!(f, "Less than"),
+            Self::GreaterThan => write!(f, "Greater than"),
+            Self::LessOrEqual => write!(f, "Less or equal"),
+            Self::GreaterOrEqual => write!(f, "Greater or equal"),
+            Self::Equal => write!(f, "Equal"),
+            Self::NotEqual => write!(f, "Not equal"),
+            Self::And => write!(f, "Logical 'and'"),
+            Self::Or => write!(f, "Logical 'or'"),
+        }
+    }
+}
+
+impl BinaryOp {
+    /// the operator as written in C source.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::Remainder => "%",
+            Self::BitwiseAnd => "&",
+            Self::BitwiseOr => "|",
+            Self::BitwiseXor => "^",
+            Self::LeftShift => "<<",
+            Self::RightShift => ">>",
+            Self::LessThan => "<",
+            Self::GreaterThan => ">",
+            Self::LessOrEqual => "<=",
+            Self::GreaterOrEqual => ">=",
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::And => "&&",
+            Self::Or => "||",
+        }
+    }
+
+    /// whether the operator compares its operands, giving an `int` whatever
+    /// their type.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Self::LessThan
+                | Self::GreaterThan
+                | Self::LessOrEqual
+                | Self::GreaterOrEqual
+                | Self::Equal
+                | Self::NotEqual
+        )
+    }
+
+    /// the compound assignment operator that carries out the operation as
+    /// written in C source, or `=` for one that no compound assignment does.
+    pub fn assignment_symbol(&self) -> &'static str {
+        match self {
+            Self::Add => "+=",
+            Self::Subtract => "-=",
+            Self::Multiply => "*=",
+            Self::Divide => "/=",
+            Self::Remainder => "%=",
+            Self::BitwiseAnd => "&=",
+            Self::BitwiseOr => "|=",
+            Self::BitwiseXor => "^=",
+            Self::LeftShift => "<<=",
+            Self::RightShift => ">>=",
+            _ => "=",
+        }
+    }
+
+    /// the operation the compound assignment operator `token` carries out.
+    fn compound(token: &Token) -> Option<Self> {
+        match token {
+            Token::PlusEqual => Some(Self::Add),
+            Token::MinusEqual => Some(Self::Subtract),
+            Token::AsteriskEqual => Some(Self::Multiply),
+            Token::FSlashEqual => Some(Self::Divide),
+            Token::PercentEqual => Some(Self::Remainder),
+            Token::AmpersandEqual => Some(Self::BitwiseAnd),
+            Token::PipeEqual => Some(Self::BitwiseOr),
+            Token::CaretEqual => Some(Self::BitwiseXor),
+            Token::LessLessEqual => Some(Self::LeftShift),
+            Token::GreaterGreaterEqual => Some(Self::RightShift),
+            _ => None,
+        }
+    }
+
+    fn from(token: Token, span: Span) -> ParseResult<Self> {
+        match token {
+            Token::Plus => Ok(Self::Add),
+            Token::Minus => Ok(Self::Subtract),
+            Token::Asterisk => Ok(Self::Multiply),
+            Token::FSlash => Ok(Self::Divide),
+            Token::Percent => Ok(Self::Remainder),
+            Token::Ampersand => Ok(Self::BitwiseAnd),
+            Token::Pipe => Ok(Self::BitwiseOr),
+            Token::Caret => Ok(Self::BitwiseXor),
+            Token::LessLess => Ok(Self::LeftShift),
+            Token::GreaterGreater => Ok(Self::RightShift),
+            Token::Less => Ok(Self::LessThan),
+            Token::Greater => Ok(Self::GreaterThan),
+            Token::LessEqual => Ok(Self::LessOrEqual),
+            Token::GreaterEqual => Ok(Self::GreaterOrEqual),
+            Token::EqualEqual => Ok(Self::Equal),
+            Token::BangEqual => Ok(Self::NotEqual),
+            Token::AmpersandAmpersand => Ok(Self::And),
+            Token::PipePipe => Ok(Self::Or),
+            _ => Err(ParseError::InvalidSyntax {
+                got: token,
+                expected: String::from("a binary operator"),
+                span,
+            }),
+        }
+    }
+    /// how tightly the operator starting with `token` binds; the assignments
+    /// and `?` are not binary operators, but take part in precedence climbing
+    /// as the loosest of them.
+    fn token_prec(token: &Token) -> u8 {
+        match token {
+            Token::Equal
+            | Token::PlusEqual
+            | Token::MinusEqual
+            | Token::AsteriskEqual
+            | Token::FSlashEqual
+            | Token::PercentEqual
+            | Token::AmpersandEqual
+            | Token::PipeEqual
+            | Token::CaretEqual
+            | Token::LessLessEqual
+            | Token::GreaterGreaterEqual => 0,
+            Token::Question => 1,
+            Token::PipePipe => 2,
+            Token::AmpersandAmpersand => 3,
+            Token::Pipe => 4,
+            Token::Caret => 5,
+            Token::Ampersand => 6,
+            Token::EqualEqual | Token::BangEqual => 7,
+            Token::Less | Token::Greater | Token::LessEqual | Token::GreaterEqual => 8,
+            Token::LessLess | Token::GreaterGreater => 9,
+            Token::Plus | Token::Minus => 10,
+            Token::Asterisk | Token::FSlash | Token::Percent => 11,
+            _ => 255,
+        }
+    }
+}
+
+/// Factor. Same ADT type as an expression, but allows for mutual recursion and precedence climbing.
+/// ### Formal Grammar as of v0.1.23
+/// ```text
+/// <factor> ::= <primary> { <postfix> } | <unop> <factor>
+///            | "*" <factor> | "&" <factor> | "++" <factor> | "--" <factor>
+///            | "(" { <type-specifier> }+ { "*" } ")" <factor>
+/// <postfix> ::= "[" <exp> "]" | "." <identifier> | "->" <identifier>
+///             | "++" | "--"
+/// <primary> ::= <const> | <identifier> | "(" <exp> ")" | <string> { <string> }
+///             | <identifier> "(" [ <argument-list> ] ")"
+/// <argument-list> ::= <exp> { "," <exp> }
+/// <const> ::= <int> | <long> | <uint> | <ulong> | <double> | <char>
+/// ```
+/// Subscripts, member accesses and postfix `++` and `--` bind tighter than
+/// the prefix operators, so `*a[1]` is `*(a[1])`, `&p->x` is `&(p->x)` and
+/// `*p++` is `*(p++)`. A cast is a prefix operator too: `(long)a[1]` converts
+/// the element, and `(long)x + y` converts only `x`.
+/// A character constant is an `int`, and adjacent string literals are one.
+#[derive(PartialEq, Debug)]
+enum FactorC {
+    Const {
+        c: Const,
+        span: Span,
+    },
+    Var {
+        name: Symbol,
+        span: Span,
+    },
+    Unary {
+        op: UnaryOp,
+        fac: Box<FactorC>,
+        span: Span,
+    },
+    Exp {
+        exp: Box<ExpC>,
+        span: Span,
+    },
+    FunctionCall {
+        name: Symbol,
+        args: Vec<ExpC>,
+        span: Span,
+    },
+    Dereference {
+        fac: Box<FactorC>,
+        span: Span,
+    },
+    AddrOf {
+        fac: Box<FactorC>,
+        span: Span,
+    },
+    Increment {
+        op: IncrementOp,
+        fac: Box<FactorC>,
+        span: Span,
+    },
+    Subscript {
+        fac: Box<FactorC>,
+        index: Box<ExpC>,
+        span: Span,
+    },
+    String {
+        val: Vec<u8>,
+        span: Span,
+    },
+    Dot {
+        fac: Box<FactorC>,
+        member: Symbol,
+        span: Span,
+    },
+    Arrow {
+        fac: Box<FactorC>,
+        member: Symbol,
+        span: Span,
+    },
+    Cast {
+        target: CType,
+        fac: Box<FactorC>,
+        span: Span,
+    },
+}
+
+impl Display for FactorC {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Const { c, .. } => write!(f, "constant factor with c = {}", c),
+            Self::Var { name, .. } => write!(f, "variable factor with name = {}", name),
+            Self::Unary { op, fac, .. } => {
+                write!(f, "unary factor with unop = {}, fac = {}", op, *fac)
+            }
+            Self::Exp { exp, .. } => write!(f, "expression factor with exp = {}", *exp),
+            Self::FunctionCall { name, args, .. } => write!(
+                f,
+                "function call factor with name = {} and {} args",
+                name,
+                args.len()
+            ),
+            Self::Dereference { fac, .. } => write!(f, "dereference factor with fac = {}", *fac),
+            Self::AddrOf { fac, .. } => write!(f, "address-of factor with fac = {}", *fac),
+            Self::Increment { op, fac, .. } => {
+                write!(f, "increment factor with op = {}, fac = {}", op, *fac)
+            }
+            Self::Subscript { fac, index, .. } => write!(
+                f,
+                "subscript factor with fac = {}, index = {}",
+                *fac, *index
+            ),
+            Self::String { val, .. } => write!(f, "string factor with val = \"{}\"", escape(val)),
+            Self::Dot { fac, member, .. } => {
+                write!(f, "dot factor with fac = {}, member = {}", *fac, member)
+            }
+            Self::Arrow { fac, member, .. } => {
+                write!(f, "arrow factor with fac = {}, member = {}", *fac, member)
+            }
+            Self::Cast { target, fac, .. } => {
+                write!(f, "cast factor with target = {}, fac = {}", target, *fac)
+            }
+        }
+    }
+}
+
+impl FactorC {
+    /// as for ExpC; parentheses add no node of their own.
+    fn height(&self) -> usize {
+        match self {
+            Self::Const { .. } | Self::Var { .. } | Self::String { .. } => 1,
+            Self::Unary { fac, .. }
+            | Self::Dereference { fac, .. }
+            | Self::AddrOf { fac, .. }
+            | Self::Increment { fac, .. }
+            | Self::Dot { fac, .. }
+            | Self::Arrow { fac, .. }
+            | Self::Cast { fac, .. } => 1 + fac.height(),
+            Self::Exp { exp, .. } => exp.height(),
+            Self::FunctionCall { args, .. } => 1 + args.iter().map(ExpC::height).max().unwrap_or(0),
+            Self::Subscript { fac, index, .. } => 1 + fac.height().max(index.height()),
+        }
+    }
+}
+
+/// Abstract C unary operation.
+/// - `~`: bitwise complement
+/// - `-`: integer negation
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnaryOp {
+    Negate,
+    BitwiseComplement,
+}
+
+impl Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Negate => write!(f, "UnaryOp::Negate"),
+            Self::BitwiseComplement => write!(f, "UnaryOp::BitwiseComplement"),
+        }
+    }
+}
+
+impl UnaryOp {
+    /// the operator as written in C source.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Negate => "-",
+            Self::BitwiseComplement => "~",
+        }
+    }
+}
+
+/// Abstract C increment or decrement.
+/// - `++x`, `--x`: the new value
+/// - `x++`, `x--`: the old value
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IncrementOp {
+    PreIncrement,
+    PreDecrement,
+    PostIncrement,
+    PostDecrement,
+}
+
+impl Display for IncrementOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PreIncrement => write!(f, "IncrementOp::PreIncrement"),
+            Self::PreDecrement => write!(f, "IncrementOp::PreDecrement"),
+            Self::PostIncrement => write!(f, "IncrementOp::PostIncrement"),
+            Self::PostDecrement => write!(f, "IncrementOp::PostDecrement"),
+        }
+    }
+}
+
+impl IncrementOp {
+    /// the operator as written in C source.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::PreIncrement | Self::PostIncrement => "++",
+            Self::PreDecrement | Self::PostDecrement => "--",
+        }
+    }
+
+    /// whether the operator is written after its operand, and gives its old
+    /// value.
+    pub fn is_postfix(&self) -> bool {
+        matches!(self, Self::PostIncrement | Self::PostDecrement)
+    }
+
+    /// the operation carried out on the operand and one.
+    pub fn binary(&self) -> BinaryOp {
+        match self {
+            Self::PreIncrement | Self::PostIncrement => BinaryOp::Add,
+            Self::PreDecrement | Self::PostDecrement => BinaryOp::Subtract,
+        }
+    }
+}
+
+/// Big scary parse function.
+/// As of v0.1.11, a thin wrapper over parse_top_level.
+/// Recovers from errors in panic mode, skipping to a point where parsing can
+/// pick up again, so one run reports every independent error, in source order.
+pub fn parse(tokens: impl IntoIterator<Item = SpannedToken>) -> Result<ProgramC, Vec<ParseError>> {
+    let mut tokens = TokenStream::new(tokens.into_iter());
+    let mut items = Vec::new();
+    while tokens.peek().is_some() {
+        match parse_top_level(&mut tokens) {
+            Ok(item) => items.extend(item),
+            Err(e) => {
+                tokens.report(e);
+                tokens.synchronize_to_fundef();
+            }
+        }
+    }
+    match tokens.errors.is_empty() {
+        true => Ok(ProgramC { items }),
+        false => Err(tokens.errors),
+    }
+}
+
+/// Expects a function definition, a declaration of one, or a variable
+/// declaration, told apart by what follows the name; or a structure
+/// declaration, which has none. A declaration of several variables is one
+/// item per variable.
+/// If this isn't found, returns an error. Errors in a definition's body are
+/// recovered from item by item, so a function is returned even then, though
+/// the program it is part of won't be.
+fn parse_top_level(tokens: &mut TokenStream<'_>) -> ParseResult<Vec<TopLevelC>> {
+    let first = expect_token(tokens, "a function definition")?;
+    if !is_specifier(&first.token) {
+        return Err(ParseError::FundefError {
+            reason: String::from(
+                "expected a function definition but first token was not a valid return type",
+            ),
+            span: first.span,
+        });
+    }
+    let (ty, storage, ty_span) = parse_specifiers(tokens, first)?;
+    if let Some(tag) = declared_struct(tokens, ty, storage) {
+        return Ok(vec![TopLevelC::Struct {
+            decl: parse_struct_declaration(tokens, tag, ty_span)?,
+        }]);
+    }
+    let declarator_ty = parse_pointers(tokens, ty);
+
+    let id_attempt = expect_token(tokens, "a function name")?;
+    let identifier = if let Token::Identifier { val } = id_attempt.token {
+        val
+    } else {
+        return Err(ParseError::InvalidIdentifier {
+            wrong_id: id_attempt.token,
+            span: id_attempt.span,
+        });
+    };
+
+    let Some(open_parens) = tokens.next_if(|t| *t == Token::OpenParens) else {
+        let decls = parse_variables(tokens, (identifier, declarator_ty), ty, storage, ty_span)?;
+        return Ok(decls
+            .into_iter()
+            .map(|decl| TopLevelC::Variable { decl })
+            .collect());
+    };
+    let ty = declarator_ty;
+    let open_parens = open_parens.span;
+    let params = parse_params(tokens)?;
+    expect_closing(tokens, Token::CloseParens, Token::OpenParens, open_parens)?;
+    if let Some(semicolon) = tokens.next_if(|t| *t == Token::Semicolon) {
+        return Ok(vec![TopLevelC::Declaration {
+            decl: FunDeclC {
+                identifier,
+                params,
+                ret: ty,
+                storage,
+                span: ty_span.to(semicolon.span),
+            },
+        }]);
+    }
+    let open_brace = expect_variant(tokens, Token::OpenBrace).map_err(|error| match error {
+        ParseError::InvalidSyntax { got, span, .. } => ParseError::InvalidSyntax {
+            got,
+            expected: String::from("'{' or ';'"),
+            span,
+        },
+        error => error,
+    })?;
+    let body = parse_block(tokens, open_brace)?;
+
+    Ok(vec![TopLevelC::Function {
+        fundef: FunDefC {
+            identifier,
+            params,
+            ret: ty,
+            storage,
+            span: ty_span.to(body.span),
+            body,
+        },
+    }])
+}
+
+/// whether `token` can start a declaration.
+fn is_specifier(token: &Token) -> bool {
+    is_type_specifier(token) || matches!(token, Token::StaticKeyword | Token::ExternKeyword)
+}
+
+/// whether `token` names a type, or part of one.
+fn is_type_specifier(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::TyKeyword {
+            ty: Type::Int
+                | Type::Long
+                | Type::Short
+                | Type::Char
+                | Type::Signed
+                | Type::Unsigned
+                | Type::Double
+                | Type::Bool
+        } | Token::StructKeyword
+    )
+}
+
+/// The type specifiers seen so far in a declaration, each allowed once, and
+/// the tag of the structure type they name, if they do.
+#[derive(Default)]
+struct TypeSpecifiers {
+    int: bool,
+    long: bool,
+    short: bool,
+    char: bool,
+    signed: bool,
+    unsigned: bool,
+    double: bool,
+    bool: bool,
+    structure: Option<Symbol>,
+}
+
+impl TypeSpecifiers {
+    /// takes in the type specifier `specifier`, unless it repeats one already
+    /// seen or can't go with one: `long` and `short` don't go together, `char`
+    /// goes only with `signed` or `unsigned`, and `double` and `_Bool` with none
+    /// of the others.
+    fn add(&mut self, specifier: &SpannedToken) -> ParseResult<()> {
+        let clash = match specifier.token {
+            _ if self.structure.is_some() => {
+                Some("two or more data types in declaration specifiers")
+            }
+            Token::TyKeyword { ty: Type::Signed } if self.unsigned => {
+                Some("both 'signed' and 'unsigned' in declaration specifiers")
+            }
+            Token::TyKeyword { ty: Type::Unsigned } if self.signed => {
+                Some("both 'signed' and 'unsigned' in declaration specifiers")
+            }
+            Token::TyKeyword { ty: Type::Long } if self.short => {
+                Some("both 'long' and 'short' in declaration specifiers")
+            }
+            Token::TyKeyword { ty: Type::Short } if self.long => {
+                Some("both 'long' and 'short' in declaration specifiers")
+            }
+            Token::TyKeyword {
+                ty: Type::Int | Type::Long | Type::Short,
+            } if self.char => Some("two or more data types in declaration specifiers"),
+            Token::TyKeyword { ty: Type::Char } if self.int || self.long || self.short => {
+                Some("two or more data types in declaration specifiers")
+            }
+            Token::TyKeyword {
+                ty: Type::Double | Type::Bool,
+            } if self.int
+                || self.long
+                || self.short
+                || self.char
+                || self.signed
+                || self.unsigned
+                || self.double
+                || self.bool =>
+            {
+                Some("two or more data types in declaration specifiers")
+            }
+            Token::TyKeyword {
+                ty: Type::Double | Type::Bool,
+            } => None,
+            _ if self.double || self.bool => {
+                Some("two or more data types in declaration specifiers")
+            }
+            _ => None,
+        };
+        let (seen, reason) = match specifier.token {
+            Token::TyKeyword { ty: Type::Long } => (&mut self.long, "'long long' is not supported"),
+            Token::TyKeyword { ty: Type::Short } => (&mut self.short, "duplicate 'short'"),
+            Token::TyKeyword { ty: Type::Char } => (&mut self.char, "duplicate 'char'"),
+            Token::TyKeyword { ty: Type::Signed } => (&mut self.signed, "duplicate 'signed'"),
+            Token::TyKeyword { ty: Type::Unsigned } => (&mut self.unsigned, "duplicate 'unsigned'"),
+            Token::TyKeyword { ty: Type::Double } => (
+                &mut self.double,
+                "two or more data types in declaration specifiers",
+            ),
+            Token::TyKeyword { ty: Type::Bool } => (&mut self.bool, "duplicate '_Bool'"),
+            _ => (
+                &mut self.int,
+                "two or more data types in declaration specifiers",
+            ),
+        };
+        let reason = match (*seen, clash) {
+            (true, _) => reason,
+            (false, Some(clash)) => clash,
+            (false, None) => {
+                *seen = true;
+                return Ok(());
+            }
+        };
+        Err(ParseError::InvalidSpecifiers {
+            reason: String::from(reason),
+            span: specifier.span,
+        })
+    }
+
+    /// takes in the structure type `tag` names, written at `span`, which goes
+    /// with no other type specifier.
+    fn add_struct(&mut self, tag: Symbol, span: Span) -> ParseResult<()> {
+        if self.int
+            || self.long
+            || self.short
+            || self.char
+            || self.signed
+            || self.unsigned
+            || self.double
+            || self.bool
+            || self.structure.is_some()
+        {
+            return Err(ParseError::InvalidSpecifiers {
+                reason: String::from("two or more data types in declaration specifiers"),
+                span,
+            });
+        }
+        self.structure = Some(tag);
+        Ok(())
+    }
+
+    /// takes in the type specifier `specifier`, and the tag after it if it is
+    /// `struct`, returning the span of both.
+    fn take(
+        &mut self,
+        tokens: &mut TokenStream<'_>,
+        specifier: &SpannedToken,
+    ) -> ParseResult<Span> {
+        if specifier.token != Token::StructKeyword {
+            self.add(specifier)?;
+            return Ok(specifier.span);
+        }
+        let got = expect_token(tokens, "a structure tag")?;
+        let Token::Identifier { val: tag } = got.token else {
+            return Err(ParseError::InvalidSyntax {
+                got: got.token,
+                expected: String::from("a structure tag"),
+                span: got.span,
+            });
+        };
+        let span = specifier.span.to(got.span);
+        self.add_struct(tag, span)?;
+        Ok(span)
+    }
+
+    /// the type the specifiers name together: a `char` is plain, signed or
+    /// unsigned as it says; otherwise the width is that of `long` or `short` if
+    /// there is one among them, and unsigned if there is an `unsigned`. `span`
+    /// takes in all of them, for when there is no type at all.
+    fn ty(&self, span: Span) -> ParseResult<CType> {
+        if let Some(tag) = self.structure {
+            return Ok(CType::Struct { tag });
+        }
+        if self.double {
+            return Ok(CType::Double);
+        }
+        if self.bool {
+            return Ok(CType::Bool);
+        }
+        if self.char {
+            return Ok(match (self.signed, self.unsigned) {
+                (true, _) => CType::SChar,
+                (_, true) => CType::UChar,
+                _ => CType::Char,
+            });
+        }
+        if !(self.int || self.long || self.short || self.signed || self.unsigned) {
+            return Err(ParseError::InvalidSpecifiers {
+                reason: String::from("expected a type in declaration specifiers"),
+                span,
+            });
+        }
+        Ok(match (self.long, self.short, self.unsigned) {
+            (true, _, false) => CType::Long,
+            (true, _, true) => CType::ULong,
+            (_, true, false) => CType::Short,
+            (_, true, true) => CType::UShort,
+            (false, false, false) => CType::Int,
+            (false, false, true) => CType::UInt,
+        })
+    }
+}
+
+/// parses the specifiers of a declaration, starting with `first`, already taken:
+/// the type specifiers `TypeSpecifiers` takes, and at most one storage class,
+/// in any order. Returns
+/// the type, the storage class and the span of all the specifiers.
+fn parse_specifiers(
+    tokens: &mut TokenStream<'_>,
+    first: SpannedToken,
+) -> ParseResult<(CType, Option<StorageClass>, Span)> {
+    let mut span = first.span;
+    let (mut types, mut storage) = (TypeSpecifiers::default(), None);
+    let mut next = Some(first);
+    while let Some(specifier) = next {
+        span = span.to(specifier.span);
+        match specifier.token {
+            Token::TyKeyword { .. } | Token::StructKeyword => {
+                span = span.to(types.take(tokens, &specifier)?)
+            }
+            Token::StaticKeyword | Token::ExternKeyword if storage.is_some() => {
+                return Err(ParseError::InvalidSpecifiers {
+                    reason: String::from("multiple storage classes in declaration specifiers"),
+                    span: specifier.span,
+                })
+            }
+            Token::StaticKeyword => storage = Some(StorageClass::Static),
+            _ => storage = Some(StorageClass::Extern),
+        }
+        next = tokens.next_if(is_specifier);
+    }
+    Ok((types.ty(span)?, storage, span))
+}
+
+/// parses the type specifiers of a parameter or a `for` loop's declaration,
+/// which can't have a storage class, starting with `first`, already taken.
+/// Returns the type and the span of all the specifiers.
+fn parse_type_specifiers(
+    tokens: &mut TokenStream<'_>,
+    first: SpannedToken,
+) -> ParseResult<(CType, Span)> {
+    let mut span = first.span;
+    let mut types = TypeSpecifiers::default();
+    let mut next = Some(first);
+    while let Some(specifier) = next {
+        span = span.to(types.take(tokens, &specifier)?);
+        next = tokens.next_if(is_type_specifier);
+    }
+    Ok((types.ty(span)?, span))
+}
+
+/// takes the `*`s of a declarator, before its name, each making `ty` a pointer
+/// to what it was.
+fn parse_pointers(tokens: &mut TokenStream<'_>, mut ty: CType) -> CType {
+    while tokens.next_if(|t| *t == Token::Asterisk).is_some() {
+        ty = ty.pointer_to();
+    }
+    ty
+}
+
+/// takes the `[n]`s of a declarator, after its name, making `ty` an array of
+/// what the ones after each make it. Each size must be a positive integer
+/// constant, and the whole array must fit in the 2GiB a stack offset can reach.
+fn parse_dimensions(tokens: &mut TokenStream<'_>, ty: CType) -> ParseResult<CType> {
+    let mut sizes = Vec::new();
+    while let Some(open_bracket) = tokens.next_if(|t| *t == Token::OpenBracket) {
+        let got = expect_token(tokens, "an array size")?;
+        let size = match got.token {
+            Token::Constant { val } | Token::LongConstant { val } => u64::try_from(val).ok(),
+            Token::UnsignedConstant { val } | Token::UnsignedLongConstant { val } => Some(val),
+            _ => {
+                return Err(ParseError::InvalidSyntax {
+                    got: got.token,
+                    expected: String::from("an integer constant array size"),
+                    span: got.span,
+                })
+            }
+        };
+        let close = expect_closing(
+            tokens,
+            Token::CloseBracket,
+            Token::OpenBracket,
+            open_bracket.span,
+        )?;
+        sizes.push((size, open_bracket.span.to(close)));
+    }
+    let mut ty = ty;
+    for (size, span) in sizes.into_iter().rev() {
+        let too_large = || ParseError::InvalidArraySize {
+            reason: String::from("array is too large"),
+            span,
+        };
+        ty = match size.and_then(|size| u32::try_from(size).ok()) {
+            Some(0) => {
+                return Err(ParseError::InvalidArraySize {
+                    reason: String::from("array size must be positive"),
+                    span,
+                })
+            }
+            Some(size) => match ty.size().checked_mul(size as usize) {
+                Some(bytes) if i32::try_from(bytes).is_ok() => ty.array_of(size),
+                _ => return Err(too_large()),
+            },
+            None => return Err(too_large()),
+        };
+    }
+    Ok(ty)
+}
+
+/// Expects a parameter list, `void` for none; stops before the closing parenthesis.
+fn parse_params(tokens: &mut TokenStream<'_>) -> ParseResult<Vec<Param>> {
+    if tokens
+        .next_if(|t| *t == Token::TyKeyword { ty: Type::Void })
+        .is_some()
+    {
+        return Ok(Vec::new());
+    }
+    let mut params = Vec::new();
+    loop {
+        let first = expect_token(tokens, "a parameter list")?;
+        if !is_type_specifier(&first.token) {
+            return Err(ParseError::InvalidSyntax {
+                got: first.token,
+                expected: String::from("'int', 'long' or 'void'"),
+                span: first.span,
+            });
+        }
+        let (ty, ty_span) = parse_type_specifiers(tokens, first)?;
+        let ty = parse_pointers(tokens, ty);
+        let id_attempt = expect_token(tokens, "a parameter name")?;
+        let Token::Identifier { val: name } = id_attempt.token else {
+            return Err(ParseError::InvalidSyntax {
+                got: id_attempt.token,
+                expected: String::from("a parameter name"),
+                span: id_attempt.span,
+            });
+        };
+        let ty = parse_dimensions(tokens, ty)?.decayed();
+        params.push(Param {
+            name,
+            ty,
+            span: ty_span.to(id_attempt.span),
+        });
+        if tokens.next_if(|t| *t == Token::Comma).is_none() {
+            return Ok(params);
+        }
+    }
+}
+
+/// parses the rest of a block after its opening brace, found at `open_brace`.
+/// An error in one of its items is reported and skipped past, and parsing goes
+/// on with the next.
+fn parse_block(tokens: &mut TokenStream<'_>, open_brace: Span) -> ParseResult<Block> {
+    let mut items = Vec::new();
+    while tokens.peek().is_some_and(|t| *t != Token::CloseBrace) {
+        match parse_block_item(tokens) {
+            Ok(item) => items.extend(item),
+            Err(e) => {
+                tokens.report(e);
+                tokens.synchronize();
+            }
+        }
+    }
+    let close_brace = expect_closing(tokens, Token::CloseBrace, Token::OpenBrace, open_brace)?;
+    Ok(Block {
+        items,
+        span: open_brace.to(close_brace),
+    })
+}
+
+/// Expects a declaration or a statement, told apart by whether it starts with
+/// a specifier. A declaration of several variables is one item per variable.
+fn parse_block_item(tokens: &mut TokenStream<'_>) -> ParseResult<Vec<BlockItem>> {
+    match tokens.next_if(is_specifier) {
+        Some(first) => {
+            let (ty, storage, ty_span) = parse_specifiers(tokens, first)?;
+            if let Some(tag) = declared_struct(tokens, ty, storage) {
+                return Ok(vec![BlockItem::Struct {
+                    decl: parse_struct_declaration(tokens, tag, ty_span)?,
+                }]);
+            }
+            let decls = parse_declaration(tokens, ty, storage, ty_span)?;
+            Ok(decls
+                .into_iter()
+                .map(|decl| BlockItem::Declaration { decl })
+                .collect())
+        }
+        None => Ok(vec![BlockItem::Statement {
+            stmt: parse_statement(tokens)?,
+        }]),
+    }
+}
+
+/// the tag of the structure a declaration declares, if its specifiers, of
+/// type `ty`, are a `struct` and its tag alone and no declarator follows them.
+fn declared_struct(
+    tokens: &mut TokenStream<'_>,
+    ty: CType,
+    storage: Option<StorageClass>,
+) -> Option<Symbol> {
+    match (ty, storage, tokens.peek()) {
+        (CType::Struct { tag }, None, Some(Token::OpenBrace | Token::Semicolon)) => Some(tag),
+        _ => None,
+    }
+}
+
+/// parses the rest of a structure declaration after `struct` and its tag,
+/// found at `span`: its members if it has any, and the `;`.
+fn parse_struct_declaration(
+    tokens: &mut TokenStream<'_>,
+    tag: Symbol,
+    span: Span,
+) -> ParseResult<StructDeclaration> {
+    let mut members = Vec::new();
+    if let Some(open_brace) = tokens.next_if(|t| *t == Token::OpenBrace) {
+        loop {
+            members.push(parse_member(tokens)?);
+            if tokens.peek().is_none_or(|t| *t == Token::CloseBrace) {
+                break;
+            }
+        }
+        expect_closing(tokens, Token::CloseBrace, Token::OpenBrace, open_brace.span)?;
+    }
+    let semicolon = expect_semicolon(tokens)?;
+    Ok(StructDeclaration {
+        tag,
+        members,
+        span: span.to(semicolon),
+    })
+}
+
+/// Expects the declaration of a member of a structure, which has type
+/// specifiers only.
+fn parse_member(tokens: &mut TokenStream<'_>) -> ParseResult<MemberDeclaration> {
+    let first = expect_token(tokens, "a member declaration")?;
+    if !is_type_specifier(&first.token) {
+        return Err(ParseError::InvalidSyntax {
+            got: first.token,
+            expected: String::from("a member declaration"),
+            span: first.span,
+        });
+    }
+    let (ty, ty_span) = parse_type_specifiers(tokens, first)?;
+    let ty = parse_pointers(tokens, ty);
+    let (name, _) = parse_member_name(tokens)?;
+    let ty = parse_dimensions(tokens, ty)?;
+    let semicolon = expect_semicolon(tokens)?;
+    Ok(MemberDeclaration {
+        name,
+        ty,
+        span: ty_span.to(semicolon),
+    })
+}
+
+/// parses the rest of a declaration after its specifiers of type `ty`, found
+/// at `ty_span`: its declarators, and the initializer of each that has one.
+fn parse_declaration(
+    tokens: &mut TokenStream<'_>,
+    ty: CType,
+    storage: Option<StorageClass>,
+    ty_span: Span,
+) -> ParseResult<Vec<Declaration>> {
+    let declarator_ty = parse_pointers(tokens, ty);
+    let name = parse_variable_name(tokens)?;
+    parse_variables(tokens, (name, declarator_ty), ty, storage, ty_span)
+}
+
+/// Expects the name a declarator declares.
+fn parse_variable_name(tokens: &mut TokenStream<'_>) -> ParseResult<Symbol> {
+    let id_attempt = expect_token(tokens, "a variable name")?;
+    match id_attempt.token {
+        Token::Identifier { val } => Ok(val),
+        got => Err(ParseError::InvalidSyntax {
+            got,
+            expected: String::from("a variable name"),
+            span: id_attempt.span,
+        }),
+    }
+}
+
+/// parses the rest of a declaration of variables after the name of the first,
+/// `first`, given with its type before its dimensions: the rest of its
+/// declarator, then any more after commas, their pointers on top of `ty`, up
+/// to the `;`. Each is a declaration of its own, in order, so an initializer
+/// can use the variables before it. The first spans from the specifiers at
+/// `ty_span`, the others from their own declarator, and the last takes in the
+/// `;`.
+fn parse_variables(
+    tokens: &mut TokenStream<'_>,
+    first: (Symbol, CType),
+    ty: CType,
+    storage: Option<StorageClass>,
+    ty_span: Span,
+) -> ParseResult<Vec<Declaration>> {
+    let (name, declarator_ty) = first;
+    let mut decls = vec![parse_declarator(
+        tokens,
+        name,
+        declarator_ty,
+        storage,
+        ty_span,
+    )?];
+    while tokens.next_if(|t| *t == Token::Comma).is_some() {
+        let after_comma = tokens.after_prev();
+        let start = tokens.tokens.peek().map_or(after_comma, |t| t.span);
+        let declarator_ty = parse_pointers(tokens, ty);
+        let name = parse_variable_name(tokens)?;
+        decls.push(parse_declarator(
+            tokens,
+            name,
+            declarator_ty,
+            storage,
+            start,
+        )?);
+    }
+    let semicolon = expect_semicolon(tokens)?;
+    if let Some(last) = decls.last_mut() {
+        last.span = last.span.to(semicolon);
+    }
+    Ok(decls)
+}
+
+/// parses the rest of the declarator of variable `name`, of type `ty` before
+/// its dimensions, after its name: the dimensions, and its initializer if it
+/// has one. It spans from `start` to there.
+fn parse_declarator(
+    tokens: &mut TokenStream<'_>,
+    name: Symbol,
+    ty: CType,
+    storage: Option<StorageClass>,
+    start: Span,
+) -> ParseResult<Declaration> {
+    let ty = parse_dimensions(tokens, ty)?;
+    let init = match tokens.next_if(|t| *t == Token::Equal) {
+        Some(_) => Some(Box::new(Exp::from_expc(parse_exp(tokens, 0)?))),
+        None => None,
+    };
+    Ok(Declaration {
+        name,
+        ty,
+        init,
+        storage,
+        span: start.to(tokens.after_prev()),
+    })
+}
+
+/// Expects a statement.
+/// If this isn't found, returns an error.
+fn parse_statement(tokens: &mut TokenStream<'_>) -> ParseResult<StatementC> {
+    if let Some(if_keyword) = tokens.next_if(|t| *t == Token::IfKeyword) {
+        return nested(tokens, if_keyword.span, |tokens| {
+            parse_if(tokens, if_keyword.span)
+        });
+    }
+    if let Some(for_keyword) = tokens.next_if(|t| *t == Token::ForKeyword) {
+        return nested(tokens, for_keyword.span, |tokens| {
+            parse_for(tokens, for_keyword.span)
+        });
+    }
+    if let Some(open_brace) = tokens.next_if(|t| *t == Token::OpenBrace) {
+        // a block too deep to parse is skipped whole, so its closing braces
+        // aren't taken for those of the blocks around it
+        if tokens.depth == MAX_NESTING {
+            tokens.skip_block();
+        }
+        return nested(tokens, open_brace.span, |tokens| {
+            Ok(StatementC::Compound {
+                block: parse_block(tokens, open_brace.span)?,
+            })
+        });
+    }
+    if let Some(switch_keyword) = tokens.next_if(|t| *t == Token::SwitchKeyword) {
+        return nested(tokens, switch_keyword.span, |tokens| {
+            parse_switch(tokens, switch_keyword.span)
+        });
+    }
+    if let Some(case_keyword) = tokens.next_if(|t| *t == Token::CaseKeyword) {
+        return nested(tokens, case_keyword.span, |tokens| {
+            parse_case(tokens, case_keyword.span)
+        });
+    }
+    if let Some(default_keyword) = tokens.next_if(|t| *t == Token::DefaultKeyword) {
+        return nested(tokens, default_keyword.span, |tokens| {
+            parse_default(tokens, default_keyword.span)
+        });
+    }
+    if let Some(brk) = tokens.next_if(|t| *t == Token::BreakKeyword) {
+        return Ok(StatementC::Break {
+            label: None,
+            span: brk.span.to(expect_semicolon(tokens)?),
+        });
+    }
+    if let Some(cont) = tokens.next_if(|t| *t == Token::ContinueKeyword) {
+        return Ok(StatementC::Continue {
+            label: None,
+            span: cont.span.to(expect_semicolon(tokens)?),
+        });
+    }
+    if let Some(semicolon) = tokens.next_if(|t| *t == Token::Semicolon) {
+        return Ok(StatementC::Null {
+            span: semicolon.span,
+        });
+    }
+    if let Some(ret) = tokens.next_if(|t| *t == Token::RetKeyword) {
+        let exp = Exp::from_expc(parse_exp(tokens, 0)?);
+        return Ok(StatementC::Return {
+            exp: Box::new(exp),
+            span: ret.span.to(expect_semicolon(tokens)?),
+        });
+    }
+    let exp = Exp::from_expc(parse_exp(tokens, 0)?);
+    Ok(StatementC::Expression {
+        span: exp.span().to(expect_semicolon(tokens)?),
+        exp: Box::new(exp),
+    })
+}
+
+/// takes the `;` ending a statement or declaration, returning its span.
+fn expect_semicolon(tokens: &mut TokenStream<'_>) -> ParseResult<Span> {
+    match tokens.next_if(|t| *t == Token::Semicolon) {
+        Some(semicolon) => Ok(semicolon.span),
+        None => Err(ParseError::MissingSemicolon {
+            span: tokens.after_prev(),
+        }),
+    }
+}
+
+/// runs `parse` one level of nesting deeper, for a construct starting at `span`,
+/// unless that would nest past the limit.
+fn nested<T>(
+    tokens: &mut TokenStream<'_>,
+    span: Span,
+    parse: impl FnOnce(&mut TokenStream<'_>) -> ParseResult<T>,
+) -> ParseResult<T> {
+    if tokens.depth == MAX_NESTING {
+        return Err(ParseError::TooDeep {
+            span,
+            limit: MAX_NESTING,
+        });
+    }
+    tokens.depth += 1;
+    let res = parse(tokens);
+    tokens.depth -= 1;
+    res
+}
+
+/// parses the rest of an `if` statement starting at its keyword, found at `if_span`.
+fn parse_if(tokens: &mut TokenStream<'_>, if_span: Span) -> ParseResult<StatementC> {
+    let open_parens = expect_variant(tokens, Token::OpenParens)?;
+    let condition = Exp::from_expc(parse_exp(tokens, 0)?);
+    expect_closing(tokens, Token::CloseParens, Token::OpenParens, open_parens)?;
+    let then_stmt = parse_statement(tokens)?;
+    let else_stmt = match tokens.next_if(|t| *t == Token::ElseKeyword) {
+        Some(_) => Some(Box::new(parse_statement(tokens)?)),
+        None => None,
+    };
+    let end = else_stmt.as_deref().unwrap_or(&then_stmt).span();
+    Ok(StatementC::If {
+        condition: Box::new(condition),
+        then_stmt: Box::new(then_stmt),
+        else_stmt,
+        span: if_span.to(end),
+    })
+}
+
+/// parses the rest of a `for` statement starting at its keyword, found at `for_span`.
+/// Any of its three clauses may be left out.
+fn parse_for(tokens: &mut TokenStream<'_>, for_span: Span) -> ParseResult<StatementC> {
+    let open_parens = expect_variant(tokens, Token::OpenParens)?;
+    let mut declared = Vec::new();
+    let init = match tokens.next_if(is_type_specifier) {
+        Some(first) => {
+            let (ty, ty_span) = parse_type_specifiers(tokens, first)?;
+            let mut decls = parse_declaration(tokens, ty, None, ty_span)?;
+            match decls.len() {
+                1 => ForInit::Declaration {
+                    decl: decls.remove(0),
+                },
+                _ => {
+                    declared = decls;
+                    ForInit::Expression { exp: None }
+                }
+            }
+        }
+        None => {
+            let exp = parse_optional_exp(tokens, Token::Semicolon)?;
+            expect_semicolon(tokens)?;
+            ForInit::Expression { exp }
+        }
+    };
+    let condition = parse_optional_exp(tokens, Token::Semicolon)?;
+    expect_semicolon(tokens)?;
+    let post = parse_optional_exp(tokens, Token::CloseParens)?;
+    expect_closing(tokens, Token::CloseParens, Token::OpenParens, open_parens)?;
+    let body = parse_statement(tokens)?;
+    let span = for_span.to(body.span());
+    let stmt = StatementC::For {
+        init,
+        condition,
+        post,
+        span,
+        body: Box::new(body),
+        label: None,
+    };
+    if declared.is_empty() {
+        return Ok(stmt);
+    }
+    // several variables are declared in a block around the loop instead, where
+    // their scope ends with it all the same
+    let mut items: Vec<BlockItem> = declared
+        .into_iter()
+        .map(|decl| BlockItem::Declaration { decl })
+        .collect();
+    items.push(BlockItem::Statement { stmt });
+    Ok(StatementC::Compound {
+        block: Block { items, span },
+    })
+}
+
+/// parses the rest of a `switch` statement starting at its keyword, found at
+/// `switch_span`.
+fn parse_switch(tokens: &mut TokenStream<'_>, switch_span: Span) -> ParseResult<StatementC> {
+    let open_parens = expect_variant(tokens, Token::OpenParens)?;
+    let control = Exp::from_expc(parse_exp(tokens, 0)?);
+    expect_closing(tokens, Token::CloseParens, Token::OpenParens, open_parens)?;
+    let body = parse_statement(tokens)?;
+    Ok(StatementC::Switch {
+        control: Box::new(control),
+        span: switch_span.to(body.span()),
+        body: Box::new(body),
+        label: None,
+        cases: vec![],
+    })
+}
+
+/// parses the rest of a `case` starting at its keyword, found at `case_span`.
+/// Whether its value is a constant is for semantic analysis to say.
+fn parse_case(tokens: &mut TokenStream<'_>, case_span: Span) -> ParseResult<StatementC> {
+    let value = Exp::from_expc(parse_exp(tokens, 0)?);
+    expect_variant(tokens, Token::Colon)?;
+    let body = parse_statement(tokens)?;
+    Ok(StatementC::Case {
+        value: Box::new(value),
+        span: case_span.to(body.span()),
+        body: Box::new(body),
+        label: None,
+    })
+}
+
+/// parses the rest of a `default` starting at its keyword, found at `default_span`.
+fn parse_default(tokens: &mut TokenStream<'_>, default_span: Span) -> ParseResult<StatementC> {
+    expect_variant(tokens, Token::Colon)?;
+    let body = parse_statement(tokens)?;
+    Ok(StatementC::Default {
+        span: default_span.to(body.span()),
+        body: Box::new(body),
+        label: None,
+    })
+}
+
+/// parses an expression, unless the next token is the `end` that follows where
+/// one may be left out.
+fn parse_optional_exp(tokens: &mut TokenStream<'_>, end: Token) -> ParseResult<Option<Box<Exp>>> {
+    match tokens.peek() {
+        Some(t) if *t == end => Ok(None),
+        _ => Ok(Some(Box::new(Exp::from_expc(parse_exp(tokens, 0)?)))),
+    }
+}
+
+/// Expects an expression.
+/// If this isn't found, returns an error.
+fn parse_exp(tokens: &mut TokenStream<'_>, min_prec: u8) -> ParseResult<ExpC> {
+    let mut left = ExpC::Factor {
+        fac: Box::new(parse_factor(tokens)?),
+    };
+
+    while let Some(next_token) = tokens.next_if(|t| {
+        matches!(
+            t,
+            Token::Plus
+                | Token::Minus
+                | Token::Asterisk
+                | Token::FSlash
+                | Token::Percent
+                | Token::Ampersand
+                | Token::Pipe
+                | Token::Caret
+                | Token::LessLess
+                | Token::GreaterGreater
+                | Token::Less
+                | Token::Greater
+                | Token::LessEqual
+                | Token::GreaterEqual
+                | Token::EqualEqual
+                | Token::BangEqual
+                | Token::AmpersandAmpersand
+                | Token::PipePipe
+                | Token::Question
+                | Token::Equal
+                | Token::PlusEqual
+                | Token::MinusEqual
+                | Token::AsteriskEqual
+                | Token::FSlashEqual
+                | Token::PercentEqual
+                | Token::AmpersandEqual
+                | Token::PipeEqual
+                | Token::CaretEqual
+                | Token::LessLessEqual
+                | Token::GreaterGreaterEqual
+        ) && BinaryOp::token_prec(t) >= min_prec
+    }) {
+        let prec = BinaryOp::token_prec(&next_token.token);
+        let compound = BinaryOp::compound(&next_token.token);
+        if next_token.token == Token::Equal || compound.is_some() {
+            // assignments group to the right, each nesting in the one before
+            let right = nested(tokens, next_token.span, |tokens| parse_exp(tokens, prec))?;
+            let height = 1 + left.height().max(right.height());
+            if height > MAX_HEIGHT {
+                return Err(ParseError::TooDeep {
+                    span: next_token.span,
+                    limit: MAX_HEIGHT,
+                });
+            }
+            let (lvalue, rvalue) = (Box::new(left), Box::new(right));
+            left = match compound {
+                None => ExpC::Assignment {
+                    lvalue,
+                    rvalue,
+                    height,
+                },
+                Some(op) => ExpC::CompoundAssignment {
+                    op,
+                    lvalue,
+                    rvalue,
+                    height,
+                },
+            };
+            continue;
+        }
+        if next_token.token == Token::Question {
+            // anything goes between `?` and `:`, as if parenthesized, and the
+            // else branch may be another conditional; both are parsed as a
+            // branch, each nesting a level deeper
+            let middle = nested(tokens, next_token.span, |tokens| parse_exp(tokens, 0))?;
+            expect_variant(tokens, Token::Colon)?;
+            let right = nested(tokens, next_token.span, |tokens| parse_exp(tokens, prec))?;
+            let height = 1 + left.height().max(middle.height()).max(right.height());
+            if height > MAX_HEIGHT {
+                return Err(ParseError::TooDeep {
+                    span: next_token.span,
+                    limit: MAX_HEIGHT,
+                });
+            }
+            left = ExpC::Conditional {
+                condition: Box::new(left),
+                then_exp: Box::new(middle),
+                else_exp: Box::new(right),
+                height,
+            };
+            continue;
+        }
+        let op = BinaryOp::from(next_token.token, next_token.span)?;
+        let right = parse_exp(tokens, prec + 1)?;
+        let height = 1 + left.height().max(right.height());
+        if height > MAX_HEIGHT {
+            return Err(ParseError::TooDeep {
+                span: next_token.span,
+                limit: MAX_HEIGHT,
+            });
+        }
+        left = ExpC::Binary {
+            op,
+            l_exp: Box::new(left),
+            r_exp: Box::new(right),
+            height,
+        }
+    }
+
+    Ok(left)
+}
+
+fn parse_factor(tokens: &mut TokenStream<'_>) -> ParseResult<FactorC> {
+    let got = expect_token(tokens, "an expression")?;
+    nested(tokens, got.span, |tokens| parse_nested_factor(tokens, got))
+}
+
+/// parses the rest of a factor starting with `got`.
+fn parse_nested_factor(tokens: &mut TokenStream<'_>, got: SpannedToken) -> ParseResult<FactorC> {
+    match got.token {
+        Token::Tilde | Token::Minus => {
+            let op = match got.token {
+                Token::Tilde => UnaryOp::BitwiseComplement,
+                _ => UnaryOp::Negate,
+            };
+            let fac = parse_factor(tokens)?;
+            Ok(FactorC::Unary {
+                op,
+                fac: Box::new(fac),
+                span: got.span.to(tokens.after_prev()),
+            })
+        }
+        Token::Asterisk => {
+            let fac = parse_factor(tokens)?;
+            Ok(FactorC::Dereference {
+                fac: Box::new(fac),
+                span: got.span.to(tokens.after_prev()),
+            })
+        }
+        Token::Ampersand => {
+            let fac = parse_factor(tokens)?;
+            Ok(FactorC::AddrOf {
+                fac: Box::new(fac),
+                span: got.span.to(tokens.after_prev()),
+            })
+        }
+        Token::PlusPlus | Token::MinusMinus => {
+            let op = match got.token {
+                Token::PlusPlus => IncrementOp::PreIncrement,
+                _ => IncrementOp::PreDecrement,
+            };
+            let fac = parse_factor(tokens)?;
+            Ok(FactorC::Increment {
+                op,
+                fac: Box::new(fac),
+                span: got.span.to(tokens.after_prev()),
+            })
+        }
+        Token::OpenParens if tokens.peek().is_some_and(is_type_specifier) => {
+            parse_cast(tokens, got.span)
+        }
+        _ => {
+            let start = got.span;
+            let primary = parse_primary(tokens, got)?;
+            parse_postfix(tokens, primary, start)
+        }
+    }
+}
+
+/// parses the rest of a cast, after the `(` at `open` and before the type
+/// name it starts: type specifiers and any `*`s, the closing `)`, then the
+/// factor it converts.
+fn parse_cast(tokens: &mut TokenStream<'_>, open: Span) -> ParseResult<FactorC> {
+    let first = expect_token(tokens, "a type name")?;
+    let (ty, _) = parse_type_specifiers(tokens, first)?;
+    let target = parse_pointers(tokens, ty);
+    expect_closing(tokens, Token::CloseParens, Token::OpenParens, open)?;
+    let fac = parse_factor(tokens)?;
+    Ok(FactorC::Cast {
+        target,
+        fac: Box::new(fac),
+        span: open.to(tokens.after_prev()),
+    })
+}
+
+/// parses the rest of a factor starting with `got` that isn't a prefix
+/// operator, before any subscripts.
+fn parse_primary(tokens: &mut TokenStream<'_>, got: SpannedToken) -> ParseResult<FactorC> {
+    match got.token {
+        Token::Constant { val } => Ok(FactorC::Const {
+            c: Const::unsuffixed(val),
+            span: got.span,
+        }),
+        Token::LongConstant { val } => Ok(FactorC::Const {
+            c: Const::Long { long: val },
+            span: got.span,
+        }),
+        Token::UnsignedConstant { val } => Ok(FactorC::Const {
+            c: Const::unsigned(val),
+            span: got.span,
+        }),
+        Token::UnsignedLongConstant { val } => Ok(FactorC::Const {
+            c: Const::ULong { ulong: val },
+            span: got.span,
+        }),
+        Token::DoubleConstant { val } => Ok(FactorC::Const {
+            c: Const::Double { double: val },
+            span: got.span,
+        }),
+        Token::StringLiteral { mut val } => {
+            let mut span = got.span;
+            while let Some(next) = tokens.next_if(|t| matches!(t, Token::StringLiteral { .. })) {
+                if let Token::StringLiteral { val: more } = next.token {
+                    val.extend(more);
+                }
+                span = span.to(next.span);
+            }
+            Ok(FactorC::String { val, span })
+        }
+        Token::Identifier { val } => match tokens.next_if(|t| *t == Token::OpenParens) {
+            Some(open_parens) => parse_call(tokens, val, got.span, open_parens.span),
+            None => Ok(FactorC::Var {
+                name: val,
+                span: got.span,
+            }),
+        },
+        Token::OpenParens => {
+            let inner = parse_exp(tokens, 0)?;
+            let close = expect_closing(tokens, Token::CloseParens, Token::OpenParens, got.span)?;
+            Ok(FactorC::Exp {
+                exp: Box::new(inner),
+                span: got.span.to(close),
+            })
+        }
+        _ => Err(ParseError::InvalidSyntax {
+            got: got.token,
+            expected: String::from("an expression"),
+            span: got.span,
+        }),
+    }
+}
+
+/// takes the `[exp]`s, `.member`s, `->member`s, `++`s and `--`s after `fac`,
+/// which starts at `start`, each applying to what came before it.
+fn parse_postfix(
+    tokens: &mut TokenStream<'_>,
+    mut fac: FactorC,
+    start: Span,
+) -> ParseResult<FactorC> {
+    let mut height = fac.height();
+    while let Some(op) = tokens.next_if(|t| {
+        matches!(
+            t,
+            Token::OpenBracket | Token::Dot | Token::Arrow | Token::PlusPlus | Token::MinusMinus
+        )
+    }) {
+        fac = match op.token {
+            Token::PlusPlus | Token::MinusMinus => {
+                height += 1;
+                FactorC::Increment {
+                    op: match op.token {
+                        Token::PlusPlus => IncrementOp::PostIncrement,
+                        _ => IncrementOp::PostDecrement,
+                    },
+                    fac: Box::new(fac),
+                    span: start.to(op.span),
+                }
+            }
+            Token::OpenBracket => {
+                let index = parse_exp(tokens, 0)?;
+                let close =
+                    expect_closing(tokens, Token::CloseBracket, Token::OpenBracket, op.span)?;
+                height = 1 + height.max(index.height());
+                FactorC::Subscript {
+                    fac: Box::new(fac),
+                    index: Box::new(index),
+                    span: start.to(close),
+                }
+            }
+            _ => {
+                let (member, member_span) = parse_member_name(tokens)?;
+                height += 1;
+                let (fac, span) = (Box::new(fac), start.to(member_span));
+                match op.token {
+                    Token::Dot => FactorC::Dot { fac, member, span },
+                    _ => FactorC::Arrow { fac, member, span },
+                }
+            }
+        };
+        if height > MAX_HEIGHT {
+            return Err(ParseError::TooDeep {
+                span: op.span,
+                limit: MAX_HEIGHT,
+            });
+        }
+    }
+    Ok(fac)
+}
+
+/// Expects the name of a member, after a `.` or `->`.
+fn parse_member_name(tokens: &mut TokenStream<'_>) -> ParseResult<(Symbol, Span)> {
+    let got = expect_token(tokens, "a member name")?;
+    match got.token {
+        Token::Identifier { val } => Ok((val, got.span)),
+        _ => Err(ParseError::InvalidSyntax {
+            got: got.token,
+            expected: String::from("a member name"),
+            span: got.span,
+        }),
+    }
+}
+
+/// parses the rest of a call to `name`, written at `name_span`, after its opening
+/// parenthesis.
+fn parse_call(
+    tokens: &mut TokenStream<'_>,
+    name: Symbol,
+    name_span: Span,
+    open_parens: Span,
+) -> ParseResult<FactorC> {
+    let mut args = Vec::new();
+    if tokens.peek() != Some(&Token::CloseParens) {
+        args.push(parse_exp(tokens, 0)?);
+        while tokens.next_if(|t| *t == Token::Comma).is_some() {
+            args.push(parse_exp(tokens, 0)?);
+        }
+    }
+    let close = expect_closing(tokens, Token::CloseParens, Token::OpenParens, open_parens)?;
+    Ok(FactorC::FunctionCall {
+        name,
+        args,
+        span: name_span.to(close),
+    })
+}
+
+/// takes the next token, whatever it is; `expected` describes what the caller wants,
+/// for the error if the input has run out.
+fn expect_token(tokens: &mut TokenStream<'_>, expected: &str) -> ParseResult<SpannedToken> {
+    match tokens.next() {
+        Some(token) => Ok(token),
+        None => Err(ParseError::SeverredStream {
+            expected: String::from(expected),
+            span: tokens.after_prev(),
+        }),
+    }
+}
+
+/// takes the next token if it is `expected`, returning its span.
+fn expect_variant(tokens: &mut TokenStream<'_>, expected: Token) -> ParseResult<Span> {
+    let quoted = format!("'{}'", expected.spelling());
+    let token = expect_token(tokens, &quoted)?;
+
+    if token.token == expected {
+        Ok(token.span)
+    } else {
+        Err(ParseError::InvalidSyntax {
+            got: token.token,
+            expected: quoted,
+            span: token.span,
+        })
+    }
+}
+
+/// takes the delimiter closing the `open` token found at `open_span`.
+fn expect_closing(
+    tokens: &mut TokenStream<'_>,
+    close: Token,
+    open: Token,
+    open_span: Span,
+) -> ParseResult<Span> {
+    expect_variant(tokens, close).map_err(|error| ParseError::Unclosed {
+        error: Box::new(error),
+        open,
+        open_span,
+    })
+}
+
+#[cfg(test)]
+/// a stream of tokens with made-up spans, one byte per token.
+fn stream(tokens: Vec<Token>) -> TokenStream<'static> {
+    TokenStream::new(
+        tokens
+            .into_iter()
+            .enumerate()
+            .map(|(i, token)| SpannedToken {
+                token,
+                span: Span {
+                    start: i,
+                    end: i + 1,
+                },
+            })
+            .collect::<Vec<_>>()
+            .into_iter(),
+    )
+}
+
+#[cfg(test)]
+fn at(start: usize, end: usize) -> Span {
+    Span { start, end }
+}
+
+#[test]
+fn test_variant_error() {
+    let mut tokens = stream(vec![Token::OpenBrace]);
+    let res = expect_variant(&mut tokens, Token::CloseBrace);
+
+    match res {
+        Ok(_) => unreachable!(),
+        Err(e) => assert_eq!(
+            e,
+            ParseError::InvalidSyntax {
+                got: Token::OpenBrace,
+                expected: String::from("'}'"),
+                span: Span { start: 0, end: 1 },
+            }
+        ),
+    }
+}
+
+#[test]
+fn test_variant_success() {
+    let mut tokens = stream(vec![Token::OpenBrace]);
+    let res = expect_variant(&mut tokens, Token::OpenBrace);
+    assert!(res.is_ok());
+}
+
+/// tests the parsing of `2`
+#[test]
+fn test_constant_exp() {
+    let tokens = &mut stream(vec![Token::Constant { val: 2 }]);
+    let res = parse_exp(tokens, 0);
+    let res = res.unwrap();
+    assert_eq!(
+        Exp::from_expc(res),
+        Exp::Const {
+            c: Const::Int { int: 2 },
+            span: at(0, 1)
+        }
+    );
+}
+
+/// tests the parsing of `~(~(~2))`
+#[test]
+fn test_nested_cmp_parens() {
+    let tokens = &mut stream(vec![
+        Token::Tilde,
+        Token::OpenParens,
+        Token::Tilde,
+        Token::OpenParens,
+        Token::Tilde,
+        Token::Constant { val: 2 },
+        Token::CloseParens,
+        Token::CloseParens,
+    ]);
+    let res = parse_exp(tokens, 0);
+    let res = res.unwrap();
+    assert_eq!(
+        Exp::from_expc(res),
+        Exp::Unary {
+            span: at(0, 8),
+            op: UnaryOp::BitwiseComplement,
+            exp: Box::new(Exp::Unary {
+                span: at(1, 8),
+                op: UnaryOp::BitwiseComplement,
+                exp: Box::new(Exp::Unary {
+                    span: at(3, 7),
+                    op: UnaryOp::BitwiseComplement,
+                    exp: Box::new(Exp::Const {
+                        c: Const::Int { int: 2 },
+                        span: at(5, 6)
+                    })
+                })
+            })
+        }
+    )
+}
+
+/// tests the parsing of `(((2)))`
+#[test]
+fn test_nested_parens() {
+    let tokens = &mut stream(vec![
+        Token::OpenParens,
+        Token::OpenParens,
+        Token::OpenParens,
+        Token::Constant { val: 2 },
+        Token::CloseParens,
+        Token::CloseParens,
+        Token::CloseParens,
+    ]);
+    let res = parse_exp(tokens, 0);
+    let res = res.unwrap();
+    assert_eq!(
+        Exp::from_expc(res),
+        Exp::Const {
+            c: Const::Int { int: 2 },
+            span: at(0, 7)
+        }
+    );
+}
+
+/// tests the parsing of `return ~(~(~2));`
+#[test]
+fn test_return_nested_parens() {
+    let mut tokens = stream(vec![
+        Token::RetKeyword,
+        Token::Tilde,
+        Token::OpenParens,
+        Token::Tilde,
+        Token::OpenParens,
+        Token::Tilde,
+        Token::Constant { val: 2 },
+        Token::CloseParens,
+        Token::CloseParens,
+        Token::Semicolon,
+    ]);
+    let res = parse_statement(&mut tokens);
+    assert!(res.is_ok());
+}
+
+/// tests the parsing of `1 + 1`
+#[test]
+fn test_one_plus_one() {
+    let tokens = &mut stream(vec![
+        Token::Constant { val: 1 },
+        Token::Plus,
+        Token::Constant { val: 1 },
+    ]);
+    let res = parse_exp(tokens, 0);
+    let res = res.unwrap();
+    let expected = ExpC::Binary {
+        op: BinaryOp::Add,
+        l_exp: Box::new(ExpC::Factor {
+            fac: Box::new(FactorC::Const {
+                c: Const::Int { int: 1 },
+                span: at(0, 1),
+            }),
+        }),
+        r_exp: Box::new(ExpC::Factor {
+            fac: Box::new(FactorC::Const {
+                c: Const::Int { int: 1 },
+                span: at(2, 3),
+            }),
+        }),
+        height: 2,
+    };
+    assert_eq!(res, expected);
+}
+
+/// tests the parsing of `1 + 2 - 3`
+#[test]
+fn test_one_plus_two_minus_three() {
+    let tokens = &mut stream(vec![
+        Token::Constant { val: 1 },
+        Token::Plus,
+        Token::Constant { val: 2 },
+        Token::Minus,
+        Token::Constant { val: 3 },
+    ]);
+    let res = parse_exp(tokens, 0);
+    let res = Exp::from_expc(res.unwrap());
+    let expected = Exp::Binary {
+        span: at(0, 5),
+        op: BinaryOp::Subtract,
+        l_exp: Box::new(Exp::Binary {
+            span: at(0, 3),
+            op: BinaryOp::Add,
+            l_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 1 },
+                span: at(0, 1),
+            }),
+            r_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 2 },
+                span: at(2, 3),
+            }),
+        }),
+        r_exp: Box::new(Exp::Const {
+            c: Const::Int { int: 3 },
+            span: at(4, 5),
+        }),
+    };
+    assert_eq!(res, expected);
+}
+
+/// tests the parsing of `1 + (2 - 3)`
+#[test]
+fn test_one_plus_parens_two_minus_three() {
+    let tokens = &mut stream(vec![
+        Token::Constant { val: 1 },
+        Token::Plus,
+        Token::OpenParens,
+        Token::Constant { val: 2 },
+        Token::Minus,
+        Token::Constant { val: 3 },
+        Token::CloseParens,
+    ]);
+    let res = parse_exp(tokens, 0);
+    let res = Exp::from_expc(res.unwrap());
+    let expected = Exp::Binary {
+        span: at(0, 7),
+        op: BinaryOp::Add,
+        l_exp: Box::new(Exp::Const {
+            c: Const::Int { int: 1 },
+            span: at(0, 1),
+        }),
+        r_exp: Box::new(Exp::Binary {
+            span: at(2, 7),
+            op: BinaryOp::Subtract,
+            l_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 2 },
+                span: at(3, 4),
+            }),
+            r_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 3 },
+                span: at(5, 6),
+            }),
+        }),
+    };
+    assert_eq!(res, expected);
+}
+
+/// tests the parsing of `1 + 2 * 3`
+#[test]
+fn test_one_plus_two_times_three() {
+    let tokens = &mut stream(vec![
+        Token::Constant { val: 1 },
+        Token::Plus,
+        Token::Constant { val: 2 },
+        Token::Asterisk,
+        Token::Constant { val: 3 },
+    ]);
+    let res = parse_exp(tokens, 0);
+    let res = Exp::from_expc(res.unwrap());
+    let expected = Exp::Binary {
+        span: at(0, 5),
+        op: BinaryOp::Add,
+        l_exp: Box::new(Exp::Const {
+            c: Const::Int { int: 1 },
+            span: at(0, 1),
+        }),
+        r_exp: Box::new(Exp::Binary {
+            span: at(2, 5),
+            op: BinaryOp::Multiply,
+            l_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 2 },
+                span: at(2, 3),
+            }),
+            r_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 3 },
+                span: at(4, 5),
+            }),
+        }),
+    };
+    assert_eq!(res, expected);
+}
+
+/// tests the parsing of `1 == 2 < 3 + 4`
+#[test]
+fn test_equality_below_relational_below_additive() {
+    let tokens = &mut stream(vec![
+        Token::Constant { val: 1 },
+        Token::EqualEqual,
+        Token::Constant { val: 2 },
+        Token::Less,
+        Token::Constant { val: 3 },
+        Token::Plus,
+        Token::Constant { val: 4 },
+    ]);
+    let res = parse_exp(tokens, 0);
+    let res = Exp::from_expc(res.unwrap());
+    let expected = Exp::Binary {
+        span: at(0, 7),
+        op: BinaryOp::Equal,
+        l_exp: Box::new(Exp::Const {
+            c: Const::Int { int: 1 },
+            span: at(0, 1),
+        }),
+        r_exp: Box::new(Exp::Binary {
+            span: at(2, 7),
+            op: BinaryOp::LessThan,
+            l_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 2 },
+                span: at(2, 3),
+            }),
+            r_exp: Box::new(Exp::Binary {
+                span: at(4, 7),
+                op: BinaryOp::Add,
+                l_exp: Box::new(Exp::Const {
+                    c: Const::Int { int: 3 },
+                    span: at(4, 5),
+                }),
+                r_exp: Box::new(Exp::Const {
+                    c: Const::Int { int: 4 },
+                    span: at(6, 7),
+                }),
+            }),
+        }),
+    };
+    assert_eq!(res, expected);
+}
+
+/// shifts bind between the relational and the additive operators and group
+/// to the left, so `1 < 2 << 3 - 4 >> 5` is `1 < ((2 << (3 - 4)) >> 5)`.
+#[test]
+fn test_shifts_between_relational_and_additive() {
+    let tokens = &mut stream(vec![
+        Token::Constant { val: 1 },
+        Token::Less,
+        Token::Constant { val: 2 },
+        Token::LessLess,
+        Token::Constant { val: 3 },
+        Token::Minus,
+        Token::Constant { val: 4 },
+        Token::GreaterGreater,
+        Token::Constant { val: 5 },
+    ]);
+    let res = Exp::from_expc(parse_exp(tokens, 0).unwrap());
+    let int = |int, start| {
+        Box::new(Exp::Const {
+            c: Const::Int { int },
+            span: at(start, start + 1),
+        })
+    };
+    let expected = Exp::Binary {
+        span: at(0, 9),
+        op: BinaryOp::LessThan,
+        l_exp: int(1, 0),
+        r_exp: Box::new(Exp::Binary {
+            span: at(2, 9),
+            op: BinaryOp::RightShift,
+            l_exp: Box::new(Exp::Binary {
+                span: at(2, 7),
+                op: BinaryOp::LeftShift,
+                l_exp: int(2, 2),
+                r_exp: Box::new(Exp::Binary {
+                    span: at(4, 7),
+                    op: BinaryOp::Subtract,
+                    l_exp: int(3, 4),
+                    r_exp: int(4, 6),
+                }),
+            }),
+            r_exp: int(5, 8),
+        }),
+    };
+    assert_eq!(res, expected);
+}
+
+/// `?:` binds looser than `||` and groups to the right, so this is
+/// `(1 || 2) ? 3 : (4 ? 5 : 6)`.
+#[test]
+fn test_conditional_below_or_and_right_associative() {
+    let tokens = &mut stream(vec![
+        Token::Constant { val: 1 },
+        Token::PipePipe,
+        Token::Constant { val: 2 },
+        Token::Question,
+        Token::Constant { val: 3 },
+        Token::Colon,
+        Token::Constant { val: 4 },
+        Token::Question,
+        Token::Constant { val: 5 },
+        Token::Colon,
+        Token::Constant { val: 6 },
+    ]);
+    let res = Exp::from_expc(parse_exp(tokens, 0).unwrap());
+    let constant = |int: i32, start: usize| {
+        Box::new(Exp::Const {
+            c: Const::Int { int },
+            span: at(start, start + 1),
+        })
+    };
+    let expected = Exp::Conditional {
+        span: at(0, 11),
+        condition: Box::new(Exp::Binary {
+            span: at(0, 3),
+            op: BinaryOp::Or,
+            l_exp: constant(1, 0),
+            r_exp: constant(2, 2),
+        }),
+        then_exp: constant(3, 4),
+        else_exp: Box::new(Exp::Conditional {
+            span: at(6, 11),
+            condition: constant(4, 6),
+            then_exp: constant(5, 8),
+            else_exp: constant(6, 10),
+        }),
+    };
+    assert_eq!(res, expected);
+}
+
+#[test]
+fn test_conditional_missing_colon() {
+    let tokens = &mut stream(vec![
+        Token::Constant { val: 1 },
+        Token::Question,
+        Token::Constant { val: 2 },
+        Token::Constant { val: 3 },
+    ]);
+    assert_eq!(
+        parse_exp(tokens, 0).unwrap_err(),
+        ParseError::InvalidSyntax {
+            got: Token::Constant { val: 3 },
+            expected: String::from("':'"),
+            span: at(3, 4),
+        }
+    );
+}
+
+/// tests the parsing of `1 * 2 - 3 * (4 + 5)`
+#[test]
+fn test_one_times_two_minus_three_times_parens_four_plus_five() {
+    let tokens = &mut stream(vec![
+        Token::Constant { val: 1 },
+        Token::Asterisk,
+        Token::Constant { val: 2 },
+        Token::Minus,
+        Token::Constant { val: 3 },
+        Token::Asterisk,
+        Token::OpenParens,
+        Token::Constant { val: 4 },
+        Token::Plus,
+        Token::Constant { val: 5 },
+        Token::CloseParens,
+    ]);
+    let res = parse_exp(tokens, 0);
+    let res = Exp::from_expc(res.unwrap());
+    let expected = Exp::Binary {
+        span: at(0, 11),
+        op: BinaryOp::Subtract,
+        l_exp: Box::new(Exp::Binary {
+            span: at(0, 3),
+            op: BinaryOp::Multiply,
+            l_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 1 },
+                span: at(0, 1),
+            }),
+            r_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 2 },
+                span: at(2, 3),
+            }),
+        }),
+        r_exp: Box::new(Exp::Binary {
+            span: at(4, 11),
+            op: BinaryOp::Multiply,
+            l_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 3 },
+                span: at(4, 5),
+            }),
+            r_exp: Box::new(Exp::Binary {
+                span: at(6, 11),
+                op: BinaryOp::Add,
+                l_exp: Box::new(Exp::Const {
+                    c: Const::Int { int: 4 },
+                    span: at(7, 8),
+                }),
+                r_exp: Box::new(Exp::Const {
+                    c: Const::Int { int: 5 },
+                    span: at(9, 10),
+                }),
+            }),
+        }),
+    };
+    assert_eq!(res, expected);
+}
+
+/// tests the parsing of `-(1 + 1)`
+#[test]
+fn test_negate_one_plus_one() {
+    let tokens = &mut stream(vec![
+        Token::Minus,
+        Token::OpenParens,
+        Token::Constant { val: 1 },
+        Token::Plus,
+        Token::Constant { val: 1 },
+        Token::CloseParens,
+    ]);
+    let res = parse_exp(tokens, 0);
+    let res = Exp::from_expc(res.unwrap());
+    let expected = Exp::Unary {
+        span: at(0, 6),
+        op: UnaryOp::Negate,
+        exp: Box::new(Exp::Binary {
+            span: at(1, 6),
+            op: BinaryOp::Add,
+            l_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 1 },
+                span: at(2, 3),
+            }),
+            r_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 1 },
+                span: at(4, 5),
+            }),
+        }),
+    };
+    assert_eq!(res, expected);
+}
+
+/// a dangling `else` belongs to the nearest `if`, so the outer one has none.
+#[test]
+fn test_dangling_else() {
+    let tokens = &mut stream(vec![
+        Token::IfKeyword,
+        Token::OpenParens,
+        Token::Constant { val: 1 },
+        Token::CloseParens,
+        Token::IfKeyword,
+        Token::OpenParens,
+        Token::Constant { val: 2 },
+        Token::CloseParens,
+        Token::RetKeyword,
+        Token::Constant { val: 3 },
+        Token::Semicolon,
+        Token::ElseKeyword,
+        Token::RetKeyword,
+        Token::Constant { val: 4 },
+        Token::Semicolon,
+    ]);
+    let StatementC::If {
+        then_stmt,
+        else_stmt: None,
+        span,
+        ..
+    } = parse_statement(tokens).unwrap()
+    else {
+        unreachable!()
+    };
+    assert_eq!(span, at(0, 15));
+    let StatementC::If {
+        else_stmt: Some(else_stmt),
+        span,
+        ..
+    } = *then_stmt
+    else {
+        unreachable!()
+    };
+    assert_eq!(span, at(4, 15));
+    assert_eq!(else_stmt.span(), at(12, 15));
+}
+
+/// nesting past the limit is an error rather than a stack overflow.
+#[test]
+fn test_nesting_too_deep() {
+    let tokens = &mut stream(
+        std::iter::repeat_n(Token::Tilde, MAX_NESTING)
+            .chain([Token::Constant { val: 1 }])
+            .collect(),
+    );
+    let err = parse_factor(tokens).unwrap_err();
+    assert_eq!(
+        err,
+        ParseError::TooDeep {
+            span: Span {
+                start: MAX_NESTING,
+                end: MAX_NESTING + 1
+            },
+            limit: MAX_NESTING,
+        }
+    );
+}
+
+/// a block nested past the limit is skipped whole, so the braces closing it
+/// don't end the blocks around it early and bring more errors.
+#[test]
+fn test_block_too_deep_skipped_whole() {
+    let tokens = &mut stream(
+        std::iter::repeat_n(Token::OpenBrace, MAX_NESTING + 1)
+            .chain(std::iter::repeat_n(Token::CloseBrace, MAX_NESTING + 2))
+            .collect(),
+    );
+    assert!(parse_block(tokens, at(0, 0)).is_ok());
+    assert_eq!(
+        tokens.errors,
+        vec![ParseError::TooDeep {
+            span: at(MAX_NESTING, MAX_NESTING + 1),
+            limit: MAX_NESTING,
+        }]
+    );
+    assert!(tokens.peek().is_none());
+}
+
+/// a chain of binary operators makes a tree as tall as it is long, so past the
+/// limit it is an error too, pointing at the operator that went over.
+#[test]
+fn test_chain_too_tall() {
+    let chain = |terms: usize| {
+        let mut tokens = vec![Token::Constant { val: 1 }];
+        for _ in 1..terms {
+            tokens.extend([Token::Plus, Token::Constant { val: 1 }]);
+        }
+        stream(tokens)
+    };
+    assert!(parse_exp(&mut chain(MAX_HEIGHT), 0).is_ok());
+    let err = parse_exp(&mut chain(MAX_HEIGHT + 1), 0).unwrap_err();
+    assert_eq!(
+        err,
+        ParseError::TooDeep {
+            span: at(2 * MAX_HEIGHT - 1, 2 * MAX_HEIGHT),
+            limit: MAX_HEIGHT,
+        }
+    );
+}
+
+/// a conditional's else branch nests in it, so a long chain of them is too deep
+/// rather than overflowing the stack, from the first operand past the limit.
+#[test]
+fn test_conditional_chain_too_deep() {
+    let chain = |conditionals: usize| {
+        let mut tokens = vec![];
+        for _ in 0..conditionals {
+            tokens.extend([
+                Token::Constant { val: 1 },
+                Token::Question,
+                Token::Constant { val: 1 },
+                Token::Colon,
+            ]);
+        }
+        tokens.push(Token::Constant { val: 1 });
+        stream(tokens)
+    };
+    assert!(parse_exp(&mut chain(MAX_NESTING - 1), 0).is_ok());
+    let err = parse_exp(&mut chain(3000), 0).unwrap_err();
+    assert_eq!(
+        err,
+        ParseError::TooDeep {
+            span: at(4 * MAX_NESTING - 2, 4 * MAX_NESTING - 1),
+            limit: MAX_NESTING,
+        }
+    );
+}
+
+/// a chain of assignments nests to the right, so a long one is too deep rather
+/// than overflowing the stack, whichever assignment operators it uses.
+#[test]
+fn test_assignment_chain_too_deep() {
+    let chain = |assignments: usize| {
+        let mut tokens = vec![];
+        for i in 0..assignments {
+            let op = match i % 2 {
+                0 => Token::Equal,
+                _ => Token::PlusEqual,
+            };
+            tokens.extend([
+                Token::Identifier {
+                    val: Symbol::intern("a"),
+                },
+                op,
+            ]);
+        }
+        tokens.push(Token::Constant { val: 1 });
+        stream(tokens)
+    };
+    assert!(parse_exp(&mut chain(MAX_NESTING - 1), 0).is_ok());
+    let err = parse_exp(&mut chain(3000), 0).unwrap_err();
+    assert_eq!(
+        err,
+        ParseError::TooDeep {
+            span: at(2 * MAX_NESTING, 2 * MAX_NESTING + 1),
+            limit: MAX_NESTING,
+        }
+    );
+}
+
+/// `=` binds looser than `?:` and groups to the right, so this is
+/// `a = (b = (1 ? 2 : 3))`.
+#[test]
+fn test_assignment_right_associative() {
+    let (a, b) = (Symbol::intern("a"), Symbol::intern("b"));
+    let tokens = &mut stream(vec![
+        Token::Identifier { val: a },
+        Token::Equal,
+        Token::Identifier { val: b },
+        Token::Equal,
+        Token::Constant { val: 1 },
+        Token::Question,
+        Token::Constant { val: 2 },
+        Token::Colon,
+        Token::Constant { val: 3 },
+    ]);
+    let res = Exp::from_expc(parse_exp(tokens, 0).unwrap());
+    let constant = |int: i32, start: usize| {
+        Box::new(Exp::Const {
+            c: Const::Int { int },
+            span: at(start, start + 1),
+        })
+    };
+    let expected = Exp::Assignment {
+        span: at(0, 9),
+        lvalue: Box::new(Exp::Var {
+            name: a,
+            span: at(0, 1),
+        }),
+        rvalue: Box::new(Exp::Assignment {
+            span: at(2, 9),
+            lvalue: Box::new(Exp::Var {
+                name: b,
+                span: at(2, 3),
+            }),
+            rvalue: Box::new(Exp::Conditional {
+                span: at(4, 9),
+                condition: constant(1, 4),
+                then_exp: constant(2, 6),
+                else_exp: constant(3, 8),
+            }),
+        }),
+    };
+    assert_eq!(res, expected);
+}
+
+/// compound assignments bind as loosely as `=` and group to the right with it,
+/// so this is `a *= (b = (a - 1))`.
+#[test]
+fn test_compound_assignment() {
+    let (a, b) = (Symbol::intern("a"), Symbol::intern("b"));
+    let tokens = &mut stream(vec![
+        Token::Identifier { val: a },
+        Token::AsteriskEqual,
+        Token::Identifier { val: b },
+        Token::Equal,
+        Token::Identifier { val: a },
+        Token::Minus,
+        Token::Constant { val: 1 },
+    ]);
+    let res = Exp::from_expc(parse_exp(tokens, 0).unwrap());
+    let expected = Exp::CompoundAssignment {
+        op: BinaryOp::Multiply,
+        span: at(0, 7),
+        lvalue: Box::new(Exp::Var {
+            name: a,
+            span: at(0, 1),
+        }),
+        rvalue: Box::new(Exp::Assignment {
+            span: at(2, 7),
+            lvalue: Box::new(Exp::Var {
+                name: b,
+                span: at(2, 3),
+            }),
+            rvalue: Box::new(Exp::Binary {
+                op: BinaryOp::Subtract,
+                span: at(4, 7),
+                l_exp: Box::new(Exp::Var {
+                    name: a,
+                    span: at(4, 5),
+                }),
+                r_exp: Box::new(Exp::Const {
+                    c: Const::Int { int: 1 },
+                    span: at(6, 7),
+                }),
+            }),
+        }),
+    };
+    assert_eq!(res, expected);
+    assert_eq!(BinaryOp::Multiply.assignment_symbol(), "*=");
+}
+
+/// a postfix `++` binds tighter than `*`, so this is `*(p++) - (--(a[1]))`.
+#[test]
+fn test_increment() {
+    let (p, a) = (Symbol::intern("p"), Symbol::intern("a"));
+    let tokens = &mut stream(vec![
+        Token::Asterisk,
+        Token::Identifier { val: p },
+        Token::PlusPlus,
+        Token::Minus,
+        Token::MinusMinus,
+        Token::Identifier { val: a },
+        Token::OpenBracket,
+        Token::Constant { val: 1 },
+        Token::CloseBracket,
+    ]);
+    let res = Exp::from_expc(parse_exp(tokens, 0).unwrap());
+    let expected = Exp::Binary {
+        op: BinaryOp::Subtract,
+        span: at(0, 9),
+        l_exp: Box::new(Exp::Dereference {
+            span: at(0, 3),
+            exp: Box::new(Exp::Increment {
+                op: IncrementOp::PostIncrement,
+                span: at(1, 3),
+                exp: Box::new(Exp::Var {
+                    name: p,
+                    span: at(1, 2),
+                }),
+            }),
+        }),
+        r_exp: Box::new(Exp::Increment {
+            op: IncrementOp::PreDecrement,
+            span: at(4, 9),
+            exp: Box::new(Exp::Subscript {
+                span: at(5, 9),
+                exp: Box::new(Exp::Var {
+                    name: a,
+                    span: at(5, 6),
+                }),
+                index: Box::new(Exp::Const {
+                    c: Const::Int { int: 1 },
+                    span: at(7, 8),
+                }),
+            }),
+        }),
+    };
+    assert_eq!(res, expected);
+}
+
+/// tests the parsing of `{ int a; { a = 1; } ; }`, after its opening brace.
+#[test]
+fn test_block_items() {
+    let a = Symbol::intern("a");
+    let tokens = &mut stream(vec![
+        Token::TyKeyword { ty: Type::Int },
+        Token::Identifier { val: a },
+        Token::Semicolon,
+        Token::OpenBrace,
+        Token::Identifier { val: a },
+        Token::Equal,
+        Token::Constant { val: 1 },
+        Token::Semicolon,
+        Token::CloseBrace,
+        Token::Semicolon,
+        Token::CloseBrace,
+    ]);
+    let block = parse_block(tokens, at(0, 0)).unwrap();
+    assert_eq!(block.span, at(0, 11));
+    let [BlockItem::Declaration { decl }, BlockItem::Statement {
+        stmt: StatementC::Compound { block: inner },
+    }, BlockItem::Statement {
+        stmt: StatementC::Null { span },
+    }] = block.items.as_slice()
+    else {
+        unreachable!()
+    };
+    assert_eq!(
+        *decl,
+        Declaration {
+            name: a,
+            ty: CType::Int,
+            init: None,
+            storage: None,
+            span: at(0, 3),
+        }
+    );
+    assert_eq!(inner.span, at(3, 9));
+    assert!(matches!(
+        inner.items.as_slice(),
+        [BlockItem::Statement {
+            stmt: StatementC::Expression { span, .. },
+        }] if *span == at(4, 8)
+    ));
+    assert_eq!(*span, at(9, 10));
+}
+
+/// an error in one item of a block is reported, and parsing picks up with the next.
+#[test]
+fn test_block_recovers_per_item() {
+    let tokens = &mut stream(vec![
+        Token::TyKeyword { ty: Type::Int },
+        Token::Constant { val: 1 },
+        Token::Semicolon,
+        Token::RetKeyword,
+        Token::Constant { val: 2 },
+        Token::Semicolon,
+        Token::CloseBrace,
+    ]);
+    let block = parse_block(tokens, at(0, 0)).unwrap();
+    assert_eq!(
+        tokens.errors,
+        vec![ParseError::InvalidSyntax {
+            got: Token::Constant { val: 1 },
+            expected: String::from("a variable name"),
+            span: at(1, 2),
+        }]
+    );
+    assert!(matches!(
+        block.items.as_slice(),
+        [BlockItem::Statement {
+            stmt: StatementC::Return { .. },
+        }]
+    ));
+}
+
+/// tests the parsing of `for (int i = 0; ; i) ;` and `for (;;) ;`
+#[test]
+fn test_for_clauses() {
+    let i = Symbol::intern("i");
+    let tokens = &mut stream(vec![
+        Token::ForKeyword,
+        Token::OpenParens,
+        Token::TyKeyword { ty: Type::Int },
+        Token::Identifier { val: i },
+        Token::Equal,
+        Token::Constant { val: 0 },
+        Token::Semicolon,
+        Token::Semicolon,
+        Token::Identifier { val: i },
+        Token::CloseParens,
+        Token::Semicolon,
+        Token::ForKeyword,
+        Token::OpenParens,
+        Token::Semicolon,
+        Token::Semicolon,
+        Token::CloseParens,
+        Token::Semicolon,
+    ]);
+    let StatementC::For {
+        init: ForInit::Declaration { decl },
+        condition: None,
+        post: Some(post),
+        body,
+        label: None,
+        span,
+    } = parse_statement(tokens).unwrap()
+    else {
+        unreachable!()
+    };
+    assert_eq!(decl.span, at(2, 7));
+    assert_eq!(
+        *post,
+        Exp::Var {
+            name: i,
+            span: at(8, 9)
+        }
+    );
+    assert_eq!(*body, StatementC::Null { span: at(10, 11) });
+    assert_eq!(span, at(0, 11));
+    assert_eq!(
+        parse_statement(tokens).unwrap(),
+        StatementC::For {
+            init: ForInit::Expression { exp: None },
+            condition: None,
+            post: None,
+            body: Box::new(StatementC::Null { span: at(16, 17) }),
+            label: None,
+            span: at(11, 17),
+        }
+    );
+}
+
+/// tests the parsing of `switch (x) case 1: default: ;`, and a `case` missing its `:`
 #[test]
-fn test_variant_success() {
-    let mut tokens = vec![Token::OpenBrace].into_iter();
-    let res = expect_variant(&mut tokens, Token::OpenBrace);
-    assert!(res.is_ok());
+fn test_switch_labels() {
+    let x = Symbol::intern("x");
+    let tokens = &mut stream(vec![
+        Token::SwitchKeyword,
+        Token::OpenParens,
+        Token::Identifier { val: x },
+        Token::CloseParens,
+        Token::CaseKeyword,
+        Token::Constant { val: 1 },
+        Token::Colon,
+        Token::DefaultKeyword,
+        Token::Colon,
+        Token::Semicolon,
+        Token::CaseKeyword,
+        Token::Constant { val: 2 },
+        Token::Semicolon,
+    ]);
+    assert_eq!(
+        parse_statement(tokens).unwrap(),
+        StatementC::Switch {
+            control: Box::new(Exp::Var {
+                name: x,
+                span: at(2, 3)
+            }),
+            body: Box::new(StatementC::Case {
+                value: Box::new(Exp::Const {
+                    c: Const::Int { int: 1 },
+                    span: at(5, 6)
+                }),
+                body: Box::new(StatementC::Default {
+                    body: Box::new(StatementC::Null { span: at(9, 10) }),
+                    label: None,
+                    span: at(7, 10),
+                }),
+                label: None,
+                span: at(4, 10),
+            }),
+            label: None,
+            cases: vec![],
+            span: at(0, 10),
+        }
+    );
+    assert!(matches!(
+        parse_statement(tokens),
+        Err(ParseError::InvalidSyntax {
+            got: Token::Semicolon,
+            ..
+        })
+    ));
 }
 
-/// tests the parsing of `2`
+/// tests the parsing of `break;` and `continue;`, and a `break` missing its `;`
 #[test]
-fn test_constant_exp() {
-    let tokens = &mut vec![Token::Constant { val: 2 }].into_iter();
-    let tokens = &mut tokens.peekable();
-    let res = parse_exp(tokens, 0);
-    let res = res.unwrap();
-    assert_eq!(Exp::from_expc(res), Exp::Const { c: 2 });
+fn test_break_continue() {
+    let tokens = &mut stream(vec![
+        Token::BreakKeyword,
+        Token::Semicolon,
+        Token::ContinueKeyword,
+        Token::Semicolon,
+        Token::BreakKeyword,
+        Token::CloseBrace,
+    ]);
+    assert_eq!(
+        parse_statement(tokens).unwrap(),
+        StatementC::Break {
+            label: None,
+            span: at(0, 2),
+        }
+    );
+    assert_eq!(
+        parse_statement(tokens).unwrap(),
+        StatementC::Continue {
+            label: None,
+            span: at(2, 4),
+        }
+    );
+    assert!(matches!(
+        parse_statement(tokens),
+        Err(ParseError::MissingSemicolon { .. })
+    ));
 }
 
-/// tests the parsing of `~(~(~2))`
+/// tests the parsing of `f(a, g())`, a call whose argument is a call with none,
+/// and `f(1,)`, missing its last argument.
 #[test]
-fn test_nested_cmp_parens() {
-    let tokens = &mut vec![
-        Token::Tilde,
+fn test_function_calls() {
+    let (f, g, a) = (
+        Symbol::intern("f"),
+        Symbol::intern("g"),
+        Symbol::intern("a"),
+    );
+    let tokens = &mut stream(vec![
+        Token::Identifier { val: f },
         Token::OpenParens,
-        Token::Tilde,
+        Token::Identifier { val: a },
+        Token::Comma,
+        Token::Identifier { val: g },
         Token::OpenParens,
-        Token::Tilde,
-        Token::Constant { val: 2 },
         Token::CloseParens,
         Token::CloseParens,
-    ]
-    .into_iter();
-    let tokens = &mut tokens.peekable();
-    let res = parse_exp(tokens, 0);
-    let res = res.unwrap();
+        Token::Semicolon,
+        Token::Identifier { val: f },
+        Token::OpenParens,
+        Token::Constant { val: 1 },
+        Token::Comma,
+        Token::CloseParens,
+    ]);
     assert_eq!(
-        Exp::from_expc(res),
-        Exp::Unary {
-            op: UnaryOp::BitwiseComplement,
-            exp: Box::new(Exp::Unary {
-                op: UnaryOp::BitwiseComplement,
-                exp: Box::new(Exp::Unary {
-                    op: UnaryOp::BitwiseComplement,
-                    exp: Box::new(Exp::Const { c: 2 })
-                })
-            })
+        Exp::from_expc(parse_exp(tokens, 0).unwrap()),
+        Exp::FunctionCall {
+            name: f,
+            args: vec![
+                Exp::Var {
+                    name: a,
+                    span: at(2, 3),
+                },
+                Exp::FunctionCall {
+                    name: g,
+                    args: vec![],
+                    span: at(4, 7),
+                },
+            ],
+            span: at(0, 8),
         }
-    )
+    );
+    tokens.next();
+    assert!(matches!(
+        parse_exp(tokens, 0),
+        Err(ParseError::InvalidSyntax {
+            got: Token::CloseParens,
+            ..
+        })
+    ));
 }
 
-/// tests the parsing of `(((2)))`
+/// tests the parsing of `int f(int a, int b) {}`'s parameters, and of a
+/// parameter list missing a name.
 #[test]
-fn test_nested_parens() {
-    let tokens = &mut vec![
-        Token::OpenParens,
-        Token::OpenParens,
-        Token::OpenParens,
-        Token::Constant { val: 2 },
-        Token::CloseParens,
+fn test_params() {
+    let (a, b) = (Symbol::intern("a"), Symbol::intern("b"));
+    let tokens = &mut stream(vec![
+        Token::TyKeyword { ty: Type::Int },
+        Token::Identifier { val: a },
+        Token::Comma,
+        Token::TyKeyword { ty: Type::Int },
+        Token::Identifier { val: b },
         Token::CloseParens,
-        Token::CloseParens,
-    ]
-    .into_iter();
-    let tokens = &mut tokens.peekable();
-    let res = parse_exp(tokens, 0);
-    let res = res.unwrap();
-    assert_eq!(Exp::from_expc(res), Exp::Const { c: 2 });
+    ]);
+    assert_eq!(
+        parse_params(tokens).unwrap(),
+        vec![
+            Param {
+                name: a,
+                ty: CType::Int,
+                span: at(0, 2),
+            },
+            Param {
+                name: b,
+                ty: CType::Int,
+                span: at(3, 5),
+            },
+        ]
+    );
+    let tokens = &mut stream(vec![Token::TyKeyword { ty: Type::Int }, Token::Comma]);
+    assert!(matches!(
+        parse_params(tokens),
+        Err(ParseError::InvalidSyntax {
+            got: Token::Comma,
+            ..
+        })
+    ));
 }
 
-/// tests the parsing of `return ~(~(~2));`
+/// a `;` after the parameter list makes a declaration, a `{` a definition, and
+/// anything else is neither.
 #[test]
-fn test_return_nested_parens() {
-    let mut tokens = vec![
-        Token::RetKeyword,
-        Token::Tilde,
-        Token::OpenParens,
-        Token::Tilde,
+fn test_function_declaration() {
+    let f = Symbol::intern("f");
+    let tokens = &mut stream(vec![
+        Token::TyKeyword { ty: Type::Int },
+        Token::Identifier { val: f },
         Token::OpenParens,
-        Token::Tilde,
-        Token::Constant { val: 2 },
+        Token::TyKeyword { ty: Type::Void },
         Token::CloseParens,
+        Token::Semicolon,
+    ]);
+    assert_eq!(
+        parse_top_level(tokens).unwrap(),
+        vec![TopLevelC::Declaration {
+            decl: FunDeclC {
+                identifier: f,
+                params: vec![],
+                ret: CType::Int,
+                storage: None,
+                span: at(0, 6),
+            },
+        }]
+    );
+    let tokens = &mut stream(vec![
+        Token::TyKeyword { ty: Type::Int },
+        Token::Identifier { val: f },
+        Token::OpenParens,
+        Token::TyKeyword { ty: Type::Void },
         Token::CloseParens,
+        Token::RetKeyword,
+    ]);
+    let error = parse_top_level(tokens).unwrap_err();
+    assert_eq!(error.to_string(), "expected '{' or ';' before 'return'");
+    assert_eq!(error.span(), at(5, 6));
+}
+
+/// a storage class may come before or after `int`; a name with no `(` after it
+/// is a variable, at file scope as in a block, and a specifier can't repeat.
+#[test]
+fn test_storage_classes() {
+    let x = Symbol::intern("x");
+    let tokens = &mut stream(vec![
+        Token::TyKeyword { ty: Type::Int },
+        Token::StaticKeyword,
+        Token::Identifier { val: x },
+        Token::Equal,
+        Token::Constant { val: 3 },
         Token::Semicolon,
-    ]
-    .into_iter();
-    let res = parse_statement(&mut tokens);
-    assert!(res.is_ok());
+    ]);
+    assert_eq!(
+        parse_top_level(tokens).unwrap(),
+        vec![TopLevelC::Variable {
+            decl: Declaration {
+                name: x,
+                ty: CType::Int,
+                init: Some(Box::new(Exp::Const {
+                    c: Const::Int { int: 3 },
+                    span: at(4, 5),
+                })),
+                storage: Some(StorageClass::Static),
+                span: at(0, 6),
+            },
+        }]
+    );
+    let tokens = &mut stream(vec![
+        Token::ExternKeyword,
+        Token::TyKeyword { ty: Type::Int },
+        Token::Identifier { val: x },
+        Token::Semicolon,
+    ]);
+    assert_eq!(
+        parse_block_item(tokens).unwrap(),
+        vec![BlockItem::Declaration {
+            decl: Declaration {
+                name: x,
+                ty: CType::Int,
+                init: None,
+                storage: Some(StorageClass::Extern),
+                span: at(0, 4),
+            },
+        }]
+    );
+    for (tokens, message, span) in [
+        (
+            vec![Token::StaticKeyword, Token::ExternKeyword],
+            "multiple storage classes in declaration specifiers",
+            at(1, 2),
+        ),
+        (
+            vec![
+                Token::TyKeyword { ty: Type::Int },
+                Token::TyKeyword { ty: Type::Int },
+            ],
+            "two or more data types in declaration specifiers",
+            at(1, 2),
+        ),
+        (
+            vec![Token::StaticKeyword, Token::Identifier { val: x }],
+            "expected a type in declaration specifiers",
+            at(0, 1),
+        ),
+    ] {
+        let error = parse_top_level(&mut stream(tokens)).unwrap_err();
+        assert_eq!(error.to_string(), message);
+        assert_eq!(error.span(), span);
+    }
 }
 
-/// tests the parsing of `1 + 1`
+/// a declaration of several variables is a declaration apiece, each with its
+/// own pointers, dimensions and initializer on top of the specifiers, at file
+/// scope as in a block, and spanning its own declarator.
 #[test]
-fn test_one_plus_one() {
-    let tokens = &mut vec![
-        Token::Constant { val: 1 },
-        Token::Plus,
-        Token::Constant { val: 1 },
-    ]
-    .into_iter();
-    let res = parse_exp(&mut tokens.peekable(), 0);
-    let res = res.unwrap();
-    let expected = ExpC::Binary {
-        op: BinaryOp::Add,
-        l_exp: Box::new(ExpC::Factor {
-            fac: Box::new(FactorC::Const { c: 1 }),
-        }),
-        r_exp: Box::new(ExpC::Factor {
-            fac: Box::new(FactorC::Const { c: 1 }),
-        }),
+fn test_multiple_declarators() {
+    let (p, a, b) = (
+        Symbol::intern("p"),
+        Symbol::intern("a"),
+        Symbol::intern("b"),
+    );
+    let tokens = || {
+        vec![
+            Token::TyKeyword { ty: Type::Int },
+            Token::Asterisk,
+            Token::Identifier { val: p },
+            Token::Equal,
+            Token::Constant { val: 0 },
+            Token::Comma,
+            Token::Identifier { val: a },
+            Token::OpenBracket,
+            Token::Constant { val: 2 },
+            Token::CloseBracket,
+            Token::Comma,
+            Token::Identifier { val: b },
+            Token::Semicolon,
+        ]
     };
-    assert_eq!(res, expected);
+    let decls = [
+        Declaration {
+            name: p,
+            ty: CType::Int.pointer_to(),
+            init: Some(Box::new(Exp::Const {
+                c: Const::Int { int: 0 },
+                span: at(4, 5),
+            })),
+            storage: None,
+            span: at(0, 5),
+        },
+        Declaration {
+            name: a,
+            ty: CType::Int.array_of(2),
+            init: None,
+            storage: None,
+            span: at(6, 10),
+        },
+        Declaration {
+            name: b,
+            ty: CType::Int,
+            init: None,
+            storage: None,
+            span: at(11, 13),
+        },
+    ];
+    let items = parse_block_item(&mut stream(tokens())).unwrap();
+    let block_decls: Vec<&Declaration> = items
+        .iter()
+        .map(|item| match item {
+            BlockItem::Declaration { decl } => decl,
+            _ => panic!("expected a declaration"),
+        })
+        .collect();
+    assert_eq!(block_decls, decls.iter().collect::<Vec<_>>());
+    let items = parse_top_level(&mut stream(tokens())).unwrap();
+    let top_decls: Vec<&Declaration> = items
+        .iter()
+        .map(|item| match item {
+            TopLevelC::Variable { decl } => decl,
+            _ => panic!("expected a variable"),
+        })
+        .collect();
+    assert_eq!(top_decls, decls.iter().collect::<Vec<_>>());
+
+    let mut tokens = tokens();
+    tokens.insert(11, Token::Comma);
+    let error = parse_block_item(&mut stream(tokens)).unwrap_err();
+    assert_eq!(error.to_string(), "expected a variable name before ','");
+    assert_eq!(error.span(), at(11, 12));
 }
 
-/// tests the parsing of `1 + 2 - 3`
+/// type specifiers combine in any order; `signed` and `int` are implied by
+/// the others, and `unsigned` makes any width unsigned. `char` goes only with
+/// `signed` or `unsigned`, and `double` and `_Bool` stand alone.
 #[test]
-fn test_one_plus_two_minus_three() {
-    let tokens = &mut vec![
-        Token::Constant { val: 1 },
-        Token::Plus,
-        Token::Constant { val: 2 },
-        Token::Minus,
-        Token::Constant { val: 3 },
-    ]
-    .into_iter();
-    let res = parse_exp(&mut tokens.peekable(), 0);
-    let res = Exp::from_expc(res.unwrap());
+fn test_type_specifiers() {
+    let ty = |t| Token::TyKeyword { ty: t };
+    for (specifiers, expected) in [
+        (vec![Type::Signed], CType::Int),
+        (vec![Type::Long, Type::Signed, Type::Int], CType::Long),
+        (vec![Type::Unsigned], CType::UInt),
+        (vec![Type::Int, Type::Unsigned], CType::UInt),
+        (vec![Type::Long, Type::Unsigned], CType::ULong),
+        (vec![Type::Unsigned, Type::Int, Type::Long], CType::ULong),
+        (vec![Type::Double], CType::Double),
+        (vec![Type::Short, Type::Int], CType::Short),
+        (vec![Type::Unsigned, Type::Short], CType::UShort),
+        (vec![Type::Char], CType::Char),
+        (vec![Type::Signed, Type::Char], CType::SChar),
+        (vec![Type::Char, Type::Unsigned], CType::UChar),
+        (vec![Type::Bool], CType::Bool),
+    ] {
+        let mut tokens: Vec<Token> = specifiers.into_iter().map(ty).collect();
+        tokens.push(Token::Semicolon);
+        let tokens = &mut stream(tokens);
+        let first = tokens.next().unwrap();
+        assert_eq!(parse_type_specifiers(tokens, first).unwrap().0, expected);
+    }
+    for (specifiers, message) in [
+        (
+            vec![Type::Signed, Type::Unsigned],
+            "both 'signed' and 'unsigned' in declaration specifiers",
+        ),
+        (
+            vec![Type::Unsigned, Type::Int, Type::Unsigned],
+            "duplicate 'unsigned'",
+        ),
+        (
+            vec![Type::Long, Type::Double],
+            "two or more data types in declaration specifiers",
+        ),
+        (
+            vec![Type::Double, Type::Unsigned],
+            "two or more data types in declaration specifiers",
+        ),
+        (
+            vec![Type::Short, Type::Long],
+            "both 'long' and 'short' in declaration specifiers",
+        ),
+        (
+            vec![Type::Char, Type::Int],
+            "two or more data types in declaration specifiers",
+        ),
+        (
+            vec![Type::Short, Type::Char],
+            "two or more data types in declaration specifiers",
+        ),
+        (vec![Type::Char, Type::Char], "duplicate 'char'"),
+        (
+            vec![Type::Unsigned, Type::Bool],
+            "two or more data types in declaration specifiers",
+        ),
+        (
+            vec![Type::Bool, Type::Double],
+            "two or more data types in declaration specifiers",
+        ),
+        (vec![Type::Bool, Type::Bool], "duplicate '_Bool'"),
+    ] {
+        let tokens = &mut stream(specifiers.into_iter().map(ty).collect());
+        let first = tokens.next().unwrap();
+        let error = parse_type_specifiers(tokens, first).unwrap_err();
+        assert_eq!(error.to_string(), message);
+    }
+}
+
+/// each `*` before a declared name wraps its type in another pointer, and `*`
+/// and `&` in front of a factor bind as tightly as the other unary operators,
+/// so `a * *p` multiplies by what `p` points to.
+#[test]
+fn test_pointers() {
+    let tokens = &mut stream(vec![
+        Token::TyKeyword { ty: Type::Long },
+        Token::Asterisk,
+        Token::Asterisk,
+        Token::Identifier {
+            val: Symbol::intern("p"),
+        },
+        Token::Equal,
+        Token::Ampersand,
+        Token::Identifier {
+            val: Symbol::intern("q"),
+        },
+        Token::Semicolon,
+    ]);
+    let [BlockItem::Declaration { decl }] = &parse_block_item(tokens).unwrap()[..] else {
+        panic!("expected a declaration");
+    };
+    assert_eq!(decl.ty, CType::Long.pointer_to().pointer_to());
+    assert_eq!(decl.ty.to_string(), "long **");
+    assert_eq!(
+        decl.init,
+        Some(Box::new(Exp::AddrOf {
+            exp: Box::new(Exp::Var {
+                name: Symbol::intern("q"),
+                span: at(6, 7),
+            }),
+            span: at(5, 7),
+        }))
+    );
+
+    let tokens = &mut stream(vec![
+        Token::Identifier {
+            val: Symbol::intern("a"),
+        },
+        Token::Asterisk,
+        Token::Asterisk,
+        Token::Identifier {
+            val: Symbol::intern("p"),
+        },
+    ]);
+    let res = Exp::from_expc(parse_exp(tokens, 0).unwrap());
     let expected = Exp::Binary {
-        op: BinaryOp::Subtract,
-        l_exp: Box::new(Exp::Binary {
-            op: BinaryOp::Add,
-            l_exp: Box::new(Exp::Const { c: 1 }),
-            r_exp: Box::new(Exp::Const { c: 2 }),
+        op: BinaryOp::Multiply,
+        l_exp: Box::new(Exp::Var {
+            name: Symbol::intern("a"),
+            span: at(0, 1),
         }),
-        r_exp: Box::new(Exp::Const { c: 3 }),
+        r_exp: Box::new(Exp::Dereference {
+            exp: Box::new(Exp::Var {
+                name: Symbol::intern("p"),
+                span: at(3, 4),
+            }),
+            span: at(2, 4),
+        }),
+        span: at(0, 4),
     };
     assert_eq!(res, expected);
 }
 
-/// tests the parsing of `1 + (2 - 3)`
+/// array sizes follow the name, the first the outermost; subscripts chain on a
+/// factor and bind tighter than the prefix operators, so `*a[1][i]`
+/// dereferences the element.
 #[test]
-fn test_one_plus_parens_two_minus_three() {
-    let tokens = &mut vec![
-        Token::Constant { val: 1 },
-        Token::Plus,
-        Token::OpenParens,
-        Token::Constant { val: 2 },
-        Token::Minus,
-        Token::Constant { val: 3 },
-        Token::CloseParens,
-    ]
-    .into_iter();
-    let res = parse_exp(&mut tokens.peekable(), 0);
-    let res = Exp::from_expc(res.unwrap());
-    let expected = Exp::Binary {
-        op: BinaryOp::Add,
-        l_exp: Box::new(Exp::Const { c: 1 }),
-        r_exp: Box::new(Exp::Binary {
-            op: BinaryOp::Subtract,
-            l_exp: Box::new(Exp::Const { c: 2 }),
-            r_exp: Box::new(Exp::Const { c: 3 }),
+fn test_arrays() {
+    let size = |val: i64| Token::Constant { val };
+    let tokens = &mut stream(vec![
+        Token::TyKeyword { ty: Type::Long },
+        Token::Identifier {
+            val: Symbol::intern("grid"),
+        },
+        Token::OpenBracket,
+        size(2),
+        Token::CloseBracket,
+        Token::OpenBracket,
+        size(3),
+        Token::CloseBracket,
+        Token::Semicolon,
+    ]);
+    let [BlockItem::Declaration { decl }] = &parse_block_item(tokens).unwrap()[..] else {
+        panic!("expected a declaration");
+    };
+    assert_eq!(decl.ty, CType::Long.array_of(3).array_of(2));
+    assert_eq!(decl.ty.to_string(), "long[2][3]");
+    assert_eq!(decl.ty.size(), 48);
+    assert_eq!(decl.ty.decayed(), CType::Long.array_of(3).pointer_to());
+
+    let tokens = &mut stream(vec![
+        Token::Asterisk,
+        Token::Identifier {
+            val: Symbol::intern("a"),
+        },
+        Token::OpenBracket,
+        size(1),
+        Token::CloseBracket,
+        Token::OpenBracket,
+        Token::Identifier {
+            val: Symbol::intern("i"),
+        },
+        Token::CloseBracket,
+    ]);
+    let res = Exp::from_expc(parse_exp(tokens, 0).unwrap());
+    let expected = Exp::Dereference {
+        exp: Box::new(Exp::Subscript {
+            exp: Box::new(Exp::Subscript {
+                exp: Box::new(Exp::Var {
+                    name: Symbol::intern("a"),
+                    span: at(1, 2),
+                }),
+                index: Box::new(Exp::Const {
+                    c: Const::Int { int: 1 },
+                    span: at(3, 4),
+                }),
+                span: at(1, 5),
+            }),
+            index: Box::new(Exp::Var {
+                name: Symbol::intern("i"),
+                span: at(6, 7),
+            }),
+            span: at(1, 8),
         }),
+        span: at(0, 8),
     };
     assert_eq!(res, expected);
+
+    for bad in [0, 1 << 31] {
+        let tokens = &mut stream(vec![
+            Token::TyKeyword { ty: Type::Int },
+            Token::Identifier {
+                val: Symbol::intern("a"),
+            },
+            Token::OpenBracket,
+            size(bad),
+            Token::CloseBracket,
+            Token::Semicolon,
+        ]);
+        let Err(ParseError::InvalidArraySize { span, .. }) = parse_block_item(tokens) else {
+            panic!("expected a bad array size for {}", bad);
+        };
+        assert_eq!(span, at(2, 5));
+    }
 }
 
-/// tests the parsing of `1 + 2 * 3`
+/// adjacent string literals are one, spanning them all, and a string is a
+/// primary that can be subscripted.
 #[test]
-fn test_one_plus_two_times_three() {
-    let tokens = &mut vec![
+fn test_string_literals() {
+    let tokens = &mut stream(vec![
+        Token::StringLiteral {
+            val: b"ab".to_vec(),
+        },
+        Token::StringLiteral { val: Vec::new() },
+        Token::StringLiteral { val: b"c".to_vec() },
+        Token::OpenBracket,
         Token::Constant { val: 1 },
-        Token::Plus,
-        Token::Constant { val: 2 },
-        Token::Asterisk,
-        Token::Constant { val: 3 },
-    ]
-    .into_iter();
-    let res = parse_exp(&mut tokens.peekable(), 0);
-    let res = Exp::from_expc(res.unwrap());
-    let expected = Exp::Binary {
-        op: BinaryOp::Add,
-        l_exp: Box::new(Exp::Const { c: 1 }),
-        r_exp: Box::new(Exp::Binary {
-            op: BinaryOp::Multiply,
-            l_exp: Box::new(Exp::Const { c: 2 }),
-            r_exp: Box::new(Exp::Const { c: 3 }),
+        Token::CloseBracket,
+    ]);
+    let res = Exp::from_expc(parse_exp(tokens, 0).unwrap());
+    let expected = Exp::Subscript {
+        exp: Box::new(Exp::String {
+            val: b"abc".to_vec(),
+            span: at(0, 3),
         }),
+        index: Box::new(Exp::Const {
+            c: Const::Int { int: 1 },
+            span: at(4, 5),
+        }),
+        span: at(0, 6),
     };
     assert_eq!(res, expected);
+    assert!(res.is_lvalue());
 }
 
-/// tests the parsing of `1 * 2 - 3 * (4 + 5)`
+/// a structure declaration lists its members, each with a type of its own;
+/// `.` and `->` are postfix operators like subscripts, binding left to right.
 #[test]
-fn test_one_times_two_minus_three_times_parens_four_plus_five() {
-    let tokens = &mut vec![
-        Token::Constant { val: 1 },
+fn test_structs() {
+    let id = |name: &str| Token::Identifier {
+        val: Symbol::intern(name),
+    };
+    let tokens = &mut stream(vec![
+        Token::StructKeyword,
+        id("s"),
+        Token::OpenBrace,
+        Token::TyKeyword { ty: Type::Int },
+        id("a"),
+        Token::Semicolon,
+        Token::StructKeyword,
+        id("s"),
         Token::Asterisk,
+        id("next"),
+        Token::OpenBracket,
         Token::Constant { val: 2 },
-        Token::Minus,
-        Token::Constant { val: 3 },
-        Token::Asterisk,
-        Token::OpenParens,
-        Token::Constant { val: 4 },
-        Token::Plus,
-        Token::Constant { val: 5 },
-        Token::CloseParens,
-    ]
-    .into_iter();
-    let res = parse_exp(&mut tokens.peekable(), 0);
-    let res = Exp::from_expc(res.unwrap());
-    let expected = Exp::Binary {
-        op: BinaryOp::Subtract,
-        l_exp: Box::new(Exp::Binary {
-            op: BinaryOp::Multiply,
-            l_exp: Box::new(Exp::Const { c: 1 }),
-            r_exp: Box::new(Exp::Const { c: 2 }),
-        }),
-        r_exp: Box::new(Exp::Binary {
-            op: BinaryOp::Multiply,
-            l_exp: Box::new(Exp::Const { c: 3 }),
-            r_exp: Box::new(Exp::Binary {
-                op: BinaryOp::Add,
-                l_exp: Box::new(Exp::Const { c: 4 }),
-                r_exp: Box::new(Exp::Const { c: 5 }),
+        Token::CloseBracket,
+        Token::Semicolon,
+        Token::CloseBrace,
+        Token::Semicolon,
+    ]);
+    let [BlockItem::Struct { decl }] = &parse_block_item(tokens).unwrap()[..] else {
+        panic!("expected a structure declaration");
+    };
+    let s = CType::Struct {
+        tag: Symbol::intern("s"),
+    };
+    assert_eq!(
+        *decl,
+        StructDeclaration {
+            tag: Symbol::intern("s"),
+            members: vec![
+                MemberDeclaration {
+                    name: Symbol::intern("a"),
+                    ty: CType::Int,
+                    span: at(3, 6),
+                },
+                MemberDeclaration {
+                    name: Symbol::intern("next"),
+                    ty: s.pointer_to().array_of(2),
+                    span: at(6, 14),
+                },
+            ],
+            span: at(0, 16),
+        }
+    );
+
+    // a declaration without members, and a variable of the structure type
+    let tokens = &mut stream(vec![Token::StructKeyword, id("s"), Token::Semicolon]);
+    let [BlockItem::Struct { decl }] = &parse_block_item(tokens).unwrap()[..] else {
+        panic!("expected a structure declaration");
+    };
+    assert!(decl.members.is_empty());
+    let tokens = &mut stream(vec![
+        Token::StructKeyword,
+        id("s"),
+        id("v"),
+        Token::Semicolon,
+    ]);
+    let [BlockItem::Declaration { decl }] = &parse_block_item(tokens).unwrap()[..] else {
+        panic!("expected a declaration");
+    };
+    assert_eq!(decl.ty, s);
+    assert_eq!(decl.ty.to_string(), "struct s");
+
+    let tokens = &mut stream(vec![
+        id("p"),
+        Token::Arrow,
+        id("next"),
+        Token::OpenBracket,
+        Token::Constant { val: 1 },
+        Token::CloseBracket,
+        Token::Dot,
+        id("a"),
+    ]);
+    let res = Exp::from_expc(parse_exp(tokens, 0).unwrap());
+    let expected = Exp::Dot {
+        exp: Box::new(Exp::Subscript {
+            exp: Box::new(Exp::Arrow {
+                exp: Box::new(Exp::Var {
+                    name: Symbol::intern("p"),
+                    span: at(0, 1),
+                }),
+                member: Symbol::intern("next"),
+                span: at(0, 3),
             }),
+            index: Box::new(Exp::Const {
+                c: Const::Int { int: 1 },
+                span: at(4, 5),
+            }),
+            span: at(0, 6),
         }),
+        member: Symbol::intern("a"),
+        span: at(0, 8),
     };
     assert_eq!(res, expected);
+    assert!(res.is_lvalue());
+
+    // two types in one declaration, and a member without a name
+    for tokens in [
+        vec![
+            Token::StructKeyword,
+            id("s"),
+            Token::TyKeyword { ty: Type::Int },
+            id("v"),
+        ],
+        vec![id("p"), Token::Arrow, Token::Constant { val: 1 }],
+    ] {
+        let tokens = &mut stream(tokens);
+        assert!(parse_block_item(tokens).is_err());
+    }
 }
 
-/// tests the parsing of `-(1 + 1)`
 #[test]
-fn test_negate_one_plus_one() {
-    let tokens = &mut vec![
-        Token::Minus,
+fn test_cast_binds_like_a_prefix_operator() {
+    let (x, p) = (Symbol::intern("x"), Symbol::intern("p"));
+    let tokens = &mut stream(vec![
         Token::OpenParens,
-        Token::Constant { val: 1 },
+        Token::TyKeyword { ty: Type::Long },
+        Token::CloseParens,
+        Token::OpenParens,
+        Token::TyKeyword { ty: Type::Unsigned },
+        Token::CloseParens,
+        Token::Identifier { val: x },
         Token::Plus,
-        Token::Constant { val: 1 },
+        Token::Asterisk,
+        Token::OpenParens,
+        Token::TyKeyword { ty: Type::Int },
+        Token::Asterisk,
         Token::CloseParens,
-    ]
-    .into_iter();
-    let res = parse_exp(&mut tokens.peekable(), 0);
-    let res = Exp::from_expc(res.unwrap());
-    let expected = Exp::Unary {
-        op: UnaryOp::Negate,
-        exp: Box::new(Exp::Binary {
-            op: BinaryOp::Add,
-            l_exp: Box::new(Exp::Const { c: 1 }),
-            r_exp: Box::new(Exp::Const { c: 1 }),
+        Token::Identifier { val: p },
+    ]);
+    let res = Exp::from_expc(parse_exp(tokens, 0).unwrap());
+    let expected = Exp::Binary {
+        op: BinaryOp::Add,
+        span: at(0, 14),
+        l_exp: Box::new(Exp::Cast {
+            target: CType::Long,
+            span: at(0, 7),
+            exp: Box::new(Exp::Cast {
+                target: CType::UInt,
+                span: at(3, 7),
+                exp: Box::new(Exp::Var {
+                    name: x,
+                    span: at(6, 7),
+                }),
+            }),
+        }),
+        r_exp: Box::new(Exp::Dereference {
+            span: at(8, 14),
+            exp: Box::new(Exp::Cast {
+                target: CType::Int.pointer_to(),
+                span: at(9, 14),
+                exp: Box::new(Exp::Var {
+                    name: p,
+                    span: at(13, 14),
+                }),
+            }),
         }),
     };
     assert_eq!(res, expected);
 }
+
+/// a type built twice is interned once, in the context current when it was
+/// built, and a fresh context starts without it.
+#[test]
+fn types_interned_per_context() {
+    for _ in 0..2 {
+        let context = Context::new();
+        let _entered = context.enter();
+        assert_eq!(CType::Int.pointer_to(), CType::Int.pointer_to());
+        assert_ne!(CType::Int.pointer_to(), CType::Long.pointer_to());
+        assert_eq!(
+            CType::Int.array_of(3).pointer_to().to_string(),
+            "int (*)[3]"
+        );
+        // int, long and int[3]
+        assert_eq!(context.types.read().unwrap().types.len(), 3);
+    }
+}
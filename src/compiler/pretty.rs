@@ -0,0 +1,494 @@
+//! Indented pretty-printing of the C AST, one node per line.
+//! Each node names itself and any operator or value it holds, and its children
+//! follow on the lines below, indented one level further. Parentheses are gone
+//! by this point, so the nesting is what shows how an expression grouped.
+use super::{
+    lexer::escape,
+    parser::{
+        Block, BlockItem, CType, Declaration, Exp, ForInit, FunDeclC, FunDefC, ProgramC,
+        StatementC, StorageClass, StructDeclaration, TopLevelC,
+    },
+    symbol::Symbol,
+};
+
+const INDENT: &str = "  ";
+
+/// the whole program, ending in a newline.
+pub fn program(prog: &ProgramC) -> String {
+    let mut res = String::from("Program\n");
+    for item in prog.items.iter() {
+        match item {
+            TopLevelC::Function { fundef } => function(fundef, 1, &mut res),
+            TopLevelC::Declaration { decl } => function_declaration(decl, 1, &mut res),
+            TopLevelC::Variable { decl } => declaration(decl, 1, &mut res),
+            TopLevelC::Struct { decl } => struct_declaration(decl, 1, &mut res),
+        }
+    }
+    res
+}
+
+fn line(depth: usize, text: &str, res: &mut String) {
+    res.push_str(&INDENT.repeat(depth));
+    res.push_str(text);
+    res.push('\n');
+}
+
+/// a node declaring `name` of type `ty`, with its storage class, if it has one,
+/// in between.
+fn named(node: &str, storage: Option<StorageClass>, ty: CType, name: Symbol) -> String {
+    match storage {
+        Some(storage) => format!("{} {} {} {}", node, storage, ty, name),
+        None => format!("{} {} {}", node, ty, name),
+    }
+}
+
+fn function(fundef: &FunDefC, depth: usize, res: &mut String) {
+    line(
+        depth,
+        &named("Function", fundef.storage, fundef.ret, fundef.identifier),
+        res,
+    );
+    for param in fundef.params.iter() {
+        line(
+            depth + 1,
+            &format!("Param {} {}", param.ty, param.name),
+            res,
+        );
+    }
+    block(&fundef.body, depth + 1, res);
+}
+
+fn function_declaration(decl: &FunDeclC, depth: usize, res: &mut String) {
+    line(
+        depth,
+        &named(
+            "FunctionDeclaration",
+            decl.storage,
+            decl.ret,
+            decl.identifier,
+        ),
+        res,
+    );
+    for param in decl.params.iter() {
+        line(
+            depth + 1,
+            &format!("Param {} {}", param.ty, param.name),
+            res,
+        );
+    }
+}
+
+/// the block's items, one after another at `depth`.
+fn block(block: &Block, depth: usize, res: &mut String) {
+    for item in block.items.iter() {
+        match item {
+            BlockItem::Statement { stmt } => statement(stmt, depth, res),
+            BlockItem::Declaration { decl } => declaration(decl, depth, res),
+            BlockItem::Struct { decl } => struct_declaration(decl, depth, res),
+        }
+    }
+}
+
+fn declaration(decl: &Declaration, depth: usize, res: &mut String) {
+    line(
+        depth,
+        &named("Declare", decl.storage, decl.ty, decl.name),
+        res,
+    );
+    if let Some(init) = &decl.init {
+        expression(init, depth + 1, res);
+    }
+}
+
+/// the structure's tag, then its members, if it has any, under it.
+fn struct_declaration(decl: &StructDeclaration, depth: usize, res: &mut String) {
+    line(depth, &format!("Struct {}", decl.tag), res);
+    for member in decl.members.iter() {
+        line(
+            depth + 1,
+            &format!("Member {} {}", member.ty, member.name),
+            res,
+        );
+    }
+}
+
+fn statement(stmt: &StatementC, depth: usize, res: &mut String) {
+    match stmt {
+        StatementC::Return { exp, .. } => {
+            line(depth, "Return", res);
+            expression(exp, depth + 1, res);
+        }
+        StatementC::Expression { exp, .. } => {
+            line(depth, "Expression", res);
+            expression(exp, depth + 1, res);
+        }
+        StatementC::If {
+            condition,
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            line(depth, "If", res);
+            expression(condition, depth + 1, res);
+            statement(then_stmt, depth + 1, res);
+            if let Some(else_stmt) = else_stmt {
+                line(depth, "Else", res);
+                statement(else_stmt, depth + 1, res);
+            }
+        }
+        StatementC::Compound { block: inner } => {
+            line(depth, "Block", res);
+            block(inner, depth + 1, res);
+        }
+        // each clause there is under a heading of its own, then the body
+        StatementC::For {
+            init,
+            condition,
+            post,
+            body,
+            ..
+        } => {
+            line(depth, "For", res);
+            match init {
+                ForInit::Declaration { decl } => {
+                    line(depth + 1, "Init", res);
+                    declaration(decl, depth + 2, res);
+                }
+                ForInit::Expression { exp: Some(exp) } => {
+                    line(depth + 1, "Init", res);
+                    expression(exp, depth + 2, res);
+                }
+                ForInit::Expression { exp: None } => {}
+            }
+            if let Some(condition) = condition {
+                line(depth + 1, "Condition", res);
+                expression(condition, depth + 2, res);
+            }
+            if let Some(post) = post {
+                line(depth + 1, "Post", res);
+                expression(post, depth + 2, res);
+            }
+            statement(body, depth + 1, res);
+        }
+        StatementC::Switch { control, body, .. } => {
+            line(depth, "Switch", res);
+            expression(control, depth + 1, res);
+            statement(body, depth + 1, res);
+        }
+        StatementC::Case { value, body, .. } => {
+            line(depth, "Case", res);
+            expression(value, depth + 1, res);
+            statement(body, depth + 1, res);
+        }
+        StatementC::Default { body, .. } => {
+            line(depth, "Default", res);
+            statement(body, depth + 1, res);
+        }
+        StatementC::Break { .. } => line(depth, "Break", res),
+        StatementC::Continue { .. } => line(depth, "Continue", res),
+        StatementC::Null { .. } => line(depth, "Null", res),
+    }
+}
+
+fn expression(exp: &Exp, depth: usize, res: &mut String) {
+    match exp {
+        Exp::Const { c, .. } => line(depth, &format!("Constant {}", c), res),
+        Exp::Var { name, .. } => line(depth, &format!("Var {}", name), res),
+        Exp::Unary { op, exp, .. } => {
+            line(depth, &format!("Unary {}", op.symbol()), res);
+            expression(exp, depth + 1, res);
+        }
+        Exp::Binary {
+            op, l_exp, r_exp, ..
+        } => {
+            line(depth, &format!("Binary {}", op.symbol()), res);
+            expression(l_exp, depth + 1, res);
+            expression(r_exp, depth + 1, res);
+        }
+        Exp::Conditional {
+            condition,
+            then_exp,
+            else_exp,
+            ..
+        } => {
+            line(depth, "Conditional", res);
+            expression(condition, depth + 1, res);
+            expression(then_exp, depth + 1, res);
+            expression(else_exp, depth + 1, res);
+        }
+        Exp::Assignment { lvalue, rvalue, .. } => {
+            line(depth, "Assign", res);
+            expression(lvalue, depth + 1, res);
+            expression(rvalue, depth + 1, res);
+        }
+        Exp::CompoundAssignment {
+            op, lvalue, rvalue, ..
+        } => {
+            line(depth, &format!("Assign {}", op.assignment_symbol()), res);
+            expression(lvalue, depth + 1, res);
+            expression(rvalue, depth + 1, res);
+        }
+        Exp::FunctionCall { name, args, .. } => {
+            line(depth, &format!("Call {}", name), res);
+            for arg in args.iter() {
+                expression(arg, depth + 1, res);
+            }
+        }
+        Exp::Cast { target, exp, .. } => {
+            line(depth, &format!("Cast {}", target), res);
+            expression(exp, depth + 1, res);
+        }
+        Exp::Dereference { exp, .. } => {
+            line(depth, "Dereference", res);
+            expression(exp, depth + 1, res);
+        }
+        Exp::AddrOf { exp, .. } => {
+            line(depth, "AddrOf", res);
+            expression(exp, depth + 1, res);
+        }
+        Exp::Increment { op, exp, .. } => {
+            let fix = match op.is_postfix() {
+                true => "Postfix",
+                false => "Prefix",
+            };
+            line(depth, &format!("{} {}", fix, op.symbol()), res);
+            expression(exp, depth + 1, res);
+        }
+        Exp::Subscript { exp, index, .. } => {
+            line(depth, "Subscript", res);
+            expression(exp, depth + 1, res);
+            expression(index, depth + 1, res);
+        }
+        Exp::String { val, .. } => line(depth, &format!("String \"{}\"", escape(val)), res),
+        Exp::Dot { exp, member, .. } => {
+            line(depth, &format!("Dot {}", member), res);
+            expression(exp, depth + 1, res);
+        }
+        Exp::Arrow { exp, member, .. } => {
+            line(depth, &format!("Arrow {}", member), res);
+            expression(exp, depth + 1, res);
+        }
+    }
+}
+
+#[cfg(test)]
+use super::{lexer::tokenize, parser::parse};
+
+#[cfg(test)]
+fn pretty_source(source: &str) -> String {
+    program(&parse(tokenize(String::from(source)).unwrap()).unwrap())
+}
+
+/// precedence decides the shape: `*` binds tighter, unless parentheses say otherwise.
+#[test]
+fn print_precedence_as_nesting() {
+    assert_eq!(
+        pretty_source("int main(void) { return 1 + 2 * 3; }"),
+        "Program
+  Function int main
+    Return
+      Binary +
+        Constant 1
+        Binary *
+          Constant 2
+          Constant 3
+"
+    );
+    assert_eq!(
+        pretty_source("int main(void) { return (1 + 2) * 3; }"),
+        "Program
+  Function int main
+    Return
+      Binary *
+        Binary +
+          Constant 1
+          Constant 2
+        Constant 3
+"
+    );
+}
+
+/// binary operators of equal precedence group to the left.
+#[test]
+fn print_left_associativity() {
+    assert_eq!(
+        pretty_source("int main(void) { return 8 - 4 - -~2; }"),
+        "Program
+  Function int main
+    Return
+      Binary -
+        Binary -
+          Constant 8
+          Constant 4
+        Unary -
+          Unary ~
+            Constant 2
+"
+    );
+}
+
+#[test]
+fn print_every_function() {
+    assert_eq!(
+        pretty_source("int one(void) { return 1; } int main(void) { return 7 % 2 ^ 3; }"),
+        "Program
+  Function int one
+    Return
+      Constant 1
+  Function int main
+    Return
+      Binary ^
+        Binary %
+          Constant 7
+          Constant 2
+        Constant 3
+"
+    );
+}
+
+#[test]
+fn print_if_else() {
+    assert_eq!(
+        pretty_source("int main(void) { if (1) if (2) return 3; else return 4; else return 5; }"),
+        "Program
+  Function int main
+    If
+      Constant 1
+      If
+        Constant 2
+        Return
+          Constant 3
+      Else
+        Return
+          Constant 4
+    Else
+      Return
+        Constant 5
+"
+    );
+}
+
+#[test]
+fn print_conditional() {
+    assert_eq!(
+        pretty_source("int main(void) { return 1 ? 2 : 3 ? 4 : 5; }"),
+        "Program
+  Function int main
+    Return
+      Conditional
+        Constant 1
+        Constant 2
+        Conditional
+          Constant 3
+          Constant 4
+          Constant 5
+"
+    );
+}
+
+/// a nested block shows as a node of its own, and a declaration holds its initializer.
+#[test]
+fn print_block_and_declarations() {
+    assert_eq!(
+        pretty_source("int main(void) { int a = 1; { int b; b = a = 2; ; } return a; }"),
+        "Program
+  Function int main
+    Declare int a
+      Constant 1
+    Block
+      Declare int b
+      Expression
+        Assign
+          Var b
+          Assign
+            Var a
+            Constant 2
+      Null
+    Return
+      Var a
+"
+    );
+}
+
+#[test]
+fn print_for_clauses() {
+    assert_eq!(
+        pretty_source(
+            "int main(void) { for (int i = 0; i < 3; ) i = i + 1; for (;;) { break; continue; } }"
+        ),
+        "Program
+  Function int main
+    For
+      Init
+        Declare int i
+          Constant 0
+      Condition
+        Binary <
+          Var i
+          Constant 3
+      Expression
+        Assign
+          Var i
+          Binary +
+            Var i
+            Constant 1
+    For
+      Block
+        Break
+        Continue
+"
+    );
+}
+
+#[test]
+fn print_switch_labels() {
+    assert_eq!(
+        pretty_source("int main(void) { switch (1) { case 2: default: break; } }"),
+        "Program
+  Function int main
+    Switch
+      Constant 1
+      Block
+        Case
+          Constant 2
+          Default
+            Break
+"
+    );
+}
+
+/// parameters are listed before the body, and a call's arguments under it.
+#[test]
+fn print_params_and_calls() {
+    assert_eq!(
+        pretty_source("int f(int a, int b) { return f(a, b + 1); }"),
+        "Program
+  Function int f
+    Param int a
+    Param int b
+    Return
+      Call f
+        Var a
+        Binary +
+          Var b
+          Constant 1
+"
+    );
+}
+
+/// a declaration lists its parameters like a definition, with no body after them.
+#[test]
+fn print_function_declarations() {
+    assert_eq!(
+        pretty_source("int f(int a); int main(void); int main(void) { return f(1); }"),
+        "Program
+  FunctionDeclaration int f
+    Param int a
+  FunctionDeclaration int main
+  Function int main
+    Return
+      Call f
+        Constant 1
+"
+    );
+}
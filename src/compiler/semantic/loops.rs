@@ -0,0 +1,529 @@
+//! Loop and switch labeling. Each loop is named `loop.<n>` and each `switch`
+//! `switch.<n>`, unique across the program, and TACKY builds the labels it
+//! jumps to around them from the names. Each `break` is given the name of the
+//! innermost loop or `switch` around it, and each `continue` of the innermost
+//! loop; one outside all of them is an error. Each `case` and `default` is
+//! named after its `switch`, which gathers them with the value each matches,
+//! and its value has to be a constant no other `case` of the `switch` has.
+use std::collections::HashMap;
+
+use super::{
+    super::{
+        lexer::Span,
+        optimize::const_fold::{fold_binary, fold_unary},
+        parser::{
+            BinaryOp, Block, BlockItem, Const, Exp, FunDefC, ProgramC, StatementC, SwitchCase,
+            TopLevelC,
+        },
+        symbol::Symbol,
+    },
+    SemanticError,
+};
+
+/// names every loop and `switch` in `program`, and points each `break`,
+/// `continue`, `case` and `default` at its own. Like `resolve`, it reports
+/// every error in a single run.
+pub fn label_loops(program: ProgramC) -> Result<ProgramC, Vec<SemanticError>> {
+    let mut labeler = Labeler::default();
+    let items = program
+        .items
+        .into_iter()
+        .map(|item| match item {
+            TopLevelC::Function { fundef } => TopLevelC::Function {
+                fundef: labeler.fundef(fundef),
+            },
+            decl @ (TopLevelC::Declaration { .. }
+            | TopLevelC::Variable { .. }
+            | TopLevelC::Struct { .. }) => decl,
+        })
+        .collect();
+    match labeler.errors.is_empty() {
+        true => Ok(ProgramC { items }),
+        false => Err(labeler.errors),
+    }
+}
+
+/// The `switch` whose body is being labeled: its name, the cases found so far,
+/// and where each value, or `None` for the default, was first used.
+struct Switch {
+    label: Symbol,
+    cases: Vec<SwitchCase>,
+    seen: HashMap<Option<Const>, Span>,
+}
+
+/// Loops and switches are numbered across the whole program. `breaks` is the
+/// name of the innermost loop or `switch` around the statement being labeled,
+/// `continues` that of the innermost loop, and `switch` the innermost `switch`.
+#[derive(Default)]
+struct Labeler {
+    loop_no: u32,
+    switch_no: u32,
+    breaks: Option<Symbol>,
+    continues: Option<Symbol>,
+    switch: Option<Switch>,
+    errors: Vec<SemanticError>,
+}
+
+impl Labeler {
+    fn fundef(&mut self, fundef: FunDefC) -> FunDefC {
+        FunDefC {
+            body: self.block(fundef.body),
+            ..fundef
+        }
+    }
+
+    fn block(&mut self, block: Block) -> Block {
+        Block {
+            items: block
+                .items
+                .into_iter()
+                .map(|item| match item {
+                    BlockItem::Statement { stmt } => BlockItem::Statement {
+                        stmt: self.statement(stmt),
+                    },
+                    decl @ (BlockItem::Declaration { .. } | BlockItem::Struct { .. }) => decl,
+                })
+                .collect(),
+            span: block.span,
+        }
+    }
+
+    fn statement(&mut self, stmt: StatementC) -> StatementC {
+        match stmt {
+            StatementC::For {
+                init,
+                condition,
+                post,
+                body,
+                span,
+                ..
+            } => {
+                let label = Symbol::numbered("loop", self.loop_no);
+                self.loop_no += 1;
+                let breaks = self.breaks.replace(label);
+                let continues = self.continues.replace(label);
+                let body = Box::new(self.statement(*body));
+                self.breaks = breaks;
+                self.continues = continues;
+                StatementC::For {
+                    init,
+                    condition,
+                    post,
+                    body,
+                    label: Some(label),
+                    span,
+                }
+            }
+            StatementC::Switch {
+                control,
+                body,
+                span,
+                ..
+            } => {
+                let label = Symbol::numbered("switch", self.switch_no);
+                self.switch_no += 1;
+                let breaks = self.breaks.replace(label);
+                let outer = self.switch.replace(Switch {
+                    label,
+                    cases: vec![],
+                    seen: HashMap::new(),
+                });
+                let body = Box::new(self.statement(*body));
+                self.breaks = breaks;
+                let switch = std::mem::replace(&mut self.switch, outer)
+                    .expect("the switch being labeled is innermost");
+                StatementC::Switch {
+                    control,
+                    body,
+                    label: Some(label),
+                    cases: switch.cases,
+                    span,
+                }
+            }
+            StatementC::Case {
+                value, body, span, ..
+            } => {
+                let label = match constant_value(&value) {
+                    Some(c) => self.add_case(Some(c), value.span(), span),
+                    None => {
+                        self.errors
+                            .push(SemanticError::NonConstantCase { span: value.span() });
+                        None
+                    }
+                };
+                StatementC::Case {
+                    value,
+                    body: Box::new(self.statement(*body)),
+                    label,
+                    span,
+                }
+            }
+            StatementC::Default { body, span, .. } => {
+                let label = self.add_case(None, span, span);
+                StatementC::Default {
+                    body: Box::new(self.statement(*body)),
+                    label,
+                    span,
+                }
+            }
+            StatementC::Break { span, .. } => {
+                if self.breaks.is_none() {
+                    self.errors.push(SemanticError::BreakOutsideLoop { span });
+                }
+                StatementC::Break {
+                    label: self.breaks,
+                    span,
+                }
+            }
+            StatementC::Continue { span, .. } => {
+                if self.continues.is_none() {
+                    self.errors
+                        .push(SemanticError::ContinueOutsideLoop { span });
+                }
+                StatementC::Continue {
+                    label: self.continues,
+                    span,
+                }
+            }
+            StatementC::If {
+                condition,
+                then_stmt,
+                else_stmt,
+                span,
+            } => StatementC::If {
+                condition,
+                then_stmt: Box::new(self.statement(*then_stmt)),
+                else_stmt: else_stmt.map(|else_stmt| Box::new(self.statement(*else_stmt))),
+                span,
+            },
+            StatementC::Compound { block } => StatementC::Compound {
+                block: self.block(block),
+            },
+            stmt @ (StatementC::Return { .. }
+            | StatementC::Expression { .. }
+            | StatementC::Null { .. }) => stmt,
+        }
+    }
+
+    /// adds the case matching `value`, or the default for `None`, to the
+    /// innermost `switch`, giving the name of its label. `span` is where the
+    /// value is written, and `stmt_span` the whole `case` or `default`.
+    fn add_case(&mut self, value: Option<Const>, span: Span, stmt_span: Span) -> Option<Symbol> {
+        let Some(switch) = &mut self.switch else {
+            self.errors.push(match value {
+                Some(_) => SemanticError::CaseOutsideSwitch { span: stmt_span },
+                None => SemanticError::DefaultOutsideSwitch { span: stmt_span },
+            });
+            return None;
+        };
+        if let Some(previous) = switch.seen.get(&value) {
+            self.errors.push(match value {
+                Some(value) => SemanticError::DuplicateCase {
+                    value: value.value(),
+                    span,
+                    previous: *previous,
+                },
+                None => SemanticError::DuplicateDefault {
+                    span,
+                    previous: *previous,
+                },
+            });
+            return None;
+        }
+        let label = match value {
+            Some(_) => {
+                Symbol::numbered(&format!("{}.case", switch.label), switch.cases.len() as u32)
+            }
+            None => Symbol::intern(&format!("{}.default", switch.label)),
+        };
+        switch.seen.insert(value, span);
+        switch.cases.push(SwitchCase { value, label });
+        Some(label)
+    }
+}
+
+/// the value of `exp` if it is a constant expression, worked out the way the
+/// program would at run time. An operation that would trap isn't constant.
+pub(super) fn constant_value(exp: &Exp) -> Option<Const> {
+    let truth = |holds: bool| Const::Int { int: holds as i32 };
+    match exp {
+        Exp::Const { c, .. } => Some(*c),
+        Exp::Unary { op, exp, .. } => Some(fold_unary(op, constant_value(exp)?)),
+        Exp::Binary {
+            op: BinaryOp::And,
+            l_exp,
+            r_exp,
+            ..
+        } => match constant_value(l_exp)?.is_zero() {
+            true => Some(truth(false)),
+            false => Some(truth(!constant_value(r_exp)?.is_zero())),
+        },
+        Exp::Binary {
+            op: BinaryOp::Or,
+            l_exp,
+            r_exp,
+            ..
+        } => match constant_value(l_exp)?.is_zero() {
+            true => Some(truth(!constant_value(r_exp)?.is_zero())),
+            false => Some(truth(true)),
+        },
+        Exp::Binary {
+            op, l_exp, r_exp, ..
+        } => fold_binary(op, constant_value(l_exp)?, constant_value(r_exp)?),
+        Exp::Conditional {
+            condition,
+            then_exp,
+            else_exp,
+            ..
+        } => match constant_value(condition)?.is_zero() {
+            true => constant_value(else_exp),
+            false => constant_value(then_exp),
+        },
+        Exp::Cast { target, exp, .. } => Some(constant_value(exp)?.convert(*target)),
+        Exp::Var { .. }
+        | Exp::Assignment { .. }
+        | Exp::CompoundAssignment { .. }
+        | Exp::FunctionCall { .. }
+        | Exp::Dereference { .. }
+        | Exp::AddrOf { .. }
+        | Exp::Increment { .. }
+        | Exp::Subscript { .. }
+        | Exp::Dot { .. }
+        | Exp::Arrow { .. }
+        | Exp::String { .. } => None,
+    }
+}
+
+#[cfg(test)]
+use super::super::{lexer::tokenize, parser::parse, unparse};
+
+#[cfg(test)]
+fn label_source(source: &str) -> Result<ProgramC, Vec<SemanticError>> {
+    label_loops(parse(tokenize(String::from(source)).unwrap()).unwrap())
+}
+
+/// loops are named in source order, an outer loop before the ones inside it.
+#[test]
+fn label_nested_loops() {
+    let program = label_source(
+        "int f(void) { for (;;) return 1; } \
+         int main(void) { for (;;) { if (1) for (;;) ; } }",
+    )
+    .unwrap();
+    let label = |stmt: &StatementC| match stmt {
+        StatementC::For { label, .. } => *label,
+        _ => None,
+    };
+    let first = |block: &Block| match &block.items[0] {
+        BlockItem::Statement { stmt } => label(stmt),
+        _ => None,
+    };
+    assert_eq!(
+        first(&program.functions().next().unwrap().body),
+        Some(Symbol::intern("loop.0"))
+    );
+    assert_eq!(
+        first(&program.functions().nth(1).unwrap().body),
+        Some(Symbol::intern("loop.1"))
+    );
+    let BlockItem::Statement {
+        stmt: StatementC::For { body, .. },
+    } = &program.functions().nth(1).unwrap().body.items[0]
+    else {
+        unreachable!()
+    };
+    let StatementC::Compound { block } = body.as_ref() else {
+        unreachable!()
+    };
+    let BlockItem::Statement {
+        stmt: StatementC::If { then_stmt, .. },
+    } = &block.items[0]
+    else {
+        unreachable!()
+    };
+    assert_eq!(label(then_stmt), Some(Symbol::intern("loop.2")));
+}
+
+/// a `break` or `continue` belongs to the innermost loop around it, and once
+/// that loop ends, to the one around that.
+#[test]
+fn label_break_and_continue() {
+    let program =
+        label_source("int main(void) { for (;;) { for (;;) break; continue; } }").unwrap();
+    let BlockItem::Statement {
+        stmt: StatementC::For { body, .. },
+    } = &program.functions().next().unwrap().body.items[0]
+    else {
+        unreachable!()
+    };
+    let StatementC::Compound { block } = body.as_ref() else {
+        unreachable!()
+    };
+    let BlockItem::Statement {
+        stmt: StatementC::For { body, .. },
+    } = &block.items[0]
+    else {
+        unreachable!()
+    };
+    assert_eq!(
+        **body,
+        StatementC::Break {
+            label: Some(Symbol::intern("loop.1")),
+            span: Span { start: 37, end: 43 },
+        }
+    );
+    assert_eq!(
+        block.items[1],
+        BlockItem::Statement {
+            stmt: StatementC::Continue {
+                label: Some(Symbol::intern("loop.0")),
+                span: Span { start: 44, end: 53 },
+            }
+        }
+    );
+    assert!(unparse::program(&program).contains("        continue;\n"));
+}
+
+/// every `break` and `continue` outside a loop is reported, where it is.
+#[test]
+fn break_outside_loop() {
+    let errors = label_source("int main(void) { if (1) break; { continue; } for (;;) ; break; }")
+        .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::BreakOutsideLoop {
+                span: Span { start: 24, end: 30 },
+            },
+            SemanticError::ContinueOutsideLoop {
+                span: Span { start: 33, end: 42 },
+            },
+            SemanticError::BreakOutsideLoop {
+                span: Span { start: 56, end: 62 },
+            },
+        ]
+    );
+    assert_eq!(errors[0].to_string(), "'break' outside of a loop or switch");
+    assert_eq!(errors[1].code(), "continue-outside-loop");
+}
+
+/// a `switch` gathers the cases in its body, at any depth but not inside
+/// another `switch`, and a `break` in it leaves the `switch`, where a
+/// `continue` goes to the loop around it.
+#[test]
+fn label_switch_cases() {
+    let program = label_source(
+        "int main(void) { for (;;) switch (1) { case 3 - 1: { default: break; } \
+         case 0 ? 1 : -1: switch (2) case 2: continue; } }",
+    )
+    .unwrap();
+    let BlockItem::Statement {
+        stmt: StatementC::For { body, .. },
+    } = &program.functions().next().unwrap().body.items[0]
+    else {
+        unreachable!()
+    };
+    let StatementC::Switch { label, cases, .. } = body.as_ref() else {
+        unreachable!()
+    };
+    assert_eq!(*label, Some(Symbol::intern("switch.0")));
+    assert_eq!(
+        *cases,
+        vec![
+            SwitchCase {
+                value: Some(Const::Int { int: 2 }),
+                label: Symbol::intern("switch.0.case.0"),
+            },
+            SwitchCase {
+                value: None,
+                label: Symbol::intern("switch.0.default"),
+            },
+            SwitchCase {
+                value: Some(Const::Int { int: -1 }),
+                label: Symbol::intern("switch.0.case.2"),
+            },
+        ]
+    );
+    let printed = unparse::program(&program);
+    assert!(printed.contains("case (3 - 1):\n"), "{}", printed);
+    let StatementC::Switch { body, .. } = body.as_ref() else {
+        unreachable!()
+    };
+    let StatementC::Compound { block } = body.as_ref() else {
+        unreachable!()
+    };
+    let BlockItem::Statement {
+        stmt: StatementC::Case { body, .. },
+    } = &block.items[1]
+    else {
+        unreachable!()
+    };
+    let StatementC::Switch {
+        body, label, cases, ..
+    } = body.as_ref()
+    else {
+        unreachable!()
+    };
+    assert_eq!(*label, Some(Symbol::intern("switch.1")));
+    assert_eq!(cases.len(), 1);
+    let StatementC::Case { body, .. } = body.as_ref() else {
+        unreachable!()
+    };
+    assert_eq!(
+        **body,
+        StatementC::Continue {
+            label: Some(Symbol::intern("loop.0")),
+            span: Span {
+                start: 107,
+                end: 116
+            },
+        }
+    );
+}
+
+/// each misplaced, repeated or non-constant case is reported, and the cases
+/// of one `switch` don't clash with those of another.
+#[test]
+fn switch_errors() {
+    let errors = label_source(
+        "int main(void) { int x = 1; case 1: default: ; \
+         switch (x) { case 1: case 2 - 1: default: default: case x: ; } \
+         switch (x) { case 1: continue; } }",
+    )
+    .unwrap_err();
+    let codes: Vec<_> = errors.iter().map(SemanticError::code).collect();
+    assert_eq!(
+        codes,
+        vec![
+            "case-outside-switch",
+            "default-outside-switch",
+            "duplicate-case",
+            "duplicate-default",
+            "non-constant-case",
+            "continue-outside-loop",
+        ]
+    );
+    assert_eq!(
+        errors[2],
+        SemanticError::DuplicateCase {
+            value: 1,
+            span: Span { start: 73, end: 78 },
+            previous: Span { start: 65, end: 66 },
+        }
+    );
+    assert_eq!(errors[2].to_string(), "duplicate case value 1");
+    assert_eq!(
+        errors[2].note(),
+        Some((
+            String::from("case 1 was first used here"),
+            Span { start: 65, end: 66 }
+        ))
+    );
+    assert_eq!(
+        errors[4].span(),
+        Span {
+            start: 103,
+            end: 104
+        }
+    );
+}
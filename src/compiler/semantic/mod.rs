@@ -0,0 +1,448 @@
+//! Semantic analysis: the checks a program has to pass after it parses and
+//! before it is lowered to TACKY, each a pass over the C AST of its own.
+//! - `resolve`: gives each variable and structure tag a unique name, and finds
+//!   the names used out of scope or declared twice, structures defined twice,
+//!   and functions and variables mistaken for each other
+//! - `typecheck`: checks every declaration of a function or file-scope variable
+//!   agrees with the others, linkage included, that it is defined at most once,
+//!   that each call passes a function as many arguments as it takes, that
+//!   variables with static storage are initialized to constants, that
+//!   the operators that only take integers aren't given a `double`, that
+//!   pointers are only dereferenced, compared and converted where C allows,
+//!   and that structures are complete where they are used and only have the
+//!   members they declare; it lays out each structure as it is defined, and
+//!   finds the integer divisions by a constant zero to warn about
+//! - `loops`: names each loop and `switch`, for TACKY's labels, finds the
+//!   `break`, `continue`, `case` and `default` statements outside of one, and
+//!   checks each `switch`'s cases
+use std::fmt::Display;
+use thiserror::Error;
+
+use super::{
+    diagnostics::Diagnostic,
+    lexer::Span,
+    parser::{CType, ProgramC},
+    symbol::Symbol,
+    warnings::WarningConfig,
+};
+
+pub mod loops;
+pub mod resolve;
+pub mod typecheck;
+
+pub use loops::label_loops;
+pub use resolve::resolve;
+pub use typecheck::{type_check, type_check_with_warnings};
+
+/// Semantic errors, each pointing at the source it is about.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SemanticError {
+    DuplicateDeclaration {
+        name: Symbol,
+        span: Span,
+        previous: Span,
+    },
+    UndeclaredVariable {
+        name: Symbol,
+        span: Span,
+    },
+    UndeclaredFunction {
+        name: Symbol,
+        span: Span,
+    },
+    NotAFunction {
+        name: Symbol,
+        span: Span,
+        declared: Span,
+    },
+    FunctionAsVariable {
+        name: Symbol,
+        span: Span,
+    },
+    ConflictingDeclaration {
+        name: Symbol,
+        span: Span,
+        previous: Span,
+    },
+    Redefinition {
+        name: Symbol,
+        span: Span,
+        previous: Span,
+    },
+    ConflictingLinkage {
+        name: Symbol,
+        internal: bool,
+        span: Span,
+        previous: Span,
+    },
+    NonConstantInitializer {
+        span: Span,
+    },
+    ExternInitializer {
+        name: Symbol,
+        span: Span,
+    },
+    WrongArgumentCount {
+        name: Symbol,
+        expected: usize,
+        got: usize,
+        span: Span,
+        declared: Span,
+    },
+    InvalidLvalue {
+        op: &'static str,
+        span: Span,
+    },
+    InvalidOperand {
+        op: &'static str,
+        ty: CType,
+        span: Span,
+    },
+    NonIntegerSwitch {
+        ty: CType,
+        span: Span,
+    },
+    IncompatibleTypes {
+        from: CType,
+        to: CType,
+        span: Span,
+    },
+    MismatchedOperands {
+        op: &'static str,
+        l_ty: CType,
+        r_ty: CType,
+        span: Span,
+    },
+    BreakOutsideLoop {
+        span: Span,
+    },
+    ContinueOutsideLoop {
+        span: Span,
+    },
+    CaseOutsideSwitch {
+        span: Span,
+    },
+    DefaultOutsideSwitch {
+        span: Span,
+    },
+    NonConstantCase {
+        span: Span,
+    },
+    DuplicateCase {
+        value: i64,
+        span: Span,
+        previous: Span,
+    },
+    DuplicateDefault {
+        span: Span,
+        previous: Span,
+    },
+    StructRedefinition {
+        tag: Symbol,
+        span: Span,
+        previous: Span,
+    },
+    DuplicateMember {
+        name: Symbol,
+        span: Span,
+        previous: Span,
+    },
+    IncompleteType {
+        ty: CType,
+        span: Span,
+    },
+    NotAStructure {
+        member: Symbol,
+        ty: CType,
+        span: Span,
+    },
+    NoSuchMember {
+        member: Symbol,
+        ty: CType,
+        span: Span,
+    },
+    StructByValue {
+        name: Symbol,
+        span: Span,
+    },
+    TooLarge {
+        ty: CType,
+        span: Span,
+    },
+    NotAScalar {
+        ty: CType,
+        span: Span,
+    },
+    InvalidCast {
+        from: CType,
+        to: CType,
+        span: Span,
+    },
+    StringTooLong {
+        ty: CType,
+        span: Span,
+    },
+}
+
+impl Display for SemanticError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateDeclaration { name, .. } => {
+                write!(f, "'{}' is already declared in this scope", name)
+            }
+            Self::UndeclaredVariable { name, .. } => {
+                write!(f, "use of undeclared variable '{}'", name)
+            }
+            Self::UndeclaredFunction { name, .. } => {
+                write!(f, "call to undeclared function '{}'", name)
+            }
+            Self::NotAFunction { name, .. } => {
+                write!(f, "'{}' is a variable, not a function", name)
+            }
+            Self::FunctionAsVariable { name, .. } => {
+                write!(f, "function '{}' used as a variable", name)
+            }
+            Self::ConflictingDeclaration { name, .. } => {
+                write!(f, "conflicting declarations of '{}'", name)
+            }
+            Self::Redefinition { name, .. } => write!(f, "redefinition of '{}'", name),
+            Self::ConflictingLinkage {
+                name,
+                internal: true,
+                ..
+            } => write!(
+                f,
+                "static declaration of '{}' follows non-static declaration",
+                name
+            ),
+            Self::ConflictingLinkage { name, .. } => write!(
+                f,
+                "non-static declaration of '{}' follows static declaration",
+                name
+            ),
+            Self::NonConstantInitializer { .. } => {
+                write!(f, "initializer of a static variable is not a constant")
+            }
+            Self::ExternInitializer { name, .. } => {
+                write!(f, "'{}' has both 'extern' and an initializer", name)
+            }
+            Self::WrongArgumentCount {
+                name,
+                expected,
+                got,
+                ..
+            } => write!(
+                f,
+                "'{}' takes {} argument{} but {} {} given",
+                name,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                got,
+                if *got == 1 { "was" } else { "were" }
+            ),
+            Self::InvalidLvalue {
+                op: op @ ("&" | "++" | "--"),
+                ..
+            } => {
+                write!(f, "the operand of '{}' must be an lvalue", op)
+            }
+            Self::InvalidLvalue { op, .. } => {
+                write!(f, "the left side of '{}' must be an lvalue", op)
+            }
+            Self::InvalidOperand { op, ty, .. } => {
+                write!(f, "invalid operand of type '{}' to '{}'", ty, op)
+            }
+            Self::NonIntegerSwitch { ty, .. } => {
+                write!(f, "switch on a value of type '{}', not an integer", ty)
+            }
+            Self::IncompatibleTypes { from, to, .. } => {
+                write!(f, "'{}' can't be converted to '{}' implicitly", from, to)
+            }
+            Self::MismatchedOperands { op, l_ty, r_ty, .. } => write!(
+                f,
+                "invalid operands of types '{}' and '{}' to '{}'",
+                l_ty, r_ty, op
+            ),
+            Self::BreakOutsideLoop { .. } => write!(f, "'break' outside of a loop or switch"),
+            Self::ContinueOutsideLoop { .. } => write!(f, "'continue' outside of a loop"),
+            Self::CaseOutsideSwitch { .. } => write!(f, "'case' outside of a switch"),
+            Self::DefaultOutsideSwitch { .. } => write!(f, "'default' outside of a switch"),
+            Self::NonConstantCase { .. } => {
+                write!(f, "a case value must be an integer constant")
+            }
+            Self::DuplicateCase { value, .. } => write!(f, "duplicate case value {}", value),
+            Self::DuplicateDefault { .. } => write!(f, "more than one 'default' in a switch"),
+            Self::StructRedefinition { tag, .. } => write!(f, "redefinition of 'struct {}'", tag),
+            Self::DuplicateMember { name, .. } => write!(f, "duplicate member '{}'", name),
+            Self::IncompleteType { ty, .. } => write!(f, "'{}' is an incomplete type", ty),
+            Self::NotAStructure { member, ty, .. } => write!(
+                f,
+                "request for member '{}' in a value of type '{}', not a structure",
+                member, ty
+            ),
+            Self::NoSuchMember { member, ty, .. } => {
+                write!(f, "'{}' has no member named '{}'", ty, member)
+            }
+            Self::StructByValue { name, .. } => write!(
+                f,
+                "'{}' passes or returns a structure by value, which isn't supported",
+                name
+            ),
+            Self::TooLarge { ty, .. } => write!(f, "'{}' is too large", ty),
+            Self::NotAScalar { ty, .. } => {
+                write!(
+                    f,
+                    "a value of type '{}' used where a scalar is required",
+                    ty
+                )
+            }
+            Self::InvalidCast { from, to, .. } => {
+                write!(f, "'{}' can't be cast to '{}'", from, to)
+            }
+            Self::StringTooLong { ty, .. } => {
+                write!(f, "string literal is too long to initialize '{}'", ty)
+            }
+        }
+    }
+}
+
+impl SemanticError {
+    /// where in the source the error was found.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::DuplicateDeclaration { span, .. }
+            | Self::UndeclaredVariable { span, .. }
+            | Self::UndeclaredFunction { span, .. }
+            | Self::NotAFunction { span, .. }
+            | Self::FunctionAsVariable { span, .. }
+            | Self::ConflictingDeclaration { span, .. }
+            | Self::Redefinition { span, .. }
+            | Self::ConflictingLinkage { span, .. }
+            | Self::NonConstantInitializer { span }
+            | Self::ExternInitializer { span, .. }
+            | Self::WrongArgumentCount { span, .. }
+            | Self::InvalidLvalue { span, .. }
+            | Self::InvalidOperand { span, .. }
+            | Self::NonIntegerSwitch { span, .. }
+            | Self::IncompatibleTypes { span, .. }
+            | Self::MismatchedOperands { span, .. }
+            | Self::BreakOutsideLoop { span }
+            | Self::ContinueOutsideLoop { span }
+            | Self::CaseOutsideSwitch { span }
+            | Self::DefaultOutsideSwitch { span }
+            | Self::NonConstantCase { span }
+            | Self::DuplicateCase { span, .. }
+            | Self::DuplicateDefault { span, .. }
+            | Self::StructRedefinition { span, .. }
+            | Self::DuplicateMember { span, .. }
+            | Self::IncompleteType { span, .. }
+            | Self::NotAStructure { span, .. }
+            | Self::NoSuchMember { span, .. }
+            | Self::StructByValue { span, .. }
+            | Self::TooLarge { span, .. }
+            | Self::NotAScalar { span, .. }
+            | Self::InvalidCast { span, .. }
+            | Self::StringTooLong { span, .. } => *span,
+        }
+    }
+
+    /// the stable name of the error, for tools reading diagnostics.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DuplicateDeclaration { .. } => "duplicate-declaration",
+            Self::UndeclaredVariable { .. } => "undeclared-variable",
+            Self::UndeclaredFunction { .. } => "undeclared-function",
+            Self::NotAFunction { .. } => "not-a-function",
+            Self::FunctionAsVariable { .. } => "function-as-variable",
+            Self::ConflictingDeclaration { .. } => "conflicting-declaration",
+            Self::Redefinition { .. } => "redefinition",
+            Self::ConflictingLinkage { .. } => "conflicting-linkage",
+            Self::NonConstantInitializer { .. } => "non-constant-initializer",
+            Self::ExternInitializer { .. } => "extern-initializer",
+            Self::WrongArgumentCount { .. } => "wrong-argument-count",
+            Self::InvalidLvalue { .. } => "invalid-lvalue",
+            Self::InvalidOperand { .. } => "invalid-operand",
+            Self::NonIntegerSwitch { .. } => "non-integer-switch",
+            Self::IncompatibleTypes { .. } => "incompatible-types",
+            Self::MismatchedOperands { .. } => "mismatched-operands",
+            Self::BreakOutsideLoop { .. } => "break-outside-loop",
+            Self::ContinueOutsideLoop { .. } => "continue-outside-loop",
+            Self::CaseOutsideSwitch { .. } => "case-outside-switch",
+            Self::DefaultOutsideSwitch { .. } => "default-outside-switch",
+            Self::NonConstantCase { .. } => "non-constant-case",
+            Self::DuplicateCase { .. } => "duplicate-case",
+            Self::DuplicateDefault { .. } => "duplicate-default",
+            Self::StructRedefinition { .. } => "struct-redefinition",
+            Self::DuplicateMember { .. } => "duplicate-member",
+            Self::IncompleteType { .. } => "incomplete-type",
+            Self::NotAStructure { .. } => "not-a-structure",
+            Self::NoSuchMember { .. } => "no-such-member",
+            Self::StructByValue { .. } => "struct-by-value",
+            Self::TooLarge { .. } => "too-large",
+            Self::NotAScalar { .. } => "not-a-scalar",
+            Self::InvalidCast { .. } => "invalid-cast",
+            Self::StringTooLong { .. } => "string-too-long",
+        }
+    }
+
+    /// a second place worth showing alongside the error, and why.
+    pub fn note(&self) -> Option<(String, Span)> {
+        match self {
+            Self::DuplicateDeclaration { name, previous, .. } => {
+                Some((format!("'{}' was first declared here", name), *previous))
+            }
+            Self::NotAFunction { name, declared, .. } => {
+                Some((format!("'{}' is declared here", name), *declared))
+            }
+            Self::ConflictingDeclaration { name, previous, .. }
+            | Self::ConflictingLinkage { name, previous, .. } => Some((
+                format!("'{}' was previously declared here", name),
+                *previous,
+            )),
+            Self::Redefinition { name, previous, .. } => {
+                Some((format!("'{}' was first defined here", name), *previous))
+            }
+            Self::WrongArgumentCount { name, declared, .. } => {
+                Some((format!("'{}' is declared here", name), *declared))
+            }
+            Self::DuplicateCase {
+                value, previous, ..
+            } => Some((format!("case {} was first used here", value), *previous)),
+            Self::DuplicateDefault { previous, .. } => {
+                Some((String::from("the first 'default' is here"), *previous))
+            }
+            Self::StructRedefinition { tag, previous, .. } => Some((
+                format!("'struct {}' was first defined here", tag),
+                *previous,
+            )),
+            Self::DuplicateMember { name, previous, .. } => {
+                Some((format!("'{}' was first declared here", name), *previous))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// runs every semantic pass over `program`, in order, giving the program ready
+/// to lower to TACKY. A pass only runs on a program the ones before it accepted.
+pub fn analyze(program: ProgramC) -> Result<ProgramC, Vec<SemanticError>> {
+    label_loops(type_check(resolve(program)?)?)
+}
+
+/// analyzes `program` like `analyze`, adding the warnings type checking found
+/// to `found`, at the levels `config` gives them, whether or not it found
+/// errors too.
+pub fn analyze_with_warnings(
+    program: ProgramC,
+    config: &WarningConfig,
+    found: &mut Vec<Diagnostic>,
+) -> Result<ProgramC, Vec<SemanticError>> {
+    let mut warnings = vec![];
+    let checked = type_check_with_warnings(resolve(program)?, &mut warnings);
+    found.extend(
+        warnings
+            .into_iter()
+            .filter_map(|(warning, span)| config.report(warning, span)),
+    );
+    label_loops(checked?)
+}
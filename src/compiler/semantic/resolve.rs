@@ -0,0 +1,962 @@
+//! Name resolution. Each block is a scope, nested in the one around it; a
+//! declaration adds its name to the innermost scope, where it shadows any
+//! declaration of the name further out, and each use of a name refers to the
+//! innermost declaration in scope. Every variable is renamed `<name>.<n>`,
+//! unique across the program, so that later stages never have to think about
+//! scopes. A `for` loop is a scope of its own, around its body, for a variable
+//! its initializer declares. A function's parameters share a scope with the
+//! outermost block of its body, so the body can't declare them again.
+//! Functions and file-scope variables are declared in the file scope around
+//! all of them, from their first declaration on, and keep their names, since
+//! they have linkage: they are the same object wherever they are declared, in
+//! this file or another, so declaring one again there is no error. A block's
+//! `extern` declaration brings such a name into the block, also unrenamed; a
+//! `static` local is renamed like any other.
+//!
+//! Structure tags have scopes of their own, alongside the variables', and are
+//! renamed `<tag>.<n>` the same way, unique across every program resolved in
+//! the compilation context, since their layouts are kept by tag. A tag used
+//! before any declaration of it is in scope declares it in the innermost scope,
+//! as a structure yet to be defined; so does `struct s;`, even where an outer
+//! `s` is in scope.
+use std::{collections::HashMap, sync::atomic::Ordering};
+
+use super::{
+    super::{
+        context::Context,
+        lexer::Span,
+        parser::{
+            Block, BlockItem, CType, Declaration, Exp, ForInit, FunDeclC, FunDefC,
+            MemberDeclaration, Param, ProgramC, StatementC, StorageClass, StructDeclaration,
+            TopLevelC,
+        },
+        symbol::Symbol,
+    },
+    SemanticError,
+};
+
+/// resolves every name in `program` to its declaration, renaming variables to
+/// their declaration's unique name. Errors don't stop the walk, so one run
+/// reports every one of them, in source order.
+pub fn resolve(program: ProgramC) -> Result<ProgramC, Vec<SemanticError>> {
+    let mut resolver = Resolver {
+        scopes: vec![HashMap::new()],
+        tags: vec![HashMap::new()],
+        ..Resolver::default()
+    };
+    let items = program
+        .items
+        .into_iter()
+        .map(|item| match item {
+            TopLevelC::Function { fundef } => TopLevelC::Function {
+                fundef: resolver.fundef(fundef),
+            },
+            TopLevelC::Declaration { decl } => TopLevelC::Declaration {
+                decl: resolver.fundecl(decl),
+            },
+            TopLevelC::Variable { decl } => TopLevelC::Variable {
+                decl: resolver.global(decl),
+            },
+            TopLevelC::Struct { decl } => TopLevelC::Struct {
+                decl: resolver.struct_declaration(decl),
+            },
+        })
+        .collect();
+    match resolver.errors.is_empty() {
+        true => Ok(ProgramC { items }),
+        false => Err(resolver.errors),
+    }
+}
+
+/// A name in scope: the unique name it was given, where it was declared,
+/// whether it is a function's, and whether it has linkage, which a variable
+/// does when declared at file scope or `extern`.
+struct Resolved {
+    name: Symbol,
+    span: Span,
+    function: bool,
+    linkage: bool,
+}
+
+/// A structure tag in scope: the unique tag it was given, and where it was
+/// defined, if it has been.
+struct ResolvedTag {
+    tag: Symbol,
+    defined: Option<Span>,
+}
+
+/// The scopes enclosing the point being resolved, innermost last, the file
+/// scope first, and the errors found so far; `tags` holds the structure tags
+/// declared in each of the same scopes. Variables are numbered across the
+/// whole program.
+#[derive(Default)]
+struct Resolver {
+    scopes: Vec<HashMap<Symbol, Resolved>>,
+    tags: Vec<HashMap<Symbol, ResolvedTag>>,
+    var_no: u32,
+    errors: Vec<SemanticError>,
+}
+
+impl Resolver {
+    /// the function is in scope in its own body, so it can call itself.
+    fn fundef(&mut self, fundef: FunDefC) -> FunDefC {
+        self.declare_global(fundef.identifier, fundef.span, true);
+        let ret = self.ty(fundef.ret);
+        self.enter();
+        let params = self.params(fundef.params);
+        let body = self.block_items(fundef.body);
+        self.leave();
+        FunDefC {
+            params,
+            ret,
+            body,
+            ..fundef
+        }
+    }
+
+    /// a declaration's parameters are a scope of their own, there only to
+    /// catch the same name given twice.
+    fn fundecl(&mut self, decl: FunDeclC) -> FunDeclC {
+        self.declare_global(decl.identifier, decl.span, true);
+        let ret = self.ty(decl.ret);
+        self.enter();
+        let params = self.params(decl.params);
+        self.leave();
+        FunDeclC {
+            params,
+            ret,
+            ..decl
+        }
+    }
+
+    /// opens a scope, for variables and structure tags alike.
+    fn enter(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.tags.push(HashMap::new());
+    }
+
+    fn leave(&mut self) {
+        self.scopes.pop();
+        self.tags.pop();
+    }
+
+    fn params(&mut self, params: Vec<Param>) -> Vec<Param> {
+        params
+            .into_iter()
+            .map(|param| Param {
+                name: self.declare(param.name, param.span),
+                ty: self.ty(param.ty),
+                ..param
+            })
+            .collect()
+    }
+
+    /// a file-scope variable is in scope in its own initializer too.
+    fn global(&mut self, decl: Declaration) -> Declaration {
+        self.declare_global(decl.name, decl.span, false);
+        Declaration {
+            ty: self.ty(decl.ty),
+            init: decl.init.map(|init| Box::new(self.exp(*init))),
+            ..decl
+        }
+    }
+
+    /// adds `name` to the file scope, unless it is already there. Whether the
+    /// declarations agree on what it is, type checking finds out.
+    fn declare_global(&mut self, name: Symbol, span: Span, function: bool) {
+        self.scopes[0].entry(name).or_insert(Resolved {
+            name,
+            span,
+            function,
+            linkage: true,
+        });
+    }
+
+    fn block(&mut self, block: Block) -> Block {
+        self.enter();
+        let block = self.block_items(block);
+        self.leave();
+        block
+    }
+
+    /// the block's items, in the innermost scope rather than one of their own.
+    fn block_items(&mut self, block: Block) -> Block {
+        let items = block
+            .items
+            .into_iter()
+            .map(|item| match item {
+                BlockItem::Statement { stmt } => BlockItem::Statement {
+                    stmt: self.statement(stmt),
+                },
+                BlockItem::Declaration { decl } => BlockItem::Declaration {
+                    decl: self.declaration(decl),
+                },
+                BlockItem::Struct { decl } => BlockItem::Struct {
+                    decl: self.struct_declaration(decl),
+                },
+            })
+            .collect();
+        Block {
+            items,
+            span: block.span,
+        }
+    }
+
+    /// a variable is in scope from its declaration on, its own initializer included.
+    fn declaration(&mut self, decl: Declaration) -> Declaration {
+        let name = match decl.storage {
+            Some(StorageClass::Extern) => self.declare_extern(decl.name, decl.span),
+            _ => self.declare(decl.name, decl.span),
+        };
+        Declaration {
+            name,
+            ty: self.ty(decl.ty),
+            init: decl.init.map(|init| Box::new(self.exp(*init))),
+            ..decl
+        }
+    }
+
+    /// adds `name`, declared `extern` at `span`, to the innermost scope under its
+    /// own name. It may be declared so more than once, but not alongside a local
+    /// of the same name.
+    fn declare_extern(&mut self, name: Symbol, span: Span) -> Symbol {
+        let scope = self.scopes.last_mut().expect("declaration outside a block");
+        match scope.get(&name) {
+            Some(previous) if !previous.linkage => {
+                self.errors.push(SemanticError::DuplicateDeclaration {
+                    name,
+                    span,
+                    previous: previous.span,
+                })
+            }
+            Some(_) => return name,
+            None => (),
+        }
+        scope.insert(
+            name,
+            Resolved {
+                name,
+                span,
+                function: false,
+                linkage: true,
+            },
+        );
+        name
+    }
+
+    /// adds `name`, declared at `span`, to the innermost scope, and returns the
+    /// unique name it was given.
+    fn declare(&mut self, name: Symbol, span: Span) -> Symbol {
+        let scope = self.scopes.last_mut().expect("declaration outside a block");
+        if let Some(previous) = scope.get(&name) {
+            self.errors.push(SemanticError::DuplicateDeclaration {
+                name,
+                span,
+                previous: previous.span,
+            });
+        }
+        let unique = Symbol::numbered(&name.as_str(), self.var_no);
+        self.var_no += 1;
+        scope.insert(
+            name,
+            Resolved {
+                name: unique,
+                span,
+                function: false,
+                linkage: false,
+            },
+        );
+        unique
+    }
+
+    fn statement(&mut self, stmt: StatementC) -> StatementC {
+        match stmt {
+            StatementC::Return { exp, span } => StatementC::Return {
+                exp: Box::new(self.exp(*exp)),
+                span,
+            },
+            StatementC::Expression { exp, span } => StatementC::Expression {
+                exp: Box::new(self.exp(*exp)),
+                span,
+            },
+            StatementC::If {
+                condition,
+                then_stmt,
+                else_stmt,
+                span,
+            } => StatementC::If {
+                condition: Box::new(self.exp(*condition)),
+                then_stmt: Box::new(self.statement(*then_stmt)),
+                else_stmt: else_stmt.map(|else_stmt| Box::new(self.statement(*else_stmt))),
+                span,
+            },
+            StatementC::Compound { block } => StatementC::Compound {
+                block: self.block(block),
+            },
+            StatementC::For {
+                init,
+                condition,
+                post,
+                body,
+                label,
+                span,
+            } => {
+                self.enter();
+                let init = match init {
+                    ForInit::Declaration { decl } => ForInit::Declaration {
+                        decl: self.declaration(decl),
+                    },
+                    ForInit::Expression { exp } => ForInit::Expression {
+                        exp: exp.map(|exp| Box::new(self.exp(*exp))),
+                    },
+                };
+                let stmt = StatementC::For {
+                    init,
+                    condition: condition.map(|condition| Box::new(self.exp(*condition))),
+                    post: post.map(|post| Box::new(self.exp(*post))),
+                    body: Box::new(self.statement(*body)),
+                    label,
+                    span,
+                };
+                self.leave();
+                stmt
+            }
+            StatementC::Switch {
+                control,
+                body,
+                label,
+                cases,
+                span,
+            } => StatementC::Switch {
+                control: Box::new(self.exp(*control)),
+                body: Box::new(self.statement(*body)),
+                label,
+                cases,
+                span,
+            },
+            StatementC::Case {
+                value,
+                body,
+                label,
+                span,
+            } => StatementC::Case {
+                value: Box::new(self.exp(*value)),
+                body: Box::new(self.statement(*body)),
+                label,
+                span,
+            },
+            StatementC::Default { body, label, span } => StatementC::Default {
+                body: Box::new(self.statement(*body)),
+                label,
+                span,
+            },
+            stmt @ (StatementC::Break { .. }
+            | StatementC::Continue { .. }
+            | StatementC::Null { .. }) => stmt,
+        }
+    }
+
+    fn exp(&mut self, exp: Exp) -> Exp {
+        match exp {
+            Exp::Var { name, span } => match self.lookup(name) {
+                Some(resolved) if resolved.function => {
+                    self.errors
+                        .push(SemanticError::FunctionAsVariable { name, span });
+                    Exp::Var { name, span }
+                }
+                Some(resolved) => Exp::Var {
+                    name: resolved.name,
+                    span,
+                },
+                None => {
+                    self.errors
+                        .push(SemanticError::UndeclaredVariable { name, span });
+                    Exp::Var { name, span }
+                }
+            },
+            exp @ (Exp::Assignment { .. }
+            | Exp::CompoundAssignment { .. }
+            | Exp::Increment { .. }) => self.assignment(exp),
+            exp @ (Exp::Const { .. } | Exp::String { .. }) => exp,
+            // the boxes are reused, which also keeps this frame small for
+            // deeply nested expressions
+            Exp::Unary { op, mut exp, span } => {
+                *exp = self.exp(*exp);
+                Exp::Unary { op, exp, span }
+            }
+            Exp::Binary {
+                op,
+                mut l_exp,
+                mut r_exp,
+                span,
+            } => {
+                *l_exp = self.exp(*l_exp);
+                *r_exp = self.exp(*r_exp);
+                Exp::Binary {
+                    op,
+                    l_exp,
+                    r_exp,
+                    span,
+                }
+            }
+            Exp::Conditional {
+                mut condition,
+                mut then_exp,
+                mut else_exp,
+                span,
+            } => {
+                *condition = self.exp(*condition);
+                *then_exp = self.exp(*then_exp);
+                *else_exp = self.exp(*else_exp);
+                Exp::Conditional {
+                    condition,
+                    then_exp,
+                    else_exp,
+                    span,
+                }
+            }
+            Exp::FunctionCall { name, args, span } => {
+                match self.lookup(name) {
+                    Some(resolved) if !resolved.function => {
+                        let declared = resolved.span;
+                        self.errors.push(SemanticError::NotAFunction {
+                            name,
+                            span,
+                            declared,
+                        });
+                    }
+                    Some(_) => (),
+                    None => self
+                        .errors
+                        .push(SemanticError::UndeclaredFunction { name, span }),
+                }
+                Exp::FunctionCall {
+                    name,
+                    args: args.into_iter().map(|arg| self.exp(arg)).collect(),
+                    span,
+                }
+            }
+            Exp::Cast { target, exp, span } => self.cast(target, exp, span),
+            Exp::Dereference { mut exp, span } => {
+                *exp = self.exp(*exp);
+                Exp::Dereference { exp, span }
+            }
+            Exp::AddrOf { mut exp, span } => {
+                self.require_lvalue(&exp, "&");
+                *exp = self.exp(*exp);
+                Exp::AddrOf { exp, span }
+            }
+            Exp::Subscript { exp, index, span } => self.subscript(exp, index, span),
+            exp @ (Exp::Dot { .. } | Exp::Arrow { .. }) => self.member_access(exp),
+        }
+    }
+
+    /// resolves the structure, or pointer to one, that `.` or `->` picks a
+    /// member out of; members are looked up by type checking. Kept out of
+    /// `exp` so that its frame stays small for deeply nested expressions.
+    fn member_access(&mut self, exp: Exp) -> Exp {
+        match exp {
+            Exp::Dot {
+                mut exp,
+                member,
+                span,
+            } => {
+                *exp = self.exp(*exp);
+                Exp::Dot { exp, member, span }
+            }
+            Exp::Arrow {
+                mut exp,
+                member,
+                span,
+            } => {
+                *exp = self.exp(*exp);
+                Exp::Arrow { exp, member, span }
+            }
+            exp => exp,
+        }
+    }
+
+    /// resolves both sides of an assignment, simple or compound, the left of
+    /// which has to be an lvalue, or the operand of `++` or `--`, which has to
+    /// be one too. Kept out of `exp` so that its frame stays small for deeply
+    /// nested expressions.
+    fn assignment(&mut self, exp: Exp) -> Exp {
+        match exp {
+            Exp::Assignment {
+                mut lvalue,
+                mut rvalue,
+                span,
+            } => {
+                self.require_lvalue(&lvalue, "=");
+                *lvalue = self.exp(*lvalue);
+                *rvalue = self.exp(*rvalue);
+                Exp::Assignment {
+                    lvalue,
+                    rvalue,
+                    span,
+                }
+            }
+            Exp::CompoundAssignment {
+                op,
+                mut lvalue,
+                mut rvalue,
+                span,
+            } => {
+                self.require_lvalue(&lvalue, op.assignment_symbol());
+                *lvalue = self.exp(*lvalue);
+                *rvalue = self.exp(*rvalue);
+                Exp::CompoundAssignment {
+                    op,
+                    lvalue,
+                    rvalue,
+                    span,
+                }
+            }
+            Exp::Increment { op, mut exp, span } => {
+                self.require_lvalue(&exp, op.symbol());
+                *exp = self.exp(*exp);
+                Exp::Increment { op, exp, span }
+            }
+            exp => exp,
+        }
+    }
+
+    /// resolves the operand of a cast, and the tags in the type it casts to.
+    /// Kept out of `exp` so that its frame stays small for deeply nested
+    /// expressions.
+    fn cast(&mut self, target: CType, mut exp: Box<Exp>, span: Span) -> Exp {
+        let target = self.ty(target);
+        *exp = self.exp(*exp);
+        Exp::Cast { target, exp, span }
+    }
+
+    /// resolves both operands of `a[i]`. Kept out of `exp` so that its frame
+    /// stays small for deeply nested expressions.
+    fn subscript(&mut self, mut exp: Box<Exp>, mut index: Box<Exp>, span: Span) -> Exp {
+        *exp = self.exp(*exp);
+        *index = self.exp(*index);
+        Exp::Subscript { exp, index, span }
+    }
+
+    /// reports `exp` unless it is an lvalue, as the operand `op` needs. Kept
+    /// out of `exp` so that its frame stays small for deeply nested expressions.
+    fn require_lvalue(&mut self, exp: &Exp, op: &'static str) {
+        if !exp.is_lvalue() {
+            self.errors.push(SemanticError::InvalidLvalue {
+                op,
+                span: exp.span(),
+            });
+        }
+    }
+
+    /// the innermost declaration of `name` in scope.
+    fn lookup(&self, name: Symbol) -> Option<&Resolved> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(&name))
+    }
+
+    /// a structure's tag is in scope in its own members, so a structure can
+    /// point to another of its kind. A declaration without members refers to
+    /// the structure of that tag in the innermost scope, or declares a new one
+    /// there; a definition defines that one, unless it already has been.
+    fn struct_declaration(&mut self, decl: StructDeclaration) -> StructDeclaration {
+        let scope = self.tags.last_mut().expect("declaration outside a block");
+        let resolved = scope.entry(decl.tag).or_insert_with(|| ResolvedTag {
+            tag: Self::unique_tag(decl.tag),
+            defined: None,
+        });
+        let tag = resolved.tag;
+        if !decl.members.is_empty() {
+            match resolved.defined {
+                Some(previous) => self.errors.push(SemanticError::StructRedefinition {
+                    tag: decl.tag,
+                    span: decl.span,
+                    previous,
+                }),
+                None => resolved.defined = Some(decl.span),
+            }
+        }
+        let members = decl
+            .members
+            .into_iter()
+            .map(|member| MemberDeclaration {
+                ty: self.ty(member.ty),
+                ..member
+            })
+            .collect();
+        StructDeclaration {
+            tag,
+            members,
+            span: decl.span,
+        }
+    }
+
+    /// `ty` with each structure tag in it resolved to its unique one. A tag
+    /// not in scope is declared in the innermost scope.
+    fn ty(&mut self, ty: CType) -> CType {
+        match ty {
+            CType::Struct { tag } => {
+                if let Some(resolved) = self.tags.iter().rev().find_map(|scope| scope.get(&tag)) {
+                    return CType::Struct { tag: resolved.tag };
+                }
+                let unique = Self::unique_tag(tag);
+                let scope = self.tags.last_mut().expect("declaration outside a block");
+                scope.insert(
+                    tag,
+                    ResolvedTag {
+                        tag: unique,
+                        defined: None,
+                    },
+                );
+                CType::Struct { tag: unique }
+            }
+            CType::Pointer { referenced } => self.ty(referenced.get()).pointer_to(),
+            CType::Array { element, size } => self.ty(element.get()).array_of(size),
+            ty => ty,
+        }
+    }
+
+    fn unique_tag(tag: Symbol) -> Symbol {
+        // numbered across the compilation, which may resolve more than one program
+        let no = Context::with(|context| context.tag_no.fetch_add(1, Ordering::Relaxed));
+        Symbol::numbered(&tag.as_str(), no)
+    }
+}
+
+#[cfg(test)]
+use super::super::{lexer::tokenize, parser::parse, unparse};
+
+#[cfg(test)]
+fn resolve_source(source: &str) -> Result<ProgramC, Vec<SemanticError>> {
+    resolve(parse(tokenize(String::from(source)).unwrap()).unwrap())
+}
+
+/// an inner declaration shadows an outer one until its block ends, and every
+/// declaration gets a name of its own, even across functions.
+#[test]
+fn shadowing_renames() {
+    let resolved = resolve_source(
+        "int f(void) { int a = 1; return a; } \
+         int main(void) { int a = 2; { int a = a + 1; a = 4; } return a; }",
+    )
+    .unwrap();
+    assert_eq!(
+        unparse::program(&resolved),
+        "int f(void) {
+    int a.0 = 1;
+    return a.0;
+}
+
+int main(void) {
+    int a.1 = 2;
+    {
+        int a.2 = (a.2 + 1);
+        (a.2 = 4);
+    }
+    return a.1;
+}
+"
+    );
+}
+
+#[test]
+fn duplicate_declaration() {
+    let errors =
+        resolve_source("int main(void) { int a; { int a; } int a = 3; return a; }").unwrap_err();
+    assert_eq!(
+        errors,
+        vec![SemanticError::DuplicateDeclaration {
+            name: Symbol::intern("a"),
+            span: Span { start: 35, end: 45 },
+            previous: Span { start: 17, end: 23 },
+        }]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "'a' is already declared in this scope"
+    );
+    assert_eq!(
+        errors[0].note(),
+        Some((
+            String::from("'a' was first declared here"),
+            Span { start: 17, end: 23 }
+        ))
+    );
+    let errors = resolve_source("int main(void) { int a = 1, b, a; return a; }").unwrap_err();
+    assert_eq!(
+        errors,
+        vec![SemanticError::DuplicateDeclaration {
+            name: Symbol::intern("a"),
+            span: Span { start: 31, end: 33 },
+            previous: Span { start: 17, end: 26 },
+        }]
+    );
+}
+
+/// a variable goes out of scope with its block, and each bad use is reported.
+#[test]
+fn undeclared_variables() {
+    let errors = resolve_source("int main(void) { { int a = 1; } b = 2; return a; }").unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::UndeclaredVariable {
+                name: Symbol::intern("b"),
+                span: Span { start: 32, end: 33 },
+            },
+            SemanticError::UndeclaredVariable {
+                name: Symbol::intern("a"),
+                span: Span { start: 46, end: 47 },
+            },
+        ]
+    );
+}
+
+/// only an lvalue, a variable or a dereferenced pointer, can be assigned to or
+/// have its address taken.
+#[test]
+fn assign_to_non_variable() {
+    let errors = resolve_source(
+        "int main(void) { int a; a + 1 = 2; *&a = 3; int *p = &(a + 1); return (a) = 3; }",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::InvalidLvalue {
+                op: "=",
+                span: Span { start: 24, end: 29 },
+            },
+            SemanticError::InvalidLvalue {
+                op: "&",
+                span: Span { start: 54, end: 61 },
+            },
+        ]
+    );
+    assert_eq!(errors[0].code(), "invalid-lvalue");
+    assert_eq!(
+        errors[1].to_string(),
+        "the operand of '&' must be an lvalue"
+    );
+}
+
+/// a variable declared by a `for` initializer is in scope in the loop only, and
+/// may shadow one outside it; one declared in the body is fresh on each pass.
+#[test]
+fn for_loop_scope() {
+    let resolved = resolve_source(
+        "int main(void) { int i = 5; for (int i = 0; i < 3; i = i + 1) { int i = 7; } return i; }",
+    )
+    .unwrap();
+    assert_eq!(
+        unparse::program(&resolved),
+        "int main(void) {
+    int i.0 = 5;
+    for (int i.1 = 0; (i.1 < 3); (i.1 = (i.1 + 1)))
+        {
+            int i.2 = 7;
+        }
+    return i.0;
+}
+"
+    );
+    let errors = resolve_source("int main(void) { for (int j = 0; ; ) ; return j; }").unwrap_err();
+    assert_eq!(errors[0].code(), "undeclared-variable");
+}
+
+/// parameters are renamed like variables, and share a scope with the body's
+/// outermost block, though not with a block nested in it.
+#[test]
+fn parameter_scope() {
+    let resolved =
+        resolve_source("int f(int a, int b) { { int a = b; } return f(a, b); }").unwrap();
+    assert_eq!(
+        unparse::program(&resolved),
+        "int f(int a.0, int b.1) {
+    {
+        int a.2 = b.1;
+    }
+    return f(a.0, b.1);
+}
+"
+    );
+    let errors = resolve_source("int f(int a, int a) { int b; int a; return b; }").unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::DuplicateDeclaration {
+                name: Symbol::intern("a"),
+                span: Span { start: 13, end: 18 },
+                previous: Span { start: 6, end: 11 },
+            },
+            SemanticError::DuplicateDeclaration {
+                name: Symbol::intern("a"),
+                span: Span { start: 29, end: 35 },
+                previous: Span { start: 13, end: 18 },
+            },
+        ]
+    );
+}
+
+/// a function is in scope from its first declaration on, keeping its name, and a
+/// variable of the same name shadows it like any other.
+#[test]
+fn function_scope() {
+    let resolved = resolve_source(
+        "int g(int f); int f(int a) { return a ? f(a - 1) : g(a); } int g(int f) { return f; }",
+    )
+    .unwrap();
+    assert_eq!(
+        unparse::program(&resolved),
+        "int g(int f.0);
+
+int f(int a.1) {
+    return (a.1 ? f((a.1 - 1)) : g(a.1));
+}
+
+int g(int f.2) {
+    return f.2;
+}
+"
+    );
+    let errors = resolve_source(
+        "int main(void) { int a = 1; return a() + main + later(); } int later(void) { return 0; }",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::NotAFunction {
+                name: Symbol::intern("a"),
+                span: Span { start: 35, end: 38 },
+                declared: Span { start: 17, end: 27 },
+            },
+            SemanticError::FunctionAsVariable {
+                name: Symbol::intern("main"),
+                span: Span { start: 41, end: 45 },
+            },
+            SemanticError::UndeclaredFunction {
+                name: Symbol::intern("later"),
+                span: Span { start: 48, end: 55 },
+            },
+        ]
+    );
+    assert_eq!(errors[2].to_string(), "call to undeclared function 'later'");
+}
+
+/// a variable declared at file scope or `extern` keeps its name, so every
+/// declaration of it means the same object; a `static` local gets a name of its
+/// own like any other local. A local `extern` can't share a scope with a
+/// variable without linkage.
+#[test]
+fn linkage_scope() {
+    let resolved = resolve_source(
+        "int x = 1; int f(void) { int x = 2; { extern int x; static int s; return x + s; } }",
+    )
+    .unwrap();
+    assert_eq!(
+        unparse::program(&resolved),
+        "int x = 1;
+
+int f(void) {
+    int x.0 = 2;
+    {
+        extern int x;
+        static int s.1;
+        return (x + s.1);
+    }
+}
+"
+    );
+    let errors =
+        resolve_source("int main(void) { extern int a; extern int a; int b; extern int b; }")
+            .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![SemanticError::DuplicateDeclaration {
+            name: Symbol::intern("b"),
+            span: Span { start: 52, end: 65 },
+            previous: Span { start: 45, end: 51 },
+        }]
+    );
+}
+
+/// each structure gets a tag of its own, even one declared with the same tag
+/// as another in an outer scope; one declared without members there is the
+/// outer one until it is declared again. A structure can only be defined once
+/// in a scope.
+#[test]
+fn struct_tag_scope() {
+    let resolved = resolve_source(
+        "struct s { int a; }; struct s *p; \
+         int main(void) { struct s *q; struct s; struct s *r; struct t *u; return 0; }",
+    )
+    .unwrap();
+    let tags: Vec<Symbol> = resolved
+        .items
+        .iter()
+        .flat_map(|item| match item {
+            TopLevelC::Struct { decl } => vec![decl.tag],
+            TopLevelC::Variable { decl } => vec![pointee_tag(decl.ty)],
+            TopLevelC::Function { fundef } => fundef
+                .body
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    BlockItem::Declaration { decl } => Some(pointee_tag(decl.ty)),
+                    BlockItem::Struct { decl } => Some(decl.tag),
+                    BlockItem::Statement { .. } => None,
+                })
+                .collect(),
+            TopLevelC::Declaration { .. } => vec![],
+        })
+        .collect();
+    let [outer, p, q, inner, r, u] = tags[..] else {
+        panic!("expected six tags, got {:?}", tags);
+    };
+    assert_eq!([p, q], [outer, outer]);
+    assert_eq!(r, inner);
+    assert_ne!(inner, outer);
+    assert!(outer.as_str().starts_with("s.") && u.as_str().starts_with("t."));
+
+    let errors =
+        resolve_source("struct s { int a; }; struct s; struct s { long b; }; int main(void) { struct s { int c; }; return 0; }")
+            .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![SemanticError::StructRedefinition {
+            tag: Symbol::intern("s"),
+            span: Span { start: 31, end: 52 },
+            previous: Span { start: 0, end: 20 },
+        }]
+    );
+    assert_eq!(errors[0].to_string(), "redefinition of 'struct s'");
+}
+
+#[cfg(test)]
+fn pointee_tag(ty: CType) -> Symbol {
+    if let CType::Pointer { referenced } = ty {
+        if let CType::Struct { tag } = referenced.get() {
+            return tag;
+        }
+    }
+    panic!("expected a pointer to a structure, got {}", ty)
+}
+
+/// each compilation numbers its structure tags from 0, whatever another
+/// compiled before it.
+#[test]
+fn tags_numbered_per_context() {
+    for _ in 0..2 {
+        let _entered = Context::new().enter();
+        let resolved = resolve_source("struct s { int a; }; struct t { int b; };").unwrap();
+        let tags: Vec<String> = resolved
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                TopLevelC::Struct { decl } => Some(decl.tag.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tags, ["s.0", "t.1"]);
+    }
+}
@@ -0,0 +1,1776 @@
+//! Type checking. Every variable is a `char`, a `short`, an `int` or a `long`,
+//! any of them signed or unsigned, a `_Bool`, a `double`, a pointer, an array
+//! or a structure, and a function's type is those of its parameters and what it
+//! returns. The declarations of a name with linkage, a function or a variable
+//! declared at file scope or `extern`, all have to agree on its type and on its
+//! linkage; `static` gives it internal linkage, and `extern`, or no storage
+//! class on a function, the linkage an earlier declaration gave it. It may be
+//! defined only once, a variable by a declaration with an initializer, and each
+//! call has to pass a function as many arguments as it takes. Every conversion
+//! the program makes is spelled out as a `Cast`: an operand of a type narrower
+//! than `int` to `int`, the operands of a binary operator to their common type,
+//! the wider of the two, or the unsigned one if they are as wide, and a value
+//! to the type of whatever it is assigned, returned, passed or matched against.
+//! A variable with static storage is initialized before the program runs, so
+//! its initializer has to be a constant, and is replaced by its value in the
+//! variable's type.
+//! A shift is carried out in the type of its left operand, promoted, not in a
+//! common type, and its count is converted to that type to match it.
+//! A `double` can't be the operand of `~`, `%`, a bitwise operator or a shift,
+//! nor what a `switch` is on or a case's value.
+//! A pointer can only be dereferenced, compared with `==` and `!=` to a pointer
+//! of its type or a null pointer constant, an integer constant that is zero,
+//! ordered against a pointer of its type, or tested for being null. It is
+//! assigned, returned and passed only as a pointer of the same type or a
+//! `_Bool`, which gets whether it isn't null, and the only value converted to
+//! one implicitly is a null pointer constant, so the address of a variable, not
+//! being constant, can't initialize a variable with static storage; a string
+//! literal, whose `char` array is, can. An integer can also be added to a
+//! pointer, or subtracted from one, moving it that many elements along, and two
+//! pointers of the same type subtracted to count the elements between them;
+//! `a[i]` is `*(a + i)`.
+//! A cast converts any scalar to any other, except a pointer to or from a
+//! `double`. A `_Bool` is an unsigned integer that holds only 0 or 1: any
+//! value but zero converts to 1.
+//! An array is used as a pointer to its first element everywhere but as the
+//! operand of `&`, and can't be assigned to, nor initialized but a `char`
+//! array by a string literal that fits in it.
+//! A structure is laid out when it is defined, each member after the last at
+//! the next multiple of its own alignment, and can't have the same member
+//! twice, nor one of its own type. Until then it is incomplete: nothing can be
+//! declared of its type, only pointed to, and a pointer to it can't be moved
+//! along. A structure is assigned, initialized and chosen by `?:` as a whole,
+//! only from one of its own type; it can't be an operand of anything else, nor
+//! be cast, nor passed to a function or returned from one. `.` and `->` pick
+//! out a member of a complete structure, by value or through a pointer.
+//! A compound assignment like `x += y` is checked as `x = x + y` would be,
+//! but only its right side is converted in the tree, as the lvalue is
+//! evaluated once; its value has the lvalue's type. `++` and `--` add or
+//! subtract one in the same way, from a scalar that isn't an array.
+//! An integer division or remainder by a constant zero is warned about, as
+//! `-Wdiv-by-zero`, once its divisor has the operation's type.
+//! Resolution has already made sure every call is to a function declared
+//! before it.
+use std::collections::HashMap;
+
+use super::{
+    super::{
+        lexer::Span,
+        parser::{
+            BinaryOp, Block, BlockItem, CType, Const, Declaration, Exp, ForInit, Member, ProgramC,
+            StatementC, StorageClass, StructDeclaration, StructLayout, TopLevelC, UnaryOp,
+        },
+        symbol::Symbol,
+        warnings::Warning,
+    },
+    loops::constant_value,
+    SemanticError,
+};
+
+/// checks the declarations and definition of every name with linkage in
+/// `program` against each other, and each call against them, makes every
+/// conversion explicit, and folds the initializers of variables with static
+/// storage. Like `resolve`, it reports every error in a single run.
+pub fn type_check(program: ProgramC) -> Result<ProgramC, Vec<SemanticError>> {
+    type_check_with_warnings(program, &mut vec![])
+}
+
+/// type checks `program` like `type_check`, adding each warning it deserves
+/// to `warnings` with where it was found, errors or not.
+pub fn type_check_with_warnings(
+    mut program: ProgramC,
+    warnings: &mut Vec<(Warning, Span)>,
+) -> Result<ProgramC, Vec<SemanticError>> {
+    let mut checker = Checker::default();
+    for item in program.items.iter_mut() {
+        checker.top_level(item);
+    }
+    warnings.append(&mut checker.warnings);
+    match checker.errors.is_empty() {
+        true => Ok(program),
+        false => Err(checker.errors),
+    }
+}
+
+/// The type of a name with linkage.
+#[derive(PartialEq)]
+enum SymbolType {
+    Variable { ty: CType },
+    Function { params: Vec<CType>, ret: CType },
+}
+
+/// What the first declaration of a name with linkage said about it: its type,
+/// and whether it is visible outside the file; and where its definition is,
+/// once there is one.
+struct Signature {
+    ty: SymbolType,
+    global: bool,
+    declared: Span,
+    defined: Option<Span>,
+}
+
+/// The names with linkage declared so far, the types of the variables without
+/// linkage, which resolution made unique, and the errors and warnings found.
+/// `ret` is what the function being checked returns, and `switches` the types
+/// of the control expressions of the `switch`es around the statement being
+/// checked.
+#[derive(Default)]
+struct Checker {
+    symbols: HashMap<Symbol, Signature>,
+    locals: HashMap<Symbol, CType>,
+    ret: Option<CType>,
+    switches: Vec<CType>,
+    errors: Vec<SemanticError>,
+    warnings: Vec<(Warning, Span)>,
+}
+
+impl Checker {
+    /// the function is declared before its body is checked, so it can call itself.
+    fn top_level(&mut self, item: &mut TopLevelC) {
+        let (name, span, storage) = (item.identifier(), item.span(), item.storage());
+        let (ty, defined) = match item {
+            TopLevelC::Function { fundef } => (
+                SymbolType::Function {
+                    params: fundef.params.iter().map(|param| param.ty).collect(),
+                    ret: fundef.ret,
+                },
+                Some(span),
+            ),
+            TopLevelC::Declaration { decl } => (
+                SymbolType::Function {
+                    params: decl.params.iter().map(|param| param.ty).collect(),
+                    ret: decl.ret,
+                },
+                None,
+            ),
+            TopLevelC::Struct { decl } => return self.struct_declaration(decl),
+            TopLevelC::Variable { decl } => {
+                if storage != Some(StorageClass::Extern) {
+                    self.object_type(decl.ty, span);
+                }
+                self.static_init(decl);
+                (
+                    SymbolType::Variable { ty: decl.ty },
+                    decl.init.is_some().then_some(span),
+                )
+            }
+        };
+        if let SymbolType::Function { params, ret } = &ty {
+            if params.iter().chain([ret]).any(CType::is_struct) {
+                self.errors
+                    .push(SemanticError::StructByValue { name, span });
+            }
+        }
+        // a function declared without a storage class is `extern`; a variable
+        // is a definition, if only a tentative one, with external linkage
+        let inherits = match ty {
+            SymbolType::Function { .. } => storage != Some(StorageClass::Static),
+            SymbolType::Variable { .. } => storage == Some(StorageClass::Extern),
+        };
+        self.declare(name, ty, storage, inherits, span, defined);
+        if let TopLevelC::Function { fundef } = item {
+            self.locals
+                .extend(fundef.params.iter().map(|param| (param.name, param.ty)));
+            self.ret = Some(fundef.ret);
+            self.block(&mut fundef.body);
+            self.ret = None;
+        }
+    }
+
+    /// records a declaration of `name`, checking it against earlier ones. One
+    /// that `inherits` its linkage takes that of an earlier declaration, if any.
+    fn declare(
+        &mut self,
+        name: Symbol,
+        ty: SymbolType,
+        storage: Option<StorageClass>,
+        inherits: bool,
+        span: Span,
+        defined: Option<Span>,
+    ) {
+        let internal = storage == Some(StorageClass::Static);
+        let Some(signature) = self.symbols.get_mut(&name) else {
+            self.symbols.insert(
+                name,
+                Signature {
+                    ty,
+                    global: !internal,
+                    declared: span,
+                    defined,
+                },
+            );
+            return;
+        };
+        if signature.ty != ty {
+            self.errors.push(SemanticError::ConflictingDeclaration {
+                name,
+                span,
+                previous: signature.declared,
+            });
+        } else if !inherits && signature.global == internal {
+            self.errors.push(SemanticError::ConflictingLinkage {
+                name,
+                internal,
+                span,
+                previous: signature.declared,
+            });
+        }
+        match (signature.defined, defined) {
+            (Some(previous), Some(_)) => self.errors.push(SemanticError::Redefinition {
+                name,
+                span,
+                previous,
+            }),
+            (None, Some(_)) => signature.defined = defined,
+            _ => (),
+        }
+    }
+
+    /// replaces the initializer of a variable with static storage by its value,
+    /// converted to the variable's type. A string literal stays as it is, for
+    /// a `char` array to start out as or a `char *` to point to.
+    fn static_init(&mut self, decl: &mut Declaration) {
+        if self.string_init(decl) {
+            return;
+        }
+        if let Some(init) = &mut decl.init {
+            let ty = self.exp(init);
+            self.assign(init, ty, decl.ty);
+            match constant_value(init) {
+                Some(c) => {
+                    **init = Exp::Const {
+                        c,
+                        span: init.span(),
+                    }
+                }
+                None if matches!(**init, Exp::String { .. }) => {}
+                None => self
+                    .errors
+                    .push(SemanticError::NonConstantInitializer { span: init.span() }),
+            }
+        }
+    }
+
+    /// whether `decl` is of a `char` array initialized by a string literal,
+    /// whose bytes it starts out as, padded with nulls. The literal's null
+    /// is dropped if the array is just long enough for its other bytes; a
+    /// literal with more than that is reported.
+    fn string_init(&mut self, decl: &Declaration) -> bool {
+        let (Some(init), CType::Array { element, size }) = (&decl.init, decl.ty) else {
+            return false;
+        };
+        let Exp::String { val, span } = &**init else {
+            return false;
+        };
+        if !matches!(element.get(), CType::Char | CType::SChar | CType::UChar) {
+            return false;
+        }
+        if val.len() > size as usize {
+            self.errors.push(SemanticError::StringTooLong {
+                ty: decl.ty,
+                span: *span,
+            });
+        }
+        true
+    }
+
+    fn block(&mut self, block: &mut Block) {
+        for item in block.items.iter_mut() {
+            match item {
+                BlockItem::Statement { stmt } => self.statement(stmt),
+                BlockItem::Declaration { decl } => self.declaration(decl),
+                BlockItem::Struct { decl } => self.struct_declaration(decl),
+            }
+        }
+    }
+
+    /// lays out a structure's members, unless it is only declared.
+    fn struct_declaration(&mut self, decl: &StructDeclaration) {
+        if decl.members.is_empty() {
+            return;
+        }
+        let mut seen = HashMap::new();
+        let mut members = Vec::with_capacity(decl.members.len());
+        let (mut size, mut alignment) = (0, 1);
+        for member in decl.members.iter() {
+            if let Some(&previous) = seen.get(&member.name) {
+                self.errors.push(SemanticError::DuplicateMember {
+                    name: member.name,
+                    span: member.span,
+                    previous,
+                });
+                continue;
+            }
+            seen.insert(member.name, member.span);
+            if !member.ty.is_complete() {
+                self.errors.push(SemanticError::IncompleteType {
+                    ty: member.ty,
+                    span: member.span,
+                });
+            }
+            let offset = usize::next_multiple_of(size, member.ty.alignment());
+            members.push(Member {
+                name: member.name,
+                ty: member.ty,
+                offset,
+            });
+            size = offset + member.ty.size();
+            alignment = alignment.max(member.ty.alignment());
+        }
+        let ty = CType::Struct { tag: decl.tag };
+        let size = usize::next_multiple_of(size, alignment);
+        if i32::try_from(size).is_err() {
+            self.errors.push(SemanticError::TooLarge {
+                ty,
+                span: decl.span,
+            });
+        }
+        CType::define_struct(
+            decl.tag,
+            StructLayout {
+                members,
+                size,
+                alignment,
+            },
+        );
+    }
+
+    /// reports `ty` if an object declared at `span` can't be of it: a
+    /// structure not yet defined, or one too large to fit on the stack.
+    fn object_type(&mut self, ty: CType, span: Span) {
+        if !ty.is_complete() {
+            self.errors.push(SemanticError::IncompleteType { ty, span });
+        } else if i32::try_from(ty.size()).is_err() {
+            self.errors.push(SemanticError::TooLarge { ty, span });
+        }
+    }
+
+    /// a local declared `extern` refers to a variable with linkage, and can't
+    /// define it; one declared `static` is initialized like a file-scope one.
+    fn declaration(&mut self, decl: &mut Declaration) {
+        match decl.storage {
+            Some(StorageClass::Extern) => {
+                if decl.init.is_some() {
+                    self.errors.push(SemanticError::ExternInitializer {
+                        name: decl.name,
+                        span: decl.span,
+                    });
+                }
+                let ty = SymbolType::Variable { ty: decl.ty };
+                self.declare(decl.name, ty, decl.storage, true, decl.span, None);
+            }
+            Some(StorageClass::Static) => {
+                self.locals.insert(decl.name, decl.ty);
+                self.object_type(decl.ty, decl.span);
+                self.static_init(decl);
+            }
+            None => {
+                self.locals.insert(decl.name, decl.ty);
+                self.object_type(decl.ty, decl.span);
+                if self.string_init(decl) {
+                    return;
+                }
+                if let Some(init) = &mut decl.init {
+                    let ty = self.exp(init);
+                    self.assign(init, ty, decl.ty);
+                }
+            }
+        }
+    }
+
+    fn statement(&mut self, stmt: &mut StatementC) {
+        match stmt {
+            StatementC::Return { exp, .. } => {
+                let ty = self.exp(exp);
+                if let Some(ret) = self.ret {
+                    self.assign(exp, ty, ret);
+                }
+            }
+            StatementC::Expression { exp, .. } => {
+                self.exp(exp);
+            }
+            StatementC::If {
+                condition,
+                then_stmt,
+                else_stmt,
+                ..
+            } => {
+                self.scalar(condition);
+                self.statement(then_stmt);
+                if let Some(else_stmt) = else_stmt {
+                    self.statement(else_stmt);
+                }
+            }
+            StatementC::Compound { block } => self.block(block),
+            StatementC::For {
+                init,
+                condition,
+                post,
+                body,
+                ..
+            } => {
+                match init {
+                    ForInit::Declaration { decl } => self.declaration(decl),
+                    ForInit::Expression { exp } => {
+                        if let Some(exp) = exp {
+                            self.exp(exp);
+                        }
+                    }
+                }
+                if let Some(condition) = condition {
+                    self.scalar(condition);
+                }
+                if let Some(post) = post {
+                    self.exp(post);
+                }
+                self.statement(body);
+            }
+            StatementC::Switch { control, body, .. } => {
+                let ty = self.exp(control);
+                if !ty.is_integer() {
+                    self.errors.push(SemanticError::NonIntegerSwitch {
+                        ty,
+                        span: control.span(),
+                    });
+                }
+                convert(control, ty, ty.promoted());
+                self.switches.push(ty.promoted());
+                self.statement(body);
+                self.switches.pop();
+            }
+            // a case outside any switch is for loop labeling to report
+            StatementC::Case { value, body, .. } => {
+                let ty = self.exp(value);
+                if !ty.is_integer() {
+                    self.errors
+                        .push(SemanticError::NonConstantCase { span: value.span() });
+                }
+                if let Some(&control) = self.switches.last() {
+                    convert(value, ty, control);
+                }
+                self.statement(body);
+            }
+            StatementC::Default { body, .. } => self.statement(body),
+            StatementC::Break { .. } | StatementC::Continue { .. } | StatementC::Null { .. } => {}
+        }
+    }
+
+    /// the type of `exp`, once the conversions in it are made explicit.
+    fn exp(&mut self, exp: &mut Exp) -> CType {
+        match exp {
+            Exp::Const { c, .. } => c.ty(),
+            Exp::Var { .. }
+            | Exp::Dereference { .. }
+            | Exp::Subscript { .. }
+            | Exp::String { .. }
+            | Exp::Dot { .. }
+            | Exp::Arrow { .. } => self.object(exp).decayed(),
+            Exp::Cast { target, exp, span } => self.cast(*target, exp, *span),
+            Exp::Unary { op, exp, span } => {
+                let ty = self.exp(exp);
+                if ty.is_pointer()
+                    || ty.is_struct()
+                    || (*op == UnaryOp::BitwiseComplement && !ty.is_integer())
+                {
+                    self.errors.push(SemanticError::InvalidOperand {
+                        op: op.symbol(),
+                        ty,
+                        span: *span,
+                    });
+                }
+                convert(exp, ty, ty.promoted());
+                ty.promoted()
+            }
+            Exp::Binary {
+                op: BinaryOp::And | BinaryOp::Or,
+                l_exp,
+                r_exp,
+                ..
+            } => {
+                self.scalar(l_exp);
+                self.scalar(r_exp);
+                CType::Int
+            }
+            // the operands are checked here, and the operator apart, to keep
+            // this frame small for long chains of operators
+            Exp::Binary {
+                op,
+                l_exp,
+                r_exp,
+                span,
+            } => {
+                let (l_ty, r_ty) = (self.exp(l_exp), self.exp(r_exp));
+                self.binary(op, (l_exp, l_ty), (r_exp, r_ty), *span)
+            }
+            Exp::Assignment { lvalue, rvalue, .. } => self.assignment(lvalue, rvalue),
+            Exp::CompoundAssignment { .. } => self.compound_assignment(exp),
+            Exp::Increment { .. } => self.increment(exp),
+            Exp::Conditional {
+                condition,
+                then_exp,
+                else_exp,
+                span,
+            } => {
+                self.scalar(condition);
+                let (then_ty, else_ty) = (self.exp(then_exp), self.exp(else_exp));
+                self.conditional((then_exp, then_ty), (else_exp, else_ty), *span)
+            }
+            Exp::AddrOf { exp, .. } => match exp.is_lvalue() {
+                true => self.object(exp).pointer_to(),
+                false => self.exp(exp).pointer_to(),
+            },
+            Exp::FunctionCall { name, args, span } => {
+                let tys: Vec<CType> = args.iter_mut().map(|arg| self.exp(arg)).collect();
+                let Some(Signature {
+                    ty: SymbolType::Function { params, ret },
+                    declared,
+                    ..
+                }) = self.symbols.get(name)
+                else {
+                    return CType::Int;
+                };
+                if params.len() != args.len() {
+                    self.errors.push(SemanticError::WrongArgumentCount {
+                        name: *name,
+                        expected: params.len(),
+                        got: args.len(),
+                        span: *span,
+                        declared: *declared,
+                    });
+                }
+                let (params, ret) = (params.clone(), *ret);
+                for ((arg, ty), param) in args.iter_mut().zip(tys).zip(params) {
+                    self.assign(arg, ty, param);
+                }
+                ret
+            }
+        }
+    }
+
+    /// the type of `exp`, which is tested for being zero, as a condition or
+    /// an operand of `&&` or `||`.
+    fn scalar(&mut self, exp: &mut Exp) -> CType {
+        let ty = self.exp(exp);
+        if ty.is_struct() {
+            self.errors.push(SemanticError::NotAScalar {
+                ty,
+                span: exp.span(),
+            });
+        }
+        ty
+    }
+
+    /// the type of casting `exp` to `target`, which neither can be a structure,
+    /// nor one a pointer and the other a `double`.
+    fn cast(&mut self, target: CType, exp: &mut Exp, span: Span) -> CType {
+        let ty = self.exp(exp);
+        let between_pointer_and_double = (ty.is_pointer() && target == CType::Double)
+            || (ty == CType::Double && target.is_pointer());
+        if ty.is_struct() || target.is_struct() || between_pointer_and_double {
+            self.errors.push(SemanticError::InvalidCast {
+                from: ty,
+                to: target,
+                span,
+            });
+        }
+        target
+    }
+
+    /// the type of the object the lvalue `exp` designates, before an array
+    /// decays: a variable, what a pointer points to, the element a subscript
+    /// picks out of one, its index converted to `long`, a member of a
+    /// structure, or a string literal's `char` array, one longer than its
+    /// bytes for the terminating null.
+    fn object(&mut self, exp: &mut Exp) -> CType {
+        match exp {
+            Exp::Var { name, .. } => self.variable_type(*name),
+            Exp::String { val, .. } => CType::Char.array_of(val.len() as u32 + 1),
+            Exp::Dereference { exp, span } => match self.exp(exp) {
+                CType::Pointer { referenced } => referenced.get(),
+                ty => {
+                    self.errors.push(SemanticError::InvalidOperand {
+                        op: "*",
+                        ty,
+                        span: *span,
+                    });
+                    CType::Int
+                }
+            },
+            Exp::Subscript { exp, index, span } => {
+                let (ty, index_ty) = (self.exp(exp), self.exp(index));
+                match (ty, index_ty) {
+                    (CType::Pointer { referenced }, _) if index_ty.is_integer() => {
+                        convert(index, index_ty, CType::Long);
+                        referenced.get()
+                    }
+                    (_, CType::Pointer { referenced }) if ty.is_integer() => {
+                        convert(exp, ty, CType::Long);
+                        referenced.get()
+                    }
+                    _ => {
+                        self.errors.push(SemanticError::MismatchedOperands {
+                            op: "[]",
+                            l_ty: ty,
+                            r_ty: index_ty,
+                            span: *span,
+                        });
+                        CType::Int
+                    }
+                }
+            }
+            Exp::Dot { exp, member, span } => {
+                let ty = self.exp(exp);
+                self.member(ty, *member, *span)
+            }
+            Exp::Arrow { exp, member, span } => {
+                let ty = self.exp(exp);
+                self.member_through(ty, *member, *span)
+            }
+            _ => self.exp(exp),
+        }
+    }
+
+    /// the type of the member `member` of a value of type `ty`, which has to
+    /// be a complete structure with such a member.
+    fn member(&mut self, ty: CType, member: Symbol, span: Span) -> CType {
+        if !ty.is_struct() {
+            self.errors
+                .push(SemanticError::NotAStructure { member, ty, span });
+            return CType::Int;
+        }
+        let Some(layout) = ty.layout() else {
+            self.errors.push(SemanticError::IncompleteType { ty, span });
+            return CType::Int;
+        };
+        match layout.member(member) {
+            Some(found) => found.ty,
+            None => {
+                self.errors
+                    .push(SemanticError::NoSuchMember { member, ty, span });
+                CType::Int
+            }
+        }
+    }
+
+    /// the type of the member `member` of the structure a pointer of type
+    /// `ty` points to.
+    fn member_through(&mut self, ty: CType, member: Symbol, span: Span) -> CType {
+        match ty {
+            CType::Pointer { referenced } => self.member(referenced.get(), member, span),
+            _ => {
+                self.errors
+                    .push(SemanticError::InvalidOperand { op: "->", ty, span });
+                CType::Int
+            }
+        }
+    }
+
+    /// the type of assigning `rvalue` to `lvalue`, which can't be an array.
+    fn assignment(&mut self, lvalue: &mut Exp, rvalue: &mut Exp) -> CType {
+        let l_ty = match lvalue.is_lvalue() {
+            true => self.object(lvalue),
+            false => self.exp(lvalue),
+        };
+        let r_ty = self.exp(rvalue);
+        if l_ty.is_array() {
+            self.errors.push(SemanticError::InvalidLvalue {
+                op: "=",
+                span: lvalue.span(),
+            });
+            return l_ty.decayed();
+        }
+        if !l_ty.is_complete() {
+            self.errors.push(SemanticError::IncompleteType {
+                ty: l_ty,
+                span: lvalue.span(),
+            });
+        }
+        self.assign(rvalue, r_ty, l_ty);
+        l_ty
+    }
+
+    /// the type of `lvalue op= rvalue`, which is that of `lvalue`: `rvalue` is
+    /// converted to the type the operation is carried out in, and TACKY
+    /// converts the value `lvalue` holds to it and the result back, as the
+    /// lvalue is only evaluated once. A pointer can be moved along by `+=` and
+    /// `-=` with an integer, converted to `long`, and nothing else. Kept out
+    /// of `exp` so that its frame stays small for deeply nested expressions.
+    fn compound_assignment(&mut self, exp: &mut Exp) -> CType {
+        let Exp::CompoundAssignment {
+            op,
+            lvalue,
+            rvalue,
+            span,
+        } = exp
+        else {
+            unreachable!("only a compound assignment is checked here");
+        };
+        let span = *span;
+        let l_ty = match lvalue.is_lvalue() {
+            true => self.object(lvalue),
+            false => self.exp(lvalue),
+        };
+        let r_ty = self.exp(rvalue);
+        let symbol = op.assignment_symbol();
+        if l_ty.is_array() {
+            self.errors.push(SemanticError::InvalidLvalue {
+                op: symbol,
+                span: lvalue.span(),
+            });
+            return l_ty.decayed();
+        }
+        let additive = matches!(op, BinaryOp::Add | BinaryOp::Subtract);
+        let common = match l_ty {
+            CType::Pointer { referenced } if additive && r_ty.is_integer() => {
+                if !referenced.get().is_complete() {
+                    self.errors.push(SemanticError::IncompleteType {
+                        ty: referenced.get(),
+                        span,
+                    });
+                }
+                CType::Long
+            }
+            _ if l_ty.is_pointer() || l_ty.is_struct() || r_ty.is_pointer() || r_ty.is_struct() => {
+                self.errors.push(SemanticError::MismatchedOperands {
+                    op: symbol,
+                    l_ty,
+                    r_ty,
+                    span,
+                });
+                return l_ty;
+            }
+            _ => operation_type(op, l_ty, r_ty),
+        };
+        let bad = [common, r_ty].into_iter().find(|ty| !ty.is_integer());
+        if let (true, Some(ty)) = (integer_only(op), bad) {
+            self.errors.push(SemanticError::InvalidOperand {
+                op: symbol,
+                ty,
+                span,
+            });
+        }
+        convert(rvalue, r_ty, common);
+        self.check_divisor(op, rvalue, span);
+        l_ty
+    }
+
+    /// warns about an integer division or remainder by a constant zero, which
+    /// traps when it runs. The divisor has been converted to the type the
+    /// operation is carried out in, so a `double` one, which gives an infinity
+    /// or a NaN instead, is told apart however the zero is written.
+    fn check_divisor(&mut self, op: &BinaryOp, divisor: &Exp, span: Span) {
+        if !matches!(op, BinaryOp::Divide | BinaryOp::Remainder) {
+            return;
+        }
+        if let Some(c) = constant_value(divisor) {
+            if c.ty().is_integer() && c.is_zero() {
+                self.warnings.push((Warning::DivByZero, span));
+            }
+        }
+    }
+
+    /// the type of `++x`, `--x`, `x++` or `x--`, which is that of `x`; a
+    /// pointer to an incomplete type can't be moved along, nor can an array.
+    fn increment(&mut self, exp: &mut Exp) -> CType {
+        let Exp::Increment { op, exp, span } = exp else {
+            unreachable!("only an increment or a decrement is checked here");
+        };
+        let ty = match exp.is_lvalue() {
+            true => self.object(exp),
+            false => self.exp(exp),
+        };
+        match ty {
+            CType::Array { .. } => self.errors.push(SemanticError::InvalidLvalue {
+                op: op.symbol(),
+                span: exp.span(),
+            }),
+            CType::Struct { .. } => self.errors.push(SemanticError::InvalidOperand {
+                op: op.symbol(),
+                ty,
+                span: *span,
+            }),
+            CType::Pointer { referenced } if !referenced.get().is_complete() => {
+                self.errors.push(SemanticError::IncompleteType {
+                    ty: referenced.get(),
+                    span: *span,
+                })
+            }
+            _ => {}
+        }
+        ty.decayed()
+    }
+
+    /// the type of a binary operation on operands of the types given, other
+    /// than `&&` and `||`, converting them to the type it is carried out in.
+    fn binary(
+        &mut self,
+        op: &BinaryOp,
+        (l_exp, l_ty): (&mut Exp, CType),
+        (r_exp, r_ty): (&mut Exp, CType),
+        span: Span,
+    ) -> CType {
+        if l_ty.is_struct() || r_ty.is_struct() {
+            self.errors.push(SemanticError::MismatchedOperands {
+                op: op.symbol(),
+                l_ty,
+                r_ty,
+                span,
+            });
+            return CType::Int;
+        }
+        if op.is_comparison() {
+            let common = match l_ty.is_pointer() || r_ty.is_pointer() {
+                true => {
+                    let equality = matches!(op, BinaryOp::Equal | BinaryOp::NotEqual);
+                    let common = match equality {
+                        true => common_pointer(l_exp, l_ty, r_exp, r_ty),
+                        false => (l_ty == r_ty).then_some(l_ty),
+                    };
+                    common.unwrap_or_else(|| {
+                        self.errors.push(SemanticError::MismatchedOperands {
+                            op: op.symbol(),
+                            l_ty,
+                            r_ty,
+                            span,
+                        });
+                        l_ty
+                    })
+                }
+                false => l_ty.common(r_ty),
+            };
+            convert(l_exp, l_ty, common);
+            convert(r_exp, r_ty, common);
+            return CType::Int;
+        }
+        let additive = matches!(op, BinaryOp::Add | BinaryOp::Subtract);
+        if additive && (l_ty.is_pointer() || r_ty.is_pointer()) {
+            return self.pointer_arithmetic(op, (l_exp, l_ty), (r_exp, r_ty), span);
+        }
+        if let Some(ty) = [l_ty, r_ty].into_iter().find(CType::is_pointer) {
+            self.errors.push(SemanticError::InvalidOperand {
+                op: op.symbol(),
+                ty,
+                span,
+            });
+            return ty;
+        }
+        let common = operation_type(op, l_ty, r_ty);
+        let bad = [common, r_ty].into_iter().find(|ty| !ty.is_integer());
+        if let (true, Some(ty)) = (integer_only(op), bad) {
+            self.errors.push(SemanticError::InvalidOperand {
+                op: op.symbol(),
+                ty,
+                span,
+            });
+        }
+        convert(l_exp, l_ty, common);
+        convert(r_exp, r_ty, common);
+        self.check_divisor(op, r_exp, span);
+        common
+    }
+
+    /// the type of adding an integer to a pointer, either way round, or
+    /// subtracting one from it, converting the integer to `long`; or of
+    /// subtracting two pointers of the same type, a `long` count of elements.
+    fn pointer_arithmetic(
+        &mut self,
+        op: &BinaryOp,
+        (l_exp, l_ty): (&mut Exp, CType),
+        (r_exp, r_ty): (&mut Exp, CType),
+        span: Span,
+    ) -> CType {
+        let subtract = *op == BinaryOp::Subtract;
+        for ty in [l_ty, r_ty] {
+            if let CType::Pointer { referenced } = ty {
+                if !referenced.get().is_complete() {
+                    self.errors.push(SemanticError::IncompleteType {
+                        ty: referenced.get(),
+                        span,
+                    });
+                }
+            }
+        }
+        match (l_ty.is_pointer(), r_ty.is_pointer()) {
+            (true, false) if r_ty.is_integer() => {
+                convert(r_exp, r_ty, CType::Long);
+                l_ty
+            }
+            (false, true) if l_ty.is_integer() && !subtract => {
+                convert(l_exp, l_ty, CType::Long);
+                r_ty
+            }
+            (true, true) if subtract && l_ty == r_ty => CType::Long,
+            _ => {
+                self.errors.push(SemanticError::MismatchedOperands {
+                    op: op.symbol(),
+                    l_ty,
+                    r_ty,
+                    span,
+                });
+                // the type it was meant to have, so that uses of it don't
+                // report the mistake again
+                match subtract && l_ty.is_pointer() && r_ty.is_pointer() {
+                    true => CType::Long,
+                    false => l_ty,
+                }
+            }
+        }
+    }
+
+    /// the type of a conditional expression with arms of the types given,
+    /// converting them to it.
+    fn conditional(
+        &mut self,
+        (then_exp, then_ty): (&mut Exp, CType),
+        (else_exp, else_ty): (&mut Exp, CType),
+        span: Span,
+    ) -> CType {
+        let common = match then_ty.is_pointer() || else_ty.is_pointer() {
+            _ if then_ty.is_struct() || else_ty.is_struct() => {
+                if then_ty != else_ty {
+                    self.errors.push(SemanticError::MismatchedOperands {
+                        op: "?:",
+                        l_ty: then_ty,
+                        r_ty: else_ty,
+                        span,
+                    });
+                }
+                return then_ty;
+            }
+            true => common_pointer(then_exp, then_ty, else_exp, else_ty).unwrap_or_else(|| {
+                self.errors.push(SemanticError::MismatchedOperands {
+                    op: "?:",
+                    l_ty: then_ty,
+                    r_ty: else_ty,
+                    span,
+                });
+                then_ty
+            }),
+            false => then_ty.common(else_ty),
+        };
+        convert(then_exp, then_ty, common);
+        convert(else_exp, else_ty, common);
+        common
+    }
+
+    /// converts `exp`, of type `from`, to type `to` as assigning it would,
+    /// unless one is a pointer and the other isn't the same type, or `exp` a
+    /// null pointer constant for `to`, or `to` a `_Bool`, which any pointer is
+    /// tested into. Nothing is assigned to an array, and a structure only from
+    /// one of its own type.
+    fn assign(&mut self, exp: &mut Exp, from: CType, to: CType) {
+        let allowed = match (from.is_pointer(), to.is_pointer()) {
+            _ if to.is_array() => false,
+            _ if from.is_struct() || to.is_struct() => from == to,
+            (false, false) => true,
+            (_, true) if is_null_pointer(exp) => true,
+            (true, false) => to == CType::Bool,
+            _ => from == to,
+        };
+        match allowed {
+            true => convert(exp, from, to),
+            false => self.errors.push(SemanticError::IncompatibleTypes {
+                from,
+                to,
+                span: exp.span(),
+            }),
+        }
+    }
+
+    /// the type of the variable `name`, which resolution found declared; one
+    /// redeclared as a function was reported there.
+    fn variable_type(&self, name: Symbol) -> CType {
+        match (self.locals.get(&name), self.symbols.get(&name)) {
+            (Some(ty), _)
+            | (
+                None,
+                Some(Signature {
+                    ty: SymbolType::Variable { ty },
+                    ..
+                }),
+            ) => *ty,
+            _ => CType::Int,
+        }
+    }
+}
+
+/// whether `op` only takes integer operands.
+fn integer_only(op: &BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Remainder
+            | BinaryOp::BitwiseAnd
+            | BinaryOp::BitwiseOr
+            | BinaryOp::BitwiseXor
+            | BinaryOp::LeftShift
+            | BinaryOp::RightShift
+    )
+}
+
+/// the type `op` is carried out in on arithmetic operands of the types given:
+/// their common type, or the left one's, promoted, for a shift.
+fn operation_type(op: &BinaryOp, l_ty: CType, r_ty: CType) -> CType {
+    match op {
+        BinaryOp::LeftShift | BinaryOp::RightShift => l_ty.promoted(),
+        _ => l_ty.common(r_ty),
+    }
+}
+
+/// whether `exp` is a null pointer constant, an integer constant that is zero.
+fn is_null_pointer(exp: &Exp) -> bool {
+    matches!(exp, Exp::Const { c, .. } if c.ty().is_integer() && c.is_zero())
+}
+
+/// the type two operands, one of them a pointer, are converted to before
+/// they are compared for equality, or chosen between: their own if they have
+/// the same, or that of the pointer if the other is a null pointer constant.
+fn common_pointer(l_exp: &Exp, l_ty: CType, r_exp: &Exp, r_ty: CType) -> Option<CType> {
+    if l_ty == r_ty || is_null_pointer(r_exp) {
+        Some(l_ty)
+    } else if is_null_pointer(l_exp) {
+        Some(r_ty)
+    } else {
+        None
+    }
+}
+
+/// wraps `exp`, of type `from`, in a cast to type `to`, unless they are the same.
+/// A constant is converted on the spot instead, unless to a pointer: there are
+/// no pointer constants, and the cast keeps the type for TACKY.
+fn convert(exp: &mut Exp, from: CType, to: CType) {
+    if from == to {
+        return;
+    }
+    if let Exp::Const { c, .. } = exp {
+        if !to.is_pointer() {
+            *c = c.convert(to);
+            return;
+        }
+    }
+    let span = exp.span();
+    let placeholder = Exp::Const {
+        c: Const::Int { int: 0 },
+        span,
+    };
+    let inner = std::mem::replace(exp, placeholder);
+    *exp = Exp::Cast {
+        target: to,
+        exp: Box::new(inner),
+        span,
+    };
+}
+
+#[cfg(test)]
+use super::{
+    super::{lexer::tokenize, parser::parse},
+    resolve,
+};
+
+#[cfg(test)]
+fn check_source(source: &str) -> Result<ProgramC, Vec<SemanticError>> {
+    type_check(resolve(parse(tokenize(String::from(source)).unwrap()).unwrap()).unwrap())
+}
+
+/// a function may be declared any number of times, before or after its
+/// definition, and called from anywhere after the first.
+#[test]
+fn declarations_agree() {
+    assert!(check_source(
+        "int even(int n); int odd(int n) { return n == 0 ? 0 : even(n - 1); } \
+         int even(int n) { return n == 0 ? 1 : odd(n - 1); } int odd(int m); \
+         int main(void) { return even(4); }",
+    )
+    .is_ok());
+}
+
+#[test]
+fn wrong_argument_count() {
+    let errors =
+        check_source("int f(int a); int main(void) { return f() + f(1, 2) + f(3); }").unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::WrongArgumentCount {
+                name: Symbol::intern("f"),
+                expected: 1,
+                got: 0,
+                span: Span { start: 38, end: 41 },
+                declared: Span { start: 0, end: 13 },
+            },
+            SemanticError::WrongArgumentCount {
+                name: Symbol::intern("f"),
+                expected: 1,
+                got: 2,
+                span: Span { start: 44, end: 51 },
+                declared: Span { start: 0, end: 13 },
+            },
+        ]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "'f' takes 1 argument but 0 were given"
+    );
+    assert_eq!(
+        errors[1].to_string(),
+        "'f' takes 1 argument but 2 were given"
+    );
+    assert_eq!(errors[0].code(), "wrong-argument-count");
+}
+
+/// a declaration that disagrees with an earlier one is pointed at it, and so is
+/// a second definition at the first.
+#[test]
+fn conflicting_declarations() {
+    let errors = check_source(
+        "int f(int a); int f(int a, int b); int g(void) { return 1; } int g(void) { return 2; }",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::ConflictingDeclaration {
+                name: Symbol::intern("f"),
+                span: Span { start: 14, end: 34 },
+                previous: Span { start: 0, end: 13 },
+            },
+            SemanticError::Redefinition {
+                name: Symbol::intern("g"),
+                span: Span { start: 61, end: 86 },
+                previous: Span { start: 35, end: 60 },
+            },
+        ]
+    );
+    assert_eq!(
+        errors[1].note(),
+        Some((
+            String::from("'g' was first defined here"),
+            Span { start: 35, end: 60 }
+        ))
+    );
+}
+
+/// every declaration of a name with linkage has to agree with the first on
+/// whether it is `static`, unless it is `extern`; a variable can't be
+/// initialized twice, or a function redeclared as one.
+#[test]
+fn conflicting_linkage() {
+    assert!(check_source(
+        "static int f(void); int f(void); extern int f(void); int x; extern int x; \
+         int x = 1; int x; static int y; extern int y; int main(void) { return x + y; }",
+    )
+    .is_ok());
+    let errors =
+        check_source("int x = 1; static int x; static int g(void); int g; int x = 2;").unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::ConflictingLinkage {
+                name: Symbol::intern("x"),
+                internal: true,
+                span: Span { start: 11, end: 24 },
+                previous: Span { start: 0, end: 10 },
+            },
+            SemanticError::ConflictingDeclaration {
+                name: Symbol::intern("g"),
+                span: Span { start: 45, end: 51 },
+                previous: Span { start: 25, end: 44 },
+            },
+            SemanticError::Redefinition {
+                name: Symbol::intern("x"),
+                span: Span { start: 52, end: 62 },
+                previous: Span { start: 0, end: 10 },
+            },
+        ]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "static declaration of 'x' follows non-static declaration"
+    );
+    let errors = check_source("static int h; int h;").unwrap_err();
+    assert_eq!(
+        errors[0].to_string(),
+        "non-static declaration of 'h' follows static declaration"
+    );
+    assert_eq!(errors[0].code(), "conflicting-linkage");
+}
+
+/// a static variable's initializer is folded to its value, so it has to be a
+/// constant, and a local `extern` can't have one at all.
+#[test]
+fn static_initializers() {
+    let checked = check_source(
+        "int x = 2 * 3 - 1; int main(void) { static int s = -(4 / 2); return x + s; }",
+    )
+    .unwrap();
+    assert_eq!(
+        super::super::unparse::program(&checked),
+        "int x = 5;
+
+int main(void) {
+    static int s.0 = -2;
+    return (x + s.0);
+}
+"
+    );
+    let errors = check_source(
+        "int a = 1; int b = a; int main(void) { static int s = main(); extern int e = 3; return 0; }",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::NonConstantInitializer {
+                span: Span { start: 19, end: 20 },
+            },
+            SemanticError::NonConstantInitializer {
+                span: Span { start: 54, end: 60 },
+            },
+            SemanticError::ExternInitializer {
+                name: Symbol::intern("e"),
+                span: Span { start: 62, end: 79 },
+            },
+        ]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "initializer of a static variable is not a constant"
+    );
+    assert_eq!(
+        errors[2].to_string(),
+        "'e' has both 'extern' and an initializer"
+    );
+}
+
+/// an `int` meeting a `long` is converted to it, as is one assigned, passed or
+/// returned where a `long` goes, and a `long` assigned to an `int` is truncated;
+/// constants are converted on the spot.
+#[test]
+fn implicit_conversions() {
+    let checked = check_source(
+        "long f(long a); int main(void) { int i = 3; long l = i + 2L; i = l; return f(i) + f(1); }",
+    )
+    .unwrap();
+    assert_eq!(
+        super::super::unparse::program(&checked),
+        "long f(long a.0);
+
+int main(void) {
+    int i.1 = 3;
+    long l.2 = (((long) i.1) + 2L);
+    (i.1 = ((int) l.2));
+    return ((int) (f(((long) i.1)) + f(1L)));
+}
+"
+    );
+}
+
+/// an `int` meeting an `unsigned int` converts to it, but an `unsigned int`
+/// meeting a `long` converts to the `long`, which holds all its values.
+#[test]
+fn unsigned_conversions() {
+    let checked =
+        check_source("int main(void) { unsigned u = 1; long l = u + 2; return u < -1 && l > u; }")
+            .unwrap();
+    assert_eq!(
+        super::super::unparse::program(&checked),
+        "int main(void) {
+    unsigned int u.0 = 1U;
+    long l.1 = ((long) (u.0 + 2U));
+    return ((u.0 < ((unsigned int) (-1))) && (l.1 > ((long) u.0)));
+}
+"
+    );
+}
+
+/// `~`, `%` and the bitwise operators only take integers, as does a `switch`,
+/// so a `double` operand is reported at the operator using it.
+#[test]
+fn integer_only_operators() {
+    let errors = check_source(
+        "int main(void) { double d = 1.5; long l = ~d + (d % 2) + (3 & d); switch (d) {} return 0; }",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::InvalidOperand {
+                op: "~",
+                ty: CType::Double,
+                span: Span { start: 42, end: 44 },
+            },
+            SemanticError::InvalidOperand {
+                op: "%",
+                ty: CType::Double,
+                span: Span { start: 47, end: 54 },
+            },
+            SemanticError::InvalidOperand {
+                op: "&",
+                ty: CType::Double,
+                span: Span { start: 57, end: 64 },
+            },
+            SemanticError::NonIntegerSwitch {
+                ty: CType::Double,
+                span: Span { start: 74, end: 75 },
+            },
+        ]
+    );
+    assert_eq!(
+        errors[1].to_string(),
+        "invalid operand of type 'double' to '%'"
+    );
+}
+
+/// an integer divided by a constant zero, however it is written, is warned
+/// about, and so is a remainder or a compound assignment; a `double` divided
+/// by zero isn't, nor is a program with errors kept from its warnings.
+#[test]
+fn division_by_zero_warnings() {
+    fn warned(source: &str) -> Vec<&str> {
+        let mut warnings = vec![];
+        let program = parse(tokenize(String::from(source)).unwrap()).unwrap();
+        let _ = type_check_with_warnings(resolve(program).unwrap(), &mut warnings);
+        warnings
+            .into_iter()
+            .map(|(warning, span)| {
+                assert_eq!(warning, Warning::DivByZero);
+                &source[span.start..span.end]
+            })
+            .collect()
+    }
+    assert_eq!(
+        warned(
+            "int main(void) { long l = 5; l /= 0; l %= (2 - 2); return 1 / 0L + 7u % (char)0 + 3 / 1; }"
+        ),
+        ["l /= 0", "l %= (2 - 2)", "1 / 0L", "7u % (char)0"]
+    );
+    assert!(
+        warned("int main(void) { double d = 1.0 / 0; d /= 0; return 1 / 0.0 > d; }").is_empty()
+    );
+    assert_eq!(
+        warned("int main(void) { int *p = 0; return 2 % 0 + ~p; }"),
+        ["2 % 0"]
+    );
+}
+
+/// a shift has the type of its left operand, promoted, whatever its count's,
+/// which is converted to match; neither side can be a `double`.
+#[test]
+fn shift_types() {
+    let checked = check_source(
+        "int main(void) { char c = 1; long l = 2; unsigned u = c << l; l >>= c; return u >> 1; }",
+    )
+    .unwrap();
+    assert_eq!(
+        super::super::unparse::program(&checked),
+        "int main(void) {
+    char c.0 = 1;
+    long l.1 = 2L;
+    unsigned int u.2 = ((unsigned int) (((int) c.0) << ((int) l.1)));
+    (l.1 >>= ((long) c.0));
+    return ((int) (u.2 >> 1U));
+}
+"
+    );
+    let errors = check_source("int main(void) { double d = 1.0; int i = 1 << d; return d >> 1; }")
+        .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::InvalidOperand {
+                op: "<<",
+                ty: CType::Double,
+                span: Span { start: 41, end: 47 },
+            },
+            SemanticError::InvalidOperand {
+                op: ">>",
+                ty: CType::Double,
+                span: Span { start: 56, end: 62 },
+            },
+        ]
+    );
+}
+
+/// a `char` or a `short` is promoted to an `int` before any operator works on
+/// it, even one of a type as narrow, and converted back when assigned.
+#[test]
+fn integer_promotions() {
+    let checked = check_source(
+        "int main(void) { char c = 1; unsigned short s = 2; c = c + s; switch (s) case 2: return -c; return ~c; }",
+    )
+    .unwrap();
+    assert_eq!(
+        super::super::unparse::program(&checked),
+        "int main(void) {
+    char c.0 = 1;
+    unsigned short s.1 = 2;
+    (c.0 = ((char) (((int) c.0) + ((int) s.1))));
+    switch (((int) s.1))
+        case 2:
+            return (-((int) c.0));
+    return (~((int) c.0));
+}
+"
+    );
+}
+
+/// pointers only convert implicitly from a constant 0 or a pointer to the same
+/// type, take no arithmetic but adding and subtracting, and are ordered only
+/// against the same type.
+#[test]
+fn pointer_conversions() {
+    let int_ptr = CType::Int.pointer_to();
+    let long_ptr = CType::Long.pointer_to();
+    assert!(check_source(
+        "int *f(int *p) { return p == 0 ? 0 : p; } int main(void) { int x = 1; int *p = 0; p = f(&x); return *p + (p != 0); }"
+    )
+    .is_ok());
+    let errors = check_source(
+        "int main(void) { long l = 2; int *p = &l; int i = p; p = 1; int *q = -p; return *l + (p < &l); }",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::IncompatibleTypes {
+                from: long_ptr,
+                to: int_ptr,
+                span: Span { start: 38, end: 40 },
+            },
+            SemanticError::IncompatibleTypes {
+                from: int_ptr,
+                to: CType::Int,
+                span: Span { start: 50, end: 51 },
+            },
+            SemanticError::IncompatibleTypes {
+                from: CType::Int,
+                to: int_ptr,
+                span: Span { start: 57, end: 58 },
+            },
+            SemanticError::InvalidOperand {
+                op: "-",
+                ty: int_ptr,
+                span: Span { start: 69, end: 71 },
+            },
+            SemanticError::InvalidOperand {
+                op: "*",
+                ty: CType::Long,
+                span: Span { start: 80, end: 82 },
+            },
+            SemanticError::MismatchedOperands {
+                op: "<",
+                l_ty: int_ptr,
+                r_ty: long_ptr,
+                span: Span { start: 85, end: 93 },
+            },
+        ]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "'long *' can't be converted to 'int *' implicitly"
+    );
+    assert_eq!(
+        errors[5].to_string(),
+        "invalid operands of types 'int *' and 'long *' to '<'"
+    );
+}
+
+/// an array decays to a pointer to its first element, so it can be subscripted,
+/// offset and passed as a pointer, but not assigned; only pointers to the same
+/// type subtract, and only from a pointer is an integer subtracted.
+#[test]
+fn array_types() {
+    let int_ptr = CType::Int.pointer_to();
+    assert!(check_source(
+        "int first(int *p) { return *p; } int main(void) { int a[3]; long g[2][3]; int *p = a + 1; long n = &a[2] - p; long *row = g[1]; return first(a) + 2[a] + p[-1] + n + row[2] + *(g + 1)[0]; }"
+    )
+    .is_ok());
+    let errors = check_source(
+        "int main(void) { int a[3]; int b[3]; long l = 1; double d = 1.0; int *p = a; a = b; p = p + d; long n = p - &l; int x = l[1]; return 1 - p; }",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::InvalidLvalue {
+                op: "=",
+                span: Span { start: 77, end: 78 },
+            },
+            SemanticError::MismatchedOperands {
+                op: "+",
+                l_ty: int_ptr,
+                r_ty: CType::Double,
+                span: Span { start: 88, end: 93 },
+            },
+            SemanticError::MismatchedOperands {
+                op: "-",
+                l_ty: int_ptr,
+                r_ty: CType::Long.pointer_to(),
+                span: Span {
+                    start: 104,
+                    end: 110
+                },
+            },
+            SemanticError::MismatchedOperands {
+                op: "[]",
+                l_ty: CType::Long,
+                r_ty: CType::Int,
+                span: Span {
+                    start: 120,
+                    end: 124
+                },
+            },
+            SemanticError::MismatchedOperands {
+                op: "-",
+                l_ty: CType::Int,
+                r_ty: int_ptr,
+                span: Span {
+                    start: 133,
+                    end: 138
+                },
+            },
+        ]
+    );
+    assert_eq!(
+        errors[3].to_string(),
+        "invalid operands of types 'long' and 'int' to '[]'"
+    );
+}
+
+/// a string literal is a `char` array one longer than its bytes, which decays
+/// to a `char *` and can have its address taken, but isn't assigned to. It
+/// initializes a `char` array it fits in, not counting its null, and a
+/// `char *` even with static storage.
+#[test]
+fn string_types() {
+    assert!(check_source(
+        "char *g = \"hi\"; char a[2] = \"hi\"; int main(void) { static char *q = \"q\"; unsigned char b[5] = \"hi\"; char *s = \"hi\"; long n = &\"hi\" - &\"ho\"; return s[1] + (*&\"hi\")[0] + \"abc\"[n] + b[4]; }"
+    )
+    .is_ok());
+    let errors = check_source(
+        "char s[1] = \"hi\"; int main(void) { int *p = \"hi\"; \"hi\" = s; int a[3] = \"hi\"; return 0; }",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::StringTooLong {
+                ty: CType::Char.array_of(1),
+                span: Span { start: 12, end: 16 }
+            },
+            SemanticError::IncompatibleTypes {
+                from: CType::Char.pointer_to(),
+                to: CType::Int.pointer_to(),
+                span: Span { start: 44, end: 48 },
+            },
+            SemanticError::InvalidLvalue {
+                op: "=",
+                span: Span { start: 50, end: 54 },
+            },
+            SemanticError::IncompatibleTypes {
+                from: CType::Char.pointer_to(),
+                to: CType::Int.array_of(3),
+                span: Span { start: 71, end: 75 },
+            },
+        ]
+    );
+}
+
+/// each member of a structure is placed at the next multiple of its own
+/// alignment, and the structure is as aligned as its most aligned member and
+/// padded out to a multiple of that.
+#[test]
+fn struct_layout() {
+    let program = check_source(
+        "struct s { char c; long l; int i; }; struct t { short h; struct s inner; char tail[3]; }; int main(void) { struct t v; struct t *p = &v; return v.inner.c + p->tail[1] + p->inner.i; }",
+    )
+    .unwrap();
+    let layouts: Vec<_> = program
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            TopLevelC::Struct { decl } => CType::Struct { tag: decl.tag }.layout(),
+            _ => None,
+        })
+        .collect();
+    let offsets = |layout: &StructLayout| -> Vec<_> {
+        layout.members.iter().map(|member| member.offset).collect()
+    };
+    assert_eq!(offsets(&layouts[0]), vec![0, 8, 16]);
+    assert_eq!((layouts[0].size, layouts[0].alignment), (24, 8));
+    assert_eq!(offsets(&layouts[1]), vec![0, 8, 32]);
+    assert_eq!((layouts[1].size, layouts[1].alignment), (40, 8));
+}
+
+/// members are looked up in the structure a `.` is applied to or a `->`
+/// points at, which has to be complete; structures aren't scalars and can
+/// only be assigned whole from the same type.
+#[test]
+fn struct_errors() {
+    let errors = check_source(
+        "struct a { int x; int x; }; struct b; struct c { long y; }; int f(struct c c); int main(void) { struct a s; struct b *p = 0; struct c t; int i = 1; i = s.y + i.x + p->x; s = t; if (s) return 1; return t * 2; }",
+    )
+    .unwrap_err();
+    // the tags are numbered as they're resolved, so the errors are compared
+    // by what they print rather than the types they hold
+    let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "duplicate member 'x'",
+            "'f' passes or returns a structure by value, which isn't supported",
+            "'struct a' has no member named 'y'",
+            "request for member 'x' in a value of type 'int', not a structure",
+            "'struct b' is an incomplete type",
+            "'struct c' can't be converted to 'struct a' implicitly",
+            "a value of type 'struct a' used where a scalar is required",
+            "invalid operands of types 'struct c' and 'int' to '*'",
+        ]
+    );
+    assert_eq!(
+        errors[0],
+        SemanticError::DuplicateMember {
+            name: Symbol::intern("x"),
+            span: Span { start: 18, end: 24 },
+            previous: Span { start: 11, end: 17 },
+        }
+    );
+    assert!(matches!(
+        errors[4],
+        SemanticError::IncompleteType {
+            span: Span {
+                start: 164,
+                end: 168
+            },
+            ..
+        }
+    ));
+}
+
+/// a compound assignment converts only its right side, to the type the
+/// operation is carried out in, and a pointer only takes an integer from `+=`
+/// and `-=`.
+#[test]
+fn compound_assignment_types() {
+    let checked = check_source(
+        "int main(void) { char c = 1; int a[2]; int *p = a; c += 2L; p -= c; return c; }",
+    )
+    .unwrap();
+    assert_eq!(
+        super::super::unparse::program(&checked),
+        "int main(void) {
+    char c.0 = 1;
+    int a.1[2];
+    int *p.2 = a.1;
+    (c.0 += 2L);
+    (p.2 -= ((long) c.0));
+    return ((int) c.0);
+}
+"
+    );
+    let int_ptr = CType::Int.pointer_to();
+    let errors = check_source(
+        "int main(void) { int a[2]; int *p = a; double d = 1.0; p *= 2; d %= 2; a += 1; d += p; return 0; }",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticError::MismatchedOperands {
+                op: "*=",
+                l_ty: int_ptr,
+                r_ty: CType::Int,
+                span: Span { start: 55, end: 61 },
+            },
+            SemanticError::InvalidOperand {
+                op: "%=",
+                ty: CType::Double,
+                span: Span { start: 63, end: 69 },
+            },
+            SemanticError::InvalidLvalue {
+                op: "+=",
+                span: Span { start: 71, end: 72 },
+            },
+            SemanticError::MismatchedOperands {
+                op: "+=",
+                l_ty: CType::Double,
+                r_ty: int_ptr,
+                span: Span { start: 79, end: 85 },
+            },
+        ]
+    );
+    assert_eq!(
+        errors[2].to_string(),
+        "the left side of '+=' must be an lvalue"
+    );
+}
+
+#[test]
+fn increment_errors() {
+    let errors =
+        check_source("struct s; int main(void) { int a[2]; struct s *p = 0; a++; --p; return 0; }")
+            .unwrap_err();
+    assert_eq!(
+        errors[0],
+        SemanticError::InvalidLvalue {
+            op: "++",
+            span: Span { start: 54, end: 55 },
+        }
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "the operand of '++' must be an lvalue"
+    );
+    assert_eq!(errors[1].span(), Span { start: 59, end: 62 });
+    assert_eq!(errors[1].to_string(), "'struct s' is an incomplete type");
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn cast_errors() {
+    let errors = check_source(
+        "struct s { int a; }; int main(void) { struct s v; int *p = 0; (double)p; (int)v; return (long)p; }",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors[0],
+        SemanticError::InvalidCast {
+            from: CType::Int.pointer_to(),
+            to: CType::Double,
+            span: Span { start: 62, end: 71 },
+        }
+    );
+    assert_eq!(errors[1].to_string(), "'struct s' can't be cast to 'int'");
+    assert_eq!(errors.len(), 2);
+}
+
+/// anything scalar converts to a `_Bool`, a pointer too, and a constant becomes
+/// 0 or 1 on the spot; a `_Bool` is promoted to `int` like any narrow type, but
+/// is no more a pointer than any other integer.
+#[test]
+fn bool_conversions() {
+    let checked = check_source(
+        "static _Bool s = 256; int main(void) { int *p = 0; _Bool b = p; _Bool c = 0.5; return b + c + s; }",
+    )
+    .unwrap();
+    assert_eq!(
+        super::super::unparse::program(&checked),
+        "static _Bool s = 1;
+
+int main(void) {
+    int *p.0 = ((int *) 0);
+    _Bool b.1 = ((_Bool) p.0);
+    _Bool c.2 = 1;
+    return ((((int) b.1) + ((int) c.2)) + ((int) s));
+}
+"
+    );
+    let errors = check_source("int main(void) { _Bool b = 1; int *p = b; return 0; }").unwrap_err();
+    assert_eq!(
+        errors,
+        vec![SemanticError::IncompatibleTypes {
+            from: CType::Bool,
+            to: CType::Int.pointer_to(),
+            span: Span { start: 39, end: 40 },
+        }]
+    );
+}
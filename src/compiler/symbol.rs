@@ -0,0 +1,159 @@
+//! Interned names for the values the IRs refer to.
+//! A `Symbol` is a copyable handle into the string table of the compilation
+//! context current on its thread, so identifiers, TACKY temporaries and
+//! assembly pseudos can be compared, hashed and copied as cheaply as integers
+//! while still printing as their names. The lexer interns each identifier as
+//! it reads it, and the text is only looked up again to emit it or quote it in
+//! a diagnostic. Generated temporaries are interned as `tmp.<n>`, and
+//! uniquified user variables as `<name>.<n>`. Interned strings live as long
+//! as their context.
+//!
+//! `cargo bench --bench codegen -- symbols` times interning and lookups.
+use std::{collections::HashMap, fmt::Display, sync::Arc};
+
+use super::context::Context;
+
+/// The names interned in a context, each at its symbol's index.
+#[derive(Default)]
+pub struct SymbolTable {
+    names: Vec<Arc<str>>,
+    ids: HashMap<Arc<str>, u32>,
+}
+
+impl SymbolTable {
+    /// how many names have been interned.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// An interned name. Two symbols of the same context are equal exactly when
+/// their names are. Their order is their names' order, which does not depend
+/// on when or on which thread they were interned.
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        Context::with(|context| {
+            if let Some(&id) = context
+                .symbols
+                .read()
+                .expect("symbol table poisoned")
+                .ids
+                .get(name)
+            {
+                return Symbol(id);
+            }
+            let mut table = context.symbols.write().expect("symbol table poisoned");
+            // another thread may have interned it since
+            if let Some(&id) = table.ids.get(name) {
+                return Symbol(id);
+            }
+            let id = u32::try_from(table.names.len()).expect("more than u32::MAX symbols");
+            let name: Arc<str> = name.into();
+            table.names.push(name.clone());
+            table.ids.insert(name, id);
+            Symbol(id)
+        })
+    }
+
+    /// the symbol for `<prefix>.<no>`, the form every generated name takes.
+    pub fn numbered(prefix: &str, no: u32) -> Self {
+        Symbol::intern(&format!("{}.{}", prefix, no))
+    }
+
+    /// the symbol's name, which it must have been interned in the current context to have.
+    pub fn as_str(self) -> Arc<str> {
+        Context::with(|context| {
+            context
+                .symbols
+                .read()
+                .expect("symbol table poisoned")
+                .names
+                .get(self.0 as usize)
+                .cloned()
+                .expect("symbol from another context")
+        })
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match self == other {
+            true => std::cmp::Ordering::Equal,
+            false => self.as_str().cmp(&other.as_str()),
+        }
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Symbol::intern(&name))
+    }
+}
+
+#[test]
+fn interning_is_by_name() {
+    let a = Symbol::intern("symbol_test.a");
+    assert_eq!(a, Symbol::intern("symbol_test.a"));
+    assert_ne!(a, Symbol::intern("symbol_test.b"));
+    assert_eq!(
+        &*Symbol::numbered("symbol_test", 7).as_str(),
+        "symbol_test.7"
+    );
+    assert_eq!(a.to_string(), "symbol_test.a");
+    assert_eq!(format!("{:?}", a), "\"symbol_test.a\"");
+}
+
+/// a symbol keeps naming the same text however many are interned after it.
+#[test]
+fn lookup_survives_growth() {
+    let first = Symbol::intern("symbol_growth.first");
+    let text = first.as_str();
+    for no in 0..10_000 {
+        Symbol::numbered("symbol_growth", no);
+    }
+    assert_eq!(&*first.as_str(), "symbol_growth.first");
+    assert!(Arc::ptr_eq(&first.as_str(), &text));
+}
+
+/// order follows the names, not the order they were interned in.
+#[test]
+fn symbols_order_by_name() {
+    let later = Symbol::intern("symbol_order.b");
+    let earlier = Symbol::intern("symbol_order.a");
+    assert!(earlier < later);
+    assert_eq!(later.cmp(&later), std::cmp::Ordering::Equal);
+}
@@ -1,34 +1,271 @@
-use super::parser::*;
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use super::{diagnostics::LineIndex, lexer::escape, parser::*, symbol::Symbol};
 
 /// TACKY program
-/// ### Grammar as of v0.1.1
-/// `program = Program(function_definition)`
+/// ### Grammar as of v0.1.3
+/// `program = Program(top_level*)`
+/// The source files named by line information come first, when there is any,
+/// then the functions, then the variables with static storage, then the
+/// constants.
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProgramTacky {
-    pub function: Box<FunDefTacky>,
+    pub items: Vec<TopLevelTacky>,
+}
+
+impl ProgramTacky {
+    /// the function definitions, in the order they appear in the source.
+    pub fn functions(&self) -> impl Iterator<Item = &FunDefTacky> {
+        self.items.iter().filter_map(|item| match item {
+            TopLevelTacky::Function { fundef } => Some(fundef),
+            TopLevelTacky::File { .. }
+            | TopLevelTacky::StaticVariable { .. }
+            | TopLevelTacky::StaticConstant { .. } => None,
+        })
+    }
+
+    /// the names of the variables with static storage and of the constants,
+    /// which live in memory rather than in a function's frame, and which any
+    /// call may read or write.
+    pub fn statics(&self) -> HashSet<Symbol> {
+        self.items
+            .iter()
+            .filter_map(|item| match item {
+                TopLevelTacky::StaticVariable { name, .. }
+                | TopLevelTacky::StaticConstant { name, .. } => Some(*name),
+                TopLevelTacky::Function { .. } | TopLevelTacky::File { .. } => None,
+            })
+            .collect()
+    }
+}
+
+/// prints the source files one per line, then each function in turn,
+/// separated by blank lines, then the variables with static storage and the
+/// constants, one per line.
+impl Display for ProgramTacky {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, item) in self.items.iter().enumerate() {
+            match item {
+                TopLevelTacky::File { no, name } => {
+                    writeln!(f, "file {} \"{}\"", no, escape(name.as_str().as_bytes()))?
+                }
+                TopLevelTacky::Function { fundef } => {
+                    if i != 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", fundef)?
+                }
+                TopLevelTacky::StaticVariable { name, global, init } => {
+                    if i != 0 && matches!(self.items[i - 1], TopLevelTacky::Function { .. }) {
+                        writeln!(f)?;
+                    }
+                    match (init, global) {
+                        (Some(init), true) => writeln!(f, "global {} = {}", name, init)?,
+                        (Some(init), false) => writeln!(f, "static {} = {}", name, init)?,
+                        (None, _) => writeln!(f, "extern {}", name)?,
+                    }
+                }
+                TopLevelTacky::StaticConstant { name, init } => {
+                    if i != 0 && matches!(self.items[i - 1], TopLevelTacky::Function { .. }) {
+                        writeln!(f)?;
+                    }
+                    writeln!(f, "constant {} = {}", name, init)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// TACKY top-level item
+/// ### Grammar as of v0.1.18
+/// ```text
+/// top_level = Function(function_definition)
+///           | StaticVariable(identifier name, bool global, static_init? init)
+///           | StaticConstant(identifier name, static_init init)
+///           | File(int no, identifier name)
+/// static_init = Scalar(const) | Zero(type) | String(bytes, bool null_terminated)
+///             | Address(identifier name, type)
+/// ```
+/// A variable with static storage defined in another file has no `init`, and
+/// takes no storage in this one. One defined here without an initializer is
+/// initialized to 0, an array or a structure to as many zero bytes as it
+/// takes. A constant is never written to, and is what a string literal's
+/// `char` array is; a `char *` initialized by one holds its address.
+/// A file is a source file line information names by its number, counting
+/// from 1 in the order the files first come up.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TopLevelTacky {
+    Function {
+        fundef: FunDefTacky,
+    },
+    StaticVariable {
+        name: Symbol,
+        global: bool,
+        init: Option<StaticInit>,
+    },
+    StaticConstant {
+        name: Symbol,
+        init: StaticInit,
+    },
+    File {
+        no: u32,
+        name: Symbol,
+    },
+}
+
+/// The value a variable with static storage or a constant starts with. A
+/// string is the bytes of a `char` array, and a null after them if there's
+/// room for one. An address is that of the constant `name`, of type `ty`.
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StaticInit {
+    Scalar { c: Const },
+    Zero { ty: CType },
+    String { val: Vec<u8>, null_terminated: bool },
+    Address { name: Symbol, ty: CType },
+}
+
+impl StaticInit {
+    /// the type of the variable it initializes.
+    pub fn ty(&self) -> CType {
+        match self {
+            Self::Scalar { c } => c.ty(),
+            Self::Zero { ty } => *ty,
+            Self::String {
+                val,
+                null_terminated,
+            } => CType::Char.array_of(val.len() as u32 + *null_terminated as u32),
+            Self::Address { ty, .. } => ty.decayed(),
+        }
+    }
+}
+
+/// prints a constant as itself, zero bytes as `zero` and their type, e.g.
+/// `zero int[3]`, a string quoted, with its null if it has one, e.g.
+/// `"hi\000"`, and an address as `&` and the name, e.g. `&.string.0`.
+impl Display for StaticInit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Scalar { c } => write!(f, "{}", c),
+            Self::Zero { ty } => write!(f, "zero {}", ty),
+            Self::String {
+                val,
+                null_terminated,
+            } => match null_terminated {
+                true => write!(f, "\"{}\\000\"", escape(val)),
+                false => write!(f, "\"{}\"", escape(val)),
+            },
+            Self::Address { name, .. } => write!(f, "&{}", name),
+        }
+    }
 }
 
 /// TACKY function definition
-/// ### Grammar as of v0.1.1
-/// `function_definition = Function(identifier, instruction* body)`
+/// ### Grammar as of v0.1.12
+/// `function_definition = Function(identifier, bool global, val* params, instruction* body)`
+/// Each parameter is the variable it is passed in.
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunDefTacky {
-    pub identifier: String,
+    pub identifier: Symbol,
+    pub global: bool,
+    pub params: Vec<ValTacky>,
     pub instructions: Vec<InstructionTacky>,
 }
 
+/// prints a `function name:` header, `static` first for a function not visible
+/// outside the file, with the parameters in parentheses if there are any, then
+/// one instruction per indented line.
+/// Labels sit back at half the indentation, so the code they mark stands out.
+impl Display for FunDefTacky {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.global {
+            write!(f, "static ")?;
+        }
+        write!(f, "function {}", self.identifier)?;
+        if !self.params.is_empty() {
+            write!(f, "({})", join(&self.params))?;
+        }
+        writeln!(f, ":")?;
+        for instr in self.instructions.iter() {
+            match instr {
+                InstructionTacky::Label { name: _ } => writeln!(f, "  {}", instr)?,
+                _ => writeln!(f, "    {}", instr)?,
+            }
+        }
+        Ok(())
+    }
+}
+
 /// TACKY instruction
-/// ### Grammar as of v0.1.2
+/// ### Grammar as of v0.1.17
 /// ```text
 /// instruction = Return(val)
+///             | SignExtend(val src, val dst) | Truncate(val src, val dst)
+///             | ZeroExtend(val src, val dst)
+///             | DoubleToInt(val src, val dst) | IntToDouble(val src, val dst)
 ///             | Unary(unary_operator, val src, val dst)
 ///             | Binary(binary_operator, val src1, val src2, val dst)
+///             | Copy(val src, val dst)
+///             | Jump(identifier target)
+///             | JumpIfZero(val condition, identifier target)
+///             | JumpIfNotZero(val condition, identifier target)
+///             | Label(identifier)
+///             | FunCall(identifier name, val* args, val dst)
+///             | TailCall(identifier name, val* args)
+///             | GetAddress(val src, val dst)
+///             | Load(val src_ptr, val dst) | Store(val src, val dst_ptr)
+///             | AddPtr(val ptr, val index, int scale, val dst)
+///             | Loc(int file, int line, int col)
 /// ```
-#[derive(PartialEq, Debug)]
+/// `SignExtend` widens a signed value to the size of `dst`, `ZeroExtend` an
+/// unsigned one, and `Truncate` keeps as many low bits of a value as `dst` has.
+/// Converting between a signed and an unsigned type of one size keeps the bits,
+/// and is a `Copy` between values of the two types. `DoubleToInt` truncates a
+/// `double` toward zero into an `int` or a `long`, and `IntToDouble` rounds
+/// either of them to the nearest `double`; both take the integer as signed, so
+/// the unsigned types convert by way of the signed ones.
+/// `GetAddress` takes the address of the variable `src`, which doesn't read
+/// it; `Load` reads the value `src_ptr` points to, and `Store` writes `src`
+/// where `dst_ptr` points. `AddPtr` moves `ptr` along `index` elements of
+/// `scale` bytes each, the `long` `index` negative to move back.
+/// `TailCall` calls `name` and returns what it returns, which lets the callee
+/// return straight to this function's caller.
+/// `Loc` says the instructions after it were written at `line` and `col` of
+/// the numbered `file`; it only appears with `-g`, and does nothing.
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InstructionTacky {
     Ret {
         v: ValTacky,
     },
+    SignExtend {
+        src: ValTacky,
+        dst: ValTacky,
+    },
+    Truncate {
+        src: ValTacky,
+        dst: ValTacky,
+    },
+    ZeroExtend {
+        src: ValTacky,
+        dst: ValTacky,
+    },
+    DoubleToInt {
+        src: ValTacky,
+        dst: ValTacky,
+    },
+    IntToDouble {
+        src: ValTacky,
+        dst: ValTacky,
+    },
     Unary {
         op: UnaryOp,
         src: ValTacky,
@@ -40,57 +277,695 @@ pub enum InstructionTacky {
         src2: ValTacky,
         dst: ValTacky,
     },
+    Copy {
+        src: ValTacky,
+        dst: ValTacky,
+    },
+    Jump {
+        target: String,
+    },
+    JumpIfZero {
+        condition: ValTacky,
+        target: String,
+    },
+    JumpIfNotZero {
+        condition: ValTacky,
+        target: String,
+    },
+    Label {
+        name: String,
+    },
+    FunCall {
+        name: Symbol,
+        args: Vec<ValTacky>,
+        dst: ValTacky,
+    },
+    TailCall {
+        name: Symbol,
+        args: Vec<ValTacky>,
+    },
+    GetAddress {
+        src: ValTacky,
+        dst: ValTacky,
+    },
+    Load {
+        src_ptr: ValTacky,
+        dst: ValTacky,
+    },
+    Store {
+        src: ValTacky,
+        dst_ptr: ValTacky,
+    },
+    AddPtr {
+        ptr: ValTacky,
+        index: ValTacky,
+        scale: usize,
+        dst: ValTacky,
+    },
+    Loc {
+        file: u32,
+        line: usize,
+        col: usize,
+    },
+}
+
+/// the items printed one after another, split by commas.
+fn join(items: &[impl Display]) -> String {
+    let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+    items.join(", ")
+}
+
+/// prints the instruction as an assignment or a keyword statement,
+/// e.g. `tmp.2 = tmp.1 + 1`, `tmp.3 = call f(tmp.2, 4)` or `jump_if_zero tmp.2, end`.
+/// Addresses, loads and stores are written as in C: `tmp.4 = &a.0`, `tmp.5 = *tmp.4`
+/// and `*tmp.4 = 1`.
+impl Display for InstructionTacky {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ret { v } => write!(f, "ret {}", v),
+            Self::SignExtend { src, dst } => write!(f, "{} = sign_extend {}", dst, src),
+            Self::Truncate { src, dst } => write!(f, "{} = truncate {}", dst, src),
+            Self::ZeroExtend { src, dst } => write!(f, "{} = zero_extend {}", dst, src),
+            Self::DoubleToInt { src, dst } => write!(f, "{} = double_to_int {}", dst, src),
+            Self::IntToDouble { src, dst } => write!(f, "{} = int_to_double {}", dst, src),
+            Self::Unary { op, src, dst } => write!(f, "{} = {}{}", dst, op.symbol(), src),
+            Self::Binary {
+                op,
+                src1,
+                src2,
+                dst,
+            } => write!(f, "{} = {} {} {}", dst, src1, op.symbol(), src2),
+            Self::Copy { src, dst } => write!(f, "{} = {}", dst, src),
+            Self::Jump { target } => write!(f, "jump {}", target),
+            Self::JumpIfZero { condition, target } => {
+                write!(f, "jump_if_zero {}, {}", condition, target)
+            }
+            Self::JumpIfNotZero { condition, target } => {
+                write!(f, "jump_if_not_zero {}, {}", condition, target)
+            }
+            Self::Label { name } => write!(f, "{}:", name),
+            Self::FunCall { name, args, dst } => {
+                write!(f, "{} = call {}({})", dst, name, join(args))
+            }
+            Self::TailCall { name, args } => write!(f, "tail_call {}({})", name, join(args)),
+            Self::GetAddress { src, dst } => write!(f, "{} = &{}", dst, src),
+            Self::Load { src_ptr, dst } => write!(f, "{} = *{}", dst, src_ptr),
+            Self::Store { src, dst_ptr } => write!(f, "*{} = {}", dst_ptr, src),
+            Self::AddPtr {
+                ptr,
+                index,
+                scale,
+                dst,
+            } => write!(f, "{} = add_ptr {}, {}, {}", dst, ptr, index, scale),
+            Self::Loc { file, line, col } => write!(f, "loc {} {}:{}", file, line, col),
+        }
+    }
+}
+
+impl InstructionTacky {
+    /// the value written by this instruction, if any. A store writes
+    /// wherever its pointer points, not a value of its own.
+    pub fn dst(&self) -> Option<&ValTacky> {
+        match self {
+            Self::Ret { v: _ }
+            | Self::Jump { target: _ }
+            | Self::JumpIfZero { .. }
+            | Self::JumpIfNotZero { .. }
+            | Self::Label { name: _ }
+            | Self::Store { .. }
+            | Self::TailCall { .. }
+            | Self::Loc { .. } => None,
+            Self::SignExtend { dst, .. }
+            | Self::Truncate { dst, .. }
+            | Self::ZeroExtend { dst, .. }
+            | Self::DoubleToInt { dst, .. }
+            | Self::IntToDouble { dst, .. }
+            | Self::Unary { dst, .. }
+            | Self::Binary { dst, .. }
+            | Self::Copy { dst, .. }
+            | Self::FunCall { dst, .. }
+            | Self::GetAddress { dst, .. }
+            | Self::Load { dst, .. }
+            | Self::AddPtr { dst, .. } => Some(dst),
+        }
+    }
+
+    /// the values read by this instruction. Taking a variable's address
+    /// doesn't read it, and it can't be replaced by another value.
+    pub fn srcs(&self) -> Vec<&ValTacky> {
+        match self {
+            Self::Ret { v } => vec![v],
+            Self::JumpIfZero { condition, .. } | Self::JumpIfNotZero { condition, .. } => {
+                vec![condition]
+            }
+            Self::Jump { target: _ }
+            | Self::Label { name: _ }
+            | Self::GetAddress { .. }
+            | Self::Loc { .. } => vec![],
+            Self::Load { src_ptr, .. } => vec![src_ptr],
+            Self::Store { src, dst_ptr } => vec![src, dst_ptr],
+            Self::SignExtend { src, .. }
+            | Self::Truncate { src, .. }
+            | Self::ZeroExtend { src, .. }
+            | Self::DoubleToInt { src, .. }
+            | Self::IntToDouble { src, .. }
+            | Self::Unary { src, .. }
+            | Self::Copy { src, .. } => vec![src],
+            Self::Binary { src1, src2, .. } => vec![src1, src2],
+            Self::AddPtr { ptr, index, .. } => vec![ptr, index],
+            Self::FunCall { args, .. } | Self::TailCall { args, .. } => args.iter().collect(),
+        }
+    }
+
+    /// mutable access to the values read by this instruction, for passes that rewrite operands.
+    pub fn srcs_mut(&mut self) -> Vec<&mut ValTacky> {
+        match self {
+            Self::Ret { v } => vec![v],
+            Self::JumpIfZero { condition, .. } | Self::JumpIfNotZero { condition, .. } => {
+                vec![condition]
+            }
+            Self::Jump { target: _ }
+            | Self::Label { name: _ }
+            | Self::GetAddress { .. }
+            | Self::Loc { .. } => vec![],
+            Self::Load { src_ptr, .. } => vec![src_ptr],
+            Self::Store { src, dst_ptr } => vec![src, dst_ptr],
+            Self::SignExtend { src, .. }
+            | Self::Truncate { src, .. }
+            | Self::ZeroExtend { src, .. }
+            | Self::DoubleToInt { src, .. }
+            | Self::IntToDouble { src, .. }
+            | Self::Unary { src, .. }
+            | Self::Copy { src, .. } => vec![src],
+            Self::Binary { src1, src2, .. } => vec![src1, src2],
+            Self::AddPtr { ptr, index, .. } => vec![ptr, index],
+            Self::FunCall { args, .. } | Self::TailCall { args, .. } => args.iter_mut().collect(),
+        }
+    }
 }
 
 /// TACKY value
-/// ### Grammar as of v0.1.1
-/// `val = Constant(int) | Var(identifier)`
-#[derive(PartialEq, Debug, Clone)]
+/// ### Grammar as of v0.1.12
+/// `val = Constant(const) | Var(identifier, type)`
+/// A variable carries its type wherever it is used, so later passes can size it.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ValTacky {
-    Const { int: i32 },
-    TmpVar { no: u16 },
+    Const { c: Const },
+    TmpVar { name: Symbol, ty: CType },
+}
+
+impl ValTacky {
+    /// the generated `int` temporary `tmp.<no>`.
+    pub fn tmp(no: u32) -> Self {
+        ValTacky::TmpVar {
+            name: Symbol::numbered("tmp", no),
+            ty: CType::Int,
+        }
+    }
+
+    /// the `int` constant `int`.
+    pub fn int(int: i32) -> Self {
+        ValTacky::Const {
+            c: Const::Int { int },
+        }
+    }
+
+    pub fn ty(&self) -> CType {
+        match self {
+            Self::Const { c } => c.ty(),
+            Self::TmpVar { ty, .. } => *ty,
+        }
+    }
+}
+
+impl Display for ValTacky {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Const { c } => write!(f, "{}", c),
+            Self::TmpVar { name, .. } => write!(f, "{}", name),
+        }
+    }
+}
+
+/// lowers the C AST to TACKY, unoptimized. It should already have been through
+/// `semantic::analyze`, which gives its variables unique names and its loops labels.
+pub fn gen_tacky(cprog: ProgramC) -> ProgramTacky {
+    TackyEmitter::gen_tacky(cprog)
 }
 
 /// Keeps track of various data
 /// within TACKY representation.
-pub struct TackyEmitter {
-    tmp_no: u16,
+/// Temporaries and labels are numbered across the whole program, so labels stay
+/// unique in the assembly file, where local labels aren't scoped to a function.
+/// A variable the source happened to name `tmp` has the same form as the
+/// temporaries once resolved, so the program's variables are kept to skip over.
+/// Whether each function is visible outside the file is decided by its first
+/// declaration, and kept for when its definition is translated. The types of
+/// the variables and of what each function returns are kept too, for the
+/// values that hold them; a function never declared returns an int. Each
+/// string literal becomes a constant of its own, kept until the end, when they
+/// follow the variables with static storage.
+/// Given the source's `lines`, each statement starts with the line and column it
+/// was written at, in the file numbered by its place in `files`.
+#[derive(Default)]
+pub struct TackyEmitter<'a> {
+    tmp_no: u32,
+    label_no: u32,
+    string_no: u32,
+    strings: Vec<(Symbol, StaticInit)>,
+    variables: HashMap<Symbol, CType>,
+    global_functions: HashMap<Symbol, bool>,
+    return_types: HashMap<Symbol, CType>,
+    lines: Option<&'a LineIndex<'a>>,
+    files: Vec<Symbol>,
 }
 
-impl TackyEmitter {
+/// What the declarations of a variable with static storage said about it: its
+/// type, whether it is visible outside the file, the value one initialized it
+/// to, and whether one defined it without an initializer, which initializes it
+/// to 0.
+struct StaticVariable {
+    ty: CType,
+    global: bool,
+    init: Option<StaticInit>,
+    tentative: bool,
+}
+
+impl<'a> TackyEmitter<'a> {
     pub fn new() -> Self {
-        TackyEmitter { tmp_no: 0 }
+        TackyEmitter {
+            tmp_no: 0,
+            label_no: 0,
+            string_no: 0,
+            strings: Vec::new(),
+            variables: HashMap::new(),
+            global_functions: HashMap::new(),
+            return_types: HashMap::new(),
+            lines: None,
+            files: Vec::new(),
+        }
     }
+
     pub fn gen_tacky(cprog: ProgramC) -> ProgramTacky {
-        ProgramTacky {
-            function: Box::new(Self::new().translate_fundef(*cprog.function)),
+        Self::new().translate_program(cprog)
+    }
+
+    /// lowers the program as `gen_tacky` does, marking where in `lines` each
+    /// statement came from for the debugger.
+    pub fn gen_tacky_with_lines(cprog: ProgramC, lines: &'a LineIndex<'a>) -> ProgramTacky {
+        TackyEmitter {
+            lines: Some(lines),
+            ..Self::new()
         }
+        .translate_program(cprog)
     }
 
+    fn translate_program(mut self, cprog: ProgramC) -> ProgramTacky {
+        let mut order = Vec::new();
+        let mut statics = HashMap::new();
+        let mut static_decls = Vec::new();
+        for item in cprog.items.iter() {
+            let global = item.storage() != Some(StorageClass::Static);
+            match item {
+                TopLevelC::Function { fundef } => {
+                    self.global_functions
+                        .entry(fundef.identifier)
+                        .or_insert(global);
+                    self.return_types.insert(fundef.identifier, fundef.ret);
+                    self.variables
+                        .extend(fundef.params.iter().map(|param| (param.name, param.ty)));
+                    let mut decls = Vec::new();
+                    declared_in(&fundef.body, &mut decls);
+                    for decl in decls {
+                        self.variables.insert(decl.name, decl.ty);
+                        if decl.storage.is_some() {
+                            static_decls.push(decl);
+                        }
+                    }
+                }
+                TopLevelC::Declaration { decl } => {
+                    self.global_functions
+                        .entry(decl.identifier)
+                        .or_insert(global);
+                    self.return_types.insert(decl.identifier, decl.ret);
+                }
+                TopLevelC::Variable { decl } => {
+                    self.variables.insert(decl.name, decl.ty);
+                    static_decls.push(decl);
+                }
+                TopLevelC::Struct { .. } => {}
+            }
+        }
+        // once every variable is known, so the string constants they point to
+        // can be named around them
+        for decl in static_decls {
+            self.declare_static(decl, &mut order, &mut statics);
+        }
+        let functions = cprog.items.into_iter().filter_map(|item| match item {
+            TopLevelC::Function { fundef } => Some(TopLevelTacky::Function {
+                fundef: self.translate_fundef(fundef),
+            }),
+            // declarations had their say above
+            TopLevelC::Declaration { .. }
+            | TopLevelC::Variable { .. }
+            | TopLevelC::Struct { .. } => None,
+        });
+        let mut items: Vec<TopLevelTacky> = functions.collect();
+        items.splice(
+            0..0,
+            self.files
+                .iter()
+                .zip(1..)
+                .map(|(name, no)| TopLevelTacky::File { no, name: *name }),
+        );
+        items.extend(order.into_iter().map(|name| {
+            let var = &statics[&name];
+            TopLevelTacky::StaticVariable {
+                name,
+                global: var.global,
+                init: var.init.clone().or(var.tentative.then_some(
+                    match var.ty.is_array() || var.ty.is_struct() {
+                        true => StaticInit::Zero { ty: var.ty },
+                        false => StaticInit::Scalar {
+                            c: Const::Int { int: 0 }.convert(var.ty),
+                        },
+                    },
+                )),
+            }
+        }));
+        items.extend(
+            self.strings
+                .drain(..)
+                .map(|(name, init)| TopLevelTacky::StaticConstant { name, init }),
+        );
+        ProgramTacky { items }
+    }
+
+    /// a body that can run off its end returns 0 there, which is what `main`
+    /// has to do, and as good as anything for other functions. With lines to
+    /// mark, the function starts at its declaration, and that return is at its
+    /// closing brace.
     fn translate_fundef(&mut self, cfundef: FunDefC) -> FunDefTacky {
+        let falls_through = block_may_fall_through(&cfundef.body);
+        let close = cfundef.body.span.end - 1;
+        let mut instructions = Vec::new();
+        self.locate(cfundef.span.start, &mut instructions);
+        instructions.extend(self.translate_block(cfundef.body));
+        if falls_through {
+            self.locate(close, &mut instructions);
+            instructions.push(InstructionTacky::Ret {
+                v: ValTacky::Const {
+                    c: Const::Int { int: 0 }.convert(cfundef.ret),
+                },
+            });
+        }
         FunDefTacky {
             identifier: cfundef.identifier,
-            instructions: self.translate_statement(*cfundef.statement),
+            global: self.global_functions[&cfundef.identifier],
+            params: cfundef
+                .params
+                .iter()
+                .map(|param| ValTacky::TmpVar {
+                    name: param.name,
+                    ty: param.ty,
+                })
+                .collect(),
+            instructions,
         }
     }
 
+    fn translate_block(&mut self, block: Block) -> Vec<InstructionTacky> {
+        let mut instrs = Vec::new();
+        for item in block.items {
+            match item {
+                BlockItem::Statement { stmt } => instrs.extend(self.translate_statement(stmt)),
+                BlockItem::Declaration { decl } => self.translate_declaration(decl, &mut instrs),
+                BlockItem::Struct { .. } => {}
+            }
+        }
+        instrs
+    }
+
+    /// a declaration with an initializer copies it into the variable; one
+    /// without leaves it as it was. A variable with static storage was
+    /// initialized before the program started. A `char` array is copied from
+    /// a constant of its own type holding the string literal, padded with
+    /// nulls.
+    fn translate_declaration(&mut self, decl: Declaration, instrs: &mut Vec<InstructionTacky>) {
+        if decl.storage.is_some() {
+            return;
+        }
+        if let Some(init) = decl.init {
+            self.locate(decl.span.start, instrs);
+            let src = match *init {
+                Exp::String { val, .. } if decl.ty.is_array() => ValTacky::TmpVar {
+                    name: self.string_constant(val, decl.ty),
+                    ty: decl.ty,
+                },
+                init => self.translate_expression(init, instrs),
+            };
+            instrs.push(InstructionTacky::Copy {
+                src,
+                dst: ValTacky::TmpVar {
+                    name: decl.name,
+                    ty: decl.ty,
+                },
+            });
+        }
+    }
+
+    /// marks the code that follows as written at `offset` in the source, when
+    /// there are lines to find it in, numbering its file the first time it
+    /// comes up.
+    fn locate(&mut self, offset: usize, instrs: &mut Vec<InstructionTacky>) {
+        let Some(lines) = self.lines else {
+            return;
+        };
+        let loc = lines.locate(offset);
+        let name = Symbol::intern(loc.file);
+        let file = match self.files.iter().position(|file| *file == name) {
+            Some(i) => i,
+            None => {
+                self.files.push(name);
+                self.files.len() - 1
+            }
+        };
+        instrs.push(InstructionTacky::Loc {
+            file: file as u32 + 1,
+            line: loc.line,
+            col: loc.col,
+        });
+    }
+
     fn translate_statement(&mut self, cstate: StatementC) -> Vec<InstructionTacky> {
         let mut instrs = Vec::new();
+        // a block's own statements say where they are, and a null one has no code
+        if !matches!(
+            cstate,
+            StatementC::Compound { .. } | StatementC::Null { .. }
+        ) {
+            self.locate(cstate.span().start, &mut instrs);
+        }
         match cstate {
-            StatementC::Return { exp } => {
+            StatementC::Return { exp, .. } => {
                 let v = self.translate_expression(*exp, &mut instrs);
                 instrs.push(InstructionTacky::Ret { v });
             }
+            StatementC::Expression { exp, .. } => {
+                self.translate_expression(*exp, &mut instrs);
+            }
+            StatementC::Compound { block } => instrs.extend(self.translate_block(block)),
+            StatementC::For {
+                init,
+                condition,
+                post,
+                body,
+                label,
+                ..
+            } => {
+                let label = label.expect("loops are labeled by semantic analysis");
+                self.translate_for(init, condition, post, *body, label, &mut instrs);
+            }
+            StatementC::Switch {
+                control,
+                body,
+                label,
+                cases,
+                ..
+            } => {
+                let label = label.expect("a switch is labeled by semantic analysis");
+                self.translate_switch(*control, *body, label, cases, &mut instrs);
+            }
+            StatementC::Case { body, label, .. } | StatementC::Default { body, label, .. } => {
+                let label = label.expect("a case is labeled by semantic analysis");
+                instrs.push(InstructionTacky::Label {
+                    name: label.to_string(),
+                });
+                instrs.extend(self.translate_statement(*body));
+            }
+            StatementC::Break { label, .. } => {
+                let label = label.expect("a break is labeled by semantic analysis");
+                instrs.push(InstructionTacky::Jump {
+                    target: break_label(label),
+                });
+            }
+            StatementC::Continue { label, .. } => {
+                let label = label.expect("a continue is labeled by semantic analysis");
+                instrs.push(InstructionTacky::Jump {
+                    target: continue_label(label),
+                });
+            }
+            StatementC::Null { .. } => {}
+            StatementC::If {
+                condition,
+                then_stmt,
+                else_stmt,
+                ..
+            } => {
+                let condition = self.translate_expression(*condition, &mut instrs);
+                let end_label = self.get_new_label("if_end");
+                match else_stmt {
+                    None => {
+                        instrs.push(InstructionTacky::JumpIfZero {
+                            condition,
+                            target: end_label.clone(),
+                        });
+                        instrs.extend(self.translate_statement(*then_stmt));
+                    }
+                    Some(else_stmt) => {
+                        let else_label = self.get_new_label("else");
+                        instrs.push(InstructionTacky::JumpIfZero {
+                            condition,
+                            target: else_label.clone(),
+                        });
+                        instrs.extend(self.translate_statement(*then_stmt));
+                        instrs.extend([
+                            InstructionTacky::Jump {
+                                target: end_label.clone(),
+                            },
+                            InstructionTacky::Label { name: else_label },
+                        ]);
+                        instrs.extend(self.translate_statement(*else_stmt));
+                    }
+                }
+                instrs.push(InstructionTacky::Label { name: end_label });
+            }
         };
         instrs
     }
 
+    /// lowers a `for` loop to a test at the top, jumping out when it fails, and
+    /// a jump back to it after the body and the post expression. A `break`
+    /// jumps past the loop and a `continue` to its post expression, so a loop
+    /// without a condition only leaves by one of them or by returning.
+    fn translate_for(
+        &mut self,
+        init: ForInit,
+        condition: Option<Box<Exp>>,
+        post: Option<Box<Exp>>,
+        body: StatementC,
+        label: Symbol,
+        instrs: &mut Vec<InstructionTacky>,
+    ) {
+        match init {
+            ForInit::Declaration { decl } => self.translate_declaration(decl, instrs),
+            ForInit::Expression { exp: Some(exp) } => {
+                self.translate_expression(*exp, instrs);
+            }
+            ForInit::Expression { exp: None } => {}
+        }
+        let start_label = label.to_string();
+        instrs.push(InstructionTacky::Label {
+            name: start_label.clone(),
+        });
+        if let Some(condition) = condition {
+            let condition = self.translate_expression(*condition, instrs);
+            instrs.push(InstructionTacky::JumpIfZero {
+                condition,
+                target: break_label(label),
+            });
+        }
+        instrs.extend(self.translate_statement(body));
+        instrs.push(InstructionTacky::Label {
+            name: continue_label(label),
+        });
+        if let Some(post) = post {
+            self.translate_expression(*post, instrs);
+        }
+        instrs.extend([
+            InstructionTacky::Jump {
+                target: start_label,
+            },
+            InstructionTacky::Label {
+                name: break_label(label),
+            },
+        ]);
+    }
+
+    /// lowers a `switch` to a comparison of its control value with each case in
+    /// turn, jumping to the first that matches, then to the default or past the
+    /// body if none does. The body is then laid out once, with the cases'
+    /// labels where they were written, so control falls from one to the next.
+    fn translate_switch(
+        &mut self,
+        control: Exp,
+        body: StatementC,
+        label: Symbol,
+        cases: Vec<SwitchCase>,
+        instrs: &mut Vec<InstructionTacky>,
+    ) {
+        let control = self.translate_expression(control, instrs);
+        let mut default = None;
+        for case in cases {
+            let Some(value) = case.value else {
+                default = Some(case.label);
+                continue;
+            };
+            let matched = self.get_new_tmpvar(CType::Int);
+            instrs.extend([
+                InstructionTacky::Binary {
+                    op: BinaryOp::Equal,
+                    src1: control.clone(),
+                    src2: ValTacky::Const { c: value },
+                    dst: matched.clone(),
+                },
+                InstructionTacky::JumpIfNotZero {
+                    condition: matched,
+                    target: case.label.to_string(),
+                },
+            ]);
+        }
+        instrs.push(InstructionTacky::Jump {
+            target: default.map_or_else(|| break_label(label), |default| default.to_string()),
+        });
+        instrs.extend(self.translate_statement(body));
+        instrs.push(InstructionTacky::Label {
+            name: break_label(label),
+        });
+    }
+
     fn translate_expression(&mut self, cexp: Exp, instrs: &mut Vec<InstructionTacky>) -> ValTacky {
         match cexp {
-            Exp::Const { c } => ValTacky::Const { int: c },
-            Exp::Unary { op, exp } => {
+            Exp::Const { c, .. } => ValTacky::Const { c },
+            Exp::Var { name, .. } => self.translate_var(name, instrs),
+            Exp::String { val, .. } => self.translate_string(val, instrs),
+            Exp::Cast { target, exp, .. } => {
+                let src = self.translate_expression(*exp, instrs);
+                self.translate_conversion(src, target, instrs)
+            }
+            Exp::Assignment { lvalue, rvalue, .. } => {
+                self.translate_assignment(*lvalue, *rvalue, instrs)
+            }
+            Exp::CompoundAssignment { .. } => self.translate_compound_assignment(cexp, instrs),
+            Exp::Increment { .. } => self.translate_increment(cexp, instrs),
+            Exp::Dereference { exp, .. } => self.translate_dereference(*exp, instrs),
+            Exp::Subscript { exp, index, .. } => self.translate_subscript(*exp, *index, instrs),
+            Exp::AddrOf { exp, .. } => self.translate_address(*exp, instrs),
+            Exp::Dot { .. } | Exp::Arrow { .. } => self.translate_member_value(cexp, instrs),
+            Exp::Unary { op, exp, .. } => {
                 let src = self.translate_expression(*exp, instrs);
-                let dst = self.get_new_tmpvar();
+                let dst = self.get_new_tmpvar(src.ty());
                 instrs.push(InstructionTacky::Unary {
                     op,
                     src,
@@ -98,14 +973,36 @@ impl TackyEmitter {
                 });
                 dst
             }
-            Exp::Binary { op, l_exp, r_exp } => {
+            Exp::Binary {
+                op: op @ (BinaryOp::And | BinaryOp::Or),
+                l_exp,
+                r_exp,
+                ..
+            } => self.translate_logical(op, *l_exp, *r_exp, instrs),
+            Exp::Binary {
+                op, l_exp, r_exp, ..
+            } => {
                 let src1 = self.translate_expression(*l_exp, instrs);
                 let src2 = self.translate_expression(*r_exp, instrs);
-                let dst = self.get_new_tmpvar();
-                instrs.push(InstructionTacky::Binary {
-                    op,
-                    src1,
-                    src2,
+                self.translate_binary(op, src1, src2, instrs)
+            }
+            Exp::Conditional {
+                condition,
+                then_exp,
+                else_exp,
+                ..
+            } => self.translate_conditional(*condition, *then_exp, *else_exp, instrs),
+            // arguments are evaluated left to right, each before the call
+            Exp::FunctionCall { name, args, .. } => {
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.translate_expression(arg, instrs))
+                    .collect();
+                let ret = self.return_types.get(&name).copied().unwrap_or(CType::Int);
+                let dst = self.get_new_tmpvar(ret);
+                instrs.push(InstructionTacky::FunCall {
+                    name,
+                    args,
                     dst: dst.clone(),
                 });
                 dst
@@ -113,14 +1010,932 @@ impl TackyEmitter {
         }
     }
 
-    fn get_new_tmpvar(&mut self) -> ValTacky {
-        self.tmp_no += 1;
+    /// a store through a pointer gives the value stored.
+    fn translate_assignment(
+        &mut self,
+        lvalue: Exp,
+        rvalue: Exp,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        match lvalue {
+            Exp::Var { name, .. } => {
+                let src = self.translate_expression(rvalue, instrs);
+                let dst = self.variable(name);
+                instrs.push(InstructionTacky::Copy {
+                    src,
+                    dst: dst.clone(),
+                });
+                dst
+            }
+            Exp::Dereference { exp, .. } => {
+                let dst_ptr = self.translate_expression(*exp, instrs);
+                self.translate_store(dst_ptr, rvalue, instrs)
+            }
+            Exp::Subscript { exp, index, .. } => {
+                let dst_ptr = self.translate_element(*exp, *index, instrs);
+                self.translate_store(dst_ptr, rvalue, instrs)
+            }
+            lvalue @ (Exp::Dot { .. } | Exp::Arrow { .. }) => {
+                let dst_ptr = self.translate_address(lvalue, instrs);
+                self.translate_store(dst_ptr, rvalue, instrs)
+            }
+            _ => unreachable!("only an lvalue is assigned to after semantic analysis"),
+        }
+    }
+
+    /// `lvalue op= rvalue`: the object is found once, read, converted to the
+    /// type of `rvalue`, which type checking made the one the operation is
+    /// carried out in, and the result converted back to be written to it. A
+    /// pointer isn't converted, but moved along by the `long` it is given.
+    fn translate_compound_assignment(
+        &mut self,
+        exp: Exp,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        let Exp::CompoundAssignment {
+            op, lvalue, rvalue, ..
+        } = exp
+        else {
+            unreachable!("only a compound assignment is translated here");
+        };
+        let (current, dst_ptr) = self.translate_lvalue(*lvalue, instrs);
+        let src2 = self.translate_expression(*rvalue, instrs);
+        let ty = current.ty();
+        let src1 = match ty.is_pointer() {
+            true => current.clone(),
+            false => self.translate_conversion(current.clone(), src2.ty(), instrs),
+        };
+        let result = self.translate_binary(op, src1, src2, instrs);
+        let src = self.translate_conversion(result, ty, instrs);
+        self.translate_write(current, dst_ptr, src, instrs)
+    }
+
+    /// `++x`, `--x`, `x++` or `x--`: one is added to or subtracted from `x`
+    /// as by a compound assignment, promoted first if it is narrower than an
+    /// `int`. A postfix one gives the value `x` had before, which a variable
+    /// is copied out of so that writing it doesn't change it.
+    fn translate_increment(&mut self, exp: Exp, instrs: &mut Vec<InstructionTacky>) -> ValTacky {
+        let Exp::Increment { op, exp, .. } = exp else {
+            unreachable!("only an increment or a decrement is translated here");
+        };
+        let (current, dst_ptr) = self.translate_lvalue(*exp, instrs);
+        let old = match (op.is_postfix(), &dst_ptr) {
+            (true, None) => {
+                let old = self.get_new_tmpvar(current.ty());
+                instrs.push(InstructionTacky::Copy {
+                    src: current.clone(),
+                    dst: old.clone(),
+                });
+                old
+            }
+            _ => current.clone(),
+        };
+        let ty = current.ty();
+        let src = match ty.is_pointer() {
+            true => {
+                let one = ValTacky::Const {
+                    c: Const::Long { long: 1 },
+                };
+                self.translate_binary(op.binary(), current.clone(), one, instrs)
+            }
+            false => {
+                let src1 = self.translate_conversion(current.clone(), ty.promoted(), instrs);
+                let one = ValTacky::Const {
+                    c: Const::Int { int: 1 }.convert(ty.promoted()),
+                };
+                let result = self.translate_binary(op.binary(), src1, one, instrs);
+                self.translate_conversion(result, ty, instrs)
+            }
+        };
+        let new = self.translate_write(current, dst_ptr, src, instrs);
+        match op.is_postfix() {
+            true => old,
+            false => new,
+        }
+    }
+
+    /// the current value of `lvalue` and, unless it is a variable that can be
+    /// written directly, the address it is read from and written back to.
+    fn translate_lvalue(
+        &mut self,
+        lvalue: Exp,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> (ValTacky, Option<ValTacky>) {
+        match lvalue {
+            Exp::Var { name, .. } if !self.variable(name).ty().is_array() => {
+                (self.variable(name), None)
+            }
+            lvalue => {
+                let dst_ptr = self.translate_address(lvalue, instrs);
+                (self.translate_load(dst_ptr.clone(), instrs), Some(dst_ptr))
+            }
+        }
+    }
+
+    /// writes `src` back to the lvalue `translate_lvalue` gave `current` and
+    /// `dst_ptr` for, giving the value written.
+    fn translate_write(
+        &mut self,
+        current: ValTacky,
+        dst_ptr: Option<ValTacky>,
+        src: ValTacky,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        match dst_ptr {
+            None => {
+                instrs.push(InstructionTacky::Copy {
+                    src,
+                    dst: current.clone(),
+                });
+                current
+            }
+            Some(dst_ptr) => {
+                instrs.push(InstructionTacky::Store {
+                    src: src.clone(),
+                    dst_ptr,
+                });
+                src
+            }
+        }
+    }
+
+    /// the value of the variable `name`; an array's is the address of its
+    /// first element, which it doesn't read.
+    fn translate_var(&mut self, name: Symbol, instrs: &mut Vec<InstructionTacky>) -> ValTacky {
+        let src = self.variable(name);
+        self.translate_decay(src, instrs)
+    }
+
+    /// a string literal is the address of the first byte of its constant.
+    fn translate_string(&mut self, val: Vec<u8>, instrs: &mut Vec<InstructionTacky>) -> ValTacky {
+        let ty = CType::Char.array_of(val.len() as u32 + 1);
+        let src = ValTacky::TmpVar {
+            name: self.string_constant(val, ty),
+            ty,
+        };
+        self.translate_decay(src, instrs)
+    }
+
+    /// `src` as it is used in an expression, the address of its first element
+    /// if it is an array.
+    fn translate_decay(&mut self, src: ValTacky, instrs: &mut Vec<InstructionTacky>) -> ValTacky {
+        if !src.ty().is_array() {
+            return src;
+        }
+        let dst = self.get_new_tmpvar(src.ty().decayed());
+        instrs.push(InstructionTacky::GetAddress {
+            src,
+            dst: dst.clone(),
+        });
+        dst
+    }
+
+    /// `*exp`, what the pointer `exp` points to.
+    fn translate_dereference(&mut self, exp: Exp, instrs: &mut Vec<InstructionTacky>) -> ValTacky {
+        let src_ptr = self.translate_expression(exp, instrs);
+        self.translate_load(src_ptr, instrs)
+    }
+
+    /// `a[i]` is `*(a + i)`.
+    fn translate_subscript(
+        &mut self,
+        exp: Exp,
+        index: Exp,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        let src_ptr = self.translate_element(exp, index, instrs);
+        self.translate_load(src_ptr, instrs)
+    }
+
+    /// the object `src_ptr` points to. An array is used as the address of its
+    /// first element, the address it is at, so nothing is read. A program that
+    /// wasn't type checked may dereference anything, which is taken to point to
+    /// an `int`.
+    fn translate_load(
+        &mut self,
+        src_ptr: ValTacky,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        let referenced = match src_ptr.ty() {
+            CType::Pointer { referenced } => referenced.get(),
+            _ => CType::Int,
+        };
+        let dst = self.get_new_tmpvar(referenced.decayed());
+        instrs.push(match referenced.is_array() {
+            true => InstructionTacky::Copy {
+                src: src_ptr,
+                dst: dst.clone(),
+            },
+            false => InstructionTacky::Load {
+                src_ptr,
+                dst: dst.clone(),
+            },
+        });
+        dst
+    }
+
+    /// stores `rvalue` where `dst_ptr` points, giving the value stored.
+    fn translate_store(
+        &mut self,
+        dst_ptr: ValTacky,
+        rvalue: Exp,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        let src = self.translate_expression(rvalue, instrs);
+        instrs.push(InstructionTacky::Store {
+            src: src.clone(),
+            dst_ptr,
+        });
+        src
+    }
+
+    /// the address of `exp[index]`, the pointer of the two moved along by the
+    /// other. A program that wasn't type checked may subscript two integers,
+    /// the first taken as a pointer.
+    fn translate_element(
+        &mut self,
+        exp: Exp,
+        index: Exp,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        let exp = self.translate_expression(exp, instrs);
+        let index = self.translate_expression(index, instrs);
+        match index.ty().is_pointer() && !exp.ty().is_pointer() {
+            true => self.translate_add_ptr(index, exp, instrs),
+            false => self.translate_add_ptr(exp, index, instrs),
+        }
+    }
+
+    /// `ptr` moved along `index` of the elements it points to, an `int` if it
+    /// isn't a pointer, which only a program that wasn't checked has.
+    fn translate_add_ptr(
+        &mut self,
+        ptr: ValTacky,
+        index: ValTacky,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        let referenced = match ptr.ty() {
+            CType::Pointer { referenced } => referenced.get(),
+            _ => CType::Int,
+        };
+        let index = self.translate_to_long(index, instrs);
+        let dst = self.get_new_tmpvar(referenced.pointer_to());
+        instrs.push(InstructionTacky::AddPtr {
+            ptr,
+            index,
+            scale: referenced.size(),
+            dst: dst.clone(),
+        });
+        dst
+    }
+
+    /// `src` as a `long`, which type checking has already made any index.
+    fn translate_to_long(&mut self, src: ValTacky, instrs: &mut Vec<InstructionTacky>) -> ValTacky {
+        if src.ty() == CType::Long {
+            return src;
+        }
+        let dst = self.get_new_tmpvar(CType::Long);
+        self.translate_cast(src, dst.clone(), instrs);
+        dst
+    }
+
+    /// a binary operation on `src1` and `src2`, other than `&&` and `||`. A
+    /// comparison gives an `int`, and anything else the type of its operands,
+    /// but for pointer arithmetic: adding an integer to a pointer or
+    /// subtracting one from it moves the pointer, and subtracting two pointers
+    /// counts the elements between them.
+    fn translate_binary(
+        &mut self,
+        op: BinaryOp,
+        src1: ValTacky,
+        src2: ValTacky,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        match (&op, src1.ty(), src2.ty()) {
+            (BinaryOp::Add, CType::Pointer { .. }, _) => {
+                return self.translate_add_ptr(src1, src2, instrs)
+            }
+            (BinaryOp::Add, _, CType::Pointer { .. }) => {
+                return self.translate_add_ptr(src2, src1, instrs)
+            }
+            (BinaryOp::Subtract, CType::Pointer { referenced }, CType::Pointer { .. }) => {
+                let bytes = self.get_new_tmpvar(CType::Long);
+                let dst = self.get_new_tmpvar(CType::Long);
+                instrs.extend([
+                    InstructionTacky::Binary {
+                        op,
+                        src1,
+                        src2,
+                        dst: bytes.clone(),
+                    },
+                    InstructionTacky::Binary {
+                        op: BinaryOp::Divide,
+                        src1: bytes,
+                        src2: ValTacky::Const {
+                            c: Const::Long {
+                                long: referenced.get().size() as i64,
+                            },
+                        },
+                        dst: dst.clone(),
+                    },
+                ]);
+                return dst;
+            }
+            (BinaryOp::Subtract, CType::Pointer { .. }, _) => {
+                let index = self.translate_to_long(src2, instrs);
+                let negated = self.get_new_tmpvar(CType::Long);
+                instrs.push(InstructionTacky::Unary {
+                    op: UnaryOp::Negate,
+                    src: index,
+                    dst: negated.clone(),
+                });
+                return self.translate_add_ptr(src1, negated, instrs);
+            }
+            _ => (),
+        }
+        let ty = match op {
+            BinaryOp::LessThan
+            | BinaryOp::GreaterThan
+            | BinaryOp::LessOrEqual
+            | BinaryOp::GreaterOrEqual
+            | BinaryOp::Equal
+            | BinaryOp::NotEqual => CType::Int,
+            _ => src1.ty(),
+        };
+        let dst = self.get_new_tmpvar(ty);
+        instrs.push(InstructionTacky::Binary {
+            op,
+            src1,
+            src2,
+            dst: dst.clone(),
+        });
+        dst
+    }
+
+    /// `&*p` is just `p`, which isn't dereferenced, and `&a[i]` the address of
+    /// the element, which isn't read. Any operand but a variable or a string
+    /// literal, which only a program that wasn't checked has, is copied to a
+    /// temporary for the address to point to.
+    fn translate_address(&mut self, exp: Exp, instrs: &mut Vec<InstructionTacky>) -> ValTacky {
+        let src = match exp {
+            Exp::Var { name, .. } => self.variable(name),
+            Exp::String { val, .. } => {
+                let ty = CType::Char.array_of(val.len() as u32 + 1);
+                ValTacky::TmpVar {
+                    name: self.string_constant(val, ty),
+                    ty,
+                }
+            }
+            Exp::Dereference { exp, .. } => return self.translate_expression(*exp, instrs),
+            Exp::Subscript { exp, index, .. } => {
+                return self.translate_element(*exp, *index, instrs)
+            }
+            Exp::Dot { exp, member, .. } => {
+                let ptr = self.translate_address(*exp, instrs);
+                return self.translate_member(ptr, member, instrs);
+            }
+            Exp::Arrow { exp, member, .. } => {
+                let ptr = self.translate_expression(*exp, instrs);
+                return self.translate_member(ptr, member, instrs);
+            }
+            exp => {
+                let val = self.translate_expression(exp, instrs);
+                let src = self.get_new_tmpvar(val.ty());
+                instrs.push(InstructionTacky::Copy {
+                    src: val,
+                    dst: src.clone(),
+                });
+                src
+            }
+        };
+        let dst = self.get_new_tmpvar(src.ty().pointer_to());
+        instrs.push(InstructionTacky::GetAddress {
+            src,
+            dst: dst.clone(),
+        });
+        dst
+    }
+
+    /// the member `exp`, `.` or `->`, picks out, read from its address.
+    fn translate_member_value(&mut self, exp: Exp, instrs: &mut Vec<InstructionTacky>) -> ValTacky {
+        let src_ptr = self.translate_address(exp, instrs);
+        self.translate_load(src_ptr, instrs)
+    }
+
+    /// the address of the member `member` of the structure `ptr` points to,
+    /// that many bytes along. A program that wasn't type checked may name a
+    /// member of anything, which is taken to be an `int` at its start.
+    fn translate_member(
+        &mut self,
+        ptr: ValTacky,
+        member: Symbol,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        let found = match ptr.ty() {
+            CType::Pointer { referenced } => referenced
+                .get()
+                .layout()
+                .and_then(|layout| layout.member(member).map(|found| (found.ty, found.offset))),
+            _ => None,
+        };
+        let (ty, offset) = found.unwrap_or((CType::Int, 0));
+        let dst = self.get_new_tmpvar(ty.pointer_to());
+        instrs.push(match offset {
+            0 => InstructionTacky::Copy {
+                src: ptr,
+                dst: dst.clone(),
+            },
+            _ => InstructionTacky::AddPtr {
+                ptr,
+                index: ValTacky::Const {
+                    c: Const::Long {
+                        long: offset as i64,
+                    },
+                },
+                scale: 1,
+                dst: dst.clone(),
+            },
+        });
+        dst
+    }
+
+    /// `src` converted to `target`, in a new temporary unless it has that type
+    /// already.
+    fn translate_conversion(
+        &mut self,
+        src: ValTacky,
+        target: CType,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        if src.ty() == target {
+            return src;
+        }
+        let dst = self.get_new_tmpvar(target);
+        self.translate_cast(src, dst.clone(), instrs);
+        dst
+    }
+
+    /// converts `src` to the type of `dst` into it. Between integer types it is
+    /// one instruction. A type narrower than `int` goes to and from a `double`
+    /// by way of an `int`, which holds all its values. An `unsigned int` goes to and from a `double` by way of
+    /// a `long`, which holds all its values. An `unsigned long` is split into
+    /// its high and low 32 bits, each converted exactly, and the `double`
+    /// rounded once when they are added back together; one from a `double` at
+    /// least 2^63 is converted after taking 2^63 off, and has it added back.
+    /// Anything becomes a `_Bool` by being compared against zero, so every
+    /// nonzero value is 1.
+    fn translate_cast(&mut self, src: ValTacky, dst: ValTacky, instrs: &mut Vec<InstructionTacky>) {
+        let (from, target) = (src.ty(), dst.ty());
+        let double = |double: f64| ValTacky::Const {
+            c: Const::Double { double },
+        };
+        let two_to_the_63 = 9223372036854775808.0;
+        match (from, target) {
+            (_, CType::Bool) => instrs.push(InstructionTacky::Binary {
+                op: BinaryOp::NotEqual,
+                src1: src,
+                src2: ValTacky::Const {
+                    c: Const::Int { int: 0 }.convert(from),
+                },
+                dst,
+            }),
+            (CType::Double, _) | (_, CType::Double) if from.size() < 4 || target.size() < 4 => {
+                let int = self.get_new_tmpvar(CType::Int);
+                self.translate_cast(src, int.clone(), instrs);
+                self.translate_cast(int, dst, instrs);
+            }
+            (CType::Double, CType::Int | CType::Long) => {
+                instrs.push(InstructionTacky::DoubleToInt { src, dst })
+            }
+            (CType::Int | CType::Long, CType::Double) => {
+                instrs.push(InstructionTacky::IntToDouble { src, dst })
+            }
+            (CType::UInt, CType::Double) => {
+                let long = self.get_new_tmpvar(CType::Long);
+                instrs.extend([
+                    InstructionTacky::ZeroExtend {
+                        src,
+                        dst: long.clone(),
+                    },
+                    InstructionTacky::IntToDouble { src: long, dst },
+                ]);
+            }
+            (CType::Double, CType::UInt) => {
+                let long = self.get_new_tmpvar(CType::Long);
+                instrs.extend([
+                    InstructionTacky::DoubleToInt {
+                        src,
+                        dst: long.clone(),
+                    },
+                    InstructionTacky::Truncate { src: long, dst },
+                ]);
+            }
+            (CType::ULong, CType::Double) => {
+                let halves = ValTacky::Const {
+                    c: Const::ULong { ulong: 1 << 32 },
+                };
+                let mut half = |op, instrs: &mut Vec<InstructionTacky>| {
+                    let (bits, long) = (
+                        self.get_new_tmpvar(CType::ULong),
+                        self.get_new_tmpvar(CType::Long),
+                    );
+                    let converted = self.get_new_tmpvar(CType::Double);
+                    instrs.extend([
+                        InstructionTacky::Binary {
+                            op,
+                            src1: src.clone(),
+                            src2: halves.clone(),
+                            dst: bits.clone(),
+                        },
+                        InstructionTacky::Copy {
+                            src: bits,
+                            dst: long.clone(),
+                        },
+                        InstructionTacky::IntToDouble {
+                            src: long,
+                            dst: converted.clone(),
+                        },
+                    ]);
+                    converted
+                };
+                let high = half(BinaryOp::Divide, instrs);
+                let low = half(BinaryOp::Remainder, instrs);
+                let scaled = self.get_new_tmpvar(CType::Double);
+                instrs.extend([
+                    InstructionTacky::Binary {
+                        op: BinaryOp::Multiply,
+                        src1: high,
+                        src2: double(4294967296.0),
+                        dst: scaled.clone(),
+                    },
+                    InstructionTacky::Binary {
+                        op: BinaryOp::Add,
+                        src1: scaled,
+                        src2: low,
+                        dst,
+                    },
+                ]);
+            }
+            (CType::Double, CType::ULong) => {
+                let large_label = self.get_new_label("to_ulong_large");
+                let end_label = self.get_new_label("to_ulong_end");
+                let large = self.get_new_tmpvar(CType::Int);
+                let (long, reduced) = (
+                    self.get_new_tmpvar(CType::Long),
+                    self.get_new_tmpvar(CType::Double),
+                );
+                instrs.extend([
+                    InstructionTacky::Binary {
+                        op: BinaryOp::GreaterOrEqual,
+                        src1: src.clone(),
+                        src2: double(two_to_the_63),
+                        dst: large.clone(),
+                    },
+                    InstructionTacky::JumpIfNotZero {
+                        condition: large,
+                        target: large_label.clone(),
+                    },
+                    InstructionTacky::DoubleToInt {
+                        src: src.clone(),
+                        dst: long.clone(),
+                    },
+                    InstructionTacky::Copy {
+                        src: long.clone(),
+                        dst: dst.clone(),
+                    },
+                    InstructionTacky::Jump {
+                        target: end_label.clone(),
+                    },
+                    InstructionTacky::Label { name: large_label },
+                    InstructionTacky::Binary {
+                        op: BinaryOp::Subtract,
+                        src1: src,
+                        src2: double(two_to_the_63),
+                        dst: reduced.clone(),
+                    },
+                    InstructionTacky::DoubleToInt {
+                        src: reduced,
+                        dst: long.clone(),
+                    },
+                    InstructionTacky::Copy {
+                        src: long,
+                        dst: dst.clone(),
+                    },
+                    InstructionTacky::Binary {
+                        op: BinaryOp::Add,
+                        src1: dst.clone(),
+                        src2: ValTacky::Const {
+                            c: Const::ULong { ulong: 1 << 63 },
+                        },
+                        dst,
+                    },
+                    InstructionTacky::Label { name: end_label },
+                ]);
+            }
+            _ => instrs.push(match target.size().cmp(&from.size()) {
+                Ordering::Equal => InstructionTacky::Copy { src, dst },
+                Ordering::Less => InstructionTacky::Truncate { src, dst },
+                Ordering::Greater if from.is_signed() => InstructionTacky::SignExtend { src, dst },
+                Ordering::Greater => InstructionTacky::ZeroExtend { src, dst },
+            }),
+        }
+    }
+
+    /// lowers `c ? a : b` to a branch on `c` that evaluates only one of `a` and `b`,
+    /// each arm copying its value into the result before they join.
+    fn translate_conditional(
+        &mut self,
+        condition: Exp,
+        then_exp: Exp,
+        else_exp: Exp,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        let else_label = self.get_new_label("cond_else");
+        let end_label = self.get_new_label("cond_end");
+
+        let condition = self.translate_expression(condition, instrs);
+        instrs.push(InstructionTacky::JumpIfZero {
+            condition,
+            target: else_label.clone(),
+        });
+        let src1 = self.translate_expression(then_exp, instrs);
+        let dst = self.get_new_tmpvar(src1.ty());
+        instrs.extend([
+            InstructionTacky::Copy {
+                src: src1,
+                dst: dst.clone(),
+            },
+            InstructionTacky::Jump {
+                target: end_label.clone(),
+            },
+            InstructionTacky::Label { name: else_label },
+        ]);
+        let src2 = self.translate_expression(else_exp, instrs);
+        instrs.extend([
+            InstructionTacky::Copy {
+                src: src2,
+                dst: dst.clone(),
+            },
+            InstructionTacky::Label { name: end_label },
+        ]);
+        dst
+    }
+
+    /// lowers `&&` and `||` so the right operand is only evaluated when the left
+    /// one doesn't already decide the result: `&&` jumps to where it is 0 as soon
+    /// as either operand is zero, and `||` to where it is 1 as soon as either isn't.
+    fn translate_logical(
+        &mut self,
+        op: BinaryOp,
+        l_exp: Exp,
+        r_exp: Exp,
+        instrs: &mut Vec<InstructionTacky>,
+    ) -> ValTacky {
+        let (decided_prefix, end_prefix, decided) = match op {
+            BinaryOp::And => ("and_false", "and_end", 0),
+            _ => ("or_true", "or_end", 1),
+        };
+        let decided_label = self.get_new_label(decided_prefix);
+        let end_label = self.get_new_label(end_prefix);
+        let jump = |condition, target: &String| match op {
+            BinaryOp::And => InstructionTacky::JumpIfZero {
+                condition,
+                target: target.clone(),
+            },
+            _ => InstructionTacky::JumpIfNotZero {
+                condition,
+                target: target.clone(),
+            },
+        };
+
+        let src1 = self.translate_expression(l_exp, instrs);
+        instrs.push(jump(src1, &decided_label));
+        let src2 = self.translate_expression(r_exp, instrs);
+        instrs.push(jump(src2, &decided_label));
+        let dst = self.get_new_tmpvar(CType::Int);
+        instrs.extend([
+            InstructionTacky::Copy {
+                src: ValTacky::int(1 - decided),
+                dst: dst.clone(),
+            },
+            InstructionTacky::Jump {
+                target: end_label.clone(),
+            },
+            InstructionTacky::Label {
+                name: decided_label,
+            },
+            InstructionTacky::Copy {
+                src: ValTacky::int(decided),
+                dst: dst.clone(),
+            },
+            InstructionTacky::Label { name: end_label },
+        ]);
+        dst
+    }
+
+    /// a fresh temporary of type `ty`.
+    fn get_new_tmpvar(&mut self, ty: CType) -> ValTacky {
+        loop {
+            self.tmp_no += 1;
+            let name = Symbol::numbered("tmp", self.tmp_no - 1);
+            if !self.variables.contains_key(&name) {
+                return ValTacky::TmpVar { name, ty };
+            }
+        }
+    }
+
+    /// the name of a fresh constant of the `char` array type `ty`, holding the
+    /// bytes `val` padded with nulls. The leading `.` keeps it apart from any
+    /// variable, and marks it local to the file.
+    fn string_constant(&mut self, val: Vec<u8>, ty: CType) -> Symbol {
+        self.string_no += 1;
+        let name = Symbol::numbered(".string", self.string_no - 1);
+        self.strings.push((name, padded_string(val, ty.size())));
+        name
+    }
+
+    /// records a declaration of a variable with static storage in `statics`,
+    /// and its name in `order` the first time it is declared, which decides
+    /// whether it is visible outside the file. Any declaration but an `extern`
+    /// one defines it. A string literal initializes a `char` array to its
+    /// bytes, and a pointer to the address of a constant holding them.
+    fn declare_static(
+        &mut self,
+        decl: &Declaration,
+        order: &mut Vec<Symbol>,
+        statics: &mut HashMap<Symbol, StaticVariable>,
+    ) {
+        let init = decl.init.as_ref().map(|init| match &**init {
+            Exp::Const { c, .. } => StaticInit::Scalar { c: *c },
+            Exp::String { val, .. } if decl.ty.is_array() => {
+                padded_string(val.clone(), decl.ty.size())
+            }
+            Exp::String { val, .. } => {
+                let ty = CType::Char.array_of(val.len() as u32 + 1);
+                StaticInit::Address {
+                    name: self.string_constant(val.clone(), ty),
+                    ty,
+                }
+            }
+            _ => unreachable!("static initializers are folded by semantic analysis"),
+        });
+        let var = statics.entry(decl.name).or_insert_with(|| {
+            order.push(decl.name);
+            StaticVariable {
+                ty: decl.ty,
+                global: decl.storage != Some(StorageClass::Static),
+                init: None,
+                tentative: false,
+            }
+        });
+        var.init = var.init.take().or(init);
+        var.tentative |= decl.storage != Some(StorageClass::Extern);
+    }
+
+    /// the program's variable `name`, of the type it was declared with.
+    fn variable(&self, name: Symbol) -> ValTacky {
         ValTacky::TmpVar {
-            no: self.tmp_no - 1,
+            name,
+            ty: self.variables[&name],
+        }
+    }
+
+    /// a fresh label `<prefix>.<no>`.
+    fn get_new_label(&mut self, prefix: &str) -> String {
+        self.label_no += 1;
+        format!("{}.{}", prefix, self.label_no - 1)
+    }
+}
+
+/// the label just past the loop or `switch` named `label`.
+fn break_label(label: Symbol) -> String {
+    format!("break_{}", label)
+}
+
+/// the label of the loop named `label` that its next pass goes through,
+/// before its post expression.
+fn continue_label(label: Symbol) -> String {
+    format!("continue_{}", label)
+}
+
+/// adds the declarations in `block`, at any depth, to `decls`, in source order.
+fn declared_in<'a>(block: &'a Block, decls: &mut Vec<&'a Declaration>) {
+    for item in block.items.iter() {
+        match item {
+            BlockItem::Declaration { decl } => decls.push(decl),
+            BlockItem::Statement { stmt } => declared_in_statement(stmt, decls),
+            BlockItem::Struct { .. } => {}
         }
     }
 }
 
+fn declared_in_statement<'a>(stmt: &'a StatementC, decls: &mut Vec<&'a Declaration>) {
+    match stmt {
+        StatementC::Compound { block } => declared_in(block, decls),
+        StatementC::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            declared_in_statement(then_stmt, decls);
+            if let Some(else_stmt) = else_stmt {
+                declared_in_statement(else_stmt, decls);
+            }
+        }
+        StatementC::For { init, body, .. } => {
+            if let ForInit::Declaration { decl } = init {
+                decls.push(decl);
+            }
+            declared_in_statement(body, decls);
+        }
+        StatementC::Switch { body, .. }
+        | StatementC::Case { body, .. }
+        | StatementC::Default { body, .. } => declared_in_statement(body, decls),
+        StatementC::Return { .. }
+        | StatementC::Expression { .. }
+        | StatementC::Break { .. }
+        | StatementC::Continue { .. }
+        | StatementC::Null { .. } => {}
+    }
+}
+
+/// the `size` bytes of a `char` array holding `val` and as many nulls after
+/// it as fit; the last of them is the one that terminates the string.
+fn padded_string(mut val: Vec<u8>, size: usize) -> StaticInit {
+    let null_terminated = val.len() < size;
+    val.resize(size - null_terminated as usize, 0);
+    StaticInit::String {
+        val,
+        null_terminated,
+    }
+}
+
+/// whether control can reach the end of `block` without returning: not once
+/// any statement in it can't fall through.
+fn block_may_fall_through(block: &Block) -> bool {
+    block.items.iter().all(|item| match item {
+        BlockItem::Statement { stmt } => may_fall_through(stmt),
+        BlockItem::Declaration { .. } | BlockItem::Struct { .. } => true,
+    })
+}
+
+/// whether control can reach the end of `stmt` without returning. A `break`
+/// or `continue` leaves the statement too, for somewhere its loop decides. A
+/// `switch` may jump past its body, or into the middle of it, so it is always
+/// taken to fall through.
+fn may_fall_through(stmt: &StatementC) -> bool {
+    match stmt {
+        StatementC::Switch { .. } => true,
+        StatementC::Case { body, .. } | StatementC::Default { body, .. } => may_fall_through(body),
+        StatementC::Return { .. } | StatementC::Break { .. } | StatementC::Continue { .. } => false,
+        StatementC::Expression { .. } | StatementC::Null { .. } => true,
+        StatementC::Compound { block } => block_may_fall_through(block),
+        StatementC::For {
+            condition,
+            body,
+            label,
+            ..
+        } => condition.is_some() || label.is_some_and(|label| breaks_out(body, label)),
+        StatementC::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => match else_stmt {
+            Some(else_stmt) => may_fall_through(then_stmt) || may_fall_through(else_stmt),
+            None => true,
+        },
+    }
+}
+
+/// whether `stmt` holds a `break` out of the loop named `label`.
+fn breaks_out(stmt: &StatementC, label: Symbol) -> bool {
+    match stmt {
+        StatementC::Break { label: target, .. } => *target == Some(label),
+        StatementC::Compound { block } => block.items.iter().any(|item| match item {
+            BlockItem::Statement { stmt } => breaks_out(stmt, label),
+            BlockItem::Declaration { .. } | BlockItem::Struct { .. } => false,
+        }),
+        StatementC::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            breaks_out(then_stmt, label)
+                || else_stmt
+                    .as_ref()
+                    .is_some_and(|else_stmt| breaks_out(else_stmt, label))
+        }
+        StatementC::For { body, .. }
+        | StatementC::Switch { body, .. }
+        | StatementC::Case { body, .. }
+        | StatementC::Default { body, .. } => breaks_out(body, label),
+        StatementC::Return { .. }
+        | StatementC::Expression { .. }
+        | StatementC::Continue { .. }
+        | StatementC::Null { .. } => false,
+    }
+}
+
 /// ## TESTS THE FOLLOWING TRANSLATION
 /// ### C (AST input):
 /// ```c
@@ -133,12 +1948,16 @@ impl TackyEmitter {
 #[test]
 fn translate_return() {
     let return_three = StatementC::Return {
-        exp: Box::new(Exp::Const { c: 3 }),
+        span: Span::default(),
+        exp: Box::new(Exp::Const {
+            c: Const::Int { int: 3 },
+            span: Span::default(),
+        }),
     };
     assert_eq!(
         TackyEmitter::new().translate_statement(return_three),
         vec![InstructionTacky::Ret {
-            v: ValTacky::Const { int: 3 }
+            v: ValTacky::int(3)
         }]
     );
 }
@@ -156,9 +1975,14 @@ fn translate_return() {
 #[test]
 fn translate_return_complement() {
     let return_comp_two = StatementC::Return {
+        span: Span::default(),
         exp: Box::new(Exp::Unary {
+            span: Span::default(),
             op: UnaryOp::BitwiseComplement,
-            exp: Box::new(Exp::Const { c: 2 }),
+            exp: Box::new(Exp::Const {
+                c: Const::Int { int: 2 },
+                span: Span::default(),
+            }),
         }),
     };
     assert_eq!(
@@ -166,11 +1990,11 @@ fn translate_return_complement() {
         vec![
             InstructionTacky::Unary {
                 op: UnaryOp::BitwiseComplement,
-                src: ValTacky::Const { int: 2 },
-                dst: ValTacky::TmpVar { no: 0 }
+                src: ValTacky::int(2),
+                dst: ValTacky::tmp(0)
             },
             InstructionTacky::Ret {
-                v: ValTacky::TmpVar { no: 0 }
+                v: ValTacky::tmp(0)
             }
         ]
     );
@@ -191,13 +2015,20 @@ fn translate_return_complement() {
 #[test]
 fn translate_threefold_unary() {
     let return_negcompneg_eight = StatementC::Return {
+        span: Span::default(),
         exp: Box::new(Exp::Unary {
+            span: Span::default(),
             op: UnaryOp::Negate,
             exp: Box::new(Exp::Unary {
+                span: Span::default(),
                 op: UnaryOp::BitwiseComplement,
                 exp: Box::new(Exp::Unary {
+                    span: Span::default(),
                     op: UnaryOp::Negate,
-                    exp: Box::new(Exp::Const { c: 8 }),
+                    exp: Box::new(Exp::Const {
+                        c: Const::Int { int: 8 },
+                        span: Span::default(),
+                    }),
                 }),
             }),
         }),
@@ -207,21 +2038,21 @@ fn translate_threefold_unary() {
         vec![
             InstructionTacky::Unary {
                 op: UnaryOp::Negate,
-                src: ValTacky::Const { int: 8 },
-                dst: ValTacky::TmpVar { no: 0 }
+                src: ValTacky::int(8),
+                dst: ValTacky::tmp(0)
             },
             InstructionTacky::Unary {
                 op: UnaryOp::BitwiseComplement,
-                src: ValTacky::TmpVar { no: 0 },
-                dst: ValTacky::TmpVar { no: 1 }
+                src: ValTacky::tmp(0),
+                dst: ValTacky::tmp(1)
             },
             InstructionTacky::Unary {
                 op: UnaryOp::Negate,
-                src: ValTacky::TmpVar { no: 1 },
-                dst: ValTacky::TmpVar { no: 2 }
+                src: ValTacky::tmp(1),
+                dst: ValTacky::tmp(2)
             },
             InstructionTacky::Ret {
-                v: ValTacky::TmpVar { no: 2 }
+                v: ValTacky::tmp(2)
             }
         ]
     );
@@ -240,10 +2071,18 @@ fn translate_threefold_unary() {
 #[test]
 fn translate_one_plus_one() {
     let ret_statement = StatementC::Return {
+        span: Span::default(),
         exp: Box::new(Exp::Binary {
+            span: Span::default(),
             op: BinaryOp::Add,
-            l_exp: Box::new(Exp::Const { c: 1 }),
-            r_exp: Box::new(Exp::Const { c: 2 }),
+            l_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 1 },
+                span: Span::default(),
+            }),
+            r_exp: Box::new(Exp::Const {
+                c: Const::Int { int: 2 },
+                span: Span::default(),
+            }),
         }),
     };
     assert_eq!(
@@ -251,13 +2090,488 @@ fn translate_one_plus_one() {
         vec![
             InstructionTacky::Binary {
                 op: BinaryOp::Add,
-                src1: ValTacky::Const { int: 1 },
-                src2: ValTacky::Const { int: 2 },
-                dst: ValTacky::TmpVar { no: 0 }
+                src1: ValTacky::int(1),
+                src2: ValTacky::int(2),
+                dst: ValTacky::tmp(0)
             },
             InstructionTacky::Ret {
-                v: ValTacky::TmpVar { no: 0 }
+                v: ValTacky::tmp(0)
             }
         ]
     );
 }
+
+#[cfg(test)]
+use super::{
+    lexer::{tokenize, Span},
+    semantic::analyze,
+};
+
+#[cfg(test)]
+fn print_source(source: &str) -> String {
+    let analyzed = analyze(parse(tokenize(String::from(source)).unwrap()).unwrap()).unwrap();
+    TackyEmitter::gen_tacky(analyzed).to_string()
+}
+
+#[test]
+fn print_return_constant() {
+    assert_eq!(
+        print_source("int main(void) { return 2; }"),
+        "function main:\n    ret 2\n"
+    );
+}
+
+#[test]
+fn print_nested_operations() {
+    assert_eq!(
+        print_source("int main(void) { return -(~3 * 5) % 2; }"),
+        "function main:
+    tmp.0 = ~3
+    tmp.1 = tmp.0 * 5
+    tmp.2 = -tmp.1
+    tmp.3 = tmp.2 % 2
+    ret tmp.3
+"
+    );
+}
+
+/// temporaries are numbered across the whole program, not per function.
+#[test]
+fn print_every_function() {
+    assert_eq!(
+        print_source("int one(void) { return 1 | 6; } int main(void) { return 4 ^ 2 & 7; }"),
+        "function one:
+    tmp.0 = 1 | 6
+    ret tmp.0
+
+function main:
+    tmp.1 = 2 & 7
+    tmp.2 = 4 ^ tmp.1
+    ret tmp.2
+"
+    );
+}
+
+#[test]
+fn print_control_flow() {
+    let fundef = FunDefTacky {
+        identifier: Symbol::intern("main"),
+        global: true,
+        params: vec![],
+        instructions: vec![
+            InstructionTacky::Copy {
+                src: ValTacky::int(1),
+                dst: ValTacky::tmp(0),
+            },
+            InstructionTacky::JumpIfZero {
+                condition: ValTacky::tmp(0),
+                target: String::from("else"),
+            },
+            InstructionTacky::JumpIfNotZero {
+                condition: ValTacky::tmp(0),
+                target: String::from("end"),
+            },
+            InstructionTacky::Jump {
+                target: String::from("end"),
+            },
+            InstructionTacky::Label {
+                name: String::from("else"),
+            },
+            InstructionTacky::Label {
+                name: String::from("end"),
+            },
+            InstructionTacky::Ret {
+                v: ValTacky::tmp(0),
+            },
+        ],
+    };
+    assert_eq!(
+        fundef.to_string(),
+        "function main:
+    tmp.0 = 1
+    jump_if_zero tmp.0, else
+    jump_if_not_zero tmp.0, end
+    jump end
+  else:
+  end:
+    ret tmp.0
+"
+    );
+}
+
+/// the right operand of `&&` and `||` is skipped once the left one decides the
+/// result, and every label is new, even across functions.
+#[test]
+fn short_circuit_logical_operators() {
+    assert_eq!(
+        print_source("int one(void) { return 1 && 2; } int main(void) { return -1 || 2 / 0; }"),
+        "function one:
+    jump_if_zero 1, and_false.0
+    jump_if_zero 2, and_false.0
+    tmp.0 = 1
+    jump and_end.1
+  and_false.0:
+    tmp.0 = 0
+  and_end.1:
+    ret tmp.0
+
+function main:
+    tmp.1 = -1
+    jump_if_not_zero tmp.1, or_true.2
+    tmp.2 = 2 / 0
+    jump_if_not_zero tmp.2, or_true.2
+    tmp.3 = 0
+    jump or_end.3
+  or_true.2:
+    tmp.3 = 1
+  or_end.3:
+    ret tmp.3
+"
+    );
+}
+
+/// a false condition jumps past the branch taken when it is true, and a body
+/// that can fall off its end returns 0.
+#[test]
+fn if_else_branches() {
+    assert_eq!(
+        print_source(
+            "int one(void) { if (1 < 2) return 3; else return 4; } \
+             int main(void) { if (0) return 5; }"
+        ),
+        "function one:
+    tmp.0 = 1 < 2
+    jump_if_zero tmp.0, else.1
+    ret 3
+    jump if_end.0
+  else.1:
+    ret 4
+  if_end.0:
+
+function main:
+    jump_if_zero 0, if_end.2
+    ret 5
+  if_end.2:
+    ret 0
+"
+    );
+}
+
+/// only the arm the condition picks is evaluated, and both copy into the result.
+#[test]
+fn conditional_branches_and_joins() {
+    assert_eq!(
+        print_source("int main(void) { return 1 < 2 ? 3 : -4; }"),
+        "function main:
+    tmp.0 = 1 < 2
+    jump_if_zero tmp.0, cond_else.0
+    tmp.1 = 3
+    jump cond_end.1
+  cond_else.0:
+    tmp.2 = -4
+    tmp.1 = tmp.2
+  cond_end.1:
+    ret tmp.1
+"
+    );
+}
+
+/// variables are written by copies, and an assignment's value is the variable.
+#[test]
+fn variables_and_assignment() {
+    assert_eq!(
+        print_source(
+            "int main(void) { int a = 1 + 2; int b; { int a = 4; b = a = a * 5; } return a - b; }"
+        ),
+        "function main:
+    tmp.0 = 1 + 2
+    a.0 = tmp.0
+    a.2 = 4
+    tmp.1 = a.2 * 5
+    a.2 = tmp.1
+    b.1 = a.2
+    tmp.2 = a.0 - b.1
+    ret tmp.2
+"
+    );
+}
+
+/// a compound assignment finds its object once, reads it and writes the result
+/// back, converted from and to the type the operation is carried out in.
+#[test]
+fn compound_assignment_evaluates_lvalue_once() {
+    assert_eq!(
+        print_source(
+            "int main(void) { char c = 1; int a[2]; int i; c += 300; a[i = 1] *= c; return c; }"
+        ),
+        "function main:
+    c.0 = 1
+    tmp.0 = sign_extend c.0
+    tmp.1 = tmp.0 + 300
+    tmp.2 = truncate tmp.1
+    c.0 = tmp.2
+    tmp.3 = &a.1
+    i.2 = 1
+    tmp.4 = sign_extend i.2
+    tmp.5 = add_ptr tmp.3, tmp.4, 4
+    tmp.6 = *tmp.5
+    tmp.7 = sign_extend c.0
+    tmp.8 = tmp.6 * tmp.7
+    *tmp.5 = tmp.8
+    tmp.9 = sign_extend c.0
+    ret tmp.9
+"
+    );
+}
+
+/// `x++` copies out the value `x` had before adding to it, which `++x` doesn't
+/// need; through a pointer the value loaded is already a copy.
+#[test]
+fn postfix_increment_copies_old_value() {
+    assert_eq!(
+        print_source("int main(void) { int x = 1; int *p = &x; x++; ++x; (*p)--; return *p++; }"),
+        "function main:
+    x.0 = 1
+    tmp.0 = &x.0
+    p.1 = tmp.0
+    tmp.1 = x.0
+    tmp.2 = x.0 + 1
+    x.0 = tmp.2
+    tmp.3 = x.0 + 1
+    x.0 = tmp.3
+    tmp.4 = *p.1
+    tmp.5 = tmp.4 - 1
+    *p.1 = tmp.5
+    tmp.6 = p.1
+    tmp.7 = add_ptr p.1, 1L, 4
+    p.1 = tmp.7
+    tmp.8 = *tmp.6
+    ret tmp.8
+"
+    );
+}
+
+/// a variable named `tmp` resolves to a name a temporary would have, which
+/// the temporaries skip.
+#[test]
+fn temporaries_skip_variable_names() {
+    assert_eq!(
+        print_source("int main(void) { int tmp = -1; return ~tmp * 2; }"),
+        "function main:
+    tmp.1 = -1
+    tmp.0 = tmp.1
+    tmp.2 = ~tmp.0
+    tmp.3 = tmp.2 * 2
+    ret tmp.3
+"
+    );
+}
+
+/// a `for` tests its condition at the top of each pass and runs its post
+/// expression at the label `continue` will jump to; without a condition it
+/// loops until something in its body leaves.
+#[test]
+fn for_loop_labels() {
+    assert_eq!(
+        print_source(
+            "int main(void) { int s = 0; for (int i = 0; i < 3; i = i + 1) s = s + i; \
+             for (;;) return s; }"
+        ),
+        "function main:
+    s.0 = 0
+    i.1 = 0
+  loop.0:
+    tmp.0 = i.1 < 3
+    jump_if_zero tmp.0, break_loop.0
+    tmp.1 = s.0 + i.1
+    s.0 = tmp.1
+  continue_loop.0:
+    tmp.2 = i.1 + 1
+    i.1 = tmp.2
+    jump loop.0
+  break_loop.0:
+  loop.1:
+    ret s.0
+  continue_loop.1:
+    jump loop.1
+  break_loop.1:
+"
+    );
+}
+
+/// `break` and `continue` jump to the labels of the innermost loop, and a loop
+/// without a condition that can `break` falls through to the rest of the body.
+#[test]
+fn break_and_continue_jumps() {
+    assert_eq!(
+        print_source("int main(void) { for (;;) { for (;;) continue; if (1) break; } }"),
+        "function main:
+  loop.0:
+  loop.1:
+    jump continue_loop.1
+  continue_loop.1:
+    jump loop.1
+  break_loop.1:
+    jump_if_zero 1, if_end.0
+    jump break_loop.0
+  if_end.0:
+  continue_loop.0:
+    jump loop.0
+  break_loop.0:
+    ret 0
+"
+    );
+}
+
+/// a `switch` compares its control value with each case, then jumps to the
+/// default, or past the body without one; its cases fall through into each
+/// other.
+#[test]
+fn switch_compares_then_jumps() {
+    assert_eq!(
+        print_source(
+            "int main(void) { int x = 2; switch (x) { case 1: x = 5; default: return x; case 2: break; } \
+             switch (x + 1) case 3: x = 0; return x; }"
+        ),
+        "function main:
+    x.0 = 2
+    tmp.0 = x.0 == 1
+    jump_if_not_zero tmp.0, switch.0.case.0
+    tmp.1 = x.0 == 2
+    jump_if_not_zero tmp.1, switch.0.case.2
+    jump switch.0.default
+  switch.0.case.0:
+    x.0 = 5
+  switch.0.default:
+    ret x.0
+  switch.0.case.2:
+    jump break_switch.0
+  break_switch.0:
+    tmp.2 = x.0 + 1
+    tmp.3 = tmp.2 == 3
+    jump_if_not_zero tmp.3, switch.1.case.0
+    jump break_switch.1
+  switch.1.case.0:
+    x.0 = 0
+  break_switch.1:
+    ret x.0
+"
+    );
+}
+
+/// arguments are evaluated left to right before the call, whose result is a
+/// temporary like any other operation's.
+#[test]
+fn calls_pass_evaluated_arguments() {
+    assert_eq!(
+        print_source(
+            "int add(int a, int b) { return a + b; } int main(void) { return add(1, add(2, 3) * 4); }"
+        ),
+        "function add(a.0, b.1):
+    tmp.0 = a.0 + b.1
+    ret tmp.0
+
+function main:
+    tmp.1 = call add(2, 3)
+    tmp.2 = tmp.1 * 4
+    tmp.3 = call add(1, tmp.2)
+    ret tmp.3
+"
+    );
+}
+
+/// variables with static storage come after the functions, in the order they
+/// were first declared; a tentative definition is zeroed, and a name only ever
+/// declared `extern` is left for another file to define. A local `static` is
+/// initialized there too, not where it is declared.
+#[test]
+fn static_variables_follow_functions() {
+    assert_eq!(
+        print_source(
+            "int a; extern int b; static int c = 4; int a = 2; int d; \
+             static int f(void) { static int s = 1; s = s + b; return s; } \
+             int main(void) { extern int e; return f() + a + e; }"
+        ),
+        "static function f:
+    tmp.0 = s.0 + b
+    s.0 = tmp.0
+    ret s.0
+
+function main:
+    tmp.1 = call f()
+    tmp.2 = tmp.1 + a
+    tmp.3 = tmp.2 + e
+    ret tmp.3
+
+global a = 2
+extern b
+static c = 4
+global d = 0
+static s.0 = 1
+extern e
+"
+    );
+}
+
+/// each cast converts from the type of what it casts: `(long)(unsigned)x`
+/// zero-extends, having made `x` unsigned first, and `(char)` truncates, the
+/// `char` sign-extended back to the `int` returned.
+#[test]
+fn casts_extend_by_the_signedness_of_their_operand() {
+    assert_eq!(
+        print_source("int main(void) { int x = -1; long y = (long)(unsigned)x; return (char)y; }"),
+        "function main:
+    tmp.0 = -1
+    x.0 = tmp.0
+    tmp.1 = x.0
+    tmp.2 = zero_extend tmp.1
+    y.1 = tmp.2
+    tmp.3 = truncate y.1
+    tmp.4 = sign_extend tmp.3
+    ret tmp.4
+"
+    );
+}
+
+/// a conversion to `_Bool` compares against zero of the type converted from,
+/// whatever that is, so every nonzero value is 1.
+#[test]
+fn bools_compare_against_zero() {
+    assert_eq!(
+        print_source("_Bool f(long l, double d, int *p) { _Bool a = l; _Bool b = d; return p; }"),
+        "function f(l.0, d.1, p.2):
+    tmp.0 = l.0 != 0L
+    a.3 = tmp.0
+    tmp.1 = d.1 != 0.0
+    b.4 = tmp.1
+    tmp.2 = p.2 != 0UL
+    ret tmp.2
+"
+    );
+}
+
+/// with the source's lines, the function starts at its declaration, each
+/// statement and initialized declaration at its own line and column, and the
+/// return at the end of a body that can run off it at the closing brace.
+/// Files are numbered as linemarkers name them, and come first.
+#[test]
+fn statements_mark_their_lines() {
+    let source = "# 1 \"f.c\"\nint main(void) {\n  int a = 1;\n  int b;\n  ;\n  a = a + 1;\n}\n";
+    let analyzed = analyze(parse(tokenize(String::from(source)).unwrap()).unwrap()).unwrap();
+    let lines = LineIndex::new(source, "<stdin>");
+    assert_eq!(
+        TackyEmitter::gen_tacky_with_lines(analyzed, &lines).to_string(),
+        "file 1 \"f.c\"
+
+function main:
+    loc 1 1:1
+    loc 1 2:3
+    a.0 = 1
+    loc 1 5:3
+    tmp.0 = a.0 + 1
+    a.0 = tmp.0
+    loc 1 6:1
+    ret 0
+"
+    );
+}
@@ -0,0 +1,317 @@
+//! Platforms the emitted assembly can be built for.
+//! The instructions are the same x86-64 code everywhere; targets differ in how
+//! symbols and labels are spelled, which sections and directives the
+//! platform's assembler expects, and how the calling convention passes
+//! arguments and which registers it asks a function to preserve.
+use clap::ValueEnum;
+
+use super::asmgen::Register;
+
+/// Object format and ABI the output is meant for.
+/// - `Linux`: ELF, as accepted by GNU as
+/// - `MacOs`: Mach-O, as accepted by clang's integrated assembler
+/// - `Windows`: COFF with the Microsoft x64 calling convention, as accepted by
+///   llvm-mc and the mingw GNU as
+#[derive(PartialEq, Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Target {
+    #[default]
+    Linux,
+    #[value(name = "macos")]
+    MacOs,
+    Windows,
+}
+
+/// Kind of module the output is linked into, which decides how it reaches symbols.
+/// - `Static`: an executable loaded where it was linked to run; calls name
+///   their functions directly
+/// - `Pie`: a position-independent executable, what most Linux distributions
+///   build by default; calls go through the PLT, and variables are addressed
+///   relative to %rip
+/// - `Pic`: a shared library; as `Pie`, except that a variable visible outside
+///   the file is reached through the GOT, since the executable or another
+///   library may define it in this one's place
+///
+/// Only ELF output tells them apart.
+#[derive(PartialEq, Debug, Clone, Copy, Default, ValueEnum)]
+pub enum RelocationModel {
+    Static,
+    #[default]
+    Pie,
+    Pic,
+}
+
+/// Registers the System V ABI requires a function to preserve for its caller,
+/// among those the code generator uses.
+const SYSV_CALLEE_SAVED: [Register; 5] = [
+    Register::BX,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+/// Registers the Microsoft x64 convention requires a function to preserve,
+/// among those the code generator uses. It also preserves RSI and RDI, which
+/// the System V convention treats as volatile.
+const WINDOWS_CALLEE_SAVED: [Register; 7] = [
+    Register::BX,
+    Register::DI,
+    Register::SI,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+/// Registers the System V ABI passes the first integer arguments in, in order.
+const SYSV_PARAM_REGISTERS: [Register; 6] = [
+    Register::DI,
+    Register::SI,
+    Register::DX,
+    Register::CX,
+    Register::R8,
+    Register::R9,
+];
+
+/// Registers the Microsoft x64 convention passes the first arguments in, in order.
+const WINDOWS_PARAM_REGISTERS: [Register; 4] =
+    [Register::CX, Register::DX, Register::R8, Register::R9];
+
+/// Registers the System V ABI passes the first `double` arguments in, in order.
+const SYSV_DOUBLE_PARAM_REGISTERS: [Register; 8] = [
+    Register::XMM0,
+    Register::XMM1,
+    Register::XMM2,
+    Register::XMM3,
+    Register::XMM4,
+    Register::XMM5,
+    Register::XMM6,
+    Register::XMM7,
+];
+
+/// Registers the Microsoft x64 convention passes `double` arguments in, by position.
+const WINDOWS_DOUBLE_PARAM_REGISTERS: [Register; 4] = [
+    Register::XMM0,
+    Register::XMM1,
+    Register::XMM2,
+    Register::XMM3,
+];
+
+impl Target {
+    /// the platform the compiler itself is running on.
+    pub fn host() -> Self {
+        if cfg!(target_os = "macos") {
+            Target::MacOs
+        } else if cfg!(target_os = "windows") {
+            Target::Windows
+        } else {
+            Target::Linux
+        }
+    }
+
+    /// the target's name, as the command line spells it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Linux => "linux",
+            Self::MacOs => "macos",
+            Self::Windows => "windows",
+        }
+    }
+
+    /// name of a symbol as the assembler sees it.
+    /// Mach-O prefixes every C symbol with an underscore. The constants the
+    /// compiler makes up, whose names start with a `.`, are spelled as local
+    /// labels instead, which keeps them out of the object's symbol table.
+    pub fn symbol(&self, name: &str) -> String {
+        if let Some(constant) = name.strip_prefix('.') {
+            return self.local_label(constant);
+        }
+        match self {
+            Self::Linux | Self::Windows => String::from(name),
+            Self::MacOs => format!("_{}", name),
+        }
+    }
+
+    /// name of a function-local label, spelled so it stays out of the symbol table.
+    pub fn local_label(&self, name: &str) -> String {
+        match self {
+            Self::Linux | Self::Windows => format!(".L{}", name),
+            Self::MacOs => format!("L{}", name),
+        }
+    }
+
+    /// whether to mark the stack non-executable with a `.note.GNU-stack` section,
+    /// which only ELF assemblers understand.
+    pub fn has_gnu_stack_note(&self) -> bool {
+        match self {
+            Self::Linux => true,
+            Self::MacOs | Self::Windows => false,
+        }
+    }
+
+    /// whether functions are described with ELF `.type` and `.size` directives.
+    pub fn has_elf_symbol_info(&self) -> bool {
+        *self == Self::Linux
+    }
+
+    /// whether each function needs a COFF `.def` block giving its storage class and type.
+    pub fn has_coff_defs(&self) -> bool {
+        *self == Self::Windows
+    }
+
+    /// whether the assembler builds unwind tables from `.cfi_*` directives.
+    /// COFF describes unwinding with its own `.seh_*` directives instead.
+    pub fn has_cfi(&self) -> bool {
+        match self {
+            Self::Linux | Self::MacOs => true,
+            Self::Windows => false,
+        }
+    }
+
+    /// whether the ABI reserves a red zone below %rsp that leaf functions may use
+    /// without allocating it. Microsoft x64 has none.
+    pub fn has_red_zone(&self) -> bool {
+        match self {
+            Self::Linux | Self::MacOs => true,
+            Self::Windows => false,
+        }
+    }
+
+    /// registers the first arguments of a call are passed in; the rest go on
+    /// the stack, the first of them nearest the return address.
+    pub fn param_registers(&self) -> &'static [Register] {
+        match self {
+            Self::Linux | Self::MacOs => &SYSV_PARAM_REGISTERS,
+            Self::Windows => &WINDOWS_PARAM_REGISTERS,
+        }
+    }
+
+    /// registers the first `double` arguments of a call are passed in.
+    pub fn double_param_registers(&self) -> &'static [Register] {
+        match self {
+            Self::Linux | Self::MacOs => &SYSV_DOUBLE_PARAM_REGISTERS,
+            Self::Windows => &WINDOWS_DOUBLE_PARAM_REGISTERS,
+        }
+    }
+
+    /// the register each argument of a call is passed in, given which of them
+    /// are `double`s, or `None` for those passed on the stack. System V hands
+    /// out the integer and the `double` registers separately, in order; the
+    /// Microsoft convention gives each argument position one of either kind.
+    pub fn arg_registers(&self, doubles: &[bool]) -> Vec<Option<Register>> {
+        let (ints, floats) = (self.param_registers(), self.double_param_registers());
+        let (mut next_int, mut next_float) = (0, 0);
+        doubles
+            .iter()
+            .enumerate()
+            .map(|(i, &double)| match (self, double) {
+                (Self::Windows, true) => floats.get(i).copied(),
+                (Self::Windows, false) => ints.get(i).copied(),
+                (_, true) => {
+                    next_float += 1;
+                    floats.get(next_float - 1).copied()
+                }
+                (_, false) => {
+                    next_int += 1;
+                    ints.get(next_int - 1).copied()
+                }
+            })
+            .collect()
+    }
+
+    /// the directive switching to the section for read-only data, where
+    /// floating-point constants live.
+    pub fn rodata_section(&self) -> &'static str {
+        match self {
+            Self::Linux => ".section .rodata",
+            Self::MacOs => ".section __TEXT,__const",
+            Self::Windows => ".section .rdata,\"dr\"",
+        }
+    }
+
+    /// bytes a caller leaves free just above the return address, for the callee
+    /// to spill its register arguments to. Stack arguments sit above them.
+    pub fn shadow_space(&self) -> i32 {
+        match self {
+            Self::Linux | Self::MacOs => 0,
+            Self::Windows => 32,
+        }
+    }
+
+    /// how a call names the function `name`. On ELF it goes through the PLT,
+    /// so the function may come from a shared library.
+    pub fn call_symbol(&self, name: &str) -> String {
+        match self {
+            Self::Linux => format!("{}@PLT", name),
+            Self::MacOs | Self::Windows => self.symbol(name),
+        }
+    }
+
+    /// whether a variable visible outside the file is read and written through
+    /// its entry in the global offset table, which the dynamic linker fills in
+    /// with the address of whichever module's definition wins.
+    pub fn uses_got(&self, relocation: RelocationModel) -> bool {
+        *self == Self::Linux && relocation == RelocationModel::Pic
+    }
+
+    /// registers a function must restore before returning if it writes to them.
+    pub fn callee_saved(&self) -> &'static [Register] {
+        match self {
+            Self::Linux | Self::MacOs => &SYSV_CALLEE_SAVED,
+            Self::Windows => &WINDOWS_CALLEE_SAVED,
+        }
+    }
+}
+
+/// the command line spelling of each target is its name.
+#[test]
+fn target_names_match_flags() {
+    for target in Target::value_variants() {
+        assert_eq!(
+            target.to_possible_value().unwrap().get_name(),
+            target.name()
+        );
+    }
+}
+
+#[test]
+fn macos_spells_symbols_and_labels() {
+    assert_eq!(Target::Linux.symbol("main"), "main");
+    assert_eq!(Target::MacOs.symbol("main"), "_main");
+    assert_eq!(Target::Linux.local_label("end"), ".Lend");
+    assert_eq!(Target::MacOs.local_label("end"), "Lend");
+    assert_eq!(Target::Linux.symbol(".string.0"), ".Lstring.0");
+    assert_eq!(Target::MacOs.symbol(".string.0"), "Lstring.0");
+    assert_eq!(Target::Windows.symbol(".string.0"), ".Lstring.0");
+}
+
+#[test]
+fn calls_name_functions_per_target() {
+    assert_eq!(Target::Linux.call_symbol("putchar"), "putchar@PLT");
+    assert_eq!(Target::MacOs.call_symbol("putchar"), "_putchar");
+    assert_eq!(Target::Windows.call_symbol("putchar"), "putchar");
+}
+
+#[test]
+fn double_arguments_take_their_own_registers() {
+    let doubles = [true, false, true, false];
+    assert_eq!(
+        Target::Linux.arg_registers(&doubles),
+        vec![
+            Some(Register::XMM0),
+            Some(Register::DI),
+            Some(Register::XMM1),
+            Some(Register::SI)
+        ]
+    );
+    assert_eq!(
+        Target::Windows.arg_registers(&doubles),
+        vec![
+            Some(Register::XMM0),
+            Some(Register::DX),
+            Some(Register::XMM2),
+            Some(Register::R9)
+        ]
+    );
+    assert_eq!(Target::Windows.arg_registers(&[true; 5])[4], None);
+}
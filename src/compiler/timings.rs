@@ -0,0 +1,129 @@
+//! Wall-clock time spent in each phase of compilation, for `--timings`.
+//! The driver and the pass pipeline time what they run, so a new phase or
+//! optimization pass is timed as soon as it runs through them.
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+
+/// How `--timings` reports.
+/// - `Table`: aligned columns of milliseconds
+/// - `Json`: an object with a list of phases, in microseconds
+#[derive(PartialEq, Debug, Clone, Copy, Default, ValueEnum)]
+pub enum TimingsFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+/// Time spent per phase, in the order phases first ran.
+/// A phase that runs more than once, for several files or functions, adds up.
+#[derive(Debug, Default)]
+pub struct Timings {
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    /// runs `f`, adding the time it took to `phase`.
+    pub fn time<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let res = f();
+        self.record(phase, start.elapsed());
+        res
+    }
+
+    pub fn record(&mut self, phase: &'static str, elapsed: Duration) {
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => self.phases.push((phase, elapsed)),
+        }
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, elapsed)| *elapsed).sum()
+    }
+
+    /// the report in `format`, ending in a newline.
+    pub fn report(&self, format: TimingsFormat) -> String {
+        match format {
+            TimingsFormat::Table => self.table(),
+            TimingsFormat::Json => self.json(),
+        }
+    }
+
+    /// one row per phase and a total, in milliseconds to the microsecond.
+    fn table(&self) -> String {
+        let width = self
+            .phases
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(["phase".len()])
+            .max()
+            .unwrap_or_default();
+        let row = |name: &str, elapsed: Duration| {
+            format!(
+                "{:<width$}  {:>10.3}\n",
+                name,
+                elapsed.as_secs_f64() * 1e3,
+                width = width
+            )
+        };
+        let mut table = format!("{:<width$}  {:>10}\n", "phase", "ms", width = width);
+        for (name, elapsed) in self.phases.iter() {
+            table.push_str(&row(name, *elapsed));
+        }
+        table.push_str(&row("total", self.total()));
+        table
+    }
+
+    fn json(&self) -> String {
+        let micros = |elapsed: Duration| format!("{:.3}", elapsed.as_secs_f64() * 1e6);
+        let phases: Vec<String> = self
+            .phases
+            .iter()
+            .map(|(name, elapsed)| {
+                format!(
+                    "{{\"name\":\"{}\",\"microseconds\":{}}}",
+                    name,
+                    micros(*elapsed)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"phases\":[{}],\"total_microseconds\":{}}}\n",
+            phases.join(","),
+            micros(self.total())
+        )
+    }
+}
+
+#[cfg(test)]
+fn sample() -> Timings {
+    let mut timings = Timings::default();
+    timings.record("lex", Duration::from_micros(40));
+    timings.record("constant-folding", Duration::from_nanos(1600));
+    timings.record("lex", Duration::from_micros(2));
+    timings
+}
+
+/// repeated phases add up, and sub-millisecond times keep their microseconds.
+#[test]
+fn table_report() {
+    assert_eq!(
+        sample().report(TimingsFormat::Table),
+        "phase                     ms
+lex                    0.042
+constant-folding       0.002
+total                  0.044
+"
+    );
+}
+
+#[test]
+fn json_report() {
+    assert_eq!(
+        sample().report(TimingsFormat::Json),
+        "{\"phases\":[{\"name\":\"lex\",\"microseconds\":42.000},\
+         {\"name\":\"constant-folding\",\"microseconds\":1.600}],\
+         \"total_microseconds\":43.600}\n"
+    );
+}
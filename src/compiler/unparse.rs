@@ -0,0 +1,405 @@
+//! The C AST printed back out as C source, for `--emit-c`.
+//! Formatting is canonical, four-space indents and a blank line between
+//! functions, and every unary and binary expression is parenthesized, so the
+//! grouping the parser settled on is spelled out. Parsing the output again
+//! gives back the same AST.
+use super::lexer::escape;
+use super::parser::{
+    Block, BlockItem, CType, Declaration, Exp, ForInit, FunDeclC, FunDefC, Param, ProgramC,
+    StatementC, StorageClass, StructDeclaration, TopLevelC,
+};
+use super::symbol::Symbol;
+
+const INDENT: &str = "    ";
+
+/// the whole program, ending in a newline.
+pub fn program(prog: &ProgramC) -> String {
+    let items: Vec<String> = prog
+        .items
+        .iter()
+        .map(|item| match item {
+            TopLevelC::Function { fundef } => function(fundef),
+            TopLevelC::Declaration { decl } => function_declaration(decl),
+            TopLevelC::Variable { decl } => format!("{};\n", declaration(decl)),
+            TopLevelC::Struct { decl } => struct_declaration(decl, 0),
+        })
+        .collect();
+    items.join("\n")
+}
+
+/// the specifiers of a declaration, its storage class first, and the name it
+/// declares, any `*`s making its type a pointer up against it and any array
+/// sizes after it.
+fn declarator(ty: CType, storage: Option<StorageClass>, name: Symbol) -> String {
+    let (mut ty, mut dimensions) = (ty, String::new());
+    while let CType::Array { element, size } = ty {
+        dimensions.push_str(&format!("[{}]", size));
+        ty = element.get();
+    }
+    let ty = ty.to_string();
+    let gap = match ty.ends_with('*') {
+        true => "",
+        false => " ",
+    };
+    match storage {
+        Some(storage) => format!("{} {}{}{}{}", storage, ty, gap, name, dimensions),
+        None => format!("{}{}{}{}", ty, gap, name, dimensions),
+    }
+}
+
+fn function(fundef: &FunDefC) -> String {
+    format!(
+        "{}({}) {{\n{}}}\n",
+        declarator(fundef.ret, fundef.storage, fundef.identifier),
+        params(&fundef.params),
+        block(&fundef.body, 1)
+    )
+}
+
+fn function_declaration(decl: &FunDeclC) -> String {
+    format!(
+        "{}({});\n",
+        declarator(decl.ret, decl.storage, decl.identifier),
+        params(&decl.params)
+    )
+}
+
+fn params(params: &[Param]) -> String {
+    match params.is_empty() {
+        true => String::from("void"),
+        false => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|param| match param.ty {
+                    // a parameter declared as an array of arrays is a pointer
+                    // to its first, and declaring it as an array of one again
+                    // is the only way to write that without parenthesized
+                    // declarators
+                    CType::Pointer { referenced } if referenced.get().is_array() => {
+                        declarator(referenced.get().array_of(1), None, param.name)
+                    }
+                    _ => declarator(param.ty, None, param.name),
+                })
+                .collect();
+            params.join(", ")
+        }
+    }
+}
+
+/// the block's items, each on its own lines at `depth`, without the braces.
+fn block(block: &Block, depth: usize) -> String {
+    let mut res = String::new();
+    for item in block.items.iter() {
+        match item {
+            BlockItem::Statement { stmt } => res.push_str(&statement(stmt, depth)),
+            BlockItem::Declaration { decl } => {
+                res.push_str(&format!("{}{};\n", INDENT.repeat(depth), declaration(decl)));
+            }
+            BlockItem::Struct { decl } => res.push_str(&struct_declaration(decl, depth)),
+        }
+    }
+    res
+}
+
+/// the declaration, without its `;`.
+fn declaration(decl: &Declaration) -> String {
+    let declarator = declarator(decl.ty, decl.storage, decl.name);
+    match &decl.init {
+        Some(init) => format!("{} = {}", declarator, expression(init)),
+        None => declarator,
+    }
+}
+
+/// the structure declaration at `depth`, its members each on a line of
+/// their own, one level in. The tag is spelled as its type names it.
+fn struct_declaration(decl: &StructDeclaration, depth: usize) -> String {
+    let indent = INDENT.repeat(depth);
+    let ty = CType::Struct { tag: decl.tag };
+    if decl.members.is_empty() {
+        return format!("{}{};\n", indent, ty);
+    }
+    let mut res = format!("{}{} {{\n", indent, ty);
+    for member in decl.members.iter() {
+        res.push_str(&format!(
+            "{}{}{};\n",
+            indent,
+            INDENT,
+            declarator(member.ty, None, member.name)
+        ));
+    }
+    res.push_str(&format!("{}}};\n", indent));
+    res
+}
+
+fn statement(stmt: &StatementC, depth: usize) -> String {
+    match stmt {
+        StatementC::Return { exp, .. } => {
+            format!("{}return {};\n", INDENT.repeat(depth), expression(exp))
+        }
+        StatementC::Expression { exp, .. } => {
+            format!("{}{};\n", INDENT.repeat(depth), expression(exp))
+        }
+        // each branch goes on its own lines, one level in
+        StatementC::If {
+            condition,
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            let indent = INDENT.repeat(depth);
+            let mut res = format!("{}if ({})\n", indent, expression(condition));
+            res.push_str(&statement(then_stmt, depth + 1));
+            if let Some(else_stmt) = else_stmt {
+                res.push_str(&format!("{}else\n", indent));
+                res.push_str(&statement(else_stmt, depth + 1));
+            }
+            res
+        }
+        StatementC::Compound { block: inner } => {
+            let indent = INDENT.repeat(depth);
+            format!("{}{{\n{}{}}}\n", indent, block(inner, depth + 1), indent)
+        }
+        // the body goes on its own lines, one level in, and a clause left out
+        // leaves nothing between its semicolons
+        StatementC::For {
+            init,
+            condition,
+            post,
+            body,
+            ..
+        } => {
+            let init = match init {
+                ForInit::Declaration { decl } => declaration(decl),
+                ForInit::Expression { exp } => exp.as_deref().map_or(String::new(), expression),
+            };
+            let clause = |exp: &Option<Box<Exp>>| exp.as_deref().map_or(String::new(), expression);
+            format!(
+                "{}for ({}; {}; {})\n{}",
+                INDENT.repeat(depth),
+                init,
+                clause(condition),
+                clause(post),
+                statement(body, depth + 1)
+            )
+        }
+        // a case's statement goes one level in, like a loop's body
+        StatementC::Switch { control, body, .. } => format!(
+            "{}switch ({})\n{}",
+            INDENT.repeat(depth),
+            expression(control),
+            statement(body, depth + 1)
+        ),
+        StatementC::Case { value, body, .. } => format!(
+            "{}case {}:\n{}",
+            INDENT.repeat(depth),
+            expression(value),
+            statement(body, depth + 1)
+        ),
+        StatementC::Default { body, .. } => format!(
+            "{}default:\n{}",
+            INDENT.repeat(depth),
+            statement(body, depth + 1)
+        ),
+        StatementC::Break { .. } => format!("{}break;\n", INDENT.repeat(depth)),
+        StatementC::Continue { .. } => format!("{}continue;\n", INDENT.repeat(depth)),
+        StatementC::Null { .. } => format!("{};\n", INDENT.repeat(depth)),
+    }
+}
+
+fn expression(exp: &Exp) -> String {
+    match exp {
+        Exp::Const { c, .. } => c.to_string(),
+        Exp::Var { name, .. } => name.to_string(),
+        Exp::Unary { op, exp, .. } => format!("({}{})", op.symbol(), expression(exp)),
+        Exp::Binary {
+            op, l_exp, r_exp, ..
+        } => format!(
+            "({} {} {})",
+            expression(l_exp),
+            op.symbol(),
+            expression(r_exp)
+        ),
+        Exp::Conditional {
+            condition,
+            then_exp,
+            else_exp,
+            ..
+        } => format!(
+            "({} ? {} : {})",
+            expression(condition),
+            expression(then_exp),
+            expression(else_exp)
+        ),
+        Exp::Assignment { lvalue, rvalue, .. } => {
+            format!("({} = {})", expression(lvalue), expression(rvalue))
+        }
+        Exp::CompoundAssignment {
+            op, lvalue, rvalue, ..
+        } => format!(
+            "({} {} {})",
+            expression(lvalue),
+            op.assignment_symbol(),
+            expression(rvalue)
+        ),
+        Exp::FunctionCall { name, args, .. } => {
+            let args: Vec<String> = args.iter().map(expression).collect();
+            format!("{}({})", name, args.join(", "))
+        }
+        Exp::Cast { target, exp, .. } => format!("(({}) {})", target, expression(exp)),
+        Exp::Dereference { exp, .. } => format!("(*{})", expression(exp)),
+        Exp::AddrOf { exp, .. } => format!("(&{})", expression(exp)),
+        Exp::Increment { op, exp, .. } => match op.is_postfix() {
+            true => format!("({}{})", expression(exp), op.symbol()),
+            false => format!("({}{})", op.symbol(), expression(exp)),
+        },
+        Exp::Subscript { exp, index, .. } => {
+            format!("{}[{}]", expression(exp), expression(index))
+        }
+        Exp::String { val, .. } => format!("\"{}\"", escape(val)),
+        Exp::Dot { exp, member, .. } => format!("{}.{}", expression(exp), member),
+        Exp::Arrow { exp, member, .. } => format!("{}->{}", expression(exp), member),
+    }
+}
+
+#[cfg(test)]
+use super::{lexer::tokenize, parser::parse, pretty};
+
+#[cfg(test)]
+fn unparse_source(source: &str) -> String {
+    program(&parse(tokenize(String::from(source)).unwrap()).unwrap())
+}
+
+#[test]
+fn print_fully_parenthesized() {
+    assert_eq!(
+        unparse_source("int main(void){return 1+2*3-~-4;}"),
+        "int main(void) {
+    return ((1 + (2 * 3)) - (~(-4)));
+}
+"
+    );
+}
+
+#[test]
+fn print_every_function() {
+    assert_eq!(
+        unparse_source("int one(void) { return 1; } int main(void) { return (7 % 2) ^ 3; }"),
+        "int one(void) {
+    return 1;
+}
+
+int main(void) {
+    return ((7 % 2) ^ 3);
+}
+"
+    );
+}
+
+#[test]
+fn print_if_else() {
+    assert_eq!(
+        unparse_source("int main(void){if(1<2)return 3;else if(2)return 4;}"),
+        "int main(void) {
+    if ((1 < 2))
+        return 3;
+    else
+        if (2)
+            return 4;
+}
+"
+    );
+}
+
+/// a nested block keeps its braces, on lines of their own.
+#[test]
+fn print_block() {
+    assert_eq!(
+        unparse_source("int main(void){int a=1;{a=a+1;;}return a;}"),
+        "int main(void) {
+    int a = 1;
+    {
+        (a = (a + 1));
+        ;
+    }
+    return a;
+}
+"
+    );
+}
+
+#[test]
+fn print_for() {
+    assert_eq!(
+        unparse_source("int main(void){int i;for(i=0;i<3;)for(;;)break;}"),
+        "int main(void) {
+    int i;
+    for ((i = 0); (i < 3); )
+        for (; ; )
+            break;
+}
+"
+    );
+}
+
+/// a call's arguments are split by commas, as are the parameters they are passed to.
+#[test]
+fn print_calls_and_params() {
+    assert_eq!(
+        unparse_source(
+            "int add(int a,int b){return a+b;}int main(void){return add(1,add(2,3))+f();}"
+        ),
+        "int add(int a, int b) {
+    return (a + b);
+}
+
+int main(void) {
+    return (add(1, add(2, 3)) + f());
+}
+"
+    );
+}
+
+/// a declaration is printed on a line of its own, and set off like a function.
+#[test]
+fn print_function_declarations() {
+    assert_eq!(
+        unparse_source("int f(int a,int b);int g(void);int main(void){return f(1,2);}"),
+        "int f(int a, int b);
+
+int g(void);
+
+int main(void) {
+    return f(1, 2);
+}
+"
+    );
+}
+
+/// printing and parsing again gives the same tree, spans aside, and printing
+/// that gives the same source.
+#[test]
+fn round_trip() {
+    for source in [
+        "int main(void) { return -(-(-2)); }",
+        "int main(void) { return 8 - 4 - -~2; }",
+        "int main(void) { return 1 ^ 2 & 3 | (4 | 5); }",
+        "int f(void) { return (((2))); } int main(void) { return 1 * 2 - 3 * (4 + 5); }",
+        "int main(void) { if (1) if (2) return 3; else return 4; else if (5) return 6; }",
+        "int main(void) { return 1 || 2 ? 3 : 4 ? 5 : (6 ? 7 : 8) + 9; }",
+        "int main(void) { int a; int b = a = 2; { int a = b; ; if (a) { } } return a = b; }",
+        "int main(void) { int a = 0; for (int i = 0; i < 3; i = i + 1) for (a = 1; ; ) for (;;) ; }",
+        "int main(void) { for (;;) { if (1) continue; break; } }",
+        "int main(void) { switch (1 + 2) { case -3: case 0 ? 1 : 2: ; default: { switch (4) ; } } }",
+        "int f(int a, int b, int c) { return f(a = b, c ? 2 : 3, -f(1, 2, 3)); }",
+        "int f(int a); int main(void); int f(int b); int main(void) { return f(1); }",
+        "long f(long a, int long b); static long int x = 2147483648; int main(void) { return f(1l, 2L); }",
+        "int *f(int **pp, long *p); int main(void) { int x = 1; int *p = &x; return *&*p * **&p; }",
+        "struct n; struct n { int v; struct n *next; long a[3]; }; int main(void) { struct s { char c; }; struct n n; struct n *p = &n; return p->next->a[1] + (&n)->v + n.next->v; }",
+    ] {
+        let printed = unparse_source(source);
+        let original = parse(tokenize(String::from(source)).unwrap()).unwrap();
+        let reparsed = parse(tokenize(printed.clone()).unwrap()).unwrap();
+        assert_eq!(pretty::program(&original), pretty::program(&reparsed));
+        assert_eq!(program(&reparsed), printed);
+    }
+}
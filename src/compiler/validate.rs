@@ -0,0 +1,293 @@
+//! Checks on the assembly AST before it is emitted.
+//! Instruction selection leaves pseudo operands and operand combinations x86-64
+//! can't encode, and the later passes are supposed to remove them all. A bug in
+//! those passes is reported here, with the offending instruction, instead of as a
+//! panic somewhere in formatting or encoding.
+use std::fmt::Display;
+use thiserror::Error;
+
+use super::asmgen::{AsmType, FunDefAsm, InstructionAsm, OperandAsm};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AsmValidationError {
+    UnresolvedPseudo {
+        function: String,
+        index: usize,
+        operand: OperandAsm,
+    },
+    MemoryToMemory {
+        function: String,
+        index: usize,
+        instr: InstructionAsm,
+    },
+    ImmediateOperand {
+        function: String,
+        index: usize,
+        instr: InstructionAsm,
+    },
+}
+
+impl Display for AsmValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnresolvedPseudo {
+                function,
+                index,
+                operand,
+            } => write!(
+                f,
+                "(!) Internal compiler error: pseudo operand {:?} left in {} at instruction {}",
+                operand, function, index
+            ),
+            Self::MemoryToMemory {
+                function,
+                index,
+                instr,
+            } => write!(
+                f,
+                "(!) Internal compiler error: two memory operands in {} at instruction {}: {:?}",
+                function, index, instr
+            ),
+            Self::ImmediateOperand {
+                function,
+                index,
+                instr,
+            } => write!(
+                f,
+                "(!) Internal compiler error: immediate operand that can't be encoded in {} at instruction {}: {:?}",
+                function, index, instr
+            ),
+        }
+    }
+}
+
+/// Confirms every instruction of a function can be emitted as is:
+/// no pseudo operands remain, no instruction reads and writes memory through two
+/// operands, `idiv`, `setcc`, the sources of `movzbl`, `movslq`, `cmov` and `cvtsi2sd`, the
+/// destination of `cmp`, the operands of `lea` and of `double` instructions are never immediates, and
+/// only a `movq` into a register has an immediate outside the i32 range.
+pub fn validate_asm(fundef: &FunDefAsm) -> Result<(), AsmValidationError> {
+    let function = fundef.identifier.as_str();
+    for (index, instr) in fundef.instructions.iter().enumerate() {
+        let operands = instr.operands();
+        if let Some(operand) = operands
+            .iter()
+            .find(|o| matches!(o, OperandAsm::Pseudo { .. }))
+        {
+            return Err(AsmValidationError::UnresolvedPseudo {
+                function: function.to_string(),
+                index,
+                operand: **operand,
+            });
+        }
+        if operands.len() == 2 && operands.iter().all(|o| o.is_memory()) {
+            return Err(AsmValidationError::MemoryToMemory {
+                function: function.to_string(),
+                index,
+                instr: instr.clone(),
+            });
+        }
+        let movabs = matches!(
+            instr,
+            InstructionAsm::Mov {
+                ty: AsmType::Quadword,
+                dst: OperandAsm::Reg { .. },
+                ..
+            }
+        );
+        let wide = operands
+            .iter()
+            .any(|o| matches!(o, OperandAsm::Imm { int } if i32::try_from(*int).is_err()));
+        if matches!(
+            instr,
+            InstructionAsm::Idiv {
+                operand: OperandAsm::Imm { .. },
+                ..
+            } | InstructionAsm::Div {
+                operand: OperandAsm::Imm { .. },
+                ..
+            } | InstructionAsm::Cmp {
+                dst: OperandAsm::Imm { .. },
+                ..
+            } | InstructionAsm::SetCC {
+                operand: OperandAsm::Imm { .. },
+                ..
+            } | InstructionAsm::Movzx {
+                src: OperandAsm::Imm { .. },
+                ..
+            } | InstructionAsm::Movsx {
+                src: OperandAsm::Imm { .. },
+                ..
+            } | InstructionAsm::Cmov {
+                src: OperandAsm::Imm { .. },
+                ..
+            } | InstructionAsm::Cvtsi2sd {
+                src: OperandAsm::Imm { .. },
+                ..
+            } | InstructionAsm::Cvttsd2si {
+                src: OperandAsm::Imm { .. },
+                ..
+            } | InstructionAsm::Lea {
+                src: OperandAsm::Imm { .. },
+                ..
+            } | InstructionAsm::Lea {
+                dst: OperandAsm::Imm { .. },
+                ..
+            }
+        ) || (wide && !movabs)
+            || (is_double(instr) && operands.iter().any(|o| matches!(o, OperandAsm::Imm { .. })))
+        {
+            return Err(AsmValidationError::ImmediateOperand {
+                function: function.to_string(),
+                index,
+                instr: instr.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// whether the instruction operates on `double`s, in XMM registers.
+fn is_double(instr: &InstructionAsm) -> bool {
+    matches!(
+        instr,
+        InstructionAsm::Mov {
+            ty: AsmType::Double,
+            ..
+        } | InstructionAsm::Binary {
+            ty: AsmType::Double,
+            ..
+        } | InstructionAsm::Cmp {
+            ty: AsmType::Double,
+            ..
+        }
+    )
+}
+
+#[cfg(test)]
+use super::{
+    asmgen::{AsmBinaryOp, AsmUnaryOp, Register},
+    symbol::Symbol,
+};
+
+#[cfg(test)]
+fn main_with(instructions: Vec<InstructionAsm>) -> FunDefAsm {
+    FunDefAsm {
+        identifier: Symbol::intern("main"),
+        global: true,
+        instructions,
+    }
+}
+
+#[test]
+fn accept_fixed_up_function() {
+    let fundef = main_with(vec![
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Stack { off: -4 },
+            dst: OperandAsm::Reg { r: Register::R10 },
+        },
+        InstructionAsm::Mov {
+            ty: AsmType::Longword,
+            src: OperandAsm::Reg { r: Register::R10 },
+            dst: OperandAsm::Stack { off: -8 },
+        },
+        InstructionAsm::Ret,
+    ]);
+    assert_eq!(validate_asm(&fundef), Ok(()));
+}
+
+#[test]
+fn reject_leftover_pseudo() {
+    let fundef = main_with(vec![
+        InstructionAsm::Ret,
+        InstructionAsm::Unary {
+            ty: AsmType::Longword,
+            unop: AsmUnaryOp::Neg,
+            operand: OperandAsm::Pseudo {
+                name: Symbol::numbered("tmp", 3),
+            },
+        },
+    ]);
+    assert_eq!(
+        validate_asm(&fundef),
+        Err(AsmValidationError::UnresolvedPseudo {
+            function: String::from("main"),
+            index: 1,
+            operand: OperandAsm::Pseudo {
+                name: Symbol::numbered("tmp", 3)
+            },
+        })
+    );
+}
+
+/// `Memory` operands count as memory just like `Stack` ones.
+#[test]
+fn reject_memory_to_memory() {
+    let instr = InstructionAsm::Mov {
+        ty: AsmType::Longword,
+        src: OperandAsm::Stack { off: -4 },
+        dst: OperandAsm::Memory {
+            base: Register::SP,
+            off: 8,
+        },
+    };
+    assert_eq!(
+        validate_asm(&main_with(vec![instr.clone()])),
+        Err(AsmValidationError::MemoryToMemory {
+            function: String::from("main"),
+            index: 0,
+            instr,
+        })
+    );
+}
+
+#[test]
+fn reject_immediate_divisor() {
+    let instr = InstructionAsm::Idiv {
+        ty: AsmType::Longword,
+        operand: OperandAsm::Imm { int: 3 },
+    };
+    assert!(matches!(
+        validate_asm(&main_with(vec![instr])),
+        Err(AsmValidationError::ImmediateOperand { index: 0, .. })
+    ));
+}
+
+/// a 64-bit immediate is only encodable as the source of a move into a register.
+#[test]
+fn reject_wide_immediate_outside_movabs() {
+    let big = OperandAsm::Imm { int: 4294967300 };
+    let r10 = OperandAsm::Reg { r: Register::R10 };
+    let movabs = InstructionAsm::Mov {
+        ty: AsmType::Quadword,
+        src: big,
+        dst: r10,
+    };
+    assert_eq!(validate_asm(&main_with(vec![movabs])), Ok(()));
+    let add = InstructionAsm::Binary {
+        ty: AsmType::Quadword,
+        binop: AsmBinaryOp::Add,
+        src: big,
+        dst: r10,
+    };
+    assert!(matches!(
+        validate_asm(&main_with(vec![add])),
+        Err(AsmValidationError::ImmediateOperand { index: 0, .. })
+    ));
+}
+
+/// no SSE instruction takes an immediate, so `double`s are never one.
+#[test]
+fn reject_immediate_double() {
+    let instr = InstructionAsm::Binary {
+        ty: AsmType::Double,
+        binop: AsmBinaryOp::Add,
+        src: OperandAsm::Imm { int: 1 },
+        dst: OperandAsm::Reg { r: Register::XMM0 },
+    };
+    assert!(matches!(
+        validate_asm(&main_with(vec![instr])),
+        Err(AsmValidationError::ImmediateOperand { index: 0, .. })
+    ));
+}
@@ -0,0 +1,221 @@
+//! Warnings, and the `-W` flags that turn them on and off or into errors.
+//! Each warning has a stable name, the one its flags spell, as gcc's do.
+//! Semantic analysis finds them on the typed AST, where every operand has been
+//! converted to the type its operation is carried out in, and the flags decide
+//! which of those are reported, and how.
+use super::{
+    diagnostics::{Diagnostic, Severity},
+    lexer::Span,
+};
+
+/// A warning the compiler can give.
+/// - `DivByZero`: dividing, or taking a remainder, by a constant zero
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Warning {
+    DivByZero,
+}
+
+impl Warning {
+    const ALL: [Warning; 1] = [Warning::DivByZero];
+
+    /// the name `-W<name>`, `-Wno-<name>` and `-Werror=<name>` use.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DivByZero => "div-by-zero",
+        }
+    }
+
+    /// what the warning says, before the flag that controls it.
+    fn message(&self) -> &'static str {
+        match self {
+            Self::DivByZero => "division by zero",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|w| w.name() == name)
+    }
+
+    /// whether the warning is given without any `-W` flags.
+    fn on_by_default(&self) -> bool {
+        match self {
+            Self::DivByZero => true,
+        }
+    }
+}
+
+/// What to do with a warning.
+/// - `Ignore`: don't report it
+/// - `Warn`: report it and carry on
+/// - `Error`: report it as an error, and fail once the file has been checked
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Level {
+    Ignore,
+    Warn,
+    Error,
+}
+
+/// Which warnings are on, and which of those are errors, from the `-W` flags.
+/// Later flags override earlier ones about the same warning, and a warning
+/// named with `-Werror=` or `-Wno-error=` keeps that however `-Werror` is given.
+#[derive(PartialEq, Debug, Clone)]
+pub struct WarningConfig {
+    enabled: Vec<Warning>,
+    all_errors: bool,
+    errors: Vec<(Warning, bool)>,
+}
+
+impl Default for WarningConfig {
+    fn default() -> Self {
+        WarningConfig {
+            enabled: Warning::ALL
+                .into_iter()
+                .filter(Warning::on_by_default)
+                .collect(),
+            all_errors: false,
+            errors: vec![],
+        }
+    }
+}
+
+impl WarningConfig {
+    /// builds the config from `-W` flags without their `-W`: `all`, `<name>`,
+    /// `no-<name>`, `error`, `no-error`, `error=<name>` or `no-error=<name>`.
+    pub fn from_flags(flags: &[String]) -> Result<Self, String> {
+        let mut config = Self::default();
+        let warning = |name: &str| {
+            Warning::from_name(name).ok_or_else(|| format!("(!) unknown warning -W{}", name))
+        };
+        for flag in flags {
+            match flag.as_str() {
+                "all" => config.enabled = Warning::ALL.to_vec(),
+                "error" => config.all_errors = true,
+                "no-error" => config.all_errors = false,
+                _ => {
+                    if let Some(name) = flag.strip_prefix("no-error=") {
+                        config.set_error(warning(name)?, false);
+                    } else if let Some(name) = flag.strip_prefix("error=") {
+                        let warning = warning(name)?;
+                        config.enable(warning, true);
+                        config.set_error(warning, true);
+                    } else if let Some(name) = flag.strip_prefix("no-") {
+                        config.enable(warning(name)?, false);
+                    } else {
+                        config.enable(warning(flag)?, true);
+                    }
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    fn enable(&mut self, warning: Warning, on: bool) {
+        self.enabled.retain(|w| *w != warning);
+        if on {
+            self.enabled.push(warning);
+        }
+    }
+
+    fn set_error(&mut self, warning: Warning, error: bool) {
+        self.errors.retain(|(w, _)| *w != warning);
+        self.errors.push((warning, error));
+    }
+
+    pub fn level(&self, warning: Warning) -> Level {
+        if !self.enabled.contains(&warning) {
+            return Level::Ignore;
+        }
+        let error = match self.errors.iter().find(|(w, _)| *w == warning) {
+            Some((_, error)) => *error,
+            None => self.all_errors,
+        };
+        match error {
+            true => Level::Error,
+            false => Level::Warn,
+        }
+    }
+
+    /// a diagnostic for `warning` at `span`, at the level the flags give it, if any.
+    /// The message ends with the flag that controls it.
+    pub fn report(&self, warning: Warning, span: Span) -> Option<Diagnostic> {
+        let (severity, flag) = match self.level(warning) {
+            Level::Ignore => return None,
+            Level::Warn => (Severity::Warning, format!("-W{}", warning.name())),
+            Level::Error => (Severity::Error, format!("-Werror={}", warning.name())),
+        };
+        Some(Diagnostic {
+            severity,
+            ..Diagnostic::error(
+                warning.name(),
+                format!("{} [{}]", warning.message(), flag),
+                span,
+            )
+        })
+    }
+}
+
+/// The warning config for a run, and the warnings found but not yet reported.
+#[derive(Debug, Default)]
+pub struct Warnings {
+    pub config: WarningConfig,
+    pub found: Vec<Diagnostic>,
+}
+
+#[cfg(test)]
+fn level_with(flags: &[&str]) -> Level {
+    let flags: Vec<String> = flags.iter().map(|f| String::from(*f)).collect();
+    WarningConfig::from_flags(&flags)
+        .unwrap()
+        .level(Warning::DivByZero)
+}
+
+#[test]
+fn flags_set_levels() {
+    assert_eq!(level_with(&[]), Level::Warn);
+    assert_eq!(level_with(&["all"]), Level::Warn);
+    assert_eq!(level_with(&["no-div-by-zero"]), Level::Ignore);
+    assert_eq!(level_with(&["no-div-by-zero", "div-by-zero"]), Level::Warn);
+    assert_eq!(level_with(&["error"]), Level::Error);
+    assert_eq!(level_with(&["error=div-by-zero"]), Level::Error);
+    assert_eq!(level_with(&["error", "no-error"]), Level::Warn);
+}
+
+/// a disabled warning stays quiet under -Werror, and a per-warning error setting
+/// outlasts -Werror and -Wno-error.
+#[test]
+fn werror_interactions() {
+    assert_eq!(level_with(&["error", "no-div-by-zero"]), Level::Ignore);
+    assert_eq!(level_with(&["no-div-by-zero", "error"]), Level::Ignore);
+    assert_eq!(
+        level_with(&["no-div-by-zero", "error=div-by-zero"]),
+        Level::Error
+    );
+    assert_eq!(level_with(&["error", "no-error=div-by-zero"]), Level::Warn);
+    assert_eq!(level_with(&["error=div-by-zero", "no-error"]), Level::Error);
+}
+
+#[test]
+fn unknown_warning() {
+    assert_eq!(
+        WarningConfig::from_flags(&[String::from("error=bogus")]),
+        Err(String::from("(!) unknown warning -Wbogus"))
+    );
+}
+
+/// a warning is reported at the level its flags give it, naming the flag.
+#[test]
+fn report_at_level() {
+    let span = Span { start: 26, end: 31 };
+    let warning = WarningConfig::default()
+        .report(Warning::DivByZero, span)
+        .unwrap();
+    assert_eq!(warning.severity, Severity::Warning);
+    assert_eq!(warning.message, "division by zero [-Wdiv-by-zero]");
+    assert_eq!(warning.span, span);
+    let error = WarningConfig::from_flags(&[String::from("error")]).unwrap();
+    let promoted = error.report(Warning::DivByZero, span).unwrap();
+    assert_eq!(promoted.severity, Severity::Error);
+    assert_eq!(promoted.message, "division by zero [-Werror=div-by-zero]");
+    let quiet = WarningConfig::from_flags(&[String::from("no-div-by-zero")]).unwrap();
+    assert!(quiet.report(Warning::DivByZero, span).is_none());
+}
@@ -0,0 +1,27 @@
+//! crumb, a C compiler for x86-64, as a library.
+//! `compile_to_assembly` takes preprocessed source all the way to assembly
+//! text; the stage functions below run one step each, so a program can stop
+//! anywhere and look at the IR in between:
+//! `lex` → `parse` → `analyze` → `gen_tacky` → `gen_asm`, then `AsmFormatter::program`.
+//! Names are interned in the `Context` current on the thread; entering a new
+//! one for each compilation frees what the last one interned.
+pub mod compiler;
+
+pub use compiler::{
+    asmgen::{gen_asm, CodegenError, CodegenOptions, ProgramAsm},
+    compile_to_assembly,
+    context::Context,
+    emit::{emit_asm_to, AsmFormatter, Emit, Syntax},
+    interpret::{interpret, InterpretError},
+    lexer::{lex, LexError, SpannedToken, Token},
+    optimize::Pass,
+    parser::{parse, ParseError, ProgramC},
+    semantic::{analyze, SemanticError},
+    symbol::Symbol,
+    tacky::{gen_tacky, ProgramTacky},
+    target::{RelocationModel, Target},
+    CompileError, CompileOptions,
+};
+
+#[cfg(test)]
+mod test;
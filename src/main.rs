@@ -1,137 +1,606 @@
 use clap::Parser;
-use std::{path::Path, process, str};
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Write},
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    process, str,
+};
+use tempfile::TempDir;
 
-mod compiler;
-use compiler::compile;
-
-#[cfg(test)]
-mod test;
+use crumb::compiler::{
+    compile,
+    context::Context,
+    diagnostics::{
+        ColorChoice, Diagnostic, DiagnosticsFormat, JsonDiagnostic, LineIndex, Severity,
+    },
+    emit::Emit,
+    timings::{Timings, TimingsFormat},
+    warnings::{WarningConfig, Warnings},
+    CompileError, CompileOptions,
+};
 
 #[derive(Parser, Debug)]
 #[command(version("0.1.1"), about = "A C compiler for x86-64 Linux", long_about = None)]
 struct Args {
-    #[arg(help = "Path to the file to compile")]
-    file_path: String,
+    #[arg(
+        required = true,
+        help = "Paths to the C files to compile, - for standard input, and any object files to link with them"
+    )]
+    file_paths: Vec<String>,
+    #[command(flatten)]
+    options: CompileOptions,
     #[clap(
         long,
         short,
         action,
-        help = "Directs compiler to run to lexer, but stop before parsing"
+        help = "Directs for binary to be named after the source, in the current directory, instead of a.out"
     )]
-    lex: bool,
+    incd: bool,
     #[clap(
-        long,
-        short,
+        short = 'S',
         action,
-        help = "Directs compiler to run lexer and parser, but stop before assembly generation"
+        conflicts_with_all = ["emit", "check"],
+        help = "Directs compiler to stop after writing the assembly file, without assembling or linking"
     )]
-    parse: bool,
+    assembly_only: bool,
     #[clap(
-        long,
-        short,
+        short = 'c',
         action,
-        help = "Directs compiler to perform lexing, parsing, and assembly generation, but stop before code emission"
+        conflicts_with_all = ["assembly_only", "check"],
+        help = "Directs compiler to stop after writing an object file, without linking"
     )]
-    codegen: bool,
+    object_only: bool,
     #[clap(
         long,
-        short,
         action,
-        help = "Directs compiler to perform lexing, parsing, and tacky, but stop before code emission"
+        conflicts_with_all = ["assembly_only", "object_only", "check"],
+        help = "Runs the program once it is linked, exiting with its exit status"
     )]
-    tacky: bool,
+    run: bool,
     #[clap(
         long,
-        short,
-        action,
-        help = "Directs for binary to be placed adjacent in current directory"
+        value_name = "ARGS",
+        num_args = 1..,
+        allow_hyphen_values = true,
+        requires = "run",
+        help = "Passes every argument after this one to the program --run runs"
     )]
-    incd: bool,
+    run_args: Vec<String>,
+    #[clap(
+        short = 'o',
+        value_name = "PATH",
+        conflicts_with = "check",
+        help = "Path of the executable, assembly, or object file to write; - writes assembly to stdout"
+    )]
+    output: Option<String>,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "table",
+        help = "Prints the time each phase took to stderr, as a table or as json"
+    )]
+    timings: Option<TimingsFormat>,
+    #[clap(
+        short = 'l',
+        value_name = "LIB",
+        help = "Links with the library LIB, after every object file"
+    )]
+    libraries: Vec<String>,
+    #[clap(
+        short = 'W',
+        value_name = "WARNING",
+        help = "Controls warnings: -Wall, -W<name>, -Wno-<name>, -Werror and -Werror=<name>"
+    )]
+    warnings: Vec<String>,
+    #[clap(
+        short = 'L',
+        value_name = "DIR",
+        help = "Adds DIR to the directories searched for -l libraries"
+    )]
+    library_dirs: Vec<String>,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        default_value_t = ColorChoice::Auto,
+        help = "Colors diagnostics: auto colors them when stderr is a terminal and NO_COLOR isn't set"
+    )]
+    color: ColorChoice,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        default_value_t = DiagnosticsFormat::Human,
+        help = "Prints warnings and errors as source snippets, or as one JSON object per line for editors and other tools"
+    )]
+    diagnostics_format: DiagnosticsFormat,
 }
 
 fn main() {
-    let args = Args::parse();
-    let incd = args.incd; // will be moving
-
-    // driver
-    let stripped_extension = if args.file_path.ends_with(r".c") {
-        String::from(args.file_path.strip_suffix(r".c").unwrap())
-    } else {
-        println!("(!) {} is not a c file", args.file_path);
-        return;
-    };
-    let preprocessed_file = format!("{}.i", stripped_extension);
-    preprocess(&args.file_path, &preprocessed_file);
-    let assembly_file = match compile(stripped_extension, args) {
-        Ok(strang) => strang,
+    // a bare -O means -O1, as with gcc, without clap taking the next argument as its
+    // level; gcc's -f spellings of the code generation flags are accepted too
+    let args = match Args::try_parse_from(std::env::args().map(|arg| match arg.as_str() {
+        "-O" => String::from("-O1"),
+        "-fomit-frame-pointer" => String::from("--omit-frame-pointer"),
+        "-ffunction-sections" => String::from("--function-sections"),
+        "-fcf-protection" => String::from("--cf-protection"),
+        "-fpic" | "-fPIC" => String::from("--relocation-model=pic"),
+        "-fpie" | "-fPIE" => String::from("--relocation-model=pie"),
+        "-fno-pic" | "-fno-pie" => String::from("--relocation-model=static"),
+        _ => arg,
+    })) {
+        Ok(args) => args,
+        // clap would exit 2, the code for a lexing error; `--help` and
+        // `--version` end here too, and succeed
         Err(e) => {
-            println!("{}", e);
-            return;
+            let _ = e.print();
+            process::exit(match e.use_stderr() {
+                true => 1,
+                false => 0,
+            });
         }
     };
-    assemble(&assembly_file, incd);
+    let format = args.timings;
+    let running = args.run;
+    let diagnostics_format = args.diagnostics_format;
+    let mut timings = Timings::default();
+    let res = drive(args, &mut timings);
+    if let Some(format) = format {
+        eprint!("{}", timings.report(format));
+    }
+    match res {
+        Ok(0) => {}
+        Ok(code) => process::exit(code),
+        Err(failure) => {
+            match (diagnostics_format, &failure) {
+                // compile failures were rendered in the asked-for format already
+                (
+                    DiagnosticsFormat::Json,
+                    Failure::Driver { message } | Failure::Tool { message },
+                ) if cfg!(feature = "serde") => {
+                    let unlocated =
+                        JsonDiagnostic::unlocated(Severity::Error, failure.code(), message, None);
+                    eprint!("{}", json_line(&unlocated));
+                }
+                _ => eprintln!("{}", failure.message().trim_end()),
+            }
+            process::exit(failure.exit_code(running));
+        }
+    }
 }
 
-/// preprocesses the C file
-/// kind of cheating, but we're only writing a compiler, not a preprocessor,
-/// at least for now.
-pub fn preprocess(input_file: &String, preprocessed_file: &String) {
-    let output = if cfg!(target_os = "windows") {
-        todo!("This compiler currently targets x64 Linux. Make a PR or an issue if you want a different target.")
-    } else {
-        process::Command::new("gcc")
-            .args(["-E", "-P"]) // gcc only runs preprocessor
-            .arg(input_file)
-            .arg("-o")
-            .arg(preprocessed_file)
-            .output()
-            .expect("failed to execute preprocesser")
+/// one diagnostic as a line of JSON.
+#[cfg(feature = "serde")]
+fn json_line(diagnostic: &JsonDiagnostic) -> String {
+    serde_json::to_string(diagnostic).expect("diagnostics always serialize") + "\n"
+}
+
+#[cfg(not(feature = "serde"))]
+fn json_line(_diagnostic: &JsonDiagnostic) -> String {
+    unreachable!("the driver refuses json diagnostics without the serde feature")
+}
+
+/// Why the driver gave up, which decides the exit code.
+/// - `Driver`: bad arguments, files that can't be read or written, or a failed preprocessor
+/// - `Compile`: the rendered error from compiling a file
+/// - `Tool`: the assembler or linker failed
+#[derive(Debug)]
+enum Failure {
+    Driver { message: String },
+    Compile { message: String, e: CompileError },
+    Tool { message: String },
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure::Driver { message }
+    }
+}
+
+impl Failure {
+    /// the stable name of the failure, for tools reading diagnostics.
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Driver { .. } => "driver",
+            Self::Compile { e, .. } => e.code(),
+            Self::Tool { .. } => "tool",
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            Self::Driver { message } | Self::Compile { message, .. } | Self::Tool { message } => {
+                message
+            }
+        }
+    }
+
+    /// The exit code for the stage that failed, for test harnesses telling them apart:
+    /// - 0: success
+    /// - 1: anything outside compilation proper, such as arguments, files, or the preprocessor
+    /// - 2: lexing
+    /// - 3: parsing
+    /// - 4: semantic analysis, and warnings `-Werror` made errors
+    /// - 5: code generation, assembling, or linking
+    ///
+    /// `running`, under `--run`, every failure is 125 instead, as with `env` and
+    /// `timeout`, since any other code may be the program's own status.
+    fn exit_code(&self, running: bool) -> i32 {
+        if running {
+            return 125;
+        }
+        match self {
+            Self::Driver { .. } => 1,
+            Self::Compile { e, .. } => match e {
+                CompileError::Lex { .. } => 2,
+                CompileError::Parse { .. } => 3,
+                CompileError::Semantic { .. } | CompileError::WarningsAsErrors { .. } => 4,
+                CompileError::Codegen { .. } | CompileError::Internal { .. } => 5,
+                CompileError::FileIo { .. } => 1,
+            },
+            Self::Tool { .. } => 5,
+        }
+    }
+}
+
+/// compiles every C file given, then assembles, links and runs as the flags ask,
+/// returning the exit code crumb should have: 0, or the status of a program `--run` ran.
+/// Intermediate files live in a temporary directory that is gone once this returns.
+fn drive(args: Args, timings: &mut Timings) -> Result<i32, Failure> {
+    let assembly_only = args.assembly_only;
+    let object_only = args.object_only;
+    let output = args.output.clone();
+    let color = args.color.enabled(
+        env::var_os("NO_COLOR").as_deref(),
+        io::stderr().is_terminal(),
+    );
+    let json = args.diagnostics_format == DiagnosticsFormat::Json;
+    if json && !cfg!(feature = "serde") {
+        return Err(
+            String::from("(!) json diagnostics need crumb built with the serde feature").into(),
+        );
+    }
+    let render = |diagnostic: &Diagnostic, lines: &LineIndex| match json {
+        true => json_line(&diagnostic.to_json(lines)),
+        false => diagnostic.render(lines, color),
+    };
+
+    let sources: Vec<&String> = args
+        .file_paths
+        .iter()
+        .filter(|f| !f.ends_with(r".o"))
+        .collect();
+    // prebuilt objects only matter to the link step
+    let objects: Vec<&String> = args
+        .file_paths
+        .iter()
+        .filter(|f| f.ends_with(r".o"))
+        .collect();
+    if let Some(f) = sources
+        .iter()
+        .find(|f| !f.ends_with(r".c") && f.as_str() != "-")
+    {
+        return Err(format!("(!) {} is not a c file", f).into());
+    }
+    let to_stdout = output.as_deref() == Some("-");
+    if to_stdout && !assembly_only {
+        return Err(String::from("(!) -o - can only write assembly, with -S").into());
+    }
+    let writes_final = assembly_only || (object_only && args.options.emit == Emit::Obj);
+    if (assembly_only || object_only)
+        && !(args.libraries.is_empty() && args.library_dirs.is_empty())
+    {
+        let message = "-l and -L are ignored without linking";
+        match json {
+            true => eprint!(
+                "{}",
+                json_line(&JsonDiagnostic::unlocated(
+                    Severity::Warning,
+                    "unused-link-flags",
+                    message,
+                    None
+                ))
+            ),
+            false => eprintln!("warning: {}", message),
+        }
+    }
+    if output.is_some() && (assembly_only || object_only) && sources.len() > 1 {
+        return Err(String::from(
+            "(!) -o can't name the output of -S or -c for more than one file",
+        )
+        .into());
+    }
+
+    let mut warnings = Warnings {
+        config: WarningConfig::from_flags(&args.warnings)?,
+        found: vec![],
     };
 
-    let out = str::from_utf8(&output.stdout).expect("Invalid UTF-8 sequence");
-    let err = str::from_utf8(&output.stderr).expect("Invalid UTF-8 sequence");
+    let intermediates = TempDir::new().map_err(|e| format!("(!) {}", e))?;
+    let extension = match args.options.emit {
+        Emit::Asm => "s",
+        Emit::Obj => "o",
+    };
+    // each emitted file, and the name its final artifact defaults to, minus the extension
+    let mut emitted: Vec<(String, String)> = Vec::with_capacity(sources.len());
+    // --check goes on to the next file, and exits as the first that failed
+    let mut check_failed = None;
+    for (i, source) in sources.iter().enumerate() {
+        // what is interned for a file goes once its diagnostics are printed
+        let _context = Context::new().enter();
+        let (name, stem) = match source.strip_suffix(r".c") {
+            Some(stem) => (source.as_str(), stem),
+            None => ("<stdin>", "stdin"),
+        };
+        // the compiler writes straight to the final artifact when there is nothing left to do
+        let emitted_file = match &output {
+            Some(path) if writes_final && !to_stdout => path.clone(),
+            _ if writes_final && !to_stdout => format!("{}.{}", stem, extension),
+            _ => String::from(
+                intermediates
+                    .path()
+                    .join(format!("{}.{}", i, extension))
+                    .to_str()
+                    .expect("Invalid UTF-8 sequence"),
+            ),
+        };
+        // the compiler reads the preprocessed source as it parses, rather than
+        // holding it all
+        let preprocessed = intermediates.path().join(format!("{}.i", i));
+        let input = timings
+            .time("preprocess", || preprocess(source, &preprocessed))
+            .and_then(|()| fs::File::open(&preprocessed).map_err(|e| format!("(!) {}", e)))
+            .map_err(|e| format!("{}: {}", name, e))?;
+        let compiled = compile(input, emitted_file, &args.options, timings, &mut warnings);
+        // diagnostics quote the source, so it is read back whole when there are any
+        let text = match compiled.is_ok() && warnings.found.is_empty() {
+            true => String::new(),
+            false => fs::read_to_string(&preprocessed).unwrap_or_default(),
+        };
+        let lines = LineIndex::new(&text, name);
+        for warning in warnings.found.drain(..) {
+            eprint!("{}", render(&warning, &lines));
+        }
+        match compiled {
+            Ok(Some(file)) => emitted.push((file, String::from(stem))),
+            // a stage flag stopped compilation before anything was written
+            Ok(None) => {}
+            Err(e) => {
+                let diagnostics = e.diagnostics();
+                let message = match (diagnostics.is_empty(), json) {
+                    (true, false) => format!("{}: {}", name, e),
+                    (true, true) => json_line(&JsonDiagnostic::unlocated(
+                        Severity::Error,
+                        e.code(),
+                        &e.to_string(),
+                        Some(name),
+                    )),
+                    (false, _) => diagnostics.iter().map(|d| render(d, &lines)).collect(),
+                };
+                let failure = Failure::Compile { message, e };
+                if !args.options.check {
+                    return Err(failure);
+                }
+                eprintln!("{}", failure.message().trim_end());
+                check_failed.get_or_insert(failure.exit_code(false));
+            }
+        }
+    }
+    if let Some(code) = check_failed {
+        return Ok(code);
+    }
+    if emitted.is_empty() || writes_final && !to_stdout {
+        return Ok(0);
+    }
+    if to_stdout {
+        let assembly = fs::read(&emitted[0].0).map_err(|e| format!("(!) {}", e))?;
+        let mut stdout = io::stdout().lock();
+        // a reader that stopped early, like `head`, has all it wanted
+        return match stdout.write_all(&assembly).and_then(|()| stdout.flush()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(format!("(!) {}", e).into()),
+            _ => Ok(0),
+        };
+    }
 
-    if !out.is_empty() {
-        println!("PREPROCESS STDOUT: {}", out);
+    if object_only {
+        emitted.iter().try_for_each(|(emitted_file, stem)| {
+            let object_file = match &output {
+                Some(path) => path.clone(),
+                None => format!("{}.o", stem),
+            };
+            timings
+                .time("assemble", || assemble_object(emitted_file, &object_file))
+                .map_err(|message| Failure::Tool { message })
+        })?;
+        Ok(0)
+    } else {
+        let executable = output.unwrap_or(if args.run {
+            // gone with the other intermediates once the program has run
+            String::from(
+                intermediates
+                    .path()
+                    .join("a.out")
+                    .to_str()
+                    .expect("Invalid UTF-8 sequence"),
+            )
+        } else if args.incd {
+            // named after the first source, in the current directory
+            String::from(
+                Path::new(&emitted[0].1)
+                    .file_name()
+                    .expect("Invalid path")
+                    .to_str()
+                    .expect("Invalid UTF-8 sequence"),
+            )
+        } else {
+            String::from("a.out")
+        });
+        let inputs: Vec<&String> = emitted.iter().map(|(f, _)| f).chain(objects).collect();
+        // libraries come after the objects that need them
+        let libraries: Vec<String> = args
+            .library_dirs
+            .iter()
+            .map(|dir| format!("-L{}", dir))
+            .chain(args.libraries.iter().map(|lib| format!("-l{}", lib)))
+            .collect();
+        // gcc assembles whatever is still assembly as it links
+        timings
+            .time("link", || assemble(&inputs, &libraries, &executable))
+            .map_err(|message| Failure::Tool { message })?;
+        if args.run {
+            return run(&executable, &args.run_args);
+        }
+        Ok(0)
     }
-    if !err.is_empty() {
-        println!("PREPROCESS STDERR: {}", err);
+}
+
+/// runs the linked program with `args`, sharing crumb's standard streams, and
+/// reports how it ended on stderr. Returns its exit status, or 128 plus the signal
+/// that killed it, as shells do.
+fn run(executable: &str, args: &[String]) -> Result<i32, Failure> {
+    // a bare file name would be looked up on the PATH
+    let status = process::Command::new(Path::new(".").join(executable))
+        .args(args)
+        .status()
+        .map_err(|e| format!("(!) failed to run {}: {}", executable, e))?;
+    match status.code() {
+        Some(code) => {
+            eprintln!("crumb: program exited with status {}", code);
+            Ok(code)
+        }
+        None => {
+            let signal = status.signal().unwrap_or_default();
+            eprintln!("crumb: program killed by signal {}", signal);
+            Ok(128 + signal)
+        }
     }
 }
 
-/// Assemble the C file
+/// runs an external tool to completion and returns what it wrote to stdout,
+/// passing along any warnings it printed.
+/// A tool that can't be started or exits unsuccessfully is an error carrying its stderr.
+fn run_tool(stage: &str, command: &mut process::Command) -> Result<Vec<u8>, String> {
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(e) => return Err(format!("(!) failed to start {}: {}", stage, e)),
+    };
+
+    let err = String::from_utf8_lossy(&output.stderr);
+
+    if !output.status.success() {
+        return Err(format!(
+            "(!) {} failed ({}):\n{}",
+            stage, output.status, err
+        ));
+    }
+    // warnings go where ours do, leaving stdout to the output
+    eprint!("{}", err);
+    Ok(output.stdout)
+}
+
+/// preprocesses the C file, or standard input for `-`, writing the result to `output`
 /// kind of cheating, but we're only writing a compiler, not a preprocessor,
 /// at least for now.
-pub fn assemble(input_file: &String, incd: bool) {
-    let output_file = if incd {
-        Path::new(input_file)
-            .file_stem()
-            .expect("Invalid path")
-            .to_str()
-            .expect("Invalid UTF-8 sequence")
-    } else {
-        Path::new(input_file)
-            .to_str()
-            .expect("Invalid path")
-            .strip_suffix(r".s")
-            .unwrap()
-    };
-    let output = if cfg!(target_os = "windows") {
+pub fn preprocess(input_file: &String, output: &Path) -> Result<(), String> {
+    if cfg!(target_os = "windows") {
         todo!("This compiler currently targets x64 Linux. Make a PR or an issue if you want a different target.")
+    }
+    let mut command = process::Command::new("gcc");
+    // gcc only runs preprocessor, leaving linemarkers so errors can name the original lines
+    command.arg("-E");
+    if input_file == "-" {
+        // gcc can't tell the language of stdin from a file name
+        command
+            .args(["-x", "c", "-"])
+            .stdin(process::Stdio::inherit());
     } else {
-        process::Command::new("gcc") // this isn't what it looks like!!
-            .args([input_file, "-o", output_file])
-            .output()
-            .expect("failed to execute preprocesser")
-    };
-
-    let out = str::from_utf8(&output.stdout).expect("Invalid UTF-8 sequence");
-    let err = str::from_utf8(&output.stderr).expect("Invalid UTF-8 sequence");
+        command.arg(input_file);
+    }
+    command.arg("-o").arg(output);
+    run_tool("preprocess", &mut command).map(|_| ())
+}
 
-    if !out.is_empty() {
-        println!("ASSEMBLE STDOUT: {}", out);
+/// Assemble the compiled files, or just link the ones that are already object files,
+/// with the `-L` and `-l` flags in `libraries`
+/// kind of cheating, but we're only writing a compiler, not a preprocessor,
+/// at least for now.
+pub fn assemble(
+    input_files: &[&String],
+    libraries: &[String],
+    output_file: &str,
+) -> Result<(), String> {
+    if cfg!(target_os = "windows") {
+        todo!("This compiler currently targets x64 Linux. Make a PR or an issue if you want a different target.")
     }
-    if !err.is_empty() {
-        println!("ASSEMBLE STDERR: {}", err);
+    run_tool(
+        "assemble",
+        process::Command::new("gcc") // this isn't what it looks like!!
+            .args(input_files)
+            .args(libraries)
+            .args(["-o", output_file]),
+    )
+    .map(|_| ())
+}
+
+/// Assemble the assembly file into an object file, without linking.
+pub fn assemble_object(input_file: &str, output_file: &str) -> Result<(), String> {
+    if cfg!(target_os = "windows") {
+        todo!("This compiler currently targets x64 Linux. Make a PR or an issue if you want a different target.")
     }
+    run_tool(
+        "assemble",
+        process::Command::new("gcc").args(["-c", input_file, "-o", output_file]),
+    )
+    .map(|_| ())
+}
+
+#[test]
+fn exit_codes_by_stage() {
+    let compile_failure = |e| Failure::Compile {
+        message: String::new(),
+        e,
+    };
+    let lex = CompileError::Lex {
+        e: crumb::compiler::lexer::LexError::Unrecognized {
+            strang: String::from("@"),
+            span: Default::default(),
+        },
+    };
+    let parse = CompileError::Parse {
+        errors: vec![crumb::compiler::parser::ParseError::MissingSemicolon {
+            span: Default::default(),
+        }],
+    };
+    let semantic = CompileError::Semantic {
+        errors: vec![crumb::compiler::semantic::SemanticError::InvalidLvalue {
+            op: "=",
+            span: Default::default(),
+        }],
+    };
+    let codegen = CompileError::Codegen {
+        e: crumb::compiler::asmgen::CodegenError::NoSingleInstruction {
+            op: crumb::compiler::parser::BinaryOp::Add,
+        },
+    };
+    let io = CompileError::FileIo {
+        e: io::Error::other("unreadable"),
+    };
+    assert_eq!(Failure::from(String::new()).exit_code(false), 1);
+    assert_eq!(compile_failure(io).exit_code(false), 1);
+    assert_eq!(compile_failure(lex).exit_code(false), 2);
+    assert_eq!(compile_failure(parse).exit_code(false), 3);
+    assert_eq!(compile_failure(semantic).exit_code(false), 4);
+    assert_eq!(compile_failure(codegen).exit_code(false), 5);
+    assert_eq!(Failure::from(String::new()).exit_code(true), 125);
+    assert_eq!(
+        Failure::Tool {
+            message: String::new()
+        }
+        .exit_code(false),
+        5
+    );
 }
@@ -1,4 +1,7 @@
-use crate::compiler::{asmgen, lexer, parser, tacky};
+use crate::compiler::{
+    self, asmgen, diagnostics, emit, encode, lexer, optimize, parser, semantic, symbol, tacky,
+    timings::Timings,
+};
 
 static BASIC_RETURN_FROM_MAIN: &str = "int main(void) { return 2; }";
 static WHITESPACELESS_RETURN_FROM_MAIN: &str = "int main(void){return 2;}";
@@ -11,7 +14,7 @@ fn basic_return_from_main_tokenize() {
             ty: lexer::Type::Int,
         },
         lexer::Token::Identifier {
-            val: String::from("main"),
+            val: symbol::Symbol::intern("main"),
         },
         lexer::Token::OpenParens,
         lexer::Token::TyKeyword {
@@ -26,9 +29,10 @@ fn basic_return_from_main_tokenize() {
     ];
 
     if let Ok(tokens) = lexer::tokenize(source) {
+        let tokens: Vec<lexer::Token> = tokens.into_iter().map(|t| t.token).collect();
         assert_eq!(stream, tokens)
     } else {
-        assert!(false)
+        unreachable!()
     }
 }
 
@@ -40,41 +44,94 @@ fn basic_return_from_main_parse() {
         assert_eq!(
             parser::parse(tokens).unwrap(),
             parser::ProgramC {
-                function: Box::new(parser::FunDefC {
-                    identifier: String::from("main"),
-                    statement: Box::new(parser::StatementC::Return {
-                        exp: Box::new(parser::Exp::Const { c: 2 })
-                    })
-                })
+                items: vec![parser::TopLevelC::Function {
+                    fundef: parser::FunDefC {
+                        identifier: symbol::Symbol::intern("main"),
+                        ret: parser::CType::Int,
+                        params: vec![],
+                        body: parser::Block {
+                            items: vec![parser::BlockItem::Statement {
+                                stmt: parser::StatementC::Return {
+                                    exp: Box::new(parser::Exp::Const {
+                                        c: parser::Const::Int { int: 2 },
+                                        span: lexer::Span { start: 24, end: 25 }
+                                    }),
+                                    span: lexer::Span { start: 17, end: 26 }
+                                }
+                            }],
+                            span: lexer::Span { start: 15, end: 28 }
+                        },
+                        storage: None,
+                        span: lexer::Span { start: 0, end: 28 }
+                    }
+                }]
             }
         )
     } else {
-        assert!(false)
+        unreachable!()
     }
 }
 
+/// a function that needs no stack slots, doing `instr` before it returns: the
+/// frame pointer prologue and epilogue with their CFI around it.
+fn framed(instr: asmgen::InstructionAsm) -> Vec<asmgen::InstructionAsm> {
+    use asmgen::{CfiDirective, InstructionAsm, Register};
+    let cfi = |directive| InstructionAsm::Cfi { directive };
+    vec![
+        cfi(CfiDirective::StartProc),
+        InstructionAsm::Push { r: Register::BP },
+        cfi(CfiDirective::DefCfaOffset { off: 16 }),
+        cfi(CfiDirective::Offset {
+            r: Register::BP,
+            off: -16,
+        }),
+        InstructionAsm::MovQ {
+            src: Register::SP,
+            dst: Register::BP,
+        },
+        cfi(CfiDirective::DefCfaRegister { r: Register::BP }),
+        instr,
+        InstructionAsm::MovQ {
+            src: Register::BP,
+            dst: Register::SP,
+        },
+        InstructionAsm::Pop { r: Register::BP },
+        cfi(CfiDirective::DefCfa {
+            r: Register::SP,
+            off: 8,
+        }),
+        InstructionAsm::Ret,
+        cfi(CfiDirective::EndProc),
+    ]
+}
+
 #[test]
 fn basic_return_from_main_asmgen() {
     let source = BASIC_RETURN_FROM_MAIN.to_owned();
 
     assert_eq!(
-        asmgen::gen_asm(tacky::TackyEmitter::gen_tacky(
-            parser::parse(lexer::tokenize(source).expect("expected valid stream of tokens"))
-                .expect("expected valid parsing of tokens")
-        )),
+        asmgen::gen_asm(
+            tacky::TackyEmitter::gen_tacky(
+                parser::parse(lexer::tokenize(source).expect("expected valid stream of tokens"))
+                    .expect("expected valid parsing of tokens")
+            ),
+            asmgen::CodegenOptions::default()
+        )
+        .unwrap(),
         asmgen::ProgramAsm {
-            function: Box::new(asmgen::FunDefAsm {
-                identifier: String::from("main"),
-                instructions: vec![
-                    asmgen::InstructionAsm::Mov {
+            items: vec![asmgen::TopLevelAsm::Function {
+                fundef: asmgen::FunDefAsm {
+                    identifier: symbol::Symbol::intern("main"),
+                    global: true,
+                    instructions: framed(asmgen::InstructionAsm::Mov {
+                        ty: asmgen::AsmType::Longword,
                         src: asmgen::OperandAsm::Imm { int: 2 },
                         dst: asmgen::OperandAsm::Reg {
                             r: asmgen::Register::AX
                         },
-                    },
-                    asmgen::InstructionAsm::Ret
-                ]
-            })
+                    })
+                }
+            }]
         }
     )
 }
@@ -87,7 +144,7 @@ fn whitespaceless_return_from_main_tokenize() {
             ty: lexer::Type::Int,
         },
         lexer::Token::Identifier {
-            val: String::from("main"),
+            val: symbol::Symbol::intern("main"),
         },
         lexer::Token::OpenParens,
         lexer::Token::TyKeyword {
@@ -102,9 +159,10 @@ fn whitespaceless_return_from_main_tokenize() {
     ];
 
     if let Ok(tokens) = lexer::tokenize(source) {
+        let tokens: Vec<lexer::Token> = tokens.into_iter().map(|t| t.token).collect();
         assert_eq!(stream, tokens)
     } else {
-        assert!(false)
+        unreachable!()
     }
 }
 
@@ -116,16 +174,31 @@ fn whitespaceless_return_from_main_parse() {
         assert_eq!(
             parser::parse(tokens).unwrap(),
             parser::ProgramC {
-                function: Box::new(parser::FunDefC {
-                    identifier: String::from("main"),
-                    statement: Box::new(parser::StatementC::Return {
-                        exp: Box::new(parser::Exp::Const { c: 2 })
-                    })
-                })
+                items: vec![parser::TopLevelC::Function {
+                    fundef: parser::FunDefC {
+                        identifier: symbol::Symbol::intern("main"),
+                        ret: parser::CType::Int,
+                        params: vec![],
+                        body: parser::Block {
+                            items: vec![parser::BlockItem::Statement {
+                                stmt: parser::StatementC::Return {
+                                    exp: Box::new(parser::Exp::Const {
+                                        c: parser::Const::Int { int: 2 },
+                                        span: lexer::Span { start: 22, end: 23 }
+                                    }),
+                                    span: lexer::Span { start: 15, end: 24 }
+                                }
+                            }],
+                            span: lexer::Span { start: 14, end: 25 }
+                        },
+                        storage: None,
+                        span: lexer::Span { start: 0, end: 25 }
+                    }
+                }]
             }
         )
     } else {
-        assert!(false)
+        unreachable!()
     }
 }
 
@@ -134,23 +207,994 @@ fn whitespaceless_return_from_main_asmgen() {
     let source = WHITESPACELESS_RETURN_FROM_MAIN.to_owned();
 
     assert_eq!(
-        asmgen::gen_asm(tacky::TackyEmitter::gen_tacky(
-            parser::parse(lexer::tokenize(source).expect("expected valid stream of tokens"))
-                .expect("expected valid parsing of tokens")
-        )),
+        asmgen::gen_asm(
+            tacky::TackyEmitter::gen_tacky(
+                parser::parse(lexer::tokenize(source).expect("expected valid stream of tokens"))
+                    .expect("expected valid parsing of tokens")
+            ),
+            asmgen::CodegenOptions::default()
+        )
+        .unwrap(),
         asmgen::ProgramAsm {
-            function: Box::new(asmgen::FunDefAsm {
-                identifier: String::from("main"),
-                instructions: vec![
-                    asmgen::InstructionAsm::Mov {
+            items: vec![asmgen::TopLevelAsm::Function {
+                fundef: asmgen::FunDefAsm {
+                    identifier: symbol::Symbol::intern("main"),
+                    global: true,
+                    instructions: framed(asmgen::InstructionAsm::Mov {
+                        ty: asmgen::AsmType::Longword,
                         src: asmgen::OperandAsm::Imm { int: 2 },
                         dst: asmgen::OperandAsm::Reg {
                             r: asmgen::Register::AX
                         },
+                    })
+                }
+            }]
+        }
+    )
+}
+
+#[test]
+fn constant_expression_folds_to_single_move() {
+    let source = String::from("int main(void) { return (3 + 4) * 2 - 6 / 3; }");
+
+    let asm = asmgen::gen_asm(
+        optimize::Pipeline::new(2, &[], &[]).optimize(
+            tacky::TackyEmitter::gen_tacky(
+                parser::parse(lexer::tokenize(source).expect("expected valid stream of tokens"))
+                    .expect("expected valid parsing of tokens"),
+            ),
+            &mut Timings::default(),
+        ),
+        asmgen::CodegenOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        asm.functions().next().unwrap().instructions,
+        framed(asmgen::InstructionAsm::Mov {
+            ty: asmgen::AsmType::Longword,
+            src: asmgen::OperandAsm::Imm { int: 12 },
+            dst: asmgen::OperandAsm::Reg {
+                r: asmgen::Register::AX
+            },
+        })
+    );
+}
+
+/// ## TESTS THE FOLLOWING OPTIMIZATION
+/// ### TACKY (input):
+/// ```text
+/// Binary(Multiply, Constant(7), Constant(6), Var("tmp.0"))
+/// Unary(Negate, Var("tmp.0"), Var("tmp.1"))
+/// Binary(Add, Var("tmp.1"), Constant(1), Var("tmp.2"))
+/// Return(Constant(3))
+/// ```
+/// nothing reads the folded sub-expression, so none of it reaches the assembly.
+#[test]
+fn dead_subexpression_removed_after_folding() {
+    let tacky_prog = tacky::ProgramTacky {
+        items: vec![tacky::TopLevelTacky::Function {
+            fundef: tacky::FunDefTacky {
+                identifier: symbol::Symbol::intern("main"),
+                global: true,
+                params: vec![],
+                instructions: vec![
+                    tacky::InstructionTacky::Binary {
+                        op: parser::BinaryOp::Multiply,
+                        src1: tacky::ValTacky::int(7),
+                        src2: tacky::ValTacky::int(6),
+                        dst: tacky::ValTacky::tmp(0),
+                    },
+                    tacky::InstructionTacky::Unary {
+                        op: parser::UnaryOp::Negate,
+                        src: tacky::ValTacky::tmp(0),
+                        dst: tacky::ValTacky::tmp(1),
+                    },
+                    tacky::InstructionTacky::Binary {
+                        op: parser::BinaryOp::Add,
+                        src1: tacky::ValTacky::tmp(1),
+                        src2: tacky::ValTacky::int(1),
+                        dst: tacky::ValTacky::tmp(2),
+                    },
+                    tacky::InstructionTacky::Ret {
+                        v: tacky::ValTacky::int(3),
                     },
-                    asmgen::InstructionAsm::Ret
-                ]
-            })
+                ],
+            },
+        }],
+    };
+
+    let asm = format!(
+        "{}",
+        asmgen::gen_asm(
+            optimize::Pipeline::new(2, &[], &[]).optimize(tacky_prog, &mut Timings::default()),
+            asmgen::CodegenOptions::default()
+        )
+        .unwrap()
+    );
+    assert!(!asm.contains("imull"));
+    assert!(!asm.contains("negl"));
+    assert!(!asm.contains("addl"));
+    assert!(!asm.contains("subq"));
+    assert!(asm.contains("movl $3, %eax"));
+}
+
+/// copying a temporary onto itself lowers to a mem-to-mem round trip through %r10d,
+/// which the peephole pass must clean up.
+#[test]
+fn peephole_removes_self_moves_from_output() {
+    let self_copy = || tacky::ProgramTacky {
+        items: vec![tacky::TopLevelTacky::Function {
+            fundef: tacky::FunDefTacky {
+                identifier: symbol::Symbol::intern("main"),
+                global: true,
+                params: vec![],
+                instructions: vec![
+                    tacky::InstructionTacky::Unary {
+                        op: parser::UnaryOp::Negate,
+                        src: tacky::ValTacky::int(2),
+                        dst: tacky::ValTacky::tmp(0),
+                    },
+                    tacky::InstructionTacky::Copy {
+                        src: tacky::ValTacky::tmp(0),
+                        dst: tacky::ValTacky::tmp(0),
+                    },
+                    tacky::InstructionTacky::Ret {
+                        v: tacky::ValTacky::tmp(0),
+                    },
+                ],
+            },
+        }],
+    };
+
+    let unoptimized = format!(
+        "{}",
+        asmgen::gen_asm(self_copy(), asmgen::CodegenOptions::default()).unwrap()
+    );
+    assert!(unoptimized.contains("movl %r10d, -4(%rbp)"));
+
+    let text = format!(
+        "{}",
+        optimize::Pipeline::new(2, &[], &[]).optimize_asm(
+            asmgen::gen_asm(self_copy(), asmgen::CodegenOptions::default()).unwrap(),
+            &mut Timings::default()
+        )
+    );
+    assert!(!text.contains("movl %r10d, -4(%rbp)"));
+    for line in text.lines() {
+        if let Some(operands) = line.trim().strip_prefix("movl ") {
+            let (src, dst) = operands.split_once(", ").unwrap();
+            assert_ne!(src, dst, "self-move left in output: {}", line);
         }
+    }
+}
+
+/// ### TACKY (input):
+/// ```text
+/// JumpIfZero(Constant(0), "end")
+/// Unary(Negate, Constant(5), Var("tmp.0"))
+/// ...
+/// Return(Var("tmp.19"))
+/// Label("end")
+/// Return(Constant(7))
+/// ```
+/// the shape of `if (0) { huge body }`: the branch always skips the body,
+/// so none of it reaches the assembly.
+#[test]
+fn branch_on_constant_drops_skipped_body() {
+    let mut instructions = vec![tacky::InstructionTacky::JumpIfZero {
+        condition: tacky::ValTacky::int(0),
+        target: String::from("end"),
+    }];
+    instructions.push(tacky::InstructionTacky::Unary {
+        op: parser::UnaryOp::Negate,
+        src: tacky::ValTacky::int(5),
+        dst: tacky::ValTacky::tmp(0),
+    });
+    for no in 1..20 {
+        instructions.push(tacky::InstructionTacky::Binary {
+            op: parser::BinaryOp::Multiply,
+            src1: tacky::ValTacky::tmp(no - 1),
+            src2: tacky::ValTacky::int(3),
+            dst: tacky::ValTacky::tmp(no),
+        });
+    }
+    instructions.push(tacky::InstructionTacky::Ret {
+        v: tacky::ValTacky::tmp(19),
+    });
+    instructions.push(tacky::InstructionTacky::Label {
+        name: String::from("end"),
+    });
+    instructions.push(tacky::InstructionTacky::Ret {
+        v: tacky::ValTacky::int(7),
+    });
+    let tacky_prog = tacky::ProgramTacky {
+        items: vec![tacky::TopLevelTacky::Function {
+            fundef: tacky::FunDefTacky {
+                identifier: symbol::Symbol::intern("main"),
+                global: true,
+                params: vec![],
+                instructions,
+            },
+        }],
+    };
+
+    let asm = format!(
+        "{}",
+        asmgen::gen_asm(
+            optimize::Pipeline::new(2, &[], &[]).optimize(tacky_prog, &mut Timings::default()),
+            asmgen::CodegenOptions::default()
+        )
+        .unwrap()
+    );
+    assert!(!asm.contains("negl"));
+    assert!(!asm.contains("imull"));
+    assert!(!asm.contains("jmp"));
+    assert!(!asm.contains(".Lend"));
+    assert!(asm.contains("movl $7, %eax"));
+}
+
+/// with the frame pointer omitted, nothing in the output refers to %rbp.
+#[test]
+fn omit_frame_pointer_leaves_rbp_alone() {
+    let source = String::from("int main(void) { return (~7 * 3 - 100 / 7) % 9 ^ -2; }");
+    let asm = asmgen::gen_asm(
+        tacky::TackyEmitter::gen_tacky(
+            parser::parse(lexer::tokenize(source).expect("expected valid stream of tokens"))
+                .expect("expected valid parsing of tokens"),
+        ),
+        asmgen::CodegenOptions {
+            omit_frame_pointer: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let text = format!("{}", asm);
+    assert!(text.contains("(%rsp)"));
+    assert!(!text.contains("%rbp"));
+    assert!(!text.contains("%ebp"));
+}
+
+/// comments sit in front of the instructions for their TACKY instruction,
+/// and leave the instructions themselves exactly as they were.
+#[test]
+fn asm_comments_do_not_change_code() {
+    let gen = |asm_comments| {
+        let source = String::from("int main(void) { return (~7 * 3 - 100 / 8) % 9 ^ -2; }");
+        optimize::Pipeline::new(2, &[], &[]).optimize_asm(
+            asmgen::gen_asm(
+                tacky::TackyEmitter::gen_tacky(
+                    parser::parse(
+                        lexer::tokenize(source).expect("expected valid stream of tokens"),
+                    )
+                    .expect("expected valid parsing of tokens"),
+                ),
+                asmgen::CodegenOptions {
+                    asm_comments,
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+            &mut Timings::default(),
+        )
+    };
+    let plain = gen(false);
+    let commented = gen(true);
+
+    let text = format!("{}", commented);
+    assert!(text.contains("\t# tmp.0 = ~7\n\tmovl $7, -4(%rbp)\n\tnotl -4(%rbp)"));
+    assert!(text.contains("\t# ret tmp.6\n"));
+    assert!(!format!("{}", plain).contains('#'));
+
+    let without_comments: Vec<asmgen::InstructionAsm> = commented
+        .functions()
+        .flat_map(|f| f.instructions.iter().cloned())
+        .filter(|i| !matches!(i, asmgen::InstructionAsm::Comment { text: _ }))
+        .collect();
+    assert_eq!(
+        without_comments,
+        plain.functions().next().unwrap().instructions
+    );
+}
+
+/// each function in a file gets its own label and a frame sized for its own temporaries.
+#[test]
+fn two_functions_get_their_own_frames() {
+    let source =
+        String::from("int helper(void) { return -(~1 * 5); } int main(void) { return 2; }");
+    let asm = asmgen::gen_asm(
+        tacky::TackyEmitter::gen_tacky(
+            parser::parse(lexer::tokenize(source).expect("expected valid stream of tokens"))
+                .expect("expected valid parsing of tokens"),
+        ),
+        asmgen::CodegenOptions::default(),
+    )
+    .unwrap();
+    let names: Vec<String> = asm.functions().map(|f| f.identifier.to_string()).collect();
+    assert_eq!(names, vec!["helper", "main"]);
+
+    let text = format!("{}", asm);
+    let (helper, main) = text.split_once("\t.globl main\n").unwrap();
+    assert!(helper.contains("\t.globl helper\n"));
+    assert!(helper.contains("helper:\n"));
+    assert!(helper.contains("subq $16, %rsp"));
+    assert!(main.starts_with("\t.type main, @function\nmain:\n"));
+    assert!(!main.contains("subq"));
+    assert_eq!(text.matches(".note.GNU-stack").count(), 1);
+}
+
+/// with a section per function, `--gc-sections` drops the function main never uses.
+#[test]
+fn function_sections_let_linker_drop_unused() {
+    let source = String::from("int unused(void) { return 4; } int main(void) { return 2; }");
+    let asm = asmgen::gen_asm(
+        tacky::TackyEmitter::gen_tacky(
+            parser::parse(lexer::tokenize(source).expect("expected valid stream of tokens"))
+                .expect("expected valid parsing of tokens"),
+        ),
+        asmgen::CodegenOptions::default(),
+    )
+    .unwrap();
+    let formatter = emit::AsmFormatter {
+        function_sections: true,
+        ..Default::default()
+    };
+    let text = formatter.program(&asm);
+    assert!(text.starts_with("\t.section .text.unused,\"ax\",@progbits\n"));
+    assert!(text.contains("\t.section .text.main,\"ax\",@progbits\n"));
+    assert!(!text.contains("\t.text\n"));
+    assert_eq!(text.matches(".note.GNU-stack").count(), 1);
+
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("sections.s");
+    let binary = dir.path().join("sections");
+    std::fs::write(&source, text).unwrap();
+    let linked = std::process::Command::new("gcc")
+        .arg(&source)
+        .arg("-Wl,--gc-sections")
+        .arg("-o")
+        .arg(&binary)
+        .status()
+        .unwrap();
+    assert!(linked.success());
+    let symbols = std::process::Command::new("nm")
+        .arg(&binary)
+        .output()
+        .unwrap();
+    let symbols = String::from_utf8(symbols.stdout).unwrap();
+    assert!(symbols.contains(" T main"));
+    assert!(!symbols.contains("unused"));
+}
+
+/// `--cf-protection` objects advertise IBT and SHSTK and still link and run,
+/// whether they come from the assembler or straight from the encoder.
+#[test]
+fn cf_protection_marks_binary_ibt_compatible() {
+    let source = String::from("int main(void) { return 2; }");
+    let asm = asmgen::gen_asm(
+        tacky::TackyEmitter::gen_tacky(
+            parser::parse(lexer::tokenize(source).expect("expected valid stream of tokens"))
+                .expect("expected valid parsing of tokens"),
+        ),
+        asmgen::CodegenOptions::default(),
+    )
+    .unwrap();
+    let formatter = emit::AsmFormatter {
+        cf_protection: true,
+        ..Default::default()
+    };
+    let text = formatter.program(&asm);
+    assert!(text.contains("main:\n\t.cfi_startproc\n\tendbr64\n"));
+    assert_eq!(text.matches(".note.gnu.property").count(), 1);
+
+    let dir = tempfile::tempdir().unwrap();
+    let assembled = dir.path().join("cet.s");
+    std::fs::write(&assembled, text).unwrap();
+    let encoded = dir.path().join("cet.o");
+    emit::emit_object(
+        asm,
+        encoded.to_str().unwrap().to_owned(),
+        Default::default(),
+        false,
+        true,
+    )
+    .unwrap();
+    let object = dir.path().join("cet_as.o");
+    let assembled_ok = std::process::Command::new("gcc")
+        .arg("-c")
+        .arg(&assembled)
+        .arg("-o")
+        .arg(&object)
+        .status()
+        .unwrap();
+    assert!(assembled_ok.success());
+    // both frame descriptions start at the landing pad
+    let frames = |input: &std::path::Path| {
+        let readelf = std::process::Command::new("readelf")
+            .arg("--debug-dump=frames")
+            .arg(input)
+            .output()
+            .unwrap();
+        String::from_utf8(readelf.stdout).unwrap()
+    };
+    assert_eq!(frames(&encoded), frames(&object));
+    // the linker ANDs the property with the C runtime's objects, so check it before linking
+    for (input, binary) in [(object, "cet_as"), (encoded, "cet_obj")] {
+        let notes = std::process::Command::new("readelf")
+            .arg("-n")
+            .arg(&input)
+            .output()
+            .unwrap();
+        let notes = String::from_utf8(notes.stdout).unwrap();
+        assert!(notes.contains("x86 feature: IBT, SHSTK"), "{}", notes);
+        let binary = dir.path().join(binary);
+        let linked = std::process::Command::new("gcc")
+            .arg(&input)
+            .arg("-o")
+            .arg(&binary)
+            .status()
+            .unwrap();
+        assert!(linked.success());
+        let ran = std::process::Command::new(&binary).status().unwrap();
+        assert_eq!(ran.code(), Some(2));
+    }
+}
+
+/// the encoder puts variables in the same sections with the same symbols as the
+/// assembler, and its references to them are relocated to the same place.
+#[test]
+fn object_file_places_static_variables() {
+    let source = "int total = 3; static int calls; int count(int n) { static int last = -1; \
+                  calls = calls + 1; last = n; total = total + n; return calls * 10 + last; } \
+                  int main(void) { return count(4) + total; }";
+    let asm = asmgen::gen_asm(
+        tacky::TackyEmitter::gen_tacky(
+            semantic::analyze(
+                parser::parse(lexer::tokenize(String::from(source)).unwrap()).unwrap(),
+            )
+            .unwrap(),
+        ),
+        asmgen::CodegenOptions::default(),
+    )
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let assembled = dir.path().join("statics.s");
+    std::fs::write(&assembled, emit::AsmFormatter::default().program(&asm)).unwrap();
+    let encoded = dir.path().join("statics.o");
+    emit::emit_object(
+        asm,
+        encoded.to_str().unwrap().to_owned(),
+        Default::default(),
+        false,
+        false,
+    )
+    .unwrap();
+    let object = dir.path().join("statics_as.o");
+    let assembled_ok = std::process::Command::new("gcc")
+        .arg("-c")
+        .arg(&assembled)
+        .arg("-o")
+        .arg(&object)
+        .status()
+        .unwrap();
+    assert!(assembled_ok.success());
+    let symbols = |input: &std::path::Path| {
+        let nm = std::process::Command::new("nm")
+            .arg(input)
+            .output()
+            .unwrap();
+        String::from_utf8(nm.stdout).unwrap()
+    };
+    assert_eq!(symbols(&encoded), symbols(&object));
+    assert!(symbols(&encoded).contains(" D total\n"));
+    assert!(symbols(&encoded).contains(" b calls\n"));
+    assert!(symbols(&encoded).contains(" d last.1\n"));
+    for (input, binary) in [(object, "statics_as"), (encoded, "statics_obj")] {
+        let binary = dir.path().join(binary);
+        let linked = std::process::Command::new("gcc")
+            .arg(&input)
+            .arg("-o")
+            .arg(&binary)
+            .status()
+            .unwrap();
+        assert!(linked.success());
+        let ran = std::process::Command::new(&binary).status().unwrap();
+        assert_eq!(ran.code(), Some(21));
+    }
+}
+
+/// string literals and `double` constants stay out of the symbol table, from
+/// the assembler and the encoder alike, and are still found through their
+/// section, including from a pointer initialized to one.
+#[test]
+fn object_file_keeps_constants_local() {
+    let source = "char *greeting = \"hi\"; double half(void) { return 0.5; } \
+                  int main(void) { char *s = \"ok\"; return s[0] - greeting[1] + (int)(half() * 4.0); }";
+    let asm = asmgen::gen_asm(
+        tacky::TackyEmitter::gen_tacky(
+            semantic::analyze(
+                parser::parse(lexer::tokenize(String::from(source)).unwrap()).unwrap(),
+            )
+            .unwrap(),
+        ),
+        asmgen::CodegenOptions::default(),
+    )
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let assembled = dir.path().join("constants.s");
+    std::fs::write(&assembled, emit::AsmFormatter::default().program(&asm)).unwrap();
+    let encoded = dir.path().join("constants.o");
+    emit::emit_object(
+        asm,
+        encoded.to_str().unwrap().to_owned(),
+        Default::default(),
+        false,
+        false,
     )
+    .unwrap();
+    let object = dir.path().join("constants_as.o");
+    let assembled_ok = std::process::Command::new("gcc")
+        .arg("-c")
+        .arg(&assembled)
+        .arg("-o")
+        .arg(&object)
+        .status()
+        .unwrap();
+    assert!(assembled_ok.success());
+    let symbols = |input: &std::path::Path| {
+        let nm = std::process::Command::new("nm")
+            .arg(input)
+            .output()
+            .unwrap();
+        // functions are laid out differently, so leave out the addresses
+        String::from_utf8(nm.stdout)
+            .unwrap()
+            .lines()
+            .map(|l| l.split_once(' ').unwrap().1.to_owned())
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    assert_eq!(symbols(&encoded), symbols(&object));
+    assert!(!symbols(&encoded).contains("string"));
+    assert!(!symbols(&encoded).contains("double"));
+    for (input, binary) in [(object, "constants_as"), (encoded, "constants_obj")] {
+        let binary = dir.path().join(binary);
+        let linked = std::process::Command::new("gcc")
+            .arg(&input)
+            .arg("-o")
+            .arg(&binary)
+            .status()
+            .unwrap();
+        assert!(linked.success());
+        let ran = std::process::Command::new(&binary).status().unwrap();
+        assert_eq!(ran.code(), Some(8));
+    }
+}
+
+/// every Windows function gets unwind information the unwinder can follow
+/// through its frame, whatever the body pushes for its calls.
+/// Skipped when `llvm-mc` or `llvm-readobj` isn't installed.
+#[test]
+fn windows_functions_unwind() {
+    let installed = |tool| {
+        std::process::Command::new(tool)
+            .arg("--version")
+            .output()
+            .is_ok()
+    };
+    if !installed("llvm-mc") || !installed("llvm-readobj") {
+        return;
+    }
+    let source = "int f(int a, int b, int c, int d, int e, int f); \
+                  int big(int n) { int a[2000]; a[n] = n; return a[n / 2]; } \
+                  int calls(int n) { return f(n, n, n, n, n, n) + f(1, 2, 3, 4, 5, 6); }";
+    let asm = asmgen::gen_asm(
+        tacky::TackyEmitter::gen_tacky(
+            semantic::analyze(
+                parser::parse(lexer::tokenize(String::from(source)).unwrap()).unwrap(),
+            )
+            .unwrap(),
+        ),
+        asmgen::CodegenOptions {
+            target: compiler::target::Target::Windows,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let assembly = dir.path().join("unwind.s");
+    let object = dir.path().join("unwind.obj");
+    std::fs::write(
+        &assembly,
+        emit::AsmFormatter::new(emit::Syntax::Att, compiler::target::Target::Windows).program(&asm),
+    )
+    .unwrap();
+    let assembled = std::process::Command::new("llvm-mc")
+        .args(["-triple=x86_64-pc-windows-msvc", "-filetype=obj", "-o"])
+        .arg(&object)
+        .arg(&assembly)
+        .status()
+        .unwrap();
+    assert!(assembled.success());
+    let readobj = std::process::Command::new("llvm-readobj")
+        .arg("--unwind")
+        .arg(&object)
+        .output()
+        .unwrap();
+    let unwind = String::from_utf8(readobj.stdout).unwrap();
+    assert_eq!(unwind.matches("RuntimeFunction {").count(), 2, "{}", unwind);
+    assert_eq!(
+        unwind.matches("FrameRegister: RBP").count(),
+        2,
+        "{}",
+        unwind
+    );
+    // the big frame is allocated a page at a time
+    assert!(unwind.contains("ALLOC_LARGE size=4096"), "{}", unwind);
+    assert_eq!(unwind.matches("SET_FPREG reg=RBP, offset=0x0").count(), 2);
+    assert_eq!(unwind.matches("PUSH_NONVOL reg=RBP").count(), 2);
+}
+
+fn parse_diagnostic(source: &str) -> String {
+    let errors = parser::parse(lexer::tokenize(String::from(source)).unwrap()).unwrap_err();
+    let lines = diagnostics::LineIndex::new(source, "main.c");
+    errors
+        .iter()
+        .map(|e| {
+            diagnostics::Diagnostic {
+                notes: e.note().into_iter().collect(),
+                ..diagnostics::Diagnostic::error(e.code(), e.to_string(), e.span())
+            }
+            .render(&lines, false)
+        })
+        .collect()
+}
+
+/// an unclosed parenthesis is reported where the `)` was due, and noted where it opened.
+#[test]
+fn unclosed_parens_across_lines_diagnostic() {
+    assert_eq!(
+        parse_diagnostic("int main(void) {\n  return (1 +\n     2;\n}\n"),
+        "error: expected ')' before ';'
+ --> main.c:3:7
+  |
+3 |      2;
+  |       ^
+note: to match this '('
+ --> main.c:2:10
+  |
+2 |   return (1 +
+  |          ^
+"
+    );
+}
+
+/// running out of input points just past the last token.
+#[test]
+fn end_of_input_diagnostic() {
+    assert_eq!(
+        parse_diagnostic("int main(void) {\n  return 2;\n\n"),
+        "error: expected '}' at end of input
+ --> main.c:2:12
+  |
+2 |   return 2;
+  |            ^
+note: to match this '{'
+ --> main.c:1:16
+  |
+1 | int main(void) {
+  |                ^
+"
+    );
+    assert_eq!(
+        parse_diagnostic("int main(void) {\n  return 2\n}"),
+        "error: expected ';' after expression
+ --> main.c:2:11
+  |
+2 |   return 2
+  |           ^
+"
+    );
+}
+
+/// recovery reports each independent error once, in source order, and the
+/// first diagnostic is the same one the parser gave before it recovered.
+#[test]
+fn independent_errors_each_reported() {
+    let source = "int main(void) {\n  return 1 + ;\n}\n\n\
+                  int two(void) {\n  return (2 * 3;\n}\n\n\
+                  int three(void) {\n  return 3 4;\n}\n";
+    let rendered = parse_diagnostic(source);
+    let headers: Vec<&str> = rendered
+        .lines()
+        .filter(|l| l.starts_with("error:"))
+        .collect();
+    assert_eq!(
+        headers,
+        vec![
+            "error: expected an expression before ';'",
+            "error: expected ')' before ';'",
+            "error: expected ';' after expression",
+        ]
+    );
+    assert!(rendered.contains(" --> main.c:2:14\n"));
+    assert!(rendered.contains(" --> main.c:6:16\n"));
+    assert!(rendered.contains(" --> main.c:10:11\n"));
+}
+
+/// errors that only follow from one already reported, before any token parses
+/// after recovering, are left out.
+#[test]
+fn cascading_errors_suppressed() {
+    for source in [
+        "int main(void) { return 1 2 3 4; }",
+        "int main(void) { return ~; } int f(void) { return 1; }",
+        "int main(void) { return 1 return 2; }",
+        "int main(void) { return (1;",
+        "int main(void { return 2; } int f(void) { return 3; }",
+    ] {
+        let errors = parser::parse(lexer::tokenize(String::from(source)).unwrap()).unwrap_err();
+        assert_eq!(errors.len(), 1, "{}: {:?}", source, errors);
+    }
+}
+
+/// the parser pulls tokens from the lexer, so a lexer error cuts the input short;
+/// it is the error reported, over those the parser found.
+#[test]
+fn lex_error_found_while_parsing() {
+    let source = "int main(void) { return 1 / 0 + ; }\nint f(void) { return 1 @ 2; }\n";
+    match compiler::run_parser(source.as_bytes()) {
+        Err(compiler::CompileError::Lex { e }) => {
+            assert_eq!(e.span(), lexer::Span { start: 59, end: 60 })
+        }
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+}
+
+/// runs `source` through every stage, optimized or not, stopping at the first error.
+/// Only returning is checked: no input may make a stage panic.
+fn compile_without_panic(source: String, optimized: bool) {
+    let Ok(tokens) = lexer::tokenize(source) else {
+        return;
+    };
+    let Ok(c_ast) = parser::parse(tokens) else {
+        return;
+    };
+    let mut tacky = tacky::TackyEmitter::gen_tacky(c_ast);
+    if optimized {
+        tacky = optimize::Pipeline::new(2, &[], &[]).optimize(tacky, &mut Timings::default());
+    }
+    let options = asmgen::CodegenOptions {
+        red_zone: optimized,
+        cmov: optimized,
+        ..Default::default()
+    };
+    let Ok(mut asm) = asmgen::gen_asm(tacky, options) else {
+        return;
+    };
+    if optimized {
+        asm = optimize::Pipeline::new(2, &[], &[]).optimize_asm(asm, &mut Timings::default());
+    }
+    let _ = format!("{}", asm);
+    for fundef in asm.functions() {
+        encode::encode_instructions(&fundef.instructions).unwrap();
+    }
+}
+
+/// garbage bytes, and garbage made of real tokens, are errors rather than panics.
+#[test]
+fn garbage_does_not_panic() {
+    const PIECES: [&str; 31] = [
+        "int",
+        "void",
+        "struct",
+        "return",
+        "main",
+        "f",
+        "(",
+        ")",
+        "{",
+        "}",
+        ";",
+        "-",
+        "--",
+        "~",
+        "+",
+        "*",
+        "/",
+        "%",
+        "&",
+        "|",
+        "^",
+        "[",
+        "]",
+        // a member name right after the dot, so no double constants are made
+        ".f",
+        "->",
+        "0",
+        "7",
+        "2147483647",
+        "\"ab\"",
+        "'c'",
+        " ",
+    ];
+    // a fixed linear congruential generator keeps failures reproducible
+    let mut state: u64 = 0x2545F4914F6CDD1D;
+    let mut next = move || {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) as usize
+    };
+
+    for _ in 0..2000 {
+        let len = next() % 64;
+        let bytes: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+        compile_without_panic(String::from_utf8_lossy(&bytes).into_owned(), false);
+
+        let soup: String = (0..len).map(|_| PIECES[next() % PIECES.len()]).collect();
+        compile_without_panic(soup.clone(), false);
+        compile_without_panic(soup, true);
+    }
+
+    // a valid prefix gets the soup past the function header
+    for _ in 0..2000 {
+        let len = next() % 16;
+        let body: String = (0..len).map(|_| PIECES[11 + next() % 20]).collect();
+        let source = format!("int main(void) {{ return {}; }}", body);
+        compile_without_panic(source.clone(), false);
+        compile_without_panic(source, true);
+    }
+}
+
+/// AST nodes carry the spans of the source they were parsed from, parentheses
+/// included, which the line index turns back into lines and columns.
+#[test]
+fn ast_spans_locate_source() {
+    let source = "int main(void) {\n  return -(1 + 2) * 3;\n}\n";
+    let prog = parser::parse(lexer::tokenize(String::from(source)).unwrap()).unwrap();
+    let lines = diagnostics::LineIndex::new(source, "main.c");
+    let text = |span: lexer::Span| &source[span.start..span.end];
+    let fundef = prog.functions().next().unwrap();
+    assert_eq!(text(fundef.span), source.trim_end());
+    assert_eq!(text(fundef.body.span), "{\n  return -(1 + 2) * 3;\n}");
+    let [parser::BlockItem::Statement {
+        stmt: parser::StatementC::Return { exp, span },
+    }] = fundef.body.items.as_slice()
+    else {
+        unreachable!()
+    };
+    assert_eq!(text(*span), "return -(1 + 2) * 3;");
+    assert_eq!(text(exp.span()), "-(1 + 2) * 3");
+    let parser::Exp::Binary { l_exp, r_exp, .. } = exp.as_ref() else {
+        unreachable!()
+    };
+    assert_eq!(text(l_exp.span()), "-(1 + 2)");
+    assert_eq!(text(r_exp.span()), "3");
+    let parser::Exp::Unary { exp: inner, .. } = l_exp.as_ref() else {
+        unreachable!()
+    };
+    assert_eq!(text(inner.span()), "(1 + 2)");
+    let loc = lines.locate(inner.span().start);
+    assert_eq!((loc.line, loc.col), (2, 11));
+    let loc = lines.locate(r_exp.span().start);
+    assert_eq!((loc.line, loc.col), (2, 21));
+}
+
+/// the library's stage functions, run one after another, give the assembly
+/// `compile_to_assembly` does, and its errors are the stages' own.
+#[test]
+fn library_stages_match_compile_to_assembly() {
+    let source = "int two(void) { return 2; } int main(void) { return ~(3 * 4) % 5; }";
+    let options = compiler::CompileOptions {
+        syntax: emit::Syntax::Intel,
+        ..Default::default()
+    };
+    let tokens = crate::lex(source).unwrap();
+    let tacky = crate::gen_tacky(crate::parse(tokens).unwrap());
+    let asm = crate::gen_asm(tacky, Default::default()).unwrap();
+    let formatter = crate::AsmFormatter::new(emit::Syntax::Intel, options.target);
+    assert_eq!(
+        compiler::compile_to_assembly(source, &options).unwrap(),
+        formatter.program(&asm)
+    );
+
+    assert!(matches!(
+        compiler::compile_to_assembly("int main(void) { return @; }", &options),
+        Err(compiler::CompileError::Lex { .. })
+    ));
+    assert!(matches!(
+        compiler::compile_to_assembly("int main(void) { return 1 }", &options),
+        Err(compiler::CompileError::Parse { .. })
+    ));
+}
+
+/// source that hasn't been preprocessed can still have comments, which
+/// compile to nothing.
+#[test]
+fn comments_need_no_preprocessor() {
+    let options = compiler::CompileOptions::default();
+    let commented = "// two\nint two(void) { return /* not 3 */ 2; }\n/*\n * main\n */\nint main(void) { return two(); }";
+    assert_eq!(
+        compiler::compile_to_assembly(commented, &options).unwrap(),
+        compiler::compile_to_assembly(
+            "int two(void) { return 2; } int main(void) { return two(); }",
+            &options
+        )
+        .unwrap()
+    );
+    assert!(matches!(
+        compiler::compile_to_assembly("int main(void) { return 2; } /* end", &options),
+        Err(compiler::CompileError::Lex { .. })
+    ));
+}
+
+/// the assembly is the same however many threads translate the functions, and
+/// keeps them in source order.
+#[test]
+fn codegen_jobs_are_deterministic() {
+    let source: String = (0..64)
+        .map(|i| {
+            format!(
+                "int f{}(void) {{ return ({} * 7 - {}) % 5 ^ ~{}; }}\n",
+                i, i, i, i
+            )
+        })
+        .collect();
+    let compile = |jobs| {
+        let options = compiler::CompileOptions {
+            jobs,
+            optimize: 1,
+            ..Default::default()
+        };
+        compiler::compile_to_assembly(&source, &options).unwrap()
+    };
+    let sequential = compile(1);
+    let order: Vec<usize> = (0..64)
+        .map(|i| sequential.find(&format!("f{}:", i)).unwrap())
+        .collect();
+    assert!(order.windows(2).all(|w| w[0] < w[1]));
+    for jobs in [2, 8, 2, 8] {
+        assert_eq!(compile(jobs), sequential, "--jobs {}", jobs);
+    }
+}
+
+/// the IRs come back from JSON equal to what went in, so the serde derives
+/// cover every node the programs reach.
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trips() {
+    fn round_trip<T>(ir: &T)
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let json = serde_json::to_string(ir).unwrap();
+        assert_eq!(&serde_json::from_str::<T>(&json).unwrap(), ir, "{}", json);
+    }
+
+    for source in [
+        BASIC_RETURN_FROM_MAIN,
+        "int main(void) { return ~-(1 + 2) * 3 / 4 % 5; }",
+        "int f(void) { return 6 & 3 | 8 ^ 1; } int main(void) { return -2147483647 - 1; }",
+    ] {
+        let tokens = lexer::tokenize(String::from(source)).unwrap();
+        let c_ast = parser::parse(tokens.clone()).unwrap();
+        round_trip(&tokens);
+        round_trip(&c_ast);
+        for level in 0..=2 {
+            let tacky = tacky::gen_tacky(parser::parse(tokens.clone()).unwrap());
+            let tacky =
+                optimize::Pipeline::new(level, &[], &[]).optimize(tacky, &mut Timings::default());
+            round_trip(&tacky);
+            let options = asmgen::CodegenOptions {
+                red_zone: level >= 1,
+                cmov: level >= 1,
+                ..Default::default()
+            };
+            let asm = asmgen::gen_asm(tacky, options).unwrap();
+            round_trip(
+                &optimize::Pipeline::new(level, &[], &[])
+                    .optimize_asm(asm, &mut Timings::default()),
+            );
+        }
+    }
 }
@@ -0,0 +1,283 @@
+//! System V ABI interoperability: functions crumb compiles, called from code
+//! gcc compiles. `tests/abi/callee.c` is compiled by crumb with `-c`, under
+//! each set of flags that changes the prologue, the epilogue or the object
+//! writer, and linked with `tests/abi/caller.c`, which calls every function
+//! and checks what it returns and that the callee-saved registers and %rsp
+//! survive the call. The caller prints each failure and exits with their count.
+//!
+//! Unwinding through crumb's frames is checked the same way: `tests/abi/trap.c`
+//! traps inside crumb code, and `tests/abi/unwind.c` walks the stack from its
+//! signal handler, whether the system assembler or crumb itself wrote the
+//! frame descriptions.
+//!
+//! Arguments cross in both directions through `tests/abi/arguments.c`, which
+//! crumb compiles, and `tests/abi/arguments_main.c`, which calls its functions
+//! and is called back by them. Its assembly helpers check that %rsp is aligned
+//! at each of crumb's calls, and clobber the caller-saved registers under it.
+//! Each side defines a variable there that the other reads and writes, and
+//! crumb finds the members of structures gcc lays out, and their sizes.
+//! The same functions are built into a shared library with `-fpic` too, where
+//! crumb reaches its own variable through the GOT, like gcc's side does.
+//!
+//! Debuggers find crumb's lines through the line table the assembler builds
+//! from its `.loc` directives under `-g`, which readelf decodes.
+use assert_cmd::Command;
+use std::{fs, path::Path, process};
+use tempfile::TempDir;
+
+const FLAG_SETS: [&[&str]; 6] = [
+    &[],
+    &["-O1"],
+    &["-O2"],
+    &["--omit-frame-pointer"],
+    &["--emit", "obj"],
+    &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+];
+
+#[test]
+fn gcc_calls_crumb() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/abi");
+    let tmpdir = TempDir::new().unwrap();
+    let object = tmpdir.path().join("callee.o");
+    let executable = tmpdir.path().join("abi");
+    for flags in FLAG_SETS {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg(fixture.join("callee.c"))
+            .args(["-c", "-o"])
+            .arg(&object)
+            .args(flags)
+            .assert()
+            .success();
+        for opt in ["-O0", "-O2"] {
+            Command::new("gcc")
+                .arg(opt)
+                .arg(fixture.join("caller.c"))
+                .arg(&object)
+                .arg("-o")
+                .arg(&executable)
+                .assert()
+                .success();
+            let output = Command::new(&executable).output().unwrap();
+            assert!(
+                output.status.success(),
+                "crumb {:?}, gcc {}: {}",
+                flags,
+                opt,
+                String::from_utf8_lossy(&output.stdout)
+            );
+        }
+    }
+}
+
+#[test]
+fn arguments_cross_both_ways() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/abi");
+    let tmpdir = TempDir::new().unwrap();
+    let object = tmpdir.path().join("arguments.o");
+    let executable = tmpdir.path().join("arguments");
+    for flags in FLAG_SETS {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg(fixture.join("arguments.c"))
+            .args(["-c", "-o"])
+            .arg(&object)
+            .args(flags)
+            .assert()
+            .success();
+        for opt in ["-O0", "-O2"] {
+            Command::new("gcc")
+                .arg(opt)
+                .arg(fixture.join("arguments_main.c"))
+                .arg(&object)
+                .arg("-o")
+                .arg(&executable)
+                .assert()
+                .success();
+            let output = Command::new(&executable).output().unwrap();
+            assert!(
+                output.status.success(),
+                "crumb {:?}, gcc {}: {}",
+                flags,
+                opt,
+                String::from_utf8_lossy(&output.stdout)
+            );
+        }
+    }
+}
+
+/// flag sets that build code for a shared library.
+const PIC_FLAG_SETS: [&[&str]; 3] = [&["-fpic"], &["-fpic", "-O2"], &["-fpic", "--emit", "obj"]];
+
+/// the executable gets its own copy of the variable crumb defines, which the
+/// library only sees if it goes through the GOT.
+#[test]
+fn arguments_cross_into_shared_library() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/abi");
+    let tmpdir = TempDir::new().unwrap();
+    let object = tmpdir.path().join("arguments.o");
+    let library = tmpdir.path().join("libarguments.so");
+    let executable = tmpdir.path().join("arguments");
+    for flags in PIC_FLAG_SETS {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg(fixture.join("arguments.c"))
+            .args(["-c", "-o"])
+            .arg(&object)
+            .args(flags)
+            .assert()
+            .success();
+        Command::new("gcc")
+            .arg("-shared")
+            .arg(&object)
+            .arg("-o")
+            .arg(&library)
+            .assert()
+            .success();
+        Command::new("gcc")
+            .arg(fixture.join("arguments_main.c"))
+            .arg(&library)
+            .arg(format!("-Wl,-rpath,{}", tmpdir.path().display()))
+            .arg("-o")
+            .arg(&executable)
+            .assert()
+            .success();
+        let output = Command::new(&executable).output().unwrap();
+        assert!(
+            output.status.success(),
+            "crumb {:?}: {}",
+            flags,
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
+
+/// flag sets that change the frame the unwinder has to describe.
+const FRAME_FLAG_SETS: [&[&str]; 7] = [
+    &[],
+    &["-O2"],
+    &["--omit-frame-pointer"],
+    &["-O2", "--omit-frame-pointer"],
+    &["--emit", "obj"],
+    &["-O2", "--emit", "obj"],
+    &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+];
+
+#[test]
+fn backtrace_through_crumb() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/abi");
+    let tmpdir = TempDir::new().unwrap();
+    let object = tmpdir.path().join("trap.o");
+    let executable = tmpdir.path().join("unwind");
+    for flags in FRAME_FLAG_SETS {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg(fixture.join("trap.c"))
+            .args(["-c", "-o"])
+            .arg(&object)
+            .args(flags)
+            .assert()
+            .success();
+        for opt in ["-O0", "-O2"] {
+            Command::new("gcc")
+                .args([opt, "-rdynamic"])
+                .arg(fixture.join("unwind.c"))
+                .arg(&object)
+                .arg("-o")
+                .arg(&executable)
+                .assert()
+                .success();
+            let output = Command::new(&executable).output().unwrap();
+            assert!(
+                output.status.success(),
+                "crumb {:?}, gcc {}: {}",
+                flags,
+                opt,
+                String::from_utf8_lossy(&output.stdout)
+            );
+        }
+    }
+}
+
+/// there is one frame description entry for every function.
+#[test]
+fn frame_description_per_function() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/abi/callee.c");
+    let functions = fs::read_to_string(&source)
+        .unwrap()
+        .lines()
+        .filter(|l| l.starts_with("int "))
+        .count();
+    let tmpdir = TempDir::new().unwrap();
+    let object = tmpdir.path().join("callee.o");
+    for flags in FRAME_FLAG_SETS {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg(&source)
+            .args(["-c", "-o"])
+            .arg(&object)
+            .args(flags)
+            .assert()
+            .success();
+        let frames = process::Command::new("readelf")
+            .arg("--debug-dump=frames")
+            .arg(&object)
+            .output()
+            .unwrap();
+        let fdes = String::from_utf8_lossy(&frames.stdout)
+            .lines()
+            .filter(|l| l.contains(" FDE "))
+            .count();
+        assert_eq!(fdes, functions, "crumb {:?}", flags);
+    }
+}
+
+/// under `-g`, each function starts at the line it is declared on and each
+/// statement at its own, whatever the optimizer did; object files crumb writes
+/// itself can't carry the table, so it refuses to write one.
+#[test]
+fn line_table_per_statement() {
+    let tmpdir = TempDir::new().unwrap();
+    let source = tmpdir.path().join("lines.c");
+    fs::write(
+        &source,
+        "int twice(int x) {\n    int y = x * 2;\n    return y;\n}\n\n\
+         int main(void) {\n    int a = twice(3);\n    return a;\n}\n",
+    )
+    .unwrap();
+    let object = tmpdir.path().join("lines.o");
+    for flags in [&[][..], &["-O2"], &["--syntax", "intel"]] {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg(&source)
+            .args(["-g", "-c", "-o"])
+            .arg(&object)
+            .args(flags)
+            .assert()
+            .success();
+        let table = process::Command::new("readelf")
+            .arg("--debug-dump=decodedline")
+            .arg(&object)
+            .output()
+            .unwrap();
+        let table = String::from_utf8_lossy(&table.stdout);
+        let lines: Vec<&str> = table
+            .lines()
+            .filter_map(|l| match l.split_whitespace().collect::<Vec<_>>()[..] {
+                ["lines.c", line, ..] if line != "-" => Some(line),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines, ["1", "2", "3", "6", "7", "8"], "crumb {:?}", flags);
+    }
+    let refused = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg(&source)
+        .args(["-g", "-c", "--emit", "obj", "-o"])
+        .arg(&object)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&refused.stderr);
+    assert_eq!(refused.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("-g"), "{}", stderr);
+}
@@ -0,0 +1,91 @@
+// Compiled by crumb, and linked with arguments_main.c, which gcc compiles.
+// gcc code passes arguments to these, and they pass arguments back to it.
+// Each side also defines a variable the other reads and writes.
+// misaligned_<n> is one function under a name for each number of arguments.
+int gcc_weigh(int a, int b, int c, int d, int e, int f, int g, int h);
+int gcc_narrow(char a, unsigned char b, short c, unsigned short d, signed char e, char f, short g,
+               unsigned char h);
+int misaligned_0(void);
+int misaligned_1(int a);
+int misaligned_7(int a, int b, int c, int d, int e, int f, int g);
+int misaligned_8(int a, int b, int c, int d, int e, int f, int g, int h);
+int clobber(int a, int b, int c, int d, int e, int f);
+extern int gcc_step;
+
+int crumb_total = 40;
+
+int weigh(int a, int b, int c, int d, int e, int f, int g, int h) {
+    return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h;
+}
+
+int weigh_in_gcc(int x) {
+    return gcc_weigh(x, x + 1, x + 2, x + 3, x + 4, x + 5, x + 6, x + 7);
+}
+
+short narrow(char a, unsigned char b, short c, unsigned short d, signed char e, char f, short g,
+             unsigned char h) {
+    return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h;
+}
+
+int narrow_in_gcc(int x) {
+    return gcc_narrow(x, x, x, x, -x, x, -x, x);
+}
+
+int misalignments(int a) {
+    return misaligned_0() + misaligned_7(a, 2, 3, 4, 5, 6, 7) + misaligned_8(a, 2, 3, 4, 5, 6, 7, 8);
+}
+
+int misalignments_with_more_slots(int a, int b, int c) {
+    int d = a * b - c;
+    return (misaligned_7(a, b, c, d, 5, 6, 7) + d * 0) * (a + b * c) + misaligned_1(d);
+}
+
+int survives_clobber(int a, int b) {
+    int product = a * b;
+    return clobber(a, b, 3, 4, 5, 6) + product - a + b;
+}
+
+int step_total(void) {
+    crumb_total = crumb_total + gcc_step;
+    gcc_step = gcc_step * 2;
+    return crumb_total;
+}
+
+// structures gcc lays out too: an array member is aligned like its element,
+// not to 16 as an array variable is, and so is an array of structures.
+struct with_array {
+    int a;
+    int arr[4];
+};
+
+struct padded {
+    char c;
+    double d;
+};
+
+struct nested {
+    struct padded items[5];
+    char tail;
+};
+
+long with_array_offset(struct with_array *s) {
+    return (char *)s->arr - (char *)s;
+}
+
+long with_array_size(struct with_array *s) {
+    return (char *)(s + 1) - (char *)s;
+}
+
+long nested_tail_offset(struct nested *n) {
+    return &n->tail - (char *)n;
+}
+
+long nested_size(struct nested *n) {
+    return (char *)(n + 1) - (char *)n;
+}
+
+int fill_nested(struct nested *n) {
+    n->items[4].d = 2.5;
+    n->tail = 7;
+    return n->items[1].c;
+}
@@ -0,0 +1,110 @@
+// Calls the functions crumb compiled from arguments.c with arguments, and is
+// called back by them, checking what each returns, and shares a variable each
+// way with them. It also hands them structures, whose members they must find
+// where gcc put them. Prints a line per failure and exits with how many there were.
+#include <stddef.h>
+#include <stdio.h>
+
+int weigh(int a, int b, int c, int d, int e, int f, int g, int h);
+int weigh_in_gcc(int x);
+short narrow(char a, unsigned char b, short c, unsigned short d, signed char e, char f, short g,
+             unsigned char h);
+int narrow_in_gcc(int x);
+int misalignments(int a);
+int misalignments_with_more_slots(int a, int b, int c);
+int survives_clobber(int a, int b);
+int step_total(void);
+extern int crumb_total;
+
+struct with_array {
+    int a;
+    int arr[4];
+};
+
+struct padded {
+    char c;
+    double d;
+};
+
+struct nested {
+    struct padded items[5];
+    char tail;
+};
+
+long with_array_offset(struct with_array *s);
+long with_array_size(struct with_array *s);
+long nested_tail_offset(struct nested *n);
+long nested_size(struct nested *n);
+int fill_nested(struct nested *n);
+
+int gcc_step = 1;
+
+int gcc_weigh(int a, int b, int c, int d, int e, int f, int g, int h) {
+    return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h;
+}
+
+int gcc_narrow(char a, unsigned char b, short c, unsigned short d, signed char e, char f, short g,
+               unsigned char h) {
+    return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h;
+}
+
+/* misaligned_<n> returns how far %rsp was from 16-byte alignment at the call,
+ * whatever it is passed; each name is the same code. clobber overwrites every
+ * caller-saved register but %rax, which it returns 0 in. */
+__asm__(".text\n"
+        ".globl misaligned_0, misaligned_1, misaligned_7, misaligned_8\n"
+        "misaligned_0:\n"
+        "misaligned_1:\n"
+        "misaligned_7:\n"
+        "misaligned_8:\n\t"
+        "lea 8(%rsp), %rax\n\t"
+        "and $15, %eax\n\t"
+        "ret\n"
+        ".globl clobber\n"
+        "clobber:\n\t"
+        "mov $-1, %rcx\n\t"
+        "mov $-1, %rdx\n\t"
+        "mov $-1, %rsi\n\t"
+        "mov $-1, %rdi\n\t"
+        "mov $-1, %r8\n\t"
+        "mov $-1, %r9\n\t"
+        "mov $-1, %r10\n\t"
+        "mov $-1, %r11\n\t"
+        "xor %eax, %eax\n\t"
+        "ret\n");
+
+static int check(const char *name, int got, int expected) {
+    if (got == expected)
+        return 0;
+    printf("%s returned %d, not %d\n", name, got, expected);
+    return 1;
+}
+
+int main(void) {
+    int failures = 0;
+    failures += check("weigh", weigh(1, -2, 3, -4, 5, -6, 7, -8), -36);
+    failures += check("weigh_in_gcc", weigh_in_gcc(3), gcc_weigh(3, 4, 5, 6, 7, 8, 9, 10));
+    failures += check("narrow", narrow(-1, 200, -300, 60000, -5, 100, 1000, 255), -13030);
+    failures += check("narrow_in_gcc", narrow_in_gcc(300),
+                      gcc_narrow(300, 300, 300, 300, -300, 300, -300, 300));
+    failures += check("misalignments", misalignments(1), 0);
+    failures += check("misalignments_with_more_slots", misalignments_with_more_slots(2, 3, 4), 0);
+    failures += check("survives_clobber", survives_clobber(6, 7), 43);
+    failures += check("crumb_total", crumb_total, 40);
+    failures += check("step_total", step_total(), 41);
+    crumb_total = 10;
+    failures += check("step_total", step_total(), 12);
+    failures += check("gcc_step", gcc_step, 4);
+    struct with_array shape;
+    failures += check("with_array_offset", with_array_offset(&shape),
+                      offsetof(struct with_array, arr));
+    failures += check("with_array_size", with_array_size(&shape), sizeof shape);
+    struct nested n = {0};
+    n.items[1].c = 9;
+    failures += check("nested_tail_offset", nested_tail_offset(&n), offsetof(struct nested, tail));
+    failures += check("nested_size", nested_size(&n), sizeof n);
+    failures += check("fill_nested", fill_nested(&n), 9);
+    failures += check("nested tail", n.tail, 7);
+    failures += check("nested item", n.items[4].d == 2.5, 1);
+    return failures;
+}
@@ -0,0 +1,15 @@
+// Compiled by crumb, and called from caller.c, which gcc compiles. Each
+// function is repeated there as a macro, for gcc to work out what it returns.
+int two(void) { return 2; }
+
+int int_min(void) { return -2147483647 - 1; }
+
+int all_ones(void) { return ~0; }
+
+int divides(void) { return (20 - 3) / 5 + (20 - 3) % (4 - 1) * -~7; }
+
+int by_powers_of_two(void) { return (~100) / 8 + (~100) % 16 * 3; }
+
+int many_slots(void) {
+    return ((1 + 2) * (3 + 4) - (5 * 6) / (7 - 8)) ^ ((9 | 10) & (11 - 12) * (13 % 14)) + -(15 * -16);
+}
@@ -0,0 +1,104 @@
+// Calls the functions crumb compiled from callee.c, checking what each returns
+// and that each leaves the callee-saved registers and the stack pointer as they
+// were. Prints a line per failure and exits with how many there were.
+#include <stdio.h>
+
+#define TWO 2
+#define INT_MIN_ (-2147483647 - 1)
+#define ALL_ONES (~0)
+#define DIVIDES ((20 - 3) / 5 + (20 - 3) % (4 - 1) * -~7)
+#define BY_POWERS_OF_TWO ((~100) / 8 + (~100) % 16 * 3)
+#define MANY_SLOTS \
+    (((1 + 2) * (3 + 4) - (5 * 6) / (7 - 8)) ^ ((9 | 10) & (11 - 12) * (13 % 14)) + -(15 * -16))
+
+int two(void);
+int int_min(void);
+int all_ones(void);
+int divides(void);
+int by_powers_of_two(void);
+int many_slots(void);
+
+static const char *saved_names[] = {"rbx", "rbp", "r12", "r13", "r14", "r15"};
+
+/* what the assembly in call_checked reads and writes, at fixed offsets */
+struct call {
+    long before[6];      /* 0: loaded into the callee-saved registers */
+    long after[6];       /* 48: the same registers after the call */
+    long sp_before;      /* 96 */
+    long sp_after;       /* 104 */
+    int (*f)(void);      /* 112 */
+    long caller_sp;      /* 120 */
+    long caller_bp;      /* 128 */
+    long result;         /* 136 */
+};
+
+/* calls `f` with a known value in each callee-saved register, and reads them
+ * all back after. The call is made on a 16-byte aligned stack below the red
+ * zone, which gcc may be using, and %rsp and %rbp are put back by hand. */
+static int call_checked(const char *name, int (*f)(void), int expected) {
+    struct call c = {
+        .before = {0x1111111111111111, 0x2222222222222222, 0x3333333333333333,
+                   0x4444444444444444, 0x5555555555555555, 0x6666666666666666},
+        .f = f,
+    };
+    struct call *p = &c;
+    __asm__ volatile(
+        "mov %%rsp, 120(%%rax)\n\t"
+        "mov %%rbp, 128(%%rax)\n\t"
+        "sub $128, %%rsp\n\t"
+        "and $-16, %%rsp\n\t"
+        "push %%rax\n\t"
+        "push %%rax\n\t"
+        "mov %%rsp, 96(%%rax)\n\t"
+        "mov 0(%%rax), %%rbx\n\t"
+        "mov 8(%%rax), %%rbp\n\t"
+        "mov 16(%%rax), %%r12\n\t"
+        "mov 24(%%rax), %%r13\n\t"
+        "mov 32(%%rax), %%r14\n\t"
+        "mov 40(%%rax), %%r15\n\t"
+        "call *112(%%rax)\n\t"
+        "mov (%%rsp), %%rcx\n\t"
+        "mov %%rsp, 104(%%rcx)\n\t"
+        "movslq %%eax, %%rax\n\t"
+        "mov %%rax, 136(%%rcx)\n\t"
+        "mov %%rbx, 48(%%rcx)\n\t"
+        "mov %%rbp, 56(%%rcx)\n\t"
+        "mov %%r12, 64(%%rcx)\n\t"
+        "mov %%r13, 72(%%rcx)\n\t"
+        "mov %%r14, 80(%%rcx)\n\t"
+        "mov %%r15, 88(%%rcx)\n\t"
+        "mov 128(%%rcx), %%rbp\n\t"
+        "mov 120(%%rcx), %%rsp\n\t"
+        "mov %%rcx, %%rax\n\t"
+        : "+a"(p)
+        :
+        : "rbx", "rcx", "rdx", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13", "r14",
+          "r15", "memory", "cc");
+    int failures = 0;
+    if (c.result != expected) {
+        printf("%s returned %ld, not %d\n", name, c.result, expected);
+        failures++;
+    }
+    for (int i = 0; i < 6; i++) {
+        if (c.after[i] != c.before[i]) {
+            printf("%s changed %s\n", name, saved_names[i]);
+            failures++;
+        }
+    }
+    if (c.sp_after != c.sp_before) {
+        printf("%s moved rsp by %ld\n", name, c.sp_after - c.sp_before);
+        failures++;
+    }
+    return failures;
+}
+
+int main(void) {
+    int failures = 0;
+    failures += call_checked("two", two, TWO);
+    failures += call_checked("int_min", int_min, INT_MIN_);
+    failures += call_checked("all_ones", all_ones, ALL_ONES);
+    failures += call_checked("divides", divides, DIVIDES);
+    failures += call_checked("by_powers_of_two", by_powers_of_two, BY_POWERS_OF_TWO);
+    failures += call_checked("many_slots", many_slots, MANY_SLOTS);
+    return failures;
+}
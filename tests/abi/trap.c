@@ -0,0 +1,3 @@
+// Compiled by crumb, and called from unwind.c, which gcc compiles.
+// The division traps, so the unwinder has to start inside this function.
+int crumb_divides_by_zero(void) { return 1 / 0; }
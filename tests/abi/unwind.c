@@ -0,0 +1,66 @@
+// Unwinds the stack through a function crumb compiled. crumb_divides_by_zero
+// traps with SIGFPE, and the handler walks the stack with backtrace(), which
+// follows the unwind tables the assembler built from crumb's CFI directives.
+// The walk must go from the crumb function to call_crumb, the gcc-compiled
+// function that called it, and on to main. Link with -rdynamic so dladdr can
+// name the frames. Prints the frames and exits 1 if the walk goes wrong.
+#define _GNU_SOURCE
+#include <dlfcn.h>
+#include <execinfo.h>
+#include <setjmp.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+
+int crumb_divides_by_zero(void);
+
+static sigjmp_buf escape;
+static volatile sig_atomic_t unwound;
+
+__attribute__((noinline)) int call_crumb(void) {
+    // adding to the result keeps gcc from turning the call into a jump
+    return crumb_divides_by_zero() + 1;
+}
+
+static const char *frame_name(void *frame) {
+    Dl_info info;
+    if (dladdr(frame, &info) && info.dli_sname) {
+        return info.dli_sname;
+    }
+    return "?";
+}
+
+static void on_fpe(int sig) {
+    (void)sig;
+    void *frames[32];
+    int n = backtrace(frames, 32);
+    const char *expected[] = {"crumb_divides_by_zero", "call_crumb", "main"};
+    for (int i = 0; i + 2 < n; i++) {
+        int matched = 1;
+        for (int j = 0; j < 3; j++) {
+            matched &= strcmp(frame_name(frames[i + j]), expected[j]) == 0;
+        }
+        if (matched) {
+            unwound = 1;
+        }
+    }
+    if (!unwound) {
+        for (int i = 0; i < n; i++) {
+            printf("frame %d: %s\n", i, frame_name(frames[i]));
+        }
+    }
+    siglongjmp(escape, 1);
+}
+
+int main(void) {
+    struct sigaction action;
+    memset(&action, 0, sizeof action);
+    action.sa_handler = on_fpe;
+    sigaction(SIGFPE, &action, NULL);
+    if (sigsetjmp(escape, 1) == 0) {
+        call_crumb();
+        printf("crumb_divides_by_zero returned without trapping\n");
+        return 1;
+    }
+    return unwound ? 0 : 1;
+}
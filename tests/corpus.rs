@@ -0,0 +1,262 @@
+//! Compares crumb against the system C compiler on a corpus of programs.
+//! Every program in `tests/corpus/valid` is built by both, and the two
+//! executables must exit with the same status and print the same output.
+//! Every program in `tests/corpus/invalid` must make crumb fail without writing
+//! an output file. Each new language feature should add programs to both.
+//! Programs in `tests/corpus/unsupported` are valid C that crumb doesn't handle
+//! yet: crumb must reject them the same way, and `cc` must accept them.
+//!
+//! Without a `cc` on the path the comparison is skipped, though invalid and
+//! unsupported programs are still checked. Run with `--nocapture` to see the
+//! summary table.
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+/// how long a compiled program may run before it counts as hung.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// the C files in `tests/corpus/<dir>`, sorted by name.
+fn corpus(dir: &str) -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/corpus")
+        .join(dir);
+    let mut sources: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "c"))
+        .collect();
+    sources.sort();
+    sources
+}
+
+fn name(source: &Path) -> String {
+    source.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+/// what running a program came to: its exit status, or a signal or the
+/// timeout when it didn't exit, and what it printed.
+#[derive(PartialEq, Debug)]
+enum Outcome {
+    Exited { code: i32, stdout: String },
+    Killed,
+    TimedOut,
+}
+
+impl Outcome {
+    fn summary(&self) -> String {
+        match self {
+            Self::Exited { code, .. } => code.to_string(),
+            Self::Killed => String::from("signal"),
+            Self::TimedOut => String::from("timeout"),
+        }
+    }
+}
+
+fn run_with_timeout(executable: &Path) -> Outcome {
+    let mut child = Command::new(executable)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // read as it is written, so a full pipe can't stall the program
+    let mut pipe = child.stdout.take().unwrap();
+    let reader = thread::spawn(move || {
+        let mut stdout = String::new();
+        pipe.read_to_string(&mut stdout).map(|_| stdout)
+    });
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if start.elapsed() > TIMEOUT {
+            child.kill().unwrap();
+            child.wait().unwrap();
+            return Outcome::TimedOut;
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+    let stdout = reader.join().unwrap().unwrap();
+    match status.code() {
+        Some(code) => Outcome::Exited { code, stdout },
+        None => Outcome::Killed,
+    }
+}
+
+/// builds `source` into `executable` with `compiler`, returning its stderr on failure.
+fn build(compiler: &str, source: &Path, executable: &Path) -> Result<(), String> {
+    let output = Command::new(compiler)
+        .arg(source)
+        .arg("-o")
+        .arg(executable)
+        .output()
+        .unwrap();
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(String::from_utf8_lossy(&output.stderr).into_owned()),
+    }
+}
+
+fn cc_available() -> bool {
+    Command::new("cc")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// the rows of a summary table, their columns padded to line up.
+fn table<const N: usize>(header: [&str; N], rows: &[[String; N]]) -> String {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let line = |cells: [&str; N]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        padded.join("  ").trim_end().to_string() + "\n"
+    };
+    let mut res = line(header);
+    for row in rows {
+        res.push_str(&line(row.each_ref().map(String::as_str)));
+    }
+    res
+}
+
+#[test]
+fn valid_programs_match_cc() {
+    if !cc_available() {
+        eprintln!("skipping valid_programs_match_cc: no cc on the path");
+        return;
+    }
+    let crumb = env!("CARGO_BIN_EXE_crumb");
+    let tmpdir = TempDir::new().unwrap();
+    let mut rows = vec![];
+    let mut failures = 0;
+    for source in corpus("valid") {
+        let name = name(&source);
+        let ours = tmpdir.path().join(format!("{}.crumb", name));
+        let theirs = tmpdir.path().join(format!("{}.cc", name));
+        build("cc", &source, &theirs).unwrap_or_else(|e| panic!("cc rejected {}: {}", name, e));
+        let expected = run_with_timeout(&theirs);
+        let (got, verdict) = match build(crumb, &source, &ours) {
+            Err(e) => (String::from("error"), format!("FAIL {}", e.trim_end())),
+            Ok(()) => {
+                let got = run_with_timeout(&ours);
+                let verdict = match got == expected {
+                    true => String::from("ok"),
+                    false => format!("FAIL {:?}", got),
+                };
+                (got.summary(), verdict)
+            }
+        };
+        if verdict != "ok" {
+            failures += 1;
+        }
+        rows.push([name, got, expected.summary(), verdict]);
+    }
+    println!("{}", table(["program", "crumb", "cc", "result"], &rows));
+    assert_eq!(
+        failures,
+        0,
+        "{}",
+        table(["program", "crumb", "cc", "result"], &rows)
+    );
+}
+
+/// runs crumb on every program in `tests/corpus/<dir>`, which must all be
+/// rejected without an output file, and returns the summary rows and the
+/// number of failures.
+fn check_rejected(dir: &str) -> (Vec<[String; 3]>, usize) {
+    let crumb = env!("CARGO_BIN_EXE_crumb");
+    let tmpdir = TempDir::new().unwrap();
+    let mut rows = vec![];
+    let mut failures = 0;
+    for source in corpus(dir) {
+        let name = name(&source);
+        let executable = tmpdir.path().join(&name).with_extension("");
+        let output = Command::new(crumb)
+            .arg(&source)
+            .arg("-o")
+            .arg(&executable)
+            .output()
+            .unwrap();
+        let code = output
+            .status
+            .code()
+            .map_or(String::from("signal"), |c| c.to_string());
+        let verdict = match (output.status.code(), executable.exists()) {
+            (_, true) => "FAIL wrote output",
+            (Some(0), false) => "FAIL accepted",
+            (Some(_), false) => "ok",
+            (None, false) => "FAIL crashed",
+        };
+        if verdict != "ok" {
+            failures += 1;
+        }
+        rows.push([name, code, verdict.to_string()]);
+    }
+    (rows, failures)
+}
+
+#[test]
+fn invalid_programs_are_rejected() {
+    let (rows, failures) = check_rejected("invalid");
+    println!("{}", table(["program", "crumb", "result"], &rows));
+    assert_eq!(
+        failures,
+        0,
+        "{}",
+        table(["program", "crumb", "result"], &rows)
+    );
+}
+
+#[test]
+fn unsupported_programs_are_rejected() {
+    let (rows, failures) = check_rejected("unsupported");
+    println!("{}", table(["program", "crumb", "result"], &rows));
+    assert_eq!(
+        failures,
+        0,
+        "{}",
+        table(["program", "crumb", "result"], &rows)
+    );
+}
+
+#[test]
+fn unsupported_programs_are_valid_c() {
+    if !cc_available() {
+        eprintln!("skipping unsupported_programs_are_valid_c: no cc on the path");
+        return;
+    }
+    let tmpdir = TempDir::new().unwrap();
+    for source in corpus("unsupported") {
+        let name = name(&source);
+        let executable = tmpdir.path().join(&name).with_extension("");
+        build("cc", &source, &executable).unwrap_or_else(|e| panic!("cc rejected {}: {}", name, e));
+    }
+}
+
+#[test]
+fn table_lines_up_columns() {
+    let rows = [[
+        String::from("long_name.c"),
+        String::from("3"),
+        String::from("3"),
+        String::from("ok"),
+    ]];
+    assert_eq!(
+        table(["program", "crumb", "cc", "result"], &rows),
+        "program      crumb  cc  result\nlong_name.c  3      3   ok\n"
+    );
+}
@@ -0,0 +1,5 @@
+int main(void) {
+    int x = 3;
+    int *p = &(x + 1);
+    return *p;
+}
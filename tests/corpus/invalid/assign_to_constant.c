@@ -0,0 +1 @@
+int main(void) { int a; 3 = a; return a; }
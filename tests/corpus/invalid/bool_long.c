@@ -0,0 +1,4 @@
+int main(void) {
+    long _Bool b = 1;
+    return b;
+}
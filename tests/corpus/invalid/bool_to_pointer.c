@@ -0,0 +1,5 @@
+int main(void) {
+    _Bool b = 1;
+    int *p = b;
+    return 0;
+}
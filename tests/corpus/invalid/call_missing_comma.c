@@ -0,0 +1 @@
+int main(void) { return main(1 2); }
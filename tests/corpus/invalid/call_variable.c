@@ -0,0 +1,4 @@
+int main(void) {
+    int f = 3;
+    return f();
+}
@@ -0,0 +1 @@
+int main(void) { for (;;) { case 1: return 0; } }
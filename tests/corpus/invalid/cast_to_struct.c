@@ -0,0 +1,8 @@
+struct s {
+    int a;
+};
+
+int main(void) {
+    struct s v = (struct s)1;
+    return v.a;
+}
@@ -0,0 +1,4 @@
+int main(void) {
+    char int c = 1;
+    return c;
+}
@@ -0,0 +1,6 @@
+int f(int a);
+int f(int a, int b);
+
+int main(void) {
+    return 0;
+}
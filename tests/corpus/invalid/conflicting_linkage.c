@@ -0,0 +1,6 @@
+int x = 1;
+static int x;
+
+int main(void) {
+    return x;
+}
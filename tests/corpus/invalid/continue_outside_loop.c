@@ -0,0 +1 @@
+int main(void) { for (;;) return 1; continue; }
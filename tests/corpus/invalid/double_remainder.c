@@ -0,0 +1,4 @@
+int main(void) {
+    double d = 7.5;
+    return d % 2;
+}
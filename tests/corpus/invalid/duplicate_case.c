@@ -0,0 +1 @@
+int main(void) { switch (1) { case 1: case 3 - 2: return 0; } return 1; }
@@ -0,0 +1 @@
+int main(void) { switch (0) { default: ; default: ; } return 0; }
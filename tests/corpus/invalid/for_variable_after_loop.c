@@ -0,0 +1 @@
+int main(void) { for (int i = 0; i < 3; i = i + 1) ; return i; }
@@ -0,0 +1,5 @@
+int main(void) {
+    long l = 3;
+    int *p = &l;
+    return *p;
+}
@@ -0,0 +1,6 @@
+struct list;
+
+int main(void) {
+    struct list l;
+    return 0;
+}
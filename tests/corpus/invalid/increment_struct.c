@@ -0,0 +1,7 @@
+struct s { int x; };
+
+int main(void) {
+    struct s v;
+    ++v;
+    return 0;
+}
@@ -0,0 +1,4 @@
+int main(void) {
+    long short x = 1;
+    return x;
+}
@@ -0,0 +1 @@
+main(void) { return 0; }
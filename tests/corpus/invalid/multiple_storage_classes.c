@@ -0,0 +1,5 @@
+static extern int x;
+
+int main(void) {
+    return 0;
+}
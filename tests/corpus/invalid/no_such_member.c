@@ -0,0 +1,10 @@
+struct point {
+    int x;
+    int y;
+};
+
+int main(void) {
+    struct point p;
+    p.x = 1;
+    return p.z;
+}
@@ -0,0 +1 @@
+int main(void) { int a = 1; switch (a) { case a: return 0; } return 1; }
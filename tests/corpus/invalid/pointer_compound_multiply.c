@@ -0,0 +1,6 @@
+int main(void) {
+    int x = 1;
+    int *p = &x;
+    p *= 2;
+    return x;
+}
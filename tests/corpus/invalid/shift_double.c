@@ -0,0 +1,4 @@
+int main(void) {
+    double d = 2.0;
+    return 1 << d;
+}
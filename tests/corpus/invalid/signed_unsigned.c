@@ -0,0 +1,4 @@
+int main(void) {
+    signed unsigned int x = 1;
+    return x;
+}
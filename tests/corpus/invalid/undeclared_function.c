@@ -0,0 +1,7 @@
+int main(void) {
+    return later(1);
+}
+
+int later(int a) {
+    return a;
+}
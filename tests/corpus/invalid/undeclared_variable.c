@@ -0,0 +1 @@
+int main(void) { { int a = 1; } return a; }
@@ -0,0 +1,4 @@
+int main(void) {
+    char *s = "never closed;
+    return 0;
+}
@@ -0,0 +1,9 @@
+int f;
+
+int f(void) {
+    return 0;
+}
+
+int main(void) {
+    return 0;
+}
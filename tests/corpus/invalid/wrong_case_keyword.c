@@ -0,0 +1 @@
+int main(void) { RETURN 0; }
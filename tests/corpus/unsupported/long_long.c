@@ -0,0 +1,4 @@
+int main(void) {
+    long long x = 1;
+    return x;
+}
@@ -0,0 +1,12 @@
+struct pair {
+    int a;
+    int b;
+};
+
+int sum(struct pair p) {
+    return p.a + p.b;
+}
+
+int main(void) {
+    return 0;
+}
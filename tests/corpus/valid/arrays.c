@@ -0,0 +1,38 @@
+static long totals[3];
+
+int sum(int *values, int count) {
+    int total = 0;
+    for (int i = 0; i < count; i = i + 1)
+        total = total + values[i];
+    return total;
+}
+
+long last(long row[4]) {
+    return row[3];
+}
+
+int main(void) {
+    int a[5];
+    for (int i = 0; i < 5; i = i + 1)
+        a[i] = i * i;
+    if (sum(a, 5) != 30)
+        return 1;
+    int *p = a + 1;
+    if (*p != 1 || p[2] != 9 || 3[a] != 9)
+        return 2;
+    if (&a[4] - p != 3 || a + 5 - 1 != &a[4])
+        return 3;
+    long grid[2][4];
+    for (int r = 0; r < 2; r = r + 1)
+        for (int c = 0; c < 4; c = c + 1)
+            grid[r][c] = r * 10 + c;
+    if (last(grid[1]) != 13 || *grid[0] != 0)
+        return 4;
+    if (grid[1][2] != 12 || *(grid[1] + 1) != 11)
+        return 5;
+    char letters[3];
+    letters[0] = 97;
+    letters[1] = letters[0] + 1;
+    totals[2] = letters[1] + sum(a, 3);
+    return totals[0] + totals[1] + totals[2] - 100;
+}
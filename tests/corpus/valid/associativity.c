@@ -0,0 +1 @@
+int main(void) { return 100 - 20 - 30 - 4; }
@@ -0,0 +1 @@
+int main(void) { return (240 | 15) & 60 ^ 5; }
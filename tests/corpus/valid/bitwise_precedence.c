@@ -0,0 +1 @@
+int main(void) { return 1 | 6 ^ 3 & 5 + 2; }
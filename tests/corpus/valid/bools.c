@@ -0,0 +1,35 @@
+_Bool never;
+static _Bool always = 42;
+static _Bool half = 0.5;
+
+_Bool is_nonzero(long x) {
+    return x;
+}
+
+int main(void) {
+    _Bool wrapped = 256;
+    _Bool quarter = 0.25;
+    int x = 7;
+    _Bool pointer = &x;
+    int *null = 0;
+    _Bool none = null;
+    if (wrapped != 1 || quarter != 1 || pointer != 1 || none != 0)
+        return 1;
+    if (never || always == 0 || half == 0)
+        return 2;
+    _Bool flags[3];
+    flags[0] = -1;
+    flags[1] = 0;
+    flags[2] = 2.0;
+    _Bool up = 1;
+    up++;
+    _Bool down = 0;
+    down--;
+    _Bool sum = 1;
+    sum += 4;
+    if (flags[0] + flags[1] + flags[2] != 2 || up != 1 || down != 1 || sum != 1)
+        return 3;
+    if (is_nonzero(1L << 40) != 1 || (_Bool)0.0 || (int)(_Bool)-3 != 1)
+        return 4;
+    return wrapped + quarter + pointer + (_Bool)x * 10;
+}
@@ -0,0 +1,16 @@
+int main(void) {
+    int found = 0;
+    for (int i = 2; ; i = i + 1) {
+        int prime = 1;
+        for (int d = 2; d * d <= i; d = d + 1)
+            if (i % d == 0) {
+                prime = 0;
+                break;
+            }
+        if (prime == 0)
+            continue;
+        found = found + 1;
+        if (found == 10)
+            return i;
+    }
+}
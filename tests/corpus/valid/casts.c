@@ -0,0 +1,26 @@
+struct pair {
+    int first;
+    int second;
+};
+
+static long wide = (long)4000000000u;
+static char narrow = (char)300;
+
+int main(void) {
+    int negative = -1;
+    if ((long)negative != -1 || (unsigned long)(unsigned)negative != 4294967295ul)
+        return 1;
+    if ((char)257 != 1 || (unsigned char)-1 != 255 || (short)65537 != 1)
+        return 2;
+    if ((double)7 / 2 != 3.5 || (int)-3.9 != -3 || (unsigned)3e9 != 3000000000u)
+        return 3;
+    struct pair p;
+    p.first = 5;
+    p.second = 6;
+    long address = (long)&p;
+    struct pair *q = (struct pair *)address;
+    int *members = (int *)q;
+    if (q->second != 6 || members[1] != 6 || (char *)&p.second - (char *)&p != 4)
+        return 4;
+    return wide / 1000000000 + narrow + (int)(unsigned char)-2 - 200;
+}
@@ -0,0 +1,29 @@
+static unsigned char counter = 250;
+
+unsigned char tick(void) {
+    counter = counter + 3;
+    return counter;
+}
+
+int main(void) {
+    char c = 100;
+    signed char s = -100;
+    unsigned char u = 200;
+    int sum = c + c;
+    c = c + c;
+    if (sum != 200)
+        return 1;
+    if (c != -56)
+        return 2;
+    if (u + u != 400)
+        return 3;
+    if (s * 2 != -200)
+        return 4;
+    if (~u != -201)
+        return 5;
+    if (tick() != 253 || tick() != 0)
+        return 6;
+    char from_long = 4294967553l;
+    unsigned char from_negative = -1;
+    return from_long + from_negative - 200;
+}
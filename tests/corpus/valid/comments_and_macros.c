@@ -0,0 +1,6 @@
+/* preprocessed first, as cc would */
+#define ANSWER (6 * 7)
+int main(void) {
+    // the answer
+    return ANSWER;
+}
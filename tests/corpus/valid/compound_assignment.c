@@ -0,0 +1,53 @@
+struct point {
+    short x;
+    long y;
+};
+
+static int calls;
+
+int next(void) {
+    calls += 1;
+    return calls;
+}
+
+int main(void) {
+    int x = 10;
+    x += 5;
+    x -= 3;
+    x *= 4;
+    x /= 6;
+    x %= 5;
+    char c = 100;
+    c += 100;
+    unsigned u = 3;
+    u -= 5;
+    double d = 1.5;
+    d *= x;
+    int i = 7;
+    i += 2.9;
+    if (x != 3 || c != -56 || u != 4294967294u || d != 4.5 || i != 9)
+        return 1;
+    long a[4];
+    long *p = a;
+    p += 3;
+    p -= 1;
+    *p = 9;
+    a[1] = 6;
+    a[1] |= 8;
+    a[1] ^= 3;
+    a[1] &= 13;
+    if (a[2] != 9 || a[1] != 13)
+        return 2;
+    a[next()] += 100;
+    if (calls != 1 || a[1] != 113)
+        return 3;
+    struct point pt;
+    struct point *pp = &pt;
+    pt.x = 30000;
+    pt.x += 10000;
+    pp->y = 5;
+    pp->y *= next() + 1;
+    int y = 2;
+    int z = y += x *= 2;
+    return (pt.x < 0) + pp->y + z + calls;
+}
@@ -0,0 +1,5 @@
+int main(void) {
+    int a = 0;
+    int b = a ? 4 : 5;
+    return a ? 1 : b ? b * 2 : 3;
+}
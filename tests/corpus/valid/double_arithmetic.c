@@ -0,0 +1,23 @@
+int putchar(int c);
+
+double average(double a, double b, double c) {
+    return (a + b + c) / 3.0;
+}
+
+double scale = 1.5e1;
+
+int main(void) {
+    double x = .25;
+    double y = x * -4 + average(1.0, 2., 6e0);
+    if (y != 2.0)
+        return 1;
+    if (0.1 + 0.2 == 0.3)
+        return 2;
+    if (x > y || y <= 1.5)
+        return 3;
+    for (int i = 0; i < 3; i = i + 1)
+        putchar(48 + y * i);
+    putchar(10);
+    int digits = scale / 4;
+    return x ? digits : 0;
+}
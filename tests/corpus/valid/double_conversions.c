@@ -0,0 +1,26 @@
+unsigned long round_trip(unsigned long u) {
+    double d = u;
+    unsigned long back = d;
+    return back;
+}
+
+int main(void) {
+    int truncated = -7.9;
+    unsigned int big = 4294967295u;
+    double from_unsigned = big;
+    long from_double = 1e18;
+    if (truncated != -7)
+        return 1;
+    if (from_unsigned != 4294967295.0)
+        return 2;
+    if (from_double != 1000000000000000000l)
+        return 3;
+    if (round_trip(9223372036854777856ul) != 9223372036854777856ul)
+        return 4;
+    if (round_trip(12345) != 12345)
+        return 5;
+    double huge = 18446744073709551615ul;
+    if (huge != 18446744073709551616.0)
+        return 6;
+    return -truncated + (from_unsigned > 0);
+}
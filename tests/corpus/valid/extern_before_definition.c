@@ -0,0 +1,15 @@
+int read(void) {
+    extern int value;
+    return value;
+}
+
+int value = 17;
+
+int main(void) {
+    int value = 3;
+    {
+        extern int value;
+        value = value + 1;
+    }
+    return read() + value;
+}
@@ -0,0 +1,12 @@
+int main(void) {
+    int i = 100;
+    int total = 0;
+    for (int i = 0; i < 10; i = i + 1)
+        for (int j = i; j < 10; j = j + 3)
+            total = total + j;
+    for (; total > 200; )
+        total = total - 7;
+    for (int n = 1; ; n = n * 2)
+        if (n > 64)
+            return total + i + n;
+}
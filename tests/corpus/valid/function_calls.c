@@ -0,0 +1,13 @@
+int weigh(int a, int b, int c, int d, int e, int f, int g, int h) {
+    return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h;
+}
+
+int last(int a, int b, int c, int d, int e, int f, int g) {
+    return g - a;
+}
+
+int main(void) {
+    int x = 3;
+    x = weigh(x, x + 1, 0, 0, 0, 0, 0, last(1, 2, 3, 4, 5, 6, 9)) - x;
+    return x + weigh(1, 1, 1, 1, 1, 1, 1, 1);
+}
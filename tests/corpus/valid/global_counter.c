@@ -0,0 +1,12 @@
+int counter = 5;
+
+int bump(int by) {
+    counter = counter + by;
+    return counter;
+}
+
+int main(void) {
+    bump(3);
+    bump(4);
+    return counter;
+}
@@ -0,0 +1,9 @@
+int main(void) {
+    int a = 3;
+    if (a > 2)
+        if (a > 5)
+            return 1;
+        else
+            a = a * 7;
+    return a;
+}
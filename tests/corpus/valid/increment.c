@@ -0,0 +1,40 @@
+static int calls;
+
+int next(void) {
+    return ++calls;
+}
+
+int main(void) {
+    int x = 5;
+    int a = x++;
+    int b = ++x;
+    int c = x--;
+    int d = --x;
+    if (a != 5 || b != 7 || c != 7 || d != 5 || x != 5)
+        return 1;
+    char ch = 127;
+    ch++;
+    unsigned char uc = 0;
+    uc--;
+    double dbl = 0.5;
+    dbl++;
+    if (ch != -128 || uc != 255 || --dbl != 0.5)
+        return 2;
+    long arr[4];
+    for (int i = 0; i < 4; i++)
+        arr[i] = i * 10;
+    long *p = arr;
+    long first = *p++;
+    long second = *++p;
+    if (first != 0 || second != 20 || p - arr != 2)
+        return 3;
+    p--;
+    --p;
+    if (p != arr)
+        return 4;
+    arr[next()]++;
+    ++arr[next()];
+    if (calls != 2 || arr[1] != 11 || arr[2] != 21)
+        return 5;
+    return a + b + arr[2]++ + arr[2] + next();
+}
@@ -0,0 +1 @@
+int main(void) { return 2147483647 / 65536 % 251; }
@@ -0,0 +1,22 @@
+long big = 4294967296;
+
+long scale(long x, int by) {
+    return x * by / 2;
+}
+
+long last(int a, int b, int c, int d, int e, int f, long g, long h) {
+    return h - g + a;
+}
+
+int main(void) {
+    long a = 3000000000;
+    long b = scale(a, 4);
+    int low = b;
+    if (b / 1000000 != 6000)
+        return 1;
+    if (a % 7 != 3000000000 - 3000000000 / 7 * 7)
+        return 2;
+    if (last(1, 2, 3, 4, 5, 6, -big, big) != 2 * big + 1)
+        return 3;
+    return (big / 65536 + low) % 251;
+}
@@ -0,0 +1,13 @@
+int negative = -5;
+
+int main(void) {
+    long wide = negative;
+    long sum = wide + 2147483647L;
+    int truncated = 4294967298;
+    static long limit = 2147483648;
+    if (wide != -5l)
+        return 1;
+    if (sum != 2147483642)
+        return 2;
+    return truncated + (limit > 2147483647) * 10;
+}
@@ -0,0 +1,15 @@
+int is_odd(int n);
+
+int is_even(int n) {
+    return n == 0 ? 1 : is_odd(n - 1);
+}
+
+int is_odd(int n) {
+    return n == 0 ? 0 : is_even(n - 1);
+}
+
+int is_even(int n);
+
+int main(void) {
+    return is_even(10) * 10 + is_odd(7) * 3 + is_even(5);
+}
@@ -0,0 +1 @@
+int main(void) { return (2 + 3) * (4 - (10 / (3 + 2))); }
@@ -0,0 +1,36 @@
+static int calls;
+
+int swap(int *a, int *b) {
+    int tmp = *a;
+    *a = *b;
+    *b = tmp;
+    calls = calls + 1;
+    return *a - *b;
+}
+
+int *larger(int *a, int *b) {
+    return *a > *b ? a : b;
+}
+
+int main(void) {
+    int x = 3;
+    int y = 10;
+    int *px = &x;
+    int **ppx = &px;
+    if (swap(&x, &y) != 7)
+        return 1;
+    if (x != 10 || y != 3)
+        return 2;
+    *larger(&x, &y) = 0;
+    if (x != 0)
+        return 3;
+    **ppx = 42;
+    if (*px != x || px != &x || px == &y)
+        return 4;
+    int *counter = &calls;
+    *counter = *counter * 5;
+    long l = 100;
+    long *pl = &l;
+    *pl = *pl + x;
+    return l - calls;
+}
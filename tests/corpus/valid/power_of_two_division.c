@@ -0,0 +1 @@
+int main(void) { return (~100) / 8 + (~100) % 16 + 40; }
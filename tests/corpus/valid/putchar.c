@@ -0,0 +1,12 @@
+int putchar(int c);
+
+int print_digit(int d) {
+    return putchar(48 + d);
+}
+
+int main(void) {
+    for (int i = 0; i < 10; i = i + 1)
+        print_digit(9 - i);
+    putchar(10);
+    return 0;
+}
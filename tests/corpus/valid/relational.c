@@ -0,0 +1 @@
+int main(void) { return (1 < 2) + (2 <= 2) * 2 + (3 > 4) * 4 + (5 >= 6) * 8 + (7 == 7) * 16 + (8 != 8) * 32; }
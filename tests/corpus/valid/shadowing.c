@@ -0,0 +1,10 @@
+int main(void) {
+    int a = 2;
+    {
+        int a = a = 5;
+        a = a * 3;
+    }
+    int b = a = a + 1;
+    { ; }
+    return a + b;
+}
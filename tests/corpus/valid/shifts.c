@@ -0,0 +1,25 @@
+int shift_left(int x, int n) {
+    return x << n;
+}
+
+int main(void) {
+    int x = -17;
+    unsigned u = 4000000000u;
+    long l = 1;
+    char c = 3;
+    int n = 4;
+    if (x >> 2 != -5 || u >> n != 250000000u || (l << 40) != 1099511627776)
+        return 1;
+    if ((c << n) != 48 || -1L >> 63 != -1 || shift_left(1, 30) != 1073741824)
+        return 2;
+    x <<= 3;
+    u >>= c;
+    l <<= 2L;
+    unsigned char uc = 200;
+    uc >>= 1;
+    if (x != -136 || u != 500000000u || l != 4 || uc != 100)
+        return 3;
+    // shifts bind looser than addition and tighter than comparison
+    int bits = 1 << 2 + 1 < 9;
+    return bits + (256 >> 4 >> 1) + (-64 >> n);
+}
@@ -0,0 +1 @@
+int main(void) { return (0 && 1 / 0) + (1 || 1 / 0) * 2 + ((3 && 0) == 0) * 4; }
@@ -0,0 +1,30 @@
+short halve(short s) {
+    return s / 2;
+}
+
+int main(void) {
+    short s = 40000;
+    unsigned short u = 40000;
+    long l = s;
+    unsigned long ul = u;
+    double d = s;
+    short back = -12345.75;
+    unsigned short wrapped = 65536 + 7;
+    if (s != -25536 || l != -25536)
+        return 1;
+    if (ul != 40000)
+        return 2;
+    if (d != -25536.0)
+        return 3;
+    if (back != -12345)
+        return 4;
+    if (halve(s) != -12768)
+        return 5;
+    switch (wrapped) {
+    case 7:
+        break;
+    default:
+        return 6;
+    }
+    return u - 39990 + wrapped;
+}
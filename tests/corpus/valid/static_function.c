@@ -0,0 +1,13 @@
+static int twice(int n);
+
+int tentative;
+int tentative;
+
+static int twice(int n) {
+    return 2 * n;
+}
+
+int main(void) {
+    tentative = twice(21);
+    return tentative;
+}
@@ -0,0 +1,11 @@
+int next(void) {
+    static int calls;
+    calls = calls + 1;
+    return calls;
+}
+
+int main(void) {
+    next();
+    next();
+    return next() * 10;
+}
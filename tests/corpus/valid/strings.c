@@ -0,0 +1,35 @@
+int putchar(int c);
+int puts(char *s);
+
+int length(char *s) {
+    int n = 0;
+    for (; s[n]; n = n + 1)
+        ;
+    return n;
+}
+
+int print(char *s) {
+    for (; *s; s = s + 1)
+        putchar(*s);
+    return 0;
+}
+
+int main(void) {
+    char *greeting = "hello, " "world";
+    puts(greeting);
+    print("tab\tquote\"backslash\\\n");
+    print("\x41\102\103\n");
+    if (length(greeting) != 12 || length("") != 0)
+        return 1;
+    if ('a' != 97 || '\n' != 10 || '\0' != 0 || '\'' != 39)
+        return 2;
+    char *middle = &"abcdef"[2];
+    if (middle[0] != 'c' || "xyz"[2] != 'z')
+        return 3;
+    char *embedded = "one\0two";
+    if (length(embedded) != 3 || embedded[4] != 't')
+        return 4;
+    putchar('!');
+    putchar('\n');
+    return length("four") + '\x10';
+}
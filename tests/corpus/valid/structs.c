@@ -0,0 +1,56 @@
+struct point {
+    int x;
+    int y;
+};
+
+struct node {
+    char tag;
+    long value;
+    struct node *next;
+};
+
+static struct point origin;
+
+int manhattan(struct point *p) {
+    return p->x + p->y;
+}
+
+long total(struct node *list) {
+    long sum = 0;
+    for (; list; list = list->next)
+        sum = sum + list->value;
+    return sum;
+}
+
+int main(void) {
+    struct point a;
+    a.x = 3;
+    a.y = 4;
+    if (manhattan(&a) != 7 || origin.x != 0)
+        return 1;
+    struct point b = a;
+    b.y = 10;
+    if (a.y != 4 || b.x != 3 || b.y != 10)
+        return 2;
+    struct node nodes[3];
+    for (int i = 0; i < 3; i = i + 1) {
+        nodes[i].tag = 65 + i;
+        nodes[i].value = (i + 1) * 100;
+        nodes[i].next = i < 2 ? &nodes[i + 1] : 0;
+    }
+    if (total(nodes) != 600 || nodes[2].tag != 67)
+        return 3;
+    struct outer {
+        struct point corner;
+        short flags[3];
+    };
+    struct outer box;
+    box.corner = b;
+    box.flags[2] = 5;
+    struct outer *pbox = &box;
+    if (pbox->corner.y != 10 || (&box.corner)->x != 3 || pbox->flags[2] != 5)
+        return 4;
+    origin = pbox->corner;
+    *&a = origin;
+    return a.x + a.y + nodes[1].next->tag;
+}
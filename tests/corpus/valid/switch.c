@@ -0,0 +1,19 @@
+int main(void) {
+    int total = 0;
+    for (int i = 0; i < 6; i = i + 1) {
+        switch (i % 4) {
+        case 0:
+            total = total + 1;
+        case 1 + 1:
+            total = total + 10;
+            break;
+        case -1:
+            return 99;
+        default:
+            if (i == 5) continue;
+            total = total + 100;
+        }
+        total = total + 1000;
+    }
+    return total % 256;
+}
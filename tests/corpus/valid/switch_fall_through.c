@@ -0,0 +1,22 @@
+int main(void) {
+    int n = 5;
+    int steps = 0;
+    switch (n % 3) {
+        int unused;
+    default:
+        steps = 100;
+    case 2:
+        for (int i = 0; i < n; i = i + 1) {
+            switch (i) {
+            case 1:
+                continue;
+            case 3:
+                break;
+            }
+            steps = steps + 1;
+        }
+    case 0:
+        steps = steps * 2;
+    }
+    return steps;
+}
@@ -0,0 +1,3 @@
+int unused(void) { return 9; }
+
+int main(void) { return 3; }
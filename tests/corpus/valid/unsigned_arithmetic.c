@@ -0,0 +1,19 @@
+unsigned int hash(unsigned int h, int c) {
+    return h * 31u + c;
+}
+
+int main(void) {
+    unsigned int h = 2166136261u;
+    for (int i = 0; i < 100; i = i + 1)
+        h = hash(h, i);
+    unsigned long big = 18446744073709551615ul;
+    if (big / 3 != 6148914691236517205ul)
+        return 1;
+    if (big % 1000 != 615)
+        return 2;
+    if (h / 16 % 2 > 1u)
+        return 3;
+    if (-1 < 0u)
+        return 4;
+    return h % 251;
+}
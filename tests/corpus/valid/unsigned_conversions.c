@@ -0,0 +1,27 @@
+static unsigned long limit = -1;
+
+int main(void) {
+    unsigned int u = -3;
+    long widened = u;
+    int back = u;
+    unsigned long mixed = u + 1l;
+    signed long s = -2;
+    unsigned long reinterpreted = s;
+    if (widened != 4294967293l)
+        return 1;
+    if (back != -3)
+        return 2;
+    if (mixed != 4294967294ul)
+        return 3;
+    if (limit != 18446744073709551615UL)
+        return 4;
+    if (reinterpreted / 2 != 9223372036854775807UL)
+        return 5;
+    if (s / 2 != -1)
+        return 6;
+    switch (u) {
+    case 4294967293u:
+        return 7;
+    }
+    return 8;
+}
@@ -0,0 +1,12 @@
+int
+main
+(
+void
+)
+{
+return
+1
++
+2
+;
+}
@@ -0,0 +1,328 @@
+//! Property tests: the front end turns any input into a result or a structured
+//! error, never a panic, and random well-formed expressions compile to programs
+//! that exit with what a reference evaluator computes for them, as the TACKY
+//! interpreter also finds.
+use assert_cmd::Command;
+use crumb::{
+    compile_to_assembly,
+    compiler::{
+        lexer::Span,
+        optimize::Pipeline,
+        parser::{
+            BinaryOp, Block, BlockItem, CType, Const, Exp, FunDefC, ProgramC, StatementC,
+            TopLevelC, UnaryOp,
+        },
+        timings::Timings,
+        unparse,
+    },
+    gen_tacky, interpret, lex, parse, CompileError, CompileOptions, InterpretError, Symbol,
+};
+use proptest::{prelude::*, sample::select};
+
+/// C source made of real tokens, glued together with and without spaces.
+const PIECES: [&str; 38] = [
+    "int",
+    "void",
+    "return",
+    "main",
+    "f",
+    "(",
+    ")",
+    "{",
+    "}",
+    ";",
+    "-",
+    "--",
+    "~",
+    "+",
+    "*",
+    "/",
+    "%",
+    "&",
+    "|",
+    "^",
+    "<",
+    ">",
+    "<=",
+    ">=",
+    "==",
+    "!=",
+    "<<",
+    ">>",
+    "&&",
+    "||",
+    "?",
+    ":",
+    "=",
+    "0",
+    "7",
+    "2147483647",
+    "2147483648",
+    " ",
+];
+
+/// compiles `source` in memory, checking that a failure is a lexing, parsing or
+/// semantic error pointing inside the source.
+fn assert_structured(source: &str) -> Result<(), TestCaseError> {
+    match compile_to_assembly(source, &CompileOptions::default()) {
+        Ok(_) => Ok(()),
+        Err(
+            e @ (CompileError::Lex { .. }
+            | CompileError::Parse { .. }
+            | CompileError::Semantic { .. }),
+        ) => {
+            let diagnostics = e.diagnostics();
+            prop_assert!(!diagnostics.is_empty(), "{}", e);
+            for diagnostic in diagnostics {
+                let span = diagnostic.span;
+                prop_assert!(span.start <= span.end && span.end <= source.len(), "{}", e);
+            }
+            Ok(())
+        }
+        Err(e) => Err(TestCaseError::fail(format!("unexpected error {}", e))),
+    }
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_bytes_are_structured_errors(bytes in any::<Vec<u8>>()) {
+        assert_structured(&String::from_utf8_lossy(&bytes))?;
+    }
+
+    #[test]
+    fn token_soup_is_structured_errors(pieces in proptest::collection::vec(select(&PIECES[..]), 0..48)) {
+        assert_structured(&pieces.concat())?;
+    }
+
+    #[test]
+    fn valid_prefix_then_token_soup(pieces in proptest::collection::vec(select(&PIECES[10..]), 0..24)) {
+        assert_structured(&format!("int main(void) {{ return {}; }}", pieces.concat()))?;
+    }
+}
+
+/// constants are mostly small, so that few expressions overflow, with the odd
+/// large one to reach the edges of `int`.
+fn constant() -> impl Strategy<Value = i32> {
+    prop_oneof![4 => 0..=16, 1 => 0..=i32::MAX]
+}
+
+fn unary_op() -> impl Strategy<Value = UnaryOp> {
+    prop_oneof![Just(UnaryOp::Negate), Just(UnaryOp::BitwiseComplement)]
+}
+
+fn binary_op() -> impl Strategy<Value = BinaryOp> {
+    prop_oneof![
+        Just(BinaryOp::Add),
+        Just(BinaryOp::Subtract),
+        Just(BinaryOp::Multiply),
+        Just(BinaryOp::Divide),
+        Just(BinaryOp::Remainder),
+        Just(BinaryOp::BitwiseAnd),
+        Just(BinaryOp::BitwiseOr),
+        Just(BinaryOp::BitwiseXor),
+        Just(BinaryOp::LeftShift),
+        Just(BinaryOp::RightShift),
+        Just(BinaryOp::LessThan),
+        Just(BinaryOp::GreaterThan),
+        Just(BinaryOp::LessOrEqual),
+        Just(BinaryOp::GreaterOrEqual),
+        Just(BinaryOp::Equal),
+        Just(BinaryOp::NotEqual),
+        Just(BinaryOp::And),
+        Just(BinaryOp::Or),
+    ]
+}
+
+/// expression trees shrink towards their subtrees and smaller constants.
+fn expression() -> impl Strategy<Value = Exp> {
+    let leaf = constant().prop_map(|int| Exp::Const {
+        c: Const::Int { int },
+        span: Span::default(),
+    });
+    leaf.prop_recursive(6, 48, 2, |inner| {
+        prop_oneof![
+            (unary_op(), inner.clone()).prop_map(|(op, exp)| Exp::Unary {
+                op,
+                exp: Box::new(exp),
+                span: Span::default(),
+            }),
+            (binary_op(), inner.clone(), inner.clone()).prop_map(|(op, l_exp, r_exp)| {
+                Exp::Binary {
+                    op,
+                    l_exp: Box::new(l_exp),
+                    r_exp: Box::new(r_exp),
+                    span: Span::default(),
+                }
+            }),
+            (inner.clone(), inner.clone(), inner).prop_map(|(condition, then_exp, else_exp)| {
+                Exp::Conditional {
+                    condition: Box::new(condition),
+                    then_exp: Box::new(then_exp),
+                    else_exp: Box::new(else_exp),
+                    span: Span::default(),
+                }
+            }),
+        ]
+    })
+}
+
+/// what C says the expression evaluates to, or `None` if evaluating it is
+/// undefined: signed overflow, or dividing by zero, unless `&&`, `||` or `?:`
+/// skip it.
+fn evaluate(exp: &Exp) -> Option<i32> {
+    match exp {
+        Exp::Const {
+            c: Const::Int { int },
+            ..
+        } => Some(*int),
+        Exp::Unary { op, exp, .. } => {
+            let v = evaluate(exp)?;
+            match op {
+                UnaryOp::Negate => v.checked_neg(),
+                UnaryOp::BitwiseComplement => Some(!v),
+            }
+        }
+        Exp::Binary {
+            op: BinaryOp::And,
+            l_exp,
+            r_exp,
+            ..
+        } => match evaluate(l_exp)? {
+            0 => Some(0),
+            _ => Some((evaluate(r_exp)? != 0) as i32),
+        },
+        Exp::Binary {
+            op: BinaryOp::Or,
+            l_exp,
+            r_exp,
+            ..
+        } => match evaluate(l_exp)? {
+            0 => Some((evaluate(r_exp)? != 0) as i32),
+            _ => Some(1),
+        },
+        Exp::Binary {
+            op, l_exp, r_exp, ..
+        } => {
+            let (l, r) = (evaluate(l_exp)?, evaluate(r_exp)?);
+            match op {
+                BinaryOp::Add => l.checked_add(r),
+                BinaryOp::Subtract => l.checked_sub(r),
+                BinaryOp::Multiply => l.checked_mul(r),
+                BinaryOp::Divide => l.checked_div(r),
+                BinaryOp::Remainder => l.checked_rem(r),
+                BinaryOp::BitwiseAnd => Some(l & r),
+                BinaryOp::BitwiseOr => Some(l | r),
+                BinaryOp::BitwiseXor => Some(l ^ r),
+                // a count past the width, or a negative value shifted left, is
+                // undefined; a negative one shifted right fills with ones, as
+                // every compiler for x86-64 has it
+                BinaryOp::LeftShift | BinaryOp::RightShift if !(0..32).contains(&r) => None,
+                BinaryOp::LeftShift if l < 0 => None,
+                BinaryOp::LeftShift => i32::try_from(i64::from(l) << r).ok(),
+                BinaryOp::RightShift => Some(l >> r),
+                BinaryOp::LessThan => Some((l < r) as i32),
+                BinaryOp::GreaterThan => Some((l > r) as i32),
+                BinaryOp::LessOrEqual => Some((l <= r) as i32),
+                BinaryOp::GreaterOrEqual => Some((l >= r) as i32),
+                BinaryOp::Equal => Some((l == r) as i32),
+                BinaryOp::NotEqual => Some((l != r) as i32),
+                BinaryOp::And | BinaryOp::Or => unreachable!(),
+            }
+        }
+        Exp::Conditional {
+            condition,
+            then_exp,
+            else_exp,
+            ..
+        } => match evaluate(condition)? {
+            0 => evaluate(else_exp),
+            _ => evaluate(then_exp),
+        },
+        Exp::Const { .. } | Exp::Cast { .. } => unreachable!("only ints are generated"),
+        Exp::Var { .. }
+        | Exp::Assignment { .. }
+        | Exp::CompoundAssignment { .. }
+        | Exp::FunctionCall { .. }
+        | Exp::Dereference { .. }
+        | Exp::AddrOf { .. }
+        | Exp::Increment { .. }
+        | Exp::Subscript { .. }
+        | Exp::String { .. }
+        | Exp::Dot { .. }
+        | Exp::Arrow { .. } => unreachable!(
+            "no variables, calls, pointers, arrays, strings or structures are generated"
+        ),
+    }
+}
+
+fn main_returning(exp: Exp) -> String {
+    unparse::program(&ProgramC {
+        items: vec![TopLevelC::Function {
+            fundef: FunDefC {
+                identifier: Symbol::intern("main"),
+                ret: CType::Int,
+                params: vec![],
+                body: Block {
+                    items: vec![BlockItem::Statement {
+                        stmt: StatementC::Return {
+                            exp: Box::new(exp),
+                            span: Span::default(),
+                        },
+                    }],
+                    span: Span::default(),
+                },
+                storage: None,
+                span: Span::default(),
+            },
+        }],
+    })
+}
+
+/// compiles and runs `source` from standard input, returning its exit status.
+fn run_status(source: &str, level: &str) -> Option<i32> {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["-", level, "--run"])
+        .write_stdin(source)
+        .output()
+        .unwrap()
+        .status
+        .code()
+}
+
+/// interprets the TACKY for `source`, as generated or optimized at -O2.
+fn interpreted(source: &str, optimized: bool) -> Result<i32, InterpretError> {
+    let tacky = gen_tacky(parse(lex(source).unwrap()).unwrap());
+    match optimized {
+        false => interpret(&tacky),
+        true => interpret(&Pipeline::new(2, &[], &[]).optimize(tacky, &mut Timings::default())),
+    }
+}
+
+proptest! {
+    #[test]
+    fn interpreter_matches_reference(exp in expression()) {
+        let expected = evaluate(&exp);
+        prop_assume!(expected.is_some());
+        let source = main_returning(exp);
+        for optimized in [false, true] {
+            prop_assert_eq!(interpreted(&source, optimized), Ok(expected.unwrap()), "{}", source);
+        }
+    }
+}
+
+proptest! {
+    // each case assembles, links and runs two programs
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn expressions_match_reference(exp in expression()) {
+        let expected = evaluate(&exp);
+        prop_assume!(expected.is_some());
+        let expected = expected.unwrap() & 0xff;
+        let source = main_returning(exp);
+        for level in ["-O0", "-O2"] {
+            prop_assert_eq!(run_status(&source, level), Some(expected), "{} {}", level, source);
+        }
+    }
+}
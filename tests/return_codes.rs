@@ -1,37 +1,116 @@
+// the expected values deliberately mirror the C source expressions verbatim
+#![allow(
+    clippy::identity_op,
+    clippy::erasing_op,
+    clippy::precedence,
+    clippy::modulo_one
+)]
+
 use assert_cmd::Command;
-use std::{io::Write, str};
+use crumb::compiler::{optimize::Pipeline, timings::Timings};
+use std::{io::Write, process::Stdio, str};
 use tempfile::{NamedTempFile, TempDir};
 
-fn return_exitcode(source: &str) -> i32 {
+fn return_exitcode(source: &str, flags: &[&str]) -> i32 {
+    assert_round_trip(source);
     let tmpdir = TempDir::new().unwrap();
     let mut tmpsource = NamedTempFile::with_suffix_in(r".c", tmpdir.path()).unwrap();
     write!(tmpsource, "{}", source).unwrap();
     let source_name = tmpsource.path().to_str().unwrap();
+    let binary_name = source_name.strip_suffix(r".c").unwrap();
 
     let compile_res_vec = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
         .arg(source_name)
+        .args(flags)
+        .args(["-o", binary_name])
         .ok()
         .unwrap()
         .stdout;
     let compile_res_str = str::from_utf8(&compile_res_vec).unwrap();
     assert!(!compile_res_str.starts_with("(!)"));
 
-    let binary_name = source_name.strip_suffix(r".c").unwrap();
-
-    match Command::new(binary_name).ok() {
+    let exitcode = match Command::new(binary_name).ok() {
         Ok(out) => out.status.code().unwrap(),
         Err(e) => e.as_output().unwrap().status.code().unwrap(),
+    };
+    assert_interpreted(source, exitcode);
+    exitcode
+}
+
+/// checks that interpreting the TACKY for `source`, as generated and after
+/// every optimization, gives the exit status the compiled program did.
+fn assert_interpreted(source: &str, exitcode: i32) {
+    let tacky = || {
+        let analyzed = crumb::analyze(crumb::parse(crumb::lex(source).unwrap()).unwrap());
+        crumb::gen_tacky(analyzed.unwrap())
+    };
+    let optimized = Pipeline::new(2, &[], &[]).optimize(tacky(), &mut Timings::default());
+    for (tacky, stage) in [(tacky(), "generated"), (optimized, "optimized")] {
+        let result = crumb::interpret(&tacky).unwrap_or_else(|e| panic!("{}: {}", e, source));
+        assert_eq!(result & 0xff, exitcode, "{} TACKY for {}", stage, source);
     }
 }
 
+/// checks that `source` printed back out with --emit-c parses to the same AST,
+/// and prints the same again.
+fn assert_round_trip(source: &str) {
+    let crumb = |input: &str, flag: &str| {
+        let stdout = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .args(["-", flag])
+            .write_stdin(input)
+            .ok()
+            .unwrap()
+            .stdout;
+        String::from_utf8(stdout).unwrap()
+    };
+    let printed = crumb(source, "--emit-c");
+    assert_eq!(
+        crumb(&printed, "--emit-ast"),
+        crumb(source, "--emit-ast"),
+        "{}",
+        printed
+    );
+    assert_eq!(crumb(&printed, "--emit-c"), printed);
+}
+
+/// runs the compiler on `source` from the directory holding it, reporting whether
+/// it exited successfully and which files it left beside the source.
+fn stage_run(source: &str, flags: &[&str]) -> (bool, Vec<String>) {
+    let tmpdir = TempDir::new().unwrap();
+    let mut tmpsource = NamedTempFile::with_suffix_in(r".c", tmpdir.path()).unwrap();
+    write!(tmpsource, "{}", source).unwrap();
+
+    let status = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .current_dir(tmpdir.path())
+        .arg(tmpsource.path())
+        .args(flags)
+        .output()
+        .unwrap()
+        .status;
+    let written = std::fs::read_dir(tmpdir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| !name.ends_with(".c"))
+        .collect();
+    (status.success(), written)
+}
+
 macro_rules! basic_mainret {
     ($name:tt, $str:expr, $res:expr) => {
+        basic_mainret!($name, $str, $res, []);
+    };
+    ($name:tt, $str:expr, $res:expr, [$($flag:expr),*]) => {
         #[test]
         fn $name() {
             let source_code: &str = &format!("int main(void) {{ return {}; }}", $str);
             let expected_bytes: i8 = $res; // voodoo done as unary - cannot be done on a u8. IAFM.
-            assert_eq!((expected_bytes as u8) as i32, return_exitcode(source_code));
+            assert_eq!(
+                (expected_bytes as u8) as i32,
+                return_exitcode(source_code, &[$($flag),*])
+            );
         }
     };
 }
@@ -89,3 +168,1917 @@ basic_mainret!(
     "1 + 2 | 2 + 1",
     1 + 2 | 2 + 1
 );
+
+basic_mainret!(return_one_less_than_two, "1 < 2", 1);
+basic_mainret!(return_negative_greater_than_one, "-1 > 1", 0);
+basic_mainret!(return_two_less_or_equal_two, "2 <= 2", 1);
+basic_mainret!(return_one_greater_or_equal_two, "1 >= 2", 0);
+basic_mainret!(return_three_equal_three, "3 == 3", 1);
+basic_mainret!(return_three_not_equal_three, "3 != 3", 0);
+basic_mainret!(
+    return_comparisons_by_precedence,
+    "1 + 1 == 2 > 1 != 0 < 1",
+    1
+);
+basic_mainret!(return_and_true, "2 && -1", 1);
+basic_mainret!(return_or_false, "0 || 0", 0);
+basic_mainret!(return_and_skips_trap, "0 && 1 / 0", 0);
+basic_mainret!(return_or_skips_trap, "3 || 1 % 0", 1);
+basic_mainret!(return_logical_by_precedence, "1 || 0 && 0 | 2 == 2", 1);
+basic_mainret!(optimized_return_or_skips_trap, "0 < 1 || 1 / 0", 1, ["-O2"]);
+basic_mainret!(
+    optimized_return_comparison_chain,
+    "(1 < 2) + (2 < 1) * 4 + (5 >= 5) * 8",
+    9,
+    ["-O2"]
+);
+
+basic_mainret!(return_conditional, "1 < 2 ? 3 : 4", 3);
+basic_mainret!(
+    return_conditional_skips_trap,
+    "0 ? 1 / 0 : 5 ? 6 : 1 % 0",
+    6
+);
+basic_mainret!(return_conditional_right_associative, "0 ? 1 : 0 ? 2 : 3", 3);
+basic_mainret!(return_conditional_below_or, "1 || 0 ? -2 + 1 : 8", -1);
+basic_mainret!(
+    optimized_return_conditional,
+    "(1 > 2 ? 3 : 4) * 5",
+    20,
+    ["-O2"]
+);
+
+/// with constant folding off, each branch between two constants is left for
+/// codegen, which turns it into a conditional move from -O1.
+#[test]
+fn return_conditional_move() {
+    let source = "int main(void) { return (3 > 2 ? 10 : 20) + (2 > 3 ? 30 : 40); }";
+    let unfolded = ["-O", "--disable-pass", "constant-folding"];
+    assert_eq!(return_exitcode(source, &unfolded), 50);
+    assert_eq!(
+        return_exitcode(source, &[&unfolded[..], &["--emit", "obj"]].concat()),
+        50
+    );
+}
+
+#[test]
+fn return_from_if_else() {
+    let source = "int main(void) { if (1 < 2) return 3; else return 4; }";
+    assert_eq!(return_exitcode(source, &[]), 3);
+    assert_eq!(return_exitcode(source, &["-O2"]), 3);
+    let source = "int main(void) { if (2 < 1) return 3; else return 4; }";
+    assert_eq!(return_exitcode(source, &[]), 4);
+    assert_eq!(return_exitcode(source, &["--emit", "obj"]), 4);
+}
+
+#[test]
+fn return_zero_past_untaken_if() {
+    let source = "int main(void) { if (0 && 1 / 0) return 7; }";
+    assert_eq!(return_exitcode(source, &[]), 0);
+    assert_eq!(return_exitcode(source, &["-O2"]), 0);
+}
+
+#[test]
+fn return_from_else_if_chain() {
+    let source = "int main(void) { if (0) return 1; else if (2 > 3) return 2; \
+                  else if (1) if (0) return 3; else return 4; else return 5; }";
+    assert_eq!(return_exitcode(source, &[]), 4);
+    assert_eq!(return_exitcode(source, &["-O2"]), 4);
+}
+
+#[test]
+fn return_assigned_variables() {
+    let source = "int main(void) { int a = 3; int b; b = a = a * 4; a = a - 2; return a + b; }";
+    assert_eq!(return_exitcode(source, &[]), 22);
+    assert_eq!(return_exitcode(source, &["-O2"]), 22);
+}
+
+/// an inner declaration shadows an outer one only until its block ends.
+#[test]
+fn return_through_shadowing_blocks() {
+    let source = "int main(void) { int a = 1; { int a = 2; { a = a + 5; } if (a == 7) { int a; a = 30; } }                   int tmp = a + 10; ; return tmp * 2; }";
+    assert_eq!(return_exitcode(source, &[]), 22);
+    assert_eq!(return_exitcode(source, &["-O2"]), 22);
+    assert_eq!(return_exitcode(source, &["-O", "--emit", "obj"]), 22);
+}
+
+/// a variable assigned in one arm of an `if` keeps the value it had in the other.
+#[test]
+fn return_variable_assigned_in_branch() {
+    let source =
+        "int main(void) { int x = 5; int y = 0; if (x > 3) y = x * 2; else x = 1; return x + y; }";
+    assert_eq!(return_exitcode(source, &[]), 15);
+    assert_eq!(return_exitcode(source, &["-O2"]), 15);
+    let source =
+        "int main(void) { int x = 2; int y = 0; if (x > 3) y = x * 2; else x = 1; return x + y; }";
+    assert_eq!(return_exitcode(source, &["-O2"]), 1);
+}
+
+/// each clause of a `for` may be left out, and a variable its initializer
+/// declares shadows one outside the loop only until the loop ends.
+#[test]
+fn return_from_for_loops() {
+    let source = "int main(void) { int sum = 0; for (int i = 1; i <= 10; i = i + 1) sum = sum + i; return sum; }";
+    assert_eq!(return_exitcode(source, &[]), 55);
+    assert_eq!(return_exitcode(source, &["-O2"]), 55);
+    assert_eq!(return_exitcode(source, &["-O", "--emit", "obj"]), 55);
+    let source = "int main(void) { int n = 0; for (int i = 0; i < 4; i = i + 1) for (int j = i; j < 4; j = j + 1) n = n + 1; return n; }";
+    assert_eq!(return_exitcode(source, &[]), 10);
+    assert_eq!(return_exitcode(source, &["-O2"]), 10);
+    let source = "int main(void) { int i = 7; int k = 0; for (int i = 0; i < 3; i = i + 1) { int i = 1; k = k + i; } for (; k < 20; ) k = k * 2; return i + k; }";
+    assert_eq!(return_exitcode(source, &[]), 31);
+    assert_eq!(return_exitcode(source, &["-O2"]), 31);
+    let source = "int main(void) { int a = 1; for (;;) { a = a * 3; if (a > 50) return a; } }";
+    assert_eq!(return_exitcode(source, &[]), 81);
+    assert_eq!(return_exitcode(source, &["-O2"]), 81);
+}
+
+/// `continue` skips to a loop's post expression and `break` leaves the
+/// innermost loop, even one without a condition.
+#[test]
+fn return_through_break_and_continue() {
+    let source = "int main(void) { int sum = 0; for (int i = 0; ; i = i + 1) { if (i % 2) continue; if (i > 10) break; sum = sum + i; } return sum; }";
+    assert_eq!(return_exitcode(source, &[]), 30);
+    assert_eq!(return_exitcode(source, &["-O2"]), 30);
+    assert_eq!(return_exitcode(source, &["-O", "--emit", "obj"]), 30);
+    let source = "int main(void) { int n = 0; for (int i = 0; i < 5; i = i + 1) for (;;) { n = n + i; break; } return n; }";
+    assert_eq!(return_exitcode(source, &[]), 10);
+    assert_eq!(return_exitcode(source, &["-O2"]), 10);
+}
+
+/// a `switch` jumps to the matching case and falls through from there, to
+/// the default when nothing matches, and past its body without one.
+#[test]
+fn return_from_switch() {
+    let source = "int main(void) { int total = 0; for (int i = 0; i < 6; i = i + 1) { switch (i % 4) { case 0: total = total + 1; case 1 + 1: total = total + 10; break; case -1: return 99; default: if (i == 5) continue; total = total + 100; } total = total + 1000; } return total % 256; }";
+    assert_eq!(return_exitcode(source, &[]), 112);
+    assert_eq!(return_exitcode(source, &["-O2"]), 112);
+    assert_eq!(return_exitcode(source, &["-O", "--emit", "obj"]), 112);
+    let source = "int main(void) { int x = 7; switch (x) { case 1: return 1; case 2: return 2; } switch (3) default: x = x * 2; return x; }";
+    assert_eq!(return_exitcode(source, &[]), 14);
+    assert_eq!(return_exitcode(source, &["-O2"]), 14);
+}
+
+/// arguments past the sixth go on the stack, an odd number of them padded to
+/// keep it aligned, and each call gets variables of its own, so recursion works
+/// under every frame layout.
+#[test]
+fn return_from_calls() {
+    let source = "int weigh(int a, int b, int c, int d, int e, int f, int g, int h) { return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h; } int last(int a, int b, int c, int d, int e, int f, int g) { return g - a; } int fib(int n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } int main(void) { return weigh(1, 2, 3, 4, 5, 6, 7, 8) - last(1, 2, 3, 4, 5, 6, 7) + fib(10); }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--omit-frame-pointer"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 253, "{:?}", flags);
+    }
+}
+
+/// at -O2 a call whose result is returned is a jump that reuses the caller's
+/// frame, so recursion this deep runs in constant stack, with arguments in
+/// registers, on the stack, and in XMM registers. A callee taking more stack
+/// arguments than its caller was passed is called as usual. The TACKY as
+/// generated would recurse once per call, so it isn't interpreted here.
+#[test]
+fn return_from_tail_calls() {
+    let source = "int count(int n, int acc) { if (n == 0) return acc; return count(n - 1, acc + 1); } long many(long a, long b, long c, long d, long e, long f, long g, long h) { if (a == 0) return b + c + d + e + f + g + h; return many(a - 1, b + 1, c, d, e, f, g + 2, h + 3); } double half(double x, int n) { if (n == 0) return x; return half(x / 2.0, n - 1); } long spill(long a) { return many(a, 1, 1, 1, 1, 1, 1, 1); } int main(void) { return (count(10000000, 0) == 10000000) + 2 * (many(10000000, 0, 0, 0, 0, 0, 0, 0) == 60000000) + 4 * (half(1024.0, 10) == 1.0) + 8 * (spill(3) == 25); }";
+    for flags in [
+        &["-O2"][..],
+        &["-O2", "--omit-frame-pointer"],
+        &["-O2", "--emit", "obj"],
+    ] {
+        let tmpdir = TempDir::new().unwrap();
+        let binary = tmpdir.path().join("tail");
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .args(["-", "-o", binary.to_str().unwrap()])
+            .args(flags)
+            .write_stdin(source)
+            .assert()
+            .success();
+        let status = std::process::Command::new(&binary).status().unwrap();
+        assert_eq!(status.code(), Some(15), "{:?}", flags);
+    }
+}
+
+/// a declaration lets a function be called before its definition, and adds
+/// nothing to the program itself.
+#[test]
+fn return_from_declared_functions() {
+    let source = "int odd(int n); int even(int n) { return n == 0 ? 1 : odd(n - 1); } int odd(int n) { return n == 0 ? 0 : even(n - 1); } int unused(int a, int b); int main(void) { return even(10) * 40 + odd(9) * 2; }";
+    for flags in [&[][..], &["-O2"], &["-O", "--emit", "obj"]] {
+        assert_eq!(return_exitcode(source, flags), 42, "{:?}", flags);
+    }
+}
+
+/// a variable with static storage keeps its value from call to call, whether it
+/// is declared at file scope or in a function, and `extern` reaches one defined
+/// later in the file.
+#[test]
+fn return_from_static_variables() {
+    let source = "int counter = 3; static int hidden; int tick(void) { static int calls = 10; calls = calls + 1; counter = counter + 1; return calls; } int later(void); int main(void) { extern int total; int a = tick(); int b = tick(); hidden = a + b; total = total + hidden; return total + counter + later(); } int total = 1; int later(void) { extern int counter; return counter; }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 34, "{:?}", flags);
+    }
+}
+
+/// a `long` keeps all 64 bits through arithmetic, calls and static storage, an
+/// `int` mixed in is sign-extended, and assigning a `long` to an `int` keeps the
+/// low 32 bits.
+#[test]
+fn return_from_long_arithmetic() {
+    let source = "static long total = 4294967296; long add(long a, int b) { return a + b; } int main(void) { int negative = -6; long sum = add(total, negative) * 3; int low = sum / 2; if (sum / 4294967296 != 2) return 1; if (sum % 1000000000 != 884901870) return 2; return low - 2147483623; }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 16, "{:?}", flags);
+    }
+}
+
+/// unsigned values divide and compare as unsigned, an `unsigned int` is
+/// zero-extended into a `long`, and -1 converted to unsigned is the largest
+/// value of its type.
+#[test]
+fn return_from_unsigned_arithmetic() {
+    let source = "static unsigned long total = 18446744073709551615ul; unsigned div(unsigned a, unsigned b) { return a / b; } int main(void) { unsigned int x = -10; unsigned long wide = x; long back = x; if (x > 5u) x = div(x, 1000000); if (wide % 4294967296 != 4294967286) return 1; if (back < 0) return 2; if (total / 2 != 9223372036854775807ul) return 3; if (-1 >= 1u) return x % 64; return 0; }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 6, "{:?}", flags);
+    }
+}
+
+/// `double`s are passed, returned and stored in full, integers convert to and
+/// from them in both directions, unsigned ones included, and negation flips
+/// the sign of a zero.
+#[test]
+fn return_from_double_arithmetic() {
+    let source = "static double third = 1.0 / 3; double mix(double a, long b, double c) { return a * b - c; } int main(void) { unsigned long big = 18446744073709549568ul; double d = big; unsigned long back = d; unsigned int u = 3e9; double neg = -0.0; if (back != big) return 1; if (u != 3000000000u) return 2; if (1 / neg > 0) return 3; if (third * 3 != 1.0) return 4; int r = mix(2.5, -4, third); return -r + (neg ? 100 : 0); }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 10, "{:?}", flags);
+    }
+}
+
+/// `char`s and `short`s are promoted to `int` for arithmetic, keep their low
+/// bits when assigned to, and are extended by their own signedness when passed.
+#[test]
+fn return_from_char_arithmetic() {
+    let source = "static char grade = -56; unsigned short pick(unsigned char a, short b) { return a * b; } int main(void) { unsigned char u = grade; short s = u * 300; char c = s; unsigned short w = -1; if (u != 200) return 1; if (w != 65535) return 2; if (c + 1 != 97) return 3; if (~s != 5535) return 4; return pick(grade, -3) % 100; }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 36, "{:?}", flags);
+    }
+}
+
+/// stores through a pointer reach the variable itself, whether it is a local
+/// of the caller, a static, or pointed to through a pointer to a pointer.
+#[test]
+fn return_through_pointers() {
+    let source = "static long total; long *add(long *sum, int *n) { *sum = *sum + *n; *n = 0; return sum; } int main(void) { int x = 7; int *p = &x; int **pp = &p; double d = 1.5; double *dp = &d; *dp = *dp * 4.0; long *t = add(&total, *pp); if (x != 0 || t != &total) return 1; **pp = 3; *add(t, &x) = *dp * 3.0 + 2; if (p == 0 || *dp != 6.0) return 2; return total + x; }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 20, "{:?}", flags);
+    }
+}
+
+/// array elements are found by scaling the index by the element size, whether
+/// that is a power of two or a whole row, and an array passed to a function
+/// decays to a pointer to its first element.
+#[test]
+fn return_through_arrays() {
+    let source = "static int counts[4]; int total(int *xs, int n) { int t = 0; for (int i = 0; i < n; i = i + 1) t = t + xs[i]; return t; } int main(void) { long m[3][3]; for (int i = 0; i < 3; i = i + 1) for (int j = 0; j < 3; j = j + 1) m[i][j] = i * 3 + j; int k = 2; long *row = m[k]; counts[k] = row[1] + m[1][k]; counts[3] = &m[2][2] - &m[0][1]; if (*(m[1] + 1) != 4 || row - *m != 6) return 1; return total(counts, 4); }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 19, "{:?}", flags);
+    }
+}
+
+/// string literals are read from their constants, escapes and all, with
+/// adjacent ones joined, and a character constant is the byte it names.
+#[test]
+fn return_through_strings() {
+    let source = r#"int count(char *s, int c) { int n = 0; for (; *s; s = s + 1) if (*s == c) n = n + 1; return n; } int main(void) { char *s = "a\tb" "\x61" "a\\"; return count(s, 'a') * 10 + count("\n\n\101", '\n') + "xyz"[2] - 'z' + s[1]; }"#;
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 41, "{:?}", flags);
+    }
+}
+
+/// a string literal initializes a `char` array with its bytes and nulls
+/// after them, none if it just fits, and a pointer with static storage with
+/// the address of its constant.
+#[test]
+fn return_through_string_initializers() {
+    let source = r#"char padded[8] = "abc"; char *p = "xyz"; static char full[3] = "abc"; int main(void) { char s[6] = "hi"; static char *q = "q"; char t[2] = "ok"; int sum = 0; for (int i = 0; i < 6; i = i + 1) sum = sum + s[i]; for (int i = 0; i < 8; i = i + 1) sum = sum + padded[i]; return sum - 'h' - 'i' - 'a' - 'b' - 'c' + p[2] + q[0] + q[1] + full[2] - t[1]; }"#;
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 227, "{:?}", flags);
+    }
+}
+
+/// a declaration may declare several variables, each with its own pointers,
+/// dimensions and initializer, which can use the ones before it, at file
+/// scope, in a block, and in a `for` loop's header.
+#[test]
+fn return_through_multiple_declarators() {
+    let source = "int g = 2, *gp, h[3]; int main(void) { int a = 1, b, c = a + 2; b = c * 10; gp = &g; h[2] = 5; int total = 0; for (int i = 0, j = 10; i < j; i = i + 1) total = total + i; static long s = 4, t; return a + b + c + *gp + h[2] + total + s + t; }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 90, "{:?}", flags);
+    }
+}
+
+/// structure members are read and written at their offsets, whether reached
+/// with `.` or `->`, and a whole structure is copied by assignment, the odd
+/// bytes at its end included.
+#[test]
+fn return_through_structs() {
+    let source = "struct rec { char c; short s; char name[5]; }; struct box { long id; struct rec r; }; static struct box saved; int sum(struct rec *r) { return r->c + r->s + r->name[4]; } int main(void) { struct box b; b.id = 2; b.r.c = 1; b.r.s = 300; b.r.name[4] = 7; saved = b; struct box *p = &saved; struct rec copy = p->r; copy.s = copy.s - 290; if (saved.r.s != 300 || p->id != 2) return 1; return sum(&copy) + sum(&saved.r) - 300; }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 26, "{:?}", flags);
+    }
+}
+
+/// a compound assignment reads and writes its object once, carrying out the
+/// operation in the common type and converting the result back, and moves a
+/// pointer along by elements.
+#[test]
+fn return_through_compound_assignment() {
+    let source = "static long total; int next(int *i) { *i += 1; return *i; } int main(void) { int i = 0; char c = 120; c += 10; unsigned short s = 1; s -= 2; long a[3]; a[0] = 0; a[1] = 2; a[2] = 4; long *p = a; p += 2; *p *= 3; a[next(&i)] ^= 7; total += *p; total /= 4; total %= 2; i |= 8; i &= 12; return c + s / 1000 + a[1] + total + i; }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 209, "{:?}", flags);
+    }
+}
+
+/// `++` and `--` before an operand give its new value and after it its old
+/// one, wrapping narrow integers and moving pointers along by elements.
+#[test]
+fn return_through_increments() {
+    let source = "int main(void) { int x = 3; int y = x++ * 10; y = y + ++x; unsigned char c = 0; c--; double d = 1.5; d++; long a[3]; long *p = a; *p++ = 7; *p++ = 8; *p = 9; p--; long q = *p--; short s = 32767; s++; for (int i = 0; i < 5; ++i) x--; return y + c / 5 + (d > 2) + q + *p + (s < 0) + x; }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 103, "{:?}", flags);
+    }
+}
+
+/// shifts keep the type of their left operand, filling from the sign bit only
+/// if it is signed, by a constant count or one in a variable, and fold in
+/// static initializers.
+#[test]
+fn return_through_shifts() {
+    let source = "static long bits = 1L << 33; int main(void) { int x = -100; unsigned u = 3000000000u; long n = 3; x >>= n; u >>= 28; unsigned char c = 1; c <<= 7; int k = 2; return (x == -13) + u + (c << k) / 64 + (bits >> 30) + (1u << 31 >> 31 == 1) + (5 >> k << 4); }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 45, "{:?}", flags);
+    }
+}
+
+/// casts extend by the signedness of what they convert, truncate, go to and
+/// from `double`, fold in static initializers, and reinterpret pointers.
+#[test]
+fn return_through_casts() {
+    let source = "static char low = (char)258; int main(void) { int x = -1; long l = (long)(unsigned)x; int i = 7; long addr = (long)&i; int *p = (int *)addr; return (l == 4294967295L) + low + (int)(unsigned char)x / 5 + (int)((double)*p / 2 * 10); }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 89, "{:?}", flags);
+    }
+}
+
+/// a `_Bool` holds 1 for anything nonzero, assigned, returned or cast, a
+/// pointer or a `double` too, and is set by `setne` from a comparison with zero.
+#[test]
+fn return_through_bools() {
+    let source = "static _Bool s = 300; _Bool test(double d) { return d; } int main(void) { long big = 1L << 35; _Bool b = big; int x = 0; _Bool p = &x; _Bool f = x; b += 2; return s + b * 2 + p * 4 + f * 8 + test(0.1) * 16 + (_Bool)-1 * 32; }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 55, "{:?}", flags);
+    }
+}
+
+/// comparisons with a NaN are unordered: only `!=` is true for one, whether
+/// the result is kept, converted to a `_Bool` or branched on.
+#[test]
+fn return_from_nan_comparisons() {
+    let source = "double quiet(double zero) { return zero / zero; } int main(void) { double n = quiet(0.0); double one = 1.0; if (n == n || n < one || n <= one || n > one || n >= one) return 100; if (one < n || one <= n || one == n) return 101; _Bool eq = n == one; _Bool ne = n != one; int r = (n == n) + (n != n) * 2 + eq * 4 + ne * 8 + (n < one) * 16 + (n >= one) * 32; return r + (one <= one) * 64 + (one < 2.0) * 128; }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 202, "{:?}", flags);
+    }
+}
+
+/// a NaN is nonzero: it converts to a `_Bool` of 1, and is true as a condition
+/// and an operand of `&&`, `||` and `?:`.
+#[test]
+fn return_through_nan_truthiness() {
+    let source = "double quiet(double zero) { return zero / zero; } int main(void) { double n = quiet(0.0); double z = 0.0; if (z || (z && n) || n == 0) return 100; _Bool b = n; int r = 0; if (n) r = r + 1; if (n && 1) r = r + 2; if (z || n) r = r + 4; r = r + (n ? 8 : 0); for (; n;) { r = r + 16; break; } return r + b * 32 + (_Bool)n * 64 + (z ? 128 : 0); }";
+    for flags in [
+        &[][..],
+        &["-O2"],
+        &["--syntax", "intel"],
+        &["-O", "--emit", "obj"],
+        &["-O2", "--omit-frame-pointer", "--emit", "obj"],
+    ] {
+        assert_eq!(return_exitcode(source, flags), 127, "{:?}", flags);
+    }
+}
+
+/// an expression as tall as the parser allows, a chain of 1024 terms, gets
+/// through every stage without overflowing the stack, in a debug build too, and
+/// one a term longer is a parse error.
+#[test]
+fn tallest_expression_compiles() {
+    let chain = |terms: usize| {
+        let ones = "1 + ".repeat(terms - 2);
+        format!("int main(void) {{ return {}1 - 1000; }}", ones)
+    };
+    let crumb = |source: &str, flags: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg("-")
+            .args(flags)
+            .write_stdin(source)
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+    let tallest = chain(1024);
+    for flags in [
+        &["--check"][..],
+        &["--emit-c"],
+        &["--emit-ast"],
+        &["--emit-tacky"],
+        &["-O2", "--emit-tacky"],
+        &["--codegen"],
+    ] {
+        assert_eq!(crumb(&tallest, flags), Some(0), "{:?}", flags);
+    }
+    let tmpdir = TempDir::new().unwrap();
+    let binary = tmpdir.path().join("tallest");
+    for flags in [&[][..], &["-O2", "--emit", "obj"]] {
+        let binary = binary.to_str().unwrap();
+        assert_eq!(crumb(&tallest, &[flags, &["-o", binary]].concat()), Some(0));
+        let status = std::process::Command::new(binary).status().unwrap();
+        assert_eq!(status.code(), Some(23), "{:?}", flags);
+    }
+    assert_eq!(crumb(&chain(1025), &["--parse"]), Some(3));
+}
+
+/// a warning from the preprocessor goes to stderr, leaving stdout to what
+/// the compiler writes there.
+#[test]
+fn tool_warnings_go_to_stderr() {
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["-", "--emit-tacky"])
+        .write_stdin("#warning careful\nint main(void) { return 0; }\n")
+        .output()
+        .unwrap();
+    let (stdout, stderr) = (
+        str::from_utf8(&output.stdout).unwrap(),
+        str::from_utf8(&output.stderr).unwrap(),
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stderr);
+    assert!(stdout.starts_with("function main:"), "{}", stdout);
+    assert!(stderr.contains("warning: #warning careful"), "{}", stderr);
+}
+
+/// semantic errors are all reported, each with where it went wrong, and fail
+/// with their stage's exit code.
+#[test]
+fn semantic_errors_reported() {
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["-", "--check"])
+        .write_stdin("int main(void) {\n  int a;\n  int a = b;\n  1 = a;\n}\n")
+        .output()
+        .unwrap();
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(4), "{}", stderr);
+    for message in [
+        "error: 'a' is already declared in this scope\n --> <stdin>:3:3\n",
+        "note: 'a' was first declared here\n --> <stdin>:2:3\n",
+        "error: use of undeclared variable 'b'\n --> <stdin>:3:11\n",
+        "error: the left side of '=' must be an lvalue\n --> <stdin>:4:3\n",
+    ] {
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["-", "--check"])
+        .write_stdin("int main(void) {\n  for (;;) break;\n  continue;\n}\n")
+        .output()
+        .unwrap();
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(4), "{}", stderr);
+    assert!(
+        stderr.contains("error: 'continue' outside of a loop\n --> <stdin>:3:3\n"),
+        "{}",
+        stderr
+    );
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["-", "--check"])
+        .write_stdin("int main(void) {\n  switch (1) {\n  case 4:\n  case 2 * 2: ;\n  }\n}\n")
+        .output()
+        .unwrap();
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(4), "{}", stderr);
+    for message in [
+        "error: duplicate case value 4\n --> <stdin>:4:8\n",
+        "note: case 4 was first used here\n --> <stdin>:3:8\n",
+    ] {
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+}
+
+basic_mainret!(
+    optimized_return_negcmpneg_two,
+    "-(~(-2))",
+    -(!(-2)),
+    ["-O2"]
+);
+basic_mainret!(
+    optimized_return_unary_and_binary,
+    "-(1 + 1) * ~(4 - 5)",
+    -(1 + 1) * !(4 - 5),
+    ["-O2"]
+);
+basic_mainret!(
+    optimized_return_big_bitwise_guy,
+    "1 ^ 2 & 3 | (4 | 5)",
+    1 ^ 2 & 3 | (4 | 5),
+    ["-O2"]
+);
+basic_mainret!(
+    optimized_return_folded_arithmetic,
+    "(3 + 4) * 2 - 6 / 3",
+    (3 + 4) * 2 - 6 / 3,
+    ["-O2"]
+);
+basic_mainret!(
+    optimized_return_wrapping_add,
+    "2147483647 + 2",
+    i32::MAX.wrapping_add(2) as i8,
+    ["-O2"]
+);
+basic_mainret!(
+    optimized_return_repeated_subexpression,
+    "(2 * ~3) + (~3 * 2) - (2 * ~3)",
+    (2 * !3) + (!3 * 2) - (2 * !3),
+    ["-O2"]
+);
+
+basic_mainret!(return_neg_seven_div_four, "-7 / 4", -7 / 4);
+basic_mainret!(return_neg_eight_div_four, "-8 / 4", -8 / 4);
+basic_mainret!(return_neg_one_div_two, "-1 / 2", -1 / 2);
+basic_mainret!(return_seven_div_four, "7 / 4", 7 / 4);
+basic_mainret!(return_neg_hundred_div_one, "-100 / 1", -100 / 1);
+basic_mainret!(return_neg_seven_rem_four, "-7 % 4", -7 % 4);
+basic_mainret!(return_neg_eight_rem_four, "-8 % 4", -8 % 4);
+basic_mainret!(return_neg_hundred_rem_sixteen, "-100 % 16", -100 % 16);
+basic_mainret!(return_hundred_rem_sixteen, "100 % 16", 100 % 16);
+basic_mainret!(return_neg_three_rem_one, "-3 % 1", -3 % 1);
+basic_mainret!(return_neg_three_times_eight, "-3 * 8", -3 * 8);
+basic_mainret!(return_eight_times_neg_three, "8 * -3", 8 * -3);
+basic_mainret!(
+    return_big_negative_div_pow2,
+    "-(2147483647) / 1073741824",
+    (-(2147483647) / 1073741824) as i8
+);
+basic_mainret!(
+    return_big_negative_rem_pow2,
+    "-(2147483647) % 1073741824 / 16777216",
+    (-(2147483647) % 1073741824 / 16777216) as i8
+);
+
+basic_mainret!(
+    return_long_addition_chain,
+    "1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 10 + 11 + 12 - 13 - 14 - 15",
+    1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 10 + 11 + 12 - 13 - 14 - 15
+);
+basic_mainret!(
+    return_nested_mixed_operands,
+    "(1 * 2 + 3) * (4 - 2 * 3) - (7 ^ 8) / (9 & 10 | 1) + (6 % 4) * (5 - 1)",
+    (1 * 2 + 3) * (4 - 2 * 3) - (7 ^ 8) / (9 & 10 | 1) + (6 % 4) * (5 - 1)
+);
+
+basic_mainret!(
+    intel_syntax_return_mixed_operands,
+    "(~7 * 3 - 100 / 7) % 9 ^ -2",
+    (!7 * 3 - 100 / 7) % 9 ^ -2,
+    ["--syntax", "intel"]
+);
+basic_mainret!(
+    intel_syntax_return_power_of_two_division,
+    "-7 / 4 + -9 % 8",
+    -7 / 4 + -9 % 8,
+    ["--syntax", "intel"]
+);
+
+basic_mainret!(
+    object_file_return_mixed_operands,
+    "(~7 * 3 - 100 / 7) % 9 ^ -2",
+    (!7 * 3 - 100 / 7) % 9 ^ -2,
+    ["--emit", "obj"]
+);
+basic_mainret!(
+    object_file_return_power_of_two_division,
+    "-7 / 4 + -9 % 8 * 2",
+    -7 / 4 + -9 % 8 * 2,
+    ["--emit", "obj"]
+);
+basic_mainret!(
+    object_file_return_bitwise,
+    "(5 & 12 | 3) ^ ~100 - 1000 / 3",
+    ((5 & 12 | 3) ^ !100i32 - 1000 / 3) as i8,
+    ["--emit", "obj"]
+);
+basic_mainret!(
+    optimized_object_file_return_long_chain,
+    "1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 10 + 11 + 12 - 13 - 14 - 15",
+    1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 10 + 11 + 12 - 13 - 14 - 15,
+    ["--emit", "obj", "-O"]
+);
+
+basic_mainret!(
+    frameless_return_mixed_operands,
+    "(~7 * 3 - 100 / 7) % 9 ^ -2",
+    (!7 * 3 - 100 / 7) % 9 ^ -2,
+    ["--omit-frame-pointer"]
+);
+basic_mainret!(
+    frameless_return_long_chain,
+    "1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 10 + 11 + 12 - 13 - 14 - 15",
+    1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 10 + 11 + 12 - 13 - 14 - 15,
+    ["--omit-frame-pointer"]
+);
+basic_mainret!(
+    frameless_object_file_return_division,
+    "-7 / 4 + -9 % 8 * 2",
+    -7 / 4 + -9 % 8 * 2,
+    ["--omit-frame-pointer", "--emit", "obj"]
+);
+basic_mainret!(
+    commented_return_mixed_operands,
+    "(~7 * 3 - 100 / 7) % 9 ^ -2",
+    (!7 * 3 - 100 / 7) % 9 ^ -2,
+    ["--asm-comments", "-O"]
+);
+
+#[test]
+fn return_from_main_after_other_function() {
+    let source = "int helper(void) { return 7 * 6; } int main(void) { return -(~3 * 2); }";
+    assert_eq!(return_exitcode(source, &[]), -(!3 * 2));
+    assert_eq!(return_exitcode(source, &["--emit", "obj"]), -(!3 * 2));
+}
+
+#[test]
+fn return_from_main_in_own_section() {
+    let source = "int helper(void) { return 7 * 6; } int main(void) { return -(~3 * 2); }";
+    assert_eq!(return_exitcode(source, &["--function-sections"]), -(!3 * 2));
+    assert_eq!(
+        return_exitcode(source, &["--function-sections", "--emit", "obj"]),
+        -(!3 * 2)
+    );
+}
+
+#[test]
+fn return_from_main_with_cf_protection() {
+    let source = "int helper(void) { return 7 * 6; } int main(void) { return -(~3 * 2); }";
+    assert_eq!(return_exitcode(source, &["--cf-protection"]), -(!3 * 2));
+    assert_eq!(
+        return_exitcode(source, &["--cf-protection", "--emit", "obj"]),
+        -(!3 * 2)
+    );
+}
+
+/// the assembly crumb writes for `source` with `flags`.
+fn assembly(source: &str, flags: &[&str]) -> String {
+    let stdout = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["-", "-S", "-o", "-"])
+        .args(flags)
+        .write_stdin(source)
+        .ok()
+        .unwrap()
+        .stdout;
+    String::from_utf8(stdout).unwrap()
+}
+
+/// gcc's `-f` spellings of the code generation flags do what the long ones do.
+#[test]
+fn gcc_flag_spellings() {
+    let source = "int helper(void) { return 7 * 6; } int main(void) { return helper(); }";
+    for (gcc, ours) in [
+        ("-fomit-frame-pointer", "--omit-frame-pointer"),
+        ("-ffunction-sections", "--function-sections"),
+        ("-fcf-protection", "--cf-protection"),
+    ] {
+        let expected = assembly(source, &[ours]);
+        assert_ne!(expected, assembly(source, &[]), "{}", ours);
+        assert_eq!(assembly(source, &[gcc]), expected, "{}", gcc);
+    }
+}
+
+/// a shared library reaches the variables other modules can see through the
+/// GOT, a static executable calls functions directly, and a PIE, the default,
+/// does neither; gcc's spellings choose each.
+#[test]
+fn relocation_models() {
+    let source = "int putchar(int c); int shared = 1; static int hidden = 2;
+        int main(void) { return shared + hidden + putchar(10); }";
+    let pie = assembly(source, &[]);
+    assert!(pie.contains("shared(%rip)"), "{}", pie);
+    assert!(pie.contains("call putchar@PLT"), "{}", pie);
+    let pic = assembly(source, &["--relocation-model", "pic"]);
+    assert!(pic.contains("movq shared@GOTPCREL(%rip), %rbx"), "{}", pic);
+    assert!(pic.contains("hidden(%rip)"), "{}", pic);
+    assert!(!pic.contains("hidden@GOTPCREL"), "{}", pic);
+    let no_pic = assembly(source, &["--relocation-model", "static"]);
+    assert!(no_pic.contains("call putchar\n"), "{}", no_pic);
+    for (gcc, expected) in [
+        ("-fpie", &pie),
+        ("-fPIE", &pie),
+        ("-fpic", &pic),
+        ("-fPIC", &pic),
+        ("-fno-pic", &no_pic),
+        ("-fno-pie", &no_pic),
+    ] {
+        assert_eq!(&assembly(source, &[gcc]), expected, "{}", gcc);
+    }
+}
+
+/// a balanced tree of `2^depth` negated leaves, each leaf and each operator
+/// lowering to a temporary of its own, and the value it computes.
+fn many_temporaries(depth: u32, next: &mut i32) -> (String, i32) {
+    if depth == 0 {
+        *next += 1;
+        return (format!("-{}", *next % 7), -(*next % 7));
+    }
+    let (l, lv) = many_temporaries(depth - 1, next);
+    let (r, rv) = many_temporaries(depth - 1, next);
+    match depth % 2 {
+        0 => (format!("({} + {})", l, r), lv.wrapping_add(rv)),
+        _ => (format!("({} - {})", l, r), lv.wrapping_sub(rv)),
+    }
+}
+
+/// 131071 temporaries in one function, past any 16-bit numbering of them.
+/// Unoptimized only: common subexpression elimination and copy propagation
+/// take time quadratic in the length of a block.
+#[test]
+fn return_from_many_temporaries() {
+    let (exp, value) = many_temporaries(16, &mut 0);
+    let source = format!("int main(void) {{ return {}; }}", exp);
+    let tmpdir = TempDir::new().unwrap();
+    let binary = tmpdir.path().join("many");
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["-", "-o"])
+        .arg(&binary)
+        .write_stdin(source.as_str())
+        .assert()
+        .success();
+    let exitcode = Command::new(&binary).output().unwrap().status.code();
+    assert_eq!(exitcode, Some(value & 0xff));
+    let analyzed = crumb::analyze(crumb::parse(crumb::lex(&source).unwrap()).unwrap());
+    assert_eq!(
+        crumb::interpret(&crumb::gen_tacky(analyzed.unwrap())),
+        Ok(value)
+    );
+}
+
+#[test]
+fn return_from_red_zone() {
+    let source = "int main(void) { return (~7 * 3 - 100 / 7) % 9 ^ -2; }";
+    let expected = (!7 * 3 - 100 / 7) % 9 ^ -2;
+    assert_eq!(return_exitcode(source, &["-O"]), expected as u8 as i32);
+    assert_eq!(
+        return_exitcode(source, &["-O", "--omit-frame-pointer"]),
+        expected as u8 as i32
+    );
+}
+
+#[test]
+fn stage_flags_stop_without_output() {
+    let source = "int main(void) { return 2; }";
+    for flag in [
+        "--lex",
+        "--parse",
+        "--check",
+        "--emit-ast",
+        "--tacky",
+        "--emit-tacky",
+        "--codegen",
+    ] {
+        assert_eq!(stage_run(source, &[flag]), (true, vec![]), "{}", flag);
+    }
+}
+
+#[test]
+fn stage_flags_fail_at_their_stage() {
+    let bad_token = "int main(void) { return 2 @ 3; }";
+    let bad_syntax = "int main(void) { return 2 + ; }";
+    assert_eq!(stage_run(bad_token, &["--lex"]), (false, vec![]));
+    assert_eq!(stage_run(bad_syntax, &["--lex"]), (true, vec![]));
+    assert_eq!(stage_run(bad_syntax, &["--parse"]), (false, vec![]));
+    assert_eq!(stage_run(bad_syntax, &["--codegen"]), (false, vec![]));
+}
+
+#[test]
+fn default_run_leaves_only_executable() {
+    let source = "int main(void) { return 2; }";
+    for flags in [&[][..], &["--emit", "obj"]] {
+        assert_eq!(
+            stage_run(source, flags),
+            (true, vec![String::from("a.out")])
+        );
+    }
+}
+
+#[test]
+fn assembly_flag_stops_before_linking() {
+    let (success, written) = stage_run("int main(void) { return 2; }", &["-S"]);
+    assert!(success);
+    assert_eq!(written.len(), 1);
+    assert!(written[0].ends_with(".s"));
+}
+
+#[test]
+fn linker_failure_is_reported() {
+    let tmpdir = TempDir::new().unwrap();
+    let mut tmpsource = NamedTempFile::with_suffix_in(r".c", tmpdir.path()).unwrap();
+    write!(tmpsource, "int not_main(void) {{ return 2; }}").unwrap();
+
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .current_dir(tmpdir.path())
+        .arg(tmpsource.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.starts_with("(!) assemble failed"), "{}", stderr);
+    assert!(stderr.contains("main"), "{}", stderr);
+}
+
+#[test]
+fn object_flag_stops_before_linking() {
+    let source = "int main(void) { return 2; }";
+    for flags in [&["-c"][..], &["-c", "--emit", "obj"]] {
+        let (success, written) = stage_run(source, flags);
+        assert!(success);
+        assert_eq!(written.len(), 1);
+        assert!(written[0].ends_with(".o"));
+    }
+}
+
+#[test]
+fn object_and_assembly_flags_conflict() {
+    let (success, written) = stage_run("int main(void) { return 2; }", &["-c", "-S"]);
+    assert!(!success);
+    assert!(written.is_empty());
+}
+
+/// only Linux object files can be written, and the error names the target the
+/// way `--target` spells it.
+#[test]
+fn object_files_only_for_linux() {
+    let tmpdir = TempDir::new().unwrap();
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["-", "-c", "--emit", "obj", "--target", "macos", "-o"])
+        .arg(tmpdir.path().join("main.o"))
+        .write_stdin("int main(void) { return 2; }")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(
+        stderr.contains("only be emitted for linux, not macos"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn output_flag_places_every_artifact() {
+    let source = "int main(void) { return 2; }";
+    let outdir = TempDir::new().unwrap();
+    for (flags, name) in [
+        (&[][..], "prog"),
+        (&["-S"], "prog.s"),
+        (&["-c"], "prog.o"),
+        (&["-c", "--emit", "obj"], "obj.o"),
+    ] {
+        let path = outdir.path().join(name);
+        let path = path.to_str().unwrap();
+        let mut flags = flags.to_vec();
+        flags.extend(["-o", path]);
+        assert_eq!(stage_run(source, &flags), (true, vec![]), "{:?}", flags);
+        assert!(std::fs::metadata(path).unwrap().len() > 0, "{}", path);
+    }
+    assert_eq!(
+        Command::new(outdir.path().join("prog"))
+            .output()
+            .unwrap()
+            .status
+            .code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn output_dash_writes_assembly_to_stdout() {
+    let tmpdir = TempDir::new().unwrap();
+    let mut tmpsource = NamedTempFile::with_suffix_in(r".c", tmpdir.path()).unwrap();
+    write!(tmpsource, "int main(void) {{ return 2; }}").unwrap();
+
+    let run = |flags: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .current_dir(tmpdir.path())
+            .arg(tmpsource.path())
+            .args(flags)
+            .output()
+            .unwrap()
+    };
+    let output = run(&["-S", "-o", "-"]);
+    assert!(output.status.success());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.contains("main:\n"), "{}", stdout);
+    assert_eq!(std::fs::read_dir(tmpdir.path()).unwrap().count(), 1);
+
+    assert!(!run(&["-o", "-"]).status.success());
+
+    // a reader that has gone away before anything is written isn't a failure
+    let mut child =
+        std::process::Command::new(assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")))
+            .args(["-", "-S", "-o", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+    drop(child.stdout.take());
+    let mut stdin = child.stdin.take().unwrap();
+    write!(stdin, "int main(void) {{ return 2; }}").unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr);
+}
+
+/// writes each `(name, source)` into a fresh directory, returning it and the paths.
+fn write_sources(files: &[(&str, &str)]) -> (TempDir, Vec<String>) {
+    let tmpdir = TempDir::new().unwrap();
+    let paths = files
+        .iter()
+        .map(|(name, source)| {
+            let path = tmpdir.path().join(name);
+            std::fs::write(&path, source).unwrap();
+            String::from(path.to_str().unwrap())
+        })
+        .collect();
+    (tmpdir, paths)
+}
+
+#[test]
+fn link_multiple_files() {
+    let (tmpdir, paths) = write_sources(&[
+        ("main.c", "int main(void) { return 6 * 7; }"),
+        ("lib.c", "int helper(void) { return 3; }"),
+    ]);
+    let binary = tmpdir.path().join("prog");
+    let crumb = |args: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .current_dir(tmpdir.path())
+            .args(args)
+            .ok()
+            .unwrap()
+    };
+    crumb(&[&paths[0], &paths[1], "-o", binary.to_str().unwrap()]);
+    let symbols = Command::new("nm").arg(&binary).ok().unwrap().stdout;
+    let symbols = str::from_utf8(&symbols).unwrap();
+    assert!(symbols.contains(" T main") && symbols.contains(" T helper"));
+    assert_eq!(
+        Command::new(&binary).output().unwrap().status.code(),
+        Some(42)
+    );
+
+    // a prebuilt object goes straight to the linker
+    crumb(&["-c", &paths[1]]);
+    let object = tmpdir.path().join("lib.o");
+    assert!(object.exists());
+    crumb(&[&paths[0], object.to_str().unwrap(), "--emit", "obj"]);
+    let symbols = Command::new("nm")
+        .arg("a.out")
+        .current_dir(tmpdir.path())
+        .ok();
+    assert!(str::from_utf8(&symbols.unwrap().stdout)
+        .unwrap()
+        .contains(" T helper"));
+}
+
+/// a variable is shared by every file that declares it without `static`, and
+/// each file's `static` one is its own, whatever the name.
+#[test]
+fn link_shared_variables() {
+    let (tmpdir, paths) = write_sources(&[
+        (
+            "main.c",
+            "int shared; static int own = 5; int set(void); int main(void) { int r = set(); return shared * 10 + own + r; }",
+        ),
+        (
+            "lib.c",
+            "extern int shared; static int own = 100; int set(void) { shared = 4; own = own + 1; return own; }",
+        ),
+    ]);
+    let binary = tmpdir.path().join("prog");
+    for flags in [&[][..], &["-O2", "--emit", "obj"]] {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .args(&paths)
+            .args(flags)
+            .args(["-o", binary.to_str().unwrap()])
+            .ok()
+            .unwrap();
+        assert_eq!(
+            Command::new(&binary).output().unwrap().status.code(),
+            Some(146),
+            "{:?}",
+            flags
+        );
+    }
+}
+
+#[test]
+fn errors_name_their_file() {
+    let (tmpdir, paths) = write_sources(&[
+        ("good.c", "int main(void) { return 2; }"),
+        ("bad.c", "int helper(void) { return 2 + ; }"),
+    ]);
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .current_dir(tmpdir.path())
+        .args(&paths)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!("--> {}:1:31\n", paths[1])),
+        "{}",
+        stderr
+    );
+    let left: Vec<_> = std::fs::read_dir(tmpdir.path()).unwrap().collect();
+    assert_eq!(left.len(), 2);
+}
+
+/// --check writes nothing, and reports what a real build would: warnings on a
+/// program that compiles, and every error in every file, with the exit code of
+/// the first file that failed.
+#[test]
+fn check_reports_without_writing() {
+    let (tmpdir, paths) = write_sources(&[
+        ("warns.c", "int main(void) { return 1 / 0; }"),
+        (
+            "semantic.c",
+            "int f(void) { return 1 % 0; }\nint g(void) { return 2 / 0; }",
+        ),
+        (
+            "syntax.c",
+            "int h(void) { return 2 + ; }\nint k(void) { return ); }",
+        ),
+    ]);
+    let crumb = |flags: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .current_dir(tmpdir.path())
+            .args(flags)
+            .output()
+            .unwrap()
+    };
+    let files = || std::fs::read_dir(tmpdir.path()).unwrap().count();
+
+    let output = crumb(&["--check", &paths[0]]);
+    assert!(output.status.success());
+    assert!(str::from_utf8(&output.stderr)
+        .unwrap()
+        .contains("warning: division by zero"));
+    assert_eq!(files(), 3);
+
+    let output = crumb(&["--check", "-Werror", &paths[1]]);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(
+        stderr.matches("error: division by zero").count(),
+        2,
+        "{}",
+        stderr
+    );
+    assert_eq!(
+        stderr,
+        str::from_utf8(&crumb(&["-S", "-Werror", &paths[1]]).stderr).unwrap()
+    );
+
+    let output = crumb(&["--check", "-Werror", &paths[1], &paths[2]]);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert!(
+        stderr.contains(&format!("--> {}:1:", paths[1])),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains(&format!("--> {}:1:", paths[2])),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains(&format!("--> {}:2:", paths[2])),
+        "{}",
+        stderr
+    );
+    assert_eq!(files(), 3);
+
+    assert!(!crumb(&["--check", "-S", &paths[0]]).status.success());
+    assert_eq!(files(), 3);
+}
+
+#[test]
+fn read_source_from_stdin() {
+    let tmpdir = TempDir::new().unwrap();
+    let binary = tmpdir.path().join("x");
+    let crumb = |source: &str, args: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .current_dir(tmpdir.path())
+            .arg("-")
+            .args(args)
+            .write_stdin(source)
+            .output()
+            .unwrap()
+    };
+    let source = "int main(void) { return 42; }";
+    assert!(crumb(source, &["-o", binary.to_str().unwrap()])
+        .status
+        .success());
+    assert_eq!(
+        Command::new(&binary).output().unwrap().status.code(),
+        Some(42)
+    );
+
+    let assembly = crumb(source, &["-S", "-o", "-"]);
+    assert!(str::from_utf8(&assembly.stdout)
+        .unwrap()
+        .contains("main:\n"));
+
+    let failed = crumb("int main(void) { return 42 + ; }", &[]);
+    assert!(!failed.status.success());
+    assert!(str::from_utf8(&failed.stderr)
+        .unwrap()
+        .contains("--> <stdin>:1:30\n"));
+    assert_eq!(std::fs::read_dir(tmpdir.path()).unwrap().count(), 1);
+}
+
+/// malformed input of every kind fails with a message and its stage's exit code, never a panic.
+#[test]
+fn malformed_input_is_an_error() {
+    let crumb = |source: Vec<u8>| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .args(["-", "-S", "-o", "-"])
+            .write_stdin(source)
+            .output()
+            .unwrap()
+    };
+    let garbage: Vec<u8> = (0..=255u8).rev().chain(0..=255).collect();
+    for (source, code) in [
+        (garbage, 1),
+        (
+            b"int main(void) { return 99999999999999999999; }".to_vec(),
+            2,
+        ),
+        (b"int main(void) { return 1 @ 2; }".to_vec(), 2),
+        (
+            b"int main(void) { return -(((((((((((1)))))))))))) }".to_vec(),
+            3,
+        ),
+        (
+            format!("int main(void) {{ return {}1; }}", "~".repeat(100_000)).into_bytes(),
+            3,
+        ),
+        (
+            format!("int main(void) {{ {} }}", "{".repeat(100_000)).into_bytes(),
+            3,
+        ),
+        (b"int main(void) { return a; }".to_vec(), 4),
+    ] {
+        let output = crumb(source);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(code), "{}", stderr);
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+/// higher levels never add instructions, and never change what the program returns.
+#[test]
+fn optimization_levels_agree() {
+    let sources = [
+        "int main(void) { return (3 + 4) * 2 - 6 / 3; }",
+        "int main(void) { return (2 * ~3) + (~3 * 2) - (2 * ~3); }",
+        "int main(void) { return (~7 * 3 - 100 / 7) % 9 ^ -2; }",
+        "int main(void) { return -(1 + 1) * ~(4 - 5) | 1 ^ 2 & 3; }",
+    ];
+    for source in sources {
+        let (tmpdir, paths) = write_sources(&[("main.c", source)]);
+        let mut counts = Vec::new();
+        let mut codes = Vec::new();
+        for level in ["-O0", "-O1", "-O2"] {
+            let assembly = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                .unwrap()
+                .args([&paths[0], level, "-S", "-o", "-"])
+                .ok()
+                .unwrap()
+                .stdout;
+            // instructions are the indented lines that aren't directives
+            counts.push(
+                str::from_utf8(&assembly)
+                    .unwrap()
+                    .lines()
+                    .filter(|line| line.starts_with('\t') && !line.starts_with("\t."))
+                    .count(),
+            );
+            let binary = tmpdir.path().join(level);
+            Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                .unwrap()
+                .args([&paths[0], level, "-o", binary.to_str().unwrap()])
+                .ok()
+                .unwrap();
+            codes.push(Command::new(&binary).output().unwrap().status.code());
+        }
+        assert!(
+            counts.windows(2).all(|w| w[0] >= w[1]),
+            "{source}: {counts:?}"
+        );
+        assert!(
+            codes.windows(2).all(|w| w[0] == w[1]),
+            "{source}: {codes:?}"
+        );
+    }
+}
+
+/// passes can be switched on and off whatever the level.
+#[test]
+fn pass_overrides() {
+    let (_tmpdir, paths) = write_sources(&[("main.c", "int main(void) { return 3 + 4; }")]);
+    let assembly = |flags: &[&str]| {
+        let stdout = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .args([&paths[0], "-S", "-o", "-"])
+            .args(flags)
+            .ok()
+            .unwrap()
+            .stdout;
+        String::from_utf8(stdout).unwrap()
+    };
+    assert!(!assembly(&["-O0"]).contains("$7"));
+    assert!(assembly(&["-O0", "--enable-pass", "constant-folding"]).contains("$7"));
+    assert!(!assembly(&["-O2", "--disable-pass", "constant-folding"]).contains("$7"));
+}
+
+/// every phase that ran, optimization passes included, gets a row, and the
+/// report still comes out when compilation fails.
+#[test]
+fn timings_report_each_phase() {
+    let (tmpdir, paths) = write_sources(&[("main.c", "int main(void) { return 3 + 4; }")]);
+    let timed = |flags: &[&str]| {
+        let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .current_dir(tmpdir.path())
+            .args(flags)
+            .arg(&paths[0])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let table = timed(&["--timings", "-O1"]);
+    let phases: Vec<&str> = table
+        .lines()
+        .map(|line| line.split_whitespace().next().unwrap())
+        .collect();
+    for phase in [
+        "phase",
+        "preprocess",
+        "parse",
+        "semantic",
+        "tacky",
+        "constant-folding",
+        "dead-stores",
+        "asmgen",
+        "emit",
+        "link",
+        "total",
+    ] {
+        assert!(phases.contains(&phase), "{}", table);
+    }
+    assert!(!phases.contains(&"peephole"), "{}", table);
+
+    let json = timed(&["--timings=json", "-c"]);
+    assert!(json.starts_with("{\"phases\":[{\"name\":\"preprocess\",\"microseconds\":"));
+    assert!(json.contains("{\"name\":\"assemble\",\"microseconds\":"));
+    assert!(json.trim_end().ends_with('}'));
+
+    std::fs::write(&paths[0], "int main(void) { return 3 + ; }").unwrap();
+    let failed = timed(&["--timings"]);
+    assert!(failed.contains("\nparse "), "{}", failed);
+    assert!(!failed.contains("\ntacky "), "{}", failed);
+}
+
+/// diagnostics are only colored on request when stderr isn't a terminal.
+#[test]
+fn color_flag_controls_escapes() {
+    let (_tmpdir, paths) = write_sources(&[("main.c", "int main(void) { return 2 @ 3; }")]);
+    let stderr = |flags: &[&str], no_color: Option<&str>| {
+        let mut command = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        command.arg(&paths[0]).args(flags).env_remove("NO_COLOR");
+        if let Some(value) = no_color {
+            command.env("NO_COLOR", value);
+        }
+        String::from_utf8(command.output().unwrap().stderr).unwrap()
+    };
+    assert!(stderr(&["--color=always"], None).starts_with("\x1b[1;31merror:\x1b[0m"));
+    assert!(stderr(&["--color=always"], Some("1")).contains('\x1b'));
+    assert!(stderr(&[], None).starts_with("error: unrecognized character '@'\n"));
+    assert!(!stderr(&["--color=auto"], None).contains('\x1b'));
+    assert!(!stderr(&["--color=never"], None).contains('\x1b'));
+}
+
+/// each stage that can fail has its own exit code.
+#[test]
+fn exit_code_names_failing_stage() {
+    for (source, code) in [
+        ("int main(void) { return 0; }", 0),
+        ("#include \"missing.h\"\nint main(void) { return 0; }", 1),
+        ("int main(void) { return 2 @ 3; }", 2),
+        ("int main(void) { return 2 + ; }", 3),
+        ("int helper(void) { return 0; }", 5),
+    ] {
+        let (tmpdir, paths) = write_sources(&[("main.c", source)]);
+        let status = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .current_dir(tmpdir.path())
+            .arg(&paths[0])
+            .output()
+            .unwrap()
+            .status;
+        assert_eq!(status.code(), Some(code), "{}", source);
+    }
+}
+
+/// a bad command line fails like any other trouble outside compilation, not
+/// with lexing's code, while asking for help or the version succeeds.
+#[test]
+fn usage_errors_exit_1() {
+    for (args, code) in [
+        (&["--no-such-flag", "main.c"][..], 1),
+        (&[], 1),
+        (&["-O7", "main.c"], 1),
+        (&["--help"], 0),
+        (&["--version"], 0),
+    ] {
+        let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .args(args)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(code), "{:?}", args);
+        let shown = match code {
+            0 => &output.stdout,
+            _ => &output.stderr,
+        };
+        assert!(!shown.is_empty(), "{:?}", args);
+    }
+}
+
+/// -W flags turn the division-by-zero warning off, or into an error that fails
+/// with the semantic analysis exit code.
+#[test]
+fn warning_flags() {
+    let (tmpdir, paths) = write_sources(&[("main.c", "int main(void) { return 1 / 0; }")]);
+    let crumb = |flags: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .current_dir(tmpdir.path())
+            .arg("-S")
+            .arg(&paths[0])
+            .args(flags)
+            .output()
+            .unwrap()
+    };
+    for (flags, code, shown) in [
+        (
+            &[][..],
+            0,
+            Some("warning: division by zero [-Wdiv-by-zero]"),
+        ),
+        (&["-Wno-div-by-zero"][..], 0, None),
+        (
+            &["-Werror"][..],
+            4,
+            Some("error: division by zero [-Werror=div-by-zero]"),
+        ),
+        (&["-Werror", "-Wno-div-by-zero"][..], 0, None),
+        (
+            &["-Werror=div-by-zero"][..],
+            4,
+            Some("[-Werror=div-by-zero]"),
+        ),
+        (&["-Wbogus"][..], 1, Some("unknown warning -Wbogus")),
+    ] {
+        let output = crumb(flags);
+        let stderr = str::from_utf8(&output.stderr).unwrap();
+        assert_eq!(output.status.code(), Some(code), "{:?}", flags);
+        match shown {
+            Some(message) => assert!(stderr.contains(message), "{:?}: {}", flags, stderr),
+            None => assert!(stderr.is_empty(), "{:?}: {}", flags, stderr),
+        }
+    }
+}
+
+/// -L and -l reach the linker after the objects, and are only warned about
+/// when nothing is linked.
+#[test]
+fn link_with_libraries() {
+    let (tmpdir, paths) = write_sources(&[
+        ("main.c", "int main(void) { return 6; }"),
+        ("helper.c", "int helper(void) { return 3; }"),
+    ]);
+    let dir = tmpdir.path().to_str().unwrap();
+    let crumb = |args: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .current_dir(tmpdir.path())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    assert!(crumb(&["-c", &paths[1]]).status.success());
+    assert!(Command::new("ar")
+        .current_dir(tmpdir.path())
+        .args(["rcs", "libhelper.a", "helper.o"])
+        .output()
+        .unwrap()
+        .status
+        .success());
+
+    let missing = crumb(&[&paths[0], "-lhelper"]);
+    assert_eq!(missing.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("-lhelper"));
+
+    let linked = crumb(&[&paths[0], "-L", dir, "-lhelper", "-lm", "-o", "prog"]);
+    assert!(linked.status.success());
+    assert_eq!(
+        Command::new(tmpdir.path().join("prog"))
+            .output()
+            .unwrap()
+            .status
+            .code(),
+        Some(6)
+    );
+
+    let ignored = crumb(&["-S", &paths[0], "-lm"]);
+    assert!(ignored.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&ignored.stderr),
+        "warning: -l and -L are ignored without linking\n"
+    );
+}
+
+/// --run exits with the program's status, leaving nothing behind, and a
+/// program killed by a signal exits as a shell would report it. A program
+/// that doesn't compile exits 125, whatever stage failed.
+#[test]
+fn run_flag_runs_program() {
+    let (tmpdir, paths) = write_sources(&[
+        ("main.c", "int main(void) { return 6 * 7; }"),
+        ("crash.c", "int main(void) { return 1 / 0; }"),
+        ("bad.c", "int main(void) { return 2 + ; }"),
+    ]);
+    let crumb = |args: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .current_dir(tmpdir.path())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let ran = crumb(&["--run", &paths[0], "--run-args", "one", "-2"]);
+    assert_eq!(ran.status.code(), Some(42));
+    assert_eq!(
+        String::from_utf8_lossy(&ran.stderr),
+        "crumb: program exited with status 42\n"
+    );
+    assert_eq!(std::fs::read_dir(tmpdir.path()).unwrap().count(), 3);
+
+    let crashed = crumb(&["-Wno-div-by-zero", "--run", &paths[1]]);
+    assert_eq!(crashed.status.code(), Some(128 + 8));
+    assert_eq!(
+        String::from_utf8_lossy(&crashed.stderr),
+        "crumb: program killed by signal 8\n"
+    );
+    assert_eq!(std::fs::read_dir(tmpdir.path()).unwrap().count(), 3);
+
+    let failed = crumb(&["--run", &paths[2]]);
+    assert_eq!(failed.status.code(), Some(125));
+    assert!(!String::from_utf8_lossy(&failed.stderr).contains("crumb: program"));
+    assert_eq!(std::fs::read_dir(tmpdir.path()).unwrap().count(), 3);
+
+    assert!(!crumb(&["--run", "-S", &paths[0]]).status.success());
+}
+
+/// dumps follow the pipeline in order, TACKY passes first, and can be limited to one pass.
+#[test]
+fn dump_after_passes() {
+    let (_tmpdir, paths) = write_sources(&[("main.c", "int main(void) { return 3 + 4; }")]);
+    let dumped = |flags: &[&str]| {
+        let stderr = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .args([&paths[0], "-S", "-o", "-"])
+            .args(flags)
+            .ok()
+            .unwrap()
+            .stderr;
+        String::from_utf8(stderr).unwrap()
+    };
+    let all = dumped(&["-O2", "--dump-after-all"]);
+    let headers: Vec<&str> = all.lines().filter(|l| l.starts_with("===")).collect();
+    assert_eq!(headers[0], "=== 01 after constant-folding ===");
+    assert_eq!(headers[5], "=== 06 after dead-stores ===");
+    assert!(headers.last().unwrap().ends_with(" after peephole ==="));
+
+    let peephole = dumped(&["-O2", "--dump-after", "peephole"]);
+    assert!(peephole.starts_with("=== 01 after peephole ===\n"));
+    assert!(peephole.contains("main:\n"));
+    assert!(dumped(&["-O0", "--dump-after-all"]).is_empty());
+}
+
+/// machine-made inputs nested or chained far past any limit are refused with a
+/// diagnostic, never a crash, and a long chain within the limit compiles.
+#[test]
+fn deep_nesting_does_not_crash() {
+    let depth = 50_000;
+    let chain = |terms: usize| vec!["1"; terms].join(" + ");
+    for (exp, compiles) in [
+        (
+            format!("{}1{}", "(".repeat(depth), ")".repeat(depth)),
+            false,
+        ),
+        (format!("{}1", "- ".repeat(depth)), false),
+        (
+            format!("{}1{}", "1 + (".repeat(depth), ")".repeat(depth)),
+            false,
+        ),
+        (chain(depth), false),
+        (format!("({}) * ({})", chain(1000), chain(1000)), true),
+    ] {
+        let source = format!("int main(void) {{ return {}; }}", exp);
+        let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .args(["-", "-S", "-o", "-"])
+            .write_stdin(source)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match compiles {
+            true => assert!(output.status.success(), "{}", stderr),
+            false => {
+                assert_eq!(output.status.code(), Some(3), "{}", stderr);
+                assert!(stderr.contains("levels deep"), "{}", stderr);
+            }
+        }
+    }
+
+    // the braces closing blocks nested too deeply don't bring errors of their own
+    let source = format!(
+        "int main(void) {}{} int f(void) {{ return 1 +; }}",
+        "{".repeat(depth),
+        "}".repeat(depth)
+    );
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["-", "-S", "-o", "-"])
+        .write_stdin(source)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let errors: Vec<&str> = stderr.lines().filter(|l| l.starts_with("error")).collect();
+    assert_eq!(
+        errors,
+        [
+            "error: nested more than 256 levels deep",
+            "error: expected an expression before ';'"
+        ],
+        "{}",
+        stderr
+    );
+}
+
+/// a megabyte of generated arithmetic, lexed as it is parsed, gets through TACKY
+/// generation with more temporaries than fit in 16 bits.
+#[test]
+fn megabyte_of_arithmetic() {
+    let mut source = String::new();
+    for i in 0.. {
+        if source.len() > 1 << 20 {
+            break;
+        }
+        let terms: Vec<String> = (0..20)
+            .map(|j| format!("({} * 3 - 1) / 2", i + j))
+            .collect();
+        source.push_str(&format!(
+            "int f{}(void) {{ return {}; }}\n",
+            i,
+            terms.join(" + ")
+        ));
+    }
+    source.push_str("int main(void) { return 0; }\n");
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["-", "--tacky"])
+        .write_stdin(source)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// optimizing a function takes time in proportion to its size: one four times
+/// as long, of statements each needing the one before folded, takes well under
+/// sixteen times as long, as it would if the rounds of passes grew with it.
+#[test]
+fn optimizing_scales_with_size() {
+    let compile = |statements: usize| {
+        let mut source = String::from("int main(void) { int a = 1; int b = 2; int c = 0;\n");
+        for i in 0..statements {
+            source.push_str(&format!("    c = c + a * {} + b * {};\n", i, i));
+        }
+        source.push_str("    return c; }\n");
+        let tmpdir = TempDir::new().unwrap();
+        let binary = tmpdir.path().join("long");
+        let binary = binary.to_str().unwrap();
+        // the fastest of a few runs, to keep other tests' load out of it
+        let elapsed = (0..3)
+            .map(|_| {
+                let start = std::time::Instant::now();
+                Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                    .unwrap()
+                    .args(["-", "-O2", "-o", binary])
+                    .write_stdin(source.as_str())
+                    .ok()
+                    .unwrap();
+                start.elapsed()
+            })
+            .min()
+            .unwrap();
+        let status = std::process::Command::new(binary).status().unwrap();
+        assert_eq!(
+            status.code(),
+            Some((3 * statements * (statements - 1) / 2 % 256) as i32)
+        );
+        elapsed
+    };
+    let short = compile(500);
+    let long = compile(2000);
+    assert!(
+        long < short * 10,
+        "{:?} for 500 statements, {:?} for 2000",
+        short,
+        long
+    );
+}
+
+/// sources with `\r\n` line endings and backslash-newline splices, even one
+/// splitting `return`, compile as gcc would, and errors after them are reported
+/// on the physical line and column they were written at.
+#[test]
+fn crlf_and_line_splices() {
+    let (tmpdir, paths) = write_sources(&[
+        (
+            "dos.c",
+            "int main(void) {\r\n  return 6 *\r\n    7;\r\n}\r\n",
+        ),
+        ("spliced.c", "int main(void) {\n  ret\\\nurn 4\\\n2;\n}\n"),
+        (
+            "bad_dos.c",
+            "int main(void) {\r\n  ret\\\r\nurn 2 @;\r\n}\r\n",
+        ),
+        (
+            "bad_spliced.c",
+            "int main(void) {\n  return 1 +\\\n  2\\\n @;\n}\n",
+        ),
+    ]);
+    let crumb = |args: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .current_dir(tmpdir.path())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    for (path, code) in [(&paths[0], 42), (&paths[1], 42)] {
+        let run = crumb(&["--run", path]);
+        assert_eq!(run.status.code(), Some(code), "{}", path);
+    }
+    for (path, location) in [
+        (&paths[2], "bad_dos.c:3:7"),
+        (&paths[3], "bad_spliced.c:4:2"),
+    ] {
+        let failed = crumb(&["-S", path]);
+        let stderr = String::from_utf8_lossy(&failed.stderr);
+        assert!(stderr.contains(location), "{}", stderr);
+    }
+}
+
+/// `--diagnostics-format=json` prints each diagnostic as one line of JSON that
+/// reads back as `JsonDiagnostic`, in place of the snippets people get by
+/// default, and says the serde feature is missing without it.
+#[test]
+fn json_diagnostics() {
+    let (_tmpdir, paths) = write_sources(&[
+        ("warns.c", "int main(void) {\n  return 1 / 0;\n}\n"),
+        ("bad.c", "int main(void) {\n  return (1 + 2;\n}\n"),
+    ]);
+    let crumb = |flags: &[&str]| {
+        Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg("--check")
+            .args(&paths)
+            .args(flags)
+            .output()
+            .unwrap()
+    };
+    let human = crumb(&[]);
+    assert_eq!(human.status.code(), Some(3));
+    assert!(str::from_utf8(&human.stderr)
+        .unwrap()
+        .starts_with("warning: division by zero"));
+
+    let output = crumb(&["--diagnostics-format=json"]);
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    if cfg!(not(feature = "serde")) {
+        assert_eq!(output.status.code(), Some(1), "{}", stderr);
+        assert!(stderr.contains("serde feature"), "{}", stderr);
+        return;
+    }
+    assert_eq!(output.status.code(), Some(3), "{}", stderr);
+    #[cfg(feature = "serde")]
+    {
+        use crumb::compiler::diagnostics::{JsonDiagnostic, Severity};
+        let diagnostics: Vec<JsonDiagnostic> = stderr
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(diagnostics.len(), 2, "{}", stderr);
+        let (warning, error) = (&diagnostics[0], &diagnostics[1]);
+        assert_eq!(warning.severity, Severity::Warning);
+        assert_eq!(warning.code, "div-by-zero");
+        assert_eq!(error.severity, Severity::Error);
+        assert_eq!(error.code, "unexpected-token");
+        assert_eq!(error.message, "expected ')' before ';'");
+        assert_eq!(warning.file.as_deref(), Some(paths[0].as_str()));
+        assert_eq!(error.file.as_deref(), Some(paths[1].as_str()));
+        assert_eq!((error.line, error.column), (Some(2), Some(16)));
+        assert_eq!(error.notes.len(), 1);
+        assert_eq!(error.notes[0].message, "to match this '('");
+        assert_eq!((error.notes[0].line, error.notes[0].column), (2, 10));
+    }
+}
+
+/// `--emit-ast=json` and `--emit-tacky=json` print the IR as JSON with the
+/// serde feature, and say it is missing without.
+#[test]
+fn emit_json() {
+    let (_tmpdir, paths) = write_sources(&[("main.c", "int main(void) { return -2 * 3; }")]);
+    for (flag, node) in [
+        ("--emit-ast=json", "\"Binary\""),
+        ("--emit-tacky=json", "\"TmpVar\""),
+    ] {
+        let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .arg(&paths[0])
+            .arg(flag)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        if cfg!(feature = "serde") {
+            assert!(output.status.success(), "{}", stderr);
+            assert!(
+                stdout.starts_with('{') && stdout.contains(node),
+                "{}",
+                stdout
+            );
+        } else {
+            assert_eq!(output.status.code(), Some(1), "{}", stderr);
+            assert!(stderr.contains("serde feature"), "{}", stderr);
+        }
+    }
+}
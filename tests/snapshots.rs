@@ -0,0 +1,160 @@
+//! Golden-file tests for the assembly crumb writes.
+//! Each `tests/snapshots/*.c` is compiled with `-S -o -`, plus any flags on a
+//! `// flags:` first line, for the linux target unless those flags name
+//! another, and the output must match the `.s` file beside it. After a
+//! deliberate change to the output, `UPDATE_SNAPSHOTS=1 cargo test --test
+//! snapshots` rewrites the `.s` files, and the diff shows up in review.
+//!
+//! Between them the programs reach every instruction C can produce so far:
+//! `mov` and `cmp` at every size, `movq`, `movabsq`, `push`, `pop`, `ret`,
+//! `neg`, `not`, the binary operators, `imul`, `cdq`, `cqo`, `idiv`, `div`,
+//! shifts, `setcc` for signed and unsigned orderings and straight into a
+//! `_Bool`, `movzx` and `movsx` between
+//! sizes, `cmov`, jumps and labels,
+//! `call` with arguments in registers and on the stack for both calling
+//! conventions, `leaq` and loads and stores through a pointer in a register,
+//! indexed addressing for array elements, structure copies through memory
+//! operands at an offset, stack allocation with and without
+//! `%rbp`, `%rip`-relative
+//! operands and the `.data`, `.bss` and `.rodata` sections they name, with
+//! `.byte` and `.short` for the narrow types and `.asciz` for string literals,
+//! the SSE `movsd`, arithmetic, `xorpd`, `comisd` and conversions on `double`s in XMM
+//! registers, comments, the CFI directives describing each frame and the
+//! `.seh_*` directives describing each Windows prologue; and every
+//! fixup: memory to memory moves and binary operations, `leaq`, `imul`, `movzbl`,
+//! `movslq`, `cmov`, SSE arithmetic and conversions into memory, `idiv`, `div`
+//! and `cmov` of an immediate, a quadword immediate past 32 bits, `cmp` with
+//! an immediate on the left, and a shift count moved into `%cl`.
+use assert_cmd::Command;
+use std::{env, fs, path::Path};
+
+/// lines of context shown around each change in a failure.
+const CONTEXT: usize = 2;
+
+fn compile(source: &Path) -> String {
+    let text = fs::read_to_string(source).unwrap();
+    let mut flags: Vec<&str> = match text
+        .lines()
+        .next()
+        .and_then(|l| l.strip_prefix("// flags:"))
+    {
+        Some(flags) => flags.split_whitespace().collect(),
+        None => vec![],
+    };
+    if !flags.contains(&"--target") {
+        flags.extend(["--target", "linux"]);
+    }
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg(source)
+        .args(["-S", "-o", "-"])
+        .args(flags)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}: {}",
+        source.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// a line diff of `expected` against `actual`, `-` for lines only expected and
+/// `+` for lines only in the output, with a little context around each change.
+fn diff(expected: &str, actual: &str) -> String {
+    let (old, new): (Vec<&str>, Vec<&str>) = (expected.lines().collect(), actual.lines().collect());
+    // longest common subsequence of the suffixes, filled from the end
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = match old[i] == new[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+
+    let near_change = |k: usize| {
+        let (lo, hi) = (
+            k.saturating_sub(CONTEXT),
+            (k + CONTEXT).min(lines.len() - 1),
+        );
+        lines[lo..=hi].iter().any(|(mark, _)| *mark != ' ')
+    };
+    let mut res = String::new();
+    let mut skipped = false;
+    for (k, (mark, line)) in lines.iter().enumerate() {
+        if near_change(k) {
+            if skipped {
+                res.push_str("  ...\n");
+            }
+            res.push_str(&format!("{} {}\n", mark, line));
+            skipped = false;
+        } else {
+            skipped = true;
+        }
+    }
+    res
+}
+
+#[test]
+fn assembly_matches_snapshots() {
+    let update = env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let mut sources: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "c"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty(), "no snapshots in {}", dir.display());
+
+    let mut failures = vec![];
+    for source in sources.iter() {
+        let actual = compile(source);
+        let golden = source.with_extension("s");
+        if update {
+            fs::write(&golden, &actual).unwrap();
+            continue;
+        }
+        match fs::read_to_string(&golden) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!(
+                "{} changed:\n{}",
+                golden.display(),
+                diff(&expected, &actual)
+            )),
+            Err(_) => failures.push(format!("{} is missing", golden.display())),
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\nrerun with UPDATE_SNAPSHOTS=1 if the changes are intended",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn diff_marks_changed_lines() {
+    let expected = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+    let actual = "a\nb\nC\nd\ne\nf\ng\nh\ni\nj\n";
+    assert_eq!(
+        diff(expected, actual),
+        "  a\n  b\n- c\n+ C\n  d\n  e\n  ...\n  h\n  i\n+ j\n"
+    );
+}
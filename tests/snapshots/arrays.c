@@ -0,0 +1,17 @@
+// arrays: an aligned stack slot for each local array, a zeroed array in
+// .bss, leaq with a constant offset, indexed addressing at power of two
+// scales, and imul for a row that isn't one
+static int table[5];
+
+long pick(long grid[2][3], int row, int col) {
+    return grid[row][col];
+}
+
+int main(void) {
+    char letters[3];
+    long grid[2][3];
+    letters[2] = 4;
+    grid[1][2] = letters[2];
+    table[4] = 1;
+    return pick(grid, 1, 2) + table[4];
+}
@@ -0,0 +1,123 @@
+	.text
+	.globl pick
+	.type pick, @function
+pick:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $32, %rsp
+	movq %rdi, -8(%rbp)
+	movl %esi, -12(%rbp)
+	movl %edx, -16(%rbp)
+	movslq -12(%rbp), %r11
+	movq %r11, -24(%rbp)
+	movq -8(%rbp), %rax
+	movq -24(%rbp), %rdx
+	imulq $24, %rdx
+	leaq (%rax,%rdx,1), %r11
+	movq %r11, -8(%rbp)
+	movq -8(%rbp), %r10
+	movq %r10, -8(%rbp)
+	movslq -16(%rbp), %r11
+	movq %r11, -24(%rbp)
+	movq -8(%rbp), %rax
+	movq -24(%rbp), %rdx
+	leaq (%rax,%rdx,8), %r11
+	movq %r11, -8(%rbp)
+	movq -8(%rbp), %rax
+	movq 0(%rax), %r10
+	movq %r10, -8(%rbp)
+	movq -8(%rbp), %rax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size pick, .-pick
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $80, %rsp
+	leaq -56(%rbp), %r11
+	movq %r11, -64(%rbp)
+	movq -64(%rbp), %rax
+	leaq 2(%rax), %r11
+	movq %r11, -64(%rbp)
+	movq -64(%rbp), %rax
+	movb $4, 0(%rax)
+	leaq -48(%rbp), %r11
+	movq %r11, -64(%rbp)
+	movq -64(%rbp), %rax
+	leaq 24(%rax), %r11
+	movq %r11, -64(%rbp)
+	movq -64(%rbp), %r10
+	movq %r10, -64(%rbp)
+	movq -64(%rbp), %rax
+	leaq 16(%rax), %r11
+	movq %r11, -64(%rbp)
+	leaq -56(%rbp), %r11
+	movq %r11, -72(%rbp)
+	movq -72(%rbp), %rax
+	leaq 2(%rax), %r11
+	movq %r11, -72(%rbp)
+	movq -72(%rbp), %rax
+	movb 0(%rax), %r10b
+	movb %r10b, -73(%rbp)
+	movsbq -73(%rbp), %r11
+	movq %r11, -72(%rbp)
+	movq -64(%rbp), %rax
+	movq -72(%rbp), %r10
+	movq %r10, 0(%rax)
+	leaq table(%rip), %r11
+	movq %r11, -64(%rbp)
+	movq -64(%rbp), %rax
+	leaq 16(%rax), %r11
+	movq %r11, -64(%rbp)
+	movq -64(%rbp), %rax
+	movl $1, 0(%rax)
+	leaq -48(%rbp), %r11
+	movq %r11, -64(%rbp)
+	movq -64(%rbp), %rdi
+	movl $1, %esi
+	movl $2, %edx
+	call pick@PLT
+	movq %rax, -64(%rbp)
+	leaq table(%rip), %r11
+	movq %r11, -72(%rbp)
+	movq -72(%rbp), %rax
+	leaq 16(%rax), %r11
+	movq %r11, -72(%rbp)
+	movq -72(%rbp), %rax
+	movl 0(%rax), %r10d
+	movl %r10d, -80(%rbp)
+	movslq -80(%rbp), %r11
+	movq %r11, -72(%rbp)
+	movq -64(%rbp), %r10
+	movq %r10, -64(%rbp)
+	movq -72(%rbp), %r10
+	addq %r10, -64(%rbp)
+	movl -64(%rbp), %r10d
+	movl %r10d, -80(%rbp)
+	movl -80(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.bss
+	.balign 16
+	.type table, @object
+	.size table, 20
+table:
+	.zero 20
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,3 @@
+// flags: --asm-comments
+// each group of instructions follows the TACKY it came from
+int main(void) { return ~(2 * 3) % 5; }
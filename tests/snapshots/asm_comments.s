@@ -0,0 +1,33 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	# tmp.0 = 2 * 3
+	movl $3, -4(%rbp)
+	shll $1, -4(%rbp)
+	# tmp.1 = ~tmp.0
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	notl -4(%rbp)
+	# tmp.2 = tmp.1 % 5
+	movl -4(%rbp), %eax
+	cdq
+	movl $5, %r10d
+	idivl %r10d
+	movl %edx, -4(%rbp)
+	# ret tmp.2
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
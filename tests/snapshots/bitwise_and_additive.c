@@ -0,0 +1,2 @@
+// add, sub, and, or and xor, their memory sources moved into %r10d first
+int main(void) { return (1 + 2) - (3 & 4) | (5 ^ 6); }
@@ -0,0 +1,33 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl $1, -4(%rbp)
+	addl $2, -4(%rbp)
+	movl $3, -8(%rbp)
+	andl $4, -8(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -8(%rbp), %r10d
+	subl %r10d, -4(%rbp)
+	movl $5, -8(%rbp)
+	xorl $6, -8(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -8(%rbp), %r10d
+	orl %r10d, -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
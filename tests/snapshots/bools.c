@@ -0,0 +1,9 @@
+// bools: a value of any type compared with zero and setne straight into the
+// _Bool's byte, and a _Bool zero-extended back to int, in .data as a .byte
+static _Bool seen = 2;
+int count(long n, int *p) {
+    _Bool nonzero = n;
+    _Bool valid = p;
+    seen = nonzero;
+    return nonzero + valid;
+}
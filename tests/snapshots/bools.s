@@ -0,0 +1,45 @@
+	.text
+	.globl count
+	.type count, @function
+count:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $32, %rsp
+	movq %rdi, -8(%rbp)
+	movq %rsi, -16(%rbp)
+	cmpq $0, -8(%rbp)
+	setne -17(%rbp)
+	movb -17(%rbp), %r10b
+	movb %r10b, -17(%rbp)
+	cmpq $0, -16(%rbp)
+	setne -18(%rbp)
+	movb -18(%rbp), %r10b
+	movb %r10b, -18(%rbp)
+	movb -17(%rbp), %r10b
+	movb %r10b, seen(%rip)
+	movzbl -17(%rbp), %r11d
+	movl %r11d, -24(%rbp)
+	movzbl -18(%rbp), %r11d
+	movl %r11d, -28(%rbp)
+	movl -24(%rbp), %r10d
+	movl %r10d, -24(%rbp)
+	movl -28(%rbp), %r10d
+	addl %r10d, -24(%rbp)
+	movl -24(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size count, .-count
+	.data
+	.balign 1
+	.type seen, @object
+	.size seen, 1
+seen:
+	.byte 1
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,5 @@
+// the first six arguments in registers and the seventh pushed through %rax,
+// with a padding slot to keep %rsp aligned at the call; the callee copies its
+// parameters into slots, the seventh from above the return address
+int last(int a, int b, int c, int d, int e, int f, int g) { return g - a; }
+int main(void) { return last(1, 2, 3, 4, 5, 6, 7); }
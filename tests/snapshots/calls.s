@@ -0,0 +1,60 @@
+	.text
+	.globl last
+	.type last, @function
+last:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl %edi, -4(%rbp)
+	movl %esi, -8(%rbp)
+	movl %edx, -8(%rbp)
+	movl %ecx, -8(%rbp)
+	movl %r8d, -8(%rbp)
+	movl %r9d, -8(%rbp)
+	movl 16(%rbp), %r10d
+	movl %r10d, -8(%rbp)
+	movl -8(%rbp), %r10d
+	movl %r10d, -8(%rbp)
+	movl -4(%rbp), %r10d
+	subl %r10d, -8(%rbp)
+	movl -8(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size last, .-last
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	subq $8, %rsp
+	movl $7, %eax
+	pushq %rax
+	movl $1, %edi
+	movl $2, %esi
+	movl $3, %edx
+	movl $4, %ecx
+	movl $5, %r8d
+	movl $6, %r9d
+	call last@PLT
+	addq $16, %rsp
+	movl %eax, -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,3 @@
+// cmpl sets the flags, setcc copies one into %al and movzbl widens it,
+// through %r11d into memory; a constant on the left is compared from %r11d too
+int main(void) { return (1 < -2) + (~3 >= 4 - 5) * (6 != 6 * 1) - (2 == ~~2); }
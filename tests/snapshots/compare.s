@@ -0,0 +1,64 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl $2, -4(%rbp)
+	negl -4(%rbp)
+	movl $1, %r11d
+	cmpl -4(%rbp), %r11d
+	setl %al
+	movzbl %al, %r11d
+	movl %r11d, -4(%rbp)
+	movl $3, -8(%rbp)
+	notl -8(%rbp)
+	movl $4, -12(%rbp)
+	subl $5, -12(%rbp)
+	movl -12(%rbp), %r10d
+	cmpl %r10d, -8(%rbp)
+	setge %al
+	movzbl %al, %r11d
+	movl %r11d, -8(%rbp)
+	movl $6, -12(%rbp)
+	movl $6, %r11d
+	cmpl -12(%rbp), %r11d
+	setne %al
+	movzbl %al, %r11d
+	movl %r11d, -12(%rbp)
+	movl -8(%rbp), %r10d
+	movl %r10d, -8(%rbp)
+	movl -8(%rbp), %r11d
+	imull -12(%rbp), %r11d
+	movl %r11d, -8(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -8(%rbp), %r10d
+	addl %r10d, -4(%rbp)
+	movl $2, -8(%rbp)
+	notl -8(%rbp)
+	movl -8(%rbp), %r10d
+	movl %r10d, -8(%rbp)
+	notl -8(%rbp)
+	movl $2, %r11d
+	cmpl -8(%rbp), %r11d
+	sete %al
+	movzbl %al, %r11d
+	movl %r11d, -8(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -8(%rbp), %r10d
+	subl %r10d, -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
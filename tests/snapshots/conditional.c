@@ -0,0 +1,4 @@
+// flags: -O --disable-pass constant-folding
+// a branch between two values already computed becomes a cmov, loading its
+// destination into %r11d first, and a constant source into %r10d
+int main(void) { return 1 < 2 ? 3 : 4; }
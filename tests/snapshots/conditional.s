@@ -0,0 +1,30 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	movl $1, %r11d
+	cmpl $2, %r11d
+	setl %al
+	movzbl %al, %r11d
+	movl %r11d, -4(%rbp)
+	cmpl $0, -4(%rbp)
+	movl $3, -4(%rbp)
+	movl $4, %r10d
+	movl -4(%rbp), %r11d
+	cmovel %r10d, %r11d
+	movl %r11d, -4(%rbp)
+.Lcond_end.1:
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
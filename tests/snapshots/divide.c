@@ -0,0 +1,2 @@
+// cdq before idiv, whose immediate divisor is moved into %r10d first
+int main(void) { return (20 - 3) / 5 + (20 - 3) % (4 - 1); }
@@ -0,0 +1,38 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl $20, -4(%rbp)
+	subl $3, -4(%rbp)
+	movl -4(%rbp), %eax
+	cdq
+	movl $5, %r10d
+	idivl %r10d
+	movl %eax, -4(%rbp)
+	movl $20, -8(%rbp)
+	subl $3, -8(%rbp)
+	movl $4, -12(%rbp)
+	subl $1, -12(%rbp)
+	movl -8(%rbp), %eax
+	cdq
+	idivl -12(%rbp)
+	movl %edx, -8(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -8(%rbp), %r10d
+	addl %r10d, -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,2 @@
+// division and remainder by powers of two become shifts, not idiv
+int main(void) { return (~2) / 8 + (~5) % 16 + (~1) / 1; }
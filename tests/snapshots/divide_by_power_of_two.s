@@ -0,0 +1,50 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl $2, -4(%rbp)
+	notl -4(%rbp)
+	movl -4(%rbp), %eax
+	movl %eax, %edx
+	sarl $31, %edx
+	shrl $29, %edx
+	addl %edx, %eax
+	sarl $3, %eax
+	movl %eax, -4(%rbp)
+	movl $5, -8(%rbp)
+	notl -8(%rbp)
+	movl -8(%rbp), %eax
+	movl %eax, %edx
+	sarl $31, %edx
+	shrl $28, %edx
+	addl %edx, %eax
+	andl $15, %eax
+	subl %edx, %eax
+	movl %eax, -8(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -8(%rbp), %r10d
+	addl %r10d, -4(%rbp)
+	movl $1, -8(%rbp)
+	notl -8(%rbp)
+	movl -8(%rbp), %r10d
+	movl %r10d, -8(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -8(%rbp), %r10d
+	addl %r10d, -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
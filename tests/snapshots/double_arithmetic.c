@@ -0,0 +1,15 @@
+// SSE arithmetic on doubles: movsd, addsd, subsd, mulsd, divsd, comisd against a
+// cleared %xmm0, xorpd, cvtsi2sd and cvttsd2si, doubles passed and returned in
+// XMM registers, the sign flipped with a quadword xor, and constants in .rodata
+double rate = 0.5;
+
+double blend(double a, int weight, double b) {
+    return (a * weight - b) / rate + 2.5;
+}
+
+int main(void) {
+    double x = -blend(1.0, 3, 2.5);
+    if (x)
+        return x < 2.5;
+    return x;
+}
@@ -0,0 +1,109 @@
+	.text
+	.globl blend
+	.type blend, @function
+blend:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $32, %rsp
+	movsd %xmm0, -8(%rbp)
+	movl %edi, -12(%rbp)
+	movsd %xmm1, -24(%rbp)
+	cvtsi2sdl -12(%rbp), %xmm5
+	movsd %xmm5, -32(%rbp)
+	movsd -8(%rbp), %xmm4
+	movsd %xmm4, -8(%rbp)
+	movsd -8(%rbp), %xmm5
+	mulsd -32(%rbp), %xmm5
+	movsd %xmm5, -8(%rbp)
+	movsd -8(%rbp), %xmm4
+	movsd %xmm4, -8(%rbp)
+	movsd -8(%rbp), %xmm5
+	subsd -24(%rbp), %xmm5
+	movsd %xmm5, -8(%rbp)
+	movsd -8(%rbp), %xmm4
+	movsd %xmm4, -8(%rbp)
+	movsd -8(%rbp), %xmm5
+	divsd rate(%rip), %xmm5
+	movsd %xmm5, -8(%rbp)
+	movsd -8(%rbp), %xmm4
+	movsd %xmm4, -8(%rbp)
+	movsd -8(%rbp), %xmm5
+	addsd .Ldouble.4004000000000000(%rip), %xmm5
+	movsd %xmm5, -8(%rbp)
+	movsd -8(%rbp), %xmm0
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size blend, .-blend
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movsd .Ldouble.3ff0000000000000(%rip), %xmm0
+	movl $3, %edi
+	movsd .Ldouble.4004000000000000(%rip), %xmm1
+	call blend@PLT
+	movsd %xmm0, -8(%rbp)
+	movsd -8(%rbp), %xmm4
+	movsd %xmm4, -8(%rbp)
+	movabsq $-9223372036854775808, %r10
+	xorq %r10, -8(%rbp)
+	movsd -8(%rbp), %xmm4
+	movsd %xmm4, -8(%rbp)
+	xorpd %xmm0, %xmm0
+	comisd -8(%rbp), %xmm0
+	sete %al
+	setnp %dl
+	andl %edx, %eax
+	cmpb $0, %al
+	jne .Lif_end.0
+	movsd .Ldouble.4004000000000000(%rip), %xmm5
+	comisd -8(%rbp), %xmm5
+	seta %al
+	movzbl %al, %r11d
+	movl %r11d, -12(%rbp)
+	movl -12(%rbp), %eax
+	.cfi_remember_state
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_restore_state
+.Lif_end.0:
+	cvttsd2sil -8(%rbp), %r11d
+	movl %r11d, -12(%rbp)
+	movl -12(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.globl rate
+	.data
+	.balign 8
+	.type rate, @object
+	.size rate, 8
+rate:
+	.quad 4602678819172646912
+	.section .rodata
+	.balign 8
+.Ldouble.3ff0000000000000:
+	.quad 4607182418800017408
+	.section .rodata
+	.balign 8
+.Ldouble.4004000000000000:
+	.quad 4612811918334230528
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,4 @@
+// flags: -O
+// a loop tests its condition at the top and jumps back there after its post
+// expression; -O drops the continue label nothing jumps to
+int main(void) { int s = 0; for (int i = 0; i < 5; i = i + 1) s = s + i; return s; }
@@ -0,0 +1,40 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	movl $0, -4(%rbp)
+	movl $0, -8(%rbp)
+.Lloop.0:
+	cmpl $5, -8(%rbp)
+	setl %al
+	movzbl %al, %r11d
+	movl %r11d, -12(%rbp)
+	cmpl $0, -12(%rbp)
+	je .Lbreak_loop.0
+	movl -4(%rbp), %r10d
+	movl %r10d, -12(%rbp)
+	movl -8(%rbp), %r10d
+	addl %r10d, -12(%rbp)
+	movl -12(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -8(%rbp), %r10d
+	movl %r10d, -12(%rbp)
+	addl $1, -12(%rbp)
+	movl -12(%rbp), %r10d
+	movl %r10d, -8(%rbp)
+	jmp .Lloop.0
+.Lbreak_loop.0:
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
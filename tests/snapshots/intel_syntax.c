@@ -0,0 +1,2 @@
+// flags: --syntax intel
+int main(void) { return (12 - 5) * 3 / (-2 + 4) ^ ~1; }
@@ -0,0 +1,42 @@
+	.intel_syntax noprefix
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	push rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	mov rbp, rsp
+	.cfi_def_cfa_register 6
+	sub rsp, 16
+	mov DWORD PTR [rbp-4], 12
+	sub DWORD PTR [rbp-4], 5
+	mov r10d, DWORD PTR [rbp-4]
+	mov DWORD PTR [rbp-4], r10d
+	mov r11d, DWORD PTR [rbp-4]
+	imul r11d, 3
+	mov DWORD PTR [rbp-4], r11d
+	mov DWORD PTR [rbp-8], 2
+	neg DWORD PTR [rbp-8]
+	mov r10d, DWORD PTR [rbp-8]
+	mov DWORD PTR [rbp-8], r10d
+	add DWORD PTR [rbp-8], 4
+	mov eax, DWORD PTR [rbp-4]
+	cdq
+	idiv DWORD PTR [rbp-8]
+	mov DWORD PTR [rbp-4], eax
+	mov DWORD PTR [rbp-8], 1
+	not DWORD PTR [rbp-8]
+	mov r10d, DWORD PTR [rbp-4]
+	mov DWORD PTR [rbp-4], r10d
+	mov r10d, DWORD PTR [rbp-8]
+	xor DWORD PTR [rbp-4], r10d
+	mov eax, DWORD PTR [rbp-4]
+	mov rsp, rbp
+	pop rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,3 @@
+// && and || jump past their right operand once the left one decides the
+// result, comparing constants from %r11d, and join at a label setting the result
+int main(void) { return (3 > 1 && 0) || -2; }
@@ -0,0 +1,45 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl $3, %r11d
+	cmpl $1, %r11d
+	setg %al
+	movzbl %al, %r11d
+	movl %r11d, -4(%rbp)
+	cmpl $0, -4(%rbp)
+	je .Land_false.2
+	movl $0, %r11d
+	cmpl $0, %r11d
+	je .Land_false.2
+	movl $1, -4(%rbp)
+	jmp .Land_end.3
+.Land_false.2:
+	movl $0, -4(%rbp)
+.Land_end.3:
+	cmpl $0, -4(%rbp)
+	jne .Lor_true.0
+	movl $2, -4(%rbp)
+	negl -4(%rbp)
+	cmpl $0, -4(%rbp)
+	jne .Lor_true.0
+	movl $0, -4(%rbp)
+	jmp .Lor_end.1
+.Lor_true.0:
+	movl $1, -4(%rbp)
+.Lor_end.1:
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,12 @@
+// quadword arithmetic on longs: cqo and idivq, movslq widening an int, movabsq
+// for a constant past 32 bits, and a long in .data
+long big = 8589934592;
+
+long mix(long a, int b) {
+    return a / b + 4294967296 - a % 3;
+}
+
+int main(void) {
+    int low = mix(big, 2);
+    return low;
+}
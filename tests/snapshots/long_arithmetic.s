@@ -0,0 +1,72 @@
+	.text
+	.globl mix
+	.type mix, @function
+mix:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $32, %rsp
+	movq %rdi, -8(%rbp)
+	movl %esi, -12(%rbp)
+	movslq -12(%rbp), %r11
+	movq %r11, -24(%rbp)
+	movq -8(%rbp), %rax
+	cqo
+	idivq -24(%rbp)
+	movq %rax, -24(%rbp)
+	movq -24(%rbp), %r10
+	movq %r10, -24(%rbp)
+	movabsq $4294967296, %r10
+	addq %r10, -24(%rbp)
+	movq -8(%rbp), %rax
+	cqo
+	movq $3, %r10
+	idivq %r10
+	movq %rdx, -8(%rbp)
+	movq -24(%rbp), %r10
+	movq %r10, -24(%rbp)
+	movq -8(%rbp), %r10
+	subq %r10, -24(%rbp)
+	movq -24(%rbp), %rax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size mix, .-mix
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movq big(%rip), %rdi
+	movl $2, %esi
+	call mix@PLT
+	movq %rax, -8(%rbp)
+	movl -8(%rbp), %r10d
+	movl %r10d, -12(%rbp)
+	movl -12(%rbp), %r10d
+	movl %r10d, -12(%rbp)
+	movl -12(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.globl big
+	.data
+	.balign 8
+	.type big, @object
+	.size big, 8
+big:
+	.quad 8589934592
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,5 @@
+// flags: --target macos
+// underscore-prefixed variables, in .data and .bss with no ELF symbol info
+int total = 3;
+static int calls;
+int main(void) { calls = total + 1; return calls; }
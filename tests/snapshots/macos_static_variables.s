@@ -0,0 +1,30 @@
+	.text
+	.globl _main
+_main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl _total(%rip), %r10d
+	movl %r10d, -4(%rbp)
+	addl $1, -4(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, _calls(%rip)
+	movl _calls(%rip), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.globl _total
+	.data
+	.balign 4
+_total:
+	.long 3
+	.bss
+	.balign 4
+_calls:
+	.zero 4
@@ -0,0 +1,3 @@
+// flags: --target macos
+// underscore-prefixed symbols, no ELF symbol info and no GNU-stack note
+int main(void) { return -(3 * 4) % 5; }
@@ -0,0 +1,26 @@
+	.text
+	.globl _main
+_main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl $3, -4(%rbp)
+	shll $2, -4(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	negl -4(%rbp)
+	movl -4(%rbp), %eax
+	cdq
+	movl $5, %r10d
+	idivl %r10d
+	movl %edx, -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
@@ -0,0 +1,2 @@
+// imul can't write to memory, so its product goes through %r11d
+int main(void) { return 7 * (3 - 1) * (2 + 2); }
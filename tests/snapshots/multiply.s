@@ -0,0 +1,32 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl $3, -4(%rbp)
+	subl $1, -4(%rbp)
+	movl $7, -8(%rbp)
+	movl -8(%rbp), %r11d
+	imull -4(%rbp), %r11d
+	movl %r11d, -8(%rbp)
+	movl $2, -4(%rbp)
+	addl $2, -4(%rbp)
+	movl -8(%rbp), %r10d
+	movl %r10d, -8(%rbp)
+	movl -8(%rbp), %r11d
+	imull -4(%rbp), %r11d
+	movl %r11d, -8(%rbp)
+	movl -8(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
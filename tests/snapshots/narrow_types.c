@@ -0,0 +1,19 @@
+// chars and shorts: movb and movw, movsbw, movsbl, movsbq, movzbq, movzwl and
+// movzwq extending them, byte compares, arguments extended to an int, and the
+// .byte and .short directives
+char letter = 65;
+unsigned short wide = 65535;
+
+long widen(signed char c, unsigned short s) {
+    long l = c;
+    return l + s;
+}
+
+int main(void) {
+    char c = 300;
+    short s = c;
+    unsigned char u = wide;
+    if (c)
+        c = letter;
+    return widen(c, s) + u;
+}
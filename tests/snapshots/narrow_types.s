@@ -0,0 +1,92 @@
+	.text
+	.globl widen
+	.type widen, @function
+widen:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $32, %rsp
+	movb %dil, -1(%rbp)
+	movw %si, -4(%rbp)
+	movsbq -1(%rbp), %r11
+	movq %r11, -16(%rbp)
+	movq -16(%rbp), %r10
+	movq %r10, -16(%rbp)
+	movzwq -4(%rbp), %r11
+	movq %r11, -24(%rbp)
+	movq -16(%rbp), %r10
+	movq %r10, -16(%rbp)
+	movq -24(%rbp), %r10
+	addq %r10, -16(%rbp)
+	movq -16(%rbp), %rax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size widen, .-widen
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $32, %rsp
+	movb $44, -1(%rbp)
+	movsbw -1(%rbp), %r11w
+	movw %r11w, -4(%rbp)
+	movw -4(%rbp), %r10w
+	movw %r10w, -4(%rbp)
+	movb wide(%rip), %r10b
+	movb %r10b, -5(%rbp)
+	movb -5(%rbp), %r10b
+	movb %r10b, -5(%rbp)
+	cmpb $0, -1(%rbp)
+	je .Lif_end.0
+	movb letter(%rip), %r10b
+	movb %r10b, -1(%rbp)
+.Lif_end.0:
+	movb -1(%rbp), %r10b
+	movb %r10b, -1(%rbp)
+	movw -4(%rbp), %r10w
+	movw %r10w, -4(%rbp)
+	movsbl -1(%rbp), %edi
+	movzwl -4(%rbp), %esi
+	call widen@PLT
+	movq %rax, -16(%rbp)
+	movzbq -5(%rbp), %r11
+	movq %r11, -24(%rbp)
+	movq -16(%rbp), %r10
+	movq %r10, -16(%rbp)
+	movq -24(%rbp), %r10
+	addq %r10, -16(%rbp)
+	movl -16(%rbp), %r10d
+	movl %r10d, -28(%rbp)
+	movl -28(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.globl letter
+	.data
+	.balign 1
+	.type letter, @object
+	.size letter, 1
+letter:
+	.byte 65
+	.globl wide
+	.data
+	.balign 2
+	.type wide, @object
+	.size wide, 2
+wide:
+	.short 65535
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,3 @@
+// flags: --omit-frame-pointer
+// the frame is allocated and deallocated by moving %rsp, with no %rbp
+int main(void) { return -(~4 + 1) * 3; }
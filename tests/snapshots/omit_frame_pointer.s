@@ -0,0 +1,27 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	subq $8, %rsp
+	.cfi_def_cfa_offset 16
+	movl $4, 4(%rsp)
+	notl 4(%rsp)
+	movl 4(%rsp), %r10d
+	movl %r10d, 4(%rsp)
+	addl $1, 4(%rsp)
+	movl 4(%rsp), %r10d
+	movl %r10d, 4(%rsp)
+	negl 4(%rsp)
+	movl 4(%rsp), %r10d
+	movl %r10d, 4(%rsp)
+	movl 4(%rsp), %r11d
+	imull $3, %r11d
+	movl %r11d, 4(%rsp)
+	movl 4(%rsp), %eax
+	addq $8, %rsp
+	.cfi_def_cfa_offset 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,16 @@
+// pointers: leaq of a stack slot and of a static, loads and stores through
+// %rax at each size, and the fixup of leaq into memory
+static double scale = 2.5;
+
+int store(int *p, char c) {
+    *p = c;
+    return *p;
+}
+
+int main(void) {
+    int x = 0;
+    int *p = &x;
+    double *d = &scale;
+    *d = *d * 2.0;
+    return store(p, 7) + *p;
+}
@@ -0,0 +1,88 @@
+	.text
+	.globl store
+	.type store, @function
+store:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movq %rdi, -8(%rbp)
+	movb %sil, -9(%rbp)
+	movsbl -9(%rbp), %r11d
+	movl %r11d, -16(%rbp)
+	movq -8(%rbp), %rax
+	movl -16(%rbp), %r10d
+	movl %r10d, 0(%rax)
+	movq -8(%rbp), %rax
+	movl 0(%rax), %r10d
+	movl %r10d, -16(%rbp)
+	movl -16(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size store, .-store
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $48, %rsp
+	movl $0, -8(%rbp)
+	leaq -8(%rbp), %r11
+	movq %r11, -16(%rbp)
+	movq -16(%rbp), %r10
+	movq %r10, -16(%rbp)
+	leaq scale(%rip), %r11
+	movq %r11, -24(%rbp)
+	movq -24(%rbp), %r10
+	movq %r10, -24(%rbp)
+	movq -24(%rbp), %rax
+	movsd 0(%rax), %xmm4
+	movsd %xmm4, -32(%rbp)
+	movsd -32(%rbp), %xmm4
+	movsd %xmm4, -32(%rbp)
+	movsd -32(%rbp), %xmm5
+	mulsd .Ldouble.4000000000000000(%rip), %xmm5
+	movsd %xmm5, -32(%rbp)
+	movq -24(%rbp), %rax
+	movsd -32(%rbp), %xmm4
+	movsd %xmm4, 0(%rax)
+	movq -16(%rbp), %rdi
+	movb $7, %r10b
+	movsbl %r10b, %esi
+	call store@PLT
+	movl %eax, -36(%rbp)
+	movq -16(%rbp), %rax
+	movl 0(%rax), %r10d
+	movl %r10d, -40(%rbp)
+	movl -36(%rbp), %r10d
+	movl %r10d, -36(%rbp)
+	movl -40(%rbp), %r10d
+	addl %r10d, -36(%rbp)
+	movl -36(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.data
+	.balign 8
+	.type scale, @object
+	.size scale, 8
+scale:
+	.quad 4612811918334230528
+	.section .rodata
+	.balign 8
+.Ldouble.4000000000000000:
+	.quad 4611686018427387904
+	.section .note.GNU-stack,"",@progbits
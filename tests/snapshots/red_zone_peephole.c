@@ -0,0 +1,3 @@
+// flags: -O2 --disable-pass constant-folding
+// at -O2 a small leaf frame stays in the red zone, and the peephole pass runs
+int main(void) { return -(~4 + 1) * 3; }
@@ -0,0 +1,28 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	movl $4, -4(%rbp)
+	notl -4(%rbp)
+	movl -4(%rbp), %r10d
+	addl $1, -4(%rbp)
+	movl -4(%rbp), %r10d
+	negl -4(%rbp)
+	movl -4(%rbp), %r10d
+	movl -4(%rbp), %r11d
+	imull $3, %r11d
+	movl %r11d, -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
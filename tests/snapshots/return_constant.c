@@ -0,0 +1,2 @@
+// mov of a constant into %eax, between the frame setup and teardown
+int main(void) { return 2; }
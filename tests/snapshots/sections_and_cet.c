@@ -0,0 +1,4 @@
+// flags: --function-sections --cf-protection
+int one(void) { return 1; }
+
+int main(void) { return -1 * 2; }
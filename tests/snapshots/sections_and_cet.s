@@ -0,0 +1,53 @@
+	.section .text.one,"ax",@progbits
+	.globl one
+	.type one, @function
+one:
+	.cfi_startproc
+	endbr64
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	movl $1, %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size one, .-one
+	.section .text.main,"ax",@progbits
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	endbr64
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl $1, -4(%rbp)
+	negl -4(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	shll $1, -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.gnu.property,"a"
+	.align 8
+	.long 4
+	.long 16
+	.long 5
+	.string "GNU"
+	.long 0xc0000002
+	.long 4
+	.long 3
+	.align 8
+	.section .note.GNU-stack,"",@progbits
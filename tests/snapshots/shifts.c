@@ -0,0 +1,7 @@
+// shifts: shl and sar by a constant, and by a count moved into %cl, shr
+// for an unsigned value, and the count converted to the shifted type first
+int shift(int x, unsigned u, long n) {
+    x <<= 3;
+    u >>= n;
+    return (x >> n) + (u << 1);
+}
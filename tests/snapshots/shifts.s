@@ -0,0 +1,52 @@
+	.text
+	.globl shift
+	.type shift, @function
+shift:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $32, %rsp
+	movl %edi, -4(%rbp)
+	movl %esi, -8(%rbp)
+	movq %rdx, -16(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -20(%rbp)
+	shll $3, -20(%rbp)
+	movl -20(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -16(%rbp), %r10d
+	movl %r10d, -20(%rbp)
+	movl -8(%rbp), %r10d
+	movl %r10d, -24(%rbp)
+	movl -20(%rbp), %ecx
+	shrl %cl, -24(%rbp)
+	movl -24(%rbp), %r10d
+	movl %r10d, -8(%rbp)
+	movl -16(%rbp), %r10d
+	movl %r10d, -20(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -20(%rbp), %ecx
+	sarl %cl, -4(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -8(%rbp), %r10d
+	movl %r10d, -8(%rbp)
+	shll $1, -8(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -8(%rbp), %r10d
+	addl %r10d, -4(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size shift, .-shift
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,6 @@
+// globals in .data, zeroed ones in .bss, a static local under its unique name,
+// and each read and written %rip-relative; only external names get .globl
+int total = 3;
+static int calls;
+int count(int n) { static int last = -1; calls = calls + 1; last = n; total = total + n; return calls; }
+int main(void) { return count(4) + total; }
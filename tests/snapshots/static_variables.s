@@ -0,0 +1,76 @@
+	.text
+	.globl count
+	.type count, @function
+count:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl %edi, -4(%rbp)
+	movl calls(%rip), %r10d
+	movl %r10d, -8(%rbp)
+	addl $1, -8(%rbp)
+	movl -8(%rbp), %r10d
+	movl %r10d, calls(%rip)
+	movl -4(%rbp), %r10d
+	movl %r10d, last.1(%rip)
+	movl total(%rip), %r10d
+	movl %r10d, -8(%rbp)
+	movl -4(%rbp), %r10d
+	addl %r10d, -8(%rbp)
+	movl -8(%rbp), %r10d
+	movl %r10d, total(%rip)
+	movl calls(%rip), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size count, .-count
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl $4, %edi
+	call count@PLT
+	movl %eax, -4(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	movl total(%rip), %r10d
+	addl %r10d, -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.globl total
+	.data
+	.balign 4
+	.type total, @object
+	.size total, 4
+total:
+	.long 3
+	.bss
+	.balign 4
+	.type calls, @object
+	.size calls, 4
+calls:
+	.zero 4
+	.data
+	.balign 4
+	.type last.1, @object
+	.size last.1, 4
+last.1:
+	.long -1
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,10 @@
+// strings: string literals and their escapes as .asciz constants in .rodata,
+// leaq of a %rip-relative constant for the decayed pointer, and character
+// constants as immediates
+int puts(char *s);
+
+int main(void) {
+    char *s = "tab\there" " \"quoted\"";
+    puts(s);
+    return "xyz"[1] - 'y' + s[3];
+}
@@ -0,0 +1,57 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $32, %rsp
+	leaq .Lstring.0(%rip), %r11
+	movq %r11, -8(%rbp)
+	movq -8(%rbp), %r10
+	movq %r10, -8(%rbp)
+	movq -8(%rbp), %rdi
+	call puts@PLT
+	movl %eax, -12(%rbp)
+	leaq .Lstring.1(%rip), %r11
+	movq %r11, -24(%rbp)
+	movq -24(%rbp), %rax
+	leaq 1(%rax), %r11
+	movq %r11, -24(%rbp)
+	movq -24(%rbp), %rax
+	movb 0(%rax), %r10b
+	movb %r10b, -25(%rbp)
+	movsbl -25(%rbp), %r11d
+	movl %r11d, -12(%rbp)
+	movl -12(%rbp), %r10d
+	movl %r10d, -12(%rbp)
+	subl $121, -12(%rbp)
+	movq -8(%rbp), %rax
+	leaq 3(%rax), %r11
+	movq %r11, -8(%rbp)
+	movq -8(%rbp), %rax
+	movb 0(%rax), %r10b
+	movb %r10b, -25(%rbp)
+	movsbl -25(%rbp), %r11d
+	movl %r11d, -32(%rbp)
+	movl -12(%rbp), %r10d
+	movl %r10d, -12(%rbp)
+	movl -32(%rbp), %r10d
+	addl %r10d, -12(%rbp)
+	movl -12(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .rodata
+.Lstring.0:
+	.asciz "tab\011here \"quoted\""
+	.section .rodata
+.Lstring.1:
+	.asciz "xyz"
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,28 @@
+// structs: members loaded and stored at an offset from the structure's
+// address, and structures copied in pieces through memory operands off %rax
+// and %rdx, down to a word and a byte for an odd size
+struct pair {
+    long first;
+    int second;
+    char flag;
+};
+
+struct name {
+    char text[7];
+};
+
+int second(struct pair *p) {
+    return p->second;
+}
+
+int main(void) {
+    struct pair a;
+    a.first = 1;
+    a.second = 2;
+    a.flag = 3;
+    struct pair b = a;
+    struct name n;
+    n.text[6] = 4;
+    struct name m = n;
+    return second(&b) + b.flag + m.text[6];
+}
@@ -0,0 +1,125 @@
+	.text
+	.globl second
+	.type second, @function
+second:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movq %rdi, -8(%rbp)
+	movq -8(%rbp), %rax
+	leaq 8(%rax), %r11
+	movq %r11, -8(%rbp)
+	movq -8(%rbp), %rax
+	movl 0(%rax), %r10d
+	movl %r10d, -12(%rbp)
+	movl -12(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size second, .-second
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $80, %rsp
+	leaq -16(%rbp), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %r10
+	movq %r10, -56(%rbp)
+	movq -56(%rbp), %rax
+	movq $1, 0(%rax)
+	leaq -16(%rbp), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %rax
+	leaq 8(%rax), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %rax
+	movl $2, 0(%rax)
+	leaq -16(%rbp), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %rax
+	leaq 12(%rax), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %rax
+	movb $3, 0(%rax)
+	leaq -16(%rbp), %rax
+	leaq -32(%rbp), %rdx
+	movq 0(%rax), %r10
+	movq %r10, 0(%rdx)
+	movq 8(%rax), %r10
+	movq %r10, 8(%rdx)
+	leaq -48(%rbp), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %r10
+	movq %r10, -56(%rbp)
+	movq -56(%rbp), %r10
+	movq %r10, -56(%rbp)
+	movq -56(%rbp), %rax
+	leaq 6(%rax), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %rax
+	movb $4, 0(%rax)
+	leaq -48(%rbp), %rax
+	leaq -40(%rbp), %rdx
+	movl 0(%rax), %r10d
+	movl %r10d, 0(%rdx)
+	movw 4(%rax), %r10w
+	movw %r10w, 4(%rdx)
+	movb 6(%rax), %r10b
+	movb %r10b, 6(%rdx)
+	leaq -32(%rbp), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %rdi
+	call second@PLT
+	movl %eax, -60(%rbp)
+	leaq -32(%rbp), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %rax
+	leaq 12(%rax), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %rax
+	movb 0(%rax), %r10b
+	movb %r10b, -61(%rbp)
+	movsbl -61(%rbp), %r11d
+	movl %r11d, -68(%rbp)
+	movl -60(%rbp), %r10d
+	movl %r10d, -60(%rbp)
+	movl -68(%rbp), %r10d
+	addl %r10d, -60(%rbp)
+	leaq -40(%rbp), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %r10
+	movq %r10, -56(%rbp)
+	movq -56(%rbp), %r10
+	movq %r10, -56(%rbp)
+	movq -56(%rbp), %rax
+	leaq 6(%rax), %r11
+	movq %r11, -56(%rbp)
+	movq -56(%rbp), %rax
+	movb 0(%rax), %r10b
+	movb %r10b, -61(%rbp)
+	movsbl -61(%rbp), %r11d
+	movl %r11d, -68(%rbp)
+	movl -60(%rbp), %r10d
+	movl %r10d, -60(%rbp)
+	movl -68(%rbp), %r10d
+	addl %r10d, -60(%rbp)
+	movl -60(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
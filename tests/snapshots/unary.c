@@ -0,0 +1,2 @@
+// neg and not, each copying its operand stack slot to stack slot through %r10d
+int main(void) { return -~-5; }
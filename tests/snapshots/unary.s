@@ -0,0 +1,27 @@
+	.text
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl $5, -4(%rbp)
+	negl -4(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	notl -4(%rbp)
+	movl -4(%rbp), %r10d
+	movl %r10d, -4(%rbp)
+	negl -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.section .note.GNU-stack,"",@progbits
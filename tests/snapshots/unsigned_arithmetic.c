@@ -0,0 +1,13 @@
+// unsigned arithmetic: div after clearing %edx, setb and seta for the
+// comparisons, and a movl through %eax zero-extending into a quadword
+unsigned int count = 4000000000u;
+
+unsigned long scale(unsigned int a, unsigned int b) {
+    unsigned long wide = a / b;
+    return wide + a % 7u;
+}
+
+int main(void) {
+    unsigned long r = scale(count, 3u);
+    return (r < 10ul) + (count > 5u);
+}
@@ -0,0 +1,82 @@
+	.text
+	.globl scale
+	.type scale, @function
+scale:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $32, %rsp
+	movl %edi, -4(%rbp)
+	movl %esi, -8(%rbp)
+	movl -4(%rbp), %eax
+	movl $0, %edx
+	divl -8(%rbp)
+	movl %eax, -8(%rbp)
+	movl -8(%rbp), %eax
+	movq %rax, -16(%rbp)
+	movq -16(%rbp), %r10
+	movq %r10, -16(%rbp)
+	movl -4(%rbp), %eax
+	movl $0, %edx
+	movl $7, %r10d
+	divl %r10d
+	movl %edx, -4(%rbp)
+	movl -4(%rbp), %eax
+	movq %rax, -24(%rbp)
+	movq -16(%rbp), %r10
+	movq %r10, -16(%rbp)
+	movq -24(%rbp), %r10
+	addq %r10, -16(%rbp)
+	movq -16(%rbp), %rax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size scale, .-scale
+	.globl main
+	.type main, @function
+main:
+	.cfi_startproc
+	pushq %rbp
+	.cfi_def_cfa_offset 16
+	.cfi_offset 6, -16
+	movq %rsp, %rbp
+	.cfi_def_cfa_register 6
+	subq $16, %rsp
+	movl count(%rip), %edi
+	movl $3, %esi
+	call scale@PLT
+	movq %rax, -8(%rbp)
+	movq -8(%rbp), %r10
+	movq %r10, -8(%rbp)
+	cmpq $10, -8(%rbp)
+	setb %al
+	movzbl %al, %r11d
+	movl %r11d, -12(%rbp)
+	cmpl $5, count(%rip)
+	seta %al
+	movzbl %al, %r11d
+	movl %r11d, -16(%rbp)
+	movl -12(%rbp), %r10d
+	movl %r10d, -12(%rbp)
+	movl -16(%rbp), %r10d
+	addl %r10d, -12(%rbp)
+	movl -12(%rbp), %eax
+	movq %rbp, %rsp
+	popq %rbp
+	.cfi_def_cfa 7, 8
+	ret
+	.cfi_endproc
+	.size main, .-main
+	.globl count
+	.data
+	.balign 4
+	.type count, @object
+	.size count, 4
+count:
+	.long 4000000000
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,5 @@
+// flags: --target windows
+// four arguments in %ecx, %edx, %r8d and %r9d, the fifth on the stack above
+// 32 bytes of shadow space, which the callee skips to find it
+int last(int a, int b, int c, int d, int e) { return e - a; }
+int main(void) { return last(1, 2, 3, 4, 5); }
@@ -0,0 +1,60 @@
+	.text
+	.def last;
+	.scl 2;
+	.type 32;
+	.endef
+	.globl last
+last:
+	.seh_proc last
+	pushq %rbp
+	.seh_pushreg %rbp
+	subq $16, %rsp
+	.seh_stackalloc 16
+	movq %rsp, %rbp
+	.seh_setframe %rbp, 0
+	.seh_endprologue
+	movl %ecx, 12(%rbp)
+	movl %edx, 8(%rbp)
+	movl %r8d, 8(%rbp)
+	movl %r9d, 8(%rbp)
+	movl 64(%rbp), %r10d
+	movl %r10d, 8(%rbp)
+	movl 8(%rbp), %r10d
+	movl %r10d, 8(%rbp)
+	movl 12(%rbp), %r10d
+	subl %r10d, 8(%rbp)
+	movl 8(%rbp), %eax
+	leaq 16(%rbp), %rsp
+	popq %rbp
+	ret
+	.seh_endproc
+	.def main;
+	.scl 2;
+	.type 32;
+	.endef
+	.globl main
+main:
+	.seh_proc main
+	pushq %rbp
+	.seh_pushreg %rbp
+	subq $16, %rsp
+	.seh_stackalloc 16
+	movq %rsp, %rbp
+	.seh_setframe %rbp, 0
+	.seh_endprologue
+	subq $8, %rsp
+	movl $5, %eax
+	pushq %rax
+	movl $1, %ecx
+	movl $2, %edx
+	movl $3, %r8d
+	movl $4, %r9d
+	subq $32, %rsp
+	call last
+	addq $48, %rsp
+	movl %eax, 12(%rbp)
+	movl 12(%rbp), %eax
+	leaq 16(%rbp), %rsp
+	popq %rbp
+	ret
+	.seh_endproc
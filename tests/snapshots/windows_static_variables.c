@@ -0,0 +1,7 @@
+// flags: --target windows --syntax intel
+// a static function's symbol is class 3, not 2, and variables are addressed
+// relative to rip in Intel syntax too
+int total = 3;
+static int calls;
+static int count(void) { calls = calls + 1; return calls; }
+int main(void) { total = count() + total; return total; }
@@ -0,0 +1,63 @@
+	.intel_syntax noprefix
+	.text
+	.def count;
+	.scl 3;
+	.type 32;
+	.endef
+count:
+	.seh_proc count
+	push rbp
+	.seh_pushreg rbp
+	sub rsp, 16
+	.seh_stackalloc 16
+	mov rbp, rsp
+	.seh_setframe rbp, 0
+	.seh_endprologue
+	mov r10d, DWORD PTR [rip+calls]
+	mov DWORD PTR [rbp+12], r10d
+	add DWORD PTR [rbp+12], 1
+	mov r10d, DWORD PTR [rbp+12]
+	mov DWORD PTR [rip+calls], r10d
+	mov eax, DWORD PTR [rip+calls]
+	lea rsp, QWORD PTR [rbp+16]
+	pop rbp
+	ret
+	.seh_endproc
+	.def main;
+	.scl 2;
+	.type 32;
+	.endef
+	.globl main
+main:
+	.seh_proc main
+	push rbp
+	.seh_pushreg rbp
+	sub rsp, 16
+	.seh_stackalloc 16
+	mov rbp, rsp
+	.seh_setframe rbp, 0
+	.seh_endprologue
+	sub rsp, 32
+	call count
+	add rsp, 32
+	mov DWORD PTR [rbp+12], eax
+	mov r10d, DWORD PTR [rbp+12]
+	mov DWORD PTR [rbp+12], r10d
+	mov r10d, DWORD PTR [rip+total]
+	add DWORD PTR [rbp+12], r10d
+	mov r10d, DWORD PTR [rbp+12]
+	mov DWORD PTR [rip+total], r10d
+	mov eax, DWORD PTR [rip+total]
+	lea rsp, QWORD PTR [rbp+16]
+	pop rbp
+	ret
+	.seh_endproc
+	.globl total
+	.data
+	.balign 4
+total:
+	.long 3
+	.bss
+	.balign 4
+calls:
+	.zero 4
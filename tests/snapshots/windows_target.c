@@ -0,0 +1,4 @@
+// flags: --target windows
+// a COFF .def block before each function, and no CFI directives
+int helper(void) { return 7 ^ 2; }
+int main(void) { return 10 / 3; }
@@ -0,0 +1,46 @@
+	.text
+	.def helper;
+	.scl 2;
+	.type 32;
+	.endef
+	.globl helper
+helper:
+	.seh_proc helper
+	pushq %rbp
+	.seh_pushreg %rbp
+	subq $16, %rsp
+	.seh_stackalloc 16
+	movq %rsp, %rbp
+	.seh_setframe %rbp, 0
+	.seh_endprologue
+	movl $7, 12(%rbp)
+	xorl $2, 12(%rbp)
+	movl 12(%rbp), %eax
+	leaq 16(%rbp), %rsp
+	popq %rbp
+	ret
+	.seh_endproc
+	.def main;
+	.scl 2;
+	.type 32;
+	.endef
+	.globl main
+main:
+	.seh_proc main
+	pushq %rbp
+	.seh_pushreg %rbp
+	subq $16, %rsp
+	.seh_stackalloc 16
+	movq %rsp, %rbp
+	.seh_setframe %rbp, 0
+	.seh_endprologue
+	movl $10, %eax
+	cdq
+	movl $3, %r10d
+	idivl %r10d
+	movl %eax, 12(%rbp)
+	movl 12(%rbp), %eax
+	leaq 16(%rbp), %rsp
+	popq %rbp
+	ret
+	.seh_endproc